@@ -0,0 +1,543 @@
+// Local user accounts: who can log in and who can sudo.
+//
+// Users and groups come from the system's account database (getpwent and
+// getgrouplist through uzers), so NSS sources are included. Everything
+// else is read from files and degrades per field when they can't be:
+// `locked` is None without a readable /etc/shadow, and `last_login` is
+// None without lastlog2's database or the older /var/log/lastlog. wtmp
+// isn't read. `can_sudo` is set for root, for members of the sudo, wheel
+// and admin groups that distributions grant by default, and for users or
+// groups named by a rule in the sudoers files that can be read. The
+// sudoers parse is deliberately shallow: it reads rule subjects, User_Alias
+// definitions and include directives, nothing about what a rule allows, so
+// any rule counts. Nothing runs sudo.
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::app::State;
+use crate::error::CommandResult;
+use crate::settings::SettingsStore;
+
+const SUDOERS: &str = "/etc/sudoers";
+const SUDOERS_DIR: &str = "/etc/sudoers.d";
+const DEFAULT_SUDO_GROUPS: &[&str] = &["sudo", "wheel", "admin"];
+const NO_LOGIN_SHELLS: &[&str] = &["nologin", "false", "sync", "halt", "shutdown"];
+const LASTLOG2: &str = "/var/lib/lastlog/lastlog2.db";
+const LASTLOG: &str = "/var/log/lastlog";
+// struct lastlog: a 32-bit time, a 32-byte line and a 256-byte host
+const LASTLOG_RECORD: usize = 4 + 32 + 256;
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+#[derive(Serialize, Clone)]
+pub struct UserAccount {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    // Group name, or the gid when it has none
+    pub primary_group: String,
+    pub groups: Vec<String>,
+    pub home: String,
+    pub shell: String,
+    pub interactive: bool,
+    // Below the system uid threshold in settings
+    pub system: bool,
+    // None when /etc/shadow can't be read
+    pub locked: Option<bool>,
+    pub last_login: Option<String>,
+    pub last_login_from: Option<String>,
+    pub can_sudo: bool,
+    // Why, e.g. "group wheel" or "sudoers.d/90-users: user alice"
+    pub sudo_via: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct AccountInventory {
+    pub users: Vec<UserAccount>,
+    pub shadow_readable: bool,
+    // "lastlog2", "lastlog" or None
+    pub last_login_source: Option<String>,
+    pub sudoers_read: Vec<String>,
+    pub sudoers_unreadable: Vec<String>,
+}
+
+// Subjects of sudoers rules
+#[derive(Default, Debug, PartialEq)]
+pub struct SudoGrants {
+    // Granted user -> the files that name them
+    pub users: BTreeMap<String, BTreeSet<String>>,
+    pub groups: BTreeMap<String, BTreeSet<String>>,
+    // Files with a rule for ALL users
+    pub everyone: BTreeSet<String>,
+    pub aliases: BTreeMap<String, Vec<String>>,
+    pub includes: Vec<String>,
+    pub include_dirs: Vec<String>,
+}
+
+// Backslash-continued lines joined, comments dropped. `#` followed by a
+// digit is a uid and `#include` a directive, not a comment.
+fn logical_lines(text: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for raw in text.lines() {
+        match raw.strip_suffix('\\') {
+            Some(continued) => {
+                current.push_str(continued);
+                current.push(' ');
+            }
+            None => {
+                current.push_str(raw);
+                lines.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+        .into_iter()
+        .map(|line| {
+            let trimmed = line.trim();
+            if trimmed.starts_with("#include") {
+                return trimmed.to_string();
+            }
+            let bytes = trimmed.as_bytes();
+            let cut = (0..bytes.len()).find(|&i| {
+                bytes[i] == b'#'
+                    && (i == 0 || bytes[i - 1].is_ascii_whitespace() || bytes[i - 1] == b',')
+                    && !bytes.get(i + 1).is_some_and(u8::is_ascii_digit)
+            });
+            trimmed[..cut.unwrap_or(bytes.len())].trim().to_string()
+        })
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+fn add_subject(grants: &mut SudoGrants, subject: &str, source: &str, depth: usize) {
+    let subject = subject.trim();
+    if subject.is_empty() || subject.starts_with('!') || subject.starts_with('+') {
+        return;
+    }
+    if subject == "ALL" {
+        grants.everyone.insert(source.to_string());
+    } else if let Some(group) = subject.strip_prefix('%') {
+        let group = group.strip_prefix(':').unwrap_or(group);
+        grants.groups.entry(group.to_string()).or_default().insert(source.to_string());
+    } else if let Some(members) = grants.aliases.get(subject).cloned() {
+        if depth < MAX_INCLUDE_DEPTH {
+            for member in members {
+                add_subject(grants, &member, source, depth + 1);
+            }
+        }
+    } else {
+        grants.users.entry(subject.to_string()).or_default().insert(source.to_string());
+    }
+}
+
+// Adds what one sudoers file grants; `source` names it in the reasons
+pub fn parse_sudoers(text: &str, source: &str, grants: &mut SudoGrants) {
+    for line in logical_lines(text) {
+        let mut words = line.splitn(2, char::is_whitespace);
+        let first = words.next().unwrap_or_default();
+        let rest = words.next().unwrap_or_default().trim();
+        match first {
+            "#include" | "@include" => grants.includes.push(rest.to_string()),
+            "#includedir" | "@includedir" => grants.include_dirs.push(rest.to_string()),
+            "User_Alias" => {
+                for definition in rest.split(':') {
+                    if let Some((name, members)) = definition.split_once('=') {
+                        let members = members.split(',').map(|m| m.trim().to_string()).collect();
+                        grants.aliases.insert(name.trim().to_string(), members);
+                    }
+                }
+            }
+            _ if first.starts_with("Defaults") || first.ends_with("_Alias") => {}
+            _ => {
+                // "user, %group  host = ..." - the subjects are what comes
+                // before the host list
+                let Some((left, _)) = line.split_once('=') else { continue };
+                let compact = left.replace(", ", ",").replace(" ,", ",");
+                let mut fields = compact.split_whitespace();
+                let (Some(subjects), Some(_hosts)) = (fields.next(), fields.next()) else {
+                    continue;
+                };
+                for subject in subjects.split(',') {
+                    add_subject(grants, subject, source, 0);
+                }
+            }
+        }
+    }
+}
+
+// Files sudo reads from an includedir: no '.' in the name, not ending in '~'
+pub fn included_file(name: &str) -> bool {
+    !name.contains('.') && !name.ends_with('~')
+}
+
+fn read_sudoers(
+    path: &Path,
+    depth: usize,
+    grants: &mut SudoGrants,
+    read: &mut Vec<String>,
+    unreadable: &mut Vec<String>,
+) {
+    let shown = path.display().to_string();
+    if depth > MAX_INCLUDE_DEPTH || read.contains(&shown) {
+        return;
+    }
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(_) => {
+            unreadable.push(shown);
+            return;
+        }
+    };
+    read.push(shown.clone());
+    let source = shown.strip_prefix("/etc/").unwrap_or(&shown).to_string();
+    let (includes, dirs) = (grants.includes.len(), grants.include_dirs.len());
+    parse_sudoers(&text, &source, grants);
+    let base = path.parent().unwrap_or(Path::new("/etc"));
+    let new_includes: Vec<PathBuf> = grants.includes[includes..].iter().map(|p| base.join(p)).collect();
+    let new_dirs: Vec<PathBuf> = grants.include_dirs[dirs..].iter().map(|p| base.join(p)).collect();
+    for include in new_includes {
+        read_sudoers(&include, depth + 1, grants, read, unreadable);
+    }
+    for dir in new_dirs {
+        read_sudoers_dir(&dir, depth + 1, grants, read, unreadable);
+    }
+}
+
+fn read_sudoers_dir(
+    dir: &Path,
+    depth: usize,
+    grants: &mut SudoGrants,
+    read: &mut Vec<String>,
+    unreadable: &mut Vec<String>,
+) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        let shown = dir.display().to_string();
+        if !unreadable.contains(&shown) {
+            unreadable.push(shown);
+        }
+        return;
+    };
+    let mut names: Vec<String> = entries.flatten().map(|e| e.file_name().to_string_lossy().into_owned()).collect();
+    names.sort();
+    for name in names.into_iter().filter(|n| included_file(n)) {
+        read_sudoers(&dir.join(name), depth, grants, read, unreadable);
+    }
+}
+
+// User -> locked, from /etc/shadow. A password starting with '!' or '*'
+// can't be used, and an expiry day (days since 1970) before `today` closes
+// the account.
+pub fn parse_shadow(text: &str, today: i64) -> HashMap<String, bool> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            let (name, password) = (fields.first()?, fields.get(1)?);
+            let expired = fields
+                .get(7)
+                .and_then(|days| days.parse::<i64>().ok())
+                .is_some_and(|day| day < today);
+            Some((name.to_string(), password.starts_with('!') || password.starts_with('*') || expired))
+        })
+        .collect()
+}
+
+// `shells` is /etc/shells when it could be read
+pub fn is_interactive(shell: &str, shells: Option<&[String]>) -> bool {
+    let name = Path::new(shell).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    if shell.is_empty() || NO_LOGIN_SHELLS.contains(&name.as_str()) {
+        return false;
+    }
+    shells.is_none_or(|shells| shells.iter().any(|s| s == shell))
+}
+
+// Time and remote host of one /var/log/lastlog record; None if never
+pub fn parse_lastlog_record(record: &[u8]) -> Option<(i64, Option<String>)> {
+    if record.len() < LASTLOG_RECORD {
+        return None;
+    }
+    let time = i32::from_ne_bytes(record[..4].try_into().ok()?) as i64;
+    if time == 0 {
+        return None;
+    }
+    let host = &record[36..LASTLOG_RECORD];
+    let host = String::from_utf8_lossy(&host[..host.iter().position(|b| *b == 0).unwrap_or(host.len())]).into_owned();
+    Some((time, (!host.is_empty()).then_some(host)))
+}
+
+fn lastlog2() -> Option<HashMap<String, (i64, Option<String>)>> {
+    let conn = rusqlite::Connection::open_with_flags(LASTLOG2, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY).ok()?;
+    let mut stmt = conn.prepare("SELECT Name, Time, RemoteHost FROM Lastlog2").ok()?;
+    let rows = stmt
+        .query_map([], |r| {
+            let host: Option<String> = r.get(2)?;
+            Ok((r.get::<_, String>(0)?, (r.get::<_, i64>(1)?, host.filter(|h| !h.is_empty()))))
+        })
+        .ok()?;
+    Some(rows.flatten().filter(|(_, (time, _))| *time > 0).collect())
+}
+
+fn lastlog_for(lastlog: &[u8], uid: u32) -> Option<(i64, Option<String>)> {
+    let start = (uid as usize).checked_mul(LASTLOG_RECORD)?;
+    parse_lastlog_record(lastlog.get(start..start.checked_add(LASTLOG_RECORD)?)?)
+}
+
+fn rfc3339(time: i64) -> Option<String> {
+    chrono::DateTime::from_timestamp(time, 0).map(|t| t.to_rfc3339())
+}
+
+// The reasons `name` in `groups` can use sudo
+pub fn sudo_reasons(name: &str, uid: u32, groups: &[String], grants: &SudoGrants) -> Vec<String> {
+    let mut reasons = Vec::new();
+    if uid == 0 {
+        reasons.push("uid 0".to_string());
+    }
+    for group in groups {
+        if DEFAULT_SUDO_GROUPS.contains(&group.as_str()) {
+            reasons.push(format!("group {}", group));
+        }
+        for source in grants.groups.get(group).into_iter().flatten() {
+            reasons.push(format!("{}: group %{}", source, group));
+        }
+    }
+    for key in [name.to_string(), format!("#{}", uid)] {
+        for source in grants.users.get(&key).into_iter().flatten() {
+            reasons.push(format!("{}: user {}", source, key));
+        }
+    }
+    for source in &grants.everyone {
+        reasons.push(format!("{}: ALL", source));
+    }
+    reasons.dedup();
+    reasons
+}
+
+// Name, uid, gid, primary group, home, groups, shell
+type SystemAccount = (String, u32, u32, String, String, Vec<String>, String);
+
+#[cfg(unix)]
+fn system_accounts() -> Vec<SystemAccount> {
+    use std::sync::Mutex;
+    use uzers::os::unix::UserExt;
+    // getpwent walks process-wide state
+    static ENUMERATING: Mutex<()> = Mutex::new(());
+    let _guard = ENUMERATING.lock().unwrap();
+    // SAFETY: serialized by ENUMERATING; nothing else here walks the database
+    let users: Vec<uzers::User> = unsafe { uzers::all_users() }.collect();
+    users
+        .into_iter()
+        .map(|user| {
+            let name = user.name().to_string_lossy().into_owned();
+            let gid = user.primary_group_id();
+            let primary = uzers::get_group_by_gid(gid)
+                .map(|g| g.name().to_string_lossy().into_owned())
+                .unwrap_or_else(|| gid.to_string());
+            let mut groups: Vec<String> = uzers::get_user_groups(user.name(), gid)
+                .unwrap_or_default()
+                .iter()
+                .map(|g| g.name().to_string_lossy().into_owned())
+                .collect();
+            groups.sort();
+            groups.dedup();
+            (
+                name,
+                user.uid(),
+                gid,
+                primary,
+                user.home_dir().display().to_string(),
+                groups,
+                user.shell().display().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(not(unix))]
+fn system_accounts() -> Vec<SystemAccount> {
+    Vec::new()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_user_accounts(settings: State<'_, SettingsStore>) -> CommandResult<AccountInventory> {
+    let threshold = settings.get().system_uid_threshold;
+    let today = chrono::Utc::now().timestamp().div_euclid(86_400);
+    let shadow = std::fs::read_to_string("/etc/shadow").ok().map(|text| parse_shadow(&text, today));
+    let shells: Option<Vec<String>> = std::fs::read_to_string("/etc/shells").ok().map(|text| {
+        text.lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(str::to_string)
+            .collect()
+    });
+
+    let mut grants = SudoGrants::default();
+    let (mut read, mut unreadable) = (Vec::new(), Vec::new());
+    read_sudoers(Path::new(SUDOERS), 0, &mut grants, &mut read, &mut unreadable);
+    // Most distributions include it from /etc/sudoers; try it on its own
+    // when that couldn't be read
+    if !read.iter().any(|p| p.starts_with(SUDOERS_DIR)) {
+        read_sudoers_dir(Path::new(SUDOERS_DIR), 1, &mut grants, &mut read, &mut unreadable);
+    }
+
+    let lastlog2 = lastlog2();
+    let lastlog = if lastlog2.is_none() { std::fs::read(LASTLOG).ok() } else { None };
+    let last_login_source = match (&lastlog2, &lastlog) {
+        (Some(_), _) => Some("lastlog2".to_string()),
+        (None, Some(_)) => Some("lastlog".to_string()),
+        _ => None,
+    };
+
+    let mut users: Vec<UserAccount> = system_accounts()
+        .into_iter()
+        .map(|(name, uid, gid, primary_group, home, mut groups, shell)| {
+            if !groups.contains(&primary_group) {
+                groups.push(primary_group.clone());
+                groups.sort();
+            }
+            let last = match (&lastlog2, &lastlog) {
+                (Some(entries), _) => entries.get(&name).cloned(),
+                (None, Some(bytes)) => lastlog_for(bytes, uid),
+                _ => None,
+            };
+            let sudo_via = sudo_reasons(&name, uid, &groups, &grants);
+            UserAccount {
+                locked: shadow.as_ref().map(|s| s.get(&name).copied().unwrap_or(false)),
+                interactive: is_interactive(&shell, shells.as_deref()),
+                system: uid < threshold && uid != 0,
+                last_login: last.as_ref().and_then(|(time, _)| rfc3339(*time)),
+                last_login_from: last.and_then(|(_, host)| host),
+                can_sudo: !sudo_via.is_empty(),
+                sudo_via,
+                name,
+                uid,
+                gid,
+                primary_group,
+                groups,
+                home,
+                shell,
+            }
+        })
+        .collect();
+    users.sort_by_key(|u| u.uid);
+
+    Ok(AccountInventory {
+        users,
+        shadow_readable: shadow.is_some(),
+        last_login_source,
+        sudoers_read: read,
+        sudoers_unreadable: unreadable,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUDOERS_FIXTURE: &str = r#"# /etc/sudoers: any rule counts, whatever it allows
+Defaults	env_reset
+Defaults:alice	!requiretty
+Host_Alias	SERVERS = web1, web2
+
+# Members of the admin group may gain root privileges
+root	ALL=(ALL:ALL) ALL
+%admin ALL=(ALL) ALL
+%:devops	SERVERS = (root) NOPASSWD: /usr/bin/systemctl
+#1001	ALL = (ALL) ALL
+bob, carol	ALL=(ALL) ALL # trailing comment
+#dave	ALL=(ALL) ALL
+User_Alias	OPERATORS = erin, \
+		frank, %ops
+OPERATORS	web1 = /usr/sbin/reboot, \
+		/usr/sbin/shutdown
+grace \
+	ALL = (ALL) ALL
+
+#includedir /etc/sudoers.d
+@include extra
+"#;
+
+    fn parsed(text: &str) -> SudoGrants {
+        let mut grants = SudoGrants::default();
+        parse_sudoers(text, "sudoers", &mut grants);
+        grants
+    }
+
+    fn names(map: &BTreeMap<String, BTreeSet<String>>) -> Vec<&str> {
+        map.keys().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn rule_subjects_users_groups_and_uids() {
+        let grants = parsed(SUDOERS_FIXTURE);
+        assert_eq!(names(&grants.users), ["#1001", "bob", "carol", "erin", "frank", "grace", "root"]);
+        assert_eq!(names(&grants.groups), ["admin", "devops", "ops"]);
+        assert!(grants.everyone.is_empty());
+        assert_eq!(grants.users["bob"], BTreeSet::from(["sudoers".to_string()]));
+    }
+
+    #[test]
+    fn comments_are_dropped_but_uids_and_includes_are_not() {
+        let grants = parsed(SUDOERS_FIXTURE);
+        // "#dave" is a commented-out rule, "#1001" a uid
+        assert!(!grants.users.contains_key("#dave") && !grants.users.contains_key("dave"));
+        assert!(grants.users.contains_key("#1001"));
+        assert_eq!(grants.include_dirs, ["/etc/sudoers.d"]);
+        assert_eq!(grants.includes, ["extra"]);
+    }
+
+    #[test]
+    fn continued_lines_join_before_parsing() {
+        let grants = parsed(SUDOERS_FIXTURE);
+        assert_eq!(grants.aliases["OPERATORS"], ["erin", "frank", "%ops"]);
+        assert!(grants.users.contains_key("grace"));
+        // A continuation doesn't turn the command list into subjects
+        assert!(!grants.users.keys().any(|u| u.contains("/usr/sbin")));
+    }
+
+    #[test]
+    fn both_include_spellings_are_read() {
+        let grants = parsed("#include /etc/sudoers.local\n@includedir /etc/sudoers.more\n@include other\n");
+        assert_eq!(grants.includes, ["/etc/sudoers.local", "other"]);
+        assert_eq!(grants.include_dirs, ["/etc/sudoers.more"]);
+    }
+
+    #[test]
+    fn negated_and_netgroup_subjects_grant_nothing() {
+        let grants = parsed("!mallory ALL=(ALL) ALL\n+netgroup ALL=(ALL) ALL\nALL ALL=(ALL) ALL\n");
+        assert!(grants.users.is_empty());
+        assert_eq!(grants.everyone, BTreeSet::from(["sudoers".to_string()]));
+    }
+
+    #[test]
+    fn includes_are_followed_relative_to_the_including_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("sudoers");
+        std::fs::write(&root, "root ALL=(ALL) ALL\n@include local\n#includedir sudoers.d\n").unwrap();
+        std::fs::write(dir.path().join("local"), "alice ALL=(ALL) ALL\n").unwrap();
+        std::fs::create_dir(dir.path().join("sudoers.d")).unwrap();
+        std::fs::write(dir.path().join("sudoers.d/90-users"), "%ops ALL=(ALL) ALL\n@include missing\n").unwrap();
+        std::fs::write(dir.path().join("sudoers.d/README.txt"), "skipped ALL=(ALL) ALL\n").unwrap();
+        std::fs::write(dir.path().join("sudoers.d/old~"), "skipped ALL=(ALL) ALL\n").unwrap();
+
+        let (mut grants, mut read, mut unreadable) = (SudoGrants::default(), Vec::new(), Vec::new());
+        read_sudoers(&root, 0, &mut grants, &mut read, &mut unreadable);
+        assert_eq!(names(&grants.users), ["alice", "root"]);
+        assert_eq!(names(&grants.groups), ["ops"]);
+        assert_eq!(read.len(), 3);
+        assert_eq!(unreadable, [dir.path().join("sudoers.d/missing").display().to_string()]);
+    }
+
+    #[test]
+    fn an_include_loop_stops() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("sudoers");
+        std::fs::write(&root, "root ALL=(ALL) ALL\n@include sudoers\n").unwrap();
+        let (mut grants, mut read, mut unreadable) = (SudoGrants::default(), Vec::new(), Vec::new());
+        read_sudoers(&root, 0, &mut grants, &mut read, &mut unreadable);
+        assert_eq!(read.len(), 1);
+        assert!(unreadable.is_empty());
+    }
+}
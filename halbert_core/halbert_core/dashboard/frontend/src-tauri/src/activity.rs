@@ -0,0 +1,106 @@
+// Whether the dashboard is on screen.
+//
+// The frontend reports page visibility through `set_ui_active`; the window
+// gaining focus also counts as active. Losing focus doesn't count as hidden,
+// since a dashboard on a second monitor is still being watched. While
+// inactive the metrics and container samplers drop to
+// `idle_metrics_interval_secs`. Becoming active wakes them at once so the
+// first render gets a fresh sample.
+use serde::Serialize;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use crate::app::State;
+
+struct Visibility {
+    active: bool,
+    // When the current state began, RFC 3339
+    since: String,
+    // Bumped on every switch to active; sleepers wake when it changes
+    activations: u64,
+}
+
+pub struct UiActivity {
+    state: Mutex<Visibility>,
+    woken: Condvar,
+}
+
+impl Default for UiActivity {
+    fn default() -> Self {
+        UiActivity {
+            state: Mutex::new(Visibility {
+                active: true,
+                since: chrono::Utc::now().to_rfc3339(),
+                activations: 0,
+            }),
+            woken: Condvar::new(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ActivityStatus {
+    pub active: bool,
+    pub since: String,
+    // What the samplers are using right now
+    pub sampling_interval_secs: u64,
+}
+
+impl UiActivity {
+    pub fn set_active(&self, active: bool) {
+        let mut state = self.state.lock().unwrap();
+        if state.active == active {
+            return;
+        }
+        state.active = active;
+        state.since = chrono::Utc::now().to_rfc3339();
+        if active {
+            state.activations += 1;
+            self.woken.notify_all();
+        }
+        println!(
+            "[Halbert] Dashboard {}; sampling {}",
+            if active { "visible" } else { "hidden" },
+            if active { "at full rate" } else { "slowed" }
+        );
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state.lock().unwrap().active
+    }
+
+    // The sampling interval for the current state; idle never samples
+    // faster than the normal interval
+    pub fn interval_secs(&self, normal_secs: u64, idle_secs: u64) -> u64 {
+        if self.is_active() {
+            normal_secs
+        } else {
+            idle_secs.max(normal_secs)
+        }
+    }
+
+    // Sleep for `duration`, returning early if the dashboard becomes active
+    pub fn sleep(&self, duration: Duration) {
+        let state = self.state.lock().unwrap();
+        let start = state.activations;
+        let _ = self
+            .woken
+            .wait_timeout_while(state, duration, |state| state.activations == start)
+            .unwrap();
+    }
+
+    pub fn status(&self, normal_secs: u64, idle_secs: u64) -> ActivityStatus {
+        let state = self.state.lock().unwrap();
+        ActivityStatus {
+            active: state.active,
+            since: state.since.clone(),
+            sampling_interval_secs: if state.active { normal_secs } else { idle_secs.max(normal_secs) },
+        }
+    }
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn set_ui_active(activity: State<'_, UiActivity>, active: bool) {
+    activity.set_active(active);
+}
+
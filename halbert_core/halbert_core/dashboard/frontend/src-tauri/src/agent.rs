@@ -0,0 +1,268 @@
+// halbert-agent: the core without a window, for a server to be watched from
+// a desktop.
+//
+// It keeps the same stores and runs the same background tasks as the
+// desktop app (see manage_core and start_core) on the headless runtime,
+// and serves them over the remote access API. Its own config file, TOML at
+// /etc/halbert/agent.toml unless --config says otherwise, says where the
+// data lives and where the API listens:
+//
+//     data_dir = "/var/lib/halbert"
+//     [api]
+//     listen = "0.0.0.0:8787"
+//     changes_listen = "127.0.0.1:8788"
+//     requests_per_minute = 120
+//
+// [api] takes the place of remote_access in settings.toml, which the agent
+// keeps in config_dir (data_dir unless set) like the desktop does in its
+// app config dir. `halbert-agent token reads|changes` prints a new token
+// for one of the listeners; a running agent picks it up when restarted.
+// Under systemd (see packaging/systemd/system/halbert-agent.service) it
+// reports READY=1 once started and STOPPING=1 on SIGTERM, and pings the
+// watchdog (see liveness). SIGTERM and Ctrl-C run the shutdown steps.
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::app::{async_runtime, AppHandle, Manager};
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::remote_access::{self, RemoteAccessSettings};
+use crate::settings::{Settings, SettingsStore};
+use crate::shutdown;
+use crate::startup;
+
+const DEFAULT_CONFIG: &str = "/etc/halbert/agent.toml";
+const USAGE: &str = "usage: halbert-agent [--config PATH] [token reads|changes]";
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AgentConfig {
+    // The database, secrets and job artifacts
+    pub data_dir: PathBuf,
+    // Where settings.toml is; data_dir when unset
+    pub config_dir: Option<PathBuf>,
+    pub api: RemoteAccessSettings,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        AgentConfig {
+            data_dir: PathBuf::from("/var/lib/halbert"),
+            config_dir: None,
+            api: RemoteAccessSettings::default(),
+        }
+    }
+}
+
+impl AgentConfig {
+    // `path` is only for the message
+    pub fn parse(text: &str, path: &Path) -> CommandResult<Self> {
+        let config: AgentConfig = toml::from_str(text)
+            .map_err(|e| CommandError::InvalidInput(format!("{}: {}", path.display(), e)))?;
+        remote_access::validate(&config.api)?;
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> CommandResult<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| CommandError::NotConfigured(format!("can't read {}: {}", path.display(), e)))?;
+        Self::parse(&text, path)
+    }
+
+    fn config_dir(&self) -> &Path {
+        self.config_dir.as_deref().unwrap_or(&self.data_dir)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Token {
+    Reads,
+    Changes,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run,
+    Token(Token),
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Invocation {
+    pub config: PathBuf,
+    pub command: Command,
+}
+
+pub fn parse_args(args: &[String]) -> Result<Invocation, String> {
+    let mut config = PathBuf::from(DEFAULT_CONFIG);
+    let mut command = Command::Run;
+    let mut args = args.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        command = match (arg, &command) {
+            ("-h" | "--help", _) => Command::Help,
+            ("--config", _) => {
+                config = PathBuf::from(args.next().ok_or("--config needs a path")?);
+                continue;
+            }
+            ("token", Command::Run) => match args.next() {
+                Some("reads") => Command::Token(Token::Reads),
+                Some("changes") => Command::Token(Token::Changes),
+                _ => return Err("token needs reads or changes".to_string()),
+            },
+            (other, _) => return Err(format!("unexpected argument '{}'", other)),
+        };
+    }
+    Ok(Invocation { config, command })
+}
+
+// The stores, with [api] in place of the settings' remote_access. Nothing
+// is written when it's already what settings.toml has.
+fn core(config: &AgentConfig) -> CommandResult<AppHandle> {
+    let (config_dir, data_dir) = (config.config_dir(), config.data_dir.as_path());
+    std::fs::create_dir_all(config_dir)?;
+    std::fs::create_dir_all(data_dir)?;
+    let app = AppHandle::new(config_dir.to_path_buf(), data_dir.to_path_buf());
+    crate::manage_core(&app, Instant::now(), config_dir, data_dir);
+    let store = app.state::<SettingsStore>();
+    if store.get().remote_access != config.api {
+        store.update(|current| {
+            Ok(Settings {
+                remote_access: config.api.clone(),
+                ..current.clone()
+            })
+        })?;
+    }
+    Ok(app)
+}
+
+fn token(config: &AgentConfig, token: Token) -> CommandResult<String> {
+    let app = core(config)?;
+    let db = app.state::<Database>();
+    db.open()?;
+    match token {
+        Token::Reads => remote_access::generate_remote_access_token(app.state(), db),
+        Token::Changes => remote_access::generate_remote_access_changes_token(app.state(), db),
+    }
+}
+
+// SIGTERM is shutdown::watch_signals'; Ctrl-C is for a run in a terminal
+fn watch_interrupt(app: AppHandle) {
+    async_runtime::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            println!("[Halbert] Interrupted, exiting");
+            app.exit(0);
+        }
+    });
+}
+
+fn run(config: &AgentConfig) -> CommandResult<i32> {
+    let app = core(config)?;
+    let api = &config.api;
+    println!(
+        "[Halbert] halbert-agent {} with data in {}, API on {}, changes on {}",
+        app.package_info().version,
+        config.data_dir.display(),
+        api.listen.as_deref().unwrap_or("(off)"),
+        api.changes_listen.as_deref().unwrap_or("(off)")
+    );
+    startup::run(app.clone(), |app| {
+        crate::start_core(app);
+        watch_interrupt(app.clone());
+    });
+    let code = app.wait_for_exit();
+    shutdown::run(&app);
+    Ok(code)
+}
+
+pub fn main() -> i32 {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let invocation = match parse_args(&args) {
+        Ok(invocation) => invocation,
+        Err(e) => {
+            eprintln!("halbert-agent: {}\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    if invocation.command == Command::Help {
+        println!("{}", USAGE);
+        return 0;
+    }
+    let outcome = AgentConfig::load(&invocation.config).and_then(|config| match invocation.command {
+        Command::Token(which) => token(&config, which).map(|token| {
+            println!("{}", token);
+            eprintln!("Shown once. Restart halbert-agent for it to take effect.");
+            0
+        }),
+        _ => run(&config),
+    });
+    outcome.unwrap_or_else(|e| {
+        eprintln!("halbert-agent: {}", e);
+        1
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn parse(text: &str) -> CommandResult<AgentConfig> {
+        AgentConfig::parse(text, Path::new("agent.toml"))
+    }
+
+    #[test]
+    fn arguments_pick_the_config_and_the_command() {
+        let run = parse_args(&[]).unwrap();
+        assert_eq!(run, Invocation { config: PathBuf::from(DEFAULT_CONFIG), command: Command::Run });
+        let token = parse_args(&args("--config /srv/agent.toml token changes")).unwrap();
+        assert_eq!(token.config, PathBuf::from("/srv/agent.toml"));
+        assert_eq!(token.command, Command::Token(Token::Changes));
+        assert_eq!(parse_args(&args("token reads --help")).unwrap().command, Command::Help);
+        assert!(parse_args(&args("token")).is_err());
+        assert!(parse_args(&args("--config")).is_err());
+        assert!(parse_args(&args("serve")).is_err());
+    }
+
+    #[test]
+    fn the_config_sets_the_dirs_and_the_api() {
+        let config = parse(
+            "data_dir = \"/srv/halbert\"\n[api]\nlisten = \"0.0.0.0:8787\"\nchanges_listen = \"127.0.0.1:8788\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.config_dir(), Path::new("/srv/halbert"));
+        assert_eq!(config.api.listen.as_deref(), Some("0.0.0.0:8787"));
+        assert_eq!(config.api.requests_per_minute, 120);
+        assert_eq!(parse("").unwrap(), AgentConfig::default());
+    }
+
+    #[test]
+    fn a_config_the_listeners_would_refuse_is_rejected() {
+        assert!(parse("[api]\nchanges_listen = \"0.0.0.0:8788\"\n").is_err(), "changes are loopback only");
+        assert!(parse("[api]\nlisten = \"everywhere\"\n").is_err());
+        assert!(parse("datadir = \"/srv\"\n").is_err(), "typos aren't ignored");
+        let missing = AgentConfig::load(Path::new("/nonexistent/agent.toml")).unwrap_err();
+        assert!(matches!(missing, CommandError::NotConfigured(_)));
+    }
+
+    #[test]
+    fn the_api_replaces_the_settings_remote_access() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AgentConfig {
+            data_dir: dir.path().join("data"),
+            config_dir: Some(dir.path().join("config")),
+            api: RemoteAccessSettings {
+                listen: Some("127.0.0.1:0".to_string()),
+                ..RemoteAccessSettings::default()
+            },
+        };
+        let app = core(&config).unwrap();
+        assert_eq!(app.state::<SettingsStore>().get().remote_access, config.api);
+        let saved = std::fs::read_to_string(dir.path().join("config/settings.toml")).unwrap();
+        assert!(saved.contains("listen = \"127.0.0.1:0\""), "{}", saved);
+        assert_eq!(app.path().app_data_dir().unwrap(), dir.path().join("data"));
+    }
+}
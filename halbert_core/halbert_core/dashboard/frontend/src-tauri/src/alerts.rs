@@ -0,0 +1,386 @@
+// Threshold alerts over named signals.
+//
+// Collectors publish `Signal`s (a name, an optional subject such as a mount
+// point, and a numeric value); rules compare one signal against a threshold.
+// Boolean conditions are published as 0.0 / 1.0. A rule with `for_secs`
+// only triggers once its condition has held that long, counted in awake
+// time (see suspend::awake) so a night asleep doesn't make "CPU high for
+// 120s" out of two samples. AlertLog remembers what was triggering at the
+// last evaluation, so the event feed can say when an alert started and when
+// it cleared, and since when each held condition has held.
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::app::{AppHandle, Emitter, Manager, State};
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::settings::{Settings, SettingsStore};
+use crate::timestamps::{self, Clock, Localize, TimeDisplay};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparison {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Eq,
+}
+
+impl Comparison {
+    fn matches(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::Gt => value > threshold,
+            Comparison::Ge => value >= threshold,
+            Comparison::Lt => value < threshold,
+            Comparison::Le => value <= threshold,
+            Comparison::Eq => (value - threshold).abs() < f64::EPSILON,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub id: String,
+    pub name: String,
+    // e.g. "cpu_percent", "disk.usage_percent", "time.drift_warning"
+    pub signal: String,
+    // Restrict the rule to one subject; None matches every subject
+    #[serde(default)]
+    pub subject: Option<String>,
+    pub comparison: Comparison,
+    pub threshold: f64,
+    #[serde(default = "default_severity")]
+    pub severity: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // How long the condition must hold before the rule triggers; 0 at once
+    #[serde(default)]
+    pub for_secs: u64,
+}
+
+fn default_severity() -> String {
+    "warning".to_string()
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Signal {
+    pub name: String,
+    pub subject: Option<String>,
+    pub value: f64,
+}
+
+impl Signal {
+    pub fn new(name: &str, subject: Option<&str>, value: f64) -> Self {
+        Signal {
+            name: name.to_string(),
+            subject: subject.map(|s| s.to_string()),
+            value,
+        }
+    }
+
+    pub fn flag(name: &str, subject: Option<&str>, set: bool) -> Self {
+        Signal::new(name, subject, if set { 1.0 } else { 0.0 })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Alert {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub severity: String,
+    pub signal: String,
+    pub subject: Option<String>,
+    pub value: f64,
+    pub threshold: f64,
+    pub triggered_at: String,
+    // Filled in per response; see timestamps
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub triggered_at_display: Option<TimeDisplay>,
+}
+
+impl Localize for Alert {
+    fn localize(&mut self, clock: &Clock) {
+        self.triggered_at_display = clock.display(&self.triggered_at);
+    }
+}
+
+// Transitions kept for the event feed
+const MAX_TRANSITIONS: usize = 500;
+
+#[derive(Clone, Debug)]
+pub struct AlertTransition {
+    pub at: String,
+    // The alert as last seen; for a cleared one, its last triggering value
+    pub alert: Alert,
+    pub cleared: bool,
+}
+
+// Rule id and subject
+pub type AlertKey = (String, Option<String>);
+
+#[derive(Default)]
+struct AlertLogInner {
+    active: HashMap<AlertKey, Alert>,
+    transitions: VecDeque<AlertTransition>,
+    // When, in awake time, each matching condition was first seen
+    holding: HashMap<AlertKey, Duration>,
+}
+
+#[derive(Default)]
+pub struct AlertLog {
+    inner: Mutex<AlertLogInner>,
+}
+
+impl AlertLog {
+    // An evaluation's result: alerts that weren't triggering before have
+    // started, and ones missing from it have cleared
+    pub fn observe(&self, alerts: &[Alert]) {
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut inner = self.inner.lock().unwrap();
+        let current: HashMap<AlertKey, Alert> = alerts
+            .iter()
+            .map(|a| ((a.rule_id.clone(), a.subject.clone()), a.clone()))
+            .collect();
+        let mut changes: Vec<AlertTransition> = Vec::new();
+        for (key, alert) in &current {
+            if !inner.active.contains_key(key) {
+                changes.push(AlertTransition {
+                    at: alert.triggered_at.clone(),
+                    alert: alert.clone(),
+                    cleared: false,
+                });
+            }
+        }
+        for (key, alert) in &inner.active {
+            if !current.contains_key(key) {
+                changes.push(AlertTransition {
+                    at: now.clone(),
+                    alert: alert.clone(),
+                    cleared: true,
+                });
+            }
+        }
+        inner.transitions.extend(changes);
+        let excess = inner.transitions.len().saturating_sub(MAX_TRANSITIONS);
+        inner.transitions.drain(..excess);
+        inner.active = current;
+    }
+
+    // An evaluation's matches narrowed to the ones that have held for their
+    // rule's for_secs
+    pub fn sustained(&self, rules: &[AlertRule], matches: Vec<Alert>) -> Vec<Alert> {
+        let mut inner = self.inner.lock().unwrap();
+        sustained(rules, matches, &mut inner.holding, crate::suspend::awake())
+    }
+
+    // Oldest first
+    pub fn since(&self, since: Option<&str>) -> Vec<AlertTransition> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .transitions
+            .iter()
+            .filter(|t| since.is_none_or(|since| t.at.as_str() >= since))
+            .cloned()
+            .collect()
+    }
+}
+
+pub fn evaluate(rules: &[AlertRule], signals: &[Signal]) -> Vec<Alert> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut alerts = Vec::new();
+
+    for rule in rules.iter().filter(|r| r.enabled) {
+        for signal in signals.iter().filter(|s| s.name == rule.signal) {
+            if rule.subject.is_some() && rule.subject != signal.subject {
+                continue;
+            }
+            if rule.comparison.matches(signal.value, rule.threshold) {
+                alerts.push(Alert {
+                    rule_id: rule.id.clone(),
+                    rule_name: rule.name.clone(),
+                    severity: rule.severity.clone(),
+                    signal: signal.name.clone(),
+                    subject: signal.subject.clone(),
+                    value: signal.value,
+                    threshold: rule.threshold,
+                    triggered_at: now.clone(),
+                    triggered_at_display: None,
+                });
+            }
+        }
+    }
+
+    alerts
+}
+
+// Keeps the matches whose rule has no for_secs or has held that long by
+// `now`; `holding` tracks when each condition started matching and
+// forgets the ones that stopped
+pub fn sustained(
+    rules: &[AlertRule],
+    matches: Vec<Alert>,
+    holding: &mut HashMap<AlertKey, Duration>,
+    now: Duration,
+) -> Vec<Alert> {
+    holding.retain(|(rule_id, subject), _| {
+        matches.iter().any(|a| a.rule_id == *rule_id && a.subject == *subject)
+    });
+    matches
+        .into_iter()
+        .filter(|alert| {
+            let needed = rules.iter().find(|r| r.id == alert.rule_id).map_or(0, |r| r.for_secs);
+            let since = *holding
+                .entry((alert.rule_id.clone(), alert.subject.clone()))
+                .or_insert(now);
+            now.saturating_sub(since) >= Duration::from_secs(needed)
+        })
+        .collect()
+}
+
+// Gather the current value of every signal the engine knows about
+pub fn collect_signals(settings: &Settings, db: &Database) -> Vec<Signal> {
+    let mut signals = Vec::new();
+
+    let metrics = crate::local_system_metrics(settings.units);
+    signals.push(Signal::new("cpu_percent", None, metrics.cpu_percent as f64));
+    signals.push(Signal::new("memory_percent", None, metrics.memory_percent as f64));
+    for disk in &metrics.disks {
+        signals.push(Signal::new(
+            "disk.usage_percent",
+            Some(&disk.mount_point),
+            disk.usage_percent as f64,
+        ));
+        if let Some(inode_percent) = disk.inodes_usage_percent {
+            signals.push(Signal::new(
+                "disk.inode_usage_percent",
+                Some(&disk.mount_point),
+                inode_percent as f64,
+            ));
+        }
+    }
+
+    let time = crate::timesync::probe_time_sync(settings.time_drift_threshold_ms);
+    signals.push(Signal::flag("time.drift_warning", None, time.drift_warning));
+    if let Some(offset) = time.offset_ms {
+        signals.push(Signal::new("time.offset_ms", None, offset.abs()));
+    }
+
+    let reboot = crate::reboot::probe_reboot_status();
+    signals.push(Signal::flag("reboot.required", None, reboot.reboot_required));
+    if let Some(count) = reboot.outdated_services {
+        signals.push(Signal::new("reboot.outdated_services", None, count as f64));
+    }
+    // One flag per module that won't be there after booting the newest kernel
+    if let Some(modules) = &reboot.dkms_not_installed {
+        signals.push(Signal::new("kernel.dkms_not_installed_count", None, modules.len() as f64));
+        for module in modules {
+            signals.push(Signal::flag("kernel.dkms_not_installed", Some(module), true));
+        }
+    }
+
+    // Only once the backend has been called in the last five minutes
+    let backend = crate::backend_latency::recent();
+    if let Some(p95) = backend.p95_ms {
+        signals.push(Signal::new("backend.p95_ms", None, p95));
+        signals.push(Signal::new("backend.error_rate", None, backend.error_rate));
+        if settings.backend_slo_p95_ms > 0 {
+            signals.push(Signal::flag("backend.slo_violated", None, p95 > settings.backend_slo_p95_ms as f64));
+        }
+    }
+
+    // From the last stored scan; the network is never touched here
+    match crate::certificates::stored_results(db, &settings.certificate_targets) {
+        Ok(results) => {
+            for result in results {
+                if let Some(days) = result.days_remaining {
+                    signals.push(Signal::new("cert.days_remaining", Some(&result.target), days as f64));
+                    signals.push(Signal::flag(
+                        "cert.expiring",
+                        Some(&result.target),
+                        days < settings.certificate_warning_days,
+                    ));
+                }
+                signals.push(Signal::flag("cert.chain_invalid", Some(&result.target), !result.chain_valid));
+            }
+        }
+        Err(e) => println!("[Halbert] Skipping certificate signals: {}", e),
+    }
+
+    // Incidents in the last hour, per kind; kinds with none read 0
+    let hour_ago = (chrono::Utc::now() - chrono::Duration::hours(1)).to_rfc3339();
+    match crate::incidents::counts_since(db, &hour_ago) {
+        Ok(counts) => {
+            for &kind in crate::incidents::KINDS {
+                let count = counts.iter().find(|(k, _)| k == kind).map_or(0, |(_, n)| *n);
+                signals.push(Signal::new("incident.count", Some(kind), count as f64));
+            }
+        }
+        Err(e) => println!("[Halbert] Skipping incident signals: {}", e),
+    }
+
+    signals
+}
+
+pub fn validate_rules(rules: &[AlertRule]) -> CommandResult<()> {
+    let mut ids = HashSet::new();
+    for rule in rules {
+        if rule.id.trim().is_empty() || rule.signal.trim().is_empty() {
+            return Err(CommandError::InvalidInput(
+                "alert rules need an id and a signal".to_string(),
+            ));
+        }
+        if !ids.insert(rule.id.as_str()) {
+            return Err(CommandError::InvalidInput(format!(
+                "duplicate alert rule id '{}'",
+                rule.id
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_alert_rules(settings: State<'_, SettingsStore>) -> Vec<AlertRule> {
+    settings.get().alert_rules
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn set_alert_rules(
+    settings: State<'_, SettingsStore>,
+    rules: Vec<AlertRule>,
+) -> CommandResult<Vec<AlertRule>> {
+    validate_rules(&rules)?;
+    let updated = settings.update(|current| {
+        let mut next = current.clone();
+        next.alert_rules = rules;
+        Ok(next)
+    })?;
+    Ok(updated.alert_rules)
+}
+
+// Evaluate every rule and announce what triggered
+pub fn evaluate_and_notify(app: &AppHandle, settings: &Settings, db: &Database) -> Vec<Alert> {
+    let log = app.state::<AlertLog>();
+    let matches = evaluate(&settings.alert_rules, &collect_signals(settings, db));
+    let alerts = log.sustained(&settings.alert_rules, matches);
+    log.observe(&alerts);
+    for alert in &alerts {
+        let _ = app.emit("alerts://triggered", alert);
+        crate::notifications::alert_triggered(app, alert);
+    }
+    alerts
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn evaluate_alerts(app: AppHandle, settings: State<'_, SettingsStore>, db: State<'_, Database>) -> Vec<Alert> {
+    let settings = settings.get();
+    let alerts = evaluate_and_notify(&app, &settings, &db);
+    timestamps::localized(&settings, alerts)
+}
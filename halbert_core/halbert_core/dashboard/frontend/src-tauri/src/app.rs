@@ -0,0 +1,12 @@
+// What the core reaches its state, paths and events through.
+//
+// With the "gui" feature (the desktop app) these are Tauri's own. Without
+// it they're the stand-ins in headless, which halbert-agent runs the same
+// stores, loops and commands on with no window (see agent). Modules that
+// only make sense with a window (the widget, screenshots, the command
+// stats) use tauri directly and are only built with "gui".
+#[cfg(feature = "gui")]
+pub use tauri::{async_runtime, AppHandle, Emitter, Manager, Runtime, State};
+
+#[cfg(not(feature = "gui"))]
+pub use crate::headless::{async_runtime, AppHandle, Emitter, Manager, Runtime, State};
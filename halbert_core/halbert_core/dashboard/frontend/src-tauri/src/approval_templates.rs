@@ -0,0 +1,249 @@
+// Approval templates for recurring maintenance.
+//
+// A template is a saved approval request. Instantiating it creates a fresh
+// pending request that goes through the risk policies like any other. The
+// action text and affected resources may contain placeholders such as
+// {journal_size} that are filled with live values at that moment; one that
+// can't be resolved is left as written rather than blocking the request.
+// A template linked to a job template runs that job when approved, as the
+// request's execution (see approvals).
+// Schedulers should go through `instantiate` so every path looks the same.
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::app::{AppHandle, Manager, State};
+use crate::approvals::{self, ApprovalRequest, Execution, NewApproval};
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::job_templates::{self, CommandLine};
+use crate::policy::RiskLevel;
+use crate::settings::SettingsStore;
+use crate::units::Units;
+
+const APT_ARCHIVES: &str = "/var/cache/apt/archives";
+
+#[derive(Serialize, Clone)]
+pub struct ApprovalTemplate {
+    pub id: i64,
+    pub name: String,
+    pub task: String,
+    pub action: String,
+    pub affected_resources: Vec<String>,
+    pub risk_level: RiskLevel,
+    pub linked_job_template: Option<String>,
+    pub job_params: Option<Value>,
+    pub created_at: String,
+}
+
+// Replace each {name} for which `resolve` has a value. Unknown or failing
+// placeholders, and unbalanced braces, are kept verbatim.
+pub fn fill_placeholders<F>(text: &str, resolve: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after.find('}').filter(|&end| {
+            let name = &after[..end];
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+        match end.and_then(|end| resolve(&after[..end]).map(|value| (end, value))) {
+            Some((end, value)) => {
+                out.push_str(&value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// "Archived and active journals take up 1.2G in the file system."
+fn journal_size() -> Option<String> {
+    let out = exec::stdout("journalctl", &["--disk-usage"])?;
+    let (_, tail) = out.split_once("take up ")?;
+    tail.split_whitespace().next().map(str::to_string)
+}
+
+fn dir_bytes(path: &std::path::Path) -> Option<u64> {
+    let mut total = 0;
+    for entry in std::fs::read_dir(path).ok()?.flatten() {
+        let meta = entry.metadata().ok()?;
+        total += if meta.is_dir() { dir_bytes(&entry.path())? } else { meta.len() };
+    }
+    Some(total)
+}
+
+// The placeholders that have live values
+fn live_value(name: &str, units: Units) -> Option<String> {
+    match name {
+        "journal_size" => journal_size(),
+        "apt_cache_size" => dir_bytes(std::path::Path::new(APT_ARCHIVES)).map(|b| units.format_bytes(b)),
+        "hostname" => sysinfo::System::host_name(),
+        "date" => Some(chrono::Local::now().format("%Y-%m-%d").to_string()),
+        _ => None,
+    }
+}
+
+type TemplateRow = (i64, String, String, String, String, String, Option<String>, Option<String>, String);
+
+const COLUMNS: &str =
+    "id, name, task, action, affected_resources, risk_level, linked_job_template, job_params, created_at";
+
+fn from_row(row: TemplateRow) -> CommandResult<ApprovalTemplate> {
+    let (id, name, task, action, resources, risk, linked_job_template, job_params, created_at) = row;
+    let corrupt = |e: String| CommandError::Internal(format!("approval template {} is corrupt: {}", name, e));
+    Ok(ApprovalTemplate {
+        affected_resources: serde_json::from_str(&resources).map_err(|e| corrupt(e.to_string()))?,
+        risk_level: RiskLevel::parse(&risk).ok_or_else(|| corrupt(format!("unknown risk level '{}'", risk)))?,
+        job_params: job_params
+            .map(|p| serde_json::from_str(&p))
+            .transpose()
+            .map_err(|e| corrupt(e.to_string()))?,
+        id,
+        name,
+        task,
+        action,
+        linked_job_template,
+        created_at,
+    })
+}
+
+fn read_row(r: &rusqlite::Row) -> rusqlite::Result<TemplateRow> {
+    Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?, r.get(5)?, r.get(6)?, r.get(7)?, r.get(8)?))
+}
+
+fn load(db: &Database, id: i64) -> CommandResult<ApprovalTemplate> {
+    let sql = format!("SELECT {} FROM approval_templates WHERE id = ?1", COLUMNS);
+    let row = db.with_conn(|conn| conn.query_row(&sql, params![id], read_row).optional())?;
+    row.map(from_row)
+        .transpose()?
+        .ok_or_else(|| CommandError::NotFound(format!("approval template {}", id)))
+}
+
+// `group_id` and `depends_on` tie the request to others (see approvals)
+pub fn instantiate(
+    app: &AppHandle,
+    template_id: i64,
+    group_id: Option<String>,
+    depends_on: Vec<String>,
+) -> CommandResult<ApprovalRequest> {
+    let db = app.state::<Database>();
+    let template = load(&db, template_id)?;
+    let units = app.state::<SettingsStore>().get().units;
+    let fill = |text: &str| fill_placeholders(text, |name| live_value(name, units));
+
+    // The request is at least as risky as the job it would run
+    let mut risk = template.risk_level;
+    let mut action_text = fill(&template.action);
+    let execution = match &template.linked_job_template {
+        Some(job) => {
+            let params = template.job_params.clone().unwrap_or(Value::Null);
+            let (commands, job_risk, _, _, _) = job_templates::resolve(&db, job, &params)?;
+            risk = risk.max(job_risk);
+            let shown: Vec<String> = commands.iter().map(CommandLine::display).collect();
+            action_text = format!("{} (runs: {})", action_text, shown.join("; "));
+            Some(Execution {
+                template: job.clone(),
+                params,
+                commands: shown,
+            })
+        }
+        None => None,
+    };
+    let new = NewApproval {
+        task: template.task.clone(),
+        action: action_text,
+        reasoning: format!("Raised from the approval template '{}'", template.name),
+        confidence: 1.0,
+        risk_level: risk.as_str().to_string(),
+        affected_resources: template.affected_resources.iter().map(|r| fill(r)).collect(),
+        execution,
+        group_id,
+        depends_on,
+    };
+    approvals::submit(app, new, None)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn create_approval_template(
+    db: State<'_, Database>,
+    name: String,
+    task: String,
+    action: String,
+    affected_resources: Vec<String>,
+    risk_level: String,
+    linked_job_template: Option<String>,
+    job_params: Option<Value>,
+) -> CommandResult<ApprovalTemplate> {
+    let name = name.trim().to_string();
+    if name.is_empty() || task.trim().is_empty() || action.trim().is_empty() {
+        return Err(CommandError::InvalidInput("name, task and action are required".to_string()));
+    }
+    let risk = RiskLevel::parse(&risk_level)
+        .ok_or_else(|| CommandError::InvalidInput(format!("unknown risk level '{}'", risk_level)))?;
+    let linked_job_template = linked_job_template.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+    if let Some(job) = &linked_job_template {
+        // Catches a missing template or params it won't accept now, not at approval time
+        job_templates::resolve(&db, job, job_params.as_ref().unwrap_or(&Value::Null))?;
+    }
+
+    let resources = serde_json::to_string(&affected_resources).map_err(|e| CommandError::Internal(e.to_string()))?;
+    let params_json = job_params
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| CommandError::Internal(e.to_string()))?;
+    let now = chrono::Utc::now().to_rfc3339();
+    let id = db.with_conn(|conn| {
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO approval_templates
+                (name, task, action, affected_resources, risk_level, linked_job_template, job_params, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![name, task.trim(), action.trim(), resources, risk.as_str(), linked_job_template, params_json, now],
+        )?;
+        Ok((inserted > 0).then(|| conn.last_insert_rowid()))
+    })?;
+    let id = id.ok_or_else(|| CommandError::Conflict(format!("approval template '{}' already exists", name)))?;
+    load(&db, id)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn list_approval_templates(db: State<'_, Database>) -> CommandResult<Vec<ApprovalTemplate>> {
+    let sql = format!("SELECT {} FROM approval_templates ORDER BY name", COLUMNS);
+    let rows: Vec<TemplateRow> = db.with_conn(|conn| {
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map([], read_row)?;
+        rows.collect()
+    })?;
+    rows.into_iter().map(from_row).collect()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn delete_approval_template(db: State<'_, Database>, template_id: i64) -> CommandResult<()> {
+    let deleted = db.with_conn(|conn| conn.execute("DELETE FROM approval_templates WHERE id = ?1", params![template_id]))?;
+    if deleted == 0 {
+        return Err(CommandError::NotFound(format!("approval template {}", template_id)));
+    }
+    Ok(())
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn instantiate_approval_template(
+    app: AppHandle,
+    template_id: i64,
+    group_id: Option<String>,
+    depends_on: Option<Vec<String>>,
+) -> CommandResult<ApprovalRequest> {
+    instantiate(&app, template_id, group_id, depends_on.unwrap_or_default())
+}
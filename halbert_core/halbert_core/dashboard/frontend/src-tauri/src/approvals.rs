@@ -0,0 +1,2036 @@
+// Approval requests and the decision hook.
+//
+// Requests that carry an `ApprovalAction` only act once approved; the store
+// marks the request approved under its lock and runs the action afterwards
+// so a double-click can never execute twice. New requests go through the
+// local risk policies first (see policy), which may decide them on the spot.
+//
+// A request needs `approvers_required` distinct approvers (set per risk
+// level in settings, one by default). Each approval is a vote kept on the
+// request and in approval_votes; the request only runs once enough have
+// come in, and a single rejection decides it at once. The window always
+// votes as this machine's approver identity, and every vote from this
+// machine counts as the same approver whatever that identity is set to; a
+// remote identity only comes from an authenticated channel (remote_access),
+// one per credential.
+// While a remote host is active, approving or rejecting one request is sent
+// to that host instead (hosts::decide_approval).
+//
+// A request may instead carry an `execution`: a job template and its
+// params, from the backend or an approval template. It is resolved when
+// the request is approved, and the job it starts is linked back onto the
+// request. `execution_status` follows that job ("running" or "queued",
+// then "completed" or "failed") apart from the decision, which stays
+// "approved" even when the job can't start, e.g. because the template was
+// deleted in the meantime; a failed execution is announced on its own.
+//
+// Related requests can share a `group_id` and name the requests they
+// follow in `depends_on` (see the section at the bottom). A request can't
+// be approved before its dependencies, rejecting one rejects everything
+// that depends on it, and an execution waits for those of its
+// dependencies to complete.
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::app::{AppHandle, Emitter, Manager, State};
+use crate::audit;
+use crate::automation::{Entry, Gate};
+use crate::calibration;
+use crate::cancellation::Operations;
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::hosts::{self, ActiveHost};
+use crate::impact::{self, ImpactSummary};
+use crate::job_templates::{self, CommandLine};
+use crate::jobs::{Job, JobManager};
+use crate::policy::{self, PolicyOutcome, RiskLevel, Subject, Verdict};
+use crate::processes::{self, ProcessTarget};
+use crate::services;
+use crate::settings::{Settings, SettingsStore};
+use crate::snapshots::{Snapshot, SnapshotPolicy};
+use crate::timestamps::{self, Clock, Localize, TimeDisplay};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ApprovalRequest {
+    #[serde(default)]
+    pub host_id: String,
+    pub id: String,
+    pub task: String,
+    pub action: String,
+    pub reasoning: String,
+    pub confidence: f32,
+    pub risk_level: String,
+    pub affected_resources: Vec<String>,
+    pub requested_at: String,
+    pub status: String,
+    #[serde(default)]
+    pub decided_at: Option<String>,
+    // Rejection reason, or what the approved action did / why it failed
+    #[serde(default)]
+    pub decision_note: Option<String>,
+    // Built-in job template this task maps to (see job_templates)
+    #[serde(default)]
+    pub task_type: Option<String>,
+    #[serde(default)]
+    pub task_params: serde_json::Value,
+    #[serde(default)]
+    pub dry_run_at: Option<String>,
+    #[serde(default)]
+    pub dry_run_summary: Option<String>,
+    // As proposed by the requester; risk_level is the effective one
+    #[serde(default)]
+    pub original_risk_level: Option<String>,
+    // Risk policy that matched when the request was inserted
+    #[serde(default)]
+    pub policy_id: Option<String>,
+    // Distinct approvals needed before the request runs
+    #[serde(default = "one_approver")]
+    pub approvers_required: u32,
+    // Every vote so far; a remote backend reports its own
+    #[serde(default)]
+    pub votes: Vec<ApprovalVote>,
+    // What approving the request runs, if anything
+    #[serde(default)]
+    pub execution: Option<Execution>,
+    // "pending" until approved, "waiting" while a dependency's execution
+    // runs; then how the job did
+    #[serde(default)]
+    pub execution_status: Option<String>,
+    #[serde(default)]
+    pub execution_job_id: Option<String>,
+    #[serde(default)]
+    pub execution_error: Option<String>,
+    // What the job snapshotted before it ran; the rollback points
+    #[serde(default)]
+    pub execution_snapshots: Vec<Snapshot>,
+    // Requests raised together, approved together with approve_group
+    #[serde(default)]
+    pub group_id: Option<String>,
+    // Ids of requests that must be approved (and have run) first
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    // Filled in per response; see timestamps
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub requested_at_display: Option<TimeDisplay>,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub decided_at_display: Option<TimeDisplay>,
+}
+
+impl Localize for ApprovalRequest {
+    fn localize(&mut self, clock: &Clock) {
+        self.requested_at_display = clock.display(&self.requested_at);
+        self.decided_at_display = clock.display_opt(self.decided_at.as_deref());
+    }
+}
+
+fn one_approver() -> u32 {
+    1
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Execution {
+    pub template: String,
+    #[serde(default)]
+    pub params: serde_json::Value,
+    // Command lines shown when the request was made; when set, approval
+    // only runs if the template still builds exactly these
+    #[serde(default)]
+    pub commands: Vec<String>,
+}
+
+// Whether an execution can still run as requested, given what its template
+// resolves to now
+pub fn check_execution(
+    execution: &Execution,
+    resolved: CommandResult<job_templates::Resolved>,
+) -> Result<job_templates::Resolved, String> {
+    let resolved = match resolved {
+        Ok(resolved) => resolved,
+        Err(CommandError::NotFound(_)) => {
+            return Err(format!(
+                "job template '{}' no longer exists; it was deleted after the request was made",
+                execution.template
+            ))
+        }
+        Err(e) => return Err(format!("job template '{}' can't run: {}", execution.template, e)),
+    };
+    let shown: Vec<String> = resolved.0.iter().map(CommandLine::display).collect();
+    if !execution.commands.is_empty() && shown != execution.commands {
+        return Err(format!(
+            "job template '{}' changed after the request was made; it would now run: {}",
+            execution.template,
+            shown.join("; ")
+        ));
+    }
+    Ok(resolved)
+}
+
+fn is_finished(status: &str) -> bool {
+    matches!(status, "completed" | "failed")
+}
+
+// Follows the linked job; true if the request changed
+fn follow_job(request: &mut ApprovalRequest, job: &Job) -> bool {
+    if request.execution_job_id.as_deref() != Some(job.id.as_str())
+        || request.execution_status.as_deref().is_some_and(is_finished)
+        || (request.execution_status.as_deref() == Some(job.status.as_str())
+            && request.execution_snapshots == job.snapshots)
+    {
+        return false;
+    }
+    request.execution_status = Some(job.status.clone());
+    request.execution_error = job.error.clone();
+    request.execution_snapshots = job.snapshots.clone();
+    true
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ApprovalVote {
+    pub approver: String,
+    // "local", "remote" (over an authenticated channel) or "policy"
+    pub source: String,
+    // "approve" or "reject"
+    pub vote: String,
+    pub at: String,
+}
+
+impl ApprovalVote {
+    fn voter(&self) -> &str {
+        voter(&self.source, &self.approver)
+    }
+}
+
+// Who a vote counts as toward the quorum: all of this machine's votes are
+// one approver
+fn voter<'a>(source: &str, identity: &'a str) -> &'a str {
+    if source == "local" {
+        "local"
+    } else {
+        identity
+    }
+}
+
+// Who a vote is cast as
+pub struct Approver {
+    pub identity: String,
+    pub source: &'static str,
+}
+
+impl Approver {
+    // This machine's user, named by `approver_identity` when it's set
+    pub fn local(settings: &Settings) -> Self {
+        Approver {
+            identity: settings
+                .approver_identity
+                .clone()
+                .filter(|i| !i.trim().is_empty())
+                .unwrap_or_else(audit::local_actor),
+            source: "local",
+        }
+    }
+
+    // An identity an authenticated channel vouches for
+    pub fn remote(identity: String) -> Self {
+        Approver {
+            identity,
+            source: "remote",
+        }
+    }
+
+    fn voter(&self) -> &str {
+        voter(self.source, &self.identity)
+    }
+
+    fn vote(&self, vote: &str) -> ApprovalVote {
+        ApprovalVote {
+            approver: self.identity.clone(),
+            source: self.source.to_string(),
+            vote: vote.to_string(),
+            at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+// What a vote did to its request
+pub enum Ballot {
+    // Counted; more approvals are still needed
+    Counted(ApprovalRequest),
+    // The approver had already voted this way; nothing changed
+    Repeated(ApprovalRequest),
+    Decided(ApprovalRequest),
+}
+
+impl Ballot {
+    pub fn into_request(self) -> ApprovalRequest {
+        match self {
+            Ballot::Counted(request) | Ballot::Repeated(request) | Ballot::Decided(request) => request,
+        }
+    }
+}
+
+// Distinct approvers in favour
+pub fn approvals(request: &ApprovalRequest) -> u32 {
+    let voters: HashSet<&str> = request.votes.iter().filter(|v| v.vote == "approve").map(ApprovalVote::voter).collect();
+    voters.len() as u32
+}
+
+pub fn approvers_required(settings: &Settings, risk_level: &str) -> u32 {
+    settings.approvers_required.get(risk_level).copied().unwrap_or(1).max(1)
+}
+
+#[derive(Serialize, Clone)]
+struct VoteEvent {
+    request: ApprovalRequest,
+    vote: ApprovalVote,
+}
+
+// Work performed by the decision hook once a request is approved
+#[derive(Clone, Debug)]
+pub enum ApprovalAction {
+    KillProcess { target: ProcessTarget, signal: String },
+    ReniceProcess { target: ProcessTarget, nice: i32 },
+    ServiceAction { unit: String, action: String },
+    // Command lines are built when the request is made, so approval runs
+    // exactly what was shown
+    RunTemplate {
+        template: String,
+        params: serde_json::Value,
+        commands: Vec<CommandLine>,
+        timeout: Option<Duration>,
+        artifacts_dir: Option<PathBuf>,
+        snapshot: SnapshotPolicy,
+    },
+}
+
+impl ApprovalAction {
+    fn run(&self, jobs: &JobManager) -> CommandResult<String> {
+        match self {
+            ApprovalAction::KillProcess { target, signal } => processes::send_signal(target, signal),
+            ApprovalAction::ReniceProcess { target, nice } => processes::renice(target, *nice),
+            ApprovalAction::ServiceAction { unit, action } => {
+                let job = services::spawn_service_job(jobs, unit, action);
+                Ok(format!("Started job {}", job.id))
+            }
+            ApprovalAction::RunTemplate {
+                template,
+                commands,
+                timeout,
+                artifacts_dir,
+                snapshot,
+                ..
+            } => {
+                let job = job_templates::spawn_template_job(
+                    jobs,
+                    template,
+                    commands.clone(),
+                    *timeout,
+                    artifacts_dir.clone(),
+                    snapshot.clone(),
+                );
+                Ok(format!("Started job {}", job.id))
+            }
+        }
+    }
+}
+
+pub struct NewApproval {
+    pub task: String,
+    pub action: String,
+    pub reasoning: String,
+    pub confidence: f32,
+    pub risk_level: String,
+    pub affected_resources: Vec<String>,
+    pub execution: Option<Execution>,
+    pub group_id: Option<String>,
+    pub depends_on: Vec<String>,
+}
+
+impl NewApproval {
+    pub fn subject(&self) -> Subject<'_> {
+        Subject {
+            task: &self.task,
+            action: &self.action,
+            risk_level: &self.risk_level,
+            affected_resources: &self.affected_resources,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct DryRunResult {
+    pub job_id: String,
+    pub commands: Vec<String>,
+    pub exit_codes: Vec<Option<i32>>,
+    pub output: Vec<String>,
+    pub summary: String,
+    pub finished_at: String,
+}
+
+#[derive(Serialize)]
+pub struct ApprovalDetail {
+    pub request: ApprovalRequest,
+    pub impact: ImpactSummary,
+    pub dry_run: Option<DryRunResult>,
+    // The job the request's execution started, while it's still kept
+    pub execution_job: Option<Job>,
+    // Mounts snapshotted before the action runs
+    pub snapshot_before: Vec<String>,
+}
+
+struct StoredApproval {
+    request: ApprovalRequest,
+    action: Option<ApprovalAction>,
+    // Last impact expansion shown to the approver, audited with the decision
+    viewed_impact: Option<ImpactSummary>,
+    dry_run: Option<DryRunResult>,
+    dry_run_running: bool,
+}
+
+struct StoreInner {
+    requests: Vec<StoredApproval>,
+    next_id: u64,
+}
+
+pub struct ApprovalStore {
+    inner: Mutex<StoreInner>,
+}
+
+impl ApprovalStore {
+    #[cfg(test)]
+    pub fn empty() -> Self {
+        ApprovalStore {
+            inner: Mutex::new(StoreInner {
+                requests: Vec::new(),
+                next_id: 1,
+            }),
+        }
+    }
+
+    pub fn with_mock_requests() -> Self {
+        let requests = mock_requests()
+            .into_iter()
+            .map(|request| StoredApproval {
+                request,
+                action: None,
+                viewed_impact: None,
+                dry_run: None,
+                dry_run_running: false,
+            })
+            .collect::<Vec<_>>();
+        let next_id = requests.len() as u64 + 1;
+        ApprovalStore {
+            inner: Mutex::new(StoreInner { requests, next_id }),
+        }
+    }
+
+    pub fn insert(
+        &self,
+        new: NewApproval,
+        action: Option<ApprovalAction>,
+        policy: &PolicyOutcome,
+        approvers_required: u32,
+    ) -> CommandResult<ApprovalRequest> {
+        // Template runs keep their type so the dry-run and detail views work
+        let (task_type, task_params) = match (&action, &new.execution) {
+            (Some(ApprovalAction::RunTemplate { template, params, .. }), _) => (Some(template.clone()), params.clone()),
+            (None, Some(execution)) => (Some(execution.template.clone()), execution.params.clone()),
+            _ => (None, serde_json::Value::Null),
+        };
+        let mut inner = self.inner.lock().unwrap();
+        let id = format!("req_{:03}", inner.next_id);
+        check_dependencies(&inner, &id, &new.depends_on)?;
+        let request = ApprovalRequest {
+            host_id: hosts::LOCAL_HOST_ID.to_string(),
+            id,
+            task: new.task,
+            action: new.action,
+            reasoning: new.reasoning,
+            confidence: new.confidence,
+            risk_level: policy.effective_risk.clone(),
+            affected_resources: new.affected_resources,
+            requested_at: chrono::Utc::now().to_rfc3339(),
+            status: "pending".to_string(),
+            decided_at: None,
+            decision_note: None,
+            task_type,
+            task_params,
+            dry_run_at: None,
+            dry_run_summary: None,
+            original_risk_level: Some(policy.original_risk.clone()),
+            policy_id: policy.rule_id.clone(),
+            approvers_required,
+            votes: Vec::new(),
+            execution_status: new.execution.as_ref().map(|_| "pending".to_string()),
+            execution: new.execution,
+            execution_job_id: None,
+            execution_error: None,
+            execution_snapshots: Vec::new(),
+            group_id: new.group_id.map(|g| g.trim().to_string()).filter(|g| !g.is_empty()),
+            depends_on: new.depends_on,
+            requested_at_display: None,
+            decided_at_display: None,
+        };
+        inner.next_id += 1;
+        inner.requests.push(StoredApproval {
+            request: request.clone(),
+            action,
+            viewed_impact: None,
+            dry_run: None,
+            dry_run_running: false,
+        });
+        Ok(request)
+    }
+
+    // Record a request that was executed directly without waiting for a
+    // decision (e.g. a forced process action) so history stays complete
+    pub fn record_executed(
+        &self,
+        new: NewApproval,
+        policy: &PolicyOutcome,
+        note: String,
+    ) -> CommandResult<ApprovalRequest> {
+        let request = self.insert(new, None, policy, 1)?;
+        Ok(self.set_decision(&request.id, "approved", Some(note)).unwrap_or(request))
+    }
+
+    pub fn pending(&self) -> Vec<ApprovalRequest> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .requests
+            .iter()
+            .filter(|r| r.request.status == "pending")
+            .map(|r| r.request.clone())
+            .collect()
+    }
+
+    // Whether a dependency still holds the request back
+    fn is_blocked(&self, request_id: &str) -> bool {
+        !blockers_of(&self.inner.lock().unwrap(), request_id).is_empty()
+    }
+
+    pub fn remember_impact(&self, request_id: &str, impact: ImpactSummary) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(stored) = inner.requests.iter_mut().find(|r| r.request.id == request_id) {
+            stored.viewed_impact = Some(impact);
+        }
+    }
+
+    fn begin_dry_run(&self, request_id: &str) -> CommandResult<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let stored = find_pending(&mut inner, request_id)?;
+        if stored.dry_run_running {
+            return Err(CommandError::Conflict(format!(
+                "a dry run for {} is already running",
+                request_id
+            )));
+        }
+        stored.dry_run_running = true;
+        Ok(())
+    }
+
+    // Replaces any earlier dry run
+    fn finish_dry_run(&self, request_id: &str, result: DryRunResult) -> CommandResult<ApprovalRequest> {
+        let mut inner = self.inner.lock().unwrap();
+        let stored = inner
+            .requests
+            .iter_mut()
+            .find(|r| r.request.id == request_id)
+            .ok_or_else(|| CommandError::NotFound(format!("approval request {}", request_id)))?;
+        stored.request.dry_run_at = Some(result.finished_at.clone());
+        stored.request.dry_run_summary = Some(result.summary.clone());
+        stored.dry_run = Some(result);
+        stored.dry_run_running = false;
+        Ok(stored.request.clone())
+    }
+
+    // A dry run that ended without a result; the earlier one, if any, stays
+    fn abandon_dry_run(&self, request_id: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(stored) = inner.requests.iter_mut().find(|r| r.request.id == request_id) {
+            stored.dry_run_running = false;
+        }
+    }
+
+    fn dry_run(&self, request_id: &str) -> Option<DryRunResult> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .requests
+            .iter()
+            .find(|r| r.request.id == request_id)
+            .and_then(|r| r.dry_run.clone())
+    }
+
+    fn action_snapshots(&self, request_id: &str) -> Option<Vec<String>> {
+        let inner = self.inner.lock().unwrap();
+        match &inner.requests.iter().find(|r| r.request.id == request_id)?.action {
+            Some(ApprovalAction::RunTemplate { snapshot, .. }) => Some(snapshot.snapshot_before.clone()),
+            _ => None,
+        }
+    }
+
+    // Count `approver`'s vote, and run the action once it's the last one
+    // needed. The decision is audited under the approver who completed it;
+    // earlier votes are audited as they come in.
+    pub fn approve(
+        &self,
+        request_id: &str,
+        jobs: &JobManager,
+        db: &Database,
+        approver: &Approver,
+    ) -> CommandResult<Ballot> {
+        let (request, action, decided) = {
+            let mut inner = self.inner.lock().unwrap();
+            if let Some(request) = repeated_vote(&inner, request_id, approver, "approve") {
+                return Ok(Ballot::Repeated(request));
+            }
+            find_pending(&mut inner, request_id)?;
+            let blockers = blockers_of(&inner, request_id);
+            if !blockers.is_empty() {
+                return Err(CommandError::DependenciesPending(format!(
+                    "{} can't be approved before {}",
+                    request_id,
+                    describe(&blockers)
+                )));
+            }
+            let stored = find_pending(&mut inner, request_id)?;
+            stored.request.votes.push(approver.vote("approve"));
+            let decided = approvals(&stored.request) >= stored.request.approvers_required;
+            if decided {
+                stored.request.status = "approved".to_string();
+                stored.request.decided_at = Some(chrono::Utc::now().to_rfc3339());
+            }
+            (stored.request.clone(), stored.action.clone(), decided)
+        };
+        record_vote(db, &request);
+        if !decided {
+            audit_vote(db, &request);
+            return Ok(Ballot::Counted(request));
+        }
+
+        let outcome = match action {
+            None => match &request.execution {
+                Some(execution) => self.execute_in_order(request_id, execution, jobs, db),
+                None => self.get(request_id),
+            },
+            Some(action) => match action.run(jobs) {
+                Ok(note) => self.set_decision(request_id, "approved", Some(note)),
+                Err(e) => {
+                    let _ = self.set_decision(request_id, "failed", Some(e.to_string()));
+                    Err(e)
+                }
+            },
+        };
+        self.audit_decision(db, request_id, &approver.identity);
+        outcome.map(Ballot::Decided)
+    }
+
+    // Starts an approved request's execution and links the job to it
+    fn execute(
+        &self,
+        request_id: &str,
+        execution: &Execution,
+        jobs: &JobManager,
+        db: &Database,
+    ) -> CommandResult<ApprovalRequest> {
+        let resolved = job_templates::resolve(db, &execution.template, &execution.params);
+        let started = check_execution(execution, resolved).map(|(commands, _, timeout, artifacts_dir, snapshot)| {
+            job_templates::spawn_template_job(jobs, &execution.template, commands, timeout, artifacts_dir, snapshot)
+        });
+        let mut inner = self.inner.lock().unwrap();
+        let stored = inner
+            .requests
+            .iter_mut()
+            .find(|r| r.request.id == request_id)
+            .ok_or_else(|| CommandError::NotFound(format!("approval request {}", request_id)))?;
+        let request = &mut stored.request;
+        match started {
+            Ok(job) => {
+                request.execution_job_id = Some(job.id.clone());
+                request.decision_note = Some(format!("Started job {}", job.id));
+                request.execution_status = Some(job.status.clone());
+                // A job that finished before it was linked
+                if let Some(latest) = jobs.get(&job.id) {
+                    follow_job(request, &latest);
+                }
+            }
+            Err(e) => {
+                println!("[Halbert] Execution of approved {} failed: {}", request_id, e);
+                request.execution_status = Some("failed".to_string());
+                request.execution_error = Some(e);
+            }
+        }
+        Ok(request.clone())
+    }
+
+    // An execution starts once its dependencies' executions have completed;
+    // until then it's "waiting" (see release_waiting)
+    fn execute_in_order(
+        &self,
+        request_id: &str,
+        execution: &Execution,
+        jobs: &JobManager,
+        db: &Database,
+    ) -> CommandResult<ApprovalRequest> {
+        let mut inner = self.inner.lock().unwrap();
+        let readiness = execution_readiness(&inner, request_id);
+        if let Readiness::Ready = readiness {
+            drop(inner);
+            return self.execute(request_id, execution, jobs, db);
+        }
+        let stored = inner
+            .requests
+            .iter_mut()
+            .find(|r| r.request.id == request_id)
+            .ok_or_else(|| CommandError::NotFound(format!("approval request {}", request_id)))?;
+        match readiness {
+            Readiness::Ready => Ok(stored.request.clone()),
+            Readiness::Waiting(on) => {
+                stored.request.execution_status = Some("waiting".to_string());
+                stored.request.decision_note = Some(format!("Waiting for {} to finish", on.join(", ")));
+                Ok(stored.request.clone())
+            }
+            Readiness::Failed(dependency) => {
+                let error = format!("didn't run because the execution of {} failed", dependency);
+                println!("[Halbert] Execution of approved {} {}", request_id, error);
+                stored.request.execution_status = Some("failed".to_string());
+                stored.request.execution_error = Some(error);
+                Ok(stored.request.clone())
+            }
+        }
+    }
+
+    // Starts (or fails) the waiting executions whose dependencies have
+    // finished; returns the requests that changed
+    pub fn release_waiting(&self, jobs: &JobManager, db: &Database) -> Vec<ApprovalRequest> {
+        let waiting: Vec<(String, Execution)> = {
+            let inner = self.inner.lock().unwrap();
+            inner
+                .requests
+                .iter()
+                .filter(|r| r.request.execution_status.as_deref() == Some("waiting"))
+                .filter(|r| !matches!(execution_readiness(&inner, &r.request.id), Readiness::Waiting(_)))
+                .filter_map(|r| Some((r.request.id.clone(), r.request.execution.clone()?)))
+                .collect()
+        };
+        waiting
+            .iter()
+            .filter_map(|(id, execution)| self.execute_in_order(id, execution, jobs, db).ok())
+            .collect()
+    }
+
+    // For job updates: the request whose linked job this is, if it changed
+    pub fn follow_execution(&self, job: &Job) -> Option<ApprovalRequest> {
+        let mut inner = self.inner.lock().unwrap();
+        let stored = inner
+            .requests
+            .iter_mut()
+            .find(|r| r.request.execution_job_id.as_deref() == Some(job.id.as_str()))?;
+        follow_job(&mut stored.request, job).then(|| stored.request.clone())
+    }
+
+    // Decided requests, newest decision first
+    pub fn history(&self, limit: usize) -> Vec<ApprovalRequest> {
+        let inner = self.inner.lock().unwrap();
+        let mut decided: Vec<ApprovalRequest> = inner
+            .requests
+            .iter()
+            .filter(|r| r.request.status != "pending")
+            .map(|r| r.request.clone())
+            .collect();
+        decided.sort_by(|a, b| b.decided_at.cmp(&a.decided_at));
+        decided.truncate(limit);
+        decided
+    }
+
+    // One rejection decides the request, whatever approvals it already has
+    pub fn reject(
+        &self,
+        request_id: &str,
+        reason: &str,
+        db: &Database,
+        approver: &Approver,
+    ) -> CommandResult<Ballot> {
+        let request = {
+            let mut inner = self.inner.lock().unwrap();
+            if let Some(request) = repeated_vote(&inner, request_id, approver, "reject") {
+                return Ok(Ballot::Repeated(request));
+            }
+            let stored = find_pending(&mut inner, request_id)?;
+            stored.request.votes.push(approver.vote("reject"));
+            stored.request.status = "rejected".to_string();
+            stored.request.decided_at = Some(chrono::Utc::now().to_rfc3339());
+            stored.request.decision_note = Some(reason.to_string());
+            stored.request.clone()
+        };
+        record_vote(db, &request);
+        self.audit_decision(db, request_id, &approver.identity);
+        Ok(Ballot::Decided(request))
+    }
+
+    // Rejects every pending request that depends on `request_id`, directly
+    // or not, as decided by `decider`
+    pub fn reject_dependents(&self, request_id: &str, db: &Database, decider: &str) -> Vec<ApprovalRequest> {
+        let rejected: Vec<ApprovalRequest> = {
+            let mut inner = self.inner.lock().unwrap();
+            let edges = dependency_edges(&inner);
+            let dependents = dependents(&edges, request_id);
+            let now = chrono::Utc::now().to_rfc3339();
+            let note = format!("Rejected because it depends on {}, which was rejected", request_id);
+            inner
+                .requests
+                .iter_mut()
+                .filter(|r| r.request.status == "pending" && dependents.contains(&r.request.id))
+                .map(|r| {
+                    r.request.status = "rejected".to_string();
+                    r.request.decided_at = Some(now.clone());
+                    r.request.decision_note = Some(note.clone());
+                    r.request.clone()
+                })
+                .collect()
+        };
+        for request in &rejected {
+            self.audit_decision(db, &request.id, decider);
+        }
+        rejected
+    }
+
+    // Everything pending in the group, in dependency order, provided this
+    // vote decides each of them; otherwise nothing's approved
+    fn group_plan(&self, group_id: &str, approver: &Approver) -> CommandResult<Vec<String>> {
+        let inner = self.inner.lock().unwrap();
+        let members: Vec<ApprovalRequest> = inner
+            .requests
+            .iter()
+            .filter(|r| r.request.group_id.as_deref() == Some(group_id) && r.request.status == "pending")
+            .map(|r| r.request.clone())
+            .collect();
+        if members.is_empty() {
+            return Err(CommandError::NotFound(format!("pending approval group {}", group_id)));
+        }
+        let outside: Vec<(String, String)> = members
+            .iter()
+            .flat_map(|m| blockers_of(&inner, &m.id))
+            .filter(|(id, _)| !members.iter().any(|m| m.id == *id))
+            .collect();
+        if !outside.is_empty() {
+            return Err(CommandError::DependenciesPending(format!(
+                "group {} can't be approved before {}",
+                group_id,
+                describe(&outside)
+            )));
+        }
+        let short: Vec<String> = members
+            .iter()
+            .filter(|m| {
+                let already = m.votes.iter().any(|v| v.voter() == approver.voter() && v.vote == "approve");
+                approvals(m) + u32::from(!already) < m.approvers_required
+            })
+            .map(|m| format!("{} ({} of {} approvals)", m.id, approvals(m), m.approvers_required))
+            .collect();
+        if !short.is_empty() {
+            return Err(CommandError::Conflict(format!(
+                "one more vote won't decide {}; approve those one by one",
+                short.join(", ")
+            )));
+        }
+        dependency_order(&members).map_err(|cycle| {
+            CommandError::Internal(format!("group {} has a dependency cycle: {}", group_id, cycle.join(", ")))
+        })
+    }
+
+    // Record the decision with exactly what the approver was shown; a null
+    // impact means the detail view was never opened. It is also kept for
+    // the confidence report (see calibration).
+    fn audit_decision(&self, db: &Database, request_id: &str, decider: &str) {
+        let (request, viewed_impact) = {
+            let inner = self.inner.lock().unwrap();
+            let Some(stored) = inner.requests.iter().find(|r| r.request.id == request_id) else {
+                return;
+            };
+            (stored.request.clone(), stored.viewed_impact.clone())
+        };
+        let detail = serde_json::json!({
+            "request": request,
+            "viewed_impact": viewed_impact,
+        });
+        let action = format!("approval.{}", request.status);
+        if let Err(e) = audit::record(db, decider, &action, request_id, &detail) {
+            println!("[Halbert] Failed to audit decision on {}: {}", request_id, e);
+        }
+        calibration::record_decision(db, &request);
+    }
+
+    pub fn get(&self, request_id: &str) -> CommandResult<ApprovalRequest> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .requests
+            .iter()
+            .find(|r| r.request.id == request_id)
+            .map(|r| r.request.clone())
+            .ok_or_else(|| CommandError::NotFound(format!("approval request {}", request_id)))
+    }
+
+    fn set_decision(
+        &self,
+        request_id: &str,
+        status: &str,
+        note: Option<String>,
+    ) -> CommandResult<ApprovalRequest> {
+        let mut inner = self.inner.lock().unwrap();
+        let stored = inner
+            .requests
+            .iter_mut()
+            .find(|r| r.request.id == request_id)
+            .ok_or_else(|| CommandError::NotFound(format!("approval request {}", request_id)))?;
+        stored.request.status = status.to_string();
+        stored.request.decided_at = Some(chrono::Utc::now().to_rfc3339());
+        stored.request.decision_note = note;
+        Ok(stored.request.clone())
+    }
+}
+
+// Insert a request under the local risk policies and announce it. A policy
+// auto-decision is applied straight away, audited with the rule as decider;
+// a policy can't approve a request that needs more than one approver, or
+// while automation is paused.
+pub fn submit(app: &AppHandle, new: NewApproval, action: Option<ApprovalAction>) -> CommandResult<ApprovalRequest> {
+    let store = app.state::<ApprovalStore>();
+    let settings = app.state::<SettingsStore>().get();
+    let outcome = policy::evaluate(&settings.risk_policies, &new.subject());
+    let required = approvers_required(&settings, &outcome.effective_risk);
+    let request = store.insert(new, action, &outcome, required)?;
+    let blocked = store.is_blocked(&request.id);
+
+    let decider = format!("policy:{}", outcome.rule_id.as_deref().unwrap_or_default());
+    let approver = Approver {
+        identity: decider.clone(),
+        source: "policy",
+    };
+    let verdict = match outcome.verdict {
+        Verdict::Approve if blocked => {
+            println!("[Halbert] Request {} waits for its dependencies; {} left it pending", request.id, decider);
+            Verdict::Pending
+        }
+        Verdict::Approve if required > 1 => {
+            println!("[Halbert] Request {} needs {} approvers; {} left it pending", request.id, required, decider);
+            Verdict::Pending
+        }
+        Verdict::Approve if !app.state::<Gate>().allows(Entry::AutoApprove, &request.task) => {
+            println!("[Halbert] Automation is paused; {} left {} pending", decider, request.id);
+            Verdict::Pending
+        }
+        verdict => verdict,
+    };
+    let decided = match verdict {
+        Verdict::Pending => {
+            let _ = app.emit("approvals://new", &request);
+            crate::notifications::approval_new(app, &request);
+            return Ok(request);
+        }
+        Verdict::Approve => {
+            let db = app.state::<Database>();
+            match store.approve(&request.id, &app.state::<JobManager>(), &db, &approver) {
+                Ok(ballot) => ballot.into_request(),
+                // The request is marked failed; report it like any other decision
+                Err(e) => {
+                    println!("[Halbert] Auto-approved {} failed: {}", request.id, e);
+                    store.get(&request.id)?
+                }
+            }
+        }
+        Verdict::Reject => {
+            let reason = format!("Rejected by risk policy {}", outcome.rule_id.as_deref().unwrap_or_default());
+            store.reject(&request.id, &reason, &app.state::<Database>(), &approver)?.into_request()
+        }
+    };
+    println!("[Halbert] Request {} {} by {}", decided.id, decided.status, decider);
+    announce_decided(app, &decided);
+    Ok(decided)
+}
+
+fn announce_decided(app: &AppHandle, request: &ApprovalRequest) {
+    let _ = app.emit("approvals://decided", request);
+    crate::notifications::approval_decided(app, request);
+    if request.execution_status.as_deref() == Some("failed") {
+        crate::notifications::approval_execution_failed(app, request);
+    }
+}
+
+// Wired to job updates, so a request's execution follows its job
+pub fn job_updated(app: &AppHandle, job: &Job) {
+    let Some(store) = app.try_state::<ApprovalStore>() else {
+        return;
+    };
+    let Some(request) = store.follow_execution(job) else {
+        return;
+    };
+    let finished = request.execution_status.as_deref().is_some_and(is_finished);
+    let mut changed = vec![request];
+    if finished {
+        changed.extend(store.release_waiting(&app.state::<JobManager>(), &app.state::<Database>()));
+    }
+    for request in &changed {
+        let _ = app.emit("approvals://updated", request);
+        if request.execution_status.as_deref() == Some("failed") {
+            crate::notifications::approval_execution_failed(app, request);
+        }
+    }
+}
+
+// The request, if `approver` already cast this vote on it
+fn repeated_vote(inner: &StoreInner, request_id: &str, approver: &Approver, vote: &str) -> Option<ApprovalRequest> {
+    inner
+        .requests
+        .iter()
+        .find(|r| r.request.id == request_id)
+        .filter(|r| r.request.votes.iter().any(|v| v.voter() == approver.voter() && v.vote == vote))
+        .map(|r| r.request.clone())
+}
+
+// Keep the request's latest vote; the store has it either way
+fn record_vote(db: &Database, request: &ApprovalRequest) {
+    let Some(vote) = request.votes.last() else {
+        return;
+    };
+    let result = db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO approval_votes (request_id, requested_at, approver, source, vote, at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![request.id, request.requested_at, vote.approver, vote.source, vote.vote, vote.at],
+        )
+        .map(|_| ())
+    });
+    if let Err(e) = result {
+        println!("[Halbert] Failed to store vote on {}: {}", request.id, e);
+    }
+}
+
+// A vote that didn't decide the request, audited on its own
+fn audit_vote(db: &Database, request: &ApprovalRequest) {
+    let Some(vote) = request.votes.last() else {
+        return;
+    };
+    let detail = serde_json::json!({
+        "vote": vote,
+        "approvals": approvals(request),
+        "approvers_required": request.approvers_required,
+    });
+    if let Err(e) = audit::record(db, &vote.approver, "approval.vote", &request.id, &detail) {
+        println!("[Halbert] Failed to audit vote on {}: {}", request.id, e);
+    }
+}
+
+fn find_pending<'a>(inner: &'a mut StoreInner, request_id: &str) -> CommandResult<&'a mut StoredApproval> {
+    let stored = inner
+        .requests
+        .iter_mut()
+        .find(|r| r.request.id == request_id)
+        .ok_or_else(|| CommandError::NotFound(format!("approval request {}", request_id)))?;
+    if stored.request.status != "pending" {
+        return Err(CommandError::Conflict(format!(
+            "request {} is already {}",
+            request_id, stored.request.status
+        )));
+    }
+    Ok(stored)
+}
+
+fn mock_requests() -> Vec<ApprovalRequest> {
+    // Mock approval requests for UI development
+    vec![
+        ApprovalRequest {
+            host_id: hosts::LOCAL_HOST_ID.to_string(),
+            id: "req_001".to_string(),
+            task: "System Update".to_string(),
+            action: "Update 47 packages including kernel 6.14.0-37".to_string(),
+            reasoning: "Security patches available. 12 critical CVEs fixed in this update.".to_string(),
+            confidence: 0.92,
+            risk_level: "medium".to_string(),
+            affected_resources: vec![
+                "linux-image-6.14.0-37-generic".to_string(),
+                "systemd".to_string(),
+                "openssh-server".to_string(),
+            ],
+            requested_at: chrono::Utc::now().to_rfc3339(),
+            status: "pending".to_string(),
+            decided_at: None,
+            decision_note: None,
+            task_type: Some("system_update".to_string()),
+            task_params: serde_json::json!({}),
+            dry_run_at: None,
+            dry_run_summary: None,
+            original_risk_level: None,
+            policy_id: None,
+            approvers_required: 1,
+            votes: Vec::new(),
+            execution: None,
+            execution_status: None,
+            execution_job_id: None,
+            execution_error: None,
+            execution_snapshots: Vec::new(),
+            group_id: None,
+            depends_on: Vec::new(),
+            requested_at_display: None,
+            decided_at_display: None,
+        },
+        ApprovalRequest {
+            host_id: hosts::LOCAL_HOST_ID.to_string(),
+            id: "req_002".to_string(),
+            task: "Disk Cleanup".to_string(),
+            action: "Delete 15.2 GB of old logs and cache files".to_string(),
+            reasoning: "Root partition at 25.2% - cleaning old logs older than 90 days.".to_string(),
+            confidence: 0.88,
+            risk_level: "low".to_string(),
+            affected_resources: vec![
+                "/var/log/*.gz".to_string(),
+                "~/.cache/thumbnails/*".to_string(),
+            ],
+            requested_at: chrono::Utc::now().to_rfc3339(),
+            status: "pending".to_string(),
+            decided_at: None,
+            decision_note: None,
+            task_type: Some("disk_cleanup".to_string()),
+            task_params: serde_json::json!({
+                "patterns": ["/var/log/*.gz", "~/.cache/thumbnails/*"],
+                "older_than_days": 90,
+            }),
+            dry_run_at: None,
+            dry_run_summary: None,
+            original_risk_level: None,
+            policy_id: None,
+            approvers_required: 1,
+            votes: Vec::new(),
+            execution: None,
+            execution_status: None,
+            execution_job_id: None,
+            execution_error: None,
+            execution_snapshots: Vec::new(),
+            group_id: None,
+            depends_on: Vec::new(),
+            requested_at_display: None,
+            decided_at_display: None,
+        },
+    ]
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_pending_approvals(
+    store: State<'_, ApprovalStore>,
+    settings: State<'_, SettingsStore>,
+) -> CommandResult<Vec<PendingApproval>> {
+    let settings = settings.get();
+    let requests = pending_requests(&store, &settings)?;
+    Ok(group_pending(timestamps::localized(&settings, requests)))
+}
+
+// The active host's pending requests, ungrouped
+pub fn pending_requests(store: &ApprovalStore, settings: &Settings) -> CommandResult<Vec<ApprovalRequest>> {
+    match hosts::active_host(settings) {
+        ActiveHost::Local => Ok(store.pending()),
+        ActiveHost::Remote(host) => hosts::fetch_approvals(&host),
+    }
+}
+
+// Decided requests with how their executions went, newest first
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_approval_history(
+    store: State<'_, ApprovalStore>,
+    settings: State<'_, SettingsStore>,
+    limit: Option<usize>,
+) -> Vec<ApprovalRequest> {
+    timestamps::localized(&settings.get(), store.history(limit.unwrap_or(100)))
+}
+
+// Expanding globs and querying packages can be slow, so this runs on a
+// blocking thread, off both the main thread and the async runtime's, and
+// returns partial results at the expansion deadline. A cancelled
+// expansion's partial impact comes back in the Cancelled error and isn't
+// remembered as viewed.
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_approval_detail(
+    app: AppHandle,
+    request_id: String,
+    operation_id: Option<String>,
+) -> CommandResult<ApprovalDetail> {
+    tokio::task::spawn_blocking(move || {
+        approval_detail(
+            &app.state(),
+            &app.state(),
+            &app.state(),
+            &app.state::<SettingsStore>().get(),
+            &app.state(),
+            &request_id,
+            operation_id,
+        )
+    })
+    .await
+    .map_err(|e| CommandError::Internal(format!("approval detail failed: {}", e)))?
+}
+
+pub fn approval_detail(
+    store: &ApprovalStore,
+    jobs: &JobManager,
+    db: &Database,
+    settings: &Settings,
+    operations: &Operations,
+    request_id: &str,
+    operation_id: Option<String>,
+) -> CommandResult<ApprovalDetail> {
+    let request = timestamps::localized(settings, store.get(request_id)?);
+    let operation = operations.start(operation_id)?;
+    let impact = impact::expand(&request.affected_resources, operation.token());
+    operation
+        .token()
+        .check("Expanding the request's resources", || serde_json::to_value(&impact).ok())?;
+    store.remember_impact(request_id, impact.clone());
+    let dry_run = store.dry_run(request_id);
+    let execution_job = request
+        .execution_job_id
+        .as_deref()
+        .and_then(|id| jobs.get(id))
+        .map(|job| timestamps::localized(settings, job));
+    let snapshot_before = store
+        .action_snapshots(request_id)
+        .or_else(|| {
+            let execution = request.execution.as_ref()?;
+            let resolved = job_templates::resolve(db, &execution.template, &execution.params).ok()?;
+            Some(resolved.4.snapshot_before)
+        })
+        .unwrap_or_default();
+    Ok(ApprovalDetail {
+        request,
+        impact,
+        dry_run,
+        execution_job,
+        snapshot_before,
+    })
+}
+
+// Run the task's dry-run variant as a job. The captured output is attached
+// to the request when the job ends and shows up in get_approval_detail.
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn dry_run_approval(
+    app: AppHandle,
+    store: State<'_, ApprovalStore>,
+    jobs: State<'_, JobManager>,
+    request_id: String,
+) -> CommandResult<Job> {
+    let request = store.get(&request_id)?;
+    let (template, dry_run) = request
+        .task_type
+        .as_deref()
+        .and_then(job_templates::builtin)
+        .and_then(|t| t.dry_run.map(|dry_run| (t, dry_run)))
+        .ok_or_else(|| {
+            CommandError::NotSupported(format!(
+                "'{}' has no dry-run capability",
+                request.task_type.as_deref().unwrap_or(&request.task)
+            ))
+        })?;
+    let commands = dry_run(&request.task_params)?;
+    let planned = (template.command)(&request.task_params)?;
+
+    store.begin_dry_run(&request_id)?;
+    let abandoned = {
+        let (app, request_id) = (app.clone(), request_id.clone());
+        DryRunGuard::new(move || app.state::<ApprovalStore>().abandon_dry_run(&request_id))
+    };
+    let job = jobs.spawn(&format!("Dry run: {}", request.task), "dry_run", move |handle| {
+        handle.log(format!("{}: {}", template.name, template.description));
+        for command in &planned {
+            handle.log(format!("Approving would run: {}", command.display()));
+        }
+
+        let mut exit_codes = Vec::new();
+        let mut output = Vec::new();
+        let mut failure = None;
+        for command in &commands {
+            let args: Vec<&str> = command.args.iter().map(String::as_str).collect();
+            match handle.run_command_captured(&command.program, &args) {
+                Ok((status, lines)) => {
+                    exit_codes.push(status.code());
+                    output.extend(lines);
+                }
+                Err(e) => {
+                    failure = Some(e);
+                    break;
+                }
+            }
+        }
+
+        let summary = match &failure {
+            Some(e) => format!("Dry run failed: {}", e),
+            None => (template.summarize_dry_run)(&output),
+        };
+        handle.log(summary.clone());
+        let result = DryRunResult {
+            job_id: handle.id().to_string(),
+            commands: commands.iter().map(|c| c.display()).collect(),
+            exit_codes,
+            output,
+            summary,
+            finished_at: chrono::Utc::now().to_rfc3339(),
+        };
+        abandoned.disarm();
+        if let Ok(request) = app.state::<ApprovalStore>().finish_dry_run(&request_id, result) {
+            let _ = app.emit("approvals://updated", &request);
+        }
+        failure.map_or(Ok(()), Err)
+    });
+    Ok(job)
+}
+
+// Owned by a dry run's job: dropped before the run records its result
+// (the work panicked, or the job was held and then discarded), it clears
+// the request's dry-run-in-progress flag
+struct DryRunGuard<F: FnOnce()> {
+    on_abandon: Option<F>,
+}
+
+impl<F: FnOnce()> DryRunGuard<F> {
+    fn new(on_abandon: F) -> Self {
+        DryRunGuard {
+            on_abandon: Some(on_abandon),
+        }
+    }
+
+    fn disarm(mut self) {
+        self.on_abandon = None;
+    }
+}
+
+impl<F: FnOnce()> Drop for DryRunGuard<F> {
+    fn drop(&mut self) {
+        if let Some(on_abandon) = self.on_abandon.take() {
+            on_abandon();
+        }
+    }
+}
+
+// The window votes as this machine; approve_as is for a vote another
+// channel has authenticated
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn approve_request(
+    app: AppHandle,
+    store: State<'_, ApprovalStore>,
+    jobs: State<'_, JobManager>,
+    db: State<'_, Database>,
+    settings: State<'_, SettingsStore>,
+    request_id: String,
+) -> CommandResult<String> {
+    let settings = settings.get();
+    approve_as(&app, &store, &jobs, &db, &settings, &request_id, &Approver::local(&settings))
+}
+
+pub fn approve_as(
+    app: &AppHandle,
+    store: &ApprovalStore,
+    jobs: &JobManager,
+    db: &Database,
+    settings: &Settings,
+    request_id: &str,
+    approver: &Approver,
+) -> CommandResult<String> {
+    if let ActiveHost::Remote(host) = hosts::active_host(settings) {
+        return hosts::decide_approval(&host, request_id, true, None);
+    }
+    match store.approve(request_id, jobs, db, approver)? {
+        Ballot::Decided(request) => {
+            println!("Approved request: {}", request_id);
+            announce_decided(app, &request);
+            Ok(format!("Request {} approved", request_id))
+        }
+        Ballot::Counted(request) => {
+            println!(
+                "[Halbert] {} voted to approve {} ({} of {})",
+                approver.identity,
+                request_id,
+                approvals(&request),
+                request.approvers_required
+            );
+            let message = format!(
+                "Request {} has {} of {} approvals",
+                request_id,
+                approvals(&request),
+                request.approvers_required
+            );
+            let vote = request.votes.last().cloned();
+            if let Some(vote) = vote {
+                let _ = app.emit("approvals://vote", VoteEvent { request, vote });
+            }
+            Ok(message)
+        }
+        Ballot::Repeated(_) => Ok(format!("{} already approved request {}", approver.identity, request_id)),
+    }
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn reject_request(
+    app: AppHandle,
+    store: State<'_, ApprovalStore>,
+    db: State<'_, Database>,
+    settings: State<'_, SettingsStore>,
+    request_id: String,
+    reason: String,
+) -> CommandResult<String> {
+    let settings = settings.get();
+    reject_as(&app, &store, &db, &settings, &request_id, &reason, &Approver::local(&settings))
+}
+
+pub fn reject_as(
+    app: &AppHandle,
+    store: &ApprovalStore,
+    db: &Database,
+    settings: &Settings,
+    request_id: &str,
+    reason: &str,
+    approver: &Approver,
+) -> CommandResult<String> {
+    if let ActiveHost::Remote(host) = hosts::active_host(settings) {
+        return hosts::decide_approval(&host, request_id, false, Some(reason));
+    }
+    if let Ballot::Decided(request) = store.reject(request_id, reason, db, approver)? {
+        println!("Rejected request {}: {}", request_id, reason);
+        announce_decided(app, &request);
+        for dependent in store.reject_dependents(request_id, db, &approver.identity) {
+            println!("[Halbert] Rejected {} along with {}", dependent.id, request_id);
+            announce_decided(app, &dependent);
+        }
+    }
+    Ok(format!("Request {} rejected", request_id))
+}
+
+// Approves every pending member of the group, each after the requests it
+// depends on. Nothing is approved unless this vote decides all of them;
+// an action that fails stops the ones after it, which stay pending.
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn approve_group(
+    app: AppHandle,
+    store: State<'_, ApprovalStore>,
+    jobs: State<'_, JobManager>,
+    db: State<'_, Database>,
+    settings: State<'_, SettingsStore>,
+    group_id: String,
+) -> CommandResult<Vec<ApprovalRequest>> {
+    let settings = settings.get();
+    approve_group_as(&app, &store, &jobs, &db, &settings, &group_id, &Approver::local(&settings))
+}
+
+pub fn approve_group_as(
+    app: &AppHandle,
+    store: &ApprovalStore,
+    jobs: &JobManager,
+    db: &Database,
+    settings: &Settings,
+    group_id: &str,
+    approver: &Approver,
+) -> CommandResult<Vec<ApprovalRequest>> {
+    if let ActiveHost::Remote(host) = hosts::active_host(settings) {
+        return hosts::approve_group(&host, group_id);
+    }
+    let order = store.group_plan(group_id, approver)?;
+    let mut approved = Vec::new();
+    for request_id in &order {
+        let request = store.approve(request_id, jobs, db, approver)?.into_request();
+        announce_decided(app, &request);
+        approved.push(request);
+    }
+    println!("[Halbert] Approved group {} ({})", group_id, order.join(", "));
+    Ok(approved)
+}
+
+// --- Groups and dependencies ---
+//
+// `depends_on` can only name requests that already exist, and a new
+// request's dependencies are checked for a cycle before it's stored, so
+// the graph stays acyclic. A dependency blocks approval until it's
+// "approved"; one that was rejected or whose action failed keeps blocking.
+
+// Grouped requests are listed under their group, in dependency order
+#[derive(Serialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
+pub enum PendingApproval {
+    Request(ApprovalRequest),
+    Group(ApprovalGroup),
+}
+
+#[derive(Serialize, Clone)]
+pub struct ApprovalGroup {
+    pub group_id: String,
+    // The riskiest member's
+    pub risk_level: String,
+    pub requested_at: String,
+    pub members: Vec<ApprovalRequest>,
+}
+
+pub fn group_pending(requests: Vec<ApprovalRequest>) -> Vec<PendingApproval> {
+    let mut list = Vec::new();
+    let mut groups: BTreeMap<String, Vec<ApprovalRequest>> = BTreeMap::new();
+    for request in requests {
+        match request.group_id.clone() {
+            Some(group_id) => {
+                if !groups.contains_key(&group_id) {
+                    list.push(PendingApproval::Group(ApprovalGroup {
+                        group_id: group_id.clone(),
+                        risk_level: String::new(),
+                        requested_at: request.requested_at.clone(),
+                        members: Vec::new(),
+                    }));
+                }
+                groups.entry(group_id).or_default().push(request);
+            }
+            None => list.push(PendingApproval::Request(request)),
+        }
+    }
+    for entry in &mut list {
+        let PendingApproval::Group(group) = entry else {
+            continue;
+        };
+        let mut members = groups.remove(&group.group_id).unwrap_or_default();
+        if let Ok(order) = dependency_order(&members) {
+            members.sort_by_key(|m| order.iter().position(|id| *id == m.id));
+        }
+        let riskiest = members.iter().filter_map(|m| RiskLevel::parse(&m.risk_level)).max();
+        group.risk_level = match riskiest {
+            Some(risk) => risk.as_str().to_string(),
+            None => members.first().map(|m| m.risk_level.clone()).unwrap_or_default(),
+        };
+        group.members = members;
+    }
+    list
+}
+
+// Ids in an order where each request comes after those it depends on
+// among `members`; Err lists the members left on a cycle
+pub fn dependency_order(members: &[ApprovalRequest]) -> Result<Vec<String>, Vec<String>> {
+    let ids: HashSet<&str> = members.iter().map(|m| m.id.as_str()).collect();
+    let mut order: Vec<String> = Vec::new();
+    let mut left: Vec<&ApprovalRequest> = members.iter().collect();
+    while !left.is_empty() {
+        let (ready, blocked): (Vec<&ApprovalRequest>, Vec<&ApprovalRequest>) = left
+            .into_iter()
+            .partition(|m| m.depends_on.iter().all(|d| !ids.contains(d.as_str()) || order.contains(d)));
+        if ready.is_empty() {
+            return Err(blocked.iter().map(|m| m.id.clone()).collect());
+        }
+        order.extend(ready.iter().map(|m| m.id.clone()));
+        left = blocked;
+    }
+    Ok(order)
+}
+
+// The path back to `id`, if giving it `depends_on` would close a cycle
+pub fn dependency_cycle(edges: &BTreeMap<String, Vec<String>>, id: &str, depends_on: &[String]) -> Option<Vec<String>> {
+    let mut paths: Vec<Vec<String>> = depends_on.iter().map(|d| vec![id.to_string(), d.clone()]).collect();
+    let mut seen = HashSet::new();
+    while let Some(path) = paths.pop() {
+        let Some(last) = path.last().cloned() else {
+            continue;
+        };
+        if last == id {
+            return Some(path);
+        }
+        if !seen.insert(last.clone()) {
+            continue;
+        }
+        for next in edges.get(&last).into_iter().flatten() {
+            let mut longer = path.clone();
+            longer.push(next.clone());
+            paths.push(longer);
+        }
+    }
+    None
+}
+
+// Everything that depends on `id`, directly or through others
+pub fn dependents(edges: &BTreeMap<String, Vec<String>>, id: &str) -> HashSet<String> {
+    let mut found: HashSet<String> = HashSet::new();
+    let mut frontier = vec![id.to_string()];
+    while let Some(current) = frontier.pop() {
+        for (dependent, deps) in edges {
+            if deps.contains(&current) && found.insert(dependent.clone()) {
+                frontier.push(dependent.clone());
+            }
+        }
+    }
+    found
+}
+
+fn dependency_edges(inner: &StoreInner) -> BTreeMap<String, Vec<String>> {
+    inner
+        .requests
+        .iter()
+        .map(|r| (r.request.id.clone(), r.request.depends_on.clone()))
+        .collect()
+}
+
+fn check_dependencies(inner: &StoreInner, id: &str, depends_on: &[String]) -> CommandResult<()> {
+    for dep in depends_on {
+        match inner.requests.iter().find(|r| r.request.id == *dep) {
+            None => return Err(CommandError::NotFound(format!("approval request {} (in depends_on)", dep))),
+            Some(r) if r.request.status == "rejected" => {
+                return Err(CommandError::InvalidInput(format!("{} was already rejected", dep)))
+            }
+            Some(_) => {}
+        }
+    }
+    if let Some(cycle) = dependency_cycle(&dependency_edges(inner), id, depends_on) {
+        return Err(CommandError::InvalidInput(format!(
+            "depends_on would form a cycle: {}",
+            cycle.join(" -> ")
+        )));
+    }
+    Ok(())
+}
+
+// Dependencies of `request_id` that aren't approved yet, with their status
+fn blockers_of(inner: &StoreInner, request_id: &str) -> Vec<(String, String)> {
+    let Some(stored) = inner.requests.iter().find(|r| r.request.id == request_id) else {
+        return Vec::new();
+    };
+    stored
+        .request
+        .depends_on
+        .iter()
+        .filter_map(|dep| {
+            let status = inner
+                .requests
+                .iter()
+                .find(|r| r.request.id == *dep)
+                .map_or("missing", |r| r.request.status.as_str());
+            (status != "approved").then(|| (dep.clone(), status.to_string()))
+        })
+        .collect()
+}
+
+fn describe(blockers: &[(String, String)]) -> String {
+    blockers
+        .iter()
+        .map(|(id, status)| format!("{} ({})", id, status))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+enum Readiness {
+    Ready,
+    // Dependencies whose executions haven't finished
+    Waiting(Vec<String>),
+    // The dependency whose execution failed
+    Failed(String),
+}
+
+// Dependencies without an execution don't hold anything up
+fn execution_readiness(inner: &StoreInner, request_id: &str) -> Readiness {
+    let Some(stored) = inner.requests.iter().find(|r| r.request.id == request_id) else {
+        return Readiness::Ready;
+    };
+    let mut waiting = Vec::new();
+    for dep in &stored.request.depends_on {
+        let Some(dependency) = inner.requests.iter().find(|r| r.request.id == *dep) else {
+            continue;
+        };
+        match dependency.request.execution_status.as_deref() {
+            None | Some("completed") => {}
+            Some("failed") => return Readiness::Failed(dep.clone()),
+            Some(_) => waiting.push(dep.clone()),
+        }
+    }
+    if waiting.is_empty() {
+        Readiness::Ready
+    } else {
+        Readiness::Waiting(waiting)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancellation;
+
+    struct Harness {
+        store: ApprovalStore,
+        jobs: JobManager,
+        db: Database,
+        _dir: tempfile::TempDir,
+    }
+
+    fn harness() -> Harness {
+        let dir = tempfile::tempdir().unwrap();
+        Harness {
+            store: ApprovalStore::empty(),
+            jobs: JobManager::empty(Gate::load(dir.path().join("automation.json"))),
+            db: Database::in_memory(),
+            _dir: dir,
+        }
+    }
+
+    fn new_approval(task: &str, depends_on: &[&str]) -> NewApproval {
+        NewApproval {
+            task: task.to_string(),
+            action: format!("do {}", task),
+            reasoning: "because".to_string(),
+            confidence: 0.9,
+            risk_level: "medium".to_string(),
+            affected_resources: vec!["/etc/example".to_string()],
+            execution: None,
+            group_id: Some("group".to_string()),
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    impl Harness {
+        fn insert(&self, task: &str, depends_on: &[&str], approvers: u32) -> CommandResult<ApprovalRequest> {
+            let new = new_approval(task, depends_on);
+            let outcome = policy::evaluate(&[], &new.subject());
+            self.store.insert(new, None, &outcome, approvers)
+        }
+
+        fn approve(&self, id: &str, approver: &str) -> CommandResult<Ballot> {
+            self.store.approve(id, &self.jobs, &self.db, &as_approver(approver))
+        }
+
+        fn count(&self, sql: &str) -> i64 {
+            self.db.with_conn(|conn| conn.query_row(sql, [], |row| row.get(0))).unwrap()
+        }
+    }
+
+    // Named approvers are remote ones; this machine is a single approver
+    fn as_approver(identity: &str) -> Approver {
+        Approver::remote(identity.to_string())
+    }
+
+    fn this_machine_as(identity: &str) -> Approver {
+        let settings = Settings {
+            approver_identity: Some(identity.to_string()),
+            ..Settings::default()
+        };
+        Approver::local(&settings)
+    }
+
+    #[test]
+    fn one_caller_under_two_names_is_one_approver() {
+        let h = harness();
+        let request = h.insert("task", &[], 2).unwrap();
+        let first = this_machine_as("alice");
+        assert!(matches!(h.store.approve(&request.id, &h.jobs, &h.db, &first).unwrap(), Ballot::Counted(_)));
+        // Renaming the local identity doesn't make a second approver
+        let second = this_machine_as("bob");
+        assert!(matches!(h.store.approve(&request.id, &h.jobs, &h.db, &second).unwrap(), Ballot::Repeated(_)));
+        let stored = h.store.get(&request.id).unwrap();
+        assert_eq!(stored.status, "pending");
+        assert_eq!(approvals(&stored), 1);
+        assert_eq!(h.count("SELECT COUNT(*) FROM approval_votes"), 1);
+        let group = h.insert("grouped", &[], 2).unwrap();
+        h.store.approve(&group.id, &h.jobs, &h.db, &first).unwrap();
+        assert!(matches!(h.store.group_plan("group", &second), Err(CommandError::Conflict(_))));
+    }
+
+    #[test]
+    fn this_machine_and_a_remote_approver_make_two() {
+        let h = harness();
+        let request = h.insert("task", &[], 2).unwrap();
+        h.store.approve(&request.id, &h.jobs, &h.db, &this_machine_as("alice")).unwrap();
+        let Ballot::Decided(decided) = h.approve(&request.id, "remote:token-1").unwrap() else {
+            panic!("a remote approver should make the second vote");
+        };
+        assert_eq!(approvals(&decided), 2);
+    }
+
+    #[test]
+    fn inserted_requests_are_pending_under_fresh_ids() {
+        let h = harness();
+        let first = h.insert("first", &[], 1).unwrap();
+        let second = h.insert("second", &[], 1).unwrap();
+        assert_eq!((first.id.as_str(), second.id.as_str()), ("req_001", "req_002"));
+        assert_eq!(first.status, "pending");
+        assert_eq!(first.risk_level, "medium");
+        assert_eq!(first.original_risk_level.as_deref(), Some("medium"));
+        let pending: Vec<String> = h.store.pending().into_iter().map(|r| r.id).collect();
+        assert_eq!(pending, ["req_001", "req_002"]);
+    }
+
+    #[test]
+    fn one_approval_decides_and_is_recorded() {
+        let h = harness();
+        let request = h.insert("task", &[], 1).unwrap();
+        let Ballot::Decided(decided) = h.approve(&request.id, "alice").unwrap() else {
+            panic!("one approval should decide");
+        };
+        assert_eq!(decided.status, "approved");
+        assert!(decided.decided_at.is_some());
+        assert!(h.store.pending().is_empty());
+        assert_eq!(h.count("SELECT COUNT(*) FROM approval_votes"), 1);
+        assert_eq!(h.count("SELECT COUNT(*) FROM audit_log WHERE action = 'approval.approved'"), 1);
+        // A decided request can't be decided again
+        assert!(matches!(
+            h.store.reject(&request.id, "too late", &h.db, &as_approver("bob")),
+            Err(CommandError::Conflict(_))
+        ));
+    }
+
+    #[test]
+    fn quorum_needs_distinct_approvers() {
+        let h = harness();
+        let request = h.insert("task", &[], 2).unwrap();
+        assert!(matches!(h.approve(&request.id, "alice").unwrap(), Ballot::Counted(_)));
+        assert!(matches!(h.approve(&request.id, "alice").unwrap(), Ballot::Repeated(_)));
+        assert_eq!(h.store.get(&request.id).unwrap().status, "pending");
+        let Ballot::Decided(decided) = h.approve(&request.id, "bob").unwrap() else {
+            panic!("the second approver should decide");
+        };
+        assert_eq!(decided.status, "approved");
+        assert_eq!(approvals(&decided), 2);
+        assert_eq!(h.count("SELECT COUNT(*) FROM approval_votes"), 2);
+        assert_eq!(h.count("SELECT COUNT(*) FROM audit_log WHERE action = 'approval.vote'"), 1);
+    }
+
+    #[test]
+    fn one_rejection_decides_despite_approvals() {
+        let h = harness();
+        let request = h.insert("task", &[], 2).unwrap();
+        h.approve(&request.id, "alice").unwrap();
+        let rejected = h.store.reject(&request.id, "no", &h.db, &as_approver("bob")).unwrap().into_request();
+        assert_eq!(rejected.status, "rejected");
+        assert_eq!(rejected.decision_note.as_deref(), Some("no"));
+        assert_eq!(h.store.history(10).len(), 1);
+    }
+
+    #[test]
+    fn dependencies_gate_approval_and_rejection_cascades() {
+        let h = harness();
+        let base = h.insert("base", &[], 1).unwrap();
+        let middle = h.insert("middle", &[&base.id], 1).unwrap();
+        let top = h.insert("top", &[&middle.id], 1).unwrap();
+        assert!(matches!(
+            h.approve(&middle.id, "alice"),
+            Err(CommandError::DependenciesPending(_))
+        ));
+        assert!(matches!(h.insert("orphan", &["req_999"], 1), Err(CommandError::NotFound(_))));
+
+        h.store.reject(&base.id, "no", &h.db, &as_approver("alice")).unwrap();
+        let cascaded: Vec<String> =
+            h.store.reject_dependents(&base.id, &h.db, "alice").into_iter().map(|r| r.id).collect();
+        assert_eq!(cascaded, [middle.id.clone(), top.id.clone()]);
+        assert!(h.store.pending().is_empty());
+        assert!(matches!(h.insert("late", &[&base.id], 1), Err(CommandError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn groups_list_together_in_dependency_order() {
+        let h = harness();
+        let first = h.insert("first", &[], 1).unwrap();
+        let second = h.insert("second", &[&first.id], 1).unwrap();
+        let plan = h.store.group_plan("group", &as_approver("alice")).unwrap();
+        assert_eq!(plan, [first.id.clone(), second.id.clone()]);
+        let listed = group_pending(h.store.pending());
+        assert_eq!(listed.len(), 1);
+        let PendingApproval::Group(group) = &listed[0] else {
+            panic!("expected a group");
+        };
+        assert_eq!(group.members.len(), 2);
+        assert!(matches!(h.store.group_plan("missing", &as_approver("alice")), Err(CommandError::NotFound(_))));
+    }
+
+    // A dry run's job as dry_run_approval builds it, minus the commands
+    fn dry_run_job(
+        store: &std::sync::Arc<ApprovalStore>,
+        jobs: &JobManager,
+        request_id: &str,
+        work: fn() -> Result<(), String>,
+    ) -> Job {
+        store.begin_dry_run(request_id).unwrap();
+        let abandoned = {
+            let (store, request_id) = (store.clone(), request_id.to_string());
+            DryRunGuard::new(move || store.abandon_dry_run(&request_id))
+        };
+        jobs.spawn("Dry run", "dry_run", move |_| {
+            work()?;
+            abandoned.disarm();
+            Ok(())
+        })
+    }
+
+    fn dry_run_running(store: &ApprovalStore, request_id: &str) -> bool {
+        let inner = store.inner.lock().unwrap();
+        inner.requests.iter().any(|r| r.request.id == request_id && r.dry_run_running)
+    }
+
+    #[test]
+    fn a_panicking_dry_run_clears_its_flag() {
+        let h = harness();
+        let store = std::sync::Arc::new(ApprovalStore::empty());
+        let new = new_approval("task", &[]);
+        let outcome = policy::evaluate(&[], &new.subject());
+        let request = store.insert(new, None, &outcome, 1).unwrap();
+        let job = dry_run_job(&store, &h.jobs, &request.id, || panic!("dry run blew up"));
+        assert_eq!(h.jobs.wait_finished(&job.id).status, "failed");
+        assert!(!dry_run_running(&store, &request.id));
+        // So another one can start
+        store.begin_dry_run(&request.id).unwrap();
+    }
+
+    #[test]
+    fn a_finished_dry_run_leaves_the_flag_to_its_result() {
+        let h = harness();
+        let store = std::sync::Arc::new(ApprovalStore::empty());
+        let new = new_approval("task", &[]);
+        let outcome = policy::evaluate(&[], &new.subject());
+        let request = store.insert(new, None, &outcome, 1).unwrap();
+        let job = dry_run_job(&store, &h.jobs, &request.id, || Ok(()));
+        assert_eq!(h.jobs.wait_finished(&job.id).status, "completed");
+        assert!(dry_run_running(&store, &request.id));
+        assert!(matches!(store.begin_dry_run(&request.id), Err(CommandError::Conflict(_))));
+    }
+
+    #[test]
+    fn a_discarded_dry_run_clears_its_flag() {
+        let dir = tempfile::tempdir().unwrap();
+        let jobs = JobManager::empty(Gate::paused(dir.path().join("automation.json")));
+        let store = std::sync::Arc::new(ApprovalStore::empty());
+        let new = new_approval("task", &[]);
+        let outcome = policy::evaluate(&[], &new.subject());
+        let request = store.insert(new, None, &outcome, 1).unwrap();
+        let job = dry_run_job(&store, &jobs, &request.id, || Ok(()));
+        assert_eq!(job.status, "queued");
+        assert!(dry_run_running(&store, &request.id));
+
+        assert_eq!(jobs.discard_held("discarded on resume").len(), 1);
+        assert!(!dry_run_running(&store, &request.id));
+        store.begin_dry_run(&request.id).unwrap();
+    }
+
+    fn store_template(db: &Database, args: &[&str]) {
+        let template = job_templates::definition(
+            "rotate-logs".to_string(),
+            "logrotate".to_string(),
+            args.iter().map(|a| a.to_string()).collect(),
+            None,
+            None,
+            None,
+            RiskLevel::Medium,
+            None,
+            Default::default(),
+            SnapshotPolicy::default(),
+        )
+        .unwrap();
+        job_templates::import_templates(db, &[template], &[]).unwrap();
+    }
+
+    // A request to run rotate-logs as it resolves now
+    fn insert_execution(h: &Harness, depends_on: &[&str]) -> ApprovalRequest {
+        let (commands, ..) = job_templates::resolve(&h.db, "rotate-logs", &serde_json::Value::Null).unwrap();
+        let new = NewApproval {
+            execution: Some(Execution {
+                template: "rotate-logs".to_string(),
+                params: serde_json::Value::Null,
+                commands: commands.iter().map(CommandLine::display).collect(),
+            }),
+            group_id: None,
+            ..new_approval("rotate", depends_on)
+        };
+        let outcome = policy::evaluate(&[], &new.subject());
+        h.store.insert(new, None, &outcome, 1).unwrap()
+    }
+
+    fn decided(ballot: CommandResult<Ballot>) -> ApprovalRequest {
+        match ballot.unwrap() {
+            Ballot::Decided(request) => request,
+            _ => panic!("the vote didn't decide the request"),
+        }
+    }
+
+    #[test]
+    fn a_template_deleted_before_approval_fails_the_execution() {
+        let h = harness();
+        store_template(&h.db, &["/etc/logrotate.conf"]);
+        let request = insert_execution(&h, &[]);
+        job_templates::import_templates(&h.db, &[], &["rotate-logs".to_string()]).unwrap();
+
+        let request = decided(h.approve(&request.id, "alice"));
+        assert_eq!(request.status, "approved");
+        assert_eq!(request.execution_status.as_deref(), Some("failed"));
+        assert!(request.execution_error.unwrap().contains("no longer exists"));
+        assert!(request.execution_job_id.is_none());
+        assert!(h.jobs.all().is_empty());
+    }
+
+    #[test]
+    fn a_template_changed_before_approval_fails_the_execution() {
+        let h = harness();
+        store_template(&h.db, &["/etc/logrotate.conf"]);
+        let request = insert_execution(&h, &[]);
+        store_template(&h.db, &["--force", "/etc/logrotate.conf"]);
+
+        let request = decided(h.approve(&request.id, "alice"));
+        assert_eq!(request.execution_status.as_deref(), Some("failed"));
+        let error = request.execution_error.unwrap();
+        assert!(error.contains("changed after the request was made") && error.contains("--force"), "{}", error);
+        assert!(h.jobs.all().is_empty());
+    }
+
+    #[test]
+    fn a_template_deleted_while_waiting_on_a_dependency_fails_when_released() {
+        let h = harness();
+        store_template(&h.db, &["/etc/logrotate.conf"]);
+        let first = h.insert("first", &[], 1).unwrap();
+        decided(h.approve(&first.id, "alice"));
+        set_execution_status(&h.store, &first.id, "running");
+        let second = insert_execution(&h, &[&first.id]);
+        let second = decided(h.approve(&second.id, "alice"));
+        assert_eq!(second.execution_status.as_deref(), Some("waiting"));
+
+        job_templates::import_templates(&h.db, &[], &["rotate-logs".to_string()]).unwrap();
+        set_execution_status(&h.store, &first.id, "completed");
+        let released = h.store.release_waiting(&h.jobs, &h.db);
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].execution_status.as_deref(), Some("failed"));
+        assert!(released[0].execution_error.as_deref().unwrap().contains("no longer exists"));
+        assert!(h.jobs.all().is_empty());
+    }
+
+    fn set_execution_status(store: &ApprovalStore, request_id: &str, status: &str) {
+        let mut inner = store.inner.lock().unwrap();
+        let stored = inner.requests.iter_mut().find(|r| r.request.id == request_id).unwrap();
+        stored.request.execution_status = Some(status.to_string());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_cancelled_detail_stops_with_the_impact_so_far() {
+        let h = harness();
+        let dir = tempfile::tempdir().unwrap();
+        let endless = cancellation::endless_tree(dir.path());
+        let new = NewApproval {
+            affected_resources: vec![format!("{}/**/never-matches", endless.display())],
+            ..new_approval("sweep", &[])
+        };
+        let outcome = policy::evaluate(&[], &new.subject());
+        let request = h.store.insert(new, None, &outcome, 1).unwrap();
+
+        let operations = Operations::default();
+        let settings = Settings::default();
+        let (detail, overran) = cancellation::cancel_during(&operations, "detail", || {
+            approval_detail(&h.store, &h.jobs, &h.db, &settings, &operations, &request.id, Some("detail".to_string()))
+        });
+        assert!(overran < cancellation::NOTICED_WITHIN, "{:?}", overran);
+        let Err(CommandError::Cancelled { partial: Some(impact), .. }) = detail else {
+            panic!("the detail wasn't cancelled");
+        };
+        assert_eq!(impact["incomplete"], true);
+        // Not remembered as viewed
+        assert!(h.store.inner.lock().unwrap().requests.iter().all(|r| r.viewed_impact.is_none()));
+    }
+
+    #[test]
+    fn a_group_needing_more_votes_is_refused_whole() {
+        let h = harness();
+        h.insert("first", &[], 1).unwrap();
+        h.insert("second", &[], 2).unwrap();
+        assert!(matches!(h.store.group_plan("group", &as_approver("alice")), Err(CommandError::Conflict(_))));
+    }
+}
@@ -0,0 +1,319 @@
+// Files jobs leave behind.
+//
+// A job with an artifacts directory (see job_templates) has it scanned
+// when its work returns: regular files modified since the job started are
+// recorded with their size, a MIME type guessed from the name and a
+// SHA-256, and flagged partial when the job failed. Symlinks and hidden
+// entries are skipped. The directory, and every artifact opened later,
+// must pass sandbox::JOB_ARTIFACT. Storage maintenance holds artifacts to
+// the per-job and total quotas in the retention settings, deleting the
+// oldest first; records whose file is gone are dropped then too.
+use rusqlite::params;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+#[cfg(feature = "gui")]
+use tauri_plugin_opener::OpenerExt;
+
+use crate::app::{AppHandle, Manager, State};
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::jobs::{Job, JobManager};
+use crate::sandbox;
+use crate::settings::SettingsStore;
+use crate::storage::RetentionPolicy;
+
+// More than this in one directory is someone else's folder, not output
+const MAX_FILES: usize = 500;
+const MIB: u64 = 1024 * 1024;
+
+#[derive(Serialize, Clone)]
+pub struct Artifact {
+    pub id: i64,
+    pub job_id: String,
+    pub path: String,
+    pub size_bytes: u64,
+    pub mime: String,
+    pub sha256: String,
+    // Left by a job that didn't complete
+    pub partial: bool,
+    pub created_at: String,
+}
+
+pub fn guess_mime(path: &Path) -> &'static str {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    match name.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("gz" | "tgz") => "application/gzip",
+        Some("tar") => "application/x-tar",
+        Some("zip") => "application/zip",
+        Some("zst") => "application/zstd",
+        Some("json") => "application/json",
+        Some("pdf") => "application/pdf",
+        Some("html" | "htm") => "text/html",
+        Some("csv") => "text/csv",
+        Some("md") => "text/markdown",
+        Some("txt" | "log") => "text/plain",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        _ => "application/octet-stream",
+    }
+}
+
+fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+// Regular files under `dir` modified at or after `since`, in name order
+fn collect(dir: &Path, since: SystemTime, found: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.flatten().collect();
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        if found.len() >= MAX_FILES {
+            return;
+        }
+        // file_type() doesn't follow symlinks
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if entry.file_name().to_string_lossy().starts_with('.') || file_type.is_symlink() {
+            continue;
+        }
+        if file_type.is_dir() {
+            collect(&entry.path(), since, found);
+        } else if file_type.is_file() && entry.metadata().and_then(|m| m.modified()).is_ok_and(|at| at >= since) {
+            found.push(entry.path());
+        }
+    }
+}
+
+// Wired as the JobManager's on_artifacts callback
+pub fn capture(app: &AppHandle, job: &Job, partial: bool) -> Result<usize, String> {
+    let Some(dir) = job.artifacts_dir.as_deref() else {
+        return Ok(0);
+    };
+    let settings = app.state::<SettingsStore>().get();
+    let dir = sandbox::JOB_ARTIFACT.validate(&settings, Path::new(dir)).map_err(|e| e.to_string())?;
+    let since = chrono::DateTime::parse_from_rfc3339(&job.started_at)
+        .map(SystemTime::from)
+        .map_err(|e| format!("bad start time {}: {}", job.started_at, e))?;
+    let mut found = Vec::new();
+    collect(dir.as_path(), since, &mut found);
+
+    let db = app.state::<Database>();
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut count = 0;
+    for path in found {
+        // Gone or unreadable since the scan
+        let (Ok(meta), Ok(sha256)) = (std::fs::metadata(&path), sha256_file(&path)) else {
+            continue;
+        };
+        db.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO job_artifacts (job_id, job_started_at, path, size_bytes, mime, sha256, partial, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    job.id,
+                    job.started_at,
+                    path.display().to_string(),
+                    meta.len() as i64,
+                    guess_mime(&path),
+                    sha256,
+                    partial,
+                    now
+                ],
+            )
+        })
+        .map_err(|e| e.to_string())?;
+        count += 1;
+    }
+    if count > 0 {
+        println!("[Halbert] Recorded {} artifact(s) of {}", count, job.id);
+    }
+    Ok(count)
+}
+
+fn read_artifact(r: &rusqlite::Row) -> rusqlite::Result<Artifact> {
+    Ok(Artifact {
+        id: r.get(0)?,
+        job_id: r.get(1)?,
+        path: r.get(2)?,
+        size_bytes: r.get::<_, i64>(3)? as u64,
+        mime: r.get(4)?,
+        sha256: r.get(5)?,
+        partial: r.get(6)?,
+        created_at: r.get(7)?,
+    })
+}
+
+// Only jobs still in memory can be looked up; their id alone isn't unique
+// across restarts
+fn job_artifacts(db: &Database, jobs: &JobManager, job_id: &str) -> CommandResult<Vec<Artifact>> {
+    let job = jobs
+        .get(job_id)
+        .ok_or_else(|| CommandError::NotFound(format!("job {}", job_id)))?;
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, job_id, path, size_bytes, mime, sha256, partial, created_at FROM job_artifacts
+             WHERE job_id = ?1 AND job_started_at = ?2 ORDER BY path",
+        )?;
+        let rows = stmt.query_map(params![job.id, job.started_at], read_artifact)?;
+        rows.collect()
+    })
+}
+
+// Only regular files are removed, never what a symlink put in their place
+fn remove_file(path: &Path) -> std::io::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_file() => std::fs::remove_file(path),
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+pub struct QuotaRow {
+    pub id: i64,
+    // Job id and start time
+    pub job: String,
+    pub size_bytes: u64,
+}
+
+// Ids to delete to bring every job under `per_job` and all of them under
+// `total`, taking the oldest first; `rows` come oldest first and a limit
+// of 0 is no limit
+pub fn over_quota(rows: &[QuotaRow], per_job: u64, total: u64) -> Vec<i64> {
+    let mut doomed = BTreeSet::new();
+    if per_job > 0 {
+        let mut used: BTreeMap<&str, u64> = BTreeMap::new();
+        for row in rows {
+            *used.entry(&row.job).or_default() += row.size_bytes;
+        }
+        for row in rows {
+            let job_used = used.entry(&row.job).or_default();
+            if *job_used > per_job {
+                *job_used -= row.size_bytes;
+                doomed.insert(row.id);
+            }
+        }
+    }
+    if total > 0 {
+        let mut used: u64 = rows.iter().filter(|r| !doomed.contains(&r.id)).map(|r| r.size_bytes).sum();
+        for row in rows {
+            if used <= total {
+                break;
+            }
+            if doomed.insert(row.id) {
+                used -= row.size_bytes;
+            }
+        }
+    }
+    doomed.into_iter().collect()
+}
+
+fn delete_rows(db: &Database, ids: &[i64]) -> CommandResult<usize> {
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let mut deleted = 0;
+        for id in ids {
+            deleted += tx.execute("DELETE FROM job_artifacts WHERE id = ?1", params![id])?;
+        }
+        tx.commit()?;
+        Ok(deleted)
+    })
+}
+
+// Called by storage maintenance; returns how many records went
+pub fn enforce_quotas(db: &Database, policy: &RetentionPolicy) -> CommandResult<usize> {
+    let rows: Vec<(i64, String, String, i64)> = db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, job_id || ' ' || job_started_at, path, size_bytes FROM job_artifacts ORDER BY created_at, id",
+        )?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)))?;
+        rows.collect()
+    })?;
+    let (present, gone): (Vec<_>, Vec<_>) = rows.into_iter().partition(|(_, _, path, _)| Path::new(path).is_file());
+    let mut ids: Vec<i64> = gone.iter().map(|(id, ..)| *id).collect();
+
+    let quota_rows: Vec<QuotaRow> = present
+        .iter()
+        .map(|(id, job, _, size)| QuotaRow {
+            id: *id,
+            job: job.clone(),
+            size_bytes: *size as u64,
+        })
+        .collect();
+    let doomed = over_quota(&quota_rows, policy.artifacts_job_mib * MIB, policy.artifacts_total_mib * MIB);
+    for (id, _, path, _) in present.iter().filter(|(id, ..)| doomed.contains(id)) {
+        match remove_file(Path::new(path)) {
+            Ok(()) => ids.push(*id),
+            Err(e) => println!("[Halbert] Can't delete artifact {}: {}", path, e),
+        }
+    }
+    delete_rows(db, &ids)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_job_artifacts(
+    db: State<'_, Database>,
+    jobs: State<'_, JobManager>,
+    job_id: String,
+) -> CommandResult<Vec<Artifact>> {
+    job_artifacts(&db, &jobs, &job_id)
+}
+
+// Opens it on this desktop, so there's nothing to do without one
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn open_job_artifact(
+    app: AppHandle,
+    db: State<'_, Database>,
+    jobs: State<'_, JobManager>,
+    settings: State<'_, SettingsStore>,
+    job_id: String,
+    artifact_id: i64,
+) -> CommandResult<()> {
+    let artifact = job_artifacts(&db, &jobs, &job_id)?
+        .into_iter()
+        .find(|a| a.id == artifact_id)
+        .ok_or_else(|| CommandError::NotFound(format!("artifact {} of job {}", artifact_id, job_id)))?;
+    let path = sandbox::JOB_ARTIFACT.validate(&settings.get(), Path::new(&artifact.path))?;
+    if !path.as_path().is_file() {
+        return Err(CommandError::NotFound(artifact.path));
+    }
+    app.opener()
+        .open_path(path.as_path().to_string_lossy(), None::<&str>)
+        .map_err(|e| CommandError::Internal(format!("failed to open {}: {}", artifact.path, e)))
+}
+
+// Removes the files and their records; returns how many
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn delete_job_artifacts(
+    db: State<'_, Database>,
+    jobs: State<'_, JobManager>,
+    job_id: String,
+) -> CommandResult<usize> {
+    let mut ids = Vec::new();
+    for artifact in job_artifacts(&db, &jobs, &job_id)? {
+        remove_file(Path::new(&artifact.path))?;
+        ids.push(artifact.id);
+    }
+    let deleted = delete_rows(&db, &ids)?;
+    println!("[Halbert] Deleted {} artifact(s) of {}", deleted, job_id);
+    Ok(deleted)
+}
@@ -0,0 +1,34 @@
+// Append-only audit log of decisions and actions taken from the app
+use rusqlite::params;
+
+use crate::db::Database;
+use crate::error::CommandResult;
+
+// The local identity decisions are attributed to
+pub fn local_actor() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "local".to_string())
+}
+
+pub fn record(
+    db: &Database,
+    actor: &str,
+    action: &str,
+    target: &str,
+    detail: &serde_json::Value,
+) -> CommandResult<()> {
+    let at = chrono::Utc::now().to_rfc3339();
+    let detail = if detail.is_null() {
+        None
+    } else {
+        Some(detail.to_string())
+    };
+    db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO audit_log (at, actor, action, target, detail) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![at, actor, action, target, detail],
+        )
+        .map(|_| ())
+    })
+}
@@ -0,0 +1,453 @@
+// The automation kill switch.
+//
+// While automation is paused nothing starts on its own. Every entry point
+// asks the shared Gate through `allows(entry, what)` (see ENTRY_POINTS):
+// JobManager::spawn holds new jobs as "queued", policy auto-approval in
+// approvals::submit leaves requests pending, timed work such as weekly
+// storage maintenance is skipped and hook scripts don't run. Whatever was
+// turned away is recorded. Jobs already running carry on unless the pause
+// asks to stop them. The pause and what it skipped are kept in
+// automation.json in the app data dir, so a restart doesn't lift it; a
+// file that can't be read keeps automation paused. Pausing and resuming
+// are audited with the reason, told to the backend (best effort) and
+// shown on the tray icon. Resuming starts the held jobs, or discards them
+// if asked, and reports everything that was skipped. Held jobs, like every
+// job, don't outlive the app; their skip records do.
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::app::{AppHandle, Emitter, State};
+use crate::audit;
+use crate::backend;
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::jobs::{Job, JobManager};
+use crate::settings::SettingsStore;
+#[cfg(feature = "gui")]
+use crate::widget;
+
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(5);
+const STOP_GRACE: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Entry {
+    Job,
+    AutoApprove,
+    Schedule,
+    Hook,
+}
+
+// Every kind of work that starts without someone asking for it right then
+pub const ENTRY_POINTS: &[Entry] = &[Entry::Job, Entry::AutoApprove, Entry::Schedule, Entry::Hook];
+
+// Commands that change the machine through a job straight away. While
+// paused, readonly::admit refuses them rather than queueing a change that
+// would then run unwatched on resume.
+pub const PAUSED_COMMANDS: &[&str] = &["start_job", "run_backup_now", "execute_cleanup", "run_storage_maintenance"];
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Pause {
+    pub reason: String,
+    pub actor: String,
+    pub paused_at: String,
+}
+
+// One kind of work turned away, however many times
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Skipped {
+    pub entry: Entry,
+    pub what: String,
+    pub count: u32,
+    pub first_at: String,
+    pub last_at: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct GateState {
+    pause: Option<Pause>,
+    skipped: Vec<Skipped>,
+}
+
+struct GateInner {
+    path: PathBuf,
+    state: Mutex<GateState>,
+}
+
+// Shared by the JobManager and the app state
+#[derive(Clone)]
+pub struct Gate {
+    inner: Arc<GateInner>,
+}
+
+impl Gate {
+    pub fn load(path: PathBuf) -> Self {
+        let state = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                println!("[Halbert] Can't read {}: {}; keeping automation paused", path.display(), e);
+                GateState {
+                    pause: Some(Pause {
+                        reason: format!("automation state unreadable: {}", e),
+                        actor: "system".to_string(),
+                        paused_at: chrono::Utc::now().to_rfc3339(),
+                    }),
+                    skipped: Vec::new(),
+                }
+            }),
+            Err(_) => GateState::default(),
+        };
+        if let Some(pause) = &state.pause {
+            println!("[Halbert] Automation is paused since {}: {}", pause.paused_at, pause.reason);
+        }
+        Gate {
+            inner: Arc::new(GateInner {
+                path,
+                state: Mutex::new(state),
+            }),
+        }
+    }
+
+    // Paused from the start, as if automation.json at `path` said so
+    #[cfg(test)]
+    pub fn paused(path: PathBuf) -> Self {
+        let state = GateState {
+            pause: Some(Pause {
+                reason: "test".to_string(),
+                actor: "test".to_string(),
+                paused_at: chrono::Utc::now().to_rfc3339(),
+            }),
+            skipped: Vec::new(),
+        };
+        std::fs::write(&path, serde_json::to_string(&state).unwrap()).unwrap();
+        Gate::load(path)
+    }
+
+    fn save(&self, state: &GateState) -> CommandResult<()> {
+        if let Some(dir) = self.inner.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = serde_json::to_string_pretty(state).map_err(|e| CommandError::Internal(e.to_string()))?;
+        std::fs::write(&self.inner.path, text)?;
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.inner.state.lock().unwrap().pause.is_some()
+    }
+
+    pub fn pause(&self) -> Option<Pause> {
+        self.inner.state.lock().unwrap().pause.clone()
+    }
+
+    // The shared guard: whether `what` may start through `entry` now. A
+    // refusal is recorded for the resume report.
+    pub fn allows(&self, entry: Entry, what: &str) -> bool {
+        let mut state = self.inner.state.lock().unwrap();
+        if state.pause.is_none() {
+            return true;
+        }
+        let now = chrono::Utc::now().to_rfc3339();
+        match state.skipped.iter_mut().find(|s| s.entry == entry && s.what == what) {
+            Some(skipped) => {
+                skipped.count += 1;
+                skipped.last_at = now;
+            }
+            None => state.skipped.push(Skipped {
+                entry,
+                what: what.to_string(),
+                count: 1,
+                first_at: now.clone(),
+                last_at: now,
+            }),
+        }
+        if let Err(e) = self.save(&state) {
+            println!("[Halbert] Can't save skipped automation: {}", e);
+        }
+        false
+    }
+
+    // None if already paused
+    fn start_pause(&self, pause: Pause) -> CommandResult<Option<Pause>> {
+        let mut state = self.inner.state.lock().unwrap();
+        if state.pause.is_some() {
+            return Ok(None);
+        }
+        let next = GateState {
+            pause: Some(pause.clone()),
+            skipped: Vec::new(),
+        };
+        self.save(&next)?;
+        *state = next;
+        Ok(Some(pause))
+    }
+
+    // The pause that ended and what it skipped; None if not paused
+    fn end_pause(&self) -> CommandResult<Option<(Pause, Vec<Skipped>)>> {
+        let mut state = self.inner.state.lock().unwrap();
+        let Some(pause) = state.pause.clone() else {
+            return Ok(None);
+        };
+        match std::fs::remove_file(&self.inner.path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        let skipped = std::mem::take(&mut state.skipped);
+        state.pause = None;
+        Ok(Some((pause, skipped)))
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct HeldJob {
+    pub id: String,
+    pub name: String,
+    pub task_type: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct AutomationStatus {
+    pub paused: bool,
+    pub pause: Option<Pause>,
+    // So far in this pause
+    pub skipped: Vec<Skipped>,
+    pub held_jobs: Vec<HeldJob>,
+    pub entry_points: Vec<Entry>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ResumeReport {
+    pub pause: Pause,
+    pub resumed_at: String,
+    pub skipped: Vec<Skipped>,
+    pub held_jobs: Vec<HeldJob>,
+    // "started" or "discarded"
+    pub held_jobs_action: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct AutomationChange {
+    pub status: AutomationStatus,
+    // Running job programs stopped by the pause
+    pub stopped_programs: usize,
+    pub resumed: Option<ResumeReport>,
+}
+
+fn held_jobs(jobs: Vec<Job>) -> Vec<HeldJob> {
+    jobs.into_iter()
+        .map(|job| HeldJob {
+            id: job.id,
+            name: job.name,
+            task_type: job.task_type,
+        })
+        .collect()
+}
+
+fn status(gate: &Gate, jobs: &JobManager) -> AutomationStatus {
+    let (pause, skipped) = {
+        let state = gate.inner.state.lock().unwrap();
+        (state.pause.clone(), state.skipped.clone())
+    };
+    AutomationStatus {
+        paused: pause.is_some(),
+        pause,
+        skipped,
+        held_jobs: held_jobs(jobs.held_jobs()),
+        entry_points: ENTRY_POINTS.to_vec(),
+    }
+}
+
+// Best effort: the backend may be down, which is one reason to pause
+fn tell_backend(settings: &SettingsStore, paused: bool, reason: &str) {
+    let body = serde_json::json!({
+        "paused": paused,
+        "reason": reason,
+        "at": chrono::Utc::now().to_rfc3339(),
+    });
+    if let Err(e) = backend::endpoint(&settings.get()).post("/api/automation/pause", &body, NOTIFY_TIMEOUT) {
+        println!("[Halbert] Couldn't tell the backend automation is {}: {}", if paused { "paused" } else { "resumed" }, e);
+    }
+}
+
+// `also_cancel_running` stops running job programs when pausing;
+// `discard_held` fails the held jobs instead of starting them on resume
+#[cfg_attr(feature = "gui", tauri::command)]
+#[allow(clippy::too_many_arguments)]
+pub async fn set_automation_paused(
+    app: AppHandle,
+    gate: State<'_, Gate>,
+    jobs: State<'_, JobManager>,
+    db: State<'_, Database>,
+    settings: State<'_, SettingsStore>,
+    paused: bool,
+    reason: String,
+    also_cancel_running: Option<bool>,
+    discard_held: Option<bool>,
+) -> CommandResult<AutomationChange> {
+    let reason = reason.trim().to_string();
+    if reason.is_empty() {
+        return Err(CommandError::InvalidInput("a reason is required".to_string()));
+    }
+    let actor = audit::local_actor();
+    let mut stopped_programs = 0;
+    let mut resumed = None;
+    if paused {
+        let pause = Pause {
+            reason: reason.clone(),
+            actor: actor.clone(),
+            paused_at: chrono::Utc::now().to_rfc3339(),
+        };
+        if gate.start_pause(pause)?.is_none() {
+            return Err(CommandError::Conflict("automation is already paused".to_string()));
+        }
+        if also_cancel_running.unwrap_or(false) {
+            stopped_programs = jobs.stop_running(STOP_GRACE);
+        }
+        audit::record(
+            &db,
+            &actor,
+            "automation.pause",
+            "automation",
+            &serde_json::json!({
+                "reason": reason,
+                "also_cancel_running": also_cancel_running.unwrap_or(false),
+                "stopped_programs": stopped_programs,
+            }),
+        )?;
+        println!("[Halbert] Automation paused by {}: {}", actor, reason);
+    } else {
+        let Some((pause, skipped)) = gate.end_pause()? else {
+            return Err(CommandError::Conflict("automation isn't paused".to_string()));
+        };
+        let discard = discard_held.unwrap_or(false);
+        let held = held_jobs(if discard {
+            jobs.discard_held("Discarded when automation resumed")
+        } else {
+            jobs.release_held()
+        });
+        let report = ResumeReport {
+            pause,
+            resumed_at: chrono::Utc::now().to_rfc3339(),
+            skipped,
+            held_jobs: held,
+            held_jobs_action: if discard { "discarded" } else { "started" }.to_string(),
+        };
+        audit::record(
+            &db,
+            &actor,
+            "automation.resume",
+            "automation",
+            &serde_json::json!({
+                "reason": reason,
+                "paused_reason": report.pause.reason,
+                "paused_at": report.pause.paused_at,
+                "skipped": report.skipped.iter().map(|s| s.count).sum::<u32>(),
+                "held_jobs": report.held_jobs.len(),
+                "held_jobs_action": report.held_jobs_action,
+            }),
+        )?;
+        println!(
+            "[Halbert] Automation resumed by {}: {} ({} held job(s) {})",
+            actor,
+            reason,
+            report.held_jobs.len(),
+            report.held_jobs_action
+        );
+        resumed = Some(report);
+    }
+    tell_backend(&settings, paused, &reason);
+    #[cfg(feature = "gui")]
+    widget::show_paused(&app, paused);
+    let status = status(&gate, &jobs);
+    let _ = app.emit("automation://changed", &status);
+    Ok(AutomationChange {
+        status,
+        stopped_programs,
+        resumed,
+    })
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_automation_status(gate: State<'_, Gate>, jobs: State<'_, JobManager>) -> AutomationStatus {
+    status(&gate, &jobs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pause(reason: &str) -> Pause {
+        Pause {
+            reason: reason.to_string(),
+            actor: "tester".to_string(),
+            paused_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    #[test]
+    fn every_clone_sees_one_pause() {
+        let dir = tempfile::tempdir().unwrap();
+        let gate = Gate::load(dir.path().join("automation.json"));
+        let other = gate.clone();
+        assert!(other.allows(Entry::Job, "backup"));
+
+        gate.start_pause(pause("incident")).unwrap().unwrap();
+        assert!(other.is_paused());
+        assert!(!other.allows(Entry::Job, "backup"));
+        assert!(other.start_pause(pause("again")).unwrap().is_none());
+
+        let (ended, skipped) = other.end_pause().unwrap().unwrap();
+        assert_eq!(ended.reason, "incident");
+        assert_eq!(skipped.len(), 1);
+        assert!(gate.allows(Entry::Job, "backup"));
+        assert!(gate.end_pause().unwrap().is_none());
+    }
+
+    #[test]
+    fn refusals_from_many_threads_are_all_counted() {
+        let dir = tempfile::tempdir().unwrap();
+        let gate = Gate::load(dir.path().join("automation.json"));
+        gate.start_pause(pause("incident")).unwrap();
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let gate = gate.clone();
+                let entry = ENTRY_POINTS[i % ENTRY_POINTS.len()];
+                std::thread::spawn(move || (0..25).all(|_| !gate.allows(entry, "scan")))
+            })
+            .collect();
+        for thread in threads {
+            assert!(thread.join().unwrap());
+        }
+        let (_, skipped) = gate.end_pause().unwrap().unwrap();
+        assert_eq!(skipped.len(), ENTRY_POINTS.len());
+        assert_eq!(skipped.iter().map(|s| s.count).sum::<u32>(), 200);
+    }
+
+    #[test]
+    fn a_restart_keeps_the_pause_and_its_skips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("automation.json");
+        let gate = Gate::load(path.clone());
+        gate.start_pause(pause("incident")).unwrap();
+        assert!(!gate.allows(Entry::Hook, "post-backup"));
+        assert!(!gate.allows(Entry::Hook, "post-backup"));
+
+        let restarted = Gate::load(path.clone());
+        assert!(restarted.is_paused());
+        let (ended, skipped) = restarted.end_pause().unwrap().unwrap();
+        assert_eq!(ended.reason, "incident");
+        assert_eq!(skipped[0].count, 2);
+        assert!(!Gate::load(path).is_paused());
+    }
+
+    #[test]
+    fn an_unreadable_state_file_keeps_automation_paused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("automation.json");
+        std::fs::write(&path, "{ not json").unwrap();
+        let gate = Gate::load(path);
+        assert!(!gate.allows(Entry::Schedule, "weekly maintenance"));
+    }
+}
@@ -0,0 +1,123 @@
+// Client for the local Halbert backend (the Python agent API).
+//
+// The auth token lives in the secret store and is read on every request,
+// so setting a new one takes effect without restarting the app. It is
+// never handed back to the frontend.
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::app::State;
+use crate::error::{CommandError, CommandResult};
+use crate::http::Endpoint;
+use crate::secrets;
+use crate::settings::{Settings, SettingsStore};
+
+pub const BACKEND_TOKEN_SECRET: &str = "backend_token";
+
+// Key older settings.toml files kept the token under
+const LEGACY_TOKEN_KEY: &str = "backend_token";
+
+pub fn endpoint(settings: &Settings) -> Endpoint<'_> {
+    Endpoint {
+        label: "backend",
+        base_url: &settings.backend_url,
+        token: secrets::read(BACKEND_TOKEN_SECRET).ok().flatten(),
+        measured: true,
+    }
+}
+
+// Move a plaintext token out of settings.toml into the secret store, then
+// drop it from the file. Runs before settings are loaded.
+pub fn migrate_legacy_token(settings_path: &Path) {
+    let Ok(text) = std::fs::read_to_string(settings_path) else {
+        return;
+    };
+    let Ok(mut table) = text.parse::<toml::Table>() else {
+        return;
+    };
+    let Some(token) = table.remove(LEGACY_TOKEN_KEY) else {
+        return;
+    };
+
+    if let Some(token) = token.as_str().filter(|t| !t.is_empty()) {
+        if let Err(e) = secrets::store(BACKEND_TOKEN_SECRET, token) {
+            // Leave the file alone so the token isn't lost
+            println!("[Halbert] Could not migrate backend token: {}", e);
+            return;
+        }
+    }
+    match toml::to_string_pretty(&table) {
+        Ok(text) => match std::fs::write(settings_path, text) {
+            Ok(()) => println!("[Halbert] Moved backend token out of {}", settings_path.display()),
+            Err(e) => println!("[Halbert] Failed to rewrite {}: {}", settings_path.display(), e),
+        },
+        Err(e) => println!("[Halbert] Failed to encode settings: {}", e),
+    }
+}
+
+#[derive(Serialize)]
+pub struct BackendTokenStatus {
+    pub stored: bool,
+    // "keyring" or "encrypted_file"
+    pub storage: Option<String>,
+}
+
+fn token_status() -> CommandResult<BackendTokenStatus> {
+    let storage = secrets::storage_of(BACKEND_TOKEN_SECRET)?;
+    Ok(BackendTokenStatus {
+        stored: storage.is_some(),
+        storage: storage.map(|s| s.to_string()),
+    })
+}
+
+// An empty token clears the stored one
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn set_backend_token(token: String) -> CommandResult<BackendTokenStatus> {
+    let token = token.trim();
+    if token.is_empty() {
+        secrets::delete(BACKEND_TOKEN_SECRET)?;
+    } else {
+        secrets::store(BACKEND_TOKEN_SECRET, token)?;
+    }
+    token_status()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_backend_token_status() -> CommandResult<BackendTokenStatus> {
+    token_status()
+}
+
+#[derive(Serialize)]
+pub struct BackendStatus {
+    pub base_url: String,
+    pub reachable: bool,
+    pub http_status: Option<u16>,
+    pub error: Option<String>,
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_backend_status(settings: State<'_, SettingsStore>) -> CommandResult<BackendStatus> {
+    let settings = settings.get();
+    // The probe blocks, so it waits on a blocking thread
+    tokio::task::spawn_blocking(move || status(&settings))
+        .await
+        .map_err(|e| CommandError::Internal(format!("backend status failed: {}", e)))
+}
+
+fn status(settings: &Settings) -> BackendStatus {
+    match endpoint(settings).probe("/api/status", Duration::from_secs(3)) {
+        Ok(code) => BackendStatus {
+            base_url: settings.backend_url.clone(),
+            reachable: true,
+            http_status: Some(code),
+            error: None,
+        },
+        Err(e) => BackendStatus {
+            base_url: settings.backend_url.clone(),
+            reachable: false,
+            http_status: None,
+            error: Some(e.to_string()),
+        },
+    }
+}
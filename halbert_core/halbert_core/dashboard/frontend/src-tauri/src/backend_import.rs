@@ -0,0 +1,613 @@
+// History from the standalone Python backend, copied into the local store.
+//
+// The backend pages out each kind from POST /api/export/<kind> (`cursor`
+// and `limit` in, `items` and `next_cursor` out). Approvals go into
+// approval_decisions, job runs into job_runs and audit entries into
+// audit_log, each marked origin "backend_import" with its original
+// timestamps and its id prefixed "backend:", so it can't collide with one
+// made here. A page's rows and the cursor after them commit together, so
+// an interrupted import picks up from the last page it stored and a row
+// that's already there is skipped and counted. The cursor of the last page
+// is kept too, so a later run only brings in what's new. Imported
+// decisions are marked forwarded and prompted: they came from the backend
+// and their outcome prompts are long past. The job leaves a JSON report
+// of what it did as its artifact.
+use rusqlite::{params, OptionalExtension, Transaction};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::app::{AppHandle, Manager, State};
+use crate::audit;
+use crate::backend;
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::http::BackendApi;
+use crate::jobs::{Job, JobManager};
+use crate::settings::SettingsStore;
+
+pub const ORIGIN: &str = "backend_import";
+pub const ID_PREFIX: &str = "backend:";
+const PAGE_SIZE: usize = 200;
+// Failures kept in the report per kind; the rest are only counted
+const MAX_FAILURES: usize = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    Approvals,
+    Jobs,
+    Audit,
+}
+
+pub const KINDS: &[Kind] = &[Kind::Approvals, Kind::Jobs, Kind::Audit];
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Approvals => "approvals",
+            Kind::Jobs => "jobs",
+            Kind::Audit => "audit",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        KINDS.iter().copied().find(|k| k.name() == name.trim())
+    }
+}
+
+// No kinds imports all of them; each once, in KINDS order
+pub fn parse_kinds(names: &[String]) -> CommandResult<Vec<Kind>> {
+    if names.is_empty() {
+        return Ok(KINDS.to_vec());
+    }
+    let mut wanted = Vec::new();
+    for name in names {
+        let kind = Kind::parse(name).ok_or_else(|| {
+            let known: Vec<&str> = KINDS.iter().map(|k| k.name()).collect();
+            CommandError::InvalidInput(format!("unknown history kind '{}' (expected {})", name, known.join(", ")))
+        })?;
+        wanted.push(kind);
+    }
+    Ok(KINDS.iter().copied().filter(|k| wanted.contains(k)).collect())
+}
+
+// "42" -> "backend:42"; an id that already carries the prefix keeps it
+pub fn namespaced(id: &str) -> String {
+    match id.starts_with(ID_PREFIX) {
+        true => id.to_string(),
+        false => format!("{}{}", ID_PREFIX, id),
+    }
+}
+
+// The backend uses both numeric and string ids
+fn id_of(item: &Value) -> Option<String> {
+    match item.get("id")? {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+#[derive(Deserialize)]
+struct Page {
+    #[serde(default)]
+    items: Vec<Value>,
+    #[serde(default)]
+    next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BackendApproval {
+    requested_at: String,
+    task: String,
+    #[serde(default)]
+    task_type: Option<String>,
+    #[serde(default)]
+    confidence: f64,
+    risk_level: String,
+    status: String,
+    #[serde(default)]
+    decided_at: Option<String>,
+    #[serde(default)]
+    outcome: Option<String>,
+    #[serde(default)]
+    outcome_notes: Option<String>,
+    #[serde(default)]
+    outcome_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BackendJob {
+    started_at: String,
+    finished_at: String,
+    task_type: String,
+    status: String,
+}
+
+#[derive(Deserialize)]
+struct BackendAudit {
+    at: String,
+    actor: String,
+    action: String,
+    target: String,
+    #[serde(default)]
+    detail: Option<Value>,
+}
+
+fn rfc3339(field: &str, value: &str) -> Result<chrono::DateTime<chrono::FixedOffset>, String> {
+    chrono::DateTime::parse_from_rfc3339(value).map_err(|e| format!("{} '{}' isn't RFC 3339: {}", field, value, e))
+}
+
+// A backend item as a local row, or why it can't be one
+#[derive(Debug, PartialEq)]
+pub enum Row {
+    Approval {
+        request_id: String,
+        requested_at: String,
+        task: String,
+        task_type: Option<String>,
+        confidence: f64,
+        risk_level: String,
+        status: String,
+        decided_at: String,
+        outcome: Option<String>,
+        outcome_notes: Option<String>,
+        outcome_at: Option<String>,
+    },
+    Job {
+        job_id: String,
+        started_at: String,
+        task_type: String,
+        status: String,
+        finished_at: i64,
+        duration_secs: f64,
+    },
+    Audit {
+        origin_id: String,
+        at: String,
+        actor: String,
+        action: String,
+        target: String,
+        detail: Option<String>,
+    },
+}
+
+pub fn map_item(kind: Kind, item: &Value) -> Result<Row, String> {
+    let id = namespaced(&id_of(item).ok_or("no id")?);
+    let parse_err = |e: serde_json::Error| e.to_string();
+    match kind {
+        Kind::Approvals => {
+            let a: BackendApproval = serde_json::from_value(item.clone()).map_err(parse_err)?;
+            rfc3339("requested_at", &a.requested_at)?;
+            // approval_decisions only keeps decided requests
+            let decided_at = a.decided_at.ok_or_else(|| format!("{} was never decided", a.status))?;
+            rfc3339("decided_at", &decided_at)?;
+            Ok(Row::Approval {
+                request_id: id,
+                requested_at: a.requested_at,
+                task: a.task,
+                task_type: a.task_type,
+                confidence: a.confidence,
+                risk_level: a.risk_level,
+                status: a.status,
+                decided_at,
+                outcome: a.outcome,
+                outcome_notes: a.outcome_notes,
+                outcome_at: a.outcome_at,
+            })
+        }
+        Kind::Jobs => {
+            let j: BackendJob = serde_json::from_value(item.clone()).map_err(parse_err)?;
+            let started = rfc3339("started_at", &j.started_at)?;
+            let finished = rfc3339("finished_at", &j.finished_at)?;
+            Ok(Row::Job {
+                job_id: id,
+                started_at: j.started_at,
+                task_type: j.task_type,
+                status: j.status,
+                finished_at: finished.timestamp(),
+                duration_secs: (finished - started).num_milliseconds().max(0) as f64 / 1000.0,
+            })
+        }
+        Kind::Audit => {
+            let a: BackendAudit = serde_json::from_value(item.clone()).map_err(parse_err)?;
+            rfc3339("at", &a.at)?;
+            Ok(Row::Audit {
+                origin_id: id,
+                at: a.at,
+                actor: a.actor,
+                action: a.action,
+                target: a.target,
+                detail: a.detail.filter(|d| !d.is_null()).map(|d| d.to_string()),
+            })
+        }
+    }
+}
+
+// False when the row was already there
+fn insert(tx: &Transaction, row: &Row, now: &str) -> rusqlite::Result<bool> {
+    let changed = match row {
+        Row::Approval {
+            request_id,
+            requested_at,
+            task,
+            task_type,
+            confidence,
+            risk_level,
+            status,
+            decided_at,
+            outcome,
+            outcome_notes,
+            outcome_at,
+        } => tx.execute(
+            "INSERT OR IGNORE INTO approval_decisions
+                 (request_id, requested_at, task, task_type, confidence, risk_level, status, decided_at,
+                  outcome, outcome_notes, outcome_at, forwarded, prompted_at, origin)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, 1, ?12, ?13)",
+            params![
+                request_id,
+                requested_at,
+                task,
+                task_type,
+                confidence,
+                risk_level,
+                status,
+                decided_at,
+                outcome,
+                outcome_notes,
+                outcome_at,
+                now,
+                ORIGIN
+            ],
+        )?,
+        Row::Job {
+            job_id,
+            started_at,
+            task_type,
+            status,
+            finished_at,
+            duration_secs,
+        } => tx.execute(
+            "INSERT OR IGNORE INTO job_runs (job_id, started_at, task_type, status, finished_at, duration_secs, origin)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![job_id, started_at, task_type, status, finished_at, duration_secs, ORIGIN],
+        )?,
+        Row::Audit {
+            origin_id,
+            at,
+            actor,
+            action,
+            target,
+            detail,
+        } => tx.execute(
+            "INSERT OR IGNORE INTO audit_log (at, actor, action, target, detail, origin, origin_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![at, actor, action, target, detail, ORIGIN, origin_id],
+        )?,
+    };
+    Ok(changed == 1)
+}
+
+pub fn saved_cursor(db: &Database, kind: Kind) -> CommandResult<Option<String>> {
+    db.with_conn(|conn| {
+        conn.query_row(
+            "SELECT cursor FROM backend_import_cursors WHERE kind = ?1",
+            [kind.name()],
+            |r| r.get::<_, Option<String>>(0),
+        )
+        .optional()
+        .map(Option::flatten)
+    })
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Failure {
+    pub id: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct KindReport {
+    pub kind: Kind,
+    // Where this run started; None from the beginning
+    pub resumed_from: Option<String>,
+    pub pages: usize,
+    pub imported: usize,
+    // Already imported
+    pub skipped: usize,
+    pub failed: usize,
+    pub failures: Vec<Failure>,
+    // Reached the backend's last page
+    pub complete: bool,
+    // What stopped it early
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+struct PageCounts {
+    imported: usize,
+    skipped: usize,
+    failures: Vec<Failure>,
+}
+
+// Pages through one kind from the saved cursor on, until the last page or
+// the first error
+pub fn import_kind(api: &dyn BackendApi, db: &Database, kind: Kind) -> KindReport {
+    let mut report = KindReport {
+        kind,
+        resumed_from: None,
+        pages: 0,
+        imported: 0,
+        skipped: 0,
+        failed: 0,
+        failures: Vec::new(),
+        complete: false,
+        error: None,
+    };
+    let mut cursor = match saved_cursor(db, kind) {
+        Ok(cursor) => cursor,
+        Err(e) => {
+            report.error = Some(e.to_string());
+            return report;
+        }
+    };
+    report.resumed_from = cursor.clone();
+    let path = format!("/api/export/{}", kind.name());
+    loop {
+        let page = api
+            .post_value(&path, &json!({ "cursor": cursor, "limit": PAGE_SIZE }))
+            .and_then(|value| {
+                serde_json::from_value::<Page>(value).map_err(|e| {
+                    CommandError::Remote(format!("backend sent an unexpected {} page: {}", kind.name(), e))
+                })
+            });
+        let page = match page {
+            Ok(page) => page,
+            Err(e) => {
+                report.error = Some(e.to_string());
+                return report;
+            }
+        };
+        if page.next_cursor.is_some() && page.next_cursor == cursor {
+            report.error = Some(format!("backend returned cursor {:?} again", cursor));
+            return report;
+        }
+        // The last page's own cursor is kept, so a later run re-reads it
+        let keep = page.next_cursor.clone().or_else(|| cursor.clone());
+        let now = chrono::Utc::now().to_rfc3339();
+        let stored = db.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            let mut counts = PageCounts::default();
+            for item in &page.items {
+                match map_item(kind, item) {
+                    Ok(row) if insert(&tx, &row, &now)? => counts.imported += 1,
+                    Ok(_) => counts.skipped += 1,
+                    Err(reason) => counts.failures.push(Failure { id: id_of(item), reason }),
+                }
+            }
+            tx.execute(
+                "INSERT INTO backend_import_cursors (kind, cursor, updated_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(kind) DO UPDATE SET cursor = ?2, updated_at = ?3",
+                params![kind.name(), keep, now],
+            )?;
+            tx.commit()?;
+            Ok(counts)
+        });
+        let counts = match stored {
+            Ok(counts) => counts,
+            Err(e) => {
+                report.error = Some(e.to_string());
+                return report;
+            }
+        };
+        report.pages += 1;
+        report.imported += counts.imported;
+        report.skipped += counts.skipped;
+        report.failed += counts.failures.len();
+        let room = MAX_FAILURES.saturating_sub(report.failures.len());
+        report.failures.extend(counts.failures.into_iter().take(room));
+        match page.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => {
+                report.complete = true;
+                return report;
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ImportReport {
+    pub started_at: String,
+    pub finished_at: String,
+    pub kinds: Vec<KindReport>,
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn import_backend_history(
+    app: AppHandle,
+    jobs: State<'_, JobManager>,
+    db: State<'_, Database>,
+    kinds: Vec<String>,
+) -> CommandResult<Job> {
+    let kinds = parse_kinds(&kinds)?;
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| CommandError::Internal(e.to_string()))?
+        .join("imports");
+    let names: Vec<&str> = kinds.iter().map(|k| k.name()).collect();
+    audit::record(&db, &audit::local_actor(), "backend_import.start", "backend", &json!({ "kinds": names }))?;
+    Ok(jobs.spawn("Import backend history", "backend_import", move |handle| {
+        std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        handle.set_artifacts_dir(&dir);
+        let settings = app.state::<SettingsStore>().get();
+        let endpoint = backend::endpoint(&settings);
+        let db = app.state::<Database>();
+        let started_at = chrono::Utc::now();
+        let mut reports = Vec::new();
+        for (i, kind) in kinds.iter().enumerate() {
+            let report = import_kind(&endpoint, &db, *kind);
+            handle.log(format!(
+                "{}: {} imported, {} already there, {} failed{}",
+                kind.name(),
+                report.imported,
+                report.skipped,
+                report.failed,
+                report.error.as_deref().map(|e| format!("; stopped: {}", e)).unwrap_or_default()
+            ));
+            reports.push(report);
+            handle.set_progress((i + 1) as f32 / kinds.len() as f32);
+        }
+        let report = ImportReport {
+            started_at: started_at.to_rfc3339(),
+            finished_at: chrono::Utc::now().to_rfc3339(),
+            kinds: reports,
+        };
+        let value = serde_json::to_value(&report).map_err(|e| e.to_string())?;
+        let file = dir.join(format!("backend-import-{}.json", started_at.format("%Y%m%dT%H%M%SZ")));
+        let text = serde_json::to_string_pretty(&value).map_err(|e| e.to_string())?;
+        std::fs::write(&file, text).map_err(|e| format!("{}: {}", file.display(), e))?;
+        handle.set_result(value);
+        let stopped: Vec<&str> = report.kinds.iter().filter(|k| k.error.is_some()).map(|k| k.kind.name()).collect();
+        if !stopped.is_empty() {
+            return Err(format!("{} stopped early; run the import again to resume", stopped.join(", ")));
+        }
+        Ok(())
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{MockServer, StubBackend};
+
+    const STARTED: &str = "2026-10-01T09:00:00Z";
+
+    fn job(id: Value) -> Value {
+        json!({
+            "id": id,
+            "started_at": STARTED,
+            "finished_at": "2026-10-01T09:00:30Z",
+            "task_type": "backup",
+            "status": "completed",
+        })
+    }
+
+    // Pages of jobs keyed by the cursor asked for
+    fn pages(body: &Value) -> CommandResult<Value> {
+        match body["cursor"].as_str() {
+            None => Ok(json!({ "items": [job(json!(1)), job(json!(2))], "next_cursor": "p2" })),
+            Some("p2") => Ok(json!({ "items": [job(json!(3))], "next_cursor": "p3" })),
+            Some(other) => Err(CommandError::HostUnreachable(format!("backend went away at {}", other))),
+        }
+    }
+
+    fn job_ids(db: &Database) -> Vec<String> {
+        db.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT job_id FROM job_runs ORDER BY job_id")?;
+            let ids = stmt.query_map([], |r| r.get(0))?.collect();
+            ids
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn ids_are_namespaced_once() {
+        assert_eq!(namespaced("42"), "backend:42");
+        assert_eq!(namespaced("backend:42"), "backend:42");
+        let Row::Job { job_id, duration_secs, .. } = map_item(Kind::Jobs, &job(json!(7))).unwrap() else {
+            panic!("not a job row");
+        };
+        assert_eq!((job_id.as_str(), duration_secs), ("backend:7", 30.0));
+        let Row::Audit { origin_id, .. } = map_item(
+            Kind::Audit,
+            &json!({ "id": " a-1 ", "at": STARTED, "actor": "bob", "action": "job.start", "target": "backup" }),
+        )
+        .unwrap() else {
+            panic!("not an audit row");
+        };
+        assert_eq!(origin_id, "backend:a-1");
+        assert_eq!(map_item(Kind::Jobs, &job(json!(""))).unwrap_err(), "no id");
+        assert_eq!(map_item(Kind::Jobs, &job(Value::Null)).unwrap_err(), "no id");
+    }
+
+    #[test]
+    fn imported_ids_never_collide_with_local_ones() {
+        let db = Database::in_memory();
+        db.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO job_runs (job_id, started_at, task_type, status, finished_at, duration_secs)
+                 VALUES ('1', ?1, 'backup', 'completed', 0, 1.0)",
+                [STARTED],
+            )
+        })
+        .unwrap();
+        let stub = StubBackend::default().on("/api/export/jobs", |_| Ok(json!({ "items": [job(json!(1))] })));
+        let report = import_kind(&stub, &db, Kind::Jobs);
+        assert_eq!((report.imported, report.skipped, report.complete), (1, 0, true));
+        assert_eq!(job_ids(&db), ["1", "backend:1"]);
+    }
+
+    #[test]
+    fn an_interrupted_import_resumes_from_the_last_stored_page() {
+        let db = Database::in_memory();
+        let first = StubBackend::default().on("/api/export/jobs", pages);
+        let report = import_kind(&first, &db, Kind::Jobs);
+        assert_eq!((report.pages, report.imported, report.complete), (2, 3, false));
+        assert!(report.error.unwrap().contains("backend went away at p3"));
+        assert_eq!(saved_cursor(&db, Kind::Jobs).unwrap().as_deref(), Some("p3"));
+
+        // The backend is back with one more page after p3
+        let second = StubBackend::default().on("/api/export/jobs", |body| {
+            assert_eq!(body["cursor"], "p3");
+            Ok(json!({ "items": [job(json!(3)), job(json!(4))], "next_cursor": null }))
+        });
+        let report = import_kind(&second, &db, Kind::Jobs);
+        assert_eq!(report.resumed_from.as_deref(), Some("p3"));
+        assert_eq!((report.pages, report.imported, report.skipped, report.complete), (1, 1, 1, true));
+        assert_eq!(second.calls().len(), 1);
+        assert_eq!(job_ids(&db), ["backend:1", "backend:2", "backend:3", "backend:4"]);
+        // The last page's cursor stays, so the next run only re-reads p3
+        assert_eq!(saved_cursor(&db, Kind::Jobs).unwrap().as_deref(), Some("p3"));
+        assert_eq!(saved_cursor(&db, Kind::Audit).unwrap(), None);
+    }
+
+    #[test]
+    fn a_repeated_cursor_stops_the_import() {
+        let db = Database::in_memory();
+        let stub = StubBackend::default().on("/api/export/audit", |_| Ok(json!({ "items": [], "next_cursor": "same" })));
+        let report = import_kind(&stub, &db, Kind::Audit);
+        assert_eq!(report.pages, 1);
+        assert_eq!(report.error.as_deref(), Some("backend returned cursor Some(\"same\") again"));
+        assert_eq!(stub.calls().len(), 2);
+    }
+
+    #[test]
+    fn bad_items_are_counted_and_the_rest_imported() {
+        let db = Database::in_memory();
+        let stub = StubBackend::default().on("/api/export/jobs", |_| {
+            Ok(json!({ "items": [job(json!(1)), { "id": 2, "status": "completed" }, job(json!(1))] }))
+        });
+        let report = import_kind(&stub, &db, Kind::Jobs);
+        assert_eq!((report.imported, report.skipped, report.failed), (1, 1, 1));
+        assert_eq!(report.failures[0].id.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn pages_are_requested_over_http_with_the_cursor() {
+        let server = MockServer::start(|request| match request.body["cursor"].as_str() {
+            None => (200, json!({ "items": [job(json!(10))], "next_cursor": "c1" })),
+            _ => (200, json!({ "items": [job(json!(11))] })),
+        });
+        let db = Database::in_memory();
+        let report = import_kind(&server.endpoint(), &db, Kind::Jobs);
+        assert_eq!((report.pages, report.imported, report.complete), (2, 2, true));
+        let received = server.received();
+        assert_eq!(received.len(), 2);
+        assert!(received.iter().all(|r| r.method == "POST" && r.path == "/api/export/jobs"));
+        assert_eq!(received[0].body, json!({ "cursor": null, "limit": PAGE_SIZE }));
+        assert_eq!(received[1].body["cursor"], "c1");
+        assert_eq!(received[0].authorization.as_deref(), Some("Bearer secret"));
+    }
+}
@@ -0,0 +1,521 @@
+// How fast and how reliably the backend answers.
+//
+// Every request made through a measured Endpoint (the backend's, not remote
+// hosts') is recorded here with its duration and outcome, per endpoint:
+// the path without its query, with segments holding digits (ids) folded to
+// "{id}". Durations go into fixed log-scale buckets, four per doubling from
+// 1 ms, so a percentile read from them is the upper edge of its bucket and
+// at most about 19% high. For a streamed answer the time is until the
+// response headers arrive. 5xx answers and requests that got no answer are
+// errors; timeouts are errors counted again on their own.
+//
+// Recording names the endpoint, then takes one short lock to bump a few
+// counters in the current minute; under it nothing is allocated except the
+// first time an endpoint or minute is seen, and readers only copy out.
+// The last KEEP_MINUTES minutes stay in memory for the 5m and 1h windows.
+// Each finished minute is added once to its hour's row in SQLite, so the
+// 24h window, read from those rows plus what isn't written yet, survives a
+// restart. The 5-minute p95 is published to the alert engine as
+// backend.p95_ms, with backend.slo_violated raised while it's above
+// `backend_slo_p95_ms`; pair that with a rule's for_secs to alert on a
+// sustained violation.
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::app::{AppHandle, Manager, State};
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::settings::SettingsStore;
+
+pub const BUCKETS: usize = 64;
+const KEEP_MINUTES: i64 = 61;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+// Hourly rows older than this are deleted when flushing
+const KEEP_HOURS: i64 = 48;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    // Any HTTP answer
+    Status(u16),
+    Timeout,
+    // No answer for another reason: refused, reset, DNS
+    Failed,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    pub counts: [u64; BUCKETS],
+    pub requests: u64,
+    pub errors: u64,
+    pub timeouts: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            counts: [0; BUCKETS],
+            requests: 0,
+            errors: 0,
+            timeouts: 0,
+        }
+    }
+}
+
+// The bucket a duration falls in; bucket i holds durations up to
+// 2^(i/4) ms, the last one everything longer too
+pub fn bucket(elapsed: Duration) -> usize {
+    let ms = elapsed.as_secs_f64() * 1000.0;
+    if ms <= 1.0 {
+        return 0;
+    }
+    ((ms.log2() * 4.0).ceil() as usize).min(BUCKETS - 1)
+}
+
+pub fn upper_bound_ms(bucket: usize) -> f64 {
+    2f64.powf(bucket as f64 / 4.0)
+}
+
+// Nearest-rank percentile over bucket counts, as the bucket's upper edge;
+// None without samples
+pub fn percentile(counts: &[u64; BUCKETS], p: f64) -> Option<f64> {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    let rank = ((p / 100.0) * total as f64).ceil().max(1.0) as u64;
+    let mut seen = 0;
+    let index = counts.iter().position(|&n| {
+        seen += n;
+        seen >= rank
+    })?;
+    Some(upper_bound_ms(index))
+}
+
+impl Histogram {
+    pub fn record(&mut self, elapsed: Duration, outcome: Outcome) {
+        self.counts[bucket(elapsed)] += 1;
+        self.requests += 1;
+        match outcome {
+            Outcome::Status(code) if code < 500 => {}
+            Outcome::Status(_) | Outcome::Failed => self.errors += 1,
+            Outcome::Timeout => {
+                self.errors += 1;
+                self.timeouts += 1;
+            }
+        }
+    }
+
+    pub fn add(&mut self, other: &Histogram) {
+        for (count, more) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += more;
+        }
+        self.requests += other.requests;
+        self.errors += other.errors;
+        self.timeouts += other.timeouts;
+    }
+}
+
+// "/api/jobs/42/logs?tail=10" -> "/api/jobs/{id}/logs"
+pub fn endpoint_name(path: &str) -> String {
+    let path = path.split('?').next().unwrap_or_default();
+    path.split('/')
+        .map(|segment| match segment.bytes().any(|b| b.is_ascii_digit()) {
+            true => "{id}",
+            false => segment,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+struct Minute {
+    // Unix minutes
+    minute: i64,
+    histogram: Histogram,
+    flushed: bool,
+}
+
+struct Series {
+    endpoint: String,
+    minutes: VecDeque<Minute>,
+}
+
+struct Recorder {
+    series: Mutex<Vec<Series>>,
+}
+
+static RECORDER: Recorder = Recorder {
+    series: Mutex::new(Vec::new()),
+};
+
+fn now_minute() -> i64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64 / 60)
+}
+
+pub fn record(path: &str, elapsed: Duration, outcome: Outcome) {
+    RECORDER.record(&endpoint_name(path), now_minute(), elapsed, outcome);
+}
+
+impl Recorder {
+    fn record(&self, endpoint: &str, minute: i64, elapsed: Duration, outcome: Outcome) {
+        let mut all = self.series.lock().unwrap();
+        let index = match all.iter().position(|s| s.endpoint == endpoint) {
+            Some(index) => index,
+            None => {
+                all.push(Series {
+                    endpoint: endpoint.to_string(),
+                    minutes: VecDeque::new(),
+                });
+                all.len() - 1
+            }
+        };
+        let minutes = &mut all[index].minutes;
+        if minutes.back().is_none_or(|m| m.minute != minute) {
+            minutes.push_back(Minute {
+                minute,
+                histogram: Histogram::default(),
+                flushed: false,
+            });
+        }
+        if let Some(current) = minutes.back_mut() {
+            current.histogram.record(elapsed, outcome);
+        }
+        while minutes.front().is_some_and(|m| minute - m.minute >= KEEP_MINUTES) {
+            minutes.pop_front();
+        }
+    }
+
+    // Per endpoint: in-memory minutes from `since` on, or with
+    // `unflushed_only` every minute not written yet
+    fn collect(&self, since: i64, unflushed_only: bool) -> BTreeMap<String, Histogram> {
+        let all = self.series.lock().unwrap();
+        let mut merged = BTreeMap::new();
+        for series in all.iter() {
+            for minute in &series.minutes {
+                let wanted = if unflushed_only { !minute.flushed } else { minute.minute >= since };
+                if wanted {
+                    let histogram: &mut Histogram = merged.entry(series.endpoint.clone()).or_default();
+                    histogram.add(&minute.histogram);
+                }
+            }
+        }
+        merged
+    }
+
+    // Minutes before `before` not written yet, by endpoint
+    fn unflushed(&self, before: i64) -> Vec<(String, i64, Histogram)> {
+        let all = self.series.lock().unwrap();
+        all.iter()
+            .flat_map(|series| {
+                series
+                    .minutes
+                    .iter()
+                    .filter(|m| !m.flushed && m.minute < before)
+                    .map(|m| (series.endpoint.clone(), m.minute, m.histogram.clone()))
+            })
+            .collect()
+    }
+
+    fn mark_flushed(&self, written: &[(String, i64, Histogram)]) {
+        let mut all = self.series.lock().unwrap();
+        for series in all.iter_mut() {
+            for minute in series.minutes.iter_mut() {
+                if written.iter().any(|(e, m, _)| *e == series.endpoint && *m == minute.minute) {
+                    minute.flushed = true;
+                }
+            }
+        }
+    }
+}
+
+fn add_to_hour(tx: &rusqlite::Transaction, endpoint: &str, hour: i64, more: &Histogram) -> rusqlite::Result<()> {
+    let stored: Option<(String, i64, i64, i64)> = tx
+        .query_row(
+            "SELECT buckets, requests, errors, timeouts FROM backend_latency_hours
+             WHERE hour = ?1 AND endpoint = ?2",
+            params![hour, endpoint],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()?;
+    let mut histogram = stored.map(|stored| from_row(&stored)).unwrap_or_default();
+    histogram.add(more);
+    let buckets = serde_json::to_string(&histogram.counts[..]).unwrap_or_else(|_| "[]".to_string());
+    tx.execute(
+        "INSERT OR REPLACE INTO backend_latency_hours (hour, endpoint, buckets, requests, errors, timeouts)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            hour,
+            endpoint,
+            buckets,
+            histogram.requests as i64,
+            histogram.errors as i64,
+            histogram.timeouts as i64
+        ],
+    )?;
+    Ok(())
+}
+
+fn from_row((buckets, requests, errors, timeouts): &(String, i64, i64, i64)) -> Histogram {
+    let mut histogram = Histogram {
+        requests: *requests as u64,
+        errors: *errors as u64,
+        timeouts: *timeouts as u64,
+        ..Histogram::default()
+    };
+    let counts: Vec<u64> = serde_json::from_str(buckets).unwrap_or_default();
+    for (count, stored) in histogram.counts.iter_mut().zip(counts) {
+        *count = stored;
+    }
+    histogram
+}
+
+// Adds finished minutes (every minute, at exit) to their hours' rows
+pub fn flush(db: &Database, everything: bool) -> CommandResult<usize> {
+    let before = if everything { i64::MAX } else { now_minute() };
+    let pending = RECORDER.unflushed(before);
+    let oldest_hour = now_minute() / 60 - KEEP_HOURS;
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        for (endpoint, minute, histogram) in &pending {
+            add_to_hour(&tx, endpoint, minute / 60, histogram)?;
+        }
+        tx.execute("DELETE FROM backend_latency_hours WHERE hour < ?1", params![oldest_hour])?;
+        tx.commit()
+    })?;
+    RECORDER.mark_flushed(&pending);
+    Ok(pending.len())
+}
+
+fn stored_since(db: &Database, hour: i64) -> CommandResult<BTreeMap<String, Histogram>> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT endpoint, buckets, requests, errors, timeouts FROM backend_latency_hours WHERE hour >= ?1",
+        )?;
+        let rows = stmt.query_map(params![hour], |row| {
+            Ok((row.get::<_, String>(0)?, (row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)))
+        })?;
+        let mut merged: BTreeMap<String, Histogram> = BTreeMap::new();
+        for row in rows {
+            let (endpoint, stored) = row?;
+            merged.entry(endpoint).or_default().add(&from_row(&stored));
+        }
+        Ok(merged)
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Window {
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "1h")]
+    Hour,
+    #[serde(rename = "24h")]
+    Day,
+}
+
+impl Window {
+    pub fn parse(text: &str) -> CommandResult<Self> {
+        match text {
+            "5m" => Ok(Window::FiveMinutes),
+            "1h" => Ok(Window::Hour),
+            "24h" => Ok(Window::Day),
+            other => Err(CommandError::InvalidInput(format!(
+                "unknown window '{}'; use 5m, 1h or 24h",
+                other
+            ))),
+        }
+    }
+}
+
+// Per endpoint over the window. The 24h window starts on an hour boundary.
+pub fn histograms(db: &Database, window: Window) -> CommandResult<BTreeMap<String, Histogram>> {
+    let now = now_minute();
+    match window {
+        Window::FiveMinutes => Ok(RECORDER.collect(now - 4, false)),
+        Window::Hour => Ok(RECORDER.collect(now - 59, false)),
+        Window::Day => {
+            let mut merged = stored_since(db, (now - 24 * 60) / 60 + 1)?;
+            for (endpoint, histogram) in RECORDER.collect(0, true) {
+                merged.entry(endpoint).or_default().add(&histogram);
+            }
+            Ok(merged)
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct EndpointPerformance {
+    // "all" for every endpoint together
+    pub endpoint: String,
+    pub requests: u64,
+    pub p50_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+    pub p99_ms: Option<f64>,
+    pub error_rate: f64,
+    pub timeout_rate: f64,
+}
+
+pub fn performance(endpoint: &str, histogram: &Histogram) -> EndpointPerformance {
+    let rate = |n: u64| match histogram.requests {
+        0 => 0.0,
+        requests => n as f64 / requests as f64,
+    };
+    EndpointPerformance {
+        endpoint: endpoint.to_string(),
+        requests: histogram.requests,
+        p50_ms: percentile(&histogram.counts, 50.0),
+        p95_ms: percentile(&histogram.counts, 95.0),
+        p99_ms: percentile(&histogram.counts, 99.0),
+        error_rate: rate(histogram.errors),
+        timeout_rate: rate(histogram.timeouts),
+    }
+}
+
+fn overall(histograms: &BTreeMap<String, Histogram>) -> Histogram {
+    let mut all = Histogram::default();
+    for histogram in histograms.values() {
+        all.add(histogram);
+    }
+    all
+}
+
+// Every endpoint over the last five minutes, for alert signals
+pub fn recent() -> EndpointPerformance {
+    performance("all", &overall(&RECORDER.collect(now_minute() - 4, false)))
+}
+
+#[derive(Serialize)]
+pub struct BackendPerformance {
+    pub window: Window,
+    pub overall: EndpointPerformance,
+    // Most requests first
+    pub endpoints: Vec<EndpointPerformance>,
+    // 0 when no SLO is set
+    pub slo_p95_ms: u64,
+    // None without an SLO or without requests in the window
+    pub slo_met: Option<bool>,
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_backend_performance(
+    db: State<'_, Database>,
+    settings: State<'_, SettingsStore>,
+    window: Option<String>,
+) -> CommandResult<BackendPerformance> {
+    let window = window.as_deref().map(Window::parse).transpose()?.unwrap_or(Window::Hour);
+    let histograms = histograms(&db, window)?;
+    let overall = performance("all", &overall(&histograms));
+    let mut endpoints: Vec<EndpointPerformance> =
+        histograms.iter().map(|(endpoint, h)| performance(endpoint, h)).collect();
+    endpoints.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.endpoint.cmp(&b.endpoint)));
+    let slo_p95_ms = settings.get().backend_slo_p95_ms;
+    let slo_met = overall.p95_ms.filter(|_| slo_p95_ms > 0).map(|p95| p95 <= slo_p95_ms as f64);
+    Ok(BackendPerformance {
+        window,
+        overall,
+        endpoints,
+        slo_p95_ms,
+        slo_met,
+    })
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(FLUSH_INTERVAL);
+        if let Err(e) = flush(&app.state::<Database>(), false) {
+            println!("[Halbert] Couldn't store backend latency: {}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn counts(samples: &[(usize, u64)]) -> [u64; BUCKETS] {
+        let mut counts = [0; BUCKETS];
+        for &(bucket, n) in samples {
+            counts[bucket] = n;
+        }
+        counts
+    }
+
+    #[test]
+    fn buckets_are_four_per_doubling() {
+        assert_eq!(bucket(Duration::ZERO), 0);
+        assert_eq!(bucket(ms(1)), 0);
+        assert_eq!(bucket(Duration::from_micros(1001)), 1);
+        assert_eq!(bucket(ms(2)), 4);
+        assert_eq!(bucket(ms(1000)), 40);
+        // Everything past the last edge lands in the last bucket
+        assert_eq!(bucket(Duration::from_secs(86_400 * 365)), BUCKETS - 1);
+        for i in [0, 1, 4, 40] {
+            assert_eq!(bucket(Duration::from_secs_f64(upper_bound_ms(i) / 1000.0)), i);
+        }
+        assert_eq!(upper_bound_ms(4), 2.0);
+        assert_eq!(upper_bound_ms(40), 1024.0);
+    }
+
+    #[test]
+    fn no_samples_have_no_percentile() {
+        for p in [0.0, 50.0, 95.0, 100.0] {
+            assert_eq!(percentile(&[0; BUCKETS], p), None);
+        }
+    }
+
+    #[test]
+    fn one_sample_is_every_percentile() {
+        let one = counts(&[(40, 1)]);
+        for p in [0.0, 0.1, 50.0, 99.9, 100.0] {
+            assert_eq!(percentile(&one, p), Some(1024.0), "p{}", p);
+        }
+    }
+
+    #[test]
+    fn nearest_rank_at_the_edges() {
+        // 90 fast requests (<= 2 ms) and 10 slow ones (<= 1024 ms)
+        let split = counts(&[(4, 90), (40, 10)]);
+        assert_eq!(percentile(&split, 0.0), Some(2.0));
+        assert_eq!(percentile(&split, 90.0), Some(2.0));
+        assert_eq!(percentile(&split, 90.1), Some(1024.0));
+        assert_eq!(percentile(&split, 100.0), Some(1024.0));
+
+        // Two samples: p50 is the lower, anything above it the upper
+        let two = counts(&[(0, 1), (8, 1)]);
+        assert_eq!(percentile(&two, 50.0), Some(1.0));
+        assert_eq!(percentile(&two, 50.01), Some(4.0));
+    }
+
+    #[test]
+    fn outcomes_and_merging() {
+        let mut a = Histogram::default();
+        a.record(ms(3), Outcome::Status(200));
+        a.record(ms(3), Outcome::Status(404));
+        a.record(ms(3), Outcome::Status(503));
+        a.record(ms(5000), Outcome::Timeout);
+        a.record(ms(1), Outcome::Failed);
+        assert_eq!((a.requests, a.errors, a.timeouts), (5, 3, 1));
+
+        let mut b = Histogram::default();
+        b.record(ms(3), Outcome::Status(200));
+        b.add(&a);
+        assert_eq!((b.requests, b.errors, b.timeouts), (6, 3, 1));
+        assert_eq!(b.counts[bucket(ms(3))], 4);
+        assert_eq!(percentile(&b.counts, 50.0), Some(upper_bound_ms(bucket(ms(3)))));
+    }
+
+    #[test]
+    fn endpoints_fold_ids_and_drop_queries() {
+        assert_eq!(endpoint_name("/api/jobs/42/logs?tail=10"), "/api/jobs/{id}/logs");
+        assert_eq!(endpoint_name("/api/documents/doc-7f3a"), "/api/documents/{id}");
+        assert_eq!(endpoint_name("/api/status"), "/api/status");
+    }
+}
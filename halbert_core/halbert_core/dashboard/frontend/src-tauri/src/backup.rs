@@ -0,0 +1,273 @@
+// restic backups run as jobs.
+//
+// The repository and paths live in settings; the repository password is in
+// the secret store and reaches restic through RESTIC_PASSWORD, never argv.
+// `run_backup_now` parses restic's --json status lines into job progress.
+// Schedulers should go through `start_backup` so all runs look the same.
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::app::{AppHandle, Emitter, State};
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::jobs::{Job, JobHandle, JobManager};
+use crate::secrets;
+use crate::settings::{Settings, SettingsStore};
+
+pub const PASSWORD_SECRET: &str = "backup:password";
+const QUERY_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackupConfig {
+    // Anything restic accepts as -r, e.g. "/mnt/backup" or "sftp:host:/srv/restic"
+    pub repository: String,
+    pub include_paths: Vec<String>,
+    #[serde(default)]
+    pub exclude_patterns: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct Snapshot {
+    pub id: String,
+    pub short_id: String,
+    pub time: String,
+    pub hostname: String,
+    pub paths: Vec<String>,
+    pub tags: Vec<String>,
+    // From the snapshot summary, which restic 0.17+ records
+    pub size_bytes: Option<u64>,
+    pub duration_secs: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct BackupStatus {
+    pub repository: String,
+    pub last_snapshot: Option<Snapshot>,
+    pub age_hours: Option<f64>,
+    pub snapshot_count: usize,
+}
+
+// restic with the repository and password from settings
+struct Restic {
+    repository: String,
+    password: String,
+}
+
+impl Restic {
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new("restic");
+        command
+            .args(args)
+            .env("RESTIC_REPOSITORY", &self.repository)
+            .env("RESTIC_PASSWORD", &self.password);
+        command
+    }
+
+    fn json(&self, args: &[&str]) -> CommandResult<Value> {
+        let mut command = self.command(args);
+        let output = exec::bounded(std::time::Instant::now() + QUERY_TIMEOUT, move || command.output())
+            .ok_or_else(|| CommandError::Internal(format!("restic {} timed out", args.join(" "))))??;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(CommandError::Internal(format!(
+                "restic {} failed: {}",
+                args.join(" "),
+                stderr.trim()
+            )));
+        }
+        serde_json::from_slice(&output.stdout)
+            .map_err(|e| CommandError::Internal(format!("unexpected restic output: {}", e)))
+    }
+}
+
+fn config(settings: &Settings) -> CommandResult<(BackupConfig, Restic)> {
+    let config = settings
+        .backup
+        .clone()
+        .ok_or_else(|| CommandError::NotConfigured("no backup repository configured".to_string()))?;
+    if exec::find_in_path("restic").is_none() {
+        return Err(CommandError::ToolMissing("restic is not installed".to_string()));
+    }
+    let password = secrets::read(PASSWORD_SECRET)?
+        .ok_or_else(|| CommandError::NotConfigured("the backup repository password is not set".to_string()))?;
+    let restic = Restic {
+        repository: config.repository.clone(),
+        password,
+    };
+    Ok((config, restic))
+}
+
+fn parse_snapshot(value: &Value) -> Option<Snapshot> {
+    let strings = |key: &str| -> Vec<String> {
+        value
+            .get(key)
+            .and_then(Value::as_array)
+            .map(|items| items.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            .unwrap_or_default()
+    };
+    let summary = value.get("summary");
+    let duration_secs = summary.and_then(|s| {
+        let start = chrono::DateTime::parse_from_rfc3339(s.get("backup_start")?.as_str()?).ok()?;
+        let end = chrono::DateTime::parse_from_rfc3339(s.get("backup_end")?.as_str()?).ok()?;
+        Some((end - start).num_milliseconds() as f64 / 1000.0)
+    });
+    Some(Snapshot {
+        id: value.get("id")?.as_str()?.to_string(),
+        short_id: value.get("short_id").and_then(Value::as_str).unwrap_or_default().to_string(),
+        time: value.get("time")?.as_str()?.to_string(),
+        hostname: value.get("hostname").and_then(Value::as_str).unwrap_or_default().to_string(),
+        paths: strings("paths"),
+        tags: strings("tags"),
+        size_bytes: summary.and_then(|s| s.get("total_bytes_processed")?.as_u64()),
+        duration_secs,
+    })
+}
+
+fn snapshots(restic: &Restic) -> CommandResult<Vec<Snapshot>> {
+    let value = restic.json(&["snapshots", "--json"])?;
+    let mut snapshots: Vec<Snapshot> = value.as_array().into_iter().flatten().filter_map(parse_snapshot).collect();
+    // RFC 3339 times from one repository sort chronologically
+    snapshots.sort_by(|a, b| b.time.cmp(&a.time));
+    Ok(snapshots)
+}
+
+// One `restic backup --json` line: progress, an error, or the final summary
+fn handle_line(handle: &JobHandle, line: &str) {
+    let Ok(message) = serde_json::from_str::<Value>(line) else {
+        handle.log(line.to_string());
+        return;
+    };
+    match message.get("message_type").and_then(Value::as_str) {
+        Some("status") => {
+            if let Some(done) = message.get("percent_done").and_then(Value::as_f64) {
+                handle.set_progress(done as f32);
+            }
+        }
+        Some("error") => {
+            let error = message
+                .pointer("/error/message")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error");
+            let item = message.get("item").and_then(Value::as_str).unwrap_or_default();
+            handle.log(format!("error: {} {}", item, error));
+        }
+        Some("summary") => {
+            handle.log(format!(
+                "snapshot {} saved: {} new, {} changed, {} bytes added in {:.0}s",
+                message.get("snapshot_id").and_then(Value::as_str).unwrap_or("?"),
+                message.get("files_new").and_then(Value::as_u64).unwrap_or(0),
+                message.get("files_changed").and_then(Value::as_u64).unwrap_or(0),
+                message.get("data_added").and_then(Value::as_u64).unwrap_or(0),
+                message.get("total_duration").and_then(Value::as_f64).unwrap_or(0.0),
+            ));
+            handle.set_result(message);
+        }
+        _ => {}
+    }
+}
+
+pub fn start_backup(jobs: &JobManager, settings: &Settings) -> CommandResult<Job> {
+    let (config, restic) = config(settings)?;
+    if config.include_paths.is_empty() {
+        return Err(CommandError::NotConfigured("no backup paths configured".to_string()));
+    }
+    let mut args = vec!["backup".to_string(), "--json".to_string()];
+    for pattern in &config.exclude_patterns {
+        args.push("--exclude".to_string());
+        args.push(pattern.clone());
+    }
+    args.push("--".to_string());
+    args.extend(config.include_paths.iter().cloned());
+
+    Ok(jobs.spawn("Backup", "backup", move |handle| {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let mut command = restic.command(&args);
+        let status = handle.run_prepared(&mut command, |line| handle_line(handle, line))?;
+        // 3 means the snapshot was saved but some files couldn't be read
+        match status.code() {
+            Some(0) => Ok(()),
+            Some(3) => {
+                handle.log("Some files could not be read; the snapshot is incomplete");
+                Ok(())
+            }
+            _ => Err(format!("restic backup exited with {}", status)),
+        }
+    }))
+}
+
+// A None password keeps the stored one
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn configure_backup(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    repo_url: String,
+    password: Option<String>,
+    include_paths: Vec<String>,
+    exclude_patterns: Vec<String>,
+) -> CommandResult<BackupConfig> {
+    let repository = repo_url.trim().to_string();
+    if repository.is_empty() {
+        return Err(CommandError::InvalidInput("repository is required".to_string()));
+    }
+    let include_paths: Vec<String> = include_paths.iter().map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect();
+    if include_paths.is_empty() {
+        return Err(CommandError::InvalidInput("at least one path to back up is required".to_string()));
+    }
+    if let Some(path) = include_paths.iter().find(|p| !std::path::Path::new(p).is_absolute()) {
+        return Err(CommandError::InvalidInput(format!("backup paths must be absolute: {}", path)));
+    }
+    match password.as_deref().map(str::trim) {
+        Some("") => return Err(CommandError::InvalidInput("the repository password can't be empty".to_string())),
+        Some(password) => secrets::store(PASSWORD_SECRET, password)?,
+        None if secrets::read(PASSWORD_SECRET)?.is_none() => {
+            return Err(CommandError::InvalidInput("a repository password is required".to_string()))
+        }
+        None => {}
+    }
+
+    let config = BackupConfig {
+        repository,
+        include_paths,
+        exclude_patterns,
+    };
+    let updated = settings.update(|current| {
+        let mut next = current.clone();
+        next.backup = Some(config.clone());
+        Ok(next)
+    })?;
+    let _ = app.emit("settings://changed", &updated);
+    Ok(config)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn run_backup_now(jobs: State<'_, JobManager>, settings: State<'_, SettingsStore>) -> CommandResult<Job> {
+    start_backup(&jobs, &settings.get())
+}
+
+// Restic's snapshots, newest first; snapshots::list_snapshots lists the
+// filesystem ones
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn list_backup_snapshots(settings: State<'_, SettingsStore>) -> CommandResult<Vec<Snapshot>> {
+    let (_, restic) = config(&settings.get())?;
+    snapshots(&restic)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_last_backup_status(settings: State<'_, SettingsStore>) -> CommandResult<BackupStatus> {
+    let (config, restic) = config(&settings.get())?;
+    let snapshots = snapshots(&restic)?;
+    let snapshot_count = snapshots.len();
+    let last_snapshot = snapshots.into_iter().next();
+    let age_hours = last_snapshot
+        .as_ref()
+        .and_then(|s| chrono::DateTime::parse_from_rfc3339(&s.time).ok())
+        .map(|time| (chrono::Utc::now() - time.with_timezone(&chrono::Utc)).num_minutes() as f64 / 60.0);
+    Ok(BackupStatus {
+        repository: config.repository,
+        last_snapshot,
+        age_hours,
+        snapshot_count,
+    })
+}
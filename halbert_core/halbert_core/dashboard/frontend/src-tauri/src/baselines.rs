@@ -0,0 +1,263 @@
+// Configuration baselines for drift detection.
+//
+// A baseline is one snapshot of the machine's state, stored in SQLite as
+// JSON: each category maps a key (package, unit, user, ...) to a value
+// (version, uid, ...). Comparing takes a fresh snapshot and diffs the two
+// maps in memory. A category that couldn't be captured on either side is
+// reported as unavailable instead of as everything added or removed.
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use sysinfo::System;
+
+use crate::app::State;
+use crate::changes;
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::packages;
+use crate::services;
+
+// Kernel knobs worth noticing when they move
+const TRACKED_SYSCTLS: &[&str] = &[
+    "net.ipv4.ip_forward",
+    "net.ipv6.conf.all.forwarding",
+    "kernel.randomize_va_space",
+    "kernel.kptr_restrict",
+    "kernel.dmesg_restrict",
+    "net.ipv4.conf.all.rp_filter",
+    "net.ipv4.tcp_syncookies",
+    "vm.swappiness",
+];
+
+type Category = BTreeMap<String, String>;
+// Category name -> entries; categories that couldn't be captured are absent
+type Snapshot = BTreeMap<String, Category>;
+
+const CATEGORIES: &[&str] = &["packages", "services", "listening_ports", "users", "groups", "system"];
+
+#[derive(Serialize)]
+pub struct BaselineSummary {
+    pub id: i64,
+    pub name: String,
+    pub created_at: String,
+    // Entries per captured category
+    pub counts: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize)]
+pub struct Entry {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Serialize)]
+pub struct Change {
+    pub key: String,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Serialize, Default)]
+pub struct CategoryDiff {
+    pub added: Vec<Entry>,
+    pub removed: Vec<Entry>,
+    pub changed: Vec<Change>,
+}
+
+#[derive(Serialize)]
+pub struct BaselineComparison {
+    pub baseline: BaselineSummary,
+    pub compared_at: String,
+    pub categories: BTreeMap<String, CategoryDiff>,
+    // Missing from the baseline or from the current state
+    pub unavailable: Vec<String>,
+}
+
+// /etc/passwd and /etc/group are both name:x:id:...
+fn colon_file(path: &str, value: impl Fn(&[&str]) -> String) -> Option<Category> {
+    let text = std::fs::read_to_string(path).ok()?;
+    Some(
+        text.lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split(':').collect();
+                (fields.len() >= 4).then(|| (fields[0].to_string(), value(&fields)))
+            })
+            .collect(),
+    )
+}
+
+fn system_settings() -> Category {
+    let mut system = Category::new();
+    if let Some(kernel) = System::kernel_version() {
+        system.insert("kernel".to_string(), kernel);
+    }
+    if let Some(os) = System::long_os_version() {
+        system.insert("os".to_string(), os);
+    }
+    if let Some(hostname) = System::host_name() {
+        system.insert("hostname".to_string(), hostname);
+    }
+    let timezone = crate::timesync::probe_time_sync(0.0).timezone;
+    if let Some(timezone) = timezone {
+        system.insert("timezone".to_string(), timezone);
+    }
+    for key in TRACKED_SYSCTLS {
+        let path = format!("/proc/sys/{}", key.replace('.', "/"));
+        if let Ok(value) = std::fs::read_to_string(path) {
+            system.insert(format!("sysctl.{}", key), value.trim().to_string());
+        }
+    }
+    system
+}
+
+fn capture() -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    if let Some((manager, installed)) = packages::installed_packages() {
+        snapshot.insert("packages".to_string(), installed);
+        snapshot
+            .entry("system".to_string())
+            .or_default()
+            .insert("package_manager".to_string(), manager.to_string());
+    }
+    if let Some(units) = services::enabled_units() {
+        snapshot.insert(
+            "services".to_string(),
+            units.into_iter().map(|u| (u, "enabled".to_string())).collect(),
+        );
+    }
+    if let Ok(ports) = changes::listening_ports() {
+        snapshot.insert(
+            "listening_ports".to_string(),
+            ports.into_iter().map(|p| (p, "listening".to_string())).collect(),
+        );
+    }
+    // uid:gid:shell, so a changed shell or uid shows up as a change
+    if let Some(users) = colon_file("/etc/passwd", |f| format!("{}:{}:{}", f[2], f[3], f.get(6).unwrap_or(&""))) {
+        snapshot.insert("users".to_string(), users);
+    }
+    // gid:members
+    if let Some(groups) = colon_file("/etc/group", |f| format!("{}:{}", f[2], f[3])) {
+        snapshot.insert("groups".to_string(), groups);
+    }
+    snapshot.entry("system".to_string()).or_default().extend(system_settings());
+    snapshot
+}
+
+fn diff(before: &Category, after: &Category) -> CategoryDiff {
+    let mut result = CategoryDiff::default();
+    for (key, old) in before {
+        match after.get(key) {
+            None => result.removed.push(Entry {
+                key: key.clone(),
+                value: old.clone(),
+            }),
+            Some(new) if new != old => result.changed.push(Change {
+                key: key.clone(),
+                before: old.clone(),
+                after: new.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (key, new) in after {
+        if !before.contains_key(key) {
+            result.added.push(Entry {
+                key: key.clone(),
+                value: new.clone(),
+            });
+        }
+    }
+    result
+}
+
+fn summary(id: i64, name: String, created_at: String, snapshot: &Snapshot) -> BaselineSummary {
+    BaselineSummary {
+        id,
+        name,
+        created_at,
+        counts: snapshot.iter().map(|(k, v)| (k.clone(), v.len())).collect(),
+    }
+}
+
+fn load(db: &Database, id: i64) -> CommandResult<(BaselineSummary, Snapshot)> {
+    let row: Option<(String, String, String)> = db.with_conn(|conn| {
+        conn.query_row(
+            "SELECT name, created_at, snapshot FROM baselines WHERE id = ?1",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()
+    })?;
+    let (name, created_at, json) = row.ok_or_else(|| CommandError::NotFound(format!("baseline {}", id)))?;
+    let snapshot: Snapshot = serde_json::from_str(&json)
+        .map_err(|e| CommandError::Internal(format!("baseline {} is corrupt: {}", id, e)))?;
+    Ok((summary(id, name, created_at, &snapshot), snapshot))
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn create_baseline(db: State<'_, Database>, name: String) -> CommandResult<BaselineSummary> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(CommandError::InvalidInput("baseline name is required".to_string()));
+    }
+    let snapshot = capture();
+    let created_at = chrono::Utc::now().to_rfc3339();
+    let json = serde_json::to_string(&snapshot).map_err(|e| CommandError::Internal(e.to_string()))?;
+    let id = db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO baselines (name, created_at, snapshot) VALUES (?1, ?2, ?3)",
+            params![name, created_at, json],
+        )?;
+        Ok(conn.last_insert_rowid())
+    })?;
+    Ok(summary(id, name, created_at, &snapshot))
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn list_baselines(db: State<'_, Database>) -> CommandResult<Vec<BaselineSummary>> {
+    let rows: Vec<(i64, String, String, String)> = db.with_conn(|conn| {
+        let mut stmt = conn.prepare("SELECT id, name, created_at, snapshot FROM baselines ORDER BY id DESC")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?;
+        rows.collect()
+    })?;
+    Ok(rows
+        .into_iter()
+        .map(|(id, name, created_at, json)| {
+            let snapshot: Snapshot = serde_json::from_str(&json).unwrap_or_default();
+            summary(id, name, created_at, &snapshot)
+        })
+        .collect())
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn compare_baseline(db: State<'_, Database>, baseline_id: i64) -> CommandResult<BaselineComparison> {
+    let (baseline, before) = load(&db, baseline_id)?;
+    let after = capture();
+
+    let mut categories = BTreeMap::new();
+    let mut unavailable = Vec::new();
+    for category in CATEGORIES {
+        match (before.get(*category), after.get(*category)) {
+            (Some(old), Some(new)) => {
+                categories.insert(category.to_string(), diff(old, new));
+            }
+            _ => unavailable.push(category.to_string()),
+        }
+    }
+    Ok(BaselineComparison {
+        baseline,
+        compared_at: chrono::Utc::now().to_rfc3339(),
+        categories,
+        unavailable,
+    })
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn delete_baseline(db: State<'_, Database>, baseline_id: i64) -> CommandResult<()> {
+    let deleted = db.with_conn(|conn| conn.execute("DELETE FROM baselines WHERE id = ?1", params![baseline_id]))?;
+    if deleted == 0 {
+        return Err(CommandError::NotFound(format!("baseline {}", baseline_id)));
+    }
+    Ok(())
+}
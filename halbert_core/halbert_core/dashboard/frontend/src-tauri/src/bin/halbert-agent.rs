@@ -0,0 +1,3 @@
+fn main() {
+    std::process::exit(test_halbert_lib::agent::main())
+}
@@ -0,0 +1,240 @@
+// One invoke per panel per refresh.
+//
+// A bundle gathers what a panel shows from the same command functions the
+// panel would otherwise invoke one by one, called with their default
+// arguments, so a section is always what that command returns. Each section
+// is either `ok`, with the data and `stale_ms` (how old it is: the rate
+// limiter's cache age, or the age of a stored report; 0 when computed for
+// this call), or `failed` with the command's error, so one failing section
+// (the backend being down) doesn't fail the bundle.
+use serde::Serialize;
+
+use crate::app::{AppHandle, Manager, Runtime};
+use crate::approvals::{self, PendingApproval};
+use crate::backend::{self, BackendStatus};
+use crate::corpora::{self, CorpusInfo};
+use crate::corpus_health::{self, CorpusHealthReport};
+use crate::documents::{self, DocumentList};
+use crate::error::{CommandError, CommandResult};
+use crate::jobs::{self, JobList};
+use crate::ratelimit::Throttled;
+use crate::{MemoryStats, SystemMetrics};
+
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Section<T> {
+    Ok { data: T, stale_ms: u64 },
+    Failed { error: CommandError },
+}
+
+impl<T> Section<T> {
+    fn fresh(result: CommandResult<T>) -> Self {
+        Self::aged(result, |_| 0)
+    }
+
+    fn throttled(result: CommandResult<Throttled<T>>) -> Self {
+        match result {
+            Ok(throttled) => Section::Ok {
+                data: throttled.value,
+                stale_ms: throttled.age_ms,
+            },
+            Err(error) => Section::Failed { error },
+        }
+    }
+
+    fn aged(result: CommandResult<T>, age_ms: impl FnOnce(&T) -> u64) -> Self {
+        match result {
+            Ok(data) => Section::Ok {
+                stale_ms: age_ms(&data),
+                data,
+            },
+            Err(error) => Section::Failed { error },
+        }
+    }
+}
+
+// Milliseconds since an RFC 3339 timestamp; 0 if it doesn't parse or is
+// in the future
+pub fn age_ms(timestamp: &str, now: chrono::DateTime<chrono::Utc>) -> u64 {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map(|at| (now - at.with_timezone(&chrono::Utc)).num_milliseconds().max(0) as u64)
+        .unwrap_or(0)
+}
+
+#[derive(Serialize)]
+pub struct DashboardBundle {
+    pub generated_at: String,
+    pub metrics: Section<SystemMetrics>,
+    pub approvals: Section<Vec<PendingApproval>>,
+    pub jobs: Section<JobList>,
+    pub memory: Section<MemoryStats>,
+    pub backend: Section<BackendStatus>,
+}
+
+// The home view: get_system_metrics, get_pending_approvals, get_active_jobs,
+// get_memory_stats and get_backend_status, the last two side by side
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_dashboard_bundle<R: Runtime>(app: AppHandle<R>) -> DashboardBundle {
+    let metrics = crate::get_system_metrics(app.state(), app.state(), app.state(), app.state(), app.state());
+    let approvals = approvals::get_pending_approvals(app.state(), app.state());
+    let jobs = jobs::get_active_jobs(app.state(), app.state(), app.state(), None, None, None);
+    let (memory, backend) = tokio::join!(
+        crate::get_memory_stats(app.clone(), None),
+        backend::get_backend_status(app.state())
+    );
+    DashboardBundle {
+        generated_at: chrono::Utc::now().to_rfc3339(),
+        metrics: Section::throttled(metrics),
+        approvals: Section::fresh(approvals),
+        jobs: Section::fresh(jobs),
+        memory: Section::fresh(memory),
+        backend: Section::fresh(backend),
+    }
+}
+
+#[derive(Serialize)]
+pub struct MemoryPanelBundle {
+    pub generated_at: String,
+    pub stats: Section<MemoryStats>,
+    pub corpora: Section<Vec<CorpusInfo>>,
+    pub documents: Section<DocumentList>,
+    pub health: Section<CorpusHealthReport>,
+}
+
+// The memory view: get_memory_stats, list_corpora, get_documents and
+// get_corpus_health_report. `corpus` goes to the first and third as it
+// would to them.
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_memory_panel_bundle<R: Runtime>(app: AppHandle<R>, corpus: Option<String>) -> MemoryPanelBundle {
+    let stats = crate::get_memory_stats(app.clone(), corpus.clone()).await;
+    let corpora = corpora::list_corpora(app.state());
+    let documents = documents::get_documents(app.state(), app.state(), app.state(), None, corpus);
+    let health = corpus_health::get_corpus_health_report(app.state());
+    let now = chrono::Utc::now();
+    MemoryPanelBundle {
+        generated_at: now.to_rfc3339(),
+        stats: Section::fresh(stats),
+        corpora: Section::fresh(Ok(corpora)),
+        documents: Section::throttled(documents),
+        health: Section::aged(health, |report| age_ms(&report.generated_at, now)),
+    }
+}
+
+// Against a mock Tauri app, so only with the window
+#[cfg(all(test, feature = "gui"))]
+mod tests {
+    use super::*;
+    use crate::http::{MockServer, Received};
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // A mock app with everything the bundles read, its backend at `backend`
+    // and a one-document primary corpus
+    fn app(dir: &std::path::Path, backend: &MockServer) -> tauri::App<tauri::test::MockRuntime> {
+        let corpus = dir.join("corpus");
+        std::fs::create_dir_all(&corpus).unwrap();
+        std::fs::write(corpus.join("notes.md"), "# Notes\n").unwrap();
+        let settings = crate::settings::SettingsStore::load(dir.join("settings.toml"));
+        settings
+            .update(|current| {
+                let mut next = current.clone();
+                next.backend_url = backend.base_url.clone();
+                next.corpus_path = Some(corpus.display().to_string());
+                Ok(next)
+            })
+            .unwrap();
+        tauri::test::mock_builder()
+            .manage(settings)
+            .manage(crate::disk_history::DiskHistory::default())
+            .manage(crate::smart::SmartCache::default())
+            .manage(crate::ssh_hosts::SshHosts::new(dir.join("ssh")))
+            .manage(crate::ratelimit::RateLimiter::default())
+            .manage(crate::approvals::ApprovalStore::with_mock_requests())
+            .manage(jobs::JobManager::empty(crate::automation::Gate::load(dir.join("automation.json"))))
+            .manage(crate::job_poller::JobPoller::default())
+            .manage(crate::db::Database::in_memory())
+            .manage(corpora::IndexCache::default())
+            .manage(corpus_health::CorpusHealthStore::default())
+            .build(tauri::test::mock_context(tauri::test::noop_assets()))
+            .unwrap()
+    }
+
+    fn answer(request: &Received) -> (u16, Value) {
+        match request.path.as_str() {
+            "/api/status" => (200, json!({ "status": "ok" })),
+            _ => (200, json!({ "total_chunks": 12, "index_size_mb": 1.5 })),
+        }
+    }
+
+    // A section as the panel sees it, minus how old it is
+    fn section<T: Serialize>(section: &Section<T>) -> Value {
+        let mut value = serde_json::to_value(section).unwrap();
+        value.as_object_mut().unwrap().remove("stale_ms");
+        value
+    }
+
+    fn individually<T: Serialize>(result: CommandResult<T>) -> Value {
+        section(&Section::fresh(result))
+    }
+
+    #[test]
+    fn the_dashboard_bundle_matches_its_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = MockServer::start(answer);
+        let app = app(dir.path(), &backend);
+        let handle = app.handle().clone();
+        let bundle = tauri::async_runtime::block_on(get_dashboard_bundle(handle.clone()));
+
+        let metrics = crate::get_system_metrics(handle.state(), handle.state(), handle.state(), handle.state(), handle.state());
+        assert_eq!(section(&bundle.metrics), section(&Section::throttled(metrics)));
+        let approvals = approvals::get_pending_approvals(handle.state(), handle.state());
+        assert_eq!(section(&bundle.approvals), individually(approvals));
+        let jobs = jobs::get_active_jobs(handle.state(), handle.state(), handle.state(), None, None, None);
+        assert_eq!(section(&bundle.jobs), individually(jobs));
+        let memory = tauri::async_runtime::block_on(crate::get_memory_stats(handle.clone(), None));
+        assert_eq!(section(&bundle.memory), individually(memory));
+        assert_eq!(section(&bundle.memory)["data"]["total_documents"], 1);
+        let status = tauri::async_runtime::block_on(backend::get_backend_status(handle.state()));
+        assert_eq!(section(&bundle.backend), individually(status));
+        assert_eq!(section(&bundle.backend)["data"]["reachable"], true);
+    }
+
+    #[test]
+    fn the_memory_panel_bundle_matches_its_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = MockServer::start(answer);
+        let app = app(dir.path(), &backend);
+        let handle = app.handle().clone();
+        let corpus = Some(corpora::PRIMARY.to_string());
+        let bundle = tauri::async_runtime::block_on(get_memory_panel_bundle(handle.clone(), corpus.clone()));
+
+        let stats = tauri::async_runtime::block_on(crate::get_memory_stats(handle.clone(), corpus.clone()));
+        assert_eq!(section(&bundle.stats), individually(stats));
+        assert_eq!(section(&bundle.corpora), individually(Ok(corpora::list_corpora(handle.state()))));
+        let documents = documents::get_documents(handle.state(), handle.state(), handle.state(), None, corpus);
+        assert_eq!(section(&bundle.documents), section(&Section::throttled(documents)));
+        assert_eq!(section(&bundle.documents)["data"]["documents"].as_array().map(Vec::len), Some(1));
+        let health = corpus_health::get_corpus_health_report(handle.state());
+        assert_eq!(section(&bundle.health), individually(health));
+    }
+
+    #[test]
+    fn memory_and_backend_are_gathered_side_by_side() {
+        let dir = tempfile::tempdir().unwrap();
+        let (in_flight, most) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let (counting, highest) = (in_flight.clone(), most.clone());
+        let backend = MockServer::start(move |request| {
+            let now = counting.fetch_add(1, Ordering::SeqCst) + 1;
+            highest.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            counting.fetch_sub(1, Ordering::SeqCst);
+            answer(request)
+        });
+        let app = app(dir.path(), &backend);
+        let bundle = tauri::async_runtime::block_on(get_dashboard_bundle(app.handle().clone()));
+        assert_eq!(section(&bundle.backend)["status"], "ok");
+        assert_eq!(section(&bundle.memory)["status"], "ok");
+        assert_eq!(most.load(Ordering::SeqCst), 2, "the stats and status requests never overlapped");
+    }
+}
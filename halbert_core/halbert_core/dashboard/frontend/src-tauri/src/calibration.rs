@@ -0,0 +1,382 @@
+// How well approval confidence predicts good outcomes.
+//
+// Every decided request is kept in approval_decisions with its confidence.
+// Once an approved request has played out, `record_approval_outcome` notes
+// whether it was "successful", "caused_problem" or "unknown", and the
+// outcome is forwarded to the backend so the agent can learn from it.
+// Forwarding is retried every RETRY_INTERVAL until the backend takes it;
+// retrieval feedback (see retrieval_feedback) rides the same queue.
+// `get_confidence_report` buckets the history by confidence decile and by
+// task type: how often requests were approved, and how often approved ones
+// went well (unknown outcomes count toward neither). Approved requests
+// still without an outcome `outcome_prompt_days` after the decision are
+// listed once in a low-priority reminder.
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::app::{AppHandle, Emitter, Manager, State};
+use crate::approvals::ApprovalRequest;
+use crate::backend;
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::http::Endpoint;
+use crate::retrieval_feedback;
+use crate::settings::SettingsStore;
+
+pub const OUTCOMES: &[&str] = &["successful", "caused_problem", "unknown"];
+const RETRY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+// One forwarding pass at a time, so a retry and a fresh outcome can't both
+// send the same row
+static FORWARDING: Mutex<()> = Mutex::new(());
+
+// One decided request, as the report sees it
+#[derive(Clone, Debug)]
+pub struct Decision {
+    pub task_type: Option<String>,
+    pub confidence: f32,
+    pub status: String,
+    pub outcome: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct OutcomeStats {
+    pub requests: u32,
+    pub approved: u32,
+    pub successful: u32,
+    pub caused_problem: u32,
+    pub unknown: u32,
+    // Approved over requests
+    pub approval_rate: Option<f32>,
+    // Successful over approved requests with a known outcome
+    pub success_rate: Option<f32>,
+}
+
+impl OutcomeStats {
+    fn add(&mut self, decision: &Decision) {
+        self.requests += 1;
+        if !was_approved(&decision.status) {
+            return;
+        }
+        self.approved += 1;
+        match decision.outcome.as_deref() {
+            Some("successful") => self.successful += 1,
+            Some("caused_problem") => self.caused_problem += 1,
+            Some(_) => self.unknown += 1,
+            None => {}
+        }
+    }
+
+    fn finish(mut self) -> Self {
+        let ratio = |part: u32, whole: u32| (whole > 0).then(|| part as f32 / whole as f32);
+        self.approval_rate = ratio(self.approved, self.requests);
+        self.success_rate = ratio(self.successful, self.successful + self.caused_problem);
+        self
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ConfidenceBucket {
+    // Inclusive lower bound; the upper one is exclusive except for the last
+    pub min: f32,
+    pub max: f32,
+    pub stats: OutcomeStats,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct TaskTypeStats {
+    // "other" for requests that don't map to a job template
+    pub task_type: String,
+    pub stats: OutcomeStats,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ConfidenceReport {
+    pub overall: OutcomeStats,
+    // Always ten, lowest confidence first
+    pub buckets: Vec<ConfidenceBucket>,
+    pub task_types: Vec<TaskTypeStats>,
+    // Approved requests with no outcome recorded yet
+    pub awaiting_outcome: u32,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct OutcomeDue {
+    pub request_id: String,
+    pub task: String,
+    pub decided_at: String,
+}
+
+// "failed" means approved but the action itself errored
+fn was_approved(status: &str) -> bool {
+    matches!(status, "approved" | "failed")
+}
+
+// Decile of a confidence score; 1.0 falls in the top one
+pub fn bucket(confidence: f32) -> usize {
+    ((confidence.clamp(0.0, 1.0) * 10.0).floor() as usize).min(9)
+}
+
+pub fn summarize(decisions: &[Decision]) -> ConfidenceReport {
+    let mut overall = OutcomeStats::default();
+    let mut buckets = vec![OutcomeStats::default(); 10];
+    let mut task_types: BTreeMap<String, OutcomeStats> = BTreeMap::new();
+    let mut awaiting_outcome = 0;
+    for decision in decisions {
+        overall.add(decision);
+        buckets[bucket(decision.confidence)].add(decision);
+        let task_type = decision.task_type.clone().unwrap_or_else(|| "other".to_string());
+        task_types.entry(task_type).or_default().add(decision);
+        if was_approved(&decision.status) && decision.outcome.is_none() {
+            awaiting_outcome += 1;
+        }
+    }
+    ConfidenceReport {
+        overall: overall.finish(),
+        buckets: buckets
+            .into_iter()
+            .enumerate()
+            .map(|(i, stats)| ConfidenceBucket {
+                min: i as f32 / 10.0,
+                max: (i + 1) as f32 / 10.0,
+                stats: stats.finish(),
+            })
+            .collect(),
+        task_types: task_types
+            .into_iter()
+            .map(|(task_type, stats)| TaskTypeStats {
+                task_type,
+                stats: stats.finish(),
+            })
+            .collect(),
+        awaiting_outcome,
+    }
+}
+
+// Called with every decision; a later one (an approved action that then
+// failed) replaces the status
+pub fn record_decision(db: &Database, request: &ApprovalRequest) {
+    let Some(decided_at) = &request.decided_at else {
+        return;
+    };
+    let result = db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO approval_decisions
+                 (request_id, requested_at, task, task_type, confidence, risk_level, status, decided_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT (request_id, requested_at)
+             DO UPDATE SET status = excluded.status, decided_at = excluded.decided_at",
+            params![
+                request.id,
+                request.requested_at,
+                request.task,
+                request.task_type,
+                request.confidence,
+                request.risk_level,
+                request.status,
+                decided_at
+            ],
+        )
+        .map(|_| ())
+    });
+    if let Err(e) = result {
+        println!("[Halbert] Failed to store decision on {}: {}", request.id, e);
+    }
+}
+
+fn unforwarded_outcomes(db: &Database) -> CommandResult<Vec<(i64, Value)>> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, request_id, requested_at, task, task_type, confidence, risk_level, status,
+                    outcome, outcome_notes, outcome_at
+             FROM approval_decisions WHERE outcome IS NOT NULL AND forwarded = 0",
+        )?;
+        let rows = stmt.query_map([], |r| {
+            Ok((
+                r.get::<_, i64>(0)?,
+                json!({
+                    "request_id": r.get::<_, String>(1)?,
+                    "requested_at": r.get::<_, String>(2)?,
+                    "task": r.get::<_, String>(3)?,
+                    "task_type": r.get::<_, Option<String>>(4)?,
+                    "confidence": r.get::<_, f64>(5)?,
+                    "risk_level": r.get::<_, String>(6)?,
+                    "status": r.get::<_, String>(7)?,
+                    "outcome": r.get::<_, String>(8)?,
+                    "notes": r.get::<_, Option<String>>(9)?,
+                    "recorded_at": r.get::<_, Option<String>>(10)?,
+                }),
+            ))
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    })
+}
+
+// Posts each row to `path`, marking it forwarded in `table` once the
+// backend takes it. False at the first failure: the backend is likely down
+// and the rest can wait for the next pass.
+fn forward_rows(
+    db: &Database,
+    endpoint: &Endpoint,
+    what: &str,
+    table: &str,
+    path: &str,
+    rows: Vec<(i64, Value)>,
+) -> bool {
+    let taken = format!("UPDATE {} SET forwarded = 1, forward_attempts = forward_attempts + 1 WHERE id = ?1", table);
+    let failed = format!("UPDATE {} SET forward_attempts = forward_attempts + 1 WHERE id = ?1", table);
+    for (id, body) in rows {
+        let sent = endpoint.post_json::<Value>(path, &body);
+        let sql = if sent.is_ok() { &taken } else { &failed };
+        let _ = db.with_conn(|conn| conn.execute(sql, [id]).map(|_| ()));
+        if let Err(e) = sent {
+            println!("[Halbert] {} not forwarded yet, will retry: {}", what, e);
+            return false;
+        }
+    }
+    true
+}
+
+// Send every outcome and retrieval rating the backend hasn't acknowledged yet
+pub fn forward_pending(app: &AppHandle) {
+    let _guard = FORWARDING.lock().unwrap();
+    let db = app.state::<Database>();
+    let read = |what: &str, rows: CommandResult<Vec<(i64, Value)>>| {
+        rows.unwrap_or_else(|e| {
+            println!("[Halbert] Can't read {}: {}", what, e);
+            Vec::new()
+        })
+    };
+    let outcomes = read("approval outcomes", unforwarded_outcomes(&db));
+    let feedback = read("retrieval feedback", retrieval_feedback::unforwarded(&db));
+    if outcomes.is_empty() && feedback.is_empty() {
+        return;
+    }
+    let settings = app.state::<SettingsStore>().get();
+    let endpoint = backend::endpoint(&settings);
+    if forward_rows(&db, &endpoint, "Outcome", "approval_decisions", "/api/approvals/outcomes", outcomes) {
+        forward_rows(&db, &endpoint, "Retrieval feedback", "retrieval_feedback", "/api/rag/feedback", feedback);
+    }
+}
+
+fn remind(app: &AppHandle) -> CommandResult<()> {
+    let days = app.state::<SettingsStore>().get().outcome_prompt_days;
+    if days == 0 {
+        return Ok(());
+    }
+    let cutoff = (chrono::Utc::now() - chrono::Duration::days(days as i64)).to_rfc3339();
+    let now = chrono::Utc::now().to_rfc3339();
+    let db = app.state::<Database>();
+    let due = db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let due = {
+            let mut stmt = tx.prepare(
+                "SELECT request_id, task, decided_at FROM approval_decisions
+                 WHERE outcome IS NULL AND prompted_at IS NULL AND status IN ('approved', 'failed')
+                   AND decided_at < ?1
+                 ORDER BY decided_at",
+            )?;
+            let rows = stmt.query_map([&cutoff], |r| {
+                Ok(OutcomeDue {
+                    request_id: r.get(0)?,
+                    task: r.get(1)?,
+                    decided_at: r.get(2)?,
+                })
+            })?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        tx.execute(
+            "UPDATE approval_decisions SET prompted_at = ?1
+             WHERE outcome IS NULL AND prompted_at IS NULL AND status IN ('approved', 'failed')
+               AND decided_at < ?2",
+            params![now, cutoff],
+        )?;
+        tx.commit()?;
+        Ok(due)
+    })?;
+    if !due.is_empty() {
+        println!("[Halbert] {} approved request(s) have no recorded outcome", due.len());
+        let _ = app.emit("approvals://outcomes-due", &due);
+        crate::notifications::approval_outcomes_due(app, &due);
+    }
+    Ok(())
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        forward_pending(&app);
+        if let Err(e) = remind(&app) {
+            println!("[Halbert] Outcome reminder failed: {}", e);
+        }
+        std::thread::sleep(RETRY_INTERVAL);
+    });
+}
+
+// Applies to the latest decided request with this id (ids restart with
+// the app); rejected requests have no outcome to record
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn record_approval_outcome(
+    app: AppHandle,
+    db: State<'_, Database>,
+    request_id: String,
+    outcome: String,
+    notes: Option<String>,
+) -> CommandResult<()> {
+    if !OUTCOMES.contains(&outcome.as_str()) {
+        return Err(CommandError::InvalidInput(format!(
+            "unknown outcome '{}', expected one of {}",
+            outcome,
+            OUTCOMES.join(", ")
+        )));
+    }
+    let notes = notes.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    let latest: Option<(i64, String)> = db.with_conn(|conn| {
+        conn.query_row(
+            "SELECT id, status FROM approval_decisions WHERE request_id = ?1
+             ORDER BY requested_at DESC LIMIT 1",
+            [&request_id],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .optional()
+    })?;
+    let (id, status) = latest.ok_or_else(|| CommandError::NotFound(format!("decided request {}", request_id)))?;
+    if !was_approved(&status) {
+        return Err(CommandError::Conflict(format!(
+            "request {} was {}; only approved requests have outcomes",
+            request_id, status
+        )));
+    }
+    // A corrected outcome is forwarded again
+    db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE approval_decisions
+             SET outcome = ?1, outcome_notes = ?2, outcome_at = ?3, forwarded = 0
+             WHERE id = ?4",
+            params![outcome, notes, chrono::Utc::now().to_rfc3339(), id],
+        )
+        .map(|_| ())
+    })?;
+    println!("[Halbert] Outcome of {}: {}", request_id, outcome);
+    std::thread::spawn(move || forward_pending(&app));
+    Ok(())
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_confidence_report(db: State<'_, Database>) -> CommandResult<ConfidenceReport> {
+    let decisions = db.with_conn(|conn| {
+        let mut stmt = conn.prepare("SELECT task_type, confidence, status, outcome FROM approval_decisions")?;
+        let rows = stmt.query_map([], |r| {
+            Ok(Decision {
+                task_type: r.get(0)?,
+                confidence: r.get::<_, f64>(1)? as f32,
+                status: r.get(2)?,
+                outcome: r.get(3)?,
+            })
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    })?;
+    Ok(summarize(&decisions))
+}
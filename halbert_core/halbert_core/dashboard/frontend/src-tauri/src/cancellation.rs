@@ -0,0 +1,224 @@
+// Cancelling slow commands the user has stopped waiting for.
+//
+// A command that can take seconds takes an optional `operation_id` made up
+// by the frontend and registers it here for as long as it runs; its work
+// checks the token at each step (a directory entry, a file chunk, a probe)
+// and stops with CommandError::Cancelled, carrying whatever partial result
+// makes sense. cancel_operation trips the token. An id that has finished,
+// or was never used, cancels nothing and isn't an error. Commands that run
+// as jobs keep the registration until the job ends, and a cancelled job
+// fails instead of returning the error. Blocking lookups inside the work go
+// through `bounded`, which polls the token while it waits, so a cancel is
+// noticed within CHECK_INTERVAL even in the middle of one.
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::app::State;
+use crate::error::{CommandError, CommandResult};
+
+pub const CHECK_INTERVAL: Duration = Duration::from_millis(50);
+const MAX_ID_LEN: usize = 128;
+
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    // Err(Cancelled) once tripped; `partial` is only built then
+    pub fn check<F>(&self, what: &str, partial: F) -> CommandResult<()>
+    where
+        F: FnOnce() -> Option<serde_json::Value>,
+    {
+        match self.is_cancelled() {
+            false => Ok(()),
+            true => Err(CommandError::Cancelled {
+                message: format!("{} was cancelled", what),
+                partial: partial(),
+            }),
+        }
+    }
+}
+
+// Like exec::bounded, but also gives up as soon as the token is tripped
+pub fn bounded<T, F>(deadline: Instant, token: &CancellationToken, work: F) -> Option<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(work());
+    });
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() || token.is_cancelled() {
+            return None;
+        }
+        match rx.recv_timeout(left.min(CHECK_INTERVAL)) {
+            Ok(value) => return Some(value),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => return None,
+        }
+    }
+}
+
+type Running = Arc<Mutex<HashMap<String, CancellationToken>>>;
+
+#[derive(Default)]
+pub struct Operations {
+    running: Running,
+}
+
+// Holds the id registered until dropped
+pub struct Operation {
+    id: Option<String>,
+    token: CancellationToken,
+    running: Running,
+}
+
+impl Operation {
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        if let Some(id) = &self.id {
+            let mut running = self.running.lock().unwrap();
+            if running.get(id).is_some_and(|t| Arc::ptr_eq(&t.0, &self.token.0)) {
+                running.remove(id);
+            }
+        }
+    }
+}
+
+impl Operations {
+    // Without an id the token can't be tripped from outside, but the work
+    // doesn't need to care
+    pub fn start(&self, id: Option<String>) -> CommandResult<Operation> {
+        let token = CancellationToken::default();
+        if let Some(id) = &id {
+            if id.trim().is_empty() || id.len() > MAX_ID_LEN {
+                return Err(CommandError::InvalidInput(format!(
+                    "operation ids are 1 to {} characters",
+                    MAX_ID_LEN
+                )));
+            }
+            let mut running = self.running.lock().unwrap();
+            if running.contains_key(id) {
+                return Err(CommandError::Conflict(format!("operation '{}' is already running", id)));
+            }
+            running.insert(id.clone(), token.clone());
+        }
+        Ok(Operation {
+            id,
+            token,
+            running: self.running.clone(),
+        })
+    }
+
+    // False when nothing by that id is running
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.running.lock().unwrap().get(id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct CancelResult {
+    pub operation_id: String,
+    // False when it had already finished or never started
+    pub cancelled: bool,
+}
+
+// Longest a test lets cancelled work run on, a few CHECK_INTERVALs
+#[cfg(test)]
+pub const NOTICED_WITHIN: Duration = Duration::from_millis(250);
+
+// Runs `work` and cancels operation `id` a moment after it starts; what it
+// returned and how long it ran on after the cancel
+#[cfg(test)]
+pub fn cancel_during<T>(operations: &Operations, id: &str, work: impl FnOnce() -> T) -> (T, Duration) {
+    std::thread::scope(|scope| {
+        let canceller = scope.spawn(|| {
+            std::thread::sleep(Duration::from_millis(100));
+            assert!(operations.cancel(id), "operation {} wasn't running", id);
+            Instant::now()
+        });
+        let value = work();
+        let finished = Instant::now();
+        let cancelled = canceller.join().unwrap();
+        (value, finished.saturating_duration_since(cancelled))
+    })
+}
+
+// A directory whose `**` walk never ends: every level links back to itself
+#[cfg(all(test, unix))]
+pub fn endless_tree(dir: &std::path::Path) -> std::path::PathBuf {
+    let root = dir.join("endless");
+    std::fs::create_dir(&root).unwrap();
+    std::os::unix::fs::symlink(".", root.join("a")).unwrap();
+    std::os::unix::fs::symlink(".", root.join("b")).unwrap();
+    root
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn cancel_operation(operations: State<'_, Operations>, operation_id: String) -> CancelResult {
+    let cancelled = operations.cancel(&operation_id);
+    if cancelled {
+        println!("[Halbert] Cancelling operation {}", operation_id);
+    }
+    CancelResult {
+        operation_id,
+        cancelled,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounded_gives_up_on_a_cancel_mid_wait() {
+        let operations = Operations::default();
+        let operation = operations.start(Some("slow".to_string())).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(30);
+        let (value, overran) = cancel_during(&operations, "slow", || {
+            bounded(deadline, operation.token(), || std::thread::sleep(Duration::from_secs(5)))
+        });
+        assert!(value.is_none());
+        assert!(overran < NOTICED_WITHIN, "{:?}", overran);
+    }
+
+    #[test]
+    fn an_id_cancels_only_while_it_runs() {
+        let operations = Operations::default();
+        assert!(!operations.cancel("late"));
+        let operation = operations.start(Some("late".to_string())).unwrap();
+        assert!(matches!(operations.start(Some("late".to_string())), Err(CommandError::Conflict(_))));
+        assert!(operations.start(Some(" ".to_string())).is_err());
+        assert!(operations.cancel("late"));
+        assert!(matches!(operation.token().check("Late", || None), Err(CommandError::Cancelled { .. })));
+        drop(operation);
+        assert!(!operations.cancel("late"));
+        // Without an id nothing can reach it
+        let anonymous = operations.start(None).unwrap();
+        assert!(anonymous.token().check("Anonymous", || None).is_ok());
+    }
+}
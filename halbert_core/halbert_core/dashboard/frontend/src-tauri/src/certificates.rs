@@ -0,0 +1,334 @@
+// TLS certificate expiry for configured host:port targets.
+//
+// The handshake accepts any certificate so self-signed and expired ones
+// can still be inspected; whether the chain would have validated against
+// the system roots is recorded alongside. Results are stored in SQLite so
+// `get_certificate_status` doesn't touch the network, and refreshed daily.
+use rusqlite::{params, OptionalExtension};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use serde::{Deserialize, Serialize};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use x509_parser::prelude::*;
+
+use crate::app::{AppHandle, Emitter, Manager, State};
+use crate::collectors::{self, CollectorRegistry};
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::settings::SettingsStore;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+// Upper bound per target, covering DNS which has no timeout of its own
+const TARGET_DEADLINE: Duration = Duration::from_secs(15);
+const SCAN_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CertificateResult {
+    pub target: String,
+    pub checked_at: String,
+    // Set when no certificate could be fetched at all
+    pub error: Option<String>,
+    pub subject: Option<String>,
+    pub issuer: Option<String>,
+    pub sans: Vec<String>,
+    pub not_after: Option<String>,
+    pub days_remaining: Option<i64>,
+    pub chain_valid: bool,
+    pub validation_error: Option<String>,
+}
+
+// Accepts every certificate but remembers what the real verifier said
+#[derive(Debug)]
+struct RecordingVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    outcome: Mutex<Option<Result<(), String>>>,
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let result = self
+            .inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+            .map(|_| ())
+            .map_err(|e| e.to_string());
+        *self.outcome.lock().unwrap() = Some(result);
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+// "example.com:443" -> ("example.com", 443); "[::1]:8443" -> ("::1", 8443)
+fn parse_target(target: &str) -> CommandResult<(String, u16)> {
+    let invalid = || CommandError::InvalidInput(format!("'{}' is not host:port", target));
+    let (host, port) = target.trim().rsplit_once(':').ok_or_else(invalid)?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port: u16 = port.parse().map_err(|_| invalid())?;
+    if host.is_empty() || port == 0 {
+        return Err(invalid());
+    }
+    Ok((host.to_string(), port))
+}
+
+fn root_store() -> RootCertStore {
+    let mut roots = RootCertStore::empty();
+    match rustls_native_certs::load_native_certs() {
+        Ok(certs) => {
+            roots.add_parsable_certificates(certs);
+        }
+        Err(e) => println!("[Halbert] Couldn't load system CA certificates: {}", e),
+    }
+    roots
+}
+
+fn fetch(host: &str, port: u16) -> Result<(Vec<CertificateDer<'static>>, Result<(), String>), String> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let inner = WebPkiServerVerifier::builder_with_provider(Arc::new(root_store()), provider.clone())
+        .build()
+        .map_err(|e| e.to_string())?;
+    let verifier = Arc::new(RecordingVerifier {
+        inner,
+        outcome: Mutex::new(None),
+    });
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(verifier.clone())
+        .with_no_client_auth();
+
+    let server_name = ServerName::try_from(host.to_string()).map_err(|e| format!("invalid host name: {}", e))?;
+    let mut conn = ClientConnection::new(Arc::new(config), server_name).map_err(|e| e.to_string())?;
+
+    let addr = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("can't resolve {}: {}", host, e))?
+        .next()
+        .ok_or_else(|| format!("{} has no addresses", host))?;
+    let mut sock = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).map_err(|e| e.to_string())?;
+    sock.set_read_timeout(Some(IO_TIMEOUT)).map_err(|e| e.to_string())?;
+    sock.set_write_timeout(Some(IO_TIMEOUT)).map_err(|e| e.to_string())?;
+
+    while conn.is_handshaking() {
+        conn.complete_io(&mut sock).map_err(|e| format!("TLS handshake failed: {}", e))?;
+    }
+    let chain = conn
+        .peer_certificates()
+        .map(|certs| certs.iter().map(|c| c.clone().into_owned()).collect())
+        .unwrap_or_default();
+    let validation = verifier
+        .outcome
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| Err("server sent no certificate".to_string()));
+    Ok((chain, validation))
+}
+
+fn describe(result: &mut CertificateResult, leaf: &CertificateDer<'_>) -> Result<(), String> {
+    let (_, cert) = X509Certificate::from_der(leaf.as_ref()).map_err(|e| format!("unparseable certificate: {}", e))?;
+    result.subject = Some(cert.subject().to_string());
+    result.issuer = Some(cert.issuer().to_string());
+
+    let not_after = cert.validity().not_after.timestamp();
+    result.not_after = chrono::DateTime::from_timestamp(not_after, 0).map(|t| t.to_rfc3339());
+    result.days_remaining = Some((not_after - chrono::Utc::now().timestamp()).div_euclid(86_400));
+
+    if let Ok(Some(san)) = cert.subject_alternative_name() {
+        result.sans = san
+            .value
+            .general_names
+            .iter()
+            .filter_map(|name| match name {
+                GeneralName::DNSName(dns) => Some(dns.to_string()),
+                GeneralName::IPAddress(bytes) => match bytes.len() {
+                    4 => Some(std::net::Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).to_string()),
+                    16 => <[u8; 16]>::try_from(*bytes)
+                        .ok()
+                        .map(|b| std::net::Ipv6Addr::from(b).to_string()),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+    }
+    Ok(())
+}
+
+pub fn check_target(target: &str) -> CertificateResult {
+    let mut result = CertificateResult {
+        target: target.to_string(),
+        checked_at: chrono::Utc::now().to_rfc3339(),
+        ..Default::default()
+    };
+    let (host, port) = match parse_target(target) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            result.error = Some(e.to_string());
+            return result;
+        }
+    };
+
+    let fetched = exec::bounded(Instant::now() + TARGET_DEADLINE, move || fetch(&host, port))
+        .unwrap_or_else(|| Err("timed out".to_string()));
+    match fetched {
+        Ok((chain, validation)) => {
+            result.chain_valid = validation.is_ok();
+            result.validation_error = validation.err();
+            match chain.first() {
+                Some(leaf) => {
+                    if let Err(e) = describe(&mut result, leaf) {
+                        result.error = Some(e);
+                    }
+                }
+                None => result.error = Some("server sent no certificate".to_string()),
+            }
+        }
+        Err(e) => result.error = Some(e),
+    }
+    result
+}
+
+fn save(db: &Database, results: &[CertificateResult]) -> CommandResult<()> {
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        for result in results {
+            let json = serde_json::to_string(result).unwrap_or_default();
+            tx.execute(
+                "INSERT INTO certificate_results (target, checked_at, result) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(target) DO UPDATE SET checked_at = excluded.checked_at, result = excluded.result",
+                params![result.target, result.checked_at, json],
+            )?;
+        }
+        tx.commit()
+    })
+}
+
+// Stored results for the configured targets; targets never scanned are left out
+pub fn stored_results(db: &Database, targets: &[String]) -> CommandResult<Vec<CertificateResult>> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare("SELECT result FROM certificate_results WHERE target = ?1")?;
+        let mut results = Vec::new();
+        for target in targets {
+            let json: Option<String> = stmt.query_row(params![target], |row| row.get(0)).optional()?;
+            if let Some(result) = json.and_then(|j| serde_json::from_str(&j).ok()) {
+                results.push(result);
+            }
+        }
+        Ok(results)
+    })
+}
+
+fn scan(app: &AppHandle) -> CommandResult<Vec<CertificateResult>> {
+    let targets = app.state::<SettingsStore>().get().certificate_targets;
+    // One thread per target so a dead host only costs its own deadline
+    let handles: Vec<_> = targets
+        .into_iter()
+        .map(|target| std::thread::spawn(move || check_target(&target)))
+        .collect();
+    let results: Vec<CertificateResult> = handles.into_iter().filter_map(|h| h.join().ok()).collect();
+
+    save(&app.state::<Database>(), &results)?;
+    let _ = app.emit("certificates://scanned", &results);
+    Ok(results)
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        let registry = app.state::<CollectorRegistry>();
+        registry.wait_enabled(&app, "certificates");
+        if let Err(e) = registry.run("certificates", || scan(&app)) {
+            println!("[Halbert] Certificate scan failed: {}", e);
+        }
+        let settings = app.state::<SettingsStore>().get();
+        registry.sleep(Duration::from_secs(collectors::interval_secs(
+            &settings,
+            "certificates",
+            SCAN_INTERVAL.as_secs(),
+        )));
+    });
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn scan_certificates(app: AppHandle) -> CommandResult<Vec<CertificateResult>> {
+    scan(&app)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_certificate_status(
+    settings: State<'_, SettingsStore>,
+    db: State<'_, Database>,
+) -> CommandResult<Vec<CertificateResult>> {
+    stored_results(&db, &settings.get().certificate_targets)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn add_certificate_target(settings: State<'_, SettingsStore>, target: String) -> CommandResult<Vec<String>> {
+    let (host, port) = parse_target(&target)?;
+    // Keep one canonical spelling so duplicates are caught
+    let target = if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host.to_lowercase(), port)
+    };
+    let updated = settings.update(|current| {
+        if current.certificate_targets.contains(&target) {
+            return Err(CommandError::Conflict(format!("{} is already a target", target)));
+        }
+        let mut next = current.clone();
+        next.certificate_targets.push(target.clone());
+        Ok(next)
+    })?;
+    Ok(updated.certificate_targets)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn remove_certificate_target(
+    settings: State<'_, SettingsStore>,
+    db: State<'_, Database>,
+    target: String,
+) -> CommandResult<Vec<String>> {
+    let updated = settings.update(|current| {
+        if !current.certificate_targets.contains(&target) {
+            return Err(CommandError::NotFound(format!("certificate target {}", target)));
+        }
+        let mut next = current.clone();
+        next.certificate_targets.retain(|t| t != &target);
+        Ok(next)
+    })?;
+    db.with_conn(|conn| conn.execute("DELETE FROM certificate_results WHERE target = ?1", params![target]))?;
+    Ok(updated.certificate_targets)
+}
@@ -0,0 +1,380 @@
+// "What changed since ..." digest.
+//
+// Each section is gathered on its own and reports `available: false` with
+// a reason instead of failing the whole summary, so a box without dpkg
+// still gets its service and reboot history. New listening ports are
+// judged against snapshots a background task takes every hour.
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::time::Duration;
+use sysinfo::System;
+
+use crate::app::{AppHandle, Manager, State};
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::network;
+
+const TOP_ITEMS: usize = 20;
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const SNAPSHOT_RETENTION_DAYS: i64 = 35;
+// systemd's "unit entered failed state" journal message
+const UNIT_FAILED_MESSAGE_ID: &str = "d9b373ed55a64feb8242e02dbe79a49c";
+
+#[derive(Serialize)]
+pub struct Section<T> {
+    pub available: bool,
+    // Why the section is unavailable
+    pub error: Option<String>,
+    pub count: usize,
+    // Most recent first, at most TOP_ITEMS
+    pub items: Vec<T>,
+}
+
+impl<T> Section<T> {
+    fn from_result(result: Result<Vec<T>, String>) -> Self {
+        match result {
+            Ok(mut items) => {
+                let count = items.len();
+                items.truncate(TOP_ITEMS);
+                Section {
+                    available: true,
+                    error: None,
+                    count,
+                    items,
+                }
+            }
+            Err(e) => Section {
+                available: false,
+                error: Some(e),
+                count: 0,
+                items: Vec::new(),
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PackageChange {
+    pub name: String,
+    // "install" or "upgrade"
+    pub action: String,
+    pub version: Option<String>,
+    pub at: String,
+}
+
+#[derive(Serialize)]
+pub struct FailedService {
+    pub unit: String,
+    pub failures: usize,
+    pub last_failed_at: String,
+}
+
+#[derive(Serialize)]
+pub struct BootRecord {
+    pub boot_id: String,
+    pub started_at: String,
+}
+
+#[derive(Serialize)]
+pub struct AddedUser {
+    pub name: String,
+    pub at: String,
+}
+
+#[derive(Serialize)]
+pub struct ChangeSummary {
+    pub since: String,
+    pub generated_at: String,
+    pub packages: Section<PackageChange>,
+    pub failed_services: Section<FailedService>,
+    // "tcp 0.0.0.0:8080" style listeners absent from the baseline
+    pub new_listening_ports: Section<String>,
+    pub users_added: Section<AddedUser>,
+    pub reboots: Section<BootRecord>,
+}
+
+type Since = chrono::DateTime<chrono::Utc>;
+
+pub fn parse_since(since: &str) -> CommandResult<Since> {
+    if since.trim() == "boot" {
+        return chrono::DateTime::from_timestamp(System::boot_time() as i64, 0)
+            .ok_or_else(|| CommandError::Internal("boot time is unknown".to_string()));
+    }
+    chrono::DateTime::parse_from_rfc3339(since.trim())
+        .map(|t| t.with_timezone(&chrono::Utc))
+        .map_err(|e| CommandError::InvalidInput(format!("since must be RFC3339 or \"boot\": {}", e)))
+}
+
+fn rfc3339_from_micros(micros: i64) -> String {
+    chrono::DateTime::from_timestamp_micros(micros)
+        .map(|t| t.to_rfc3339())
+        .unwrap_or_default()
+}
+
+// --- Packages ---
+
+// "2024-05-01 10:00:00 upgrade curl:amd64 8.5.0-2 8.5.0-3"
+fn parse_dpkg_log(text: &str, since: Since) -> Vec<PackageChange> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (date, time, action, package) = (fields.first()?, fields.get(1)?, fields.get(2)?, fields.get(3)?);
+            if *action != "install" && *action != "upgrade" {
+                return None;
+            }
+            let at = chrono::NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M:%S")
+                .ok()?
+                .and_local_timezone(chrono::Local)
+                .earliest()?
+                .with_timezone(&chrono::Utc);
+            if at < since {
+                return None;
+            }
+            Some(PackageChange {
+                name: package.split(':').next().unwrap_or(package).to_string(),
+                action: action.to_string(),
+                version: fields.get(5).map(|v| v.to_string()),
+                at: at.to_rfc3339(),
+            })
+        })
+        .collect()
+}
+
+// "2024-05-01T10:00:00+0000 SUBDEBUG Upgraded: curl-8.5.0-3.fc40.x86_64"
+fn parse_dnf_rpm_log(text: &str, since: Since) -> Vec<PackageChange> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let at = chrono::DateTime::parse_from_str(fields.next()?, "%Y-%m-%dT%H:%M:%S%z")
+                .ok()?
+                .with_timezone(&chrono::Utc);
+            let _level = fields.next()?;
+            let action = match fields.next()? {
+                "Installed:" => "install",
+                "Upgraded:" => "upgrade",
+                _ => return None,
+            };
+            if at < since {
+                return None;
+            }
+            // name-version-release.arch; the name itself may contain dashes
+            let nevra = fields.next()?;
+            let mut parts = nevra.rsplitn(3, '-');
+            let release = parts.next()?;
+            let version = parts.next()?;
+            let name = parts.next()?;
+            Some(PackageChange {
+                name: name.to_string(),
+                action: action.to_string(),
+                version: Some(format!("{}-{}", version, release)),
+                at: at.to_rfc3339(),
+            })
+        })
+        .collect()
+}
+
+fn package_changes(since: Since) -> Result<Vec<PackageChange>, String> {
+    // Current log first, then the last rotation
+    let read = |paths: &[&str]| -> Option<String> {
+        let texts: Vec<String> = paths.iter().filter_map(|p| std::fs::read_to_string(p).ok()).collect();
+        (!texts.is_empty()).then(|| texts.join("\n"))
+    };
+    let mut changes = if let Some(text) = read(&["/var/log/dpkg.log", "/var/log/dpkg.log.1"]) {
+        parse_dpkg_log(&text, since)
+    } else if let Some(text) = read(&["/var/log/dnf.rpm.log"]) {
+        parse_dnf_rpm_log(&text, since)
+    } else {
+        return Err("no dpkg or dnf log found".to_string());
+    };
+    changes.sort_by(|a, b| b.at.cmp(&a.at));
+    Ok(changes)
+}
+
+// --- Journal-backed sections ---
+
+fn journal_entries(since: Since, matches: &[&str]) -> Result<Vec<Value>, String> {
+    if exec::find_in_path("journalctl").is_none() {
+        return Err("journalctl is not installed".to_string());
+    }
+    let since_arg = format!("--since=@{}", since.timestamp());
+    let mut args = vec!["-q", "--no-pager", "-o", "json", since_arg.as_str()];
+    args.extend_from_slice(matches);
+    let out = exec::stdout("journalctl", &args).ok_or_else(|| "journalctl failed".to_string())?;
+    Ok(out.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+}
+
+fn field<'a>(entry: &'a Value, name: &str) -> Option<&'a str> {
+    entry.get(name).and_then(Value::as_str)
+}
+
+fn entry_time(entry: &Value) -> String {
+    field(entry, "__REALTIME_TIMESTAMP")
+        .and_then(|t| t.parse().ok())
+        .map(rfc3339_from_micros)
+        .unwrap_or_default()
+}
+
+fn failed_services(since: Since) -> Result<Vec<FailedService>, String> {
+    let match_arg = format!("MESSAGE_ID={}", UNIT_FAILED_MESSAGE_ID);
+    let mut by_unit: BTreeMap<String, FailedService> = BTreeMap::new();
+    for entry in journal_entries(since, &[&match_arg])? {
+        let Some(unit) = field(&entry, "UNIT").or_else(|| field(&entry, "USER_UNIT")) else {
+            continue;
+        };
+        let at = entry_time(&entry);
+        let failed = by_unit.entry(unit.to_string()).or_insert_with(|| FailedService {
+            unit: unit.to_string(),
+            failures: 0,
+            last_failed_at: String::new(),
+        });
+        failed.failures += 1;
+        if at > failed.last_failed_at {
+            failed.last_failed_at = at;
+        }
+    }
+    let mut services: Vec<FailedService> = by_unit.into_values().collect();
+    services.sort_by(|a, b| b.last_failed_at.cmp(&a.last_failed_at));
+    Ok(services)
+}
+
+// useradd logs "new user: name=alice, UID=1001, GID=1001, ..."
+fn users_added(since: Since) -> Result<Vec<AddedUser>, String> {
+    let mut users: Vec<AddedUser> = journal_entries(since, &["SYSLOG_IDENTIFIER=useradd"])?
+        .iter()
+        .filter_map(|entry| {
+            let message = field(entry, "MESSAGE")?;
+            let rest = message.strip_prefix("new user: name=")?;
+            Some(AddedUser {
+                name: rest.split(',').next()?.trim().to_string(),
+                at: entry_time(entry),
+            })
+        })
+        .collect();
+    users.sort_by(|a, b| b.at.cmp(&a.at));
+    Ok(users)
+}
+
+fn reboots(since: Since) -> Result<Vec<BootRecord>, String> {
+    if exec::find_in_path("journalctl").is_none() {
+        return Err("journalctl is not installed".to_string());
+    }
+    let out = exec::stdout("journalctl", &["--list-boots", "--no-pager", "-q", "-o", "json"])
+        .ok_or_else(|| "journalctl --list-boots failed".to_string())?;
+    let boots: Vec<Value> =
+        serde_json::from_str(&out).map_err(|_| "this journalctl can't list boots as JSON".to_string())?;
+    let since_micros = since.timestamp_micros();
+    let mut records: Vec<(i64, BootRecord)> = boots
+        .iter()
+        .filter_map(|boot| {
+            let first = boot.get("first_entry").and_then(Value::as_i64)?;
+            (first >= since_micros).then(|| {
+                (
+                    first,
+                    BootRecord {
+                        boot_id: field(boot, "boot_id").unwrap_or_default().to_string(),
+                        started_at: rfc3339_from_micros(first),
+                    },
+                )
+            })
+        })
+        .collect();
+    records.sort_by_key(|(first, _)| std::cmp::Reverse(*first));
+    Ok(records.into_iter().map(|(_, record)| record).collect())
+}
+
+// --- Listening ports ---
+
+// `ss -Hltnu`: Netid State Recv-Q Send-Q Local:Port Peer:Port ...
+// Without ss the /proc/net tables give the same "tcp [::]:22" form.
+pub fn listening_ports() -> Result<BTreeSet<String>, String> {
+    let Some(out) = exec::stdout("ss", &["-H", "-l", "-t", "-n", "-u"]) else {
+        let ports = network::listening_ports();
+        if ports.is_empty() && !Path::new("/proc/net/tcp").exists() {
+            return Err("neither ss nor /proc/net is available".to_string());
+        }
+        return Ok(ports.iter().map(network::format_listener).collect());
+    };
+    Ok(out
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            Some(format!("{} {}", fields.first()?, fields.get(4)?))
+        })
+        .collect())
+}
+
+fn snapshot_ports(db: &Database) -> CommandResult<()> {
+    let ports = listening_ports().map_err(CommandError::NotSupported)?;
+    let at = chrono::Utc::now().timestamp();
+    let json = serde_json::to_string(&ports).unwrap_or_default();
+    db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO listening_port_snapshots (at, ports) VALUES (?1, ?2)",
+            params![at, json],
+        )?;
+        conn.execute(
+            "DELETE FROM listening_port_snapshots WHERE at < ?1",
+            params![at - SNAPSHOT_RETENTION_DAYS * 86_400],
+        )?;
+        Ok(())
+    })
+}
+
+// The last snapshot from before `since`, else the oldest one we have
+fn baseline(db: &Database, since: Since) -> CommandResult<Option<BTreeSet<String>>> {
+    let json: Option<String> = db.with_conn(|conn| {
+        let before = conn
+            .query_row(
+                "SELECT ports FROM listening_port_snapshots WHERE at <= ?1 ORDER BY at DESC LIMIT 1",
+                params![since.timestamp()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match before {
+            Some(json) => Ok(Some(json)),
+            None => conn
+                .query_row(
+                    "SELECT ports FROM listening_port_snapshots ORDER BY at ASC LIMIT 1",
+                    [],
+                    |row| row.get(0),
+                )
+                .optional(),
+        }
+    })?;
+    Ok(json.and_then(|j| serde_json::from_str(&j).ok()))
+}
+
+fn new_listening_ports(db: &Database, since: Since) -> Result<Vec<String>, String> {
+    let baseline = baseline(db, since)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "no port baseline has been recorded yet".to_string())?;
+    let current = listening_ports()?;
+    Ok(current.difference(&baseline).cloned().collect())
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        if let Err(e) = snapshot_ports(&app.state::<Database>()) {
+            println!("[Halbert] Listening port snapshot failed: {}", e);
+        }
+        std::thread::sleep(SNAPSHOT_INTERVAL);
+    });
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_change_summary(db: State<'_, Database>, since: String) -> CommandResult<ChangeSummary> {
+    let since_time = parse_since(&since)?;
+    Ok(ChangeSummary {
+        since: since_time.to_rfc3339(),
+        generated_at: chrono::Utc::now().to_rfc3339(),
+        packages: Section::from_result(package_changes(since_time)),
+        failed_services: Section::from_result(failed_services(since_time)),
+        new_listening_ports: Section::from_result(new_listening_ports(&db, since_time)),
+        users_added: Section::from_result(users_added(since_time)),
+        reboots: Section::from_result(reboots(since_time)),
+    })
+}
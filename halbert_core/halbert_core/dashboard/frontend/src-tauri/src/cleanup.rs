@@ -0,0 +1,652 @@
+// Disk cleanup in two steps: an itemized plan, then deleting what was
+// approved of it.
+//
+// `plan_cleanup` runs as a job that expands each target (built-in ones for
+// archived journals, package caches, thumbnails and old temp files, or a
+// custom glob with an age) into the regular files it matches right now.
+// Every file is listed with its size and modification time under its
+// target's category, and the plan is stored with per-category totals and
+// attached to a new approval request. Once that request is approved,
+// `execute_cleanup` deletes the files of the categories asked for and no
+// others. Each file is checked again just before it goes: a file that is
+// gone, is no longer a regular file, has a different size or modification
+// time, or no longer meets its category's age is skipped and counted, as
+// is one the sandbox (CLEANUP_PATH) doesn't allow. The bytes actually freed
+// are recorded on the plan, and both the request to execute and what it
+// did are audited. A plan executes once. Planning can be cancelled with an
+// operation id, also in the middle of a walk; a cancelled plan stores
+// nothing.
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::app::{AppHandle, Manager, State};
+use crate::approvals::{self, ApprovalStore, NewApproval};
+use crate::audit;
+use crate::cancellation::{CancellationToken, Operations, CHECK_INTERVAL};
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::impact;
+use crate::jobs::{Job, JobHandle, JobManager};
+use crate::sandbox;
+use crate::settings::SettingsStore;
+
+// Files listed per plan; anything past this is left for the next plan
+const MAX_ITEMS: usize = 200_000;
+const DEFAULT_JOURNAL_DAYS: u32 = 30;
+const DEFAULT_THUMBNAIL_DAYS: u32 = 90;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CleanupTarget {
+    // Archived systemd journal files, never the active ones
+    Journals { older_than_days: Option<u32> },
+    AptCache,
+    DnfCache,
+    Thumbnails { older_than_days: Option<u32> },
+    Tmp { older_than_days: u32 },
+    Custom { name: String, pattern: String, older_than_days: Option<u32> },
+}
+
+// A target resolved to what it matches
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    pub category: String,
+    pub patterns: Vec<String>,
+    pub older_than_days: Option<u32>,
+}
+
+impl CleanupTarget {
+    pub fn rule(&self) -> CommandResult<Rule> {
+        let rule = |category: &str, patterns: &[&str], older_than_days| Rule {
+            category: category.to_string(),
+            patterns: patterns.iter().map(|p| impact::expand_home(p)).collect(),
+            older_than_days,
+        };
+        Ok(match self {
+            CleanupTarget::Journals { older_than_days } => rule(
+                "journals",
+                &["/var/log/journal/*/*@*.journal", "/var/log/journal/*/*.journal~"],
+                Some(older_than_days.unwrap_or(DEFAULT_JOURNAL_DAYS)),
+            ),
+            CleanupTarget::AptCache => rule("apt_cache", &["/var/cache/apt/archives/*.deb"], None),
+            CleanupTarget::DnfCache => rule("dnf_cache", &["/var/cache/dnf/*/packages/*.rpm"], None),
+            CleanupTarget::Thumbnails { older_than_days } => rule(
+                "thumbnails",
+                &["~/.cache/thumbnails/**/*.png"],
+                Some(older_than_days.unwrap_or(DEFAULT_THUMBNAIL_DAYS)),
+            ),
+            CleanupTarget::Tmp { older_than_days } => {
+                rule("tmp", &["/tmp/**/*", "/var/tmp/**/*"], Some(*older_than_days))
+            }
+            CleanupTarget::Custom {
+                name,
+                pattern,
+                older_than_days,
+            } => {
+                let category = format!("custom:{}", name.trim());
+                if name.trim().is_empty() {
+                    return Err(CommandError::InvalidInput("a custom cleanup rule needs a name".to_string()));
+                }
+                let pattern = impact::expand_home(pattern.trim());
+                if !pattern.starts_with('/') || glob::Pattern::new(&pattern).is_err() {
+                    return Err(CommandError::InvalidInput(format!(
+                        "'{}' isn't an absolute glob pattern",
+                        pattern
+                    )));
+                }
+                Rule {
+                    category,
+                    patterns: vec![pattern],
+                    older_than_days: *older_than_days,
+                }
+            }
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CategorySummary {
+    pub category: String,
+    pub patterns: Vec<String>,
+    pub older_than_days: Option<u32>,
+    pub file_count: usize,
+    pub bytes: u64,
+    pub oldest: Option<String>,
+    pub newest: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CleanupResult {
+    pub categories: Vec<String>,
+    pub deleted: usize,
+    pub freed_bytes: u64,
+    // Gone since planning
+    pub skipped_missing: usize,
+    // Changed since planning, or no longer old enough
+    pub skipped_changed: usize,
+    // Outside the sandbox's cleanup roots
+    pub skipped_denied: usize,
+    pub failed: usize,
+}
+
+#[derive(Serialize, Clone)]
+pub struct CleanupPlan {
+    pub id: i64,
+    pub created_at: String,
+    // "planned", "executing" or "executed"
+    pub status: String,
+    pub targets: Vec<CleanupTarget>,
+    pub categories: Vec<CategorySummary>,
+    pub total_bytes: u64,
+    // More files matched than a plan lists
+    pub truncated: bool,
+    pub approval_id: Option<String>,
+    pub executed_at: Option<String>,
+    pub result: Option<CleanupResult>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CleanupItem {
+    pub category: String,
+    pub path: String,
+    pub size_bytes: u64,
+    // Unix seconds
+    pub modified_at: i64,
+}
+
+fn unix_secs(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+fn rfc3339(secs: i64) -> Option<String> {
+    chrono::DateTime::from_timestamp(secs, 0).map(|t| t.to_rfc3339())
+}
+
+// Old enough to go under `older_than_days` at `now`
+pub fn old_enough(modified_at: i64, older_than_days: Option<u32>, now: i64) -> bool {
+    older_than_days.is_none_or(|days| now - modified_at >= days as i64 * 86_400)
+}
+
+pub fn summarize(rule: &Rule, items: &[CleanupItem]) -> CategorySummary {
+    let oldest = items.iter().map(|i| i.modified_at).min();
+    let newest = items.iter().map(|i| i.modified_at).max();
+    CategorySummary {
+        category: rule.category.clone(),
+        patterns: rule.patterns.clone(),
+        older_than_days: rule.older_than_days,
+        file_count: items.len(),
+        bytes: items.iter().map(|i| i.size_bytes).sum(),
+        oldest: oldest.and_then(rfc3339),
+        newest: newest.and_then(rfc3339),
+    }
+}
+
+// The folder a pattern starts from: everything up to the last '/' before
+// its first wildcard
+pub fn literal_prefix(pattern: &str) -> &str {
+    let wildcard = pattern.find(['*', '?', '[']).unwrap_or(pattern.len());
+    match pattern[..wildcard].rfind('/') {
+        Some(0) | None => "/",
+        Some(end) => &pattern[..end],
+    }
+}
+
+// Regular files the rule matches now, symlinks not followed; stops early
+// once `token` is cancelled. A glob can walk a long time between matches,
+// so the walk runs on its own thread and is left behind on a cancel (it
+// ends at its next match).
+fn matches(rule: &Rule, now: i64, limit: usize, token: &CancellationToken) -> Vec<CleanupItem> {
+    let (tx, rx) = mpsc::sync_channel(256);
+    let patterns = rule.patterns.clone();
+    std::thread::spawn(move || {
+        for pattern in &patterns {
+            let Ok(paths) = glob::glob(pattern) else {
+                continue;
+            };
+            for path in paths.filter_map(Result::ok) {
+                if tx.send(path).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    let mut items = Vec::new();
+    while items.len() < limit && !token.is_cancelled() {
+        let path = match rx.recv_timeout(CHECK_INTERVAL) {
+            Ok(path) => path,
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        let Ok(meta) = std::fs::symlink_metadata(&path) else {
+            continue;
+        };
+        let modified_at = meta.modified().map(unix_secs).unwrap_or(now);
+        if meta.file_type().is_file() && old_enough(modified_at, rule.older_than_days, now) {
+            items.push(CleanupItem {
+                category: rule.category.clone(),
+                path: path.display().to_string(),
+                size_bytes: meta.len(),
+                modified_at,
+            });
+        }
+    }
+    items
+}
+
+// Why a planned file can't be deleted now, if it can't
+#[derive(Debug, PartialEq)]
+pub enum Recheck {
+    Delete,
+    Missing,
+    Changed,
+}
+
+// The file as it is now against what was planned
+// `now_meta` is (regular file, size, modified_at), None when it's gone
+pub fn recheck(
+    item: &CleanupItem,
+    now_meta: Option<(bool, u64, i64)>,
+    older_than_days: Option<u32>,
+    now: i64,
+) -> Recheck {
+    match now_meta {
+        None => Recheck::Missing,
+        Some((is_file, size, modified_at))
+            if is_file
+                && size == item.size_bytes
+                && modified_at == item.modified_at
+                && old_enough(modified_at, older_than_days, now) =>
+        {
+            Recheck::Delete
+        }
+        Some(_) => Recheck::Changed,
+    }
+}
+
+const PLAN_COLUMNS: &str = "id, created_at, status, targets, categories, approval_id, executed_at, result";
+
+fn read_plan(r: &rusqlite::Row) -> rusqlite::Result<(CleanupPlan, String, String, Option<String>)> {
+    Ok((
+        CleanupPlan {
+            id: r.get(0)?,
+            created_at: r.get(1)?,
+            status: r.get(2)?,
+            targets: Vec::new(),
+            categories: Vec::new(),
+            total_bytes: 0,
+            truncated: false,
+            approval_id: r.get(5)?,
+            executed_at: r.get(6)?,
+            result: None,
+        },
+        r.get(3)?,
+        r.get(4)?,
+        r.get(7)?,
+    ))
+}
+
+fn load(db: &Database, plan_id: i64) -> CommandResult<CleanupPlan> {
+    let sql = format!("SELECT {} FROM cleanup_plans WHERE id = ?1", PLAN_COLUMNS);
+    let (mut plan, targets, categories, result) = db
+        .with_conn(|conn| conn.query_row(&sql, params![plan_id], read_plan).optional())?
+        .ok_or_else(|| CommandError::NotFound(format!("cleanup plan {}", plan_id)))?;
+    let corrupt = |e: serde_json::Error| CommandError::Internal(format!("cleanup plan {} is corrupt: {}", plan_id, e));
+    plan.targets = serde_json::from_str(&targets).map_err(corrupt)?;
+    let stored: serde_json::Value = serde_json::from_str(&categories).map_err(corrupt)?;
+    plan.truncated = stored.get("truncated").and_then(|t| t.as_bool()).unwrap_or(false);
+    plan.categories = serde_json::from_value(stored.get("categories").cloned().unwrap_or_default()).map_err(corrupt)?;
+    plan.total_bytes = plan.categories.iter().map(|c| c.bytes).sum();
+    plan.result = result.map(|r| serde_json::from_str(&r)).transpose().map_err(corrupt)?;
+    Ok(plan)
+}
+
+fn items(db: &Database, plan_id: i64, category: Option<&str>) -> CommandResult<Vec<CleanupItem>> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT category, path, size_bytes, modified_at FROM cleanup_items
+             WHERE plan_id = ?1 AND (?2 IS NULL OR category = ?2) ORDER BY category, path",
+        )?;
+        let rows = stmt.query_map(params![plan_id, category], |r| {
+            Ok(CleanupItem {
+                category: r.get(0)?,
+                path: r.get(1)?,
+                size_bytes: r.get::<_, i64>(2)? as u64,
+                modified_at: r.get(3)?,
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+fn store_plan(
+    db: &Database,
+    targets: &[CleanupTarget],
+    summaries: &[CategorySummary],
+    truncated: bool,
+    found: &[CleanupItem],
+) -> CommandResult<i64> {
+    let targets = serde_json::to_string(targets).map_err(|e| CommandError::Internal(e.to_string()))?;
+    let categories = json!({ "categories": summaries, "truncated": truncated }).to_string();
+    let now = chrono::Utc::now().to_rfc3339();
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO cleanup_plans (created_at, status, targets, categories) VALUES (?1, 'planned', ?2, ?3)",
+            params![now, targets, categories],
+        )?;
+        let plan_id = tx.last_insert_rowid();
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO cleanup_items (plan_id, category, path, size_bytes, modified_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for item in found {
+                insert.execute(params![plan_id, item.category, item.path, item.size_bytes as i64, item.modified_at])?;
+            }
+        }
+        tx.commit()?;
+        Ok(plan_id)
+    })
+}
+
+fn request_approval(app: &AppHandle, plan_id: i64, summaries: &[CategorySummary]) -> CommandResult<String> {
+    let files: usize = summaries.iter().map(|c| c.file_count).sum();
+    let bytes: u64 = summaries.iter().map(|c| c.bytes).sum();
+    let breakdown: Vec<String> = summaries
+        .iter()
+        .filter(|c| c.file_count > 0)
+        .map(|c| format!("{}: {} files, {:.1} MB", c.category, c.file_count, c.bytes as f64 / 1024.0 / 1024.0))
+        .collect();
+    let request = approvals::submit(
+        app,
+        NewApproval {
+            task: "Disk Cleanup".to_string(),
+            action: format!(
+                "Delete up to {} files ({:.1} MB) from cleanup plan {}",
+                files,
+                bytes as f64 / 1024.0 / 1024.0,
+                plan_id
+            ),
+            reasoning: format!("Itemized plan: {}", breakdown.join("; ")),
+            confidence: 1.0,
+            risk_level: "medium".to_string(),
+            affected_resources: summaries.iter().flat_map(|c| c.patterns.clone()).collect(),
+            execution: None,
+            group_id: None,
+            depends_on: Vec::new(),
+        },
+        None,
+    )?;
+    let db = app.state::<Database>();
+    db.with_conn(|conn| {
+        conn.execute("UPDATE cleanup_plans SET approval_id = ?2 WHERE id = ?1", params![plan_id, request.id])
+    })?;
+    Ok(request.id)
+}
+
+// The planning job's listing: per-category summaries, the files and
+// whether MAX_ITEMS cut it short
+fn list(
+    rules: &[Rule],
+    handle: &JobHandle,
+    token: &CancellationToken,
+) -> Result<(Vec<CategorySummary>, Vec<CleanupItem>, bool), String> {
+    let now = unix_secs(SystemTime::now());
+    let mut found = Vec::new();
+    let mut summaries = Vec::new();
+    let mut truncated = false;
+    for (i, rule) in rules.iter().enumerate() {
+        let items = matches(rule, now, MAX_ITEMS - found.len(), token);
+        if token.is_cancelled() {
+            handle.set_result(json!({ "categories": summaries, "files_found": found.len() + items.len() }));
+            return Err(format!("Cancelled while listing {}; nothing was stored", rule.category));
+        }
+        truncated |= found.len() + items.len() >= MAX_ITEMS;
+        let summary = summarize(rule, &items);
+        handle.log(format!("{}: {} files, {} bytes", summary.category, summary.file_count, summary.bytes));
+        summaries.push(summary);
+        found.extend(items);
+        handle.set_progress((i + 1) as f32 / rules.len() as f32);
+    }
+    if truncated {
+        handle.log(format!("Stopped listing at {} files; plan again afterwards for the rest", MAX_ITEMS));
+    }
+    Ok((summaries, found, truncated))
+}
+
+// Runs as a job; its result has the plan id, the categories and the
+// approval request the plan is attached to
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn plan_cleanup(
+    app: AppHandle,
+    jobs: State<'_, JobManager>,
+    settings: State<'_, SettingsStore>,
+    operations: State<'_, Operations>,
+    targets: Vec<CleanupTarget>,
+    operation_id: Option<String>,
+) -> CommandResult<Job> {
+    if targets.is_empty() {
+        return Err(CommandError::InvalidInput("nothing to clean up".to_string()));
+    }
+    let rules = targets.iter().map(CleanupTarget::rule).collect::<CommandResult<Vec<Rule>>>()?;
+    let settings = settings.get();
+    for pattern in rules.iter().flat_map(|r| &r.patterns) {
+        sandbox::CLEANUP_PATH.validate(&settings, Path::new(literal_prefix(pattern)))?;
+    }
+    let mut names: Vec<&str> = rules.iter().map(|r| r.category.as_str()).collect();
+    names.sort();
+    if names.windows(2).any(|w| w[0] == w[1]) {
+        return Err(CommandError::InvalidInput("each cleanup category can only be listed once".to_string()));
+    }
+    let operation = operations.start(operation_id)?;
+    Ok(jobs.spawn("Plan disk cleanup", "cleanup_plan", move |handle| {
+        let (summaries, found, truncated) = list(&rules, handle, operation.token())?;
+        let db = app.state::<Database>();
+        let plan_id = store_plan(&db, &targets, &summaries, truncated, &found).map_err(|e| e.to_string())?;
+        let approval_id = request_approval(&app, plan_id, &summaries).map_err(|e| e.to_string())?;
+        handle.log(format!("Stored as plan {}, awaiting approval {}", plan_id, approval_id));
+        handle.set_result(json!({
+            "plan_id": plan_id,
+            "approval_id": approval_id,
+            "categories": summaries,
+            "truncated": truncated,
+        }));
+        println!("[Halbert] Cleanup plan {} lists {} file(s)", plan_id, found.len());
+        Ok(())
+    }))
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_cleanup_plan(db: State<'_, Database>, plan_id: i64) -> CommandResult<CleanupPlan> {
+    load(&db, plan_id)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_cleanup_items(
+    db: State<'_, Database>,
+    plan_id: i64,
+    category: Option<String>,
+) -> CommandResult<Vec<CleanupItem>> {
+    load(&db, plan_id)?;
+    items(&db, plan_id, category.as_deref())
+}
+
+// Deletes only the files of `categories`, once the plan's approval request
+// has been approved
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn execute_cleanup(
+    app: AppHandle,
+    db: State<'_, Database>,
+    jobs: State<'_, JobManager>,
+    approvals: State<'_, ApprovalStore>,
+    plan_id: i64,
+    categories: Vec<String>,
+) -> CommandResult<Job> {
+    let plan = load(&db, plan_id)?;
+    if categories.is_empty() {
+        return Err(CommandError::InvalidInput("choose at least one category".to_string()));
+    }
+    let ages: BTreeMap<String, Option<u32>> =
+        plan.categories.iter().map(|c| (c.category.clone(), c.older_than_days)).collect();
+    if let Some(unknown) = categories.iter().find(|c| !ages.contains_key(*c)) {
+        return Err(CommandError::InvalidInput(format!("plan {} has no category '{}'", plan_id, unknown)));
+    }
+    let approval_id = plan
+        .approval_id
+        .clone()
+        .ok_or_else(|| CommandError::Conflict(format!("plan {} was never sent for approval", plan_id)))?;
+    let request = approvals.get(&approval_id).map_err(|_| {
+        CommandError::NotFound(format!(
+            "approval {} for plan {} (requests don't outlive the app; plan again)",
+            approval_id, plan_id
+        ))
+    })?;
+    if request.status != "approved" {
+        return Err(CommandError::PermissionDenied(format!(
+            "approval {} for plan {} is {}, not approved",
+            approval_id, plan_id, request.status
+        )));
+    }
+    let claimed = db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE cleanup_plans SET status = 'executing' WHERE id = ?1 AND status = 'planned'",
+            params![plan_id],
+        )
+    })?;
+    if claimed == 0 {
+        return Err(CommandError::Conflict(format!("plan {} is already {}", plan_id, plan.status)));
+    }
+    let actor = audit::local_actor();
+    audit::record(
+        &db,
+        &actor,
+        "cleanup.execute",
+        &format!("cleanup_plan:{}", plan_id),
+        &json!({ "approval_id": approval_id, "categories": categories }),
+    )?;
+
+    let mut selected = Vec::new();
+    for category in &categories {
+        selected.extend(items(&db, plan_id, Some(category))?);
+    }
+    let name = format!("Disk cleanup (plan {})", plan_id);
+    Ok(jobs.spawn(&name, "cleanup_execute", move |handle| {
+        let settings = app.state::<SettingsStore>().get();
+        let db = app.state::<Database>();
+        let mut result = CleanupResult {
+            categories,
+            ..CleanupResult::default()
+        };
+        let total = selected.len().max(1);
+        for (i, item) in selected.iter().enumerate() {
+            let path = Path::new(&item.path);
+            let allowed = sandbox::CLEANUP_PATH
+                .validate(&settings, path)
+                .is_ok_and(|resolved| resolved.as_path() == path);
+            if !allowed {
+                result.skipped_denied += 1;
+                handle.log(format!("Not allowed: {}", item.path));
+                continue;
+            }
+            let meta = std::fs::symlink_metadata(path).ok().map(|m| {
+                let modified_at = m.modified().map(unix_secs).unwrap_or_default();
+                (m.file_type().is_file(), m.len(), modified_at)
+            });
+            let older_than_days = ages.get(&item.category).copied().flatten();
+            match recheck(item, meta, older_than_days, unix_secs(SystemTime::now())) {
+                Recheck::Missing => result.skipped_missing += 1,
+                Recheck::Changed => {
+                    result.skipped_changed += 1;
+                    handle.log(format!("Changed since planning, kept: {}", item.path));
+                }
+                Recheck::Delete => match std::fs::remove_file(path) {
+                    Ok(()) => {
+                        result.deleted += 1;
+                        result.freed_bytes += item.size_bytes;
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => result.skipped_missing += 1,
+                    Err(e) => {
+                        result.failed += 1;
+                        handle.log(format!("Couldn't delete {}: {}", item.path, e));
+                    }
+                },
+            }
+            if i % 500 == 0 {
+                handle.set_progress(i as f32 / total as f32);
+            }
+        }
+        let stored = serde_json::to_string(&result).unwrap_or_default();
+        let finished = db.with_conn(|conn| {
+            conn.execute(
+                "UPDATE cleanup_plans SET status = 'executed', executed_at = ?2, result = ?3 WHERE id = ?1",
+                params![plan_id, chrono::Utc::now().to_rfc3339(), stored],
+            )
+        });
+        if let Err(e) = finished {
+            println!("[Halbert] Can't record cleanup plan {}: {}", plan_id, e);
+        }
+        let target = format!("cleanup_plan:{}", plan_id);
+        if let Err(e) = audit::record(&db, &actor, "cleanup.executed", &target, &json!(result)) {
+            println!("[Halbert] Failed to audit cleanup plan {}: {}", plan_id, e);
+        }
+        handle.log(format!(
+            "Deleted {} files, freed {} bytes; skipped {} missing, {} changed, {} not allowed; {} failed",
+            result.deleted,
+            result.freed_bytes,
+            result.skipped_missing,
+            result.skipped_changed,
+            result.skipped_denied,
+            result.failed
+        ));
+        handle.set_result(json!(result));
+        println!("[Halbert] Cleanup plan {} freed {} bytes", plan_id, result.freed_bytes);
+        if result.failed > 0 {
+            return Err(format!("{} of {} files couldn't be deleted", result.failed, selected.len()));
+        }
+        Ok(())
+    }))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::automation::Gate;
+    use crate::cancellation::{self, NOTICED_WITHIN};
+
+    #[test]
+    fn a_cancelled_plan_stops_mid_walk_and_stores_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let endless = cancellation::endless_tree(dir.path());
+        std::fs::write(dir.path().join("old.log"), "x").unwrap();
+        let rules = vec![
+            Rule {
+                category: "logs".to_string(),
+                patterns: vec![format!("{}/*.log", dir.path().display())],
+                older_than_days: None,
+            },
+            Rule {
+                category: "endless".to_string(),
+                patterns: vec![format!("{}/**/never-matches", endless.display())],
+                older_than_days: None,
+            },
+        ];
+        let jobs = JobManager::empty(Gate::load(dir.path().join("automation.json")));
+        let operations = Operations::default();
+        let operation = operations.start(Some("plan".to_string())).unwrap();
+        let job = jobs.spawn("Plan disk cleanup", "cleanup_plan", move |handle| {
+            list(&rules, handle, operation.token()).map(drop)
+        });
+        let (job, overran) = cancellation::cancel_during(&operations, "plan", || jobs.wait_finished(&job.id));
+        assert!(overran < NOTICED_WITHIN, "{:?}", overran);
+        assert_eq!(job.status, "failed");
+        assert_eq!(job.error.as_deref(), Some("Cancelled while listing endless; nothing was stored"));
+        let result = job.result.unwrap();
+        assert_eq!(result["files_found"], 1);
+        assert_eq!(result["categories"][0]["category"], "logs");
+    }
+}
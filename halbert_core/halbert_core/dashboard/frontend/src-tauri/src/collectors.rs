@@ -0,0 +1,342 @@
+// Background collectors and the profiles that switch them.
+//
+// Each collector keeps its own thread but asks the registry before every
+// run: a disabled collector parks until it is enabled again, and a change
+// wakes parked and sleeping collectors at once, so applying a profile takes
+// effect without a restart. Whether a collector runs, and how often, comes
+// from `collectors` in settings; a collector missing there runs at its
+// default. A profile is a saved copy of that map. The built-in ones are
+// below; saved ones live in settings.
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::app::{AppHandle, Emitter, Manager, State};
+use crate::error::{CommandError, CommandResult};
+use crate::settings::{Settings, SettingsStore};
+
+const RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct Collector {
+    pub name: &'static str,
+    pub description: &'static str,
+    // None follows another setting (metrics_interval_secs for the sampler)
+    pub default_interval_secs: Option<u64>,
+}
+
+pub const COLLECTORS: &[Collector] = &[
+    Collector {
+        name: "metrics",
+        description: "CPU, memory and disk sampler behind the live dashboard",
+        default_interval_secs: None,
+    },
+    Collector {
+        name: "containers",
+        description: "Per-container CPU, memory and network from the container engine",
+        default_interval_secs: None,
+    },
+    Collector {
+        name: "smart",
+        description: "Disk temperature and wear from smartctl",
+        default_interval_secs: Some(30 * 60),
+    },
+    Collector {
+        name: "update_inventory",
+        description: "Pending package updates from apt or dnf",
+        default_interval_secs: Some(6 * 60 * 60),
+    },
+    Collector {
+        name: "certificates",
+        description: "TLS certificate expiry of the watched endpoints",
+        default_interval_secs: Some(24 * 60 * 60),
+    },
+    Collector {
+        name: "ssh_metrics",
+        description: "CPU, memory, disk and uptime of hosts reached over SSH",
+        default_interval_secs: Some(30),
+    },
+];
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CollectorConfig {
+    pub enabled: bool,
+    // None uses the collector's default
+    #[serde(default)]
+    pub interval_secs: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollectorProfile {
+    pub name: String,
+    pub collectors: BTreeMap<String, CollectorConfig>,
+    #[serde(default)]
+    pub builtin: bool,
+}
+
+fn collector(name: &str) -> Option<&'static Collector> {
+    COLLECTORS.iter().find(|c| c.name == name)
+}
+
+fn profile(name: &str, entries: &[(&str, bool, Option<u64>)]) -> CollectorProfile {
+    CollectorProfile {
+        name: name.to_string(),
+        collectors: entries
+            .iter()
+            .map(|&(collector, enabled, interval_secs)| (collector.to_string(), CollectorConfig { enabled, interval_secs }))
+            .collect(),
+        builtin: true,
+    }
+}
+
+pub fn builtin_profiles() -> Vec<CollectorProfile> {
+    vec![
+        // Disks and services matter, the live view less so
+        profile(
+            "server",
+            &[
+                ("metrics", true, Some(10)),
+                ("containers", true, Some(10)),
+                ("smart", true, Some(30 * 60)),
+                ("update_inventory", true, Some(6 * 60 * 60)),
+                ("certificates", true, Some(24 * 60 * 60)),
+                ("ssh_metrics", true, Some(30)),
+            ],
+        ),
+        // A responsive live view; nothing that spins disks or sits in the background
+        profile(
+            "laptop",
+            &[
+                ("metrics", true, Some(2)),
+                ("containers", false, None),
+                ("smart", false, None),
+                ("update_inventory", true, Some(24 * 60 * 60)),
+                ("certificates", false, None),
+                ("ssh_metrics", false, None),
+            ],
+        ),
+        profile(
+            "minimal",
+            &[
+                ("metrics", true, Some(5)),
+                ("containers", false, None),
+                ("smart", false, None),
+                ("update_inventory", false, None),
+                ("certificates", false, None),
+                ("ssh_metrics", false, None),
+            ],
+        ),
+    ]
+}
+
+pub fn config(settings: &Settings, name: &str) -> CollectorConfig {
+    settings.collectors.get(name).cloned().unwrap_or(CollectorConfig {
+        enabled: true,
+        interval_secs: None,
+    })
+}
+
+pub fn enabled(settings: &Settings, name: &str) -> bool {
+    config(settings, name).enabled
+}
+
+// `fallback` is used when neither the settings nor the collector set one
+pub fn interval_secs(settings: &Settings, name: &str, fallback: u64) -> u64 {
+    config(settings, name)
+        .interval_secs
+        .or_else(|| collector(name).and_then(|c| c.default_interval_secs))
+        .unwrap_or(fallback)
+        .max(1)
+}
+
+// Every known collector, filled in with defaults, so a saved profile
+// doesn't change meaning when defaults do
+pub fn snapshot(settings: &Settings) -> BTreeMap<String, CollectorConfig> {
+    COLLECTORS
+        .iter()
+        .map(|c| (c.name.to_string(), config(settings, c.name)))
+        .collect()
+}
+
+pub fn all_profiles(settings: &Settings) -> Vec<CollectorProfile> {
+    let mut profiles = builtin_profiles();
+    profiles.extend(settings.collector_profiles.iter().cloned());
+    profiles
+}
+
+#[derive(Clone, Default)]
+struct RunStats {
+    last_run: Option<String>,
+    last_duration_ms: Option<u64>,
+    last_error: Option<String>,
+    runs: u64,
+}
+
+#[derive(Default)]
+struct Runs {
+    stats: BTreeMap<&'static str, RunStats>,
+    // Bumped whenever collector settings change
+    generation: u64,
+}
+
+#[derive(Default)]
+pub struct CollectorRegistry {
+    runs: Mutex<Runs>,
+    changed: Condvar,
+}
+
+#[derive(Serialize)]
+pub struct CollectorStatus {
+    pub name: String,
+    pub description: String,
+    pub enabled: bool,
+    pub interval_secs: Option<u64>,
+    pub last_run: Option<String>,
+    pub last_duration_ms: Option<u64>,
+    pub last_error: Option<String>,
+    pub runs: u64,
+}
+
+impl CollectorRegistry {
+    // Blocks while `name` is disabled. Settings are read under the lock
+    // `reload` takes, so a change can't slip in between check and wait;
+    // the periodic recheck covers any change that skipped `reload`.
+    pub fn wait_enabled(&self, app: &AppHandle, name: &str) {
+        let mut runs = self.runs.lock().unwrap();
+        let mut announced = false;
+        while !enabled(&app.state::<SettingsStore>().get(), name) {
+            if !announced {
+                println!("[Halbert] Collector {} is disabled", name);
+                announced = true;
+            }
+            runs = self.changed.wait_timeout(runs, RECHECK_INTERVAL).unwrap().0;
+        }
+        if announced {
+            println!("[Halbert] Collector {} resumed", name);
+        }
+    }
+
+    // Sleep for `duration`, returning early if collector settings change
+    pub fn sleep(&self, duration: Duration) {
+        let runs = self.runs.lock().unwrap();
+        let start = runs.generation;
+        let _ = self
+            .changed
+            .wait_timeout_while(runs, duration, |runs| runs.generation == start)
+            .unwrap();
+    }
+
+    pub fn reload(&self) {
+        self.runs.lock().unwrap().generation += 1;
+        self.changed.notify_all();
+    }
+
+    // Time one run of `name` and keep its outcome
+    pub fn run<T, E: Display>(&self, name: &'static str, work: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+        let started = Instant::now();
+        let result = work();
+        let mut runs = self.runs.lock().unwrap();
+        let stats = runs.stats.entry(name).or_default();
+        stats.last_run = Some(chrono::Utc::now().to_rfc3339());
+        stats.last_duration_ms = Some(started.elapsed().as_millis() as u64);
+        stats.last_error = result.as_ref().err().map(|e| e.to_string());
+        stats.runs += 1;
+        result
+    }
+
+    pub fn status(&self, settings: &Settings) -> Vec<CollectorStatus> {
+        let runs = self.runs.lock().unwrap();
+        COLLECTORS
+            .iter()
+            .map(|c| {
+                let stats = runs.stats.get(c.name).cloned().unwrap_or_default();
+                let config = config(settings, c.name);
+                CollectorStatus {
+                    name: c.name.to_string(),
+                    description: c.description.to_string(),
+                    enabled: config.enabled,
+                    interval_secs: config.interval_secs.or(c.default_interval_secs),
+                    last_run: stats.last_run,
+                    last_duration_ms: stats.last_duration_ms,
+                    last_error: stats.last_error,
+                    runs: stats.runs,
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ProfileList {
+    pub active: Option<String>,
+    pub profiles: Vec<CollectorProfile>,
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn list_profiles(settings: State<'_, SettingsStore>) -> ProfileList {
+    let settings = settings.get();
+    ProfileList {
+        active: settings.active_profile.clone(),
+        profiles: all_profiles(&settings),
+    }
+}
+
+// Saves the current collector settings under `name`, replacing a saved
+// profile of that name
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn save_profile(settings: State<'_, SettingsStore>, name: String) -> CommandResult<CollectorProfile> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(CommandError::InvalidInput("profile name can't be empty".to_string()));
+    }
+    if builtin_profiles().iter().any(|p| p.name == name) {
+        return Err(CommandError::Conflict(format!("'{}' is a built-in profile", name)));
+    }
+    let mut saved = None;
+    settings.update(|current| {
+        let mut next = current.clone();
+        let profile = CollectorProfile {
+            name: name.clone(),
+            collectors: snapshot(current),
+            builtin: false,
+        };
+        next.collector_profiles.retain(|p| p.name != name);
+        next.collector_profiles.push(profile.clone());
+        next.active_profile = Some(name.clone());
+        saved = Some(profile);
+        Ok(next)
+    })?;
+    saved.ok_or_else(|| CommandError::Internal("profile was not saved".to_string()))
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn apply_profile(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    registry: State<'_, CollectorRegistry>,
+    name: String,
+) -> CommandResult<Vec<CollectorStatus>> {
+    let updated = settings.update(|current| {
+        let profile = all_profiles(current)
+            .into_iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| CommandError::NotFound(format!("profile '{}'", name)))?;
+        let mut next = current.clone();
+        next.collectors = profile.collectors;
+        next.active_profile = Some(profile.name);
+        Ok(next)
+    })?;
+    registry.reload();
+    println!("[Halbert] Applied collector profile {}", name);
+    let _ = app.emit("settings://changed", &updated);
+    Ok(registry.status(&updated))
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_collector_status(
+    settings: State<'_, SettingsStore>,
+    registry: State<'_, CollectorRegistry>,
+) -> Vec<CollectorStatus> {
+    registry.status(&settings.get())
+}
@@ -0,0 +1,491 @@
+// Per-command invocation timing.
+//
+// `measured` wraps the invoke handler. Each call is sent through again with
+// Webview::on_message, Tauri's entry point for a custom IPC, marked with
+// the MEASURED header and answered by a responder that records the
+// response before handing it to the call's own resolver. Only the marked
+// call goes on to the commands. So what is recorded is the command's own
+// result: an async command is timed until its future resolves, not just
+// until it's spawned; an `Err` counts as an error with its message; the
+// result size is that of the body Tauri sends back. A call that never
+// responded (it panicked, or its future was dropped) counts as an error
+// too, and so does a command no handler knew, under UNKNOWN.
+//
+// Each command's slot comes from an index made from COMMANDS when the
+// stats are, so the hot path takes no lock to find it. It then takes the
+// time, reads the request's length from its raw body or Content-Length
+// (a JSON body without one isn't counted), reads the response body's
+// length and updates that command's fixed-size ring. A String is only
+// allocated when a call fails or is slow. Calls above `slow_command_ms` are
+// logged with their argument count, never the values.
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::http::header::{HeaderMap, HeaderValue, CONTENT_LENGTH};
+use tauri::ipc::{CallbackFn, Invoke, InvokeBody, InvokeError, InvokeResponse, InvokeResponseBody, Response};
+use tauri::webview::InvokeRequest;
+use tauri::{Manager, Runtime, State, Webview};
+
+use crate::settings::SettingsStore;
+
+// Durations kept per command for the percentiles
+const SAMPLES: usize = 256;
+// Where calls to commands that aren't registered are counted
+pub const UNKNOWN: &str = "(unknown command)";
+// Set on a call measured already, as it's sent through again
+const MEASURED: &str = "x-halbert-measured";
+
+struct Entry {
+    count: u64,
+    errors: u64,
+    payload_bytes: u64,
+    result_bytes: u64,
+    // Microseconds, oldest overwritten first
+    durations_us: [u32; SAMPLES],
+    next: usize,
+    max_us: u32,
+    last_error: Option<String>,
+}
+
+impl Default for Entry {
+    fn default() -> Self {
+        Entry {
+            count: 0,
+            errors: 0,
+            payload_bytes: 0,
+            result_bytes: 0,
+            durations_us: [0; SAMPLES],
+            next: 0,
+            max_us: 0,
+            last_error: None,
+        }
+    }
+}
+
+// One entry per registered command, in the order given, then UNKNOWN's.
+// Entries are only zeroed by a reset, so a slot stays valid.
+pub struct CommandStats {
+    names: Vec<&'static str>,
+    slots: HashMap<&'static str, usize>,
+    entries: Vec<Mutex<Entry>>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CommandSummary {
+    pub command: String,
+    pub count: u64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+    pub error_rate: f64,
+    pub last_error: Option<String>,
+    pub avg_payload_bytes: u64,
+    // Serialized size of the value returned; errors count as 0
+    pub avg_result_bytes: u64,
+}
+
+// Nearest-rank percentile of unsorted samples
+pub fn percentile(samples: &[u32], p: f64) -> u32 {
+    if samples.is_empty() {
+        return 0;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_unstable();
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn ms(us: u32) -> f64 {
+    us as f64 / 1000.0
+}
+
+impl CommandStats {
+    pub fn new(commands: &[&'static str]) -> Self {
+        let names: Vec<&'static str> = commands.iter().copied().chain([UNKNOWN]).collect();
+        CommandStats {
+            slots: names.iter().enumerate().map(|(slot, name)| (*name, slot)).collect(),
+            entries: names.iter().map(|_| Mutex::default()).collect(),
+            names,
+        }
+    }
+
+    // The command's entry; UNKNOWN's for one that isn't registered
+    pub fn slot(&self, command: &str) -> usize {
+        self.slots.get(command).copied().unwrap_or(self.names.len() - 1)
+    }
+
+    pub fn record(&self, slot: usize, elapsed: Duration, payload_bytes: usize, result_bytes: usize, error: Option<&str>) {
+        let us = elapsed.as_micros().min(u32::MAX as u128) as u32;
+        let Some(entry) = self.entries.get(slot) else {
+            return;
+        };
+        let entry = &mut *entry.lock().unwrap();
+        entry.count += 1;
+        entry.payload_bytes += payload_bytes as u64;
+        entry.result_bytes += result_bytes as u64;
+        entry.durations_us[entry.next] = us;
+        entry.next = (entry.next + 1) % SAMPLES;
+        entry.max_us = entry.max_us.max(us);
+        if let Some(error) = error {
+            entry.errors += 1;
+            entry.last_error = Some(error.to_string());
+        }
+    }
+
+    pub fn name(&self, slot: usize) -> &'static str {
+        self.names.get(slot).copied().unwrap_or(UNKNOWN)
+    }
+
+    // Slowest p95 first
+    pub fn summary(&self) -> Vec<CommandSummary> {
+        let mut summary: Vec<CommandSummary> = self
+            .names
+            .iter()
+            .zip(&self.entries)
+            .map(|(command, entry)| (command, entry.lock().unwrap()))
+            .filter(|(_, entry)| entry.count > 0)
+            .map(|(command, entry)| {
+                let filled = (entry.count as usize).min(SAMPLES);
+                let samples = &entry.durations_us[..filled];
+                CommandSummary {
+                    command: command.to_string(),
+                    count: entry.count,
+                    p50_ms: ms(percentile(samples, 50.0)),
+                    p95_ms: ms(percentile(samples, 95.0)),
+                    max_ms: ms(entry.max_us),
+                    error_rate: entry.errors as f64 / entry.count as f64,
+                    last_error: entry.last_error.clone(),
+                    avg_payload_bytes: entry.payload_bytes / entry.count,
+                    avg_result_bytes: entry.result_bytes / entry.count,
+                }
+            })
+            .collect();
+        summary.sort_by(|a, b| b.p95_ms.total_cmp(&a.p95_ms).then_with(|| a.command.cmp(&b.command)));
+        summary
+    }
+
+    pub fn reset(&self) {
+        for entry in &self.entries {
+            *entry.lock().unwrap() = Entry::default();
+        }
+    }
+}
+
+// The request's size as it arrived; a parsed JSON body only has the
+// Content-Length it came with
+pub fn payload_size(body: &InvokeBody, headers: &HeaderMap) -> usize {
+    match body {
+        InvokeBody::Raw(bytes) => bytes.len(),
+        InvokeBody::Json(_) => headers
+            .get(CONTENT_LENGTH)
+            .and_then(|length| length.to_str().ok()?.parse().ok())
+            .unwrap_or(0),
+    }
+}
+
+// How many arguments a call had; their values may hold paths, tokens or
+// document text, so they are never logged
+pub fn argument_count(body: &InvokeBody) -> usize {
+    match body {
+        InvokeBody::Json(serde_json::Value::Object(fields)) => fields.len(),
+        InvokeBody::Json(serde_json::Value::Null) => 0,
+        InvokeBody::Json(_) | InvokeBody::Raw(_) => 1,
+    }
+}
+
+// One call to a command, from `measured` sending it on until it responds.
+// Dropped without `finish`, it records the call as failed.
+pub struct Call<R: Runtime> {
+    webview: Webview<R>,
+    slot: usize,
+    arguments: usize,
+    payload_bytes: usize,
+    started: Instant,
+    finished: bool,
+}
+
+impl<R: Runtime> Call<R> {
+    pub fn start(invoke: &Invoke<R>) -> Self {
+        let webview = invoke.message.webview();
+        let slot = webview.state::<CommandStats>().slot(invoke.message.command());
+        Call {
+            slot,
+            arguments: argument_count(invoke.message.payload()),
+            payload_bytes: payload_size(invoke.message.payload(), invoke.message.headers()),
+            started: Instant::now(),
+            finished: false,
+            webview,
+        }
+    }
+
+    fn finish(mut self, response: &InvokeResponse) {
+        self.finished = true;
+        match response {
+            InvokeResponse::Ok(InvokeResponseBody::Json(json)) => self.record(json.len(), None),
+            InvokeResponse::Ok(InvokeResponseBody::Raw(bytes)) => self.record(bytes.len(), None),
+            InvokeResponse::Err(InvokeError(error)) => match error.get("message").and_then(|message| message.as_str()) {
+                Some(message) => self.record(0, Some(message)),
+                None => self.record(0, Some(&error.to_string())),
+            },
+        }
+    }
+
+    fn record(&self, result_bytes: usize, error: Option<&str>) {
+        let elapsed = self.started.elapsed();
+        let stats = self.webview.state::<CommandStats>();
+        stats.record(self.slot, elapsed, self.payload_bytes, result_bytes, error);
+        let slow_after = Duration::from_millis(self.webview.state::<SettingsStore>().slow_command_ms());
+        if !slow_after.is_zero() && elapsed > slow_after {
+            println!(
+                "[Halbert] Slow command {} took {} ms ({} argument(s), {} bytes, values redacted)",
+                stats.name(self.slot),
+                elapsed.as_millis(),
+                self.arguments,
+                self.payload_bytes
+            );
+        }
+    }
+}
+
+impl<R: Runtime> Drop for Call<R> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let error = if std::thread::panicking() { "panicked" } else { "stopped before responding" };
+            self.record(0, Some(error));
+        }
+    }
+}
+
+// Wraps the invoke handler (see the top of this file). The call's own URL
+// isn't kept, so it goes again with the webview's; Tauri has checked its
+// origin against the ACL before the handler saw it.
+pub fn measured<R, F>(handler: F) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static
+where
+    R: Runtime,
+    F: Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+{
+    move |invoke: Invoke<R>| {
+        let webview = invoke.message.webview();
+        let url = match webview.url() {
+            Ok(url) if !invoke.message.headers().contains_key(MEASURED) => url,
+            _ => return handler(invoke),
+        };
+        let call = Call::start(&invoke);
+        let mut headers = invoke.message.headers().clone();
+        headers.insert(MEASURED, HeaderValue::from_static("1"));
+        let request = InvokeRequest {
+            cmd: invoke.message.command().to_string(),
+            callback: CallbackFn(0),
+            error: CallbackFn(1),
+            url,
+            body: invoke.message.payload().clone(),
+            headers,
+            invoke_key: webview.app_handle().invoke_key().to_string(),
+        };
+        let resolver = invoke.resolver;
+        webview.on_message(
+            request,
+            Box::new(move |_, _, response, _, _| {
+                call.finish(&response);
+                match response {
+                    InvokeResponse::Ok(body) => resolver.respond(Ok(Response::new(body))),
+                    InvokeResponse::Err(error) => resolver.invoke_error(error),
+                }
+            }),
+        );
+        true
+    }
+}
+
+#[tauri::command]
+pub fn get_command_stats(stats: State<'_, CommandStats>) -> Vec<CommandSummary> {
+    stats.summary()
+}
+
+#[tauri::command]
+pub fn reset_command_stats(stats: State<'_, CommandStats>) {
+    stats.reset();
+    println!("[Halbert] Command stats reset");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tauri::test::MockRuntime;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let samples: Vec<u32> = (1..=100).rev().collect();
+        assert_eq!(percentile(&samples, 50.0), 50);
+        assert_eq!(percentile(&samples, 95.0), 95);
+        assert_eq!(percentile(&samples, 100.0), 100);
+        assert_eq!(percentile(&[7], 95.0), 7);
+        assert_eq!(percentile(&[], 50.0), 0);
+    }
+
+    #[test]
+    fn results_and_errors_accumulate_per_command() {
+        let stats = CommandStats::new(&["get_active_jobs", "get_process_tree"]);
+        let fast = stats.slot("get_active_jobs");
+        let slow = stats.slot("get_process_tree");
+        assert_eq!((fast, slow, stats.slot("nope"), stats.name(2)), (0, 1, 2, UNKNOWN));
+        stats.record(fast, ms(2), 10, 300, None);
+        stats.record(fast, ms(4), 20, 100, None);
+        stats.record(slow, ms(900), 0, 0, Some("timed out"));
+        stats.record(slow, ms(100), 0, 5000, None);
+
+        let summary = stats.summary();
+        assert_eq!(summary.iter().map(|s| s.command.as_str()).collect::<Vec<_>>(), ["get_process_tree", "get_active_jobs"]);
+        let (slow, fast) = (&summary[0], &summary[1]);
+        assert_eq!((fast.count, fast.avg_payload_bytes, fast.avg_result_bytes), (2, 15, 200));
+        assert_eq!((fast.p50_ms, fast.max_ms, fast.error_rate), (2.0, 4.0, 0.0));
+        assert_eq!((slow.error_rate, slow.last_error.as_deref()), (0.5, Some("timed out")));
+        assert_eq!(slow.avg_result_bytes, 2500);
+
+        stats.reset();
+        assert!(stats.summary().is_empty());
+        assert_eq!(stats.slot("get_process_tree"), 1);
+    }
+
+    #[test]
+    fn the_ring_keeps_the_latest_samples() {
+        let stats = CommandStats::new(&["get_documents"]);
+        let slot = stats.slot("get_documents");
+        stats.record(slot, ms(1000), 0, 0, None);
+        for _ in 0..SAMPLES {
+            stats.record(slot, ms(1), 0, 0, None);
+        }
+        let summary = &stats.summary()[0];
+        assert_eq!(summary.count, SAMPLES as u64 + 1);
+        // The slow call has left the percentiles but not the maximum
+        assert_eq!((summary.p95_ms, summary.max_ms), (1.0, 1000.0));
+    }
+
+    #[test]
+    fn concurrent_calls_are_all_counted() {
+        let stats = Arc::new(CommandStats::new(&["get_system_metrics", "list_corpora"]));
+        let threads: Vec<_> = (0..8)
+            .map(|thread| {
+                let stats = stats.clone();
+                std::thread::spawn(move || {
+                    for call in 0..500 {
+                        let command = if call % 2 == 0 { "get_system_metrics" } else { "list_corpora" };
+                        let slot = stats.slot(command);
+                        let error = (thread == 0 && call % 2 == 1).then_some("backend down");
+                        stats.record(slot, ms(1), 1, 2, error);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let summary = stats.summary();
+        assert_eq!(summary.len(), 2);
+        for command in &summary {
+            assert_eq!(command.count, 2000, "{}", command.command);
+            assert_eq!((command.avg_payload_bytes, command.avg_result_bytes), (1, 2));
+        }
+        let corpora = summary.iter().find(|s| s.command == "list_corpora").unwrap();
+        assert_eq!(corpora.error_rate, 250.0 / 2000.0);
+    }
+
+    #[test]
+    fn argument_counts_never_look_at_values() {
+        assert_eq!(argument_count(&InvokeBody::Json(serde_json::json!({ "path": "/etc", "token": "x" }))), 2);
+        assert_eq!(argument_count(&InvokeBody::Json(serde_json::Value::Null)), 0);
+        assert_eq!(argument_count(&InvokeBody::Raw(vec![1, 2, 3])), 1);
+    }
+
+    #[test]
+    fn payloads_are_measured_as_they_arrived() {
+        let mut headers = HeaderMap::new();
+        let json = InvokeBody::Json(serde_json::json!({ "a": 1 }));
+        assert_eq!(payload_size(&json, &headers), 0, "no length came with it");
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("7"));
+        assert_eq!(payload_size(&json, &headers), 7);
+        assert_eq!(payload_size(&InvokeBody::Raw(vec![0; 64]), &headers), 64);
+    }
+
+    #[test]
+    fn every_registered_command_has_its_own_slot() {
+        let stats = CommandStats::new(crate::COMMANDS);
+        for (slot, command) in crate::COMMANDS.iter().enumerate() {
+            assert_eq!((stats.slot(command), stats.name(slot)), (slot, *command));
+        }
+        assert_eq!(stats.name(stats.slot("not_a_command")), UNKNOWN);
+    }
+
+    // An app whose handler answers "ping" at once, "later" from another
+    // thread after a while, and "fail" with an error
+    fn app() -> (tauri::App<MockRuntime>, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let handler = |invoke: Invoke<MockRuntime>| match invoke.message.command() {
+            "ping" => {
+                invoke.resolver.resolve("pong");
+                true
+            }
+            "later" => {
+                let resolver = invoke.resolver;
+                std::thread::spawn(move || {
+                    std::thread::sleep(ms(50));
+                    resolver.resolve(serde_json::json!({ "n": 1 }));
+                });
+                true
+            }
+            "fail" => {
+                invoke.resolver.reject(crate::error::CommandError::NotFound("job 7".to_string()));
+                true
+            }
+            _ => false,
+        };
+        let app = tauri::test::mock_builder()
+            .manage(CommandStats::new(&["ping", "later", "fail"]))
+            .manage(SettingsStore::load(dir.path().join("settings.toml")))
+            .invoke_handler(measured(handler))
+            .build(tauri::test::mock_context(tauri::test::noop_assets()))
+            .unwrap();
+        (app, dir)
+    }
+
+    fn invoke(webview: &tauri::WebviewWindow<MockRuntime>, command: &str) -> Result<InvokeResponseBody, serde_json::Value> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("9"));
+        let request = InvokeRequest {
+            cmd: command.to_string(),
+            callback: CallbackFn(0),
+            error: CallbackFn(1),
+            url: webview.url().unwrap(),
+            body: InvokeBody::Json(serde_json::json!({ "id": 7 })),
+            headers,
+            invoke_key: tauri::test::INVOKE_KEY.to_string(),
+        };
+        tauri::test::get_ipc_response(webview, request)
+    }
+
+    #[test]
+    fn calls_are_recorded_with_their_own_response() {
+        let (app, _dir) = app();
+        let webview = tauri::WebviewWindowBuilder::new(&app, "main", Default::default()).build().unwrap();
+        assert_eq!(invoke(&webview, "ping").unwrap().deserialize::<String>().unwrap(), "pong");
+        assert_eq!(invoke(&webview, "later").unwrap().deserialize::<serde_json::Value>().unwrap()["n"], 1);
+        assert_eq!(invoke(&webview, "fail").unwrap_err()["kind"], "NotFound");
+        assert!(invoke(&webview, "nope").is_err());
+
+        let summary = app.state::<CommandStats>().summary();
+        let of = |command: &str| summary.iter().find(|s| s.command == command).unwrap().clone();
+        let (ping, later, fail, unknown) = (of("ping"), of("later"), of("fail"), of(UNKNOWN));
+        assert_eq!((ping.count, ping.avg_payload_bytes, ping.avg_result_bytes, ping.error_rate), (1, 9, 6, 0.0));
+        assert!(later.p50_ms >= 50.0, "timed until it answered, not until it was handed off: {}", later.p50_ms);
+        assert_eq!(later.avg_result_bytes, 7);
+        assert_eq!((fail.error_rate, fail.last_error.as_deref()), (1.0, Some("job 7")));
+        assert_eq!((unknown.count, unknown.error_rate), (1, 1.0));
+        assert_eq!(summary.len(), 4);
+    }
+}
@@ -0,0 +1,714 @@
+// Exporting and importing Halbert's configuration as one JSON file.
+//
+// The file carries a format version and only the categories that were
+// picked. Secrets are never written: webhook URLs, host tokens and the
+// backup password stay in this machine's secret store. An import that
+// brings in something needing one (a webhook, a host, a backup repository)
+// creates it without the secret and lists it under `needs_attention`.
+// Machine-local settings (mode, active host, corpus path, onboarding) are
+// not exported either.
+//
+// "merge" adds and updates by id and keeps everything else; "replace" makes
+// each imported category exactly what the file holds. Categories missing
+// from the file are left alone in both modes. The whole file is checked
+// before anything is written, so a bad entry leaves everything as it was.
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::alerts::{self, AlertRule};
+use crate::app::{AppHandle, Emitter, State};
+use crate::backup;
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::hosts;
+use crate::job_templates::{self, JobEnvironment, ParamSchema, TemplateInfo};
+use crate::notifications::{self, WebhookEntry};
+use crate::policy::{self, RiskLevel, RiskPolicy};
+use crate::sandbox;
+use crate::secrets;
+use crate::settings::{self, Settings, SettingsStore};
+use crate::snapshots::SnapshotPolicy;
+use crate::transfers::{self, MaybeTransfer, TransferStore};
+
+pub const FORMAT: &str = "halbert-configuration";
+pub const VERSION: u32 = 1;
+
+pub const CATEGORIES: &[&str] = &[
+    "settings",
+    "alert_rules",
+    "job_templates",
+    "webhooks",
+    "risk_policies",
+    "certificate_targets",
+];
+
+// Settings fields that are their own category
+const CATEGORY_SETTINGS: &[&str] = &["alert_rules", "webhooks", "risk_policies", "certificate_targets"];
+// Settings that only make sense on the machine they were set on
+const LOCAL_SETTINGS: &[&str] = &[
+    "mode",
+    "active_host",
+    "corpus_path",
+    "corpora",
+    "mount_roots",
+    "onboarding_skipped",
+    "approver_identity",
+    // Its position depends on this machine's screens
+    "widget",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportMode {
+    Merge,
+    Replace,
+}
+
+impl ImportMode {
+    pub fn parse(value: &str) -> CommandResult<Self> {
+        match value {
+            "merge" => Ok(ImportMode::Merge),
+            "replace" => Ok(ImportMode::Replace),
+            other => Err(CommandError::InvalidInput(format!(
+                "unknown import mode '{}', expected merge or replace",
+                other
+            ))),
+        }
+    }
+}
+
+// A user job template without its timestamps
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TemplateExport {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub command: String,
+    #[serde(default)]
+    pub args_template: Vec<String>,
+    #[serde(default)]
+    pub param_schema: Option<ParamSchema>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub artifacts_dir: Option<String>,
+    pub risk_level: RiskLevel,
+    // Secret values themselves stay behind, like webhook URLs
+    #[serde(default, flatten)]
+    pub environment: JobEnvironment,
+    #[serde(default, flatten)]
+    pub snapshot: SnapshotPolicy,
+}
+
+impl From<&TemplateInfo> for TemplateExport {
+    fn from(info: &TemplateInfo) -> Self {
+        TemplateExport {
+            name: info.name.clone(),
+            description: info.description.clone(),
+            command: info.command.clone(),
+            args_template: info.args_template.clone(),
+            param_schema: info.param_schema.clone(),
+            timeout_secs: info.timeout_secs,
+            artifacts_dir: info.artifacts_dir.clone(),
+            risk_level: info.risk_level,
+            environment: info.environment.clone(),
+            snapshot: info.snapshot.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct ConfigFile {
+    pub format: String,
+    pub version: u32,
+    #[serde(default)]
+    pub exported_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<Map<String, Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alert_rules: Option<Vec<AlertRule>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_templates: Option<Vec<TemplateExport>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhooks: Option<Vec<WebhookEntry>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub risk_policies: Option<Vec<RiskPolicy>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate_targets: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct CategoryCounts {
+    pub created: usize,
+    pub updated: usize,
+    // Already identical, not understood by this version, or refused
+    pub skipped: usize,
+    // Only in replace mode
+    pub removed: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Attention {
+    pub category: String,
+    pub item: String,
+    pub reason: String,
+}
+
+#[derive(Serialize)]
+pub struct ImportReport {
+    pub mode: String,
+    pub categories: BTreeMap<String, CategoryCounts>,
+    pub needs_attention: Vec<Attention>,
+}
+
+#[derive(Serialize)]
+pub struct ExportSummary {
+    pub path: String,
+    pub version: u32,
+    // Items written per category; settings counts fields
+    pub counts: BTreeMap<String, usize>,
+}
+
+pub struct ImportPlan {
+    pub settings: Settings,
+    // Validated user templates to write, and the ones replace mode drops
+    pub templates: Vec<TemplateInfo>,
+    pub remove_templates: Vec<String>,
+    pub report: ImportReport,
+}
+
+// Empty means every category
+pub fn parse_include(include: &[String]) -> CommandResult<Vec<&'static str>> {
+    if include.is_empty() {
+        return Ok(CATEGORIES.to_vec());
+    }
+    include
+        .iter()
+        .map(|name| {
+            CATEGORIES.iter().copied().find(|c| *c == name.trim()).ok_or_else(|| {
+                CommandError::InvalidInput(format!(
+                    "unknown category '{}', expected one of {}",
+                    name,
+                    CATEGORIES.join(", ")
+                ))
+            })
+        })
+        .collect()
+}
+
+fn settings_map(settings: &Settings) -> CommandResult<Map<String, Value>> {
+    match serde_json::to_value(settings) {
+        Ok(Value::Object(fields)) => Ok(fields),
+        _ => Err(CommandError::Internal("settings did not serialize to an object".to_string())),
+    }
+}
+
+fn exportable_setting(key: &str) -> bool {
+    !CATEGORY_SETTINGS.contains(&key) && !LOCAL_SETTINGS.contains(&key)
+}
+
+pub fn build_export(settings: &Settings, templates: &[TemplateInfo], include: &[&str]) -> CommandResult<ConfigFile> {
+    let wanted = |category: &str| include.contains(&category);
+    let mut file = ConfigFile {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        ..ConfigFile::default()
+    };
+    if wanted("settings") {
+        let mut fields = settings_map(settings)?;
+        fields.retain(|key, _| exportable_setting(key));
+        file.settings = Some(fields);
+    }
+    if wanted("alert_rules") {
+        file.alert_rules = Some(settings.alert_rules.clone());
+    }
+    if wanted("job_templates") {
+        file.job_templates = Some(templates.iter().filter(|t| !t.builtin).map(TemplateExport::from).collect());
+    }
+    if wanted("webhooks") {
+        file.webhooks = Some(settings.webhooks.clone());
+    }
+    if wanted("risk_policies") {
+        file.risk_policies = Some(settings.risk_policies.clone());
+    }
+    if wanted("certificate_targets") {
+        file.certificate_targets = Some(settings.certificate_targets.clone());
+    }
+    Ok(file)
+}
+
+pub fn export_counts(file: &ConfigFile) -> BTreeMap<String, usize> {
+    let counts = [
+        ("settings", file.settings.as_ref().map(Map::len)),
+        ("alert_rules", file.alert_rules.as_ref().map(Vec::len)),
+        ("job_templates", file.job_templates.as_ref().map(Vec::len)),
+        ("webhooks", file.webhooks.as_ref().map(Vec::len)),
+        ("risk_policies", file.risk_policies.as_ref().map(Vec::len)),
+        ("certificate_targets", file.certificate_targets.as_ref().map(Vec::len)),
+    ];
+    counts
+        .into_iter()
+        .filter_map(|(category, count)| Some((category.to_string(), count?)))
+        .collect()
+}
+
+// The format and version are checked before the rest is read, so a file
+// from a newer Halbert gets a clear error rather than a field mismatch
+pub fn parse_file(text: &str) -> CommandResult<ConfigFile> {
+    let value: Value = serde_json::from_str(text)
+        .map_err(|e| CommandError::InvalidInput(format!("not a JSON configuration file: {}", e)))?;
+    if value.get("format").and_then(Value::as_str) != Some(FORMAT) {
+        return Err(CommandError::InvalidInput("not a Halbert configuration export".to_string()));
+    }
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version == 0 {
+        return Err(CommandError::InvalidInput("the file has no format version".to_string()));
+    }
+    if version > VERSION as u64 {
+        return Err(CommandError::NotSupported(format!(
+            "the file uses format version {}, this Halbert reads up to {}",
+            version, VERSION
+        )));
+    }
+    serde_json::from_value(value).map_err(|e| CommandError::InvalidInput(format!("invalid configuration file: {}", e)))
+}
+
+fn same<T: Serialize>(a: &T, b: &T) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+// Merge or replace a list whose items are identified by `key`
+pub fn merge_by_key<T, K>(current: &[T], incoming: &[T], key: K, mode: ImportMode) -> (Vec<T>, CategoryCounts)
+where
+    T: Serialize + Clone,
+    K: Fn(&T) -> String,
+{
+    let mut counts = CategoryCounts::default();
+    let mut merged: Vec<T> = match mode {
+        ImportMode::Merge => current.to_vec(),
+        ImportMode::Replace => Vec::new(),
+    };
+    for item in incoming {
+        let id = key(item);
+        match current.iter().find(|c| key(c) == id) {
+            None => counts.created += 1,
+            Some(existing) if same(existing, item) => counts.skipped += 1,
+            Some(_) => counts.updated += 1,
+        }
+        match merged.iter_mut().find(|m| key(m) == id) {
+            Some(slot) => *slot = item.clone(),
+            None => merged.push(item.clone()),
+        }
+    }
+    if mode == ImportMode::Replace {
+        counts.removed = current.iter().filter(|c| !incoming.iter().any(|i| key(i) == key(c))).count();
+    }
+    (merged, counts)
+}
+
+// Field by field; replace mode resets exported fields the file leaves out.
+// Fields this version doesn't know are skipped.
+pub fn merge_settings(
+    current: &Settings,
+    incoming: &Map<String, Value>,
+    mode: ImportMode,
+) -> CommandResult<(Settings, CategoryCounts)> {
+    let mut counts = CategoryCounts::default();
+    let mut fields = settings_map(current)?;
+    if mode == ImportMode::Replace {
+        let defaults = settings_map(&Settings::default())?;
+        for (key, default) in defaults {
+            if exportable_setting(&key) && !incoming.contains_key(&key) && fields.get(&key) != Some(&default) {
+                fields.insert(key, default);
+                counts.updated += 1;
+            }
+        }
+    }
+    for (key, value) in incoming {
+        if !exportable_setting(key) || !fields.contains_key(key) || fields.get(key) == Some(value) {
+            counts.skipped += 1;
+        } else {
+            fields.insert(key.clone(), value.clone());
+            counts.updated += 1;
+        }
+    }
+    let settings = serde_json::from_value(Value::Object(fields))
+        .map_err(|e| CommandError::InvalidInput(format!("settings: {}", e)))?;
+    Ok((settings, counts))
+}
+
+fn attention(category: &str, item: &str, reason: &str) -> Attention {
+    Attention {
+        category: category.to_string(),
+        item: item.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+// Templates whose program isn't installed (per `installed`) are refused
+// unless `force`
+fn plan_templates(
+    current: &[TemplateInfo],
+    incoming: &[TemplateExport],
+    mode: ImportMode,
+    force: bool,
+    installed: &dyn Fn(&str) -> bool,
+    plan: &mut ImportPlan,
+) -> CommandResult<CategoryCounts> {
+    let mut counts = CategoryCounts::default();
+    for template in incoming {
+        let info = job_templates::definition(
+            template.name.clone(),
+            template.command.clone(),
+            template.args_template.clone(),
+            template.param_schema.clone(),
+            template.timeout_secs,
+            template.artifacts_dir.clone(),
+            template.risk_level,
+            Some(template.description.clone()),
+            template.environment.clone(),
+            template.snapshot.clone(),
+        )
+        .map_err(|e| CommandError::InvalidInput(format!("job template '{}': {}", template.name, e)))?;
+        if !force && !installed(&info.command) {
+            counts.skipped += 1;
+            plan.report.needs_attention.push(attention(
+                "job_templates",
+                &info.name,
+                &format!("{} isn't installed on this host; import with force to keep it anyway", info.command),
+            ));
+            continue;
+        }
+        match current.iter().find(|c| c.name == info.name) {
+            None => counts.created += 1,
+            Some(existing) if same(&TemplateExport::from(existing), &TemplateExport::from(&info)) => {
+                counts.skipped += 1;
+                continue;
+            }
+            Some(_) => counts.updated += 1,
+        }
+        plan.templates.push(info);
+    }
+    if mode == ImportMode::Replace {
+        plan.remove_templates = current
+            .iter()
+            .filter(|c| !incoming.iter().any(|t| t.name == c.name))
+            .map(|c| c.name.clone())
+            .collect();
+        counts.removed = plan.remove_templates.len();
+    }
+    Ok(counts)
+}
+
+// Everything an import would change, without writing anything
+pub fn plan_import(
+    current: &Settings,
+    current_templates: &[TemplateInfo],
+    file: &ConfigFile,
+    mode: ImportMode,
+    force: bool,
+    installed: &dyn Fn(&str) -> bool,
+    has_secret: &dyn Fn(&str) -> bool,
+) -> CommandResult<ImportPlan> {
+    let mut plan = ImportPlan {
+        settings: current.clone(),
+        templates: Vec::new(),
+        remove_templates: Vec::new(),
+        report: ImportReport {
+            mode: match mode {
+                ImportMode::Merge => "merge".to_string(),
+                ImportMode::Replace => "replace".to_string(),
+            },
+            categories: BTreeMap::new(),
+            needs_attention: Vec::new(),
+        },
+    };
+    if let Some(incoming) = &file.settings {
+        let (merged, counts) = merge_settings(current, incoming, mode)?;
+        plan.settings = merged;
+        plan.report.categories.insert("settings".to_string(), counts);
+        if incoming.contains_key("hosts") {
+            for host in &plan.settings.hosts {
+                if let Some(target) = &host.ssh {
+                    if !Path::new(&target.key_path).is_file() {
+                        plan.report.needs_attention.push(attention(
+                            "settings",
+                            &format!("host {}", host.name),
+                            &format!("SSH key {} isn't on this machine; copy it or pick another", target.key_path),
+                        ));
+                    }
+                } else if !has_secret(&hosts::token_secret_name(&host.id)) {
+                    plan.report.needs_attention.push(attention(
+                        "settings",
+                        &format!("host {}", host.name),
+                        "no API token stored on this machine; add one if the agent requires it",
+                    ));
+                }
+                if host.changes_url.is_some() && !has_secret(&hosts::changes_secret_name(&host.id)) {
+                    plan.report.needs_attention.push(attention(
+                        "settings",
+                        &format!("host {}", host.name),
+                        "no approvals token stored on this machine; decisions sent to it will be refused",
+                    ));
+                }
+            }
+        }
+        if incoming.get("backup").is_some_and(|b| !b.is_null()) && !has_secret(backup::PASSWORD_SECRET) {
+            plan.report.needs_attention.push(attention(
+                "settings",
+                "backup",
+                "the repository password isn't stored on this machine; set it before the next backup",
+            ));
+        }
+    }
+    if let Some(incoming) = &file.alert_rules {
+        let (merged, counts) = merge_by_key(&current.alert_rules, incoming, |r| r.id.clone(), mode);
+        alerts::validate_rules(&merged)?;
+        plan.settings.alert_rules = merged;
+        plan.report.categories.insert("alert_rules".to_string(), counts);
+    }
+    if let Some(incoming) = &file.risk_policies {
+        let (merged, counts) = merge_by_key(&current.risk_policies, incoming, |p| p.id.clone(), mode);
+        policy::validate_policies(&merged)?;
+        plan.settings.risk_policies = merged;
+        plan.report.categories.insert("risk_policies".to_string(), counts);
+    }
+    if let Some(incoming) = &file.webhooks {
+        for webhook in incoming {
+            notifications::check_webhook(&webhook.events, &webhook.format)
+                .map_err(|e| CommandError::InvalidInput(format!("webhook {}: {}", webhook.id, e)))?;
+            if !has_secret(&notifications::url_secret_name(&webhook.id)) {
+                plan.report.needs_attention.push(attention(
+                    "webhooks",
+                    &webhook.id,
+                    "no URL stored on this machine; it won't deliver until one is set",
+                ));
+            }
+        }
+        let (merged, counts) = merge_by_key(&current.webhooks, incoming, |w| w.id.clone(), mode);
+        plan.settings.webhooks = merged;
+        plan.report.categories.insert("webhooks".to_string(), counts);
+    }
+    if let Some(incoming) = &file.certificate_targets {
+        let (merged, counts) = merge_by_key(&current.certificate_targets, incoming, |t| t.clone(), mode);
+        plan.settings.certificate_targets = merged;
+        plan.report.categories.insert("certificate_targets".to_string(), counts);
+    }
+    if let Some(incoming) = &file.job_templates {
+        let counts = plan_templates(current_templates, incoming, mode, force, installed, &mut plan)?;
+        for template in &plan.templates {
+            for name in template.environment.secret_env.values() {
+                if !has_secret(&job_templates::secret_name(name)) {
+                    plan.report.needs_attention.push(attention(
+                        "job_templates",
+                        &template.name,
+                        &format!("job secret '{}' isn't set on this machine; set it before running the job", name),
+                    ));
+                }
+            }
+        }
+        plan.report.categories.insert("job_templates".to_string(), counts);
+    }
+    Ok(plan)
+}
+
+// What export_configuration would write, as a transfer when it's large
+// (see transfers)
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn preview_configuration_export(
+    settings: State<'_, SettingsStore>,
+    db: State<'_, Database>,
+    transfers: State<'_, TransferStore>,
+    include: Vec<String>,
+) -> CommandResult<MaybeTransfer<ConfigFile>> {
+    let include = parse_include(&include)?;
+    let templates = job_templates::custom_templates(&db)?;
+    let file = build_export(&settings.get(), &templates, &include)?;
+    transfers::respond(&transfers, file)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn export_configuration(
+    settings: State<'_, SettingsStore>,
+    db: State<'_, Database>,
+    path: String,
+    include: Vec<String>,
+) -> CommandResult<ExportSummary> {
+    let target = sandbox::CONFIG_FILE.validate(&settings.get(), Path::new(path.trim()))?.into_path_buf();
+    let include = parse_include(&include)?;
+    let templates = job_templates::custom_templates(&db)?;
+    let file = build_export(&settings.get(), &templates, &include)?;
+    let text = serde_json::to_string_pretty(&file).map_err(|e| CommandError::Internal(e.to_string()))?;
+    std::fs::write(&target, text)?;
+    println!("[Halbert] Exported configuration ({}) to {}", include.join(", "), target.display());
+    Ok(ExportSummary {
+        path: target.display().to_string(),
+        version: VERSION,
+        counts: export_counts(&file),
+    })
+}
+
+// Templates are written before the settings file; if saving settings then
+// fails, the templates are already in place and the import can be re-run
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn import_configuration(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    db: State<'_, Database>,
+    path: String,
+    mode: String,
+    force: Option<bool>,
+) -> CommandResult<ImportReport> {
+    let source = sandbox::CONFIG_FILE.validate(&settings.get(), Path::new(path.trim()))?.into_path_buf();
+    let mode = ImportMode::parse(mode.trim())?;
+    let text = std::fs::read_to_string(&source)
+        .map_err(|e| CommandError::NotFound(format!("{}: {}", source.display(), e)))?;
+    let file = parse_file(&text)?;
+    let templates = job_templates::custom_templates(&db)?;
+    let installed = |program: &str| exec::find_in_path(program).is_some();
+    let has_secret = |name: &str| matches!(secrets::read(name), Ok(Some(_)));
+
+    let mut report = None;
+    let updated = settings.update(|current| {
+        let plan = plan_import(current, &templates, &file, mode, force.unwrap_or(false), &installed, &has_secret)?;
+        settings::check_live_values(current, &plan.settings)?;
+        job_templates::import_templates(&db, &plan.templates, &plan.remove_templates)?;
+        report = Some(plan.report);
+        Ok(plan.settings)
+    })?;
+    let _ = app.emit("settings://changed", &updated);
+    let report = report.ok_or_else(|| CommandError::Internal("import produced no report".to_string()))?;
+    println!(
+        "[Halbert] Imported configuration from {} ({}), {} item(s) need attention",
+        source.display(),
+        report.mode,
+        report.needs_attention.len()
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn configured() -> Settings {
+        let mut settings = Settings {
+            slow_command_ms: 750,
+            protected_units: vec!["sshd.service".to_string()],
+            ..Settings::default()
+        };
+        settings.alert_rules = vec![serde_json::from_value(json!({
+            "id": "hot", "name": "CPU hot", "signal": "cpu_percent", "comparison": "gt", "threshold": 90.0, "for_secs": 60
+        }))
+        .unwrap()];
+        settings.risk_policies = vec![serde_json::from_value(json!({
+            "id": "apt", "task": "package_upgrade", "min_risk": "high", "decision": "always_require_confirmation"
+        }))
+        .unwrap()];
+        settings.webhooks = vec![WebhookEntry {
+            id: "ops".to_string(),
+            events: vec!["alert_triggered".to_string()],
+            format: "json".to_string(),
+        }];
+        settings.certificate_targets = vec!["example.org:443".to_string()];
+        // Machine-local, so never exported
+        settings.corpus_path = Some("/srv/corpus".to_string());
+        settings.onboarding_skipped = true;
+        settings
+    }
+
+    fn store_templates(db: &Database) {
+        let template = job_templates::definition(
+            "nightly-report".to_string(),
+            "rsync".to_string(),
+            vec!["-a".to_string(), "/var/log/".to_string(), "/srv/reports/".to_string()],
+            None,
+            Some(300),
+            None,
+            RiskLevel::Medium,
+            Some("Writes the nightly report".to_string()),
+            JobEnvironment {
+                cwd: Some("/tmp".to_string()),
+                env: [("LANG".to_string(), "C".to_string())].into(),
+                secret_env: [("REPORT_TOKEN".to_string(), "report".to_string())].into(),
+            },
+            SnapshotPolicy::default(),
+        )
+        .unwrap();
+        job_templates::import_templates(db, &[template], &[]).unwrap();
+    }
+
+    fn without_timestamp(mut file: Value) -> Value {
+        file.as_object_mut().unwrap().remove("exported_at");
+        file
+    }
+
+    #[test]
+    fn an_export_imported_into_a_clean_store_exports_the_same() {
+        let (source, source_db) = (configured(), Database::in_memory());
+        store_templates(&source_db);
+        let templates = job_templates::custom_templates(&source_db).unwrap();
+        let exported = build_export(&source, &templates, CATEGORIES).unwrap();
+        let text = serde_json::to_string_pretty(&exported).unwrap();
+
+        let (clean, clean_db) = (Settings::default(), Database::in_memory());
+        let file = parse_file(&text).unwrap();
+        let plan = plan_import(&clean, &[], &file, ImportMode::Merge, false, &|_| true, &|_| false).unwrap();
+        job_templates::import_templates(&clean_db, &plan.templates, &plan.remove_templates).unwrap();
+        let imported = plan.settings;
+
+        let reexported = build_export(&imported, &job_templates::custom_templates(&clean_db).unwrap(), CATEGORIES).unwrap();
+        assert_eq!(
+            without_timestamp(serde_json::to_value(&reexported).unwrap()),
+            without_timestamp(serde_json::to_value(&exported).unwrap())
+        );
+        assert_eq!(imported.slow_command_ms, 750);
+        assert_eq!(imported.alert_rules[0].id, "hot");
+        assert_eq!((imported.corpus_path, imported.onboarding_skipped), (None, false));
+
+        let counts = &plan.report.categories;
+        for category in ["alert_rules", "risk_policies", "webhooks", "certificate_targets", "job_templates"] {
+            assert_eq!(counts[category], CategoryCounts { created: 1, ..Default::default() }, "{}", category);
+        }
+        // The webhook URL and the job secret stayed behind
+        let attention: Vec<(&str, &str)> =
+            plan.report.needs_attention.iter().map(|a| (a.category.as_str(), a.item.as_str())).collect();
+        assert_eq!(attention, [("webhooks", "ops"), ("job_templates", "nightly-report")]);
+    }
+
+    #[test]
+    fn importing_the_same_file_twice_changes_nothing() {
+        let source = configured();
+        let text = serde_json::to_string(&build_export(&source, &[], CATEGORIES).unwrap()).unwrap();
+        let file = parse_file(&text).unwrap();
+        let once = plan_import(&Settings::default(), &[], &file, ImportMode::Merge, false, &|_| true, &|_| true).unwrap();
+        let twice = plan_import(&once.settings, &[], &file, ImportMode::Replace, false, &|_| true, &|_| true).unwrap();
+        for (category, counts) in &twice.report.categories {
+            assert_eq!((counts.created, counts.updated, counts.removed), (0, 0, 0), "{}", category);
+        }
+        assert!(same(&once.settings, &twice.settings));
+    }
+
+    #[test]
+    fn only_picked_categories_are_written() {
+        let file = build_export(&configured(), &[], &parse_include(&["webhooks".to_string()]).unwrap()).unwrap();
+        assert_eq!(export_counts(&file), [("webhooks".to_string(), 1)].into());
+        let text = serde_json::to_string(&file).unwrap();
+        assert!(!text.contains("alert_rules") && !text.contains("settings"));
+        assert!(parse_include(&["passwords".to_string()]).is_err());
+    }
+
+    #[test]
+    fn files_from_elsewhere_or_newer_versions_are_refused() {
+        assert!(matches!(parse_file("{}"), Err(CommandError::InvalidInput(_))));
+        assert!(matches!(parse_file(r#"{"format":"halbert-configuration"}"#), Err(CommandError::InvalidInput(_))));
+        assert!(matches!(
+            parse_file(r#"{"format":"halbert-configuration","version":2}"#),
+            Err(CommandError::NotSupported(_))
+        ));
+    }
+}
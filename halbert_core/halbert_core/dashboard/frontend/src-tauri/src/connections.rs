@@ -0,0 +1,604 @@
+// Open TCP connections, which process owns them, and roughly how much
+// each process is moving.
+//
+// Sockets come from /proc/net/tcp and tcp6 (see network for the address
+// encoding). Owners are found by matching socket inodes against the
+// /proc/<pid>/fd links, so without root only our own user's processes get
+// a pid. Throughput comes from the kernel's per-socket tcp_info counters
+// (bytes_acked, bytes_received) as `ss -ti` prints them, read twice
+// SAMPLE_WINDOW apart; a process's rate is the sum over its sockets.
+// That's an estimate: the window is short, sockets opened or closed inside
+// it have no rate, and without ss there are no rates at all. The table
+// says which of these applied in `attribution` rather than implying
+// precision.
+//
+// Remote addresses can be reverse-resolved. Answers (and failures) are
+// cached for DNS_CACHE_TTL, lookups that don't finish within
+// DNS_DEADLINE are left for the next call, and the
+// `connection_dns_lookups` setting turns it off altogether.
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::app::State;
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::network::parse_proc_net_endpoint;
+use crate::settings::SettingsStore;
+
+const SAMPLE_WINDOW: Duration = Duration::from_secs(1);
+const DNS_CACHE_TTL: Duration = Duration::from_secs(600);
+const DNS_DEADLINE: Duration = Duration::from_millis(1500);
+const DEFAULT_LIMIT: usize = 500;
+const MAX_LIMIT: usize = 5000;
+
+// When each address was looked up, and the name it resolved to
+type DnsCache = HashMap<IpAddr, (Instant, Option<String>)>;
+
+static DNS_CACHE: Mutex<Option<DnsCache>> = Mutex::new(None);
+
+// /proc/net/tcp state codes
+const TCP_STATES: &[(&str, &str)] = &[
+    ("01", "established"),
+    ("02", "syn_sent"),
+    ("03", "syn_recv"),
+    ("04", "fin_wait1"),
+    ("05", "fin_wait2"),
+    ("06", "time_wait"),
+    ("07", "close"),
+    ("08", "close_wait"),
+    ("09", "last_ack"),
+    ("0A", "listen"),
+    ("0B", "closing"),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawSocket {
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
+    pub state: String,
+    pub uid: u32,
+    // 0 once the socket has no owner (e.g. time_wait)
+    pub inode: u64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Connection {
+    // "ipv4" or "ipv6"
+    pub family: String,
+    pub local_address: String,
+    pub local_port: u16,
+    pub remote_address: String,
+    pub remote_port: u16,
+    // Reverse DNS name, when looked up and found
+    pub remote_host: Option<String>,
+    pub state: String,
+    pub uid: u32,
+    pub pid: Option<u32>,
+    pub process: Option<String>,
+    // None when the socket wasn't there for the whole sample
+    pub sent_bytes_per_sec: Option<f64>,
+    pub received_bytes_per_sec: Option<f64>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ProcessThroughput {
+    // None gathers sockets whose owner couldn't be seen
+    pub pid: Option<u32>,
+    pub process: Option<String>,
+    pub connections: usize,
+    pub sent_bytes_per_sec: f64,
+    pub received_bytes_per_sec: f64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ConnectionTable {
+    pub connections: Vec<Connection>,
+    // Busiest first
+    pub processes: Vec<ProcessThroughput>,
+    // Matching connections before the limit
+    pub total: usize,
+    pub truncated: bool,
+    // How the rates were estimated, and what they miss
+    pub attribution: String,
+    pub sample_secs: f64,
+}
+
+pub fn tcp_state_name(code: &str) -> Option<&'static str> {
+    TCP_STATES.iter().find(|(c, _)| c.eq_ignore_ascii_case(code)).map(|(_, name)| *name)
+}
+
+// sl, local, remote, state, queues, timer, retransmits, uid, timeout, inode
+pub fn parse_proc_net_connections(text: &str) -> Vec<RawSocket> {
+    text.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (local, port) = parse_proc_net_endpoint(fields.get(1)?)?;
+            let (remote, remote_port) = parse_proc_net_endpoint(fields.get(2)?)?;
+            Some(RawSocket {
+                local: SocketAddr::new(local, port),
+                remote: SocketAddr::new(remote, remote_port),
+                state: tcp_state_name(fields.get(3)?)?.to_string(),
+                uid: fields.get(7)?.parse().ok()?,
+                inode: fields.get(9)?.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+// "192.168.1.5:52344", "[2001:db8::1]:443" or "[fe80::1%eth0]:22"
+pub fn parse_ss_endpoint(text: &str) -> Option<SocketAddr> {
+    let (address, port) = text.rsplit_once(':')?;
+    let address = address.trim_start_matches('[').trim_end_matches(']');
+    let address = address.split('%').next()?;
+    Some(SocketAddr::new(address.parse().ok()?, port.parse().ok()?))
+}
+
+// `ss -tin`: a line per socket (state, queues, local, peer), then an
+// indented line of tcp_info fields. Returns (bytes_acked, bytes_received)
+// by (local, peer).
+pub fn parse_ss_counters(text: &str) -> HashMap<(SocketAddr, SocketAddr), (u64, u64)> {
+    let mut counters = HashMap::new();
+    let mut current = None;
+    for line in text.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            current = match (fields.get(3), fields.get(4)) {
+                (Some(local), Some(peer)) => parse_ss_endpoint(local).zip(parse_ss_endpoint(peer)),
+                _ => None,
+            };
+            continue;
+        }
+        let Some(key) = current.take() else {
+            continue;
+        };
+        let field = |name: &str| {
+            line.split_whitespace()
+                .find_map(|f| f.strip_prefix(name))
+                .and_then(|v| v.parse::<u64>().ok())
+        };
+        counters.insert(key, (field("bytes_acked:").unwrap_or(0), field("bytes_received:").unwrap_or(0)));
+    }
+    counters
+}
+
+// "socket:[12345]" from a /proc/<pid>/fd link
+pub fn socket_inode(link: &str) -> Option<u64> {
+    link.strip_prefix("socket:[")?.strip_suffix(']')?.parse().ok()
+}
+
+pub fn per_second(before: u64, after: u64, secs: f64) -> f64 {
+    if secs <= 0.0 {
+        return 0.0;
+    }
+    after.saturating_sub(before) as f64 / secs
+}
+
+// "established" (the default), "all", or one of TCP_STATES' names
+pub fn state_filter(state: Option<&str>) -> CommandResult<Option<&'static str>> {
+    match state.map(str::trim).filter(|s| !s.is_empty()) {
+        None => Ok(Some("established")),
+        Some(s) if s.eq_ignore_ascii_case("all") => Ok(None),
+        Some(s) => TCP_STATES
+            .iter()
+            .map(|(_, name)| *name)
+            .find(|name| name.eq_ignore_ascii_case(s))
+            .map(Some)
+            .ok_or_else(|| CommandError::InvalidInput(format!("unknown connection state {}", s))),
+    }
+}
+
+pub fn sort_connections(connections: &mut [Connection], sort: &str) -> CommandResult<()> {
+    let total = |c: &Connection| c.sent_bytes_per_sec.unwrap_or(0.0) + c.received_bytes_per_sec.unwrap_or(0.0);
+    match sort {
+        "throughput" => connections.sort_by(|a, b| total(b).total_cmp(&total(a))),
+        "sent" => connections.sort_by(|a, b| {
+            b.sent_bytes_per_sec.unwrap_or(0.0).total_cmp(&a.sent_bytes_per_sec.unwrap_or(0.0))
+        }),
+        "received" => connections.sort_by(|a, b| {
+            b.received_bytes_per_sec.unwrap_or(0.0).total_cmp(&a.received_bytes_per_sec.unwrap_or(0.0))
+        }),
+        "process" => connections.sort_by(|a, b| (&a.process, a.pid).cmp(&(&b.process, b.pid))),
+        "remote" => connections.sort_by(|a, b| {
+            (&a.remote_address, a.remote_port).cmp(&(&b.remote_address, b.remote_port))
+        }),
+        other => return Err(CommandError::InvalidInput(format!("can't sort connections by {}", other))),
+    }
+    Ok(())
+}
+
+pub fn per_process(connections: &[Connection]) -> Vec<ProcessThroughput> {
+    let mut grouped: BTreeMap<Option<u32>, ProcessThroughput> = BTreeMap::new();
+    for c in connections {
+        let entry = grouped.entry(c.pid).or_insert_with(|| ProcessThroughput {
+            pid: c.pid,
+            process: c.process.clone(),
+            connections: 0,
+            sent_bytes_per_sec: 0.0,
+            received_bytes_per_sec: 0.0,
+        });
+        entry.connections += 1;
+        entry.sent_bytes_per_sec += c.sent_bytes_per_sec.unwrap_or(0.0);
+        entry.received_bytes_per_sec += c.received_bytes_per_sec.unwrap_or(0.0);
+    }
+    let mut processes: Vec<ProcessThroughput> = grouped.into_values().collect();
+    let busy = |p: &ProcessThroughput| p.sent_bytes_per_sec + p.received_bytes_per_sec;
+    processes.sort_by(|a, b| busy(b).total_cmp(&busy(a)));
+    processes
+}
+
+fn tcp_sockets() -> Vec<RawSocket> {
+    let mut sockets = Vec::new();
+    for file in ["tcp", "tcp6"] {
+        if let Ok(text) = std::fs::read_to_string(format!("/proc/net/{}", file)) {
+            sockets.extend(parse_proc_net_connections(&text));
+        }
+    }
+    sockets
+}
+
+fn ss_counters() -> Option<HashMap<(SocketAddr, SocketAddr), (u64, u64)>> {
+    exec::stdout("ss", &["-t", "-i", "-n"]).map(|text| parse_ss_counters(&text))
+}
+
+// Socket inode -> (pid, process name), for the processes we may look into
+fn socket_owners() -> HashMap<u64, (u32, String)> {
+    let mut owners = HashMap::new();
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return owners;
+    };
+    for entry in entries.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|n| n.parse::<u32>().ok()) else {
+            continue;
+        };
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        let mut comm = None;
+        for fd in fds.flatten() {
+            let Some(inode) = std::fs::read_link(fd.path()).ok().and_then(|l| socket_inode(&l.to_string_lossy()))
+            else {
+                continue;
+            };
+            let name = comm.get_or_insert_with(|| {
+                std::fs::read_to_string(entry.path().join("comm")).map(|c| c.trim().to_string()).unwrap_or_default()
+            });
+            owners.insert(inode, (pid, name.clone()));
+        }
+    }
+    owners
+}
+
+#[cfg(target_os = "linux")]
+fn reverse_lookup(ip: IpAddr) -> Option<String> {
+    use std::ffi::CStr;
+
+    let mut host = [0 as libc::c_char; 1025];
+    let rc = match ip {
+        IpAddr::V4(v4) => {
+            // Safety: an all-zero sockaddr_in is valid; the fields set below
+            // make it the address to look up
+            let mut sin: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_addr.s_addr = u32::from_ne_bytes(v4.octets());
+            unsafe {
+                libc::getnameinfo(
+                    &sin as *const libc::sockaddr_in as *const libc::sockaddr,
+                    std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+                    host.as_mut_ptr(),
+                    host.len() as libc::socklen_t,
+                    std::ptr::null_mut(),
+                    0,
+                    libc::NI_NAMEREQD,
+                )
+            }
+        }
+        IpAddr::V6(v6) => {
+            let mut sin6: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_addr.s6_addr = v6.octets();
+            unsafe {
+                libc::getnameinfo(
+                    &sin6 as *const libc::sockaddr_in6 as *const libc::sockaddr,
+                    std::mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t,
+                    host.as_mut_ptr(),
+                    host.len() as libc::socklen_t,
+                    std::ptr::null_mut(),
+                    0,
+                    libc::NI_NAMEREQD,
+                )
+            }
+        }
+    };
+    if rc != 0 {
+        return None;
+    }
+    Some(unsafe { CStr::from_ptr(host.as_ptr()) }.to_string_lossy().into_owned())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reverse_lookup(_ip: IpAddr) -> Option<String> {
+    None
+}
+
+// Cached names for `addresses`, looking up the uncached ones in parallel
+// until DNS_DEADLINE; late answers still land in the cache
+fn resolve(addresses: Vec<IpAddr>) -> HashMap<IpAddr, String> {
+    let now = Instant::now();
+    let mut names = HashMap::new();
+    let mut missing = Vec::new();
+    {
+        let mut cache = DNS_CACHE.lock().unwrap();
+        let cache = cache.get_or_insert_with(HashMap::new);
+        cache.retain(|_, (at, _)| now.duration_since(*at) < DNS_CACHE_TTL);
+        for ip in addresses {
+            match cache.get(&ip) {
+                Some((_, name)) => {
+                    if let Some(name) = name {
+                        names.insert(ip, name.clone());
+                    }
+                }
+                None => missing.push(ip),
+            }
+        }
+    }
+    let (tx, rx) = mpsc::channel();
+    let pending = missing.len();
+    for ip in missing {
+        let tx = tx.clone();
+        std::thread::spawn(move || {
+            let name = reverse_lookup(ip);
+            DNS_CACHE
+                .lock()
+                .unwrap()
+                .get_or_insert_with(HashMap::new)
+                .insert(ip, (Instant::now(), name.clone()));
+            let _ = tx.send((ip, name));
+        });
+    }
+    let deadline = now + DNS_DEADLINE;
+    for _ in 0..pending {
+        let Some(wait) = deadline.checked_duration_since(Instant::now()) else {
+            break;
+        };
+        match rx.recv_timeout(wait) {
+            Ok((ip, Some(name))) => {
+                names.insert(ip, name);
+            }
+            Ok((_, None)) => {}
+            Err(_) => break,
+        }
+    }
+    names
+}
+
+fn attribution(rates: bool) -> String {
+    if rates {
+        format!(
+            "Estimated from tcp_info byte counters (ss -ti) sampled over {} s and summed per process. \
+             Sockets opened or closed during the sample have no rate, and sockets of processes we \
+             can't inspect are grouped without a pid.",
+            SAMPLE_WINDOW.as_secs_f64()
+        )
+    } else {
+        "Unavailable: ss (iproute2) isn't installed, so per-socket byte counters can't be read.".to_string()
+    }
+}
+
+// `state`: "established" (default), "all" or a TCP state name. `sort`:
+// "throughput" (default), "sent", "received", "process" or "remote".
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_connections(
+    settings: State<'_, SettingsStore>,
+    state: Option<String>,
+    sort: Option<String>,
+    limit: Option<usize>,
+    resolve_hosts: Option<bool>,
+) -> CommandResult<ConnectionTable> {
+    let wanted = state_filter(state.as_deref())?;
+    let sort = sort.unwrap_or_else(|| "throughput".to_string());
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let lookups = resolve_hosts.unwrap_or(true) && settings.get().connection_dns_lookups;
+
+    let started = Instant::now();
+    let before = ss_counters();
+    if before.is_some() {
+        std::thread::sleep(SAMPLE_WINDOW);
+    }
+    let after = before.as_ref().and(ss_counters());
+    let secs = started.elapsed().as_secs_f64();
+    let sockets: Vec<RawSocket> = tcp_sockets()
+        .into_iter()
+        .filter(|s| s.state != "listen" && wanted.is_none_or(|w| s.state == w))
+        .collect();
+    let owners = socket_owners();
+
+    let mut connections: Vec<Connection> = sockets
+        .iter()
+        .map(|s| {
+            let owner = owners.get(&s.inode).filter(|_| s.inode != 0);
+            let key = (s.local, s.remote);
+            let rates = before
+                .as_ref()
+                .zip(after.as_ref())
+                .and_then(|(b, a)| b.get(&key).zip(a.get(&key)))
+                .map(|(b, a)| (per_second(b.0, a.0, secs), per_second(b.1, a.1, secs)));
+            Connection {
+                family: if s.local.is_ipv4() { "ipv4" } else { "ipv6" }.to_string(),
+                local_address: s.local.ip().to_string(),
+                local_port: s.local.port(),
+                remote_address: s.remote.ip().to_string(),
+                remote_port: s.remote.port(),
+                remote_host: None,
+                state: s.state.clone(),
+                uid: s.uid,
+                pid: owner.map(|o| o.0),
+                process: owner.map(|o| o.1.clone()),
+                sent_bytes_per_sec: rates.map(|r| r.0),
+                received_bytes_per_sec: rates.map(|r| r.1),
+            }
+        })
+        .collect();
+    let processes = per_process(&connections);
+    sort_connections(&mut connections, &sort)?;
+    let total = connections.len();
+    connections.truncate(limit);
+
+    if lookups {
+        let mut remotes: Vec<IpAddr> = connections
+            .iter()
+            .filter_map(|c| c.remote_address.parse::<IpAddr>().ok())
+            .filter(|ip| !ip.is_loopback() && !ip.is_unspecified())
+            .collect();
+        remotes.sort();
+        remotes.dedup();
+        let names = resolve(remotes);
+        for c in &mut connections {
+            if let Ok(ip) = c.remote_address.parse::<IpAddr>() {
+                c.remote_host = names.get(&ip).cloned();
+            }
+        }
+    }
+
+    Ok(ConnectionTable {
+        connections,
+        processes,
+        total,
+        truncated: total > limit,
+        attribution: attribution(before.is_some()),
+        sample_secs: secs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `ss -tin` from iproute2 6.1
+    const SS_TIN: &str = "State Recv-Q Send-Q Local Address:Port  Peer Address:Port Process
+ESTAB 0      0        192.168.1.5:52344 140.82.112.4:443
+\t cubic wscale:7,7 rto:232 rtt:30.5/4.2 mss:1448 cwnd:10 bytes_sent:5120 bytes_acked:5121 bytes_received:40960 segs_out:40
+ESTAB 0      0      [2001:db8::5]:40000 [2001:db8::1]:443
+\t cubic rto:204 bytes_acked:1 bytes_received:2
+ESTAB 0      0     [fe80::1%wlan0]:22 [fe80::2%wlan0]:51000
+\t cubic rto:204
+";
+
+    fn connection(pid: Option<u32>, process: &str, sent: Option<f64>, received: Option<f64>) -> Connection {
+        Connection {
+            family: "ipv4".to_string(),
+            local_address: "192.168.1.5".to_string(),
+            local_port: 50000,
+            remote_address: "140.82.112.4".to_string(),
+            remote_port: 443,
+            remote_host: None,
+            state: "established".to_string(),
+            uid: 1000,
+            pid,
+            process: pid.map(|_| process.to_string()),
+            sent_bytes_per_sec: sent,
+            received_bytes_per_sec: received,
+        }
+    }
+
+    fn addr(text: &str) -> SocketAddr {
+        text.parse().unwrap()
+    }
+
+    #[cfg(target_endian = "little")]
+    #[test]
+    fn proc_net_tcp_lines() {
+        let text = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:0277 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 23456 1 0000000000000000 100 0 0 10 0
+   1: 0501A8C0:CC78 04705A8C:01BB 01 00000000:00000000 02:0000066D 00000000  1000        0 98765 2 0000000000000000 20 4 30 10 -1
+   2: 0501A8C0:CC79 04705A8C:01BB 06 00000000:00000000 03:00000F1E 00000000     0        0 0 3 0000000000000000
+   3: 0501A8C0:CC7A 04705A8C:01BB 99 00000000:00000000 00:00000000 00000000     0        0 1 1 0000000000000000
+";
+        let sockets = parse_proc_net_connections(text);
+        assert_eq!(sockets.len(), 3);
+        assert_eq!(
+            sockets[1],
+            RawSocket {
+                local: addr("192.168.1.5:52344"),
+                remote: addr("140.90.112.4:443"),
+                state: "established".to_string(),
+                uid: 1000,
+                inode: 98765,
+            }
+        );
+        assert_eq!((sockets[0].state.as_str(), sockets[0].local), ("listen", addr("127.0.0.1:631")));
+        // time_wait has no owner left
+        assert_eq!((sockets[2].state.as_str(), sockets[2].inode), ("time_wait", 0));
+    }
+
+    #[test]
+    fn ss_counters_by_endpoint_pair() {
+        let counters = parse_ss_counters(SS_TIN);
+        assert_eq!(counters.len(), 3);
+        assert_eq!(counters[&(addr("192.168.1.5:52344"), addr("140.82.112.4:443"))], (5121, 40960));
+        assert_eq!(counters[&(addr("[2001:db8::5]:40000"), addr("[2001:db8::1]:443"))], (1, 2));
+        // Scope ids dropped; no counters printed reads as zero
+        assert_eq!(counters[&(addr("[fe80::1]:22"), addr("[fe80::2]:51000"))], (0, 0));
+    }
+
+    #[test]
+    fn small_parsers() {
+        assert_eq!(tcp_state_name("0a"), Some("listen"));
+        assert_eq!(tcp_state_name("0C"), None);
+        assert_eq!(socket_inode("socket:[12345]"), Some(12345));
+        assert_eq!(socket_inode("pipe:[12345]"), None);
+        assert_eq!(socket_inode("/dev/null"), None);
+        assert_eq!(per_second(1000, 3000, 2.0), 1000.0);
+        // A socket reused between the readings went backwards
+        assert_eq!(per_second(3000, 1000, 2.0), 0.0);
+        assert_eq!(per_second(0, 1000, 0.0), 0.0);
+    }
+
+    #[test]
+    fn state_filters() {
+        assert_eq!(state_filter(None).unwrap(), Some("established"));
+        assert_eq!(state_filter(Some("  ")).unwrap(), Some("established"));
+        assert_eq!(state_filter(Some("ALL")).unwrap(), None);
+        assert_eq!(state_filter(Some("Time_Wait")).unwrap(), Some("time_wait"));
+        assert!(matches!(state_filter(Some("open")), Err(CommandError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn sorting_puts_the_busiest_first() {
+        let mut connections = vec![
+            connection(Some(1), "curl", Some(10.0), Some(0.0)),
+            connection(None, "", None, None),
+            connection(Some(2), "firefox", Some(5.0), Some(500.0)),
+        ];
+        sort_connections(&mut connections, "throughput").unwrap();
+        let pids: Vec<Option<u32>> = connections.iter().map(|c| c.pid).collect();
+        assert_eq!(pids, [Some(2), Some(1), None]);
+        sort_connections(&mut connections, "sent").unwrap();
+        assert_eq!(connections[0].pid, Some(1));
+        sort_connections(&mut connections, "process").unwrap();
+        assert_eq!(connections[0].pid, None);
+        assert!(matches!(sort_connections(&mut connections, "age"), Err(CommandError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn throughput_is_summed_per_process() {
+        let connections = [
+            connection(Some(7), "firefox", Some(100.0), Some(1000.0)),
+            connection(Some(7), "firefox", Some(50.0), None),
+            connection(Some(9), "ssh", Some(1.0), Some(1.0)),
+            connection(None, "", Some(10.0), Some(10.0)),
+        ];
+        let processes = per_process(&connections);
+        assert_eq!(processes.len(), 3);
+        assert_eq!(processes[0].pid, Some(7));
+        assert_eq!(processes[0].connections, 2);
+        assert_eq!((processes[0].sent_bytes_per_sec, processes[0].received_bytes_per_sec), (150.0, 1000.0));
+        // Unowned sockets are gathered under no pid
+        assert_eq!((processes[1].pid, processes[1].process.as_deref()), (None, None));
+        assert_eq!(processes[2].pid, Some(9));
+    }
+}
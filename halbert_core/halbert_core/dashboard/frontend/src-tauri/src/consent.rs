@@ -0,0 +1,325 @@
+// Consent for capabilities that reach past Halbert's own business.
+//
+// Each capability in CAPABILITIES names the commands that use it. The
+// invoke guard (readonly::admit) asks here after the mode and pause
+// checks: a command whose capability hasn't been decided is rejected with
+// ConsentRequired, carrying the capability and why it's asked for, and
+// runs once the user calls grant_consent. A remembered decision is stored in the database;
+// one that isn't lasts until Halbert exits. A denial is remembered the same
+// way and rejects with PermissionDenied until it's revoked. Revoking drops
+// the decision, so the next use asks again. The notification worker checks
+// the webhooks capability itself before anything leaves the machine.
+// Grants, denials and revocations are audited.
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::app::State;
+use crate::audit;
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+
+pub struct Capability {
+    pub id: &'static str,
+    pub title: &'static str,
+    pub explanation: &'static str,
+    pub commands: &'static [&'static str],
+}
+
+pub const WEBHOOKS: &str = "webhooks";
+
+pub const CAPABILITIES: &[Capability] = &[
+    Capability {
+        id: "system_journal",
+        title: "Read the system journal",
+        explanation: "The journal includes authentication attempts, sudo use and other users' sessions.",
+        commands: &["follow_journal"],
+    },
+    Capability {
+        id: "other_users_processes",
+        title: "List other users' processes",
+        explanation: "Process lists show what every account on this machine is running, with command lines.",
+        commands: &["get_process_tree", "get_usage_by_user", "get_gpu_processes"],
+    },
+    Capability {
+        id: WEBHOOKS,
+        title: "Send events to webhooks",
+        explanation: "Alerts, jobs and approvals are sent to the webhook URLs you add, off this machine.",
+        commands: &["add_webhook", "test_webhook"],
+    },
+];
+
+pub fn capability(id: &str) -> Option<&'static Capability> {
+    CAPABILITIES.iter().find(|c| c.id == id)
+}
+
+pub fn capability_for(command: &str) -> Option<&'static Capability> {
+    CAPABILITIES.iter().find(|c| c.commands.contains(&command))
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Decision {
+    pub granted: bool,
+    pub remember: bool,
+    pub decided_by: String,
+    pub decided_at: String,
+}
+
+#[derive(Serialize)]
+pub struct ConsentEntry {
+    pub capability: String,
+    pub title: String,
+    pub explanation: String,
+    pub commands: Vec<String>,
+    // None while undecided
+    pub decision: Option<Decision>,
+}
+
+// Decisions that aren't remembered, by capability
+#[derive(Default)]
+pub struct ConsentStore {
+    session: Mutex<HashMap<String, Decision>>,
+}
+
+impl ConsentStore {
+    // The session decision wins over a stored one
+    pub fn decision(&self, db: &Database, capability: &str) -> CommandResult<Option<Decision>> {
+        if let Some(decision) = self.session.lock().unwrap().get(capability) {
+            return Ok(Some(decision.clone()));
+        }
+        db.with_conn(|conn| {
+            conn.query_row(
+                "SELECT granted, decided_by, decided_at FROM consents WHERE capability = ?1",
+                params![capability],
+                |row| {
+                    Ok(Decision {
+                        granted: row.get::<_, i64>(0)? != 0,
+                        remember: true,
+                        decided_by: row.get(1)?,
+                        decided_at: row.get(2)?,
+                    })
+                },
+            )
+            .optional()
+        })
+    }
+
+    pub fn allows(&self, db: &Database, capability: &str) -> bool {
+        self.decision(db, capability).is_ok_and(|d| d.is_some_and(|d| d.granted))
+    }
+}
+
+// Whether `command` may run as far as consent goes
+pub fn check(store: &ConsentStore, db: &Database, command: &str) -> CommandResult<()> {
+    let Some(capability) = capability_for(command) else {
+        return Ok(());
+    };
+    match store.decision(db, capability.id)? {
+        Some(decision) if decision.granted => Ok(()),
+        Some(_) => Err(CommandError::PermissionDenied(format!(
+            "\"{}\" was declined; revoke that decision to be asked again",
+            capability.title
+        ))),
+        None => Err(CommandError::ConsentRequired {
+            capability: capability.id.to_string(),
+            explanation: format!("{}: {}", capability.title, capability.explanation),
+        }),
+    }
+}
+
+fn known(id: &str) -> CommandResult<&'static Capability> {
+    capability(id).ok_or_else(|| CommandError::NotFound(format!("capability '{}'", id)))
+}
+
+fn decide(
+    store: &ConsentStore,
+    db: &Database,
+    capability: &str,
+    granted: bool,
+    remember: bool,
+) -> CommandResult<ConsentEntry> {
+    let capability = known(capability)?;
+    let decision = Decision {
+        granted,
+        remember,
+        decided_by: audit::local_actor(),
+        decided_at: chrono::Utc::now().to_rfc3339(),
+    };
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM consents WHERE capability = ?1", params![capability.id])?;
+        if remember {
+            tx.execute(
+                "INSERT INTO consents (capability, granted, decided_by, decided_at) VALUES (?1, ?2, ?3, ?4)",
+                params![capability.id, granted as i64, decision.decided_by, decision.decided_at],
+            )?;
+        }
+        tx.commit()
+    })?;
+    let mut session = store.session.lock().unwrap();
+    if remember {
+        session.remove(capability.id);
+    } else {
+        session.insert(capability.id.to_string(), decision.clone());
+    }
+    drop(session);
+
+    let action = if granted { "consent.grant" } else { "consent.deny" };
+    let detail = serde_json::json!({ "remember": remember });
+    if let Err(e) = audit::record(db, &decision.decided_by, action, capability.id, &detail) {
+        println!("[Halbert] Couldn't audit {} of {}: {}", action, capability.id, e);
+    }
+    println!("[Halbert] {} {} ({})", action, capability.id, if remember { "remembered" } else { "this session" });
+    Ok(entry(capability, Some(decision)))
+}
+
+fn entry(capability: &Capability, decision: Option<Decision>) -> ConsentEntry {
+    ConsentEntry {
+        capability: capability.id.to_string(),
+        title: capability.title.to_string(),
+        explanation: capability.explanation.to_string(),
+        commands: capability.commands.iter().map(|c| c.to_string()).collect(),
+        decision,
+    }
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn grant_consent(
+    store: State<'_, ConsentStore>,
+    db: State<'_, Database>,
+    capability: String,
+    remember: bool,
+) -> CommandResult<ConsentEntry> {
+    decide(&store, &db, &capability, true, remember)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn deny_consent(
+    store: State<'_, ConsentStore>,
+    db: State<'_, Database>,
+    capability: String,
+    remember: bool,
+) -> CommandResult<ConsentEntry> {
+    decide(&store, &db, &capability, false, remember)
+}
+
+// Forgets the decision, remembered or not; the next use asks again
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn revoke_consent(
+    store: State<'_, ConsentStore>,
+    db: State<'_, Database>,
+    capability: String,
+) -> CommandResult<ConsentEntry> {
+    revoke(&store, &db, &capability)
+}
+
+fn revoke(store: &ConsentStore, db: &Database, capability: &str) -> CommandResult<ConsentEntry> {
+    let capability = known(capability)?;
+    let removed = db.with_conn(|conn| {
+        conn.execute("DELETE FROM consents WHERE capability = ?1", params![capability.id])
+    })?;
+    let in_session = store.session.lock().unwrap().remove(capability.id).is_some();
+    if removed == 0 && !in_session {
+        return Err(CommandError::Conflict(format!("\"{}\" hasn't been decided", capability.title)));
+    }
+    let actor = audit::local_actor();
+    if let Err(e) = audit::record(db, &actor, "consent.revoke", capability.id, &serde_json::json!({})) {
+        println!("[Halbert] Couldn't audit consent.revoke of {}: {}", capability.id, e);
+    }
+    println!("[Halbert] consent.revoke {}", capability.id);
+    Ok(entry(capability, None))
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn list_consents(store: State<'_, ConsentStore>, db: State<'_, Database>) -> CommandResult<Vec<ConsentEntry>> {
+    CAPABILITIES
+        .iter()
+        .map(|capability| Ok(entry(capability, store.decision(&db, capability.id)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automation::Gate;
+    use crate::readonly::{admit, Mode};
+
+    #[test]
+    fn the_first_use_asks_and_a_grant_lets_it_through() {
+        let (store, db) = (ConsentStore::default(), Database::in_memory());
+        match check(&store, &db, "follow_journal") {
+            Err(CommandError::ConsentRequired { capability, explanation }) => {
+                assert_eq!(capability, "system_journal");
+                assert!(explanation.starts_with("Read the system journal: "), "{}", explanation);
+            }
+            other => panic!("expected ConsentRequired, got {:?}", other),
+        }
+        // Commands without a capability never ask
+        check(&store, &db, "get_system_info").unwrap();
+
+        decide(&store, &db, "system_journal", true, false).unwrap();
+        check(&store, &db, "follow_journal").unwrap();
+        // Only that capability
+        assert!(matches!(check(&store, &db, "get_process_tree"), Err(CommandError::ConsentRequired { .. })));
+    }
+
+    #[test]
+    fn a_denial_is_refused_until_revoked() {
+        let (store, db) = (ConsentStore::default(), Database::in_memory());
+        decide(&store, &db, WEBHOOKS, false, true).unwrap();
+        assert!(matches!(check(&store, &db, "add_webhook"), Err(CommandError::PermissionDenied(_))));
+        revoke(&store, &db, WEBHOOKS).unwrap();
+        assert!(matches!(check(&store, &db, "add_webhook"), Err(CommandError::ConsentRequired { .. })));
+    }
+
+    #[test]
+    fn revoking_takes_effect_on_the_next_call() {
+        let (store, db) = (ConsentStore::default(), Database::in_memory());
+        // Remembered in the database, and for this session only
+        decide(&store, &db, "other_users_processes", true, true).unwrap();
+        decide(&store, &db, "system_journal", true, false).unwrap();
+        check(&store, &db, "get_usage_by_user").unwrap();
+        check(&store, &db, "follow_journal").unwrap();
+        assert!(store.allows(&db, "other_users_processes"));
+
+        let entry = revoke(&store, &db, "other_users_processes").unwrap();
+        assert!(entry.decision.is_none());
+        revoke(&store, &db, "system_journal").unwrap();
+        assert!(matches!(check(&store, &db, "get_usage_by_user"), Err(CommandError::ConsentRequired { .. })));
+        assert!(matches!(check(&store, &db, "follow_journal"), Err(CommandError::ConsentRequired { .. })));
+        assert!(!store.allows(&db, "other_users_processes"));
+
+        // Nothing left to revoke
+        assert!(matches!(revoke(&store, &db, "system_journal"), Err(CommandError::Conflict(_))));
+        assert!(matches!(revoke(&store, &db, "telepathy"), Err(CommandError::NotFound(_))));
+        let audited: i64 = db
+            .with_conn(|conn| {
+                conn.query_row("SELECT COUNT(*) FROM audit_log WHERE action = 'consent.revoke'", [], |r| r.get(0))
+            })
+            .unwrap();
+        assert_eq!(audited, 2);
+    }
+
+    #[test]
+    fn mode_then_pause_then_consent() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, db) = (ConsentStore::default(), Database::in_memory());
+        let running = Gate::load(dir.path().join("running.json"));
+        let paused = Gate::paused(dir.path().join("paused.json"));
+
+        // Read-only mode is asked first, before a pause or a consent prompt
+        for command in ["start_job", "add_webhook"] {
+            assert!(matches!(admit(Mode::ReadOnly, &paused, &store, &db, command), Err(CommandError::ReadOnlyMode(_))));
+        }
+        // then the pause
+        assert!(matches!(admit(Mode::Full, &paused, &store, &db, "start_job"), Err(CommandError::AutomationPaused(_))));
+        admit(Mode::Full, &running, &store, &db, "start_job").unwrap();
+        // and last consent, which a pause doesn't answer for
+        for gate in [&running, &paused] {
+            assert!(matches!(admit(Mode::Full, gate, &store, &db, "add_webhook"), Err(CommandError::ConsentRequired { .. })));
+        }
+        decide(&store, &db, WEBHOOKS, true, false).unwrap();
+        admit(Mode::Full, &paused, &store, &db, "add_webhook").unwrap();
+    }
+}
@@ -0,0 +1,492 @@
+// Per-container resource metrics from the Docker (or Podman) Engine API.
+//
+// While a socket is present, running containers are sampled at the host
+// metrics cadence. History is keyed by container name, not id, so a
+// container that is recreated keeps its series; the first point after the
+// id changes (or after the container was gone for a while) is flagged as a
+// gap so the chart doesn't draw a line across it. Points taken at the
+// slower idle cadence while the dashboard was hidden are flagged too, but
+// still carry rates. After a suspend every series starts over the same way
+// (see `reset_rates`).
+//
+// The stats endpoint reports cumulative CPU counters. A usage percentage
+// is the container's CPU time delta over the whole system's delta between
+// two of our samples, scaled by the online CPU count; see `cpu_percent`.
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::activity::UiActivity;
+use crate::app::{AppHandle, Manager, State};
+use crate::collectors::{self, CollectorRegistry};
+use crate::error::{CommandError, CommandResult};
+use crate::sampler::MetricsHistory;
+use crate::selfusage::SelfLimiter;
+use crate::settings::SettingsStore;
+
+const DOCKER_SOCKET: &str = "/var/run/docker.sock";
+const PODMAN_SOCKET: &str = "/run/podman/podman.sock";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+// Missing for this many intervals and the next point starts a new segment
+const GAP_INTERVALS: i64 = 3;
+
+#[derive(Serialize, Clone, Copy)]
+pub struct ContainerPoint {
+    // Unix seconds
+    pub at: i64,
+    // None for the first sample of a segment, which has nothing to diff against
+    pub cpu_percent: Option<f32>,
+    pub memory_bytes: u64,
+    // None when the container has no memory limit
+    pub memory_limit_bytes: Option<u64>,
+    pub rx_bytes_per_sec: Option<f64>,
+    pub tx_bytes_per_sec: Option<f64>,
+    // First point after a restart or an absence
+    pub gap: bool,
+}
+
+// Cumulative counters from one stats reading
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CpuCounters {
+    // Container CPU time, ns
+    pub total_usage: u64,
+    // Whole-host CPU time, ns
+    pub system_usage: u64,
+    pub online_cpus: u32,
+}
+
+// This tick's sampling interval and the normal one, in seconds
+#[derive(Clone, Copy)]
+struct Cadence {
+    normal: i64,
+    current: i64,
+}
+
+#[derive(Clone, Copy)]
+struct Reading {
+    at: i64,
+    cpu: Option<CpuCounters>,
+    rx_bytes: u64,
+    tx_bytes: u64,
+}
+
+struct Series {
+    id: String,
+    last: Option<Reading>,
+    // The next reading has no previous one to take rates from
+    reset: bool,
+    points: VecDeque<ContainerPoint>,
+}
+
+#[derive(Default)]
+pub struct ContainerHistory {
+    series: Mutex<HashMap<String, Series>>,
+}
+
+// Percent of one CPU, so a container saturating two cores reads 200.
+// None when a counter went backwards (a reset) or no system time passed.
+pub fn cpu_percent(previous: &CpuCounters, current: &CpuCounters) -> Option<f32> {
+    let container = current.total_usage.checked_sub(previous.total_usage)?;
+    let system = current.system_usage.checked_sub(previous.system_usage)?;
+    if system == 0 {
+        return None;
+    }
+    Some((container as f64 / system as f64 * current.online_cpus.max(1) as f64 * 100.0) as f32)
+}
+
+fn rate(previous: u64, current: u64, seconds: i64) -> Option<f64> {
+    let delta = current.checked_sub(previous)?;
+    (seconds > 0).then(|| delta as f64 / seconds as f64)
+}
+
+impl ContainerHistory {
+    fn record(&self, name: &str, id: &str, stats: &Value, at: i64, cadence: Cadence, capacity: usize) {
+        let reading = Reading {
+            at,
+            cpu: cpu_counters(stats),
+            rx_bytes: network_total(stats, "rx_bytes"),
+            tx_bytes: network_total(stats, "tx_bytes"),
+        };
+        let (memory_bytes, memory_limit_bytes) = memory(stats);
+
+        let mut all = self.series.lock().unwrap();
+        let series = all.entry(name.to_string()).or_insert_with(|| Series {
+            id: id.to_string(),
+            last: None,
+            reset: false,
+            points: VecDeque::new(),
+        });
+        let restarted = series.id != id;
+        let stale = series.last.is_some_and(|last| at - last.at > cadence.current * GAP_INTERVALS);
+        let slowed = series.last.is_some_and(|last| at - last.at > cadence.normal * GAP_INTERVALS);
+        let previous = if restarted || stale || series.reset { None } else { series.last };
+        series.id = id.to_string();
+        series.reset = false;
+
+        let elapsed = previous.map(|p| at - p.at).unwrap_or(0);
+        let point = ContainerPoint {
+            at,
+            cpu_percent: previous
+                .and_then(|p| p.cpu)
+                .zip(reading.cpu)
+                .and_then(|(p, c)| cpu_percent(&p, &c)),
+            memory_bytes,
+            memory_limit_bytes,
+            rx_bytes_per_sec: previous.and_then(|p| rate(p.rx_bytes, reading.rx_bytes, elapsed)),
+            tx_bytes_per_sec: previous.and_then(|p| rate(p.tx_bytes, reading.tx_bytes, elapsed)),
+            gap: (series.last.is_some() && previous.is_none()) || slowed,
+        };
+        series.last = Some(reading);
+        series.points.push_back(point);
+        while series.points.len() > capacity {
+            series.points.pop_front();
+        }
+    }
+
+    // Counter deltas across a suspend would be spread over the whole sleep;
+    // the next reading of each series is a gap with no rates instead
+    pub fn reset_rates(&self) {
+        for series in self.series.lock().unwrap().values_mut() {
+            series.reset = true;
+        }
+    }
+
+    // Drop series with nothing left inside the history window
+    fn expire(&self, oldest: i64) {
+        let mut all = self.series.lock().unwrap();
+        for series in all.values_mut() {
+            while series.points.front().is_some_and(|p| p.at < oldest) {
+                series.points.pop_front();
+            }
+        }
+        all.retain(|_, series| !series.points.is_empty());
+    }
+}
+
+fn cpu_counters(stats: &Value) -> Option<CpuCounters> {
+    let cpu = stats.get("cpu_stats")?;
+    let total_usage = cpu.pointer("/cpu_usage/total_usage")?.as_u64()?;
+    let system_usage = cpu.get("system_cpu_usage")?.as_u64()?;
+    // Older engines leave online_cpus out; count the per-CPU entries instead
+    let online_cpus = cpu
+        .get("online_cpus")
+        .and_then(Value::as_u64)
+        .or_else(|| cpu.pointer("/cpu_usage/percpu_usage").and_then(Value::as_array).map(|a| a.len() as u64))
+        .unwrap_or(1) as u32;
+    Some(CpuCounters {
+        total_usage,
+        system_usage,
+        online_cpus,
+    })
+}
+
+// Usage minus reclaimable page cache, as `docker stats` shows it
+fn memory(stats: &Value) -> (u64, Option<u64>) {
+    let memory = stats.get("memory_stats");
+    let usage = memory.and_then(|m| m.get("usage")).and_then(Value::as_u64).unwrap_or(0);
+    // cgroup v2 reports inactive_file, v1 total_inactive_file
+    let inactive = memory
+        .and_then(|m| m.get("stats"))
+        .and_then(|s| s.get("inactive_file").or_else(|| s.get("total_inactive_file")))
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+    // An unlimited container reports the host's memory (or u64::MAX) as its limit
+    let limit = memory
+        .and_then(|m| m.get("limit"))
+        .and_then(Value::as_u64)
+        .filter(|&limit| limit > 0 && limit < u64::MAX);
+    (usage.saturating_sub(inactive), limit)
+}
+
+fn network_total(stats: &Value, key: &str) -> u64 {
+    stats
+        .get("networks")
+        .and_then(Value::as_object)
+        .map(|networks| networks.values().filter_map(|n| n.get(key)?.as_u64()).sum())
+        .unwrap_or(0)
+}
+
+// Docker's socket, else rootful or rootless Podman's
+pub fn socket_path() -> Option<PathBuf> {
+    let mut candidates = vec![PathBuf::from(DOCKER_SOCKET), PathBuf::from(PODMAN_SOCKET)];
+    if let Some(runtime) = std::env::var_os("XDG_RUNTIME_DIR") {
+        candidates.push(PathBuf::from(runtime).join("podman/podman.sock"));
+    }
+    candidates.into_iter().find(|path| path.exists())
+}
+
+// Decode a chunked body; anything malformed ends the body early
+fn dechunk(mut body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    while let Some(end) = body.windows(2).position(|w| w == b"\r\n") {
+        let size = std::str::from_utf8(&body[..end])
+            .ok()
+            .and_then(|line| usize::from_str_radix(line.split(';').next().unwrap_or("").trim(), 16).ok());
+        let Some(size) = size.filter(|&s| s > 0) else {
+            break;
+        };
+        let start = end + 2;
+        let Some(chunk) = body.get(start..start + size) else {
+            break;
+        };
+        out.extend_from_slice(chunk);
+        body = body.get(start + size + 2..).unwrap_or_default();
+    }
+    out
+}
+
+#[cfg(unix)]
+fn get_json(socket: &std::path::Path, path: &str) -> CommandResult<Value> {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    let unreachable = |e: std::io::Error| CommandError::HostUnreachable(format!("{}: {}", socket.display(), e));
+    let mut stream = UnixStream::connect(socket).map_err(unreachable)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT)).map_err(unreachable)?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT)).map_err(unreachable)?;
+    // HTTP/1.0 so the engine closes the connection when the body is done
+    write!(stream, "GET {} HTTP/1.0\r\nHost: docker\r\n\r\n", path).map_err(unreachable)?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).map_err(unreachable)?;
+
+    let split = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| CommandError::Remote("truncated response from the container engine".to_string()))?;
+    let head = String::from_utf8_lossy(&response[..split]).to_ascii_lowercase();
+    let status: u16 = head.split_whitespace().nth(1).and_then(|code| code.parse().ok()).unwrap_or(0);
+    let body = &response[split + 4..];
+    let body = if head.contains("transfer-encoding: chunked") {
+        dechunk(body)
+    } else {
+        body.to_vec()
+    };
+    if !(200..300).contains(&status) {
+        return Err(CommandError::Remote(format!(
+            "container engine answered HTTP {} for {}: {}",
+            status,
+            path,
+            String::from_utf8_lossy(&body).trim()
+        )));
+    }
+    serde_json::from_slice(&body).map_err(|e| CommandError::Remote(format!("bad JSON from the container engine: {}", e)))
+}
+
+#[cfg(not(unix))]
+fn get_json(_socket: &std::path::Path, _path: &str) -> CommandResult<Value> {
+    Err(CommandError::NotSupported("container metrics need a unix socket".to_string()))
+}
+
+// (name, id) of every running container
+fn running(socket: &std::path::Path) -> CommandResult<Vec<(String, String)>> {
+    let list = get_json(socket, "/containers/json")?;
+    Ok(list
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|c| {
+            let id = c.get("Id")?.as_str()?.to_string();
+            // Names come with a leading slash
+            let name = c.get("Names")?.as_array()?.first()?.as_str()?.trim_start_matches('/').to_string();
+            Some((name, id))
+        })
+        .collect())
+}
+
+fn sample(app: &AppHandle, socket: &std::path::Path, cadence: Cadence) -> CommandResult<()> {
+    let capacity = app.state::<MetricsHistory>().capacity().max(1);
+    let history = app.state::<ContainerHistory>();
+    for (name, id) in running(socket)? {
+        // one-shot skips the engine's own one-second precpu wait; we diff our readings
+        match get_json(socket, &format!("/containers/{}/stats?stream=false&one-shot=true", id)) {
+            Ok(stats) => history.record(&name, &id, &stats, chrono::Utc::now().timestamp(), cadence, capacity),
+            // Stopped between the list and the stats call
+            Err(CommandError::Remote(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    history.expire(chrono::Utc::now().timestamp() - cadence.current * capacity as i64);
+    Ok(())
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        let mut reported = false;
+        loop {
+            let registry = app.state::<CollectorRegistry>();
+            registry.wait_enabled(&app, "containers");
+            let settings = app.state::<SettingsStore>().get();
+            let activity = app.state::<UiActivity>();
+            let normal = collectors::interval_secs(&settings, "containers", settings.metrics_interval_secs);
+            // Slowed while the dashboard is hidden
+            let interval = activity.interval_secs(normal, settings.idle_metrics_interval_secs);
+            let cadence = Cadence {
+                normal: normal as i64,
+                current: interval as i64,
+            };
+            if let Some(socket) = socket_path() {
+                match registry.run("containers", || sample(&app, &socket, cadence)) {
+                    Ok(()) => reported = false,
+                    Err(e) if !reported => {
+                        println!("[Halbert] Container sampling failed: {}", e);
+                        reported = true;
+                    }
+                    Err(_) => {}
+                }
+            }
+            let backoff = app.state::<SelfLimiter>().interval_factor();
+            activity.sleep(Duration::from_secs(interval * backoff));
+        }
+    });
+}
+
+// Oldest first; `seconds` limits it to the most recent window
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_container_metrics_history(
+    history: State<'_, ContainerHistory>,
+    container: String,
+    seconds: Option<u64>,
+) -> CommandResult<Vec<ContainerPoint>> {
+    let name = container.trim().trim_start_matches('/');
+    let all = history.series.lock().unwrap();
+    let series = all
+        .get(name)
+        .ok_or_else(|| CommandError::NotFound(format!("no metrics for container '{}'", name)))?;
+    let since = seconds.map(|s| chrono::Utc::now().timestamp() - s as i64);
+    Ok(series
+        .points
+        .iter()
+        .filter(|p| since.is_none_or(|since| p.at >= since))
+        .copied()
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const CADENCE: Cadence = Cadence { normal: 5, current: 5 };
+
+    fn stats(total_usage: u64, system_usage: u64, rx_bytes: u64) -> Value {
+        json!({
+            "cpu_stats": {
+                "cpu_usage": { "total_usage": total_usage },
+                "system_cpu_usage": system_usage,
+                "online_cpus": 2,
+            },
+            "memory_stats": { "usage": 1000, "limit": 4000 },
+            "networks": { "eth0": { "rx_bytes": rx_bytes, "tx_bytes": 0 } },
+        })
+    }
+
+    fn points(history: &ContainerHistory, name: &str) -> Vec<ContainerPoint> {
+        history.series.lock().unwrap()[name].points.iter().copied().collect()
+    }
+
+    fn counters(total_usage: u64, system_usage: u64, online_cpus: u32) -> CpuCounters {
+        CpuCounters {
+            total_usage,
+            system_usage,
+            online_cpus,
+        }
+    }
+
+    #[test]
+    fn cpu_percent_is_delta_over_delta_times_cpus() {
+        let before = counters(1_000, 100_000, 4);
+        // A quarter of all CPU time on four CPUs is one full core
+        assert_eq!(cpu_percent(&before, &counters(26_000, 200_000, 4)), Some(100.0));
+        assert_eq!(cpu_percent(&before, &counters(51_000, 200_000, 4)), Some(200.0));
+        assert_eq!(cpu_percent(&before, &counters(1_000, 200_000, 4)), Some(0.0));
+        // An engine that reports no CPU count is taken as one
+        assert_eq!(cpu_percent(&counters(0, 0, 0), &counters(50, 100, 0)), Some(50.0));
+        // Restarted counters, and no system time passing
+        assert_eq!(cpu_percent(&before, &counters(500, 200_000, 4)), None);
+        assert_eq!(cpu_percent(&before, &counters(2_000, 50_000, 4)), None);
+        assert_eq!(cpu_percent(&before, &counters(2_000, 100_000, 4)), None);
+    }
+
+    #[test]
+    fn counters_from_new_and_old_engines() {
+        assert_eq!(cpu_counters(&stats(10, 20, 0)), Some(counters(10, 20, 2)));
+        // Before online_cpus, the per-CPU list gives the count
+        let old = json!({
+            "cpu_stats": {
+                "cpu_usage": { "total_usage": 10, "percpu_usage": [4, 3, 2, 1] },
+                "system_cpu_usage": 20,
+            },
+        });
+        assert_eq!(cpu_counters(&old), Some(counters(10, 20, 4)));
+        // A stopped container's stats have no system usage
+        assert_eq!(cpu_counters(&json!({ "cpu_stats": { "cpu_usage": { "total_usage": 10 } } })), None);
+    }
+
+    #[test]
+    fn memory_leaves_out_page_cache_and_fake_limits() {
+        let v2 = json!({ "memory_stats": { "usage": 5000, "limit": 8000, "stats": { "inactive_file": 1000 } } });
+        assert_eq!(memory(&v2), (4000, Some(8000)));
+        let v1 = json!({ "memory_stats": { "usage": 5000, "limit": u64::MAX, "stats": { "total_inactive_file": 2000 } } });
+        assert_eq!(memory(&v1), (3000, None));
+        assert_eq!(memory(&json!({})), (0, None));
+        let networks = json!({ "networks": { "eth0": { "rx_bytes": 10 }, "eth1": { "rx_bytes": 5 } } });
+        assert_eq!(network_total(&networks, "rx_bytes"), 15);
+        assert_eq!(network_total(&networks, "tx_bytes"), 0);
+    }
+
+    #[test]
+    fn a_new_id_or_a_long_absence_is_a_gap() {
+        let history = ContainerHistory::default();
+        history.record("db", "one", &stats(0, 0, 0), 100, CADENCE, 3);
+        history.record("db", "one", &stats(100, 1000, 0), 105, CADENCE, 3);
+        // Recreated under the same name
+        history.record("db", "two", &stats(0, 2000, 0), 110, CADENCE, 3);
+        // Gone for more than GAP_INTERVALS ticks
+        history.record("db", "two", &stats(50, 3000, 0), 110 + 5 * GAP_INTERVALS + 1, CADENCE, 3);
+        let points = points(&history, "db");
+        // Capacity 3 dropped the first
+        assert_eq!(points.len(), 3);
+        assert!(!points[0].gap);
+        assert_eq!(points[0].cpu_percent, Some(20.0));
+        assert!(points[1].gap && points[1].cpu_percent.is_none());
+        assert!(points[2].gap && points[2].cpu_percent.is_none());
+
+        history.expire(120);
+        assert!(history.series.lock().unwrap().contains_key("db"));
+        history.expire(1000);
+        assert!(history.series.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn chunked_bodies_are_decoded() {
+        assert_eq!(dechunk(b"4\r\nWiki\r\n6\r\npedia \r\n0\r\n\r\n"), b"Wikipedia ");
+        assert_eq!(dechunk(b"4;ext=1\r\nWiki\r\n0\r\n\r\n"), b"Wiki");
+        // Truncated mid-chunk: what came before is kept
+        assert_eq!(dechunk(b"4\r\nWiki\r\nff\r\nabc"), b"Wiki");
+    }
+
+    #[test]
+    fn after_a_resume_the_next_reading_starts_over() {
+        let history = ContainerHistory::default();
+        history.record("web", "abc", &stats(0, 0, 0), 100, CADENCE, 10);
+        history.record("web", "abc", &stats(500, 1000, 5000), 105, CADENCE, 10);
+        history.reset_rates();
+        // Within the gap threshold, but across a sleep: its huge deltas
+        // mustn't become a rate
+        history.record("web", "abc", &stats(900_000, 1_001_000, 90_000_000), 110, CADENCE, 10);
+        history.record("web", "abc", &stats(900_500, 1_002_000, 90_001_000), 115, CADENCE, 10);
+
+        let points = points(&history, "web");
+        assert_eq!(points[1].cpu_percent, Some(100.0));
+        assert_eq!(points[1].rx_bytes_per_sec, Some(1000.0));
+        assert!(points[2].gap);
+        assert_eq!((points[2].cpu_percent, points[2].rx_bytes_per_sec), (None, None));
+        // Once only
+        assert!(!points[3].gap);
+        assert_eq!(points[3].cpu_percent, Some(100.0));
+        assert_eq!(points[3].rx_bytes_per_sec, Some(200.0));
+    }
+}
@@ -0,0 +1,444 @@
+// RAG Q&A with persistent conversation history.
+//
+// `ask_question` sends the question and the conversation so far to the
+// backend chat endpoint. A backend that answers with `application/x-ndjson`
+// streams the answer as lines of
+//   {"delta": "..."}                      answer text, pushed as qa://chunk
+//   {"sources": [...], "usage": {...}}    metadata, any time before done
+//   {"error": "..."}                      generation failed
+//   {"done": true}                        end of answer
+// otherwise the plain ChatResponse JSON is used. Every answer is stored
+// once it completes; a stream that breaks off is stored with the partial
+// text and `failed: true` so history matches what was shown. Each source
+// gets a chunk_id, and an answer with sources a query_id, so its citations
+// can be rated (see retrieval_feedback).
+use rusqlite::{params, OptionalExtension, Row};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader};
+use std::time::{Duration, Instant};
+
+use crate::app::{AppHandle, Emitter, Manager, State};
+use crate::backend;
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::retrieval_feedback;
+use crate::settings::SettingsStore;
+
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(300);
+const TITLE_MAX_CHARS: usize = 60;
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 200;
+
+#[derive(Serialize, Clone)]
+pub struct QaMessage {
+    pub id: i64,
+    pub conversation_id: i64,
+    pub question: String,
+    pub answer: String,
+    pub sources: Value,
+    // None when there was nothing to rate
+    pub query_id: Option<String>,
+    pub prompt_tokens: Option<i64>,
+    pub completion_tokens: Option<i64>,
+    pub latency_ms: i64,
+    pub failed: bool,
+    pub error: Option<String>,
+    pub asked_at: String,
+    pub answered_at: String,
+}
+
+#[derive(Serialize)]
+pub struct ConversationSummary {
+    pub id: i64,
+    pub title: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub message_count: i64,
+}
+
+#[derive(Serialize)]
+pub struct Conversation {
+    pub id: i64,
+    pub title: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub messages: Vec<QaMessage>,
+}
+
+#[derive(Serialize, Clone)]
+struct AnswerChunk {
+    conversation_id: i64,
+    delta: String,
+}
+
+// What came back from the backend, complete or not
+struct Answer {
+    text: String,
+    sources: Value,
+    prompt_tokens: Option<i64>,
+    completion_tokens: Option<i64>,
+    error: Option<String>,
+}
+
+fn default_title(question: &str) -> String {
+    let question = question.trim();
+    if question.chars().count() <= TITLE_MAX_CHARS {
+        return question.to_string();
+    }
+    let cut: String = question.chars().take(TITLE_MAX_CHARS).collect();
+    format!("{}…", cut.trim_end())
+}
+
+fn message_from_row(row: &Row) -> rusqlite::Result<QaMessage> {
+    let sources: String = row.get("sources")?;
+    Ok(QaMessage {
+        id: row.get("id")?,
+        conversation_id: row.get("conversation_id")?,
+        question: row.get("question")?,
+        answer: row.get("answer")?,
+        sources: serde_json::from_str(&sources).unwrap_or_else(|_| json!([])),
+        query_id: row.get("query_id")?,
+        prompt_tokens: row.get("prompt_tokens")?,
+        completion_tokens: row.get("completion_tokens")?,
+        latency_ms: row.get("latency_ms")?,
+        failed: row.get("failed")?,
+        error: row.get("error")?,
+        asked_at: row.get("asked_at")?,
+        answered_at: row.get("answered_at")?,
+    })
+}
+
+fn load_messages(db: &Database, conversation_id: i64) -> CommandResult<Vec<QaMessage>> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT * FROM conversation_messages WHERE conversation_id = ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map([conversation_id], message_from_row)?;
+        rows.collect()
+    })
+}
+
+fn summary(db: &Database, id: i64) -> CommandResult<ConversationSummary> {
+    db.with_conn(|conn| {
+        conn.query_row(
+            "SELECT c.id, c.title, c.created_at, c.updated_at,
+                    (SELECT COUNT(*) FROM conversation_messages m WHERE m.conversation_id = c.id)
+             FROM conversations c WHERE c.id = ?1",
+            [id],
+            |row| {
+                Ok(ConversationSummary {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    created_at: row.get(2)?,
+                    updated_at: row.get(3)?,
+                    message_count: row.get(4)?,
+                })
+            },
+        )
+        .optional()
+    })?
+    .ok_or_else(|| CommandError::NotFound(format!("conversation {}", id)))
+}
+
+fn create_conversation(db: &Database, question: &str) -> CommandResult<i64> {
+    let now = chrono::Utc::now().to_rfc3339();
+    db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO conversations (title, created_at, updated_at) VALUES (?1, ?2, ?2)",
+            params![default_title(question), now],
+        )?;
+        Ok(conn.last_insert_rowid())
+    })
+}
+
+fn save_message(
+    db: &Database,
+    conversation_id: i64,
+    question: &str,
+    answer: &Answer,
+    query_id: Option<String>,
+    latency_ms: i64,
+    asked_at: &str,
+) -> CommandResult<QaMessage> {
+    let answered_at = chrono::Utc::now().to_rfc3339();
+    let id = db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO conversation_messages
+                (conversation_id, question, answer, sources, query_id, prompt_tokens, completion_tokens,
+                 latency_ms, failed, error, asked_at, answered_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                conversation_id,
+                question,
+                answer.text,
+                answer.sources.to_string(),
+                query_id,
+                answer.prompt_tokens,
+                answer.completion_tokens,
+                latency_ms,
+                answer.error.is_some(),
+                answer.error,
+                asked_at,
+                answered_at,
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        tx.execute(
+            "UPDATE conversations SET updated_at = ?1 WHERE id = ?2",
+            params![answered_at, conversation_id],
+        )?;
+        tx.commit()?;
+        Ok(id)
+    })?;
+
+    Ok(QaMessage {
+        id,
+        conversation_id,
+        question: question.to_string(),
+        answer: answer.text.clone(),
+        sources: answer.sources.clone(),
+        query_id,
+        prompt_tokens: answer.prompt_tokens,
+        completion_tokens: answer.completion_tokens,
+        latency_ms,
+        failed: answer.error.is_some(),
+        error: answer.error.clone(),
+        asked_at: asked_at.to_string(),
+        answered_at,
+    })
+}
+
+// Token counts as reported by the backend, if it reports them at all
+fn usage(value: &Value) -> (Option<i64>, Option<i64>) {
+    let usage = value.get("usage");
+    let prompt = usage
+        .and_then(|u| u.get("prompt_tokens"))
+        .or_else(|| value.pointer("/debug/prompt_tokens_estimate"))
+        .and_then(Value::as_i64);
+    let completion = usage.and_then(|u| u.get("completion_tokens")).and_then(Value::as_i64);
+    (prompt, completion)
+}
+
+fn request_answer(
+    app: &AppHandle,
+    settings: &crate::settings::Settings,
+    conversation_id: i64,
+    body: &Value,
+) -> Answer {
+    let mut answer = Answer {
+        text: String::new(),
+        sources: json!([]),
+        prompt_tokens: None,
+        completion_tokens: None,
+        error: None,
+    };
+
+    let response = match backend::endpoint(settings).post("/api/chat/send", body, ANSWER_TIMEOUT) {
+        Ok(response) => response,
+        Err(e) => {
+            answer.error = Some(e.to_string());
+            return answer;
+        }
+    };
+
+    if response.content_type() != "application/x-ndjson" {
+        match response.into_json::<Value>() {
+            Ok(value) => {
+                answer.text = value.get("response").and_then(Value::as_str).unwrap_or_default().to_string();
+                if let Some(sources) = value.get("sources") {
+                    answer.sources = sources.clone();
+                }
+                (answer.prompt_tokens, answer.completion_tokens) = usage(&value);
+            }
+            Err(e) => answer.error = Some(format!("unexpected response from backend: {}", e)),
+        }
+        return answer;
+    }
+
+    let mut done = false;
+    for line in BufReader::new(response.into_reader()).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                answer.error = Some(format!("answer stream interrupted: {}", e));
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event: Value = match serde_json::from_str(&line) {
+            Ok(event) => event,
+            Err(e) => {
+                answer.error = Some(format!("malformed stream line: {}", e));
+                break;
+            }
+        };
+        if let Some(delta) = event.get("delta").and_then(Value::as_str) {
+            answer.text.push_str(delta);
+            let _ = app.emit(
+                "qa://chunk",
+                AnswerChunk {
+                    conversation_id,
+                    delta: delta.to_string(),
+                },
+            );
+        }
+        if let Some(sources) = event.get("sources") {
+            answer.sources = sources.clone();
+        }
+        if event.get("usage").is_some() {
+            (answer.prompt_tokens, answer.completion_tokens) = usage(&event);
+        }
+        if let Some(error) = event.get("error").and_then(Value::as_str) {
+            answer.error = Some(error.to_string());
+            break;
+        }
+        if event.get("done").and_then(Value::as_bool) == Some(true) {
+            done = true;
+            break;
+        }
+    }
+    if answer.error.is_none() && !done {
+        answer.error = Some("answer stream ended before it was complete".to_string());
+    }
+    answer
+}
+
+// Returns the stored message; backend failures come back as a message with
+// `failed: true` rather than an error so the conversation id is never lost.
+// The database work and the (streamed, minutes-long) backend request run
+// on a blocking thread rather than one of the async runtime's.
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn ask_question(app: AppHandle, question: String, conversation_id: Option<i64>) -> CommandResult<QaMessage> {
+    tokio::task::spawn_blocking(move || {
+        let settings = app.state::<SettingsStore>().get();
+        answer_question(&app, &app.state(), &settings, question, conversation_id)
+    })
+    .await
+    .map_err(|e| CommandError::Internal(format!("asking the question failed: {}", e)))?
+}
+
+fn answer_question(
+    app: &AppHandle,
+    db: &Database,
+    settings: &crate::settings::Settings,
+    question: String,
+    conversation_id: Option<i64>,
+) -> CommandResult<QaMessage> {
+    let question = question.trim().to_string();
+    if question.is_empty() {
+        return Err(CommandError::InvalidInput("question is empty".to_string()));
+    }
+
+    let (conversation_id, history) = match conversation_id {
+        Some(id) => {
+            summary(db, id)?;
+            (id, load_messages(db, id)?)
+        }
+        None => (create_conversation(db, &question)?, Vec::new()),
+    };
+    let history: Vec<Value> = history
+        .iter()
+        .flat_map(|m| {
+            [
+                json!({ "role": "user", "content": m.question }),
+                json!({ "role": "assistant", "content": m.answer }),
+            ]
+        })
+        .collect();
+    let body = json!({
+        "message": question,
+        "history": history,
+        "persona": "guide",
+        "stream": true,
+    });
+
+    let asked_at = chrono::Utc::now().to_rfc3339();
+    let started = Instant::now();
+    let mut answer = request_answer(app, settings, conversation_id, &body);
+    let latency_ms = started.elapsed().as_millis() as i64;
+
+    let chunks = retrieval_feedback::tag_sources(&mut answer.sources);
+    // Not being able to rate the sources isn't worth losing the answer over
+    let query_id = if chunks.is_empty() {
+        None
+    } else {
+        match retrieval_feedback::record_query(db, "answer", &question, &chunks) {
+            Ok(id) => Some(id),
+            Err(e) => {
+                println!("[Halbert] Couldn't record the sources of an answer: {}", e);
+                None
+            }
+        }
+    };
+    save_message(db, conversation_id, &question, &answer, query_id, latency_ms, &asked_at)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn list_conversations(
+    db: State<'_, Database>,
+    offset: Option<u32>,
+    limit: Option<u32>,
+) -> CommandResult<Vec<ConversationSummary>> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = offset.unwrap_or(0);
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT c.id, c.title, c.created_at, c.updated_at, COUNT(m.id)
+             FROM conversations c
+             LEFT JOIN conversation_messages m ON m.conversation_id = c.id
+             GROUP BY c.id
+             ORDER BY c.updated_at DESC, c.id DESC
+             LIMIT ?1 OFFSET ?2",
+        )?;
+        let rows = stmt.query_map(params![limit, offset], |row| {
+            Ok(ConversationSummary {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                created_at: row.get(2)?,
+                updated_at: row.get(3)?,
+                message_count: row.get(4)?,
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_conversation(db: State<'_, Database>, id: i64) -> CommandResult<Conversation> {
+    let summary = summary(&db, id)?;
+    Ok(Conversation {
+        id: summary.id,
+        title: summary.title,
+        created_at: summary.created_at,
+        updated_at: summary.updated_at,
+        messages: load_messages(&db, id)?,
+    })
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn rename_conversation(db: State<'_, Database>, id: i64, title: String) -> CommandResult<ConversationSummary> {
+    let title = title.trim();
+    if title.is_empty() {
+        return Err(CommandError::InvalidInput("title is empty".to_string()));
+    }
+    let changed = db.with_conn(|conn| {
+        conn.execute("UPDATE conversations SET title = ?1 WHERE id = ?2", params![title, id])
+    })?;
+    if changed == 0 {
+        return Err(CommandError::NotFound(format!("conversation {}", id)));
+    }
+    summary(&db, id)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn delete_conversation(db: State<'_, Database>, id: i64) -> CommandResult<()> {
+    let deleted = db.with_conn(|conn| conn.execute("DELETE FROM conversations WHERE id = ?1", [id]))?;
+    if deleted == 0 {
+        return Err(CommandError::NotFound(format!("conversation {}", id)));
+    }
+    Ok(())
+}
@@ -0,0 +1,386 @@
+// Named corpora, so separate collections keep separate indexes.
+//
+// The primary corpus is `corpus_path`; `corpora` in settings adds more,
+// each a name and a root. Document commands take an optional `corpus` (the
+// primary when left out), and the name goes with every search, reindex and
+// stats request so the backend can keep an index per corpus. Tags are keyed
+// by corpus and source, so the same relative path in two corpora doesn't
+// collide. Removing a corpus only deregisters it: its files stay where they
+// are, and so do its tags, should it be added again.
+//
+// What the backend reports of a corpus's index is kept for INDEX_TTL by
+// backend and corpus (IndexCache); a reindex adjusts the kept chunk count
+// by the delta the backend reports, so the stats are right without asking
+// again.
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::app::{AppHandle, Emitter, State};
+use crate::backend;
+use crate::db::Database;
+use crate::documents;
+use crate::error::{CommandError, CommandResult};
+use crate::sandbox;
+use crate::settings::{self, Settings, SettingsStore};
+
+pub const PRIMARY: &str = "primary";
+const MAX_NAME_LEN: usize = 32;
+const INDEX_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NamedCorpus {
+    pub name: String,
+    pub path: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CorpusInfo {
+    pub name: String,
+    // None for a primary corpus that isn't configured (mock documents)
+    pub path: Option<String>,
+    pub primary: bool,
+    // Why its root can't be used right now
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CorpusStats {
+    pub name: String,
+    pub documents: u32,
+    pub size_bytes: u64,
+    pub size_display: String,
+    // From the backend's index for this corpus, when it answers
+    pub chunks: Option<u32>,
+    pub index_size_mb: Option<f32>,
+    // Newest file modification
+    pub last_modified: Option<String>,
+    // "healthy", "empty" or "missing"
+    pub status: String,
+}
+
+// The backend's counts for one corpus; either may be missing from its answer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IndexCounts {
+    pub chunks: Option<u32>,
+    pub index_size_mb: Option<f32>,
+}
+
+// By backend URL and corpus, with when they were fetched
+type KeptCounts = BTreeMap<(String, String), (Instant, IndexCounts)>;
+
+// Cloned into reindex jobs, which outlive the command that started them
+#[derive(Clone, Default)]
+pub struct IndexCache {
+    counts: Arc<Mutex<KeptCounts>>,
+}
+
+impl IndexCache {
+    // Counts fetched less than INDEX_TTL before `now`
+    pub fn get(&self, settings: &Settings, corpus: &str, now: Instant) -> Option<IndexCounts> {
+        let counts = self.counts.lock().unwrap();
+        let (at, counts) = counts.get(&(settings.backend_url.clone(), corpus.to_string()))?;
+        (now.saturating_duration_since(*at) < INDEX_TTL).then_some(*counts)
+    }
+
+    pub fn store(&self, settings: &Settings, corpus: &str, counts: IndexCounts, now: Instant) {
+        let key = (settings.backend_url.clone(), corpus.to_string());
+        self.counts.lock().unwrap().insert(key, (now, counts));
+    }
+
+    // A reindex added (or with a negative delta, dropped) chunks. Counts
+    // that aren't kept are left for the next fetch.
+    pub fn apply_delta(&self, settings: &Settings, corpus: &str, delta: i64) -> Option<IndexCounts> {
+        let mut counts = self.counts.lock().unwrap();
+        let (_, counts) = counts.get_mut(&(settings.backend_url.clone(), corpus.to_string()))?;
+        let chunks = counts.chunks.as_mut()?;
+        *chunks = (*chunks as i64 + delta).clamp(0, u32::MAX as i64) as u32;
+        Some(*counts)
+    }
+
+    pub fn forget(&self, settings: &Settings, corpus: &str) {
+        self.counts.lock().unwrap().remove(&(settings.backend_url.clone(), corpus.to_string()));
+    }
+}
+
+fn fetch_index_counts(settings: &Settings, name: &str) -> Option<IndexCounts> {
+    let index = backend::endpoint(settings)
+        .get_json::<Value>(&format!("/api/memory/stats?corpus={}", name))
+        .ok()?;
+    let field = |field: &str| index.get(field).and_then(Value::as_f64);
+    Some(IndexCounts {
+        chunks: field("total_chunks").map(|n| n as u32),
+        index_size_mb: field("index_size_mb").map(|n| n as f32),
+    })
+}
+
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+// Every corpus with a root: the primary one when corpus_path is set, then
+// the named ones in the order they were added
+pub fn names(settings: &Settings) -> Vec<String> {
+    let primary = settings.corpus_path.as_deref().is_some_and(|p| !p.is_empty());
+    primary
+        .then(|| PRIMARY.to_string())
+        .into_iter()
+        .chain(settings.corpora.iter().map(|c| c.name.clone()))
+        .collect()
+}
+
+pub fn configured_path<'a>(settings: &'a Settings, name: &str) -> Option<&'a str> {
+    if name == PRIMARY {
+        return settings.corpus_path.as_deref().filter(|p| !p.is_empty());
+    }
+    settings.corpora.iter().find(|c| c.name == name).map(|c| c.path.as_str())
+}
+
+pub fn root(settings: &Settings, name: &str) -> CommandResult<PathBuf> {
+    let path = configured_path(settings, name).ok_or_else(|| match name {
+        PRIMARY => CommandError::NotSupported("no corpus_path configured".to_string()),
+        _ => CommandError::NotFound(format!("corpus {}", name)),
+    })?;
+    std::fs::canonicalize(path).map_err(|e| CommandError::NotFound(format!("corpus path {}: {}", path, e)))
+}
+
+// Roots that resolve right now, for the sandbox
+pub fn roots(settings: &Settings) -> Vec<PathBuf> {
+    names(settings).iter().filter_map(|name| root(settings, name).ok()).collect()
+}
+
+// The corpus a command argument names; the primary one when it's left out
+pub fn pick(settings: &Settings, corpus: Option<String>) -> CommandResult<String> {
+    let Some(name) = corpus.map(|c| c.trim().to_string()).filter(|c| !c.is_empty()) else {
+        return Ok(PRIMARY.to_string());
+    };
+    if name != PRIMARY && !settings.corpora.iter().any(|c| c.name == name) {
+        return Err(CommandError::NotFound(format!("corpus {}", name)));
+    }
+    Ok(name)
+}
+
+fn check_dir(settings: &Settings, path: &str) -> CommandResult<PathBuf> {
+    let dir = sandbox::CORPUS_PATH.validate(settings, Path::new(path))?.into_path_buf();
+    if !dir.exists() {
+        return Err(CommandError::NotFound(format!("corpus path {}", path)));
+    }
+    if !dir.is_dir() {
+        return Err(CommandError::InvalidInput(format!("{} is not a directory", dir.display())));
+    }
+    Ok(dir)
+}
+
+// Called from settings::check_live_values. Names are unique and roots must
+// exist and not overlap, or a file would be indexed twice.
+pub fn check(previous: &Settings, next: &Settings) -> CommandResult<()> {
+    if next.corpus_path != previous.corpus_path {
+        if let Some(path) = next.corpus_path.as_deref().filter(|p| !p.is_empty()) {
+            check_dir(next, path)?;
+        }
+    }
+    if next.corpora == previous.corpora && next.corpus_path == previous.corpus_path {
+        return Ok(());
+    }
+    // Roots so far, to catch overlaps
+    let mut seen: Vec<(String, PathBuf)> = Vec::new();
+    let primary = next.corpus_path.as_deref().filter(|p| !p.is_empty());
+    if let Some(root) = primary.and_then(|p| std::fs::canonicalize(p).ok()) {
+        seen.push((PRIMARY.to_string(), root));
+    }
+    for corpus in &next.corpora {
+        if !valid_name(&corpus.name) || corpus.name == PRIMARY {
+            return Err(CommandError::InvalidInput(format!(
+                "invalid corpus name '{}': up to {} lowercase letters, digits, '-' or '_', and not '{}'",
+                corpus.name, MAX_NAME_LEN, PRIMARY
+            )));
+        }
+        if next.corpora.iter().filter(|c| c.name == corpus.name).count() > 1 {
+            return Err(CommandError::Conflict(format!("corpus {} is listed twice", corpus.name)));
+        }
+        let unchanged = previous.corpora.contains(corpus);
+        let dir = if unchanged {
+            match std::fs::canonicalize(&corpus.path) {
+                Ok(dir) => dir,
+                // A root that has gone missing since doesn't block other changes
+                Err(_) => continue,
+            }
+        } else {
+            check_dir(next, &corpus.path)?
+        };
+        if let Some((other, _)) = seen.iter().find(|(_, root)| root.starts_with(&dir) || dir.starts_with(root)) {
+            return Err(CommandError::Conflict(format!(
+                "corpus {} overlaps corpus {}",
+                corpus.name, other
+            )));
+        }
+        seen.push((corpus.name.clone(), dir));
+    }
+    Ok(())
+}
+
+fn info(settings: &Settings, name: &str) -> CorpusInfo {
+    CorpusInfo {
+        name: name.to_string(),
+        path: configured_path(settings, name).map(str::to_string),
+        primary: name == PRIMARY,
+        error: match root(settings, name) {
+            Ok(_) | Err(CommandError::NotSupported(_)) => None,
+            Err(e) => Some(e.to_string()),
+        },
+    }
+}
+
+// One corpus: what's on disk, plus what the backend has indexed of it
+pub fn stats(settings: &Settings, db: &Database, cache: &IndexCache, name: &str) -> CommandResult<CorpusStats> {
+    let docs = documents::local_documents(settings, db, name);
+    let (docs, missing) = match docs {
+        Ok(docs) => (docs, false),
+        Err(CommandError::NotFound(_)) => (Vec::new(), true),
+        Err(e) => return Err(e),
+    };
+    let size_bytes: u64 = docs.iter().map(|d| d.size_bytes).sum();
+    let now = Instant::now();
+    let index = cache.get(settings, name, now).or_else(|| {
+        let fetched = fetch_index_counts(settings, name)?;
+        cache.store(settings, name, fetched, now);
+        Some(fetched)
+    });
+    Ok(CorpusStats {
+        name: name.to_string(),
+        documents: docs.len() as u32,
+        size_bytes,
+        size_display: settings.units.format_bytes(size_bytes),
+        chunks: index.and_then(|i| i.chunks),
+        index_size_mb: index.and_then(|i| i.index_size_mb),
+        last_modified: docs.iter().map(|d| d.indexed_at.clone()).filter(|at| !at.is_empty()).max(),
+        status: match (missing, docs.is_empty()) {
+            (true, _) => "missing",
+            (false, true) => "empty",
+            (false, false) => "healthy",
+        }
+        .to_string(),
+    })
+}
+
+fn change(app: &AppHandle, store: &SettingsStore, edit: impl FnOnce(&mut Settings)) -> CommandResult<Settings> {
+    let (previous, updated) = store.replace(|current| {
+        let mut next = current.clone();
+        edit(&mut next);
+        settings::check_live_values(current, &next)?;
+        Ok(next)
+    })?;
+    settings::announce_change(app, &previous, &updated)?;
+    Ok(updated)
+}
+
+// The primary one is always listed, configured or not
+fn all(settings: &Settings) -> Vec<CorpusInfo> {
+    let mut names = names(settings);
+    if names.first().map(String::as_str) != Some(PRIMARY) {
+        names.insert(0, PRIMARY.to_string());
+    }
+    names.iter().map(|name| info(settings, name)).collect()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn list_corpora(store: State<'_, SettingsStore>) -> Vec<CorpusInfo> {
+    all(&store.get())
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn add_corpus(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    name: String,
+    path: String,
+) -> CommandResult<CorpusInfo> {
+    let name = name.trim().to_string();
+    if name == PRIMARY {
+        return Err(CommandError::Conflict(format!(
+            "the {} corpus is set with corpus_path in Settings",
+            PRIMARY
+        )));
+    }
+    if store.get().corpora.iter().any(|c| c.name == name) {
+        return Err(CommandError::Conflict(format!("corpus {} already exists", name)));
+    }
+    let path = path.trim().to_string();
+    let corpus = NamedCorpus {
+        name: name.clone(),
+        path: path.clone(),
+    };
+    let updated = change(&app, &store, |s| s.corpora.push(corpus))?;
+    println!("[Halbert] Added corpus {} at {}", name, path);
+    let _ = app.emit("corpus://corpora-changed", all(&updated));
+    Ok(info(&updated, &name))
+}
+
+// Deregisters the corpus; nothing under its root is touched
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn remove_corpus(app: AppHandle, store: State<'_, SettingsStore>, name: String) -> CommandResult<()> {
+    if name == PRIMARY {
+        return Err(CommandError::Conflict(format!(
+            "the {} corpus can't be removed; clear corpus_path in Settings instead",
+            PRIMARY
+        )));
+    }
+    if !store.get().corpora.iter().any(|c| c.name == name) {
+        return Err(CommandError::NotFound(format!("corpus {}", name)));
+    }
+    let updated = change(&app, &store, |s| s.corpora.retain(|c| c.name != name))?;
+    println!("[Halbert] Removed corpus {} (its files were left in place)", name);
+    let _ = app.emit("corpus://corpora-changed", all(&updated));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COUNTS: IndexCounts = IndexCounts {
+        chunks: Some(40),
+        index_size_mb: Some(2.5),
+    };
+
+    fn backend(url: &str) -> Settings {
+        Settings {
+            backend_url: url.to_string(),
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn index_counts_are_kept_per_backend_and_corpus_until_they_expire() {
+        let (cache, now) = (IndexCache::default(), Instant::now());
+        let settings = backend("http://one");
+        cache.store(&settings, PRIMARY, COUNTS, now);
+        assert_eq!(cache.get(&settings, PRIMARY, now + INDEX_TTL / 2), Some(COUNTS));
+        assert_eq!(cache.get(&settings, PRIMARY, now + INDEX_TTL), None);
+        assert_eq!(cache.get(&settings, "notes", now), None);
+        assert_eq!(cache.get(&backend("http://two"), PRIMARY, now), None);
+        cache.forget(&settings, PRIMARY);
+        assert_eq!(cache.get(&settings, PRIMARY, now), None);
+    }
+
+    #[test]
+    fn a_chunk_delta_adjusts_the_kept_count() {
+        let (cache, now) = (IndexCache::default(), Instant::now());
+        let settings = backend("http://one");
+        assert_eq!(cache.apply_delta(&settings, PRIMARY, 5), None, "nothing kept, nothing to adjust");
+        cache.store(&settings, PRIMARY, COUNTS, now);
+        assert_eq!(cache.apply_delta(&settings, PRIMARY, 5).unwrap().chunks, Some(45));
+        assert_eq!(cache.apply_delta(&settings, PRIMARY, -12).unwrap().chunks, Some(33));
+        assert_eq!(cache.apply_delta(&settings, PRIMARY, -100).unwrap().chunks, Some(0));
+        assert_eq!(cache.get(&settings, PRIMARY, now).unwrap().index_size_mb, Some(2.5));
+
+        let unknown = IndexCounts { chunks: None, ..COUNTS };
+        cache.store(&settings, "notes", unknown, now);
+        assert_eq!(cache.apply_delta(&settings, "notes", 5), None);
+    }
+}
@@ -0,0 +1,308 @@
+// Corpus health check: finds files that hurt retrieval quality.
+//
+// Runs as a job over the same file set the document list uses (so the
+// ignore list applies). Files are streamed through the hasher and UTF-8
+// check in fixed-size chunks; nothing is read fully into memory. With an
+// operation id the check can be cancelled between chunks; the job then
+// fails with how far it got and keeps the previous report.
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::app::{AppHandle, Manager, State};
+use crate::cancellation::{CancellationToken, Operations};
+use crate::documents;
+use crate::error::{CommandError, CommandResult};
+use crate::jobs::{Job, JobHandle, JobManager};
+use crate::settings::{Settings, SettingsStore};
+
+const READ_CHUNK: usize = 64 * 1024;
+
+#[derive(Serialize, Clone)]
+pub struct FindingItem {
+    pub paths: Vec<String>,
+    pub detail: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct HealthFinding {
+    // duplicate_content, near_empty, invalid_utf8, oversized, duplicate_title
+    pub category: String,
+    pub suggested_action: String,
+    pub count: usize,
+    pub items: Vec<FindingItem>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct CorpusHealthReport {
+    pub job_id: String,
+    pub corpus_path: String,
+    pub generated_at: String,
+    pub files_scanned: usize,
+    pub bytes_scanned: u64,
+    pub findings: Vec<HealthFinding>,
+}
+
+// Last completed report
+#[derive(Default)]
+pub struct CorpusHealthStore {
+    report: Mutex<Option<CorpusHealthReport>>,
+}
+
+fn suggested_action(category: &str) -> &'static str {
+    match category {
+        "duplicate_content" => "Keep one copy and delete or ignore the rest",
+        "near_empty" => "Delete the file or fill it in; empty chunks add noise to retrieval",
+        "invalid_utf8" => "Re-encode the file as UTF-8 or add it to the ignore list",
+        "oversized" => "Split the file into smaller documents or add it to the ignore list",
+        "duplicate_title" => "Rename the documents so search results can be told apart",
+        _ => "Review the listed files",
+    }
+}
+
+struct FileScan {
+    sha256: String,
+    bytes: u64,
+    // Offset of the first invalid UTF-8 sequence
+    invalid_utf8_at: Option<u64>,
+}
+
+// Interrupted once `token` is cancelled
+fn scan_file(path: &Path, token: &CancellationToken) -> std::io::Result<FileScan> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; READ_CHUNK];
+    // Bytes of a multi-byte character split across reads
+    let mut carry: Vec<u8> = Vec::new();
+    let mut offset = 0u64;
+    let mut invalid_utf8_at = None;
+
+    loop {
+        if token.is_cancelled() {
+            return Err(std::io::ErrorKind::Interrupted.into());
+        }
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        if invalid_utf8_at.is_none() {
+            let carry_start = offset - carry.len() as u64;
+            carry.extend_from_slice(&buf[..n]);
+            match std::str::from_utf8(&carry) {
+                Ok(_) => carry.clear(),
+                Err(e) if e.error_len().is_none() => {
+                    // Incomplete sequence at the end; finish it next read
+                    carry.drain(..e.valid_up_to());
+                }
+                Err(e) => invalid_utf8_at = Some(carry_start + e.valid_up_to() as u64),
+            }
+        }
+        offset += n as u64;
+    }
+    if invalid_utf8_at.is_none() && !carry.is_empty() {
+        invalid_utf8_at = Some(offset - carry.len() as u64);
+    }
+
+    let sha256 = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(FileScan {
+        sha256,
+        bytes: offset,
+        invalid_utf8_at,
+    })
+}
+
+fn finding(category: &str, items: Vec<FindingItem>) -> HealthFinding {
+    HealthFinding {
+        category: category.to_string(),
+        suggested_action: suggested_action(category).to_string(),
+        count: items.len(),
+        items,
+    }
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn run_corpus_health_check(
+    app: AppHandle,
+    jobs: State<'_, JobManager>,
+    settings: State<'_, SettingsStore>,
+    operations: State<'_, Operations>,
+    operation_id: Option<String>,
+) -> CommandResult<Job> {
+    let settings = settings.get();
+    let root = documents::corpus_root(&settings)?;
+    let operation = operations.start(operation_id)?;
+
+    let job = jobs.spawn("Corpus health check", "corpus_health", move |handle| {
+        let report = check_corpus(&settings, &root, handle, operation.token())?;
+        *app.state::<CorpusHealthStore>().report.lock().unwrap() = Some(report);
+        Ok(())
+    });
+    Ok(job)
+}
+
+// One pass over the corpus at `root`, as the job's work
+fn check_corpus(
+    settings: &Settings,
+    root: &Path,
+    handle: &JobHandle,
+    token: &CancellationToken,
+) -> Result<CorpusHealthReport, String> {
+    handle.log(format!("Scanning {}", root.display()));
+    let files = documents::walk_corpus_until(settings, root, token);
+    let total = files.len().max(1);
+
+    let mut by_hash: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut by_title: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut near_empty = Vec::new();
+    let mut invalid_utf8 = Vec::new();
+    let mut oversized = Vec::new();
+    let mut bytes_scanned = 0;
+    let mut scanned = 0;
+
+    for (i, (source, path)) in files.iter().enumerate() {
+        if token.is_cancelled() {
+            break;
+        }
+        let scan = match scan_file(path, token) {
+            Ok(scan) => scan,
+            Err(_) if token.is_cancelled() => break,
+            Err(e) => {
+                handle.log(format!("Skipped {}: {}", source, e));
+                continue;
+            }
+        };
+        bytes_scanned += scan.bytes;
+
+        if scan.bytes < settings.corpus_near_empty_bytes {
+            near_empty.push(FindingItem {
+                paths: vec![source.clone()],
+                detail: settings.units.format_bytes(scan.bytes),
+            });
+        }
+        if scan.bytes > settings.corpus_max_file_bytes {
+            oversized.push(FindingItem {
+                paths: vec![source.clone()],
+                detail: settings.units.format_bytes(scan.bytes),
+            });
+        }
+        if let Some(at) = scan.invalid_utf8_at {
+            invalid_utf8.push(FindingItem {
+                paths: vec![source.clone()],
+                detail: format!("invalid UTF-8 at byte {}", at),
+            });
+        } else if let Some(doc_type) = documents::doc_type_for(source) {
+            let title = documents::document_title(path, doc_type);
+            by_title.entry(title.to_lowercase()).or_default().push(source.clone());
+        }
+        // Empty and stub files are already reported as near_empty; all
+        // the empty ones share a hash, so they'd also show up as copies
+        if scan.bytes >= settings.corpus_near_empty_bytes {
+            by_hash.entry(scan.sha256).or_default().push(source.clone());
+        }
+
+        handle.set_progress((i + 1) as f32 / total as f32);
+        scanned = i + 1;
+    }
+    if token.is_cancelled() {
+        // The walk may have stopped early too, so the total is a floor
+        handle.set_result(serde_json::json!({ "files_scanned": scanned, "files_found": files.len() }));
+        return Err(format!("Cancelled after {} of {} files", scanned, files.len()));
+    }
+
+    let duplicates = by_hash
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(hash, paths)| FindingItem {
+            detail: format!("{} identical copies (sha256 {})", paths.len(), &hash[..12]),
+            paths,
+        })
+        .collect();
+    let duplicate_titles = by_title
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(title, paths)| FindingItem {
+            detail: format!("title \"{}\"", title),
+            paths,
+        })
+        .collect();
+
+    let report = CorpusHealthReport {
+        job_id: handle.id().to_string(),
+        corpus_path: root.display().to_string(),
+        generated_at: chrono::Utc::now().to_rfc3339(),
+        files_scanned: files.len(),
+        bytes_scanned,
+        findings: vec![
+            finding("duplicate_content", duplicates),
+            finding("near_empty", near_empty),
+            finding("invalid_utf8", invalid_utf8),
+            finding("oversized", oversized),
+            finding("duplicate_title", duplicate_titles),
+        ],
+    };
+    let problems: usize = report.findings.iter().map(|f| f.count).sum();
+    handle.log(format!("Scanned {} files, {} findings", report.files_scanned, problems));
+    handle.set_result(serde_json::json!({
+        "files_scanned": report.files_scanned,
+        "findings": problems,
+    }));
+    Ok(report)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_corpus_health_report(store: State<'_, CorpusHealthStore>) -> CommandResult<CorpusHealthReport> {
+    store
+        .report
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| CommandError::NotFound("no corpus health check has completed yet".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automation::Gate;
+    use crate::cancellation::{self, NOTICED_WITHIN};
+
+    #[test]
+    fn a_cancelled_check_stops_mid_file_and_keeps_no_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("corpus");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(root.join("notes.md"), "# Notes\n").unwrap();
+        // Sparse, so it takes no disk but far longer to read than the test runs
+        std::fs::File::create(root.join("huge.txt")).unwrap().set_len(64 << 30).unwrap();
+
+        let jobs = JobManager::empty(Gate::load(dir.path().join("automation.json")));
+        let operations = Operations::default();
+        let operation = operations.start(Some("health".to_string())).unwrap();
+        let job = jobs.spawn("Corpus health check", "corpus_health", move |handle| {
+            check_corpus(&Settings::default(), &root, handle, operation.token()).map(drop)
+        });
+        let (job, overran) = cancellation::cancel_during(&operations, "health", || jobs.wait_finished(&job.id));
+        assert!(overran < NOTICED_WITHIN, "{:?}", overran);
+        assert_eq!(job.status, "failed");
+        assert_eq!(job.error.as_deref(), Some("Cancelled after 0 of 2 files"));
+        assert_eq!(job.result.unwrap()["files_found"], 2);
+    }
+
+    #[test]
+    fn a_scan_hashes_and_finds_invalid_utf8_across_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("doc.txt");
+        // A multi-byte character split at the chunk boundary, then a bad byte
+        let mut data = vec![b'a'; READ_CHUNK - 1];
+        data.extend("é".as_bytes());
+        data.extend([b'b', 0xff]);
+        std::fs::write(&path, &data).unwrap();
+        let scan = scan_file(&path, &Default::default()).unwrap();
+        assert_eq!(scan.bytes, data.len() as u64);
+        assert_eq!(scan.invalid_utf8_at, Some(READ_CHUNK as u64 + 2));
+        assert_eq!(scan.sha256, crate::transfers::checksum(&data));
+    }
+}
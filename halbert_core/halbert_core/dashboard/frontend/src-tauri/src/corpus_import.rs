@@ -0,0 +1,302 @@
+// Copying dropped files and folders into the corpus.
+//
+// Each path is copied under the corpus root (or `target_subdir` inside it);
+// a folder keeps its own name and relative structure. Files are copied as
+// they are; the backend does any conversion (PDF text extraction etc.), so
+// a type the dashboard doesn't list is still copied and noted. Inside a
+// folder, symlinks and hidden entries are skipped with a result entry
+// rather than followed. Sources already inside the corpus, or containing
+// it, are refused so nothing copies onto itself. More than JOB_THRESHOLD
+// files run as a job, with the per-file results as the job result.
+use serde::Serialize;
+use std::path::{Component, Path, PathBuf};
+
+use crate::app::{AppHandle, State};
+use crate::corpora;
+use crate::documents;
+use crate::error::{CommandError, CommandResult};
+use crate::jobs::{Job, JobManager};
+use crate::sandbox;
+use crate::settings::{Settings, SettingsStore};
+
+const JOB_THRESHOLD: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConflictPolicy {
+    Skip,
+    Overwrite,
+    // "notes.md" becomes "notes (1).md"
+    Rename,
+}
+
+impl ConflictPolicy {
+    pub fn parse(value: &str) -> CommandResult<Self> {
+        match value {
+            "skip" => Ok(ConflictPolicy::Skip),
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            "rename" => Ok(ConflictPolicy::Rename),
+            other => Err(CommandError::InvalidInput(format!(
+                "unknown conflict policy '{}', expected skip, overwrite or rename",
+                other
+            ))),
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct ImportResult {
+    pub source: String,
+    // Corpus-relative, where it was (or would have been) written
+    pub destination: Option<String>,
+    // "copied", "overwritten", "renamed", "skipped" or "failed"
+    pub status: String,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ImportOutcome {
+    // Empty when the import runs as a job; see the job's result instead
+    pub results: Vec<ImportResult>,
+    pub job: Option<Job>,
+}
+
+// One file to copy, or an entry already decided during planning
+enum Planned {
+    Copy { source: PathBuf, relative: PathBuf },
+    Done(ImportResult),
+}
+
+fn skipped(source: &Path, message: &str) -> Planned {
+    Planned::Done(ImportResult {
+        source: source.display().to_string(),
+        destination: None,
+        status: "skipped".to_string(),
+        message: Some(message.to_string()),
+    })
+}
+
+// A relative path with normal components only
+pub fn safe_subdir(subdir: &str) -> CommandResult<PathBuf> {
+    let path = Path::new(subdir.trim().trim_matches('/'));
+    if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(CommandError::InvalidInput(format!(
+            "target folder '{}' must be a plain path inside the corpus",
+            subdir
+        )));
+    }
+    Ok(path.to_path_buf())
+}
+
+// "notes.md" -> "notes (n).md"; "README" -> "README (n)"
+pub fn renamed(path: &Path, n: u32) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{} ({}).{}", stem, n, ext.to_string_lossy()),
+        None => format!("{} ({})", stem, n),
+    };
+    path.with_file_name(name)
+}
+
+fn plan_dir(dir: &Path, relative: &Path, planned: &mut Vec<Planned>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        planned.push(Planned::Done(ImportResult {
+            source: dir.display().to_string(),
+            destination: None,
+            status: "failed".to_string(),
+            message: Some("folder could not be read".to_string()),
+        }));
+        return;
+    };
+    let mut entries: Vec<_> = entries.flatten().collect();
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let name = entry.file_name();
+        // file_type() doesn't follow symlinks
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_symlink() {
+            planned.push(skipped(&path, "symlink not followed"));
+        } else if name.to_string_lossy().starts_with('.') {
+            planned.push(skipped(&path, "hidden"));
+        } else if file_type.is_dir() {
+            plan_dir(&path, &relative.join(&name), planned);
+        } else if file_type.is_file() {
+            planned.push(Planned::Copy {
+                source: path,
+                relative: relative.join(&name),
+            });
+        } else {
+            planned.push(skipped(&path, "not a regular file"));
+        }
+    }
+}
+
+fn plan(settings: &Settings, paths: &[String], root: &Path) -> CommandResult<Vec<Planned>> {
+    let mut planned = Vec::new();
+    for raw in paths {
+        // The dropped path itself may be a link; the user picked it
+        let source = sandbox::IMPORT_SOURCE.validate(settings, Path::new(raw.trim()))?.into_path_buf();
+        if !source.exists() {
+            return Err(CommandError::NotFound(raw.to_string()));
+        }
+        if source.starts_with(root) {
+            return Err(CommandError::InvalidInput(format!("{} is already in the corpus", raw)));
+        }
+        if root.starts_with(&source) {
+            return Err(CommandError::InvalidInput(format!("{} contains the corpus itself", raw)));
+        }
+        let name = PathBuf::from(source.file_name().unwrap_or_default());
+        if source.is_dir() {
+            plan_dir(&source, &name, &mut planned);
+        } else if source.is_file() {
+            planned.push(Planned::Copy { source, relative: name });
+        } else {
+            planned.push(skipped(&source, "not a regular file"));
+        }
+    }
+    Ok(planned)
+}
+
+// Refuses to write through a symlink, either the destination itself or a
+// linked folder on the way that leads out of the target folder
+pub fn write_copy(source: &Path, destination: &Path, base: &Path) -> std::io::Result<()> {
+    let parent = destination.parent().unwrap_or(base);
+    std::fs::create_dir_all(parent)?;
+    let inside = std::fs::canonicalize(parent)?.starts_with(std::fs::canonicalize(base)?);
+    let linked = std::fs::symlink_metadata(destination).is_ok_and(|m| m.file_type().is_symlink());
+    if !inside || linked {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "destination is a symlink out of the corpus",
+        ));
+    }
+    std::fs::copy(source, destination).map(|_| ())
+}
+
+fn copy_one(source: &Path, relative: &Path, base: &Path, base_relative: &Path, policy: ConflictPolicy) -> ImportResult {
+    let mut destination = base.join(relative);
+    let mut shown = base_relative.join(relative);
+    let mut status = "copied";
+    if destination.exists() {
+        match policy {
+            ConflictPolicy::Skip => {
+                return ImportResult {
+                    source: source.display().to_string(),
+                    destination: Some(shown.display().to_string()),
+                    status: "skipped".to_string(),
+                    message: Some("already exists".to_string()),
+                };
+            }
+            ConflictPolicy::Overwrite => status = "overwritten",
+            ConflictPolicy::Rename => {
+                let n = (1..).find(|&n| !renamed(&destination, n).exists()).unwrap_or(1);
+                destination = renamed(&destination, n);
+                shown = renamed(&shown, n);
+                status = "renamed";
+            }
+        }
+    }
+    let shown = shown.display().to_string();
+    match write_copy(source, &destination, base) {
+        Ok(_) => ImportResult {
+            source: source.display().to_string(),
+            message: documents::doc_type_for(&shown)
+                .is_none()
+                .then(|| "copied; the dashboard doesn't list this type, the backend may still index it".to_string()),
+            destination: Some(shown),
+            status: status.to_string(),
+        },
+        Err(e) => ImportResult {
+            source: source.display().to_string(),
+            destination: Some(shown),
+            status: "failed".to_string(),
+            message: Some(e.to_string()),
+        },
+    }
+}
+
+fn run(
+    app: &AppHandle,
+    planned: Vec<Planned>,
+    base: &Path,
+    base_relative: &Path,
+    policy: ConflictPolicy,
+    mut progress: impl FnMut(&ImportResult, usize, usize),
+) -> Vec<ImportResult> {
+    let total = planned.len();
+    let mut results = Vec::with_capacity(total);
+    for (i, item) in planned.into_iter().enumerate() {
+        let result = match item {
+            Planned::Copy { source, relative } => copy_one(&source, &relative, base, base_relative, policy),
+            Planned::Done(result) => result,
+        };
+        progress(&result, i + 1, total);
+        results.push(result);
+    }
+    let written: Vec<String> = results
+        .iter()
+        .filter(|r| matches!(r.status.as_str(), "copied" | "overwritten" | "renamed"))
+        .filter_map(|r| r.destination.clone())
+        .collect();
+    println!("[Halbert] Imported {} of {} file(s) into the corpus", written.len(), total);
+    if !written.is_empty() {
+        documents::corpus_changed(app, corpora::PRIMARY, written);
+    }
+    results
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn import_documents(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    jobs: State<'_, JobManager>,
+    paths: Vec<String>,
+    target_subdir: Option<String>,
+    on_conflict: String,
+) -> CommandResult<ImportOutcome> {
+    let policy = ConflictPolicy::parse(on_conflict.trim())?;
+    if paths.is_empty() {
+        return Err(CommandError::InvalidInput("nothing to import".to_string()));
+    }
+    let settings = settings.get();
+    let root = documents::corpus_root(&settings)?;
+    let base_relative = match target_subdir.as_deref().filter(|s| !s.trim().is_empty()) {
+        Some(subdir) => safe_subdir(subdir)?,
+        None => PathBuf::new(),
+    };
+    // An existing symlink inside the corpus could still point elsewhere
+    let base = sandbox::IMPORT_TARGET.validate(&settings, &root.join(&base_relative))?.into_path_buf();
+    let planned = plan(&settings, &paths, &root)?;
+
+    let copies = planned.iter().filter(|p| matches!(p, Planned::Copy { .. })).count();
+    if copies <= JOB_THRESHOLD {
+        let results = run(&app, planned, &base, &base_relative, policy, |_, _, _| {});
+        return Ok(ImportOutcome { results, job: None });
+    }
+    let job = jobs.spawn(&format!("Import {} files", copies), "import_documents", move |handle| {
+        handle.log(format!("Importing {} files into {}", copies, base.display()));
+        let results = run(&app, planned, &base, &base_relative, policy, |result, done, total| {
+            if result.status == "failed" || result.status == "skipped" {
+                handle.log(format!(
+                    "{} {}: {}",
+                    result.status,
+                    result.source,
+                    result.message.as_deref().unwrap_or_default()
+                ));
+            }
+            handle.set_progress(done as f32 / total as f32);
+        });
+        let failed = results.iter().filter(|r| r.status == "failed").count();
+        handle.set_result(serde_json::to_value(&results).unwrap_or_default());
+        if failed > 0 {
+            return Err(format!("{} of {} files failed to import", failed, results.len()));
+        }
+        Ok(())
+    });
+    Ok(ImportOutcome {
+        results: Vec::new(),
+        job: Some(job),
+    })
+}
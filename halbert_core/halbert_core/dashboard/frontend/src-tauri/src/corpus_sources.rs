@@ -0,0 +1,622 @@
+// External sources mirrored into the corpus.
+//
+// A source is a connector kind plus where to fetch from; each one owns a
+// folder of its own under sources/ in the corpus. Syncing runs as a job:
+// the connector brings its checkout in the app data dir up to date (see
+// `fetch`; git is the only kind so far), the files under the source's
+// subdir are compared against what the previous sync wrote, and the
+// differences are copied in or removed. A file in the source's folder that
+// was changed locally and would be overwritten or removed fails the sync
+// with every such path listed, before anything is written; local edits
+// upstream didn't touch are left alone. Changed files go to the reindexer
+// like any other corpus change. Every source syncs nightly around
+// NIGHTLY_HOUR in the configured timezone, through the automation gate.
+//
+// git runs with a cleared environment and no system or global config, so
+// a user's credential helpers and hooks don't take part. Credentials come
+// from `auth_ref`: "token:<secret>" names a secret holding a token (or
+// "user:token") sent as an HTTP basic auth header, "ssh_key:<path>" a key
+// for ssh. Neither reaches argv or the job log.
+use base64::Engine;
+use chrono::Timelike;
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::app::{AppHandle, Manager, State};
+use crate::automation::{Entry, Gate};
+use crate::corpora;
+use crate::corpus_import;
+use crate::db::Database;
+use crate::documents;
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::jobs::{Job, JobHandle, JobManager};
+use crate::sandbox;
+use crate::secrets;
+use crate::settings::{Settings, SettingsStore};
+use crate::timestamps::{self, Zone};
+
+const SOURCES_DIR: &str = "sources";
+const KINDS: &[&str] = &["git"];
+const NIGHTLY_HOUR: u32 = 3;
+// A source synced more recently than this is skipped by the nightly run
+const NIGHTLY_MIN_AGE_SECS: i64 = 12 * 60 * 60;
+const CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+const TOKEN_USER: &str = "x-access-token";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CorpusSource {
+    pub id: i64,
+    pub kind: String,
+    pub url: String,
+    pub branch: String,
+    // Inside the repository; empty for all of it
+    pub subdir: String,
+    pub auth_ref: Option<String>,
+    // Corpus-relative folder the source owns
+    pub directory: String,
+    pub created_at: String,
+    pub last_synced_at: Option<String>,
+    // "synced", "conflict" or "failed"
+    pub last_status: Option<String>,
+    pub last_revision: Option<String>,
+    pub last_error: Option<String>,
+}
+
+// What a sync changes, by path relative to the source's folder
+#[derive(Serialize, Default, Debug, PartialEq)]
+pub struct SyncPlan {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+    pub unchanged: usize,
+    // Edited locally, left alone since upstream didn't change them
+    pub kept_local: Vec<String>,
+    pub conflicts: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct CorpusSources {
+    syncing: Mutex<BTreeSet<i64>>,
+}
+
+const SOURCE_COLUMNS: &str = "id, kind, url, branch, subdir, auth_ref, directory, created_at, \
+    last_synced_at, last_status, last_revision, last_error";
+
+fn read_source(r: &rusqlite::Row) -> rusqlite::Result<CorpusSource> {
+    Ok(CorpusSource {
+        id: r.get(0)?,
+        kind: r.get(1)?,
+        url: r.get(2)?,
+        branch: r.get(3)?,
+        subdir: r.get(4)?,
+        auth_ref: r.get(5)?,
+        directory: r.get(6)?,
+        created_at: r.get(7)?,
+        last_synced_at: r.get(8)?,
+        last_status: r.get(9)?,
+        last_revision: r.get(10)?,
+        last_error: r.get(11)?,
+    })
+}
+
+fn load(db: &Database, id: i64) -> CommandResult<CorpusSource> {
+    let sql = format!("SELECT {} FROM corpus_sources WHERE id = ?1", SOURCE_COLUMNS);
+    db.with_conn(|conn| conn.query_row(&sql, params![id], read_source).optional())?
+        .ok_or_else(|| CommandError::NotFound(format!("corpus source {}", id)))
+}
+
+fn all(db: &Database) -> CommandResult<Vec<CorpusSource>> {
+    let sql = format!("SELECT {} FROM corpus_sources ORDER BY id", SOURCE_COLUMNS);
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map([], read_source)?;
+        rows.collect()
+    })
+}
+
+// https://, ssh:// and scp-style user@host:path; anything else (file://,
+// ext::, a leading '-') is refused, as is a password in the URL
+pub fn check_git_url(url: &str) -> CommandResult<()> {
+    let scp_like = !url.contains("://")
+        && url
+            .split_once(':')
+            .is_some_and(|(host, path)| host.contains('@') && !host.contains('/') && !path.is_empty());
+    let allowed = url.starts_with("https://") || url.starts_with("ssh://") || scp_like;
+    if !allowed || url.starts_with('-') || url.chars().any(char::is_whitespace) {
+        return Err(CommandError::InvalidInput(format!(
+            "'{}' isn't a git URL this can fetch; use https://, ssh:// or user@host:path",
+            url
+        )));
+    }
+    let authority = url.split_once("://").map_or("", |(_, rest)| rest.split('/').next().unwrap_or_default());
+    if authority.split_once('@').is_some_and(|(userinfo, _)| userinfo.contains(':')) {
+        return Err(CommandError::InvalidInput(
+            "the URL carries a password; keep the token in the keyring and name it in auth_ref".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Loosely git check-ref-format: no leading '-', no "..", no odd characters
+pub fn check_branch(branch: &str) -> CommandResult<()> {
+    let valid = !branch.is_empty()
+        && !branch.starts_with('-')
+        && !branch.starts_with('/')
+        && !branch.ends_with('/')
+        && !branch.ends_with(".lock")
+        && !branch.contains("..")
+        && branch.chars().all(|c| c.is_ascii_alphanumeric() || "-_./".contains(c));
+    if valid {
+        Ok(())
+    } else {
+        Err(CommandError::InvalidInput(format!("'{}' isn't a branch name", branch)))
+    }
+}
+
+enum Auth {
+    Token(String),
+    SshKey(PathBuf),
+}
+
+fn resolve_auth(settings: &Settings, auth_ref: Option<&str>) -> CommandResult<Option<Auth>> {
+    let Some(auth_ref) = auth_ref else {
+        return Ok(None);
+    };
+    match auth_ref.split_once(':') {
+        Some(("token", name)) => {
+            let token = secrets::read(name)?
+                .ok_or_else(|| CommandError::NotConfigured(format!("no secret named {} is stored", name)))?;
+            Ok(Some(Auth::Token(token)))
+        }
+        Some(("ssh_key", path)) => {
+            let key = sandbox::SSH_KEY.validate(settings, Path::new(path))?.into_path_buf();
+            if !key.is_file() {
+                return Err(CommandError::NotFound(format!("ssh key {}", path)));
+            }
+            Ok(Some(Auth::SshKey(key)))
+        }
+        _ => Err(CommandError::InvalidInput(format!(
+            "auth_ref '{}' should be token:<secret name> or ssh_key:<path>",
+            auth_ref
+        ))),
+    }
+}
+
+// "https://host/team/docs.git" -> "docs"
+pub fn directory_name(url: &str) -> String {
+    let last = url.trim_end_matches('/').rsplit(['/', ':']).next().unwrap_or_default();
+    let name: String = last
+        .trim_end_matches(".git")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    let name = name.trim_matches('-');
+    if name.is_empty() { "source".to_string() } else { name.to_string() }
+}
+
+// Quoted for the shell git runs GIT_SSH_COMMAND through
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn git(auth: Option<&Auth>, args: &[&str]) -> Command {
+    let mut command = Command::new("git");
+    command.args(args).env_clear();
+    for name in ["PATH", "HOME", "SSH_AUTH_SOCK"] {
+        if let Some(value) = std::env::var_os(name) {
+            command.env(name, value);
+        }
+    }
+    command
+        .env("LANG", "C")
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_ALLOW_PROTOCOL", "https:ssh");
+    let ssh = match auth {
+        Some(Auth::SshKey(key)) => {
+            format!("ssh -i {} -o IdentitiesOnly=yes -o BatchMode=yes", shell_quote(&key.to_string_lossy()))
+        }
+        _ => "ssh -o BatchMode=yes".to_string(),
+    };
+    command.env("GIT_SSH_COMMAND", ssh);
+    if let Some(Auth::Token(token)) = auth {
+        let credentials = if token.contains(':') { token.clone() } else { format!("{}:{}", TOKEN_USER, token) };
+        let header = base64::engine::general_purpose::STANDARD.encode(credentials);
+        command
+            .env("GIT_CONFIG_COUNT", "1")
+            .env("GIT_CONFIG_KEY_0", "http.extraHeader")
+            .env("GIT_CONFIG_VALUE_0", format!("Authorization: Basic {}", header));
+    }
+    command
+}
+
+fn run_git(handle: &JobHandle, auth: Option<&Auth>, args: &[&str]) -> Result<Vec<String>, String> {
+    let mut output = Vec::new();
+    let status = handle.run_prepared(&mut git(auth, args), |line| output.push(line.to_string()))?;
+    if !status.success() {
+        return Err(format!("git {} failed ({})", args.first().unwrap_or(&""), status));
+    }
+    Ok(output)
+}
+
+// Brings the checkout in `cache` up to date; returns the revision
+fn fetch_git(handle: &JobHandle, source: &CorpusSource, auth: Option<&Auth>, cache: &Path) -> Result<String, String> {
+    let repo = cache.to_string_lossy().into_owned();
+    if cache.join(".git").is_dir() {
+        handle.log(format!("Fetching {} from {}", source.branch, source.url));
+        run_git(handle, auth, &["-C", &repo, "fetch", "--quiet", "--depth", "1", "origin", &source.branch])?;
+        run_git(handle, auth, &["-C", &repo, "reset", "--quiet", "--hard", "FETCH_HEAD"])?;
+        run_git(handle, auth, &["-C", &repo, "clean", "--quiet", "-fdx"])?;
+    } else {
+        handle.log(format!("Cloning {} ({})", source.url, source.branch));
+        if cache.exists() {
+            std::fs::remove_dir_all(cache).map_err(|e| format!("can't clear {}: {}", repo, e))?;
+        }
+        if let Some(parent) = cache.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let (branch, url, repo) = (source.branch.as_str(), source.url.as_str(), repo.as_str());
+        let args = ["clone", "--quiet", "--depth", "1", "--single-branch", "--branch", branch, "--", url, repo];
+        run_git(handle, auth, &args)?;
+    }
+    let revision = run_git(handle, auth, &["-C", &repo, "rev-parse", "HEAD"])?;
+    Ok(revision.first().map(|r| r.trim().to_string()).unwrap_or_default())
+}
+
+// The connector for the source's kind: updates its copy under `cache`,
+// returning the revision it is at
+fn fetch(handle: &JobHandle, source: &CorpusSource, auth: Option<&Auth>, cache: &Path) -> Result<String, String> {
+    match source.kind.as_str() {
+        "git" => fetch_git(handle, source, auth, cache),
+        other => Err(format!("no connector for '{}' sources", other)),
+    }
+}
+
+fn hash_file(path: &Path) -> Option<String> {
+    let bytes = std::fs::read(path).ok()?;
+    Some(Sha256::digest(&bytes).iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+// Regular files under `dir` by '/'-separated relative path, with their
+// hashes; .git and symlinks are left out
+fn tree(dir: &Path, prefix: &str, files: &mut BTreeMap<String, String>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let relative = if prefix.is_empty() { name.clone() } else { format!("{}/{}", prefix, name) };
+        if file_type.is_dir() && name != ".git" {
+            tree(&entry.path(), &relative, files);
+        } else if file_type.is_file() {
+            if let Some(hash) = hash_file(&entry.path()) {
+                files.insert(relative, hash);
+            }
+        }
+    }
+}
+
+// `previous` is what the last sync wrote, `upstream` what the source has
+// now and `local` what is on disk for every path in either (None when the
+// file is gone). Writing over or removing a file whose content is neither
+// what was written nor what upstream has is a conflict.
+pub fn plan_sync(
+    previous: &BTreeMap<String, String>,
+    upstream: &BTreeMap<String, String>,
+    local: &BTreeMap<String, Option<String>>,
+) -> SyncPlan {
+    let mut plan = SyncPlan::default();
+    let paths: BTreeSet<&String> = previous.keys().chain(upstream.keys()).collect();
+    for path in paths {
+        let on_disk = local.get(path).cloned().flatten();
+        let edited = |up: Option<&String>| {
+            on_disk.as_ref().is_some_and(|l| Some(l) != previous.get(path) && Some(l) != up)
+        };
+        match (previous.get(path), upstream.get(path)) {
+            (Some(before), Some(now)) if before == now => {
+                if on_disk.as_ref() == Some(now) {
+                    plan.unchanged += 1;
+                } else {
+                    plan.kept_local.push(path.clone());
+                }
+            }
+            (_, Some(now)) if edited(Some(now)) => plan.conflicts.push(path.clone()),
+            (None, Some(_)) => plan.added.push(path.clone()),
+            (Some(_), Some(_)) => plan.updated.push(path.clone()),
+            (Some(_), None) if edited(None) => plan.conflicts.push(path.clone()),
+            (Some(_), None) if on_disk.is_some() => plan.removed.push(path.clone()),
+            _ => {}
+        }
+    }
+    plan
+}
+
+fn manifest(db: &Database, source_id: i64) -> CommandResult<BTreeMap<String, String>> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare("SELECT path, sha256 FROM corpus_source_files WHERE source_id = ?1")?;
+        let rows = stmt.query_map(params![source_id], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        rows.collect()
+    })
+}
+
+fn save_manifest(db: &Database, source_id: i64, files: &BTreeMap<String, String>) -> CommandResult<()> {
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM corpus_source_files WHERE source_id = ?1", params![source_id])?;
+        for (path, hash) in files {
+            tx.execute(
+                "INSERT INTO corpus_source_files (source_id, path, sha256) VALUES (?1, ?2, ?3)",
+                params![source_id, path, hash],
+            )?;
+        }
+        tx.commit()
+    })
+}
+
+fn record_outcome(db: &Database, source_id: i64, status: &str, revision: Option<&str>, error: Option<&str>) {
+    let result = db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE corpus_sources SET last_synced_at = ?2, last_status = ?3,
+                last_revision = COALESCE(?4, last_revision), last_error = ?5 WHERE id = ?1",
+            params![source_id, chrono::Utc::now().to_rfc3339(), status, revision, error],
+        )
+    });
+    if let Err(e) = result {
+        println!("[Halbert] Can't record sync of corpus source {}: {}", source_id, e);
+    }
+}
+
+// Empty folders left behind by a removal, up to the source's own
+fn prune_empty(base: &Path, file: &Path) {
+    let mut dir = file.parent();
+    while let Some(current) = dir.filter(|d| *d != base && d.starts_with(base)) {
+        if std::fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
+
+// Why a sync stopped: "conflict" or "failed"
+struct Failure {
+    status: &'static str,
+    message: String,
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Failure {
+            status: "failed",
+            message,
+        }
+    }
+}
+
+fn sync(app: &AppHandle, handle: &JobHandle, source: &CorpusSource, cache: &Path) -> Result<SyncPlan, Failure> {
+    let settings = app.state::<SettingsStore>().get();
+    let db = app.state::<Database>();
+    let auth = resolve_auth(&settings, source.auth_ref.as_deref()).map_err(|e| e.to_string())?;
+    let root = documents::corpus_root(&settings).map_err(|e| e.to_string())?;
+    let base = root.join(&source.directory);
+    std::fs::create_dir_all(&base).map_err(|e| format!("can't create {}: {}", source.directory, e))?;
+    let base = sandbox::IMPORT_TARGET.validate(&settings, &base).map_err(|e| e.to_string())?.into_path_buf();
+
+    let revision = fetch(handle, source, auth.as_ref(), cache)?;
+    let subdir = cache.join(&source.subdir);
+    if !subdir.is_dir() {
+        return Err(format!("{} has no folder {} at {}", source.url, source.subdir, revision).into());
+    }
+    let mut upstream = BTreeMap::new();
+    tree(&subdir, "", &mut upstream);
+    let previous = manifest(&db, source.id).map_err(|e| e.to_string())?;
+    let local: BTreeMap<String, Option<String>> = previous
+        .keys()
+        .chain(upstream.keys())
+        .map(|path| (path.clone(), hash_file(&base.join(path))))
+        .collect();
+    let plan = plan_sync(&previous, &upstream, &local);
+    handle.set_result(json!({ "revision": revision, "plan": plan }));
+    if !plan.conflicts.is_empty() {
+        let message = format!(
+            "{} locally modified file(s) under {} would be overwritten or removed: {}",
+            plan.conflicts.len(),
+            source.directory,
+            plan.conflicts.join(", ")
+        );
+        return Err(Failure {
+            status: "conflict",
+            message,
+        });
+    }
+
+    for path in plan.added.iter().chain(&plan.updated) {
+        corpus_import::write_copy(&subdir.join(path), &base.join(path), &base)
+            .map_err(|e| format!("can't write {}/{}: {}", source.directory, path, e))?;
+    }
+    for path in &plan.removed {
+        let file = base.join(path);
+        std::fs::remove_file(&file).map_err(|e| format!("can't remove {}/{}: {}", source.directory, path, e))?;
+        prune_empty(&base, &file);
+    }
+    save_manifest(&db, source.id, &upstream).map_err(|e| e.to_string())?;
+    record_outcome(&db, source.id, "synced", Some(&revision), None);
+
+    handle.log(format!(
+        "At {}: {} added, {} updated, {} removed, {} unchanged",
+        revision,
+        plan.added.len(),
+        plan.updated.len(),
+        plan.removed.len(),
+        plan.unchanged
+    ));
+    for path in &plan.kept_local {
+        handle.log(format!("Kept local changes to {}", path));
+    }
+    let changed: Vec<String> = plan
+        .added
+        .iter()
+        .chain(&plan.updated)
+        .chain(&plan.removed)
+        .map(|path| format!("{}/{}", source.directory, path))
+        .collect();
+    if !changed.is_empty() {
+        documents::corpus_changed(app, corpora::PRIMARY, changed);
+    }
+    Ok(plan)
+}
+
+fn start_sync(app: &AppHandle, source: CorpusSource) -> CommandResult<Job> {
+    let cache = app.path().app_data_dir().map_err(|e| CommandError::Internal(e.to_string()))?;
+    let cache = cache.join("corpus_sources").join(source.id.to_string());
+    if !app.state::<CorpusSources>().syncing.lock().unwrap().insert(source.id) {
+        return Err(CommandError::Conflict(format!("{} is already syncing", source.url)));
+    }
+    let worker = app.clone();
+    let name = format!("Sync {}", source.url);
+    Ok(app.state::<JobManager>().spawn(&name, "corpus_sync", move |handle| {
+        let result = sync(&worker, handle, &source, &cache);
+        worker.state::<CorpusSources>().syncing.lock().unwrap().remove(&source.id);
+        match result {
+            Ok(plan) => {
+                println!(
+                    "[Halbert] Synced corpus source {}: {} added, {} updated, {} removed",
+                    source.id,
+                    plan.added.len(),
+                    plan.updated.len(),
+                    plan.removed.len()
+                );
+                Ok(())
+            }
+            Err(failure) => {
+                record_outcome(&worker.state::<Database>(), source.id, failure.status, None, Some(&failure.message));
+                Err(failure.message)
+            }
+        }
+    }))
+}
+
+fn due_nightly(source: &CorpusSource, now: chrono::DateTime<chrono::Utc>) -> bool {
+    source
+        .last_synced_at
+        .as_deref()
+        .and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok())
+        .is_none_or(|at| (now - at.with_timezone(&chrono::Utc)).num_seconds() >= NIGHTLY_MIN_AGE_SECS)
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(CHECK_INTERVAL);
+        let now = chrono::Utc::now();
+        let zone = timestamps::parse_zone(&app.state::<SettingsStore>().get().timezone).unwrap_or(Zone::Local);
+        if zone.naive(now).hour() != NIGHTLY_HOUR {
+            continue;
+        }
+        let sources = match all(&app.state::<Database>()) {
+            Ok(sources) => sources,
+            Err(e) => {
+                println!("[Halbert] Skipping nightly corpus sync: {}", e);
+                continue;
+            }
+        };
+        for source in sources.into_iter().filter(|s| due_nightly(s, now)) {
+            if !app.state::<Gate>().allows(Entry::Schedule, &format!("Nightly sync of {}", source.url)) {
+                continue;
+            }
+            match start_sync(&app, source) {
+                Ok(job) => println!("[Halbert] Nightly corpus sync started as {}", job.id),
+                Err(e) => println!("[Halbert] Nightly corpus sync skipped: {}", e),
+            }
+        }
+    });
+}
+
+// The source gets its own folder under sources/ in the corpus, named after
+// the repository
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn add_corpus_source(
+    db: State<'_, Database>,
+    settings: State<'_, SettingsStore>,
+    kind: String,
+    url: String,
+    branch: Option<String>,
+    subdir: Option<String>,
+    auth_ref: Option<String>,
+) -> CommandResult<CorpusSource> {
+    let kind = kind.trim().to_string();
+    if !KINDS.contains(&kind.as_str()) {
+        return Err(CommandError::InvalidInput(format!(
+            "unknown source kind '{}', expected one of {}",
+            kind,
+            KINDS.join(", ")
+        )));
+    }
+    let url = url.trim().to_string();
+    check_git_url(&url)?;
+    let branch = branch.map(|b| b.trim().to_string()).filter(|b| !b.is_empty()).unwrap_or_else(|| "main".to_string());
+    check_branch(&branch)?;
+    let subdir = match subdir.as_deref().filter(|s| !s.trim().is_empty()) {
+        Some(subdir) => corpus_import::safe_subdir(subdir)?.to_string_lossy().replace('\\', "/"),
+        None => String::new(),
+    };
+    let auth_ref = auth_ref.map(|a| a.trim().to_string()).filter(|a| !a.is_empty());
+    let settings = settings.get();
+    documents::corpus_root(&settings)?;
+    resolve_auth(&settings, auth_ref.as_deref())?;
+
+    let taken: BTreeSet<String> = all(&db)?.into_iter().map(|s| s.directory).collect();
+    let name = directory_name(&url);
+    let directory = (1..)
+        .map(|n| match n {
+            1 => format!("{}/{}", SOURCES_DIR, name),
+            n => format!("{}/{}-{}", SOURCES_DIR, name, n),
+        })
+        .find(|d| !taken.contains(d))
+        .unwrap_or_default();
+    let now = chrono::Utc::now().to_rfc3339();
+    let id = db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO corpus_sources (kind, url, branch, subdir, auth_ref, directory, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![kind, url, branch, subdir, auth_ref, directory, now],
+        )?;
+        Ok(conn.last_insert_rowid())
+    })?;
+    println!("[Halbert] Added corpus source {}: {} into {}", id, url, directory);
+    load(&db, id)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn list_corpus_sources(db: State<'_, Database>) -> CommandResult<Vec<CorpusSource>> {
+    all(&db)
+}
+
+// The files it synced stay in the corpus
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn remove_corpus_source(db: State<'_, Database>, source_id: i64) -> CommandResult<()> {
+    let deleted = db.with_conn(|conn| conn.execute("DELETE FROM corpus_sources WHERE id = ?1", params![source_id]))?;
+    if deleted == 0 {
+        return Err(CommandError::NotFound(format!("corpus source {}", source_id)));
+    }
+    Ok(())
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn sync_corpus_source(app: AppHandle, db: State<'_, Database>, source_id: i64) -> CommandResult<Job> {
+    let source = load(&db, source_id)?;
+    if exec::find_in_path("git").is_none() {
+        return Err(CommandError::ToolMissing("git is not installed".to_string()));
+    }
+    start_sync(&app, source)
+}
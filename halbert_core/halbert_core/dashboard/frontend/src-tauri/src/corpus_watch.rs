@@ -0,0 +1,214 @@
+// Watching the corpora for changes made outside Halbert.
+//
+// Every POLL_INTERVAL each corpus root is walked (see
+// documents::walk_corpus) and each file's size and mtime compared with the
+// walk before; files added, changed or removed go to
+// documents::corpus_changed like Halbert's own imports and deletes, which
+// note what they wrote so the next walk doesn't report it again. When the
+// corpora in settings change, `retarget` drops the watch on the old roots
+// and takes one on the new (see settings::reload_corpora). Its first walk
+// is the baseline, so files already there aren't reported. A root that
+// can't be read fails the retarget and the old watch stays.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::app::{AppHandle, Manager};
+use crate::corpora;
+use crate::documents;
+use crate::error::CommandResult;
+use crate::settings::{Settings, SettingsStore};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+// Size and mtime by corpus-relative source
+type Files = BTreeMap<String, (u64, Option<SystemTime>)>;
+
+struct Watch {
+    corpus: String,
+    root: PathBuf,
+    files: Files,
+}
+
+#[derive(Default)]
+pub struct CorpusWatcher {
+    watches: Mutex<Vec<Watch>>,
+    // Bumped by retarget, so a walk of the old roots is thrown away
+    generation: Mutex<u64>,
+}
+
+fn stat(path: &Path) -> Option<(u64, Option<SystemTime>)> {
+    let meta = std::fs::metadata(path).ok().filter(|m| m.is_file())?;
+    Some((meta.len(), meta.modified().ok()))
+}
+
+fn walk(settings: &Settings, root: &Path) -> Files {
+    documents::walk_corpus(settings, root)
+        .into_iter()
+        .filter_map(|(source, path)| Some((source, stat(&path)?)))
+        .collect()
+}
+
+// Sources added, changed or removed between two walks
+fn changed(before: &Files, after: &Files) -> Vec<String> {
+    let mut sources: Vec<String> = after
+        .iter()
+        .filter(|(source, now)| before.get(*source) != Some(now))
+        .map(|(source, _)| source.clone())
+        .chain(before.keys().filter(|source| !after.contains_key(*source)).cloned())
+        .collect();
+    sources.sort();
+    sources
+}
+
+impl CorpusWatcher {
+    pub fn retarget(&self, settings: &Settings) -> CommandResult<()> {
+        let mut watches = Vec::new();
+        for corpus in corpora::names(settings) {
+            let root = corpora::root(settings, &corpus)?;
+            std::fs::read_dir(&root)?;
+            let files = walk(settings, &root);
+            watches.push(Watch { corpus, root, files });
+        }
+        let mut generation = self.generation.lock().unwrap();
+        *generation += 1;
+        *self.watches.lock().unwrap() = watches;
+        Ok(())
+    }
+
+    // The roots being watched, by corpus
+    pub fn roots(&self) -> Vec<(String, PathBuf)> {
+        self.watches.lock().unwrap().iter().map(|w| (w.corpus.clone(), w.root.clone())).collect()
+    }
+
+    // Walks every root and returns what changed since the last walk, by
+    // corpus. The roots are walked without holding the lock.
+    pub fn poll(&self, settings: &Settings) -> Vec<(String, Vec<String>)> {
+        let started = *self.generation.lock().unwrap();
+        let walked: Vec<(String, Files)> =
+            self.roots().into_iter().map(|(corpus, root)| (corpus, walk(settings, &root))).collect();
+        let generation = self.generation.lock().unwrap();
+        if *generation != started {
+            return Vec::new();
+        }
+        let mut watches = self.watches.lock().unwrap();
+        let mut changes = Vec::new();
+        for (watch, (_, files)) in watches.iter_mut().zip(walked) {
+            let sources = changed(&watch.files, &files);
+            watch.files = files;
+            if !sources.is_empty() {
+                changes.push((watch.corpus.clone(), sources));
+            }
+        }
+        changes
+    }
+
+    // Halbert wrote or removed these itself and has announced them already
+    pub fn note(&self, corpus: &str, sources: &[String]) {
+        let mut watches = self.watches.lock().unwrap();
+        let Some(watch) = watches.iter_mut().find(|w| w.corpus == corpus) else {
+            return;
+        };
+        for source in sources {
+            let path = watch.root.join(source);
+            match stat(&path) {
+                Some(now) => {
+                    watch.files.insert(source.clone(), now);
+                }
+                // A folder is left to the next walk
+                None if path.is_dir() => {}
+                None => {
+                    watch.files.remove(source);
+                }
+            }
+        }
+    }
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        let watcher = app.state::<CorpusWatcher>();
+        if let Err(e) = watcher.retarget(&app.state::<SettingsStore>().get()) {
+            println!("[Halbert] Not watching the corpora: {}", e);
+        }
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let settings = app.state::<SettingsStore>().get();
+            for (corpus, sources) in watcher.poll(&settings) {
+                println!("[Halbert] {} file(s) changed in corpus {}", sources.len(), corpus);
+                documents::corpus_changed(&app, &corpus, sources);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(root: &Path) -> Settings {
+        Settings {
+            corpus_path: Some(root.display().to_string()),
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn reports_what_changed_since_the_last_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("kept.md"), "# Kept\n").unwrap();
+        std::fs::write(dir.path().join("edited.md"), "# Before\n").unwrap();
+        std::fs::write(dir.path().join("gone.md"), "# Gone\n").unwrap();
+        let settings = settings(dir.path());
+        let watcher = CorpusWatcher::default();
+        watcher.retarget(&settings).unwrap();
+        assert!(watcher.poll(&settings).is_empty(), "files already there aren't news");
+
+        std::fs::write(dir.path().join("edited.md"), "# After, and longer\n").unwrap();
+        std::fs::remove_file(dir.path().join("gone.md")).unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub/new.md"), "# New\n").unwrap();
+        std::fs::write(dir.path().join(".hidden.md"), "# Hidden\n").unwrap();
+        let sources = ["edited.md", "gone.md", "sub/new.md"].map(String::from).to_vec();
+        assert_eq!(watcher.poll(&settings), [(corpora::PRIMARY.to_string(), sources)]);
+        assert!(watcher.poll(&settings).is_empty());
+    }
+
+    #[test]
+    fn noted_changes_are_not_reported_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = settings(dir.path());
+        let watcher = CorpusWatcher::default();
+        watcher.retarget(&settings).unwrap();
+        std::fs::write(dir.path().join("imported.md"), "# Imported\n").unwrap();
+        std::fs::write(dir.path().join("outside.md"), "# Outside\n").unwrap();
+        watcher.note(corpora::PRIMARY, &["imported.md".to_string()]);
+        let outside = vec!["outside.md".to_string()];
+        assert_eq!(watcher.poll(&settings), [(corpora::PRIMARY.to_string(), outside)]);
+    }
+
+    #[test]
+    fn retargeting_moves_the_watch_to_the_new_roots() {
+        let (old, new) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let watcher = CorpusWatcher::default();
+        watcher.retarget(&settings(old.path())).unwrap();
+        let settings = settings(new.path());
+        watcher.retarget(&settings).unwrap();
+        assert_eq!(watcher.roots(), [(corpora::PRIMARY.to_string(), new.path().canonicalize().unwrap())]);
+
+        std::fs::write(old.path().join("old.md"), "# Old\n").unwrap();
+        std::fs::write(new.path().join("new.md"), "# New\n").unwrap();
+        let sources = vec!["new.md".to_string()];
+        assert_eq!(watcher.poll(&settings), [(corpora::PRIMARY.to_string(), sources)]);
+    }
+
+    #[test]
+    fn a_root_that_cannot_be_read_keeps_the_old_watch() {
+        let dir = tempfile::tempdir().unwrap();
+        let watcher = CorpusWatcher::default();
+        watcher.retarget(&settings(dir.path())).unwrap();
+        assert!(watcher.retarget(&settings(&dir.path().join("missing"))).is_err());
+        assert_eq!(watcher.roots(), [(corpora::PRIMARY.to_string(), dir.path().canonicalize().unwrap())]);
+    }
+}
@@ -0,0 +1,520 @@
+// Local SQLite database (halbert.db in the app data dir).
+//
+// Schema changes are appended to MIGRATIONS and applied in order on open;
+// `PRAGMA user_version` records how many have run. Never edit a migration
+// that has shipped, add a new one instead.
+//
+// Opening (with its migrations) happens in the background at startup, so
+// until it finishes every query fails fast with NotReady("database")
+// rather than waiting for it.
+use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::error::{CommandError, CommandResult};
+
+const MIGRATIONS: &[&str] = &[
+    // 1: audit log
+    "CREATE TABLE audit_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        at TEXT NOT NULL,
+        actor TEXT NOT NULL,
+        action TEXT NOT NULL,
+        target TEXT NOT NULL,
+        detail TEXT
+    );
+    CREATE INDEX audit_log_target ON audit_log(target);",
+    // 2: RAG Q&A conversation history
+    "CREATE TABLE conversations (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        title TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE TABLE conversation_messages (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        conversation_id INTEGER NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+        question TEXT NOT NULL,
+        answer TEXT NOT NULL,
+        sources TEXT NOT NULL DEFAULT '[]',
+        prompt_tokens INTEGER,
+        completion_tokens INTEGER,
+        latency_ms INTEGER NOT NULL,
+        failed INTEGER NOT NULL DEFAULT 0,
+        error TEXT,
+        asked_at TEXT NOT NULL,
+        answered_at TEXT NOT NULL
+    );
+    CREATE INDEX conversation_messages_conversation ON conversation_messages(conversation_id);",
+    // 3: document tags, keyed by corpus-relative source path
+    "CREATE TABLE document_tags (
+        source TEXT NOT NULL,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (source, tag)
+    );
+    CREATE INDEX document_tags_tag ON document_tags(tag);",
+    // 4: per-mount disk usage samples, `at` in unix seconds
+    "CREATE TABLE disk_usage_samples (
+        mount_point TEXT NOT NULL,
+        at INTEGER NOT NULL,
+        used_gb REAL NOT NULL,
+        total_gb REAL NOT NULL
+    );
+    CREATE INDEX disk_usage_samples_mount_at ON disk_usage_samples(mount_point, at);",
+    // 5: latest TLS certificate scan per target, result as JSON
+    "CREATE TABLE certificate_results (
+        target TEXT PRIMARY KEY,
+        checked_at TEXT NOT NULL,
+        result TEXT NOT NULL
+    );",
+    // 6: hourly listening port snapshots, the baseline for change summaries
+    "CREATE TABLE listening_port_snapshots (
+        at INTEGER NOT NULL,
+        ports TEXT NOT NULL
+    );
+    CREATE INDEX listening_port_snapshots_at ON listening_port_snapshots(at);",
+    // 7: named configuration snapshots for drift comparison
+    "CREATE TABLE baselines (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        snapshot TEXT NOT NULL
+    );",
+    // 8: user job templates; argv and schema are JSON
+    "CREATE TABLE job_templates (
+        name TEXT PRIMARY KEY,
+        description TEXT NOT NULL,
+        command TEXT NOT NULL,
+        args_template TEXT NOT NULL,
+        param_schema TEXT NOT NULL,
+        timeout_secs INTEGER,
+        risk_level TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );",
+    // 9: when storage maintenance last ran, unix seconds
+    "CREATE TABLE storage_maintenance (
+        ran_at INTEGER NOT NULL
+    );",
+    // 10: reusable approval requests; resources and job params are JSON
+    "CREATE TABLE approval_templates (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL UNIQUE,
+        task TEXT NOT NULL,
+        action TEXT NOT NULL,
+        affected_resources TEXT NOT NULL,
+        risk_level TEXT NOT NULL,
+        linked_job_template TEXT,
+        job_params TEXT,
+        created_at TEXT NOT NULL
+    );",
+    // 11: OOM kills and crashes, and where each journal reader left off
+    "CREATE TABLE incidents (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        at TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        victim TEXT NOT NULL,
+        pid INTEGER,
+        unit TEXT,
+        detail TEXT NOT NULL,
+        memory_percent REAL
+    );
+    CREATE INDEX incidents_at ON incidents (at);
+    CREATE TABLE journal_cursors (
+        name TEXT PRIMARY KEY,
+        cursor TEXT NOT NULL
+    );",
+    // 12: individual approval votes. Request ids restart with the app, so
+    // a request is its id plus requested_at.
+    "CREATE TABLE approval_votes (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        request_id TEXT NOT NULL,
+        requested_at TEXT NOT NULL,
+        approver TEXT NOT NULL,
+        source TEXT NOT NULL,
+        vote TEXT NOT NULL,
+        at TEXT NOT NULL
+    );
+    CREATE INDEX approval_votes_request ON approval_votes (request_id, requested_at);",
+    // 13: user scripts run on notification events, and their recent runs
+    "CREATE TABLE hooks (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        event TEXT NOT NULL,
+        script_path TEXT NOT NULL,
+        timeout_s INTEGER NOT NULL,
+        enabled INTEGER NOT NULL,
+        consecutive_failures INTEGER NOT NULL DEFAULT 0,
+        created_at TEXT NOT NULL
+    );
+    CREATE TABLE hook_runs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        hook_id INTEGER NOT NULL REFERENCES hooks (id) ON DELETE CASCADE,
+        event TEXT NOT NULL,
+        started_at TEXT NOT NULL,
+        duration_ms INTEGER NOT NULL,
+        status TEXT NOT NULL,
+        exit_code INTEGER,
+        output TEXT NOT NULL,
+        truncated INTEGER NOT NULL
+    );
+    CREATE INDEX hook_runs_hook ON hook_runs (hook_id);",
+    // 14: decided approval requests with what came of them, and whether
+    // the outcome reached the backend yet
+    "CREATE TABLE approval_decisions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        request_id TEXT NOT NULL,
+        requested_at TEXT NOT NULL,
+        task TEXT NOT NULL,
+        task_type TEXT,
+        confidence REAL NOT NULL,
+        risk_level TEXT NOT NULL,
+        status TEXT NOT NULL,
+        decided_at TEXT NOT NULL,
+        outcome TEXT,
+        outcome_notes TEXT,
+        outcome_at TEXT,
+        forwarded INTEGER NOT NULL DEFAULT 0,
+        forward_attempts INTEGER NOT NULL DEFAULT 0,
+        prompted_at TEXT,
+        UNIQUE (request_id, requested_at)
+    );",
+    // 15: files jobs left behind, and where template jobs leave them. Job
+    // ids restart with the app, so a job is its id and start time.
+    "CREATE TABLE job_artifacts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        job_id TEXT NOT NULL,
+        job_started_at TEXT NOT NULL,
+        path TEXT NOT NULL,
+        size_bytes INTEGER NOT NULL,
+        mime TEXT NOT NULL,
+        sha256 TEXT NOT NULL,
+        partial INTEGER NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX job_artifacts_job ON job_artifacts (job_id, job_started_at);
+    ALTER TABLE job_templates ADD COLUMN artifacts_dir TEXT;",
+    // 16: usage history for period summaries: local metrics samples, their
+    // hourly rollups (`hour` is the unix second the UTC hour starts) and
+    // finished jobs
+    "CREATE TABLE metrics_samples (
+        at INTEGER NOT NULL,
+        cpu_percent REAL NOT NULL,
+        memory_percent REAL NOT NULL,
+        rx_bytes INTEGER NOT NULL,
+        tx_bytes INTEGER NOT NULL
+    );
+    CREATE INDEX metrics_samples_at ON metrics_samples(at);
+    CREATE TABLE metrics_hourly (
+        hour INTEGER PRIMARY KEY,
+        samples INTEGER NOT NULL,
+        cpu_sum REAL NOT NULL,
+        cpu_histogram TEXT NOT NULL,
+        memory_sum REAL NOT NULL,
+        memory_peak REAL NOT NULL,
+        rx_bytes INTEGER NOT NULL,
+        tx_bytes INTEGER NOT NULL
+    );
+    CREATE TABLE job_runs (
+        job_id TEXT NOT NULL,
+        started_at TEXT NOT NULL,
+        task_type TEXT NOT NULL,
+        status TEXT NOT NULL,
+        finished_at INTEGER NOT NULL,
+        duration_secs REAL NOT NULL,
+        UNIQUE (job_id, started_at)
+    );
+    CREATE INDEX job_runs_finished_at ON job_runs(finished_at);",
+    // 17: corpus source connectors, and what each last sync wrote into the
+    // corpus so local edits can be told from upstream ones
+    "CREATE TABLE corpus_sources (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        kind TEXT NOT NULL,
+        url TEXT NOT NULL,
+        branch TEXT NOT NULL,
+        subdir TEXT NOT NULL,
+        auth_ref TEXT,
+        directory TEXT NOT NULL UNIQUE,
+        created_at TEXT NOT NULL,
+        last_synced_at TEXT,
+        last_status TEXT,
+        last_revision TEXT,
+        last_error TEXT
+    );
+    CREATE TABLE corpus_source_files (
+        source_id INTEGER NOT NULL REFERENCES corpus_sources(id) ON DELETE CASCADE,
+        path TEXT NOT NULL,
+        sha256 TEXT NOT NULL,
+        PRIMARY KEY (source_id, path)
+    );",
+    // 18: itemized disk cleanup plans; `modified_at` is the unix second a
+    // file was last modified when it was planned
+    "CREATE TABLE cleanup_plans (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        created_at TEXT NOT NULL,
+        status TEXT NOT NULL,
+        targets TEXT NOT NULL,
+        categories TEXT NOT NULL,
+        approval_id TEXT,
+        executed_at TEXT,
+        result TEXT
+    );
+    CREATE TABLE cleanup_items (
+        plan_id INTEGER NOT NULL REFERENCES cleanup_plans(id) ON DELETE CASCADE,
+        category TEXT NOT NULL,
+        path TEXT NOT NULL,
+        size_bytes INTEGER NOT NULL,
+        modified_at INTEGER NOT NULL
+    );
+    CREATE INDEX cleanup_items_plan ON cleanup_items(plan_id, category);",
+    // 19: where user job templates run and their environment; `secret_env`
+    // maps variables to job secret names, never values
+    "ALTER TABLE job_templates ADD COLUMN cwd TEXT;
+    ALTER TABLE job_templates ADD COLUMN env TEXT NOT NULL DEFAULT '{}';
+    ALTER TABLE job_templates ADD COLUMN secret_env TEXT NOT NULL DEFAULT '{}';",
+    // 20: what each search and answer retrieved, and the ratings given to
+    // those chunks; ratings keep their document so they outlive the query
+    "CREATE TABLE retrieval_queries (
+        query_id TEXT PRIMARY KEY,
+        kind TEXT NOT NULL,
+        query TEXT NOT NULL,
+        asked_at TEXT NOT NULL
+    );
+    CREATE INDEX retrieval_queries_asked_at ON retrieval_queries(asked_at);
+    CREATE TABLE retrieval_results (
+        query_id TEXT NOT NULL REFERENCES retrieval_queries(query_id) ON DELETE CASCADE,
+        chunk_id TEXT NOT NULL,
+        source TEXT NOT NULL,
+        rank INTEGER NOT NULL,
+        PRIMARY KEY (query_id, chunk_id)
+    );
+    CREATE TABLE retrieval_feedback (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        query_id TEXT NOT NULL,
+        chunk_id TEXT NOT NULL,
+        doc_id TEXT NOT NULL,
+        source TEXT NOT NULL,
+        rating INTEGER NOT NULL,
+        note TEXT,
+        submitted_at TEXT NOT NULL,
+        forwarded INTEGER NOT NULL DEFAULT 0,
+        forward_attempts INTEGER NOT NULL DEFAULT 0,
+        UNIQUE (query_id, chunk_id)
+    );
+    CREATE INDEX retrieval_feedback_doc ON retrieval_feedback(doc_id);
+    ALTER TABLE conversation_messages ADD COLUMN query_id TEXT;",
+    // 21: named corpora; tags and retrieved chunks say which corpus their
+    // source is in, everything so far being in the primary one
+    "CREATE TABLE document_tags_by_corpus (
+        corpus TEXT NOT NULL,
+        source TEXT NOT NULL,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (corpus, source, tag)
+    );
+    INSERT INTO document_tags_by_corpus (corpus, source, tag) SELECT 'primary', source, tag FROM document_tags;
+    DROP TABLE document_tags;
+    ALTER TABLE document_tags_by_corpus RENAME TO document_tags;
+    CREATE INDEX document_tags_tag ON document_tags(tag);
+    ALTER TABLE retrieval_results ADD COLUMN corpus TEXT NOT NULL DEFAULT 'primary';",
+    // 22: mounts a template snapshots before it runs
+    "ALTER TABLE job_templates ADD COLUMN snapshot_before TEXT NOT NULL DEFAULT '[]';
+    ALTER TABLE job_templates ADD COLUMN snapshot_optional INTEGER NOT NULL DEFAULT 0;",
+    // 23: history imported from the standalone backend, and how far each
+    // kind's import got
+    "ALTER TABLE approval_decisions ADD COLUMN origin TEXT NOT NULL DEFAULT 'local';
+    ALTER TABLE job_runs ADD COLUMN origin TEXT NOT NULL DEFAULT 'local';
+    ALTER TABLE audit_log ADD COLUMN origin TEXT NOT NULL DEFAULT 'local';
+    ALTER TABLE audit_log ADD COLUMN origin_id TEXT;
+    CREATE UNIQUE INDEX audit_log_origin_id ON audit_log(origin_id);
+    CREATE TABLE backend_import_cursors (
+        kind TEXT PRIMARY KEY,
+        cursor TEXT,
+        updated_at TEXT NOT NULL
+    );",
+    // 24: remembered consent decisions, one per capability
+    "CREATE TABLE consents (
+        capability TEXT PRIMARY KEY,
+        granted INTEGER NOT NULL,
+        decided_by TEXT NOT NULL,
+        decided_at TEXT NOT NULL
+    );",
+    // 25: backend request latency, rolled up per hour and endpoint;
+    // buckets is a JSON array of counts (see backend_latency)
+    "CREATE TABLE backend_latency_hours (
+        hour INTEGER NOT NULL,
+        endpoint TEXT NOT NULL,
+        buckets TEXT NOT NULL,
+        requests INTEGER NOT NULL,
+        errors INTEGER NOT NULL,
+        timeouts INTEGER NOT NULL,
+        PRIMARY KEY (hour, endpoint)
+    );",
+];
+
+pub struct Database {
+    path: PathBuf,
+    // Set once by open; an Err keeps the reason the open failed
+    conn: OnceLock<Result<Mutex<Connection>, String>>,
+}
+
+impl Database {
+    // Doesn't touch the file; queries answer NotReady until open has run
+    pub fn new(path: &Path) -> Self {
+        Database {
+            path: path.to_path_buf(),
+            conn: OnceLock::new(),
+        }
+    }
+
+    pub fn open(&self) -> CommandResult<()> {
+        self.set_opened(Self::connect(&self.path))
+    }
+
+    fn set_opened(&self, connected: CommandResult<Connection>) -> CommandResult<()> {
+        match connected {
+            Ok(conn) => {
+                let _ = self.conn.set(Ok(Mutex::new(conn)));
+                Ok(())
+            }
+            Err(e) => {
+                let _ = self.conn.set(Err(e.to_string()));
+                Err(e)
+            }
+        }
+    }
+
+    // A fresh, migrated database for tests; nothing touches the disk
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        let db = Database::new(Path::new(":memory:"));
+        let mut conn = Connection::open_in_memory().expect("in-memory database");
+        conn.pragma_update(None, "foreign_keys", true).expect("foreign keys");
+        migrate(&mut conn).expect("migrations");
+        let _ = db.conn.set(Ok(Mutex::new(conn)));
+        db
+    }
+
+    fn connect(path: &Path) -> CommandResult<Connection> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut conn)?;
+        Ok(conn)
+    }
+
+    // The database file plus its WAL and shared-memory companions
+    pub fn files(&self) -> Vec<PathBuf> {
+        ["", "-wal", "-shm"]
+            .iter()
+            .map(|suffix| {
+                let mut name = self.path.clone().into_os_string();
+                name.push(suffix);
+                PathBuf::from(name)
+            })
+            .collect()
+    }
+
+    // Fold the WAL back into the main file so the next open starts clean.
+    // Called on shutdown; the connection itself closes when the process exits.
+    pub fn checkpoint(&self) -> CommandResult<()> {
+        self.with_conn(|conn| {
+            conn.execute_batch("PRAGMA optimize")?;
+            conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+        })
+    }
+
+    pub fn with_conn<T, F>(&self, f: F) -> CommandResult<T>
+    where
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T>,
+    {
+        let conn = match self.conn.get() {
+            None => return Err(CommandError::NotReady("database".to_string())),
+            Some(Err(e)) => return Err(CommandError::Internal(format!("database failed to open: {}", e))),
+            Some(Ok(conn)) => conn,
+        };
+        let mut conn = conn.lock().unwrap();
+        f(&mut conn).map_err(CommandError::from)
+    }
+}
+
+fn migrate(conn: &mut Connection) -> rusqlite::Result<()> {
+    let applied: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (i, sql) in MIGRATIONS.iter().enumerate().skip(applied.max(0) as usize) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", (i + 1) as i64)?;
+        tx.commit()?;
+        println!("[Halbert] Applied database migration {}", i + 1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_memory_runs_every_migration_once() {
+        let db = Database::in_memory();
+        let version: i64 = db
+            .with_conn(|conn| conn.pragma_query_value(None, "user_version", |row| row.get(0)))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len() as i64);
+        db.with_conn(migrate).unwrap();
+        let sql = "SELECT COUNT(*) FROM sqlite_master WHERE name = 'audit_log'";
+        let tables: i64 = db.with_conn(|conn| conn.query_row(sql, [], |row| row.get(0))).unwrap();
+        assert_eq!(tables, 1);
+    }
+
+    #[test]
+    fn queries_before_open_are_not_ready() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(&dir.path().join("halbert.db"));
+        assert!(matches!(db.with_conn(|_| Ok(())), Err(CommandError::NotReady(_))));
+        db.open().unwrap();
+        db.with_conn(|_| Ok(())).unwrap();
+    }
+
+    type Query = fn(&Database) -> CommandResult<()>;
+
+    #[test]
+    fn metric_queries_fail_fast_during_a_slow_migration() {
+        let db = std::sync::Arc::new(Database::new(Path::new(":memory:")));
+        let (started, migrating) = std::sync::mpsc::channel();
+        let opening = {
+            let db = db.clone();
+            std::thread::spawn(move || {
+                let connected = (|| {
+                    let mut conn = Connection::open_in_memory()?;
+                    migrate(&mut conn)?;
+                    started.send(()).unwrap();
+                    // The rest of a migration that takes its time
+                    std::thread::sleep(std::time::Duration::from_millis(800));
+                    Ok(conn)
+                })();
+                db.set_opened(connected)
+            })
+        };
+        migrating.recv().unwrap();
+
+        // What get_backend_performance (24h) and the usage rollup query
+        let queries: [(&str, Query); 2] = [
+            ("backend performance", |db| crate::backend_latency::histograms(db, crate::backend_latency::Window::Day).map(drop)),
+            ("usage rollup", |db| crate::usage_summary::rollup(db).map(drop)),
+        ];
+        for (name, query) in queries {
+            let asked = std::time::Instant::now();
+            let result = query(&db);
+            assert!(asked.elapsed() < std::time::Duration::from_millis(100), "{} waited", name);
+            assert!(matches!(result, Err(CommandError::NotReady(ref what)) if what == "database"), "{}", name);
+        }
+        assert!(!opening.is_finished());
+
+        opening.join().unwrap().unwrap();
+        for (name, query) in queries {
+            query(&db).unwrap_or_else(|e| panic!("{} after open: {}", name, e));
+        }
+    }
+}
@@ -0,0 +1,468 @@
+// Desktop notifications, and when to hold them back.
+//
+// Every event the webhooks get also comes here, from the notification
+// worker. Events listed in settings show as a desktop notification, with
+// the notification's sound hint when the event is also in `sound_events`.
+// During quiet hours (in the configured timezone) or while the desktop's
+// do-not-disturb is on, only "critical" ones show; the rest are held and
+// delivered as one summary, e.g. "3 alerts and 1 failed job while you were
+// away", once neither applies. Do-not-disturb is read from the
+// org.freedesktop.Notifications Inhibited property, then GNOME's
+// show-banners setting, through gdbus and gsettings; a desktop that offers
+// neither counts as not disturbed. Deciding, holding and summarizing are
+// plain functions; showing goes through a NotificationSink.
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+#[cfg(feature = "gui")]
+use tauri_plugin_notification::NotificationExt;
+
+#[cfg(feature = "gui")]
+use crate::app::{AppHandle, Manager};
+use crate::app::State;
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::settings::{Settings, SettingsStore};
+use crate::timestamps::{self, Zone};
+
+// Freedesktop sound theme name
+const SOUND: &str = "message-new-instant";
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QuietHours {
+    // "HH:MM"; a window past midnight ends the next day
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DesktopNotifySettings {
+    pub enabled: bool,
+    // Events shown on the desktop (see notifications::EVENTS)
+    pub events: Vec<String>,
+    // Of those, the ones that play a sound
+    pub sound_events: Vec<String>,
+    pub quiet_hours: Option<QuietHours>,
+}
+
+impl Default for DesktopNotifySettings {
+    fn default() -> Self {
+        DesktopNotifySettings {
+            enabled: true,
+            events: [
+                "alert_triggered",
+                "job_failed",
+                "approval_new",
+                "approval_execution_failed",
+                "incident_oom_kill",
+                "incident_segfault",
+                "incident_unit_crash",
+                "hook_disabled",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+            sound_events: vec!["alert_triggered".to_string()],
+            quiet_hours: None,
+        }
+    }
+}
+
+pub fn parse_clock(text: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(text.trim(), "%H:%M").ok()
+}
+
+impl QuietHours {
+    pub fn window(&self) -> CommandResult<(NaiveTime, NaiveTime)> {
+        match (parse_clock(&self.start), parse_clock(&self.end)) {
+            (Some(start), Some(end)) => Ok((start, end)),
+            _ => Err(CommandError::InvalidInput(format!(
+                "quiet hours {}-{} should be two HH:MM times",
+                self.start, self.end
+            ))),
+        }
+    }
+}
+
+// Whether `now` falls in [start, end); equal ends make an empty window
+pub fn in_window(start: NaiveTime, end: NaiveTime, now: NaiveTime) -> bool {
+    if start <= end {
+        start <= now && now < end
+    } else {
+        now >= start || now < end
+    }
+}
+
+// Alerts carry their rule's severity; failures are warnings, the rest info
+pub fn severity(event: &str, payload: &Value) -> String {
+    match event {
+        "alert_triggered" => payload
+            .get("severity")
+            .and_then(Value::as_str)
+            .unwrap_or("warning")
+            .to_string(),
+        "job_failed" | "approval_execution_failed" | "hook_disabled" => "warning".to_string(),
+        e if e.starts_with("incident_") => "warning".to_string(),
+        _ => "info".to_string(),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Delivery {
+    Show { sound: bool },
+    Hold,
+    Skip,
+}
+
+pub fn decide(settings: &DesktopNotifySettings, event: &str, severity: &str, quiet: bool, dnd: bool) -> Delivery {
+    if !settings.enabled || !settings.events.iter().any(|e| e == event) {
+        return Delivery::Skip;
+    }
+    if severity != "critical" && (quiet || dnd) {
+        return Delivery::Hold;
+    }
+    Delivery::Show {
+        sound: settings.sound_events.iter().any(|e| e == event),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Held {
+    pub event: String,
+    pub title: String,
+}
+
+fn nouns(event: &str) -> (&'static str, &'static str) {
+    match event {
+        "alert_triggered" => ("alert", "alerts"),
+        "job_failed" => ("failed job", "failed jobs"),
+        "job_completed" => ("finished job", "finished jobs"),
+        "approval_new" => ("approval request", "approval requests"),
+        "approval_decided" => ("decided request", "decided requests"),
+        "approval_execution_failed" => ("approved request that didn't run", "approved requests that didn't run"),
+        "hook_disabled" => ("disabled hook", "disabled hooks"),
+        e if e.starts_with("incident_") => ("incident", "incidents"),
+        _ => ("notification", "notifications"),
+    }
+}
+
+// "3 alerts and 1 failed job while you were away", most frequent first
+pub fn summary(held: &[Held]) -> Option<(String, String)> {
+    if held.is_empty() {
+        return None;
+    }
+    let mut counts: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+    for item in held {
+        *counts.entry(nouns(&item.event)).or_default() += 1;
+    }
+    let mut counts: Vec<((&str, &str), usize)> = counts.into_iter().collect();
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    let parts: Vec<String> = counts
+        .iter()
+        .map(|((one, many), n)| format!("{} {}", n, if *n == 1 { one } else { many }))
+        .collect();
+    let listed = match parts.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+        None => String::new(),
+    };
+    let title = format!("{} notification(s) held", held.len());
+    Some((title, format!("{} while you were away", listed)))
+}
+
+// `(<true>,)` from gdbus' Properties.Get
+pub fn parse_gdbus_bool(output: &str) -> Option<bool> {
+    let value = output.trim().trim_start_matches('(').trim_end_matches(')').trim_end_matches(',');
+    match value.trim_start_matches('<').trim_end_matches('>') {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+pub fn parse_gsettings_bool(output: &str) -> Option<bool> {
+    match output.trim() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn do_not_disturb() -> bool {
+    let inhibited = exec::stdout(
+        "gdbus",
+        &[
+            "call",
+            "--session",
+            "--dest",
+            "org.freedesktop.Notifications",
+            "--object-path",
+            "/org/freedesktop/Notifications",
+            "--method",
+            "org.freedesktop.DBus.Properties.Get",
+            "org.freedesktop.Notifications",
+            "Inhibited",
+        ],
+    )
+    .and_then(|out| parse_gdbus_bool(&out));
+    if let Some(inhibited) = inhibited {
+        return inhibited;
+    }
+    exec::stdout("gsettings", &["get", "org.gnome.desktop.notifications", "show-banners"])
+        .and_then(|out| parse_gsettings_bool(&out))
+        .is_some_and(|banners| !banners)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn do_not_disturb() -> bool {
+    false
+}
+
+fn quiet_now(settings: &Settings) -> bool {
+    let Some(window) = settings.desktop_notifications.quiet_hours.as_ref().and_then(|q| q.window().ok()) else {
+        return false;
+    };
+    let zone = timestamps::parse_zone(&settings.timezone).unwrap_or(Zone::Local);
+    in_window(window.0, window.1, zone.naive(chrono::Utc::now()).time())
+}
+
+pub trait NotificationSink {
+    fn show(&self, title: &str, body: &str, sound: bool);
+}
+
+#[cfg(feature = "gui")]
+struct DesktopSink<'a>(&'a AppHandle);
+
+#[cfg(feature = "gui")]
+impl NotificationSink for DesktopSink<'_> {
+    fn show(&self, title: &str, body: &str, sound: bool) {
+        let mut builder = self.0.notification().builder().title(title).body(body);
+        if sound {
+            builder = builder.sound(SOUND);
+        }
+        if let Err(e) = builder.show() {
+            println!("[Halbert] Desktop notification failed: {}", e);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DesktopNotifier {
+    held: Mutex<Vec<Held>>,
+}
+
+impl DesktopNotifier {
+    pub fn offer(&self, sink: &dyn NotificationSink, delivery: Delivery, event: &str, title: &str, body: &str) {
+        match delivery {
+            Delivery::Show { sound } => sink.show(title, body, sound),
+            Delivery::Hold => self.held.lock().unwrap().push(Held {
+                event: event.to_string(),
+                title: title.to_string(),
+            }),
+            Delivery::Skip => {}
+        }
+    }
+
+    // Shows the summary of everything held, once nothing holds it back
+    pub fn flush(&self, sink: &dyn NotificationSink, quiet: bool, dnd: bool) {
+        if quiet || dnd {
+            return;
+        }
+        let held = std::mem::take(&mut *self.held.lock().unwrap());
+        if let Some((title, body)) = summary(&held) {
+            sink.show(&title, &body, false);
+        }
+    }
+
+    fn held_count(&self) -> usize {
+        self.held.lock().unwrap().len()
+    }
+}
+
+// Called from the notification worker for every event
+#[cfg(feature = "gui")]
+pub fn notify(app: &AppHandle, event: &str, title: &str, body: &str, payload: &Value) {
+    let Some(notifier) = app.try_state::<DesktopNotifier>() else {
+        return;
+    };
+    let settings = app.state::<SettingsStore>().get();
+    let severity = severity(event, payload);
+    let quiet = quiet_now(&settings);
+    // Only worth a lookup when it could change the outcome
+    let dnd = !quiet && severity != "critical" && do_not_disturb();
+    let delivery = decide(&settings.desktop_notifications, event, &severity, quiet, dnd);
+    notifier.offer(&DesktopSink(app), delivery, event, title, body);
+}
+
+#[cfg(feature = "gui")]
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(FLUSH_INTERVAL);
+        let notifier = app.state::<DesktopNotifier>();
+        if notifier.held_count() == 0 {
+            continue;
+        }
+        let quiet = quiet_now(&app.state::<SettingsStore>().get());
+        let dnd = !quiet && do_not_disturb();
+        notifier.flush(&DesktopSink(&app), quiet, dnd);
+    });
+}
+
+#[derive(Serialize)]
+pub struct DesktopNotifyStatus {
+    pub enabled: bool,
+    pub quiet_hours_now: bool,
+    pub do_not_disturb: bool,
+    pub held: usize,
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_desktop_notify_status(
+    settings: State<'_, SettingsStore>,
+    notifier: State<'_, DesktopNotifier>,
+) -> DesktopNotifyStatus {
+    let settings = settings.get();
+    DesktopNotifyStatus {
+        enabled: settings.desktop_notifications.enabled,
+        quiet_hours_now: quiet_now(&settings),
+        do_not_disturb: do_not_disturb(),
+        held: notifier.held_count(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct Recorder(RefCell<Vec<(String, String, bool)>>);
+
+    impl NotificationSink for Recorder {
+        fn show(&self, title: &str, body: &str, sound: bool) {
+            self.0.borrow_mut().push((title.to_string(), body.to_string(), sound));
+        }
+    }
+
+    fn at(text: &str) -> NaiveTime {
+        parse_clock(text).unwrap()
+    }
+
+    #[test]
+    fn windows_within_a_day_and_across_midnight() {
+        assert!(in_window(at("09:00"), at("17:00"), at("09:00")));
+        assert!(in_window(at("09:00"), at("17:00"), at("16:59")));
+        assert!(!in_window(at("09:00"), at("17:00"), at("17:00")));
+        assert!(!in_window(at("09:00"), at("17:00"), at("08:59")));
+
+        // 22:00-07:00 ends the next morning
+        assert!(in_window(at("22:00"), at("07:00"), at("22:00")));
+        assert!(in_window(at("22:00"), at("07:00"), at("23:59")));
+        assert!(in_window(at("22:00"), at("07:00"), at("00:00")));
+        assert!(in_window(at("22:00"), at("07:00"), at("06:59")));
+        assert!(!in_window(at("22:00"), at("07:00"), at("07:00")));
+        assert!(!in_window(at("22:00"), at("07:00"), at("12:00")));
+
+        assert!(!in_window(at("08:00"), at("08:00"), at("08:00")));
+    }
+
+    #[test]
+    fn quiet_hours_must_be_two_clock_times() {
+        let window = QuietHours { start: "22:30".to_string(), end: " 06:15".to_string() };
+        assert_eq!(window.window().unwrap(), (at("22:30"), at("06:15")));
+        for (start, end) in [("22", "06:00"), ("10pm", "06:00"), ("22:00", "25:00")] {
+            let window = QuietHours { start: start.to_string(), end: end.to_string() };
+            assert!(matches!(window.window(), Err(CommandError::InvalidInput(_))), "{}-{}", start, end);
+        }
+    }
+
+    #[test]
+    fn only_critical_gets_through_quiet_hours_and_do_not_disturb() {
+        let settings = DesktopNotifySettings::default();
+        assert_eq!(decide(&settings, "alert_triggered", "warning", false, false), Delivery::Show { sound: true });
+        assert_eq!(decide(&settings, "job_failed", "warning", false, false), Delivery::Show { sound: false });
+        assert_eq!(decide(&settings, "job_failed", "warning", true, false), Delivery::Hold);
+        assert_eq!(decide(&settings, "job_failed", "warning", false, true), Delivery::Hold);
+        assert_eq!(decide(&settings, "alert_triggered", "critical", true, true), Delivery::Show { sound: true });
+        // Not a listed event, or switched off
+        assert_eq!(decide(&settings, "job_completed", "info", false, false), Delivery::Skip);
+        let off = DesktopNotifySettings { enabled: false, ..settings };
+        assert_eq!(decide(&off, "alert_triggered", "critical", false, false), Delivery::Skip);
+    }
+
+    #[test]
+    fn severity_comes_from_the_alert_or_the_event() {
+        assert_eq!(severity("alert_triggered", &json!({"severity": "critical"})), "critical");
+        assert_eq!(severity("alert_triggered", &json!({})), "warning");
+        assert_eq!(severity("incident_oom_kill", &json!({})), "warning");
+        assert_eq!(severity("job_completed", &json!({})), "info");
+    }
+
+    #[test]
+    fn held_notifications_collapse_into_one_summary() {
+        let held = |event: &str| Held { event: event.to_string(), title: String::new() };
+        assert_eq!(summary(&[]), None);
+        assert_eq!(
+            summary(&[held("job_failed")]).unwrap(),
+            ("1 notification(s) held".to_string(), "1 failed job while you were away".to_string())
+        );
+        let (title, body) = summary(&[
+            held("job_failed"),
+            held("alert_triggered"),
+            held("incident_segfault"),
+            held("alert_triggered"),
+            held("incident_oom_kill"),
+            held("alert_triggered"),
+        ])
+        .unwrap();
+        assert_eq!(title, "6 notification(s) held");
+        assert_eq!(body, "3 alerts, 2 incidents and 1 failed job while you were away");
+    }
+
+    #[test]
+    fn held_ones_wait_for_the_window_to_end() {
+        let notifier = DesktopNotifier::default();
+        let sink = Recorder::default();
+        notifier.offer(&sink, Delivery::Hold, "alert_triggered", "Disk full", "");
+        notifier.offer(&sink, Delivery::Hold, "job_failed", "Backup failed", "");
+        notifier.offer(&sink, Delivery::Skip, "job_completed", "Done", "");
+        notifier.offer(&sink, Delivery::Show { sound: true }, "alert_triggered", "CPU on fire", "critical");
+        assert_eq!(*sink.0.borrow(), [("CPU on fire".to_string(), "critical".to_string(), true)]);
+        assert_eq!(notifier.held_count(), 2);
+
+        // Still quiet, then do-not-disturb: nothing released
+        notifier.flush(&sink, true, false);
+        notifier.flush(&sink, false, true);
+        assert_eq!(sink.0.borrow().len(), 1);
+
+        notifier.flush(&sink, false, false);
+        assert_eq!(notifier.held_count(), 0);
+        assert_eq!(
+            sink.0.borrow()[1],
+            (
+                "2 notification(s) held".to_string(),
+                "1 alert and 1 failed job while you were away".to_string(),
+                false
+            )
+        );
+        // Nothing left, so nothing more to show
+        notifier.flush(&sink, false, false);
+        assert_eq!(sink.0.borrow().len(), 2);
+    }
+
+    #[test]
+    fn gdbus_and_gsettings_booleans() {
+        assert_eq!(parse_gdbus_bool("(<true>,)\n"), Some(true));
+        assert_eq!(parse_gdbus_bool("(<false>,)"), Some(false));
+        assert_eq!(parse_gdbus_bool("Error: no such property"), None);
+        assert_eq!(parse_gsettings_bool("false\n"), Some(false));
+        assert_eq!(parse_gsettings_bool("'maybe'"), None);
+    }
+}
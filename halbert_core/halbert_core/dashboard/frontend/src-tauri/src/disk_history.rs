@@ -0,0 +1,182 @@
+// Per-mount disk usage history and days-until-full projection.
+//
+// Samples are taken every 10 minutes from the local host and kept in
+// SQLite for RETENTION_DAYS. The projection is a least-squares line
+// through the used space; mounts with too few samples (a USB drive that
+// was plugged in for an afternoon) get no projection at all.
+use rusqlite::params;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::app::{AppHandle, Manager, State};
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::settings::SettingsStore;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const RETENTION_DAYS: u32 = 90;
+// Window the dashboard's days_until_full is projected from
+const DASHBOARD_WINDOW_DAYS: u32 = 7;
+const MIN_SAMPLES: usize = 12;
+// Below this growth usage counts as flat
+const MIN_GROWTH_GB_PER_DAY: f64 = 0.001;
+
+#[derive(Serialize, Clone)]
+pub struct DiskSample {
+    pub at: String,
+    pub used_gb: f64,
+    pub total_gb: f64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct DiskProjection {
+    pub growth_gb_per_day: f64,
+    // None when usage is flat or shrinking
+    pub days_until_full: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct DiskTrend {
+    pub mount_point: String,
+    pub days: u32,
+    pub samples: Vec<DiskSample>,
+    // None until the mount has MIN_SAMPLES in the window
+    pub projection: Option<DiskProjection>,
+}
+
+// Latest dashboard projection per mount point, refreshed after each sample
+#[derive(Default)]
+pub struct DiskHistory {
+    days_until_full: RwLock<HashMap<String, f64>>,
+}
+
+impl DiskHistory {
+    pub fn days_until_full(&self, mount_point: &str) -> Option<f64> {
+        self.days_until_full.read().unwrap().get(mount_point).copied()
+    }
+
+    // Fill in DiskInfo.days_until_full for local metrics
+    pub fn annotate(&self, metrics: &mut crate::SystemMetrics) {
+        for disk in &mut metrics.disks {
+            disk.days_until_full = self.days_until_full(&disk.mount_point);
+        }
+    }
+}
+
+// (unix seconds, used_gb, total_gb) ordered by time
+fn load_samples(db: &Database, mount_point: &str, days: u32) -> CommandResult<Vec<(i64, f64, f64)>> {
+    let since = chrono::Utc::now().timestamp() - days as i64 * 86_400;
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT at, used_gb, total_gb FROM disk_usage_samples
+             WHERE mount_point = ?1 AND at >= ?2 ORDER BY at",
+        )?;
+        let rows = stmt.query_map(params![mount_point, since], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?;
+        rows.collect()
+    })
+}
+
+fn project(samples: &[(i64, f64, f64)]) -> Option<DiskProjection> {
+    if samples.len() < MIN_SAMPLES {
+        return None;
+    }
+    // Fit used = a + b * day, with days relative to the first sample
+    let t0 = samples[0].0;
+    let n = samples.len() as f64;
+    let xs: Vec<f64> = samples.iter().map(|s| (s.0 - t0) as f64 / 86_400.0).collect();
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = samples.iter().map(|s| s.1).sum::<f64>() / n;
+    let (mut sxy, mut sxx) = (0.0, 0.0);
+    for (x, s) in xs.iter().zip(samples) {
+        sxy += (x - mean_x) * (s.1 - mean_y);
+        sxx += (x - mean_x) * (x - mean_x);
+    }
+    if sxx == 0.0 {
+        return None;
+    }
+    let slope = sxy / sxx;
+
+    let (_, used, total) = samples[samples.len() - 1];
+    let days_until_full = if slope > MIN_GROWTH_GB_PER_DAY {
+        Some(((total - used) / slope).max(0.0))
+    } else {
+        None
+    };
+    Some(DiskProjection {
+        growth_gb_per_day: slope,
+        days_until_full,
+    })
+}
+
+fn record(app: &AppHandle) -> CommandResult<()> {
+    let db = app.state::<Database>();
+    let at = chrono::Utc::now().timestamp();
+    let disks = crate::local_system_metrics(app.state::<SettingsStore>().get().units).disks;
+
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        for disk in &disks {
+            tx.execute(
+                "INSERT INTO disk_usage_samples (mount_point, at, used_gb, total_gb) VALUES (?1, ?2, ?3, ?4)",
+                params![disk.mount_point, at, disk.used_gb as f64, disk.total_gb as f64],
+            )?;
+        }
+        tx.execute(
+            "DELETE FROM disk_usage_samples WHERE at < ?1",
+            params![at - RETENTION_DAYS as i64 * 86_400],
+        )?;
+        tx.commit()
+    })?;
+
+    let mut projections = HashMap::new();
+    for disk in &disks {
+        let samples = load_samples(&db, &disk.mount_point, DASHBOARD_WINDOW_DAYS)?;
+        if let Some(days) = project(&samples).and_then(|p| p.days_until_full) {
+            projections.insert(disk.mount_point.clone(), days);
+        }
+    }
+    *app.state::<DiskHistory>().days_until_full.write().unwrap() = projections;
+    Ok(())
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        if let Err(e) = record(&app) {
+            println!("[Halbert] Disk usage sample failed: {}", e);
+        }
+        let backoff = app.state::<crate::selfusage::SelfLimiter>().interval_factor();
+        std::thread::sleep(SAMPLE_INTERVAL * backoff as u32);
+    });
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_disk_trend(db: State<'_, Database>, mount_point: String, days: u32) -> CommandResult<DiskTrend> {
+    if days == 0 || days > RETENTION_DAYS {
+        return Err(CommandError::InvalidInput(format!(
+            "days must be between 1 and {}",
+            RETENTION_DAYS
+        )));
+    }
+    let rows = load_samples(&db, &mount_point, days)?;
+    let projection = project(&rows);
+    let samples = rows
+        .iter()
+        .map(|&(at, used_gb, total_gb)| DiskSample {
+            at: chrono::DateTime::from_timestamp(at, 0)
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_default(),
+            used_gb,
+            total_gb,
+        })
+        .collect();
+    Ok(DiskTrend {
+        mount_point,
+        days,
+        samples,
+        projection,
+    })
+}
@@ -0,0 +1,712 @@
+// RAG corpus documents and their tags.
+//
+// Documents are the text files under a corpus root (minus `corpus_ignore`
+// matches); ids are derived from the path relative to the root, and for a
+// corpus other than the primary one its name too (see corpora). Without a
+// primary `corpus_path` the mock list is served for UI development. Tags
+// are keyed by corpus and relative source path so they survive reindexing.
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::app::{AppHandle, Emitter, Manager, State};
+use crate::backend;
+use crate::cancellation::CancellationToken;
+use crate::corpora;
+use crate::corpus_watch::CorpusWatcher;
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::hosts::{self, ActiveHost};
+use crate::http::BackendApi;
+use crate::ratelimit::{self, RateLimiter, Throttled};
+use crate::reindex::Reindexer;
+use crate::retrieval_feedback::{self, RetrievedChunk};
+use crate::sandbox;
+use crate::scrape;
+use crate::settings::{Settings, SettingsStore};
+use crate::timestamps::{self, Clock, Localize, TimeDisplay};
+use crate::transfers::{self, MaybeTransfer, TransferStore};
+use crate::units::Units;
+
+const MAX_TAGS_PER_DOCUMENT: usize = 32;
+const MAX_TAG_LEN: usize = 40;
+const DEFAULT_SEARCH_LIMIT: u32 = 10;
+// How much of each file is read to find a title
+const TITLE_SCAN_BYTES: u64 = 4096;
+const CORPUS_EXTENSIONS: &[&str] = &["md", "markdown", "txt", "rst", "man"];
+const MAX_CONTENT_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Document {
+    #[serde(default)]
+    pub host_id: String,
+    #[serde(default)]
+    pub corpus: String,
+    pub id: String,
+    pub title: String,
+    pub source: String,
+    pub doc_type: String,
+    pub chunk_count: u32,
+    pub indexed_at: String,
+    // KiB or kB, per the units setting
+    pub size_kb: f32,
+    #[serde(default)]
+    pub size_bytes: u64,
+    #[serde(default)]
+    pub size_display: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Filled in per response; see timestamps
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub indexed_at_display: Option<TimeDisplay>,
+}
+
+impl Localize for Document {
+    fn localize(&mut self, clock: &Clock) {
+        self.indexed_at_display = clock.display(&self.indexed_at);
+    }
+}
+
+#[derive(Serialize)]
+pub struct DocumentList {
+    pub documents: Vec<Document>,
+}
+
+#[derive(Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub document_count: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SearchHit {
+    // The backend's chunk id, or one derived from source and snippet
+    #[serde(default)]
+    pub chunk_id: String,
+    pub source: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub snippet: String,
+    #[serde(default)]
+    pub score: f32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    results: Vec<SearchHit>,
+}
+
+#[derive(Serialize)]
+pub struct SearchResults {
+    // For submit_retrieval_feedback
+    pub query_id: String,
+    pub corpus: String,
+    pub results: Vec<SearchHit>,
+}
+
+// --- Corpus ---
+
+// The primary corpus; imports, scrapes and synced sources land there
+pub fn corpus_root(settings: &Settings) -> CommandResult<PathBuf> {
+    corpora::root(settings, corpora::PRIMARY)
+}
+
+// Resolve a corpus-relative source to a real path, refusing anything that
+// escapes the corpus root (.., absolute paths, symlinks pointing outside)
+pub fn resolve_in_corpus(settings: &Settings, corpus: &str, source: &str) -> CommandResult<PathBuf> {
+    let root = corpora::root(settings, corpus)?;
+    let path = sandbox::CORPUS_DOCUMENT.validate(settings, &root.join(source))?.into_path_buf();
+    // The sandbox allows every corpus; this one has to hold it
+    if !path.starts_with(&root) {
+        return Err(CommandError::PermissionDenied(format!(
+            "{} is outside corpus {}",
+            source, corpus
+        )));
+    }
+    if !path.exists() {
+        return Err(CommandError::NotFound(format!("document {}", source)));
+    }
+    Ok(path)
+}
+
+pub fn is_ignored(settings: &Settings, relative: &Path) -> bool {
+    let relative = relative.to_string_lossy();
+    let name = relative.rsplit('/').next().unwrap_or(&relative);
+    settings.corpus_ignore.iter().any(|pattern| {
+        glob::Pattern::new(pattern)
+            .map(|p| p.matches(name) || p.matches(&relative))
+            .unwrap_or(false)
+    })
+}
+
+// Every non-ignored file under the corpus root, as (relative, absolute).
+// Hidden entries and symlinks are skipped.
+pub fn walk_corpus(settings: &Settings, root: &Path) -> Vec<(String, PathBuf)> {
+    walk_corpus_until(settings, root, &CancellationToken::default())
+}
+
+// As walk_corpus, stopping with what it has found once `token` is cancelled
+pub fn walk_corpus_until(settings: &Settings, root: &Path, token: &CancellationToken) -> Vec<(String, PathBuf)> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        if token.is_cancelled() {
+            break;
+        }
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(relative) = path.strip_prefix(root) else {
+                continue;
+            };
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if hidden || is_ignored(settings, relative) {
+                continue;
+            }
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => pending.push(path.clone()),
+                Ok(t) if t.is_file() => files.push((relative.to_string_lossy().into_owned(), path.clone())),
+                _ => {}
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+// Primary corpus ids are the ones from before there were several
+pub fn document_id(corpus: &str, source: &str) -> String {
+    let key = match corpus {
+        corpora::PRIMARY => source.to_string(),
+        _ => format!("{}:{}", corpus, source),
+    };
+    let digest = Sha256::digest(key.as_bytes());
+    let hex: String = digest.iter().take(6).map(|b| format!("{:02x}", b)).collect();
+    format!("doc_{}", hex)
+}
+
+pub fn doc_type_for(source: &str) -> Option<&'static str> {
+    let ext = source.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase())?;
+    if source.starts_with(&format!("{}/", scrape::HELP_DIR)) {
+        return Some("help");
+    }
+    if source.contains("/man/") || ext == "man" || ext.chars().all(|c| c.is_ascii_digit()) {
+        return Some("manpage");
+    }
+    if !CORPUS_EXTENSIONS.contains(&ext.as_str()) {
+        return None;
+    }
+    Some(match ext.as_str() {
+        "md" | "markdown" => "markdown",
+        _ => "text",
+    })
+}
+
+// First markdown heading, the command in a scraped page's header, else
+// the file stem
+pub fn document_title(path: &Path, doc_type: &str) -> String {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if !matches!(doc_type, "markdown" | "manpage" | "help") {
+        return stem;
+    }
+    let mut head = String::new();
+    if let Ok(file) = std::fs::File::open(path) {
+        let _ = file.take(TITLE_SCAN_BYTES).read_to_string(&mut head);
+    }
+    if doc_type != "markdown" {
+        let fields = scrape::parse_header(&head);
+        return match (fields.get("command"), fields.get("section")) {
+            (Some(command), Some(section)) => format!("man: {}({})", command, section),
+            (Some(command), None) => format!("{} --help", command),
+            _ => stem,
+        };
+    }
+    head.lines()
+        .find_map(|line| line.strip_prefix("# "))
+        .map(|title| title.trim().to_string())
+        .filter(|title| !title.is_empty())
+        .unwrap_or(stem)
+}
+
+fn scan_documents(settings: &Settings, corpus: &str, root: &Path) -> Vec<Document> {
+    walk_corpus(settings, root)
+        .into_iter()
+        .filter_map(|(source, path)| {
+            let doc_type = doc_type_for(&source)?;
+            let meta = std::fs::metadata(&path).ok()?;
+            let modified = meta
+                .modified()
+                .ok()
+                .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339())
+                .unwrap_or_default();
+            Some(Document {
+                host_id: hosts::LOCAL_HOST_ID.to_string(),
+                corpus: corpus.to_string(),
+                id: document_id(corpus, &source),
+                title: document_title(&path, doc_type),
+                doc_type: doc_type.to_string(),
+                // Chunking happens in the backend; unknown here
+                chunk_count: 0,
+                indexed_at: modified,
+                size_kb: settings.units.kb(meta.len()),
+                size_bytes: meta.len(),
+                size_display: settings.units.format_bytes(meta.len()),
+                source,
+                tags: Vec::new(),
+                indexed_at_display: None,
+            })
+        })
+        .collect()
+}
+
+// Local documents of one corpus with their tags attached
+pub fn local_documents(settings: &Settings, db: &Database, corpus: &str) -> CommandResult<Vec<Document>> {
+    let mut docs = match corpora::root(settings, corpus) {
+        Ok(root) => scan_documents(settings, corpus, &root),
+        Err(CommandError::NotSupported(_)) => mock_documents(settings.units),
+        Err(e) => return Err(e),
+    };
+    let tags = all_tags(db, corpus)?;
+    for doc in &mut docs {
+        doc.tags = tags.get(&doc.source).cloned().unwrap_or_default();
+    }
+    Ok(docs)
+}
+
+#[derive(Serialize, Clone)]
+pub struct CorpusChanged {
+    pub corpus: String,
+    // Corpus-relative sources written or removed
+    pub sources: Vec<String>,
+}
+
+// Announce files added to or removed from a corpus, drop the cached
+// document list so the next listing sees them, and queue them for
+// reindexing (see reindex). The corpus watcher notes them so it doesn't
+// report them a second time.
+pub fn corpus_changed(app: &AppHandle, corpus: &str, sources: Vec<String>) {
+    app.state::<RateLimiter>().invalidate("get_documents");
+    app.state::<CorpusWatcher>().note(corpus, &sources);
+    app.state::<Reindexer>().queue(corpus, &sources);
+    let corpus = corpus.to_string();
+    let _ = app.emit("corpus://changed", CorpusChanged { corpus, sources });
+}
+
+// Ids are unique across corpora, so every one is looked through
+pub fn find_document(settings: &Settings, db: &Database, doc_id: &str) -> CommandResult<Document> {
+    let mut names = corpora::names(settings);
+    if names.is_empty() {
+        // Only the mock list
+        names.push(corpora::PRIMARY.to_string());
+    }
+    for name in names {
+        let docs = match local_documents(settings, db, &name) {
+            Ok(docs) => docs,
+            // A corpus whose root has gone missing has nothing to find
+            Err(CommandError::NotFound(_)) => continue,
+            Err(e) => return Err(e),
+        };
+        if let Some(doc) = docs.into_iter().find(|d| d.id == doc_id) {
+            return Ok(doc);
+        }
+    }
+    Err(CommandError::NotFound(format!("document {}", doc_id)))
+}
+
+fn mock_documents(units: Units) -> Vec<Document> {
+    // Mock document list
+    let mock = |id: &str, title: &str, source: &str, doc_type: &str, chunk_count: u32, size_kb: f32| {
+        let size_bytes = (size_kb * 1024.0) as u64;
+        Document {
+            host_id: hosts::LOCAL_HOST_ID.to_string(),
+            corpus: corpora::PRIMARY.to_string(),
+            id: id.to_string(),
+            title: title.to_string(),
+            source: source.to_string(),
+            doc_type: doc_type.to_string(),
+            chunk_count,
+            indexed_at: chrono::Utc::now().to_rfc3339(),
+            size_kb: units.kb(size_bytes),
+            size_bytes,
+            size_display: units.format_bytes(size_bytes),
+            tags: Vec::new(),
+            indexed_at_display: None,
+        }
+    };
+
+    vec![
+        mock("doc_001", "Linux System Administration Guide", "docs/linux/sysadmin.md", "markdown", 87, 124.5),
+        mock("doc_002", "Rust Programming Best Practices", "docs/rust/best-practices.md", "markdown", 62, 89.2),
+        mock("doc_003", "Tauri Desktop Development", "docs/tauri/desktop.md", "markdown", 45, 67.8),
+        mock("doc_004", "man: systemctl (System Control)", "scraped/man/systemctl.txt", "manpage", 134, 234.1),
+        mock("doc_005", "Phase 8 UI/UX Design Spec", "docs/Phase8/ui-spec.md", "markdown", 56, 78.9),
+    ]
+}
+
+// --- Tags ---
+
+// Trimmed, lowercased, deduplicated; letters, digits and - _ . : only
+pub fn normalize_tags(tags: &[String]) -> CommandResult<Vec<String>> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        let valid_chars = tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'));
+        if tag.is_empty() || tag.len() > MAX_TAG_LEN || !valid_chars {
+            return Err(CommandError::InvalidInput(format!(
+                "invalid tag '{}': use up to {} letters, digits, '-', '_', '.' or ':'",
+                tag, MAX_TAG_LEN
+            )));
+        }
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    if normalized.len() > MAX_TAGS_PER_DOCUMENT {
+        return Err(CommandError::InvalidInput(format!(
+            "at most {} tags per document",
+            MAX_TAGS_PER_DOCUMENT
+        )));
+    }
+    normalized.sort();
+    Ok(normalized)
+}
+
+// One corpus's tags by source
+fn all_tags(db: &Database, corpus: &str) -> CommandResult<BTreeMap<String, Vec<String>>> {
+    db.with_conn(|conn| {
+        let mut stmt =
+            conn.prepare("SELECT source, tag FROM document_tags WHERE corpus = ?1 ORDER BY source, tag")?;
+        let rows = stmt.query_map([corpus], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        let mut tags: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for row in rows {
+            let (source, tag) = row?;
+            tags.entry(source).or_default().push(tag);
+        }
+        Ok(tags)
+    })
+}
+
+pub fn delete_tags(db: &Database, corpus: &str, source: &str) -> CommandResult<()> {
+    db.with_conn(|conn| {
+        conn.execute("DELETE FROM document_tags WHERE corpus = ?1 AND source = ?2", [corpus, source])
+    })?;
+    Ok(())
+}
+
+// AND semantics: a document must carry every requested tag
+fn has_all_tags(doc_tags: &[String], wanted: &[String]) -> bool {
+    wanted.iter().all(|t| doc_tags.contains(t))
+}
+
+fn tag_filter(tags: Option<Vec<String>>) -> CommandResult<Vec<String>> {
+    normalize_tags(&tags.unwrap_or_default())
+}
+
+// --- Commands ---
+
+// The scan is rate limited; the tag filter is applied to its result
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_documents(
+    settings: State<'_, SettingsStore>,
+    db: State<'_, Database>,
+    limiter: State<'_, RateLimiter>,
+    tags: Option<Vec<String>>,
+    corpus: Option<String>,
+) -> CommandResult<Throttled<DocumentList>> {
+    let wanted = tag_filter(tags)?;
+    let settings = settings.get();
+    let active = hosts::active_host(&settings);
+    // A remote host has its own corpora
+    let corpus = match &active {
+        ActiveHost::Local => corpora::pick(&settings, corpus)?,
+        ActiveHost::Remote(_) => match corpus {
+            Some(name) if !corpora::valid_name(&name) => {
+                return Err(CommandError::InvalidInput(format!("invalid corpus name '{}'", name)))
+            }
+            Some(name) => name,
+            None => corpora::PRIMARY.to_string(),
+        },
+    };
+    let path = corpora::configured_path(&settings, &corpus);
+    let key = format!("{:?} {} {:?} {:?}", settings.active_host, corpus, path, settings.units);
+    let min_interval = ratelimit::interval(&settings.rate_limits_ms, "get_documents");
+    let scan = limiter.call("get_documents", &key, min_interval, || match active {
+        ActiveHost::Local => local_documents(&settings, &db, &corpus),
+        ActiveHost::Remote(host) => hosts::fetch_documents(&host, &corpus),
+    })?;
+    let documents: Vec<Document> = scan.value.into_iter().filter(|d| has_all_tags(&d.tags, &wanted)).collect();
+    Ok(Throttled {
+        value: DocumentList {
+            documents: timestamps::localized(&settings, documents),
+        },
+        cached: scan.cached,
+        age_ms: scan.age_ms,
+    })
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn set_document_tags(
+    settings: State<'_, SettingsStore>,
+    db: State<'_, Database>,
+    limiter: State<'_, RateLimiter>,
+    doc_id: String,
+    tags: Vec<String>,
+) -> CommandResult<Document> {
+    let tags = normalize_tags(&tags)?;
+    let mut doc = find_document(&settings.get(), &db, &doc_id)?;
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM document_tags WHERE corpus = ?1 AND source = ?2",
+            [&doc.corpus, &doc.source],
+        )?;
+        for tag in &tags {
+            tx.execute(
+                "INSERT INTO document_tags (corpus, source, tag) VALUES (?1, ?2, ?3)",
+                params![doc.corpus, doc.source, tag],
+            )?;
+        }
+        tx.commit()
+    })?;
+    doc.tags = tags;
+    limiter.invalidate("get_documents");
+    Ok(doc)
+}
+
+// Without a corpus, counted across all of them
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_tags(db: State<'_, Database>, corpus: Option<String>) -> CommandResult<Vec<TagCount>> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT tag, COUNT(*) FROM document_tags WHERE ?1 IS NULL OR corpus = ?1
+             GROUP BY tag ORDER BY COUNT(*) DESC, tag",
+        )?;
+        let rows = stmt.query_map([&corpus], |row| {
+            Ok(TagCount {
+                tag: row.get(0)?,
+                document_count: row.get(1)?,
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+// Delete the file from the corpus along with its tags
+#[derive(Serialize)]
+pub struct DocumentContent {
+    pub doc_id: String,
+    pub doc_type: String,
+    pub size_bytes: u64,
+    pub content: String,
+}
+
+// The whole file, as a transfer when it's large (see transfers)
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_document_content(
+    settings: State<'_, SettingsStore>,
+    db: State<'_, Database>,
+    transfers: State<'_, TransferStore>,
+    doc_id: String,
+) -> CommandResult<MaybeTransfer<DocumentContent>> {
+    let settings = settings.get();
+    let doc = find_document(&settings, &db, &doc_id)?;
+    let path = resolve_in_corpus(&settings, &doc.corpus, &doc.source)?;
+    let size_bytes = std::fs::metadata(&path)?.len();
+    if size_bytes > MAX_CONTENT_BYTES {
+        return Err(CommandError::InvalidInput(format!(
+            "{} is {} bytes, more than the {} that can be loaded",
+            doc.source, size_bytes, MAX_CONTENT_BYTES
+        )));
+    }
+    let content = String::from_utf8_lossy(&std::fs::read(&path)?).into_owned();
+    transfers::respond(
+        &transfers,
+        DocumentContent {
+            doc_id: doc.id,
+            doc_type: doc.doc_type,
+            size_bytes,
+            content,
+        },
+    )
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn delete_document(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    db: State<'_, Database>,
+    doc_id: String,
+) -> CommandResult<()> {
+    let settings = settings.get();
+    let doc = find_document(&settings, &db, &doc_id)?;
+    let path = resolve_in_corpus(&settings, &doc.corpus, &doc.source)?;
+    std::fs::remove_file(&path)?;
+    delete_tags(&db, &doc.corpus, &doc.source)?;
+    println!("[Halbert] Deleted document {} ({})", doc.id, doc.source);
+    corpus_changed(&app, &doc.corpus, vec![doc.source]);
+    Ok(())
+}
+
+// The tag filter is sent to the backend as a metadata filter. Backends that
+// reject the filter get the plain query and the filter is applied here.
+// `tags` are the corpus's local tags by source.
+pub fn search(
+    api: &dyn BackendApi,
+    tags: &BTreeMap<String, Vec<String>>,
+    corpus: &str,
+    query: &str,
+    wanted: &[String],
+    limit: u32,
+) -> CommandResult<Vec<SearchHit>> {
+    let post = |body: &Value| -> CommandResult<SearchResponse> {
+        serde_json::from_value(api.post_value("/api/rag/search", body)?)
+            .map_err(|e| CommandError::Remote(format!("backend sent an unexpected search response: {}", e)))
+    };
+    let mut body = json!({ "query": query, "limit": limit, "corpus": corpus });
+    if !wanted.is_empty() {
+        body["filter"] = json!({ "tags": wanted });
+    }
+    let response = match post(&body) {
+        Err(CommandError::Remote(msg)) if !wanted.is_empty() => {
+            println!("[Halbert] Backend search rejected the tag filter ({}), filtering locally", msg);
+            if let Some(body) = body.as_object_mut() {
+                body.remove("filter");
+            }
+            // Over-fetch since some hits will be filtered out
+            body["limit"] = Value::from(limit * 5);
+            post(&body)?
+        }
+        other => other?,
+    };
+
+    let mut hits: Vec<SearchHit> = response
+        .results
+        .into_iter()
+        .map(|mut hit| {
+            hit.tags = tags.get(&hit.source).cloned().unwrap_or_default();
+            hit.chunk_id = retrieval_feedback::chunk_id(Some(&hit.chunk_id), &hit.source, &hit.snippet);
+            hit
+        })
+        .filter(|hit| has_all_tags(&hit.tags, wanted))
+        .collect();
+    hits.truncate(limit as usize);
+    Ok(hits)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn search_documents(
+    settings: State<'_, SettingsStore>,
+    db: State<'_, Database>,
+    query: String,
+    tags: Option<Vec<String>>,
+    limit: Option<u32>,
+    corpus: Option<String>,
+) -> CommandResult<SearchResults> {
+    let query = query.trim().to_string();
+    if query.is_empty() {
+        return Err(CommandError::InvalidInput("query is empty".to_string()));
+    }
+    let wanted = tag_filter(tags)?;
+    let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, 100);
+    let settings = settings.get();
+    let corpus = corpora::pick(&settings, corpus)?;
+    let tags = all_tags(&db, &corpus)?;
+    let results = search(&backend::endpoint(&settings), &tags, &corpus, &query, &wanted, limit)?;
+    let chunks: Vec<RetrievedChunk> = results
+        .iter()
+        .map(|hit| RetrievedChunk {
+            chunk_id: hit.chunk_id.clone(),
+            corpus: corpus.clone(),
+            source: hit.source.clone(),
+        })
+        .collect();
+    let query_id = retrieval_feedback::record_query(&db, "search", &query, &chunks)?;
+    Ok(SearchResults {
+        query_id,
+        corpus,
+        results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{MockServer, StubBackend};
+
+    fn hit(source: &str) -> Value {
+        json!({ "chunk_id": format!("chunk_{}", source), "source": source, "snippet": "text", "score": 0.5 })
+    }
+
+    fn local_tags() -> BTreeMap<String, Vec<String>> {
+        BTreeMap::from([
+            ("a.md".to_string(), vec!["ops".to_string(), "linux".to_string()]),
+            ("b.md".to_string(), vec!["ops".to_string()]),
+        ])
+    }
+
+    #[test]
+    fn an_accepted_filter_is_sent_once() {
+        let stub = StubBackend::default().on("/api/rag/search", |_| Ok(json!({ "results": [hit("a.md")] })));
+        let hits = search(&stub, &local_tags(), "primary", "disk", &["ops".to_string()], 5).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].tags, ["ops", "linux"]);
+        let calls = stub.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].1["filter"], json!({ "tags": ["ops"] }));
+        assert_eq!(calls[0].1["corpus"], "primary");
+    }
+
+    #[test]
+    fn a_rejected_filter_falls_back_to_local_filtering() {
+        let stub = StubBackend::default().on("/api/rag/search", |body| {
+            if body.get("filter").is_some() {
+                return Err(CommandError::Remote("filters unsupported".to_string()));
+            }
+            Ok(json!({ "results": [hit("a.md"), hit("b.md"), hit("c.md")] }))
+        });
+        let wanted = ["linux".to_string()];
+        let hits = search(&stub, &local_tags(), "primary", "disk", &wanted, 2).unwrap();
+        let sources: Vec<&str> = hits.iter().map(|h| h.source.as_str()).collect();
+        assert_eq!(sources, ["a.md"]);
+        let calls = stub.calls();
+        assert_eq!(calls.len(), 2);
+        assert!(calls[1].1.get("filter").is_none());
+        assert_eq!(calls[1].1["limit"], 10);
+    }
+
+    #[test]
+    fn an_unreachable_backend_is_not_retried() {
+        let stub = StubBackend::default();
+        let result = search(&stub, &local_tags(), "primary", "disk", &["ops".to_string()], 5);
+        assert!(matches!(result, Err(CommandError::HostUnreachable(_))));
+        assert_eq!(stub.calls().len(), 1);
+    }
+
+    #[test]
+    fn fallback_works_end_to_end_over_http() {
+        let server = MockServer::start(|request| match request.body.get("filter") {
+            Some(_) => (422, json!({ "detail": "unknown field filter" })),
+            None => (200, json!({ "results": [hit("b.md"), { "source": "a.md", "snippet": "text" }] })),
+        });
+        let hits = search(&server.endpoint(), &local_tags(), "primary", "disk", &["ops".to_string()], 5).unwrap();
+        assert_eq!(hits.len(), 2);
+        // A hit without a backend chunk id gets one derived from its content
+        assert!(hits[1].chunk_id.starts_with("chunk_"));
+        let paths: Vec<String> = server.received().into_iter().map(|r| r.path).collect();
+        assert_eq!(paths, ["/api/rag/search", "/api/rag/search"]);
+    }
+}
@@ -0,0 +1,79 @@
+// Typed errors for Tauri commands.
+//
+// Serialized as `{ "kind": "...", "message": "..." }` so the frontend can
+// branch on `kind` instead of string-matching messages.
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "message")]
+pub enum CommandError {
+    InvalidInput(String),
+    NotFound(String),
+    // The target changed state since the request was made
+    Conflict(String),
+    PermissionDenied(String),
+    NotSupported(String),
+    // A feature needs setting up before it can be used
+    NotConfigured(String),
+    // An external program the command relies on isn't installed
+    ToolMissing(String),
+    // A remote host couldn't be reached at all
+    HostUnreachable(String),
+    // A remote host answered, but with an error or an unexpected payload
+    Remote(String),
+    // Halbert is in read-only mode and the command would change something
+    ReadOnlyMode(String),
+    // Automation is paused and the command would run a change now; resume first
+    AutomationPaused(String),
+    // A component named in the message is still starting up; try again shortly
+    NotReady(String),
+    // Requests named in the message have to be decided first
+    DependenciesPending(String),
+    // The command uses a capability the user hasn't decided on yet; see consent
+    ConsentRequired { capability: String, explanation: String },
+    // Stopped by cancel_operation; `partial` is what was done by then
+    Cancelled { message: String, partial: Option<serde_json::Value> },
+    Io(String),
+    Internal(String),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::InvalidInput(msg) => write!(f, "invalid input: {}", msg),
+            CommandError::NotFound(msg) => write!(f, "not found: {}", msg),
+            CommandError::Conflict(msg) => write!(f, "conflict: {}", msg),
+            CommandError::PermissionDenied(msg) => write!(f, "permission denied: {}", msg),
+            CommandError::NotSupported(msg) => write!(f, "not supported: {}", msg),
+            CommandError::NotConfigured(msg) => write!(f, "not configured: {}", msg),
+            CommandError::ToolMissing(msg) => write!(f, "tool missing: {}", msg),
+            CommandError::HostUnreachable(msg) => write!(f, "host unreachable: {}", msg),
+            CommandError::Remote(msg) => write!(f, "remote error: {}", msg),
+            CommandError::ReadOnlyMode(msg) => write!(f, "read-only mode: {}", msg),
+            CommandError::AutomationPaused(msg) => write!(f, "automation paused: {}", msg),
+            CommandError::NotReady(msg) => write!(f, "not ready yet: {}", msg),
+            CommandError::DependenciesPending(msg) => write!(f, "dependencies pending: {}", msg),
+            CommandError::ConsentRequired { explanation, .. } => write!(f, "consent required: {}", explanation),
+            CommandError::Cancelled { message, .. } => write!(f, "cancelled: {}", message),
+            CommandError::Io(msg) => write!(f, "io error: {}", msg),
+            CommandError::Internal(msg) => write!(f, "internal error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<std::io::Error> for CommandError {
+    fn from(e: std::io::Error) -> Self {
+        CommandError::Io(e.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for CommandError {
+    fn from(e: rusqlite::Error) -> Self {
+        CommandError::Internal(format!("database error: {}", e))
+    }
+}
+
+pub type CommandResult<T> = Result<T, CommandError>;
@@ -0,0 +1,53 @@
+// Helpers for shelling out to system tools.
+//
+// Arguments are always passed as an argv, never through a shell.
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::mpsc;
+use std::time::Instant;
+
+// Run a program and return its stdout if it exited successfully.
+// Missing binaries and non-zero exits both yield None so callers can
+// treat "tool not available" and "tool failed" the same way.
+pub fn stdout(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Run `work` on a helper thread and give up on it at the deadline. The
+// thread is left to finish on its own; its result is dropped.
+pub fn bounded<T, F>(deadline: Instant, work: F) -> Option<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(work());
+    });
+    rx.recv_timeout(deadline.saturating_duration_since(Instant::now())).ok()
+}
+
+// First executable named `program` on PATH
+pub fn find_in_path(program: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+pub fn is_executable(path: &std::path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+pub fn is_executable(path: &std::path::Path) -> bool {
+    path.is_file()
+}
@@ -0,0 +1,659 @@
+// Firewall posture (ufw, firewalld or raw nftables)
+//
+// ufw and firewalld are asked first since they usually sit on top of
+// nftables and their own view of the rules is the one the user wrote.
+// Without either, the nftables ruleset is read as JSON. Rules are reduced
+// to direction/proto/port/source/action; anything fancier is summarized
+// by its verdict only. Each rule says whether it covers IPv4, IPv6 or
+// both: ufw marks its IPv6 copies "(v6)", firewalld rich rules name a
+// family, and nftables rules take theirs from an ip/ip6 table or from an
+// ip/ip6 match inside an inet table. nftables base chains can also have
+// different policies per family, kept in `family_policies`.
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::exec;
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct FirewallRule {
+    // "in", "out" or "forward"
+    pub direction: String,
+    pub proto: Option<String>,
+    // Port, range ("8000:8100") or ufw/firewalld service name
+    pub port: Option<String>,
+    pub source: Option<String>,
+    // "allow", "deny", "reject", "limit" or "drop"
+    pub action: String,
+    // "ipv4" or "ipv6"; None when the rule covers both
+    pub family: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct FirewallStatus {
+    // "ufw", "firewalld", "nftables" or "none"
+    pub framework: String,
+    pub enabled: bool,
+    // Keyed by "incoming", "outgoing" and "routed"
+    pub default_policies: BTreeMap<String, String>,
+    pub rules: Vec<FirewallRule>,
+    // nftables only: "ipv4"/"ipv6" -> direction -> policy, for base chains
+    // in ip, ip6 and inet tables (inet counts for both)
+    pub family_policies: BTreeMap<String, BTreeMap<String, String>>,
+    // Set when the tool is installed but its state couldn't be read
+    pub detail: Option<String>,
+}
+
+impl FirewallStatus {
+    fn new(framework: &str) -> Self {
+        FirewallStatus {
+            framework: framework.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn unreadable(framework: &str, command: &str) -> Self {
+        FirewallStatus {
+            detail: Some(format!("`{}` failed; reading firewall state usually needs root", command)),
+            ..FirewallStatus::new(framework)
+        }
+    }
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_firewall_status() -> FirewallStatus {
+    // An installed but inactive framework is reported only if nothing is active
+    let mut inactive: Option<FirewallStatus> = None;
+
+    if exec::find_in_path("ufw").is_some() {
+        let status = match exec::stdout("ufw", &["status", "verbose"]) {
+            Some(out) => parse_ufw_status(&out),
+            None => FirewallStatus::unreadable("ufw", "ufw status verbose"),
+        };
+        if status.enabled {
+            return status;
+        }
+        inactive.get_or_insert(status);
+    }
+
+    if exec::find_in_path("firewall-cmd").is_some() {
+        let running = exec::stdout("firewall-cmd", &["--state"]).map(|s| s.trim() == "running");
+        if running == Some(true) {
+            return match exec::stdout("firewall-cmd", &["--list-all"]) {
+                Some(out) => parse_firewalld_list_all(&out),
+                None => FirewallStatus {
+                    enabled: true,
+                    ..FirewallStatus::unreadable("firewalld", "firewall-cmd --list-all")
+                },
+            };
+        }
+        inactive.get_or_insert(FirewallStatus::new("firewalld"));
+    }
+
+    if exec::find_in_path("nft").is_some() {
+        match exec::stdout("nft", &["-j", "list", "ruleset"]).map(|out| parse_nft_ruleset(&out)) {
+            Some(Ok(status)) if status.enabled => return status,
+            Some(Ok(status)) => {
+                inactive.get_or_insert(status);
+            }
+            Some(Err(e)) => {
+                inactive.get_or_insert(FirewallStatus {
+                    detail: Some(e),
+                    ..FirewallStatus::new("nftables")
+                });
+            }
+            None => {
+                inactive.get_or_insert(FirewallStatus::unreadable("nftables", "nft -j list ruleset"));
+            }
+        }
+    }
+
+    inactive.unwrap_or_else(|| FirewallStatus::new("none"))
+}
+
+// --- ufw ---
+
+// "deny (incoming), allow (outgoing), disabled (routed)"
+fn parse_ufw_defaults(text: &str) -> BTreeMap<String, String> {
+    text.split(',')
+        .filter_map(|part| {
+            let (policy, rest) = part.trim().split_once(' ')?;
+            let direction = rest.trim().trim_start_matches('(').trim_end_matches(')');
+            Some((direction.to_string(), policy.to_string()))
+        })
+        .collect()
+}
+
+fn strip_ufw_suffixes(text: &str) -> &str {
+    let text = text.trim().trim_end_matches("(v6)").trim();
+    text.split(" on ").next().unwrap_or(text).trim()
+}
+
+// "22/tcp", "80,443/tcp", "8000:8100/udp", "OpenSSH" or "Anywhere"
+fn parse_ufw_port(text: &str) -> (Option<String>, Option<String>) {
+    let text = strip_ufw_suffixes(text);
+    if text.is_empty() || text == "Anywhere" {
+        return (None, None);
+    }
+    match text.rsplit_once('/') {
+        Some((port, proto)) if proto == "tcp" || proto == "udp" => (Some(port.to_string()), Some(proto.to_string())),
+        _ => (Some(text.to_string()), None),
+    }
+}
+
+fn parse_ufw_source(text: &str) -> Option<String> {
+    Some(strip_ufw_suffixes(text))
+        .filter(|t| !t.is_empty() && *t != "Anywhere")
+        .map(|t| t.to_string())
+}
+
+// ufw lists every rule once per family and marks the IPv6 copy "(v6)";
+// a rule for one IPv6 source has the address instead
+fn ufw_family(to: &str, from: &str, source: Option<&str>) -> &'static str {
+    if to.contains("(v6)") || from.contains("(v6)") || source.is_some_and(|s| s.contains(':')) {
+        "ipv6"
+    } else {
+        "ipv4"
+    }
+}
+
+fn ufw_columns(line: &str) -> Vec<&str> {
+    // Columns are separated by runs of two or more spaces
+    line.split("  ").map(str::trim).filter(|c| !c.is_empty()).collect()
+}
+
+fn parse_ufw_status(output: &str) -> FirewallStatus {
+    let mut status = FirewallStatus::new("ufw");
+    let mut in_rules = false;
+
+    for line in output.lines() {
+        if let Some(state) = line.strip_prefix("Status:") {
+            status.enabled = state.trim() == "active";
+        } else if let Some(defaults) = line.strip_prefix("Default:") {
+            status.default_policies = parse_ufw_defaults(defaults);
+        } else if line.starts_with("--") {
+            in_rules = true;
+        } else if in_rules {
+            let columns = ufw_columns(line);
+            if columns.len() < 3 {
+                continue;
+            }
+            let mut action_words = columns[1].split_whitespace();
+            let action = action_words.next().unwrap_or_default().to_lowercase();
+            let direction = match action_words.next() {
+                Some("OUT") => "out",
+                Some("FWD") => "forward",
+                _ => "in",
+            };
+            let (port, proto) = parse_ufw_port(columns[0]);
+            let source = parse_ufw_source(columns[2]);
+            status.rules.push(FirewallRule {
+                direction: direction.to_string(),
+                family: Some(ufw_family(columns[0], columns[2], source.as_deref()).to_string()),
+                proto,
+                port,
+                source,
+                action,
+            });
+        }
+    }
+    status
+}
+
+// --- firewalld ---
+
+fn firewalld_target_policy(target: &str) -> &'static str {
+    match target {
+        "ACCEPT" => "allow",
+        "DROP" => "drop",
+        // "default" and "%%REJECT%%" both reject what no rule accepts
+        _ => "reject",
+    }
+}
+
+// rule family="ipv4" source address="10.0.0.0/8" port port="5432" protocol="tcp" accept
+fn parse_rich_rule(rule: &str) -> Option<FirewallRule> {
+    let mut values: BTreeMap<String, String> = BTreeMap::new();
+    let mut element = "";
+    for token in rule.split_whitespace() {
+        match token.split_once('=') {
+            Some((key, value)) => {
+                values.insert(format!("{}.{}", element, key), value.trim_matches('"').to_string());
+            }
+            None => element = token,
+        }
+    }
+    let action = match rule.split_whitespace().last()? {
+        "accept" => "allow",
+        "reject" => "reject",
+        "drop" => "drop",
+        _ => return None,
+    };
+    Some(FirewallRule {
+        direction: "in".to_string(),
+        proto: values.get("port.protocol").cloned(),
+        port: values
+            .get("port.port")
+            .or_else(|| values.get("service.name"))
+            .map(|p| p.replace('-', ":")),
+        source: values.get("source.address").cloned(),
+        action: action.to_string(),
+        family: values.get("rule.family").filter(|f| *f == "ipv4" || *f == "ipv6").cloned(),
+    })
+}
+
+fn parse_firewalld_list_all(output: &str) -> FirewallStatus {
+    let mut status = FirewallStatus::new("firewalld");
+    status.enabled = true;
+    status.default_policies.insert("outgoing".to_string(), "allow".to_string());
+
+    let mut fields: BTreeMap<&str, &str> = BTreeMap::new();
+    let mut rich_rules = Vec::new();
+    let mut in_rich_rules = false;
+    for line in output.lines().skip(1) {
+        let trimmed = line.trim();
+        if in_rich_rules && trimmed.starts_with("rule ") {
+            rich_rules.push(trimmed);
+            continue;
+        }
+        in_rich_rules = false;
+        if let Some((key, value)) = trimmed.split_once(':') {
+            if key == "rich rules" {
+                in_rich_rules = true;
+            }
+            fields.insert(key, value.trim());
+        }
+    }
+
+    let target = fields.get("target").copied().unwrap_or("default");
+    status
+        .default_policies
+        .insert("incoming".to_string(), firewalld_target_policy(target).to_string());
+
+    // Zone-wide sources restrict every service and port in the zone
+    let source = fields
+        .get("sources")
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string());
+    for service in fields.get("services").map(|s| s.split_whitespace()).into_iter().flatten() {
+        status.rules.push(FirewallRule {
+            direction: "in".to_string(),
+            proto: None,
+            port: Some(service.to_string()),
+            source: source.clone(),
+            action: "allow".to_string(),
+            family: None,
+        });
+    }
+    for port in fields.get("ports").map(|s| s.split_whitespace()).into_iter().flatten() {
+        let (port, proto) = match port.split_once('/') {
+            Some((port, proto)) => (port.replace('-', ":"), Some(proto.to_string())),
+            None => (port.to_string(), None),
+        };
+        status.rules.push(FirewallRule {
+            direction: "in".to_string(),
+            proto,
+            port: Some(port),
+            source: source.clone(),
+            action: "allow".to_string(),
+            family: None,
+        });
+    }
+    status.rules.extend(rich_rules.into_iter().filter_map(parse_rich_rule));
+    status
+}
+
+// --- nftables ---
+
+fn hook_direction(hook: &str) -> Option<(&'static str, &'static str)> {
+    match hook {
+        "input" => Some(("in", "incoming")),
+        "output" => Some(("out", "outgoing")),
+        "forward" => Some(("forward", "routed")),
+        _ => None,
+    }
+}
+
+fn nft_policy(policy: &str) -> String {
+    match policy {
+        "accept" => "allow".to_string(),
+        other => other.to_string(),
+    }
+}
+
+// Right-hand side of a match: 22, "10.0.0.0/8", {"set": [...]},
+// {"range": [a, b]} or {"prefix": {"addr": ..., "len": ...}}
+fn nft_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Number(n) => Some(n.to_string()),
+        Value::String(s) => Some(s.clone()),
+        Value::Object(obj) => {
+            if let Some(Value::Array(items)) = obj.get("set") {
+                let parts: Vec<String> = items.iter().filter_map(nft_value).collect();
+                Some(parts.join(","))
+            } else if let Some(Value::Array(range)) = obj.get("range") {
+                Some(format!("{}:{}", nft_value(range.first()?)?, nft_value(range.get(1)?)?))
+            } else if let Some(prefix) = obj.get("prefix") {
+                Some(format!("{}/{}", nft_value(prefix.get("addr")?)?, prefix.get("len")?))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+// "ip" and "ip6" tables hold one family; inet (and bridge/netdev) both
+fn nft_table_family(family: &str) -> Option<&'static str> {
+    match family {
+        "ip" => Some("ipv4"),
+        "ip6" => Some("ipv6"),
+        _ => None,
+    }
+}
+
+fn nft_rule(direction: &str, table_family: Option<&str>, exprs: &[Value]) -> Option<FirewallRule> {
+    let mut rule = FirewallRule {
+        direction: direction.to_string(),
+        family: table_family.map(str::to_string),
+        ..Default::default()
+    };
+    let mut limited = false;
+    for expr in exprs {
+        if let Some(m) = expr.get("match") {
+            // `meta nfproto ipv6` narrows an inet rule to one family
+            let meta_key = m.get("left").and_then(|l| l.get("meta")).and_then(|meta| meta.get("key"));
+            if meta_key.and_then(Value::as_str) == Some("nfproto") {
+                rule.family = m
+                    .get("right")
+                    .and_then(Value::as_str)
+                    .filter(|f| matches!(*f, "ipv4" | "ipv6"))
+                    .map(str::to_string);
+                continue;
+            }
+            let Some(payload) = m.get("left").and_then(|l| l.get("payload")) else {
+                continue;
+            };
+            let field = payload.get("field").and_then(Value::as_str).unwrap_or_default();
+            let protocol = payload.get("protocol").and_then(Value::as_str).unwrap_or_default();
+            let value = m.get("right").and_then(nft_value);
+            if let Some(family) = nft_table_family(protocol) {
+                rule.family = Some(family.to_string());
+            }
+            match field {
+                "dport" => {
+                    rule.proto = Some(protocol.to_string());
+                    rule.port = value;
+                }
+                "saddr" => rule.source = value,
+                _ => {}
+            }
+        } else if expr.get("limit").is_some() {
+            limited = true;
+        } else if expr.get("accept").is_some() {
+            rule.action = if limited { "limit" } else { "allow" }.to_string();
+        } else if expr.get("drop").is_some() {
+            rule.action = "drop".to_string();
+        } else if expr.get("reject").is_some() {
+            rule.action = "reject".to_string();
+        }
+    }
+    // Rules without a verdict (counters, jumps into sub-chains) aren't shown
+    if rule.action.is_empty() {
+        return None;
+    }
+    Some(rule)
+}
+
+fn parse_nft_ruleset(output: &str) -> Result<FirewallStatus, String> {
+    let value: Value = serde_json::from_str(output).map_err(|e| format!("unexpected nft output: {}", e))?;
+    let items = value
+        .get("nftables")
+        .and_then(Value::as_array)
+        .ok_or_else(|| "nft output has no \"nftables\" array".to_string())?;
+
+    let mut status = FirewallStatus::new("nftables");
+    // (family, table, chain) -> direction, for base chains only
+    let mut base_chains: BTreeMap<(String, String, String), &'static str> = BTreeMap::new();
+    let key = |obj: &Value, name: &str| {
+        let get = |k: &str| obj.get(k).and_then(Value::as_str).unwrap_or_default().to_string();
+        (get("family"), get("table"), get(name))
+    };
+
+    for item in items {
+        let Some(chain) = item.get("chain") else { continue };
+        if chain.get("type").and_then(Value::as_str) != Some("filter") {
+            continue;
+        }
+        let Some((direction, policy_key)) = chain.get("hook").and_then(Value::as_str).and_then(hook_direction) else {
+            continue;
+        };
+        let chain_key = key(chain, "name");
+        if let Some(policy) = chain.get("policy").and_then(Value::as_str) {
+            status.default_policies.insert(policy_key.to_string(), nft_policy(policy));
+            let families: &[&str] = match chain_key.0.as_str() {
+                "ip" => &["ipv4"],
+                "ip6" => &["ipv6"],
+                "inet" => &["ipv4", "ipv6"],
+                _ => &[],
+            };
+            for family in families {
+                status
+                    .family_policies
+                    .entry(family.to_string())
+                    .or_default()
+                    .insert(policy_key.to_string(), nft_policy(policy));
+            }
+        }
+        base_chains.insert(chain_key, direction);
+    }
+    status.enabled = !base_chains.is_empty();
+
+    for item in items {
+        let Some(rule) = item.get("rule") else { continue };
+        let rule_key = key(rule, "chain");
+        let Some(direction) = base_chains.get(&rule_key) else {
+            continue;
+        };
+        let exprs = rule.get("expr").and_then(Value::as_array).map(Vec::as_slice).unwrap_or(&[]);
+        status.rules.extend(nft_rule(direction, nft_table_family(&rule_key.0), exprs));
+    }
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(direction: &str, family: Option<&str>, proto: Option<&str>, port: Option<&str>, source: Option<&str>, action: &str) -> FirewallRule {
+        FirewallRule {
+            direction: direction.to_string(),
+            family: family.map(str::to_string),
+            proto: proto.map(str::to_string),
+            port: port.map(str::to_string),
+            source: source.map(str::to_string),
+            action: action.to_string(),
+        }
+    }
+
+    fn policies(status: &FirewallStatus) -> Vec<(&str, &str)> {
+        status.default_policies.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect()
+    }
+
+    // `ufw status verbose` on Ubuntu 22.04
+    const UFW_UBUNTU: &str = "\
+Status: active
+Logging: on (low)
+Default: deny (incoming), allow (outgoing), disabled (routed)
+New profiles: skip
+
+To                         Action      From
+--                         ------      ----
+22/tcp                     LIMIT IN    Anywhere
+80,443/tcp                 ALLOW IN    192.168.1.0/24
+8000:8100/udp on eth0      DENY IN     Anywhere
+OpenSSH                    ALLOW IN    Anywhere
+53                         ALLOW OUT   Anywhere
+Anywhere                   DENY FWD    10.0.0.0/8
+22/tcp (v6)                LIMIT IN    Anywhere (v6)
+5432/tcp                   ALLOW IN    2001:db8::/32
+
+";
+
+    #[test]
+    fn ufw_rules_keep_direction_family_and_source() {
+        let status = parse_ufw_status(UFW_UBUNTU);
+        assert_eq!(status.framework, "ufw");
+        assert!(status.enabled);
+        assert_eq!(policies(&status), [("incoming", "deny"), ("outgoing", "allow"), ("routed", "disabled")]);
+        assert_eq!(
+            status.rules,
+            [
+                rule("in", Some("ipv4"), Some("tcp"), Some("22"), None, "limit"),
+                rule("in", Some("ipv4"), Some("tcp"), Some("80,443"), Some("192.168.1.0/24"), "allow"),
+                rule("in", Some("ipv4"), Some("udp"), Some("8000:8100"), None, "deny"),
+                rule("in", Some("ipv4"), None, Some("OpenSSH"), None, "allow"),
+                rule("out", Some("ipv4"), None, Some("53"), None, "allow"),
+                rule("forward", Some("ipv4"), None, None, Some("10.0.0.0/8"), "deny"),
+                rule("in", Some("ipv6"), Some("tcp"), Some("22"), None, "limit"),
+                rule("in", Some("ipv6"), Some("tcp"), Some("5432"), Some("2001:db8::/32"), "allow"),
+            ]
+        );
+    }
+
+    #[test]
+    fn inactive_ufw_has_no_rules() {
+        let status = parse_ufw_status("Status: inactive\n");
+        assert!(!status.enabled);
+        assert!(status.rules.is_empty() && status.default_policies.is_empty());
+    }
+
+    // `firewall-cmd --list-all` on Fedora 39
+    const FIREWALLD_FEDORA: &str = "\
+FedoraWorkstation (active)
+  target: default
+  icmp-block-inversion: no
+  interfaces: wlp2s0
+  sources: 
+  services: dhcpv6-client mdns samba-client ssh
+  ports: 1025-65535/udp 1025-65535/tcp
+  protocols: 
+  forward: yes
+  masquerade: no
+  forward-ports: 
+  source-ports: 
+  icmp-blocks: 
+  rich rules: 
+\trule family=\"ipv4\" source address=\"10.0.0.0/8\" port port=\"5432\" protocol=\"tcp\" accept
+\trule family=\"ipv6\" service name=\"http\" drop
+\trule service name=\"ftp\" log limit value=\"1/m\" reject
+";
+
+    // and on CentOS 7, which has no forward line and here a zone limited
+    // to one source with a DROP target
+    const FIREWALLD_CENTOS7: &str = "\
+internal (active)
+  target: DROP
+  icmp-block-inversion: no
+  interfaces: 
+  sources: 192.168.10.0/24
+  services: ssh
+  ports: 9090/tcp
+  protocols: 
+  masquerade: no
+  forward-ports: 
+  source-ports: 
+  icmp-blocks: 
+  rich rules: 
+";
+
+    #[test]
+    fn firewalld_services_ports_and_rich_rules() {
+        let status = parse_firewalld_list_all(FIREWALLD_FEDORA);
+        assert_eq!(status.framework, "firewalld");
+        assert!(status.enabled);
+        assert_eq!(policies(&status), [("incoming", "reject"), ("outgoing", "allow")]);
+        let services: Vec<&str> = status.rules[..4].iter().filter_map(|r| r.port.as_deref()).collect();
+        assert_eq!(services, ["dhcpv6-client", "mdns", "samba-client", "ssh"]);
+        assert_eq!(
+            status.rules[4..],
+            [
+                rule("in", None, Some("udp"), Some("1025:65535"), None, "allow"),
+                rule("in", None, Some("tcp"), Some("1025:65535"), None, "allow"),
+                rule("in", Some("ipv4"), Some("tcp"), Some("5432"), Some("10.0.0.0/8"), "allow"),
+                rule("in", Some("ipv6"), None, Some("http"), None, "drop"),
+                rule("in", None, None, Some("ftp"), None, "reject"),
+            ]
+        );
+    }
+
+    #[test]
+    fn firewalld_zone_sources_limit_every_rule() {
+        let status = parse_firewalld_list_all(FIREWALLD_CENTOS7);
+        assert_eq!(policies(&status), [("incoming", "drop"), ("outgoing", "allow")]);
+        assert_eq!(
+            status.rules,
+            [
+                rule("in", None, None, Some("ssh"), Some("192.168.10.0/24"), "allow"),
+                rule("in", None, Some("tcp"), Some("9090"), Some("192.168.10.0/24"), "allow"),
+            ]
+        );
+    }
+
+    // `nft -j list ruleset`, trimmed: an inet table with an input chain and
+    // an ip6 table whose output chain has its own policy
+    const NFT_RULESET: &str = r#"{"nftables": [
+        {"metainfo": {"version": "1.0.9", "release_name": "Old Doc Yak #3", "json_schema_version": 1}},
+        {"table": {"family": "inet", "name": "filter", "handle": 1}},
+        {"chain": {"family": "inet", "table": "filter", "name": "input", "handle": 1, "type": "filter", "hook": "input", "prio": 0, "policy": "drop"}},
+        {"chain": {"family": "inet", "table": "filter", "name": "helper", "handle": 2}},
+        {"rule": {"family": "inet", "table": "filter", "chain": "input", "handle": 3, "expr": [
+            {"match": {"op": "==", "left": {"payload": {"protocol": "tcp", "field": "dport"}}, "right": 22}},
+            {"limit": {"rate": 10, "per": "minute"}},
+            {"accept": null}]}},
+        {"rule": {"family": "inet", "table": "filter", "chain": "input", "handle": 4, "expr": [
+            {"match": {"op": "==", "left": {"payload": {"protocol": "ip", "field": "saddr"}}, "right": {"prefix": {"addr": "10.0.0.0", "len": 8}}}},
+            {"match": {"op": "==", "left": {"payload": {"protocol": "tcp", "field": "dport"}}, "right": {"set": [80, 443]}}},
+            {"accept": null}]}},
+        {"rule": {"family": "inet", "table": "filter", "chain": "input", "handle": 5, "expr": [
+            {"match": {"op": "==", "left": {"meta": {"key": "nfproto"}}, "right": "ipv6"}},
+            {"match": {"op": "==", "left": {"payload": {"protocol": "udp", "field": "dport"}}, "right": {"range": [60000, 61000]}}},
+            {"reject": null}]}},
+        {"rule": {"family": "inet", "table": "filter", "chain": "input", "handle": 6, "expr": [{"counter": {"packets": 0, "bytes": 0}}]}},
+        {"rule": {"family": "inet", "table": "filter", "chain": "helper", "handle": 7, "expr": [{"drop": null}]}},
+        {"table": {"family": "ip6", "name": "egress", "handle": 2}},
+        {"chain": {"family": "ip6", "table": "egress", "name": "out", "handle": 1, "type": "filter", "hook": "output", "prio": 0, "policy": "accept"}},
+        {"rule": {"family": "ip6", "table": "egress", "chain": "out", "handle": 2, "expr": [
+            {"match": {"op": "==", "left": {"payload": {"protocol": "tcp", "field": "dport"}}, "right": 25}},
+            {"drop": null}]}}
+    ]}"#;
+
+    #[test]
+    fn nft_base_chains_policies_and_rules() {
+        let status = parse_nft_ruleset(NFT_RULESET).unwrap();
+        assert!(status.enabled);
+        assert_eq!(policies(&status), [("incoming", "drop"), ("outgoing", "allow")]);
+        let ipv4: Vec<(&str, &str)> = status.family_policies["ipv4"].iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        let ipv6: Vec<(&str, &str)> = status.family_policies["ipv6"].iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        assert_eq!(ipv4, [("incoming", "drop")]);
+        assert_eq!(ipv6, [("incoming", "drop"), ("outgoing", "allow")]);
+        // The counter-only rule and the one in a non-base chain are left out
+        assert_eq!(
+            status.rules,
+            [
+                rule("in", None, Some("tcp"), Some("22"), None, "limit"),
+                rule("in", Some("ipv4"), Some("tcp"), Some("80,443"), Some("10.0.0.0/8"), "allow"),
+                rule("in", Some("ipv6"), Some("udp"), Some("60000:61000"), None, "reject"),
+                rule("out", Some("ipv6"), Some("tcp"), Some("25"), None, "drop"),
+            ]
+        );
+    }
+
+    #[test]
+    fn nft_without_base_chains_is_not_enabled() {
+        let status = parse_nft_ruleset(r#"{"nftables": [{"metainfo": {"version": "1.0.9"}}]}"#).unwrap();
+        assert!(!status.enabled);
+        assert!(parse_nft_ruleset("not json").is_err());
+        assert!(parse_nft_ruleset(r#"{"rules": []}"#).is_err());
+    }
+}
@@ -0,0 +1,475 @@
+// Which processes are using the GPU.
+//
+// NVIDIA: `nvidia-smi --query-compute-apps` for memory and `nvidia-smi pmon`
+// for per-process SM utilization. AMD and Intel: the DRM fdinfo accounting
+// in /proc/<pid>/fdinfo (kernel 5.19+), sampled twice so engine busy time
+// can be turned into a percentage. Other users' fdinfo is only readable as
+// root; anything skipped for permissions marks the result `partial`.
+// Results are joined with the process table for names and owners; a pid
+// that exited in between is still listed, as "(exited)".
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use sysinfo::Users;
+
+use crate::error::CommandResult;
+use crate::exec;
+use crate::process_tree;
+
+// Gap between the two fdinfo readings
+const FDINFO_SAMPLE: Duration = Duration::from_millis(500);
+const NVIDIA_TIMEOUT: Duration = Duration::from_secs(5);
+const EXITED: &str = "(exited)";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GpuProcess {
+    pub pid: u32,
+    pub name: String,
+    pub user: Option<String>,
+    // "nvidia", or the DRM driver name ("amdgpu", "i915", "xe")
+    pub driver: String,
+    // GPU UUID for NVIDIA, PCI address for DRM
+    pub device: Option<String>,
+    pub memory_bytes: Option<u64>,
+    pub utilization_percent: Option<f32>,
+}
+
+#[derive(Serialize)]
+pub struct GpuProcesses {
+    pub processes: Vec<GpuProcess>,
+    // Some processes or sources couldn't be read
+    pub partial: bool,
+    // Sources that answered, e.g. ["nvidia-smi", "fdinfo"]
+    pub sources: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ComputeApp {
+    pub pid: u32,
+    pub name: String,
+    pub memory_mib: Option<u64>,
+    pub gpu_uuid: String,
+}
+
+// `--query-compute-apps=pid,process_name,used_memory,gpu_uuid
+// --format=csv,noheader,nounits`: "1234, /usr/bin/ollama, 4096, GPU-5f0e..."
+// Memory is "[N/A]" where the driver won't say (e.g. some containers).
+pub fn parse_compute_apps(text: &str) -> Vec<ComputeApp> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.len() < 4 {
+                return None;
+            }
+            Some(ComputeApp {
+                pid: fields[0].parse().ok()?,
+                name: fields[1].to_string(),
+                memory_mib: fields[2].parse().ok(),
+                gpu_uuid: fields[3].to_string(),
+            })
+        })
+        .collect()
+}
+
+// `nvidia-smi pmon -c 1 -s u`:
+//   # gpu        pid  type    sm   mem   enc   dec   command
+//   # Idx          #   C/G     %     %     %     %   name
+//       0       1234     C    45    12     -     -   ollama
+// Returns sm% by pid; "-" means the process had no samples.
+pub fn parse_pmon(text: &str) -> HashMap<u32, f32> {
+    text.lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let pid = fields.get(1)?.parse().ok()?;
+            let sm = fields.get(3)?.parse().ok()?;
+            Some((pid, sm))
+        })
+        .collect()
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct DrmClient {
+    pub driver: String,
+    pub client_id: String,
+    pub pdev: Option<String>,
+    // Device-local memory; None if the driver doesn't report it
+    pub memory_bytes: Option<u64>,
+    // Busy time per engine, ns
+    pub engines: HashMap<String, u64>,
+}
+
+fn kib_value(value: &str) -> Option<u64> {
+    let mut parts = value.split_whitespace();
+    let n: u64 = parts.next()?.parse().ok()?;
+    Some(match parts.next() {
+        Some("KiB") => n * 1024,
+        Some("MiB") => n * 1024 * 1024,
+        Some("GiB") => n * 1024 * 1024 * 1024,
+        _ => n,
+    })
+}
+
+// One /proc/<pid>/fdinfo/<fd> file. Only fds with drm-client-id are GPU
+// clients; others return None. Memory comes from drm-memory-vram (older
+// amdgpu), or drm-resident-vram / drm-resident-local* (newer amdgpu, xe).
+pub fn parse_fdinfo(text: &str) -> Option<DrmClient> {
+    let mut client = DrmClient::default();
+    let mut memory: Option<u64> = None;
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "drm-driver" => client.driver = value.to_string(),
+            "drm-client-id" => client.client_id = value.to_string(),
+            "drm-pdev" => client.pdev = Some(value.to_string()),
+            key if key == "drm-memory-vram" || key == "drm-resident-vram" || key.starts_with("drm-resident-local") => {
+                if let Some(bytes) = kib_value(value) {
+                    memory = Some(memory.unwrap_or(0) + bytes);
+                }
+            }
+            key => {
+                if let Some(engine) = key.strip_prefix("drm-engine-") {
+                    // drm-engine-capacity-* is a count, not a time
+                    if !engine.starts_with("capacity-") {
+                        if let Some(ns) = value.split_whitespace().next().and_then(|n| n.parse().ok()) {
+                            client.engines.insert(engine.to_string(), ns);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if client.client_id.is_empty() {
+        return None;
+    }
+    client.memory_bytes = memory;
+    Some(client)
+}
+
+fn nvidia() -> Option<(Vec<ComputeApp>, HashMap<u32, f32>)> {
+    exec::find_in_path("nvidia-smi")?;
+    let deadline = Instant::now() + NVIDIA_TIMEOUT;
+    let apps = exec::bounded(deadline, || {
+        exec::stdout(
+            "nvidia-smi",
+            &[
+                "--query-compute-apps=pid,process_name,used_memory,gpu_uuid",
+                "--format=csv,noheader,nounits",
+            ],
+        )
+    })
+    .flatten()?;
+    // pmon isn't supported on every board; memory alone is still useful
+    let utilization = exec::bounded(deadline, || exec::stdout("nvidia-smi", &["pmon", "-c", "1", "-s", "u"]))
+        .flatten()
+        .map(|text| parse_pmon(&text))
+        .unwrap_or_default();
+    Some((parse_compute_apps(&apps), utilization))
+}
+
+// DRM clients per pid. Returns (clients, partial).
+fn drm_clients() -> (HashMap<u32, Vec<DrmClient>>, bool) {
+    let mut clients: HashMap<u32, Vec<DrmClient>> = HashMap::new();
+    let mut partial = false;
+    let Ok(procs) = std::fs::read_dir("/proc") else {
+        return (clients, false);
+    };
+    for entry in procs.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|n| n.parse::<u32>().ok()) else {
+            continue;
+        };
+        let fds = match std::fs::read_dir(entry.path().join("fd")) {
+            Ok(fds) => fds,
+            Err(e) => {
+                partial |= e.kind() == std::io::ErrorKind::PermissionDenied;
+                continue;
+            }
+        };
+        for fd in fds.flatten() {
+            let is_drm = std::fs::read_link(fd.path()).is_ok_and(|target| target.starts_with("/dev/dri/"));
+            if !is_drm {
+                continue;
+            }
+            let info = entry.path().join("fdinfo").join(fd.file_name());
+            let Some(client) = std::fs::read_to_string(info).ok().and_then(|text| parse_fdinfo(&text)) else {
+                continue;
+            };
+            // A client shared by several fds (or dup'd) is one client
+            let list = clients.entry(pid).or_default();
+            if !list.iter().any(|c| c.client_id == client.client_id && c.driver == client.driver) {
+                list.push(client);
+            }
+        }
+    }
+    (clients, partial)
+}
+
+// Highest single-engine busy share between two readings, as a percentage
+fn engine_utilization(before: &[DrmClient], after: &[&DrmClient], elapsed: Duration) -> Option<f32> {
+    let elapsed_ns = elapsed.as_nanos() as f64;
+    let mut busiest: Option<f32> = None;
+    for client in after {
+        let Some(earlier) = before.iter().find(|c| c.client_id == client.client_id && c.driver == client.driver) else {
+            continue;
+        };
+        for (engine, ns) in &client.engines {
+            let Some(delta) = earlier.engines.get(engine).and_then(|then| ns.checked_sub(*then)) else {
+                continue;
+            };
+            let percent = (delta as f64 / elapsed_ns * 100.0).min(100.0) as f32;
+            busiest = Some(busiest.map_or(percent, |b| b.max(percent)));
+        }
+    }
+    busiest
+}
+
+pub fn gpu_processes() -> GpuProcesses {
+    let mut found: Vec<GpuProcess> = Vec::new();
+    let mut sources = Vec::new();
+    let mut partial = false;
+
+    let nvidia = nvidia();
+    if let Some((apps, utilization)) = &nvidia {
+        sources.push("nvidia-smi".to_string());
+        for app in apps {
+            found.push(GpuProcess {
+                pid: app.pid,
+                name: app.name.clone(),
+                user: None,
+                driver: "nvidia".to_string(),
+                device: Some(app.gpu_uuid.clone()),
+                memory_bytes: app.memory_mib.map(|mib| mib * 1024 * 1024),
+                utilization_percent: utilization.get(&app.pid).copied(),
+            });
+        }
+    }
+
+    if std::path::Path::new("/dev/dri").exists() {
+        let started = Instant::now();
+        let (before, partial_before) = drm_clients();
+        std::thread::sleep(FDINFO_SAMPLE);
+        let (after, partial_after) = drm_clients();
+        let elapsed = started.elapsed().max(FDINFO_SAMPLE);
+        partial |= partial_before || partial_after;
+        if !after.is_empty() {
+            sources.push("fdinfo".to_string());
+        }
+        for (pid, clients) in &after {
+            // nvidia-drm exposes fdinfo too; nvidia-smi already covered those
+            let clients: Vec<&DrmClient> = clients
+                .iter()
+                .filter(|c| !(nvidia.is_some() && c.driver.starts_with("nvidia")))
+                .collect();
+            let Some(first) = clients.first() else {
+                continue;
+            };
+            let memory = clients.iter().filter_map(|c| c.memory_bytes).reduce(|a, b| a + b);
+            found.push(GpuProcess {
+                pid: *pid,
+                name: String::new(),
+                user: None,
+                driver: first.driver.clone(),
+                device: first.pdev.clone(),
+                memory_bytes: memory,
+                utilization_percent: before
+                    .get(pid)
+                    .and_then(|earlier| engine_utilization(earlier, &clients, elapsed)),
+            });
+        }
+    }
+
+    // Names and owners from one process snapshot
+    let snap = process_tree::snapshot();
+    let users = Users::new_with_refreshed_list();
+    for process in &mut found {
+        match snap.entries.get(&process.pid) {
+            Some(entry) => {
+                process.name = entry.name.clone();
+                process.user = entry
+                    .uid
+                    .as_ref()
+                    .and_then(|uid| users.get_user_by_id(uid))
+                    .map(|user| user.name().to_string());
+            }
+            None => process.name = EXITED.to_string(),
+        }
+    }
+    found.sort_by(|a, b| b.memory_bytes.cmp(&a.memory_bytes).then(a.pid.cmp(&b.pid)));
+
+    GpuProcesses {
+        processes: found,
+        partial,
+        sources,
+    }
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_gpu_processes() -> CommandResult<GpuProcesses> {
+    Ok(gpu_processes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compute_apps_csv() {
+        // Last line: a container where the driver won't give memory
+        let text = "\
+1234, /usr/bin/ollama, 4096, GPU-5f0e8a2c-1b7d-4c3e-9a61-0d2f6b8e4a11
+88, Xwayland, 12, GPU-5f0e8a2c-1b7d-4c3e-9a61-0d2f6b8e4a11
+90210, python3, [N/A], GPU-0b4d2e6f-8c1a-4f3b-a5d7-9e2c4b6a8d03
+No running processes found
+";
+        let apps = parse_compute_apps(text);
+        assert_eq!(
+            apps,
+            [
+                ComputeApp {
+                    pid: 1234,
+                    name: "/usr/bin/ollama".to_string(),
+                    memory_mib: Some(4096),
+                    gpu_uuid: "GPU-5f0e8a2c-1b7d-4c3e-9a61-0d2f6b8e4a11".to_string(),
+                },
+                ComputeApp {
+                    pid: 88,
+                    name: "Xwayland".to_string(),
+                    memory_mib: Some(12),
+                    gpu_uuid: "GPU-5f0e8a2c-1b7d-4c3e-9a61-0d2f6b8e4a11".to_string(),
+                },
+                ComputeApp {
+                    pid: 90210,
+                    name: "python3".to_string(),
+                    memory_mib: None,
+                    gpu_uuid: "GPU-0b4d2e6f-8c1a-4f3b-a5d7-9e2c4b6a8d03".to_string(),
+                },
+            ]
+        );
+        assert!(parse_compute_apps("").is_empty());
+    }
+
+    #[test]
+    fn pmon_utilization_skips_headers_and_idle_processes() {
+        let text = "\
+# gpu        pid  type    sm   mem   enc   dec   command
+# Idx          #   C/G     %     %     %     %   name
+    0       1234     C    45    12     -     -   ollama
+    0         88     G     -     -     -     -   Xwayland
+    1      90210     C     3     1     -     -   python3
+";
+        let utilization = parse_pmon(text);
+        assert_eq!(utilization.len(), 2);
+        assert_eq!((utilization[&1234], utilization[&90210]), (45.0, 3.0));
+    }
+
+    // amdgpu before kernel 6.8, with drm-memory-* keys
+    const AMDGPU_OLD: &str = "\
+pos:\t0
+flags:\t02100002
+mnt_id:\t24
+ino:\t1107
+drm-driver:\tamdgpu
+drm-client-id:\t37
+drm-pdev:\t0000:03:00.0
+pasid:\t32770
+drm-memory-vram:\t45948 KiB
+drm-memory-gtt: \t2068 KiB
+drm-memory-cpu: \t0 KiB
+drm-engine-gfx:\t1186708409 ns
+drm-engine-compute:\t0 ns
+drm-engine-dec:\t0 ns
+drm-engine-enc:\t0 ns
+";
+
+    // amdgpu from 6.8 on, with drm-total-* and drm-resident-*
+    const AMDGPU_NEW: &str = "\
+drm-driver:\tamdgpu
+drm-client-id:\t112
+drm-pdev:\t0000:0c:00.0
+drm-total-vram:\t524288 KiB
+drm-shared-vram:\t0
+drm-resident-vram:\t262144 KiB
+drm-total-gtt:\t8192 KiB
+drm-resident-gtt:\t8192 KiB
+drm-engine-gfx:\t745000000 ns
+";
+
+    // i915 has no memory keys, and capacity counts engine instances
+    const I915: &str = "\
+drm-driver:\ti915
+drm-client-id:\t12
+drm-pdev:\t0000:00:02.0
+drm-engine-render:\t24562000 ns
+drm-engine-copy:\t0 ns
+drm-engine-video:\t1500 ns
+drm-engine-capacity-video:\t2
+drm-engine-video-enhance:\t0 ns
+";
+
+    // xe spreads device memory over local regions
+    const XE: &str = "\
+drm-driver:\txe
+drm-client-id:\t5
+drm-pdev:\t0000:03:00.0
+drm-total-system:\t4 MiB
+drm-resident-system:\t4 MiB
+drm-total-local0:\t96 MiB
+drm-resident-local0:\t64 MiB
+drm-resident-local1:\t1 GiB
+drm-cycles-rcs:\t28257900
+drm-total-cycles-rcs:\t7655183225
+";
+
+    #[test]
+    fn fdinfo_per_driver() {
+        let old = parse_fdinfo(AMDGPU_OLD).unwrap();
+        assert_eq!((old.driver.as_str(), old.client_id.as_str()), ("amdgpu", "37"));
+        assert_eq!(old.pdev.as_deref(), Some("0000:03:00.0"));
+        // VRAM only, not GTT or CPU memory
+        assert_eq!(old.memory_bytes, Some(45948 * 1024));
+        assert_eq!(old.engines.len(), 4);
+        assert_eq!(old.engines["gfx"], 1186708409);
+
+        let new = parse_fdinfo(AMDGPU_NEW).unwrap();
+        assert_eq!(new.memory_bytes, Some(262144 * 1024));
+        assert_eq!(new.engines["gfx"], 745000000);
+
+        let i915 = parse_fdinfo(I915).unwrap();
+        assert_eq!(i915.memory_bytes, None);
+        let mut engines: Vec<&str> = i915.engines.keys().map(String::as_str).collect();
+        engines.sort_unstable();
+        assert_eq!(engines, ["copy", "render", "video", "video-enhance"]);
+
+        let xe = parse_fdinfo(XE).unwrap();
+        assert_eq!(xe.memory_bytes, Some(64 * 1024 * 1024 + 1024 * 1024 * 1024));
+        assert!(xe.engines.is_empty());
+    }
+
+    #[test]
+    fn fdinfo_without_a_client_id_is_not_a_gpu_client() {
+        assert_eq!(parse_fdinfo("pos:\t0\nflags:\t0100002\nmnt_id:\t27\nino:\t4\n"), None);
+        assert_eq!(parse_fdinfo(""), None);
+    }
+
+    #[test]
+    fn utilization_is_the_busiest_engine_between_readings() {
+        let reading = |client_id: &str, gfx: u64, compute: u64| DrmClient {
+            driver: "amdgpu".to_string(),
+            client_id: client_id.to_string(),
+            engines: HashMap::from([("gfx".to_string(), gfx), ("compute".to_string(), compute)]),
+            ..Default::default()
+        };
+        let before = [reading("1", 0, 0), reading("2", 1_000, 1_000)];
+        let later = [reading("1", 100_000_000, 250_000_000), reading("2", 500, 500), reading("3", 9, 9)];
+        let after: Vec<&DrmClient> = later.iter().collect();
+        // 250ms of compute in 500ms; client 2's counter went backwards and
+        // client 3 is new, so neither counts
+        assert_eq!(engine_utilization(&before, &after, Duration::from_millis(500)), Some(50.0));
+        assert_eq!(engine_utilization(&before, &after[1..], Duration::from_millis(500)), None);
+        // Rounding never makes it more than fully busy
+        assert_eq!(engine_utilization(&before, &after[..1], Duration::from_millis(100)), Some(100.0));
+    }
+}
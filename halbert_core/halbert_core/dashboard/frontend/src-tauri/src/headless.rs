@@ -0,0 +1,235 @@
+// Tauri's app handle, managed state and async runtime, for running the core
+// without a window.
+//
+// Only what the core uses of them: `manage` and `state` keyed by type, the
+// config and data dirs, `emit` (a no-op, as nothing listens without a
+// window) and `exit`, which wakes whoever is in `wait_for_exit`. Managed
+// state lives as long as the process, as it does in Tauri; each value is
+// leaked once so a State can borrow it without holding a lock.
+use serde::Serialize;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+
+pub trait Runtime: Send + Sync + 'static {}
+
+// The only runtime there is without a window
+pub enum Headless {}
+
+impl Runtime for Headless {}
+
+pub struct State<'r, T: Send + Sync + 'static>(&'r T);
+
+impl<'r, T: Send + Sync + 'static> State<'r, T> {
+    pub fn inner(&self) -> &'r T {
+        self.0
+    }
+}
+
+impl<T: Send + Sync + 'static> Deref for State<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.0
+    }
+}
+
+impl<T: Send + Sync + 'static> Clone for State<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Send + Sync + 'static> Copy for State<'_, T> {}
+
+pub struct PathResolver {
+    config_dir: PathBuf,
+    data_dir: PathBuf,
+}
+
+impl PathResolver {
+    pub fn app_config_dir(&self) -> std::io::Result<PathBuf> {
+        Ok(self.config_dir.clone())
+    }
+
+    pub fn app_data_dir(&self) -> std::io::Result<PathBuf> {
+        Ok(self.data_dir.clone())
+    }
+}
+
+pub struct PackageInfo {
+    pub version: &'static str,
+}
+
+struct Inner {
+    states: RwLock<HashMap<TypeId, &'static (dyn Any + Send + Sync)>>,
+    paths: PathResolver,
+    exit_code: Mutex<Option<i32>>,
+    exited: Condvar,
+}
+
+pub struct AppHandle<R: Runtime = Headless> {
+    inner: Arc<Inner>,
+    runtime: PhantomData<fn() -> R>,
+}
+
+impl<R: Runtime> Clone for AppHandle<R> {
+    fn clone(&self) -> Self {
+        AppHandle {
+            inner: self.inner.clone(),
+            runtime: PhantomData,
+        }
+    }
+}
+
+impl AppHandle {
+    pub fn new(config_dir: PathBuf, data_dir: PathBuf) -> Self {
+        AppHandle {
+            inner: Arc::new(Inner {
+                states: RwLock::new(HashMap::new()),
+                paths: PathResolver { config_dir, data_dir },
+                exit_code: Mutex::new(None),
+                exited: Condvar::new(),
+            }),
+            runtime: PhantomData,
+        }
+    }
+}
+
+impl<R: Runtime> AppHandle<R> {
+    pub fn package_info(&self) -> PackageInfo {
+        PackageInfo {
+            version: env!("CARGO_PKG_VERSION"),
+        }
+    }
+
+    // The first code asked for is the one wait_for_exit returns
+    pub fn exit(&self, code: i32) {
+        self.inner.exit_code.lock().unwrap().get_or_insert(code);
+        self.inner.exited.notify_all();
+    }
+
+    pub fn wait_for_exit(&self) -> i32 {
+        let mut code = self.inner.exit_code.lock().unwrap();
+        loop {
+            if let Some(code) = *code {
+                return code;
+            }
+            code = self.inner.exited.wait(code).unwrap();
+        }
+    }
+}
+
+pub trait Manager {
+    fn manage<T: Send + Sync + 'static>(&self, state: T) -> bool;
+    fn try_state<T: Send + Sync + 'static>(&self) -> Option<State<'_, T>>;
+    fn path(&self) -> &PathResolver;
+
+    fn state<T: Send + Sync + 'static>(&self) -> State<'_, T> {
+        self.try_state()
+            .unwrap_or_else(|| panic!("state() called before manage() for {}", std::any::type_name::<T>()))
+    }
+}
+
+impl<R: Runtime> Manager for AppHandle<R> {
+    // Like Tauri, the first value managed for a type stays
+    fn manage<T: Send + Sync + 'static>(&self, state: T) -> bool {
+        let mut states = self.inner.states.write().unwrap();
+        if states.contains_key(&TypeId::of::<T>()) {
+            return false;
+        }
+        let state: &'static T = Box::leak(Box::new(state));
+        states.insert(TypeId::of::<T>(), state);
+        true
+    }
+
+    fn try_state<T: Send + Sync + 'static>(&self) -> Option<State<'_, T>> {
+        let states = self.inner.states.read().unwrap();
+        let state = *states.get(&TypeId::of::<T>())?;
+        Some(State(state.downcast_ref::<T>()?))
+    }
+
+    fn path(&self) -> &PathResolver {
+        &self.inner.paths
+    }
+}
+
+pub trait Emitter {
+    fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) -> Result<(), Infallible>;
+    fn emit_to<S: Serialize + Clone>(&self, target: &str, event: &str, payload: S) -> Result<(), Infallible>;
+}
+
+impl<R: Runtime> Emitter for AppHandle<R> {
+    fn emit<S: Serialize + Clone>(&self, _event: &str, _payload: S) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn emit_to<S: Serialize + Clone>(&self, _target: &str, _event: &str, _payload: S) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+pub mod async_runtime {
+    use std::future::Future;
+    use std::sync::OnceLock;
+    use tokio::runtime::Runtime;
+    pub use tokio::task::JoinHandle;
+
+    fn runtime() -> &'static Runtime {
+        static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+        RUNTIME.get_or_init(|| {
+            tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .expect("failed to start the async runtime")
+        })
+    }
+
+    pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        runtime().spawn(future)
+    }
+
+    pub fn block_on<F: Future>(future: F) -> F::Output {
+        runtime().block_on(future)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct Counter(Mutex<u32>);
+
+    #[test]
+    fn state_is_managed_once_per_type() {
+        let app = AppHandle::new(PathBuf::from("/etc/halbert"), PathBuf::from("/var/lib/halbert"));
+        assert!(app.try_state::<Counter>().is_none());
+        assert!(app.manage(Counter::default()));
+        *app.state::<Counter>().inner().0.lock().unwrap() += 1;
+        assert!(!app.manage(Counter(Mutex::new(7))), "the first value stays");
+        assert_eq!(*app.clone().state::<Counter>().inner().0.lock().unwrap(), 1);
+        assert_eq!(app.path().app_data_dir().unwrap(), PathBuf::from("/var/lib/halbert"));
+    }
+
+    #[test]
+    fn exit_wakes_the_waiter_with_the_first_code() {
+        let app = AppHandle::new(PathBuf::new(), PathBuf::new());
+        let waiter = app.clone();
+        let waiting = std::thread::spawn(move || waiter.wait_for_exit());
+        std::thread::sleep(Duration::from_millis(20));
+        app.exit(0);
+        app.exit(3);
+        assert_eq!(waiting.join().unwrap(), 0);
+        assert_eq!(app.wait_for_exit(), 0);
+    }
+}
@@ -0,0 +1,451 @@
+// Event hooks: the user's own scripts, run when a notification event fires.
+//
+// Hooks subscribe to the same events as webhooks and see everything that
+// goes through notifications. The script is executed directly, never
+// through a shell, with the webhook JSON body on stdin and HALBERT_*
+// variables naming the event. It leads its own process group, so killing
+// it at the timeout takes anything it started too. Runs happen on a
+// thread of their own, away from whoever raised the event, and the last
+// RUNS_KEPT runs per hook are kept with their output capped. A hook that
+// fails MAX_FAILURES times in a row is disabled and announced. Hooks don't
+// run in read-only mode, since a script can change anything.
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::app::{AppHandle, Emitter, Manager, State};
+use crate::automation::{Entry, Gate};
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::notifications;
+use crate::readonly::Mode;
+use crate::sandbox;
+use crate::settings::{Settings, SettingsStore};
+
+const MAX_FAILURES: u32 = 5;
+const MAX_TIMEOUT_S: u32 = 3600;
+const RUNS_KEPT: u32 = 20;
+// stdout and stderr together, per run
+const MAX_OUTPUT_BYTES: usize = 16 * 1024;
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Hook {
+    pub id: i64,
+    pub event: String,
+    pub script_path: String,
+    pub timeout_s: u32,
+    pub enabled: bool,
+    pub consecutive_failures: u32,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HookRun {
+    pub id: i64,
+    pub hook_id: i64,
+    pub event: String,
+    pub started_at: String,
+    pub duration_ms: u64,
+    // "succeeded", "failed" or "timed_out"
+    pub status: String,
+    pub exit_code: Option<i32>,
+    // Interleaved stdout and stderr, or why the script didn't start
+    pub output: String,
+    pub truncated: bool,
+}
+
+struct Outcome {
+    status: &'static str,
+    exit_code: Option<i32>,
+    output: Vec<u8>,
+    truncated: bool,
+    duration: Duration,
+}
+
+const HOOK_COLUMNS: &str = "id, event, script_path, timeout_s, enabled, consecutive_failures, created_at";
+
+fn read_hook(r: &rusqlite::Row) -> rusqlite::Result<Hook> {
+    Ok(Hook {
+        id: r.get(0)?,
+        event: r.get(1)?,
+        script_path: r.get(2)?,
+        timeout_s: r.get(3)?,
+        enabled: r.get(4)?,
+        consecutive_failures: r.get(5)?,
+        created_at: r.get(6)?,
+    })
+}
+
+fn load(db: &Database, id: i64) -> CommandResult<Hook> {
+    let sql = format!("SELECT {} FROM hooks WHERE id = ?1", HOOK_COLUMNS);
+    db.with_conn(|conn| conn.query_row(&sql, params![id], read_hook).optional())?
+        .ok_or_else(|| CommandError::NotFound(format!("hook {}", id)))
+}
+
+fn enabled_for(db: &Database, event: &str) -> CommandResult<Vec<Hook>> {
+    let sql = format!("SELECT {} FROM hooks WHERE event = ?1 AND enabled = 1 ORDER BY id", HOOK_COLUMNS);
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params![event], read_hook)?;
+        rows.collect()
+    })
+}
+
+pub fn check_event(event: &str) -> CommandResult<()> {
+    if notifications::EVENTS.contains(&event) {
+        return Ok(());
+    }
+    Err(CommandError::InvalidInput(format!(
+        "unknown event '{}', expected one of {}",
+        event,
+        notifications::EVENTS.join(", ")
+    )))
+}
+
+// An absolute path to an executable file
+pub fn check_script(settings: &Settings, path: &str) -> CommandResult<()> {
+    let script = sandbox::HOOK_SCRIPT.validate(settings, Path::new(path))?;
+    let script = script.as_path();
+    if !script.is_file() {
+        return Err(CommandError::NotFound(format!("script {}", path)));
+    }
+    if !exec::is_executable(script) {
+        return Err(CommandError::InvalidInput(format!("{} is not executable", path)));
+    }
+    Ok(())
+}
+
+// What the script finds in its environment besides the inherited one
+pub fn hook_env(hook: &Hook, message: &Value) -> Vec<(&'static str, String)> {
+    let field = |name: &str| message.get(name).and_then(Value::as_str).unwrap_or_default().to_string();
+    vec![
+        ("HALBERT_EVENT", hook.event.clone()),
+        ("HALBERT_HOOK_ID", hook.id.to_string()),
+        ("HALBERT_TITLE", field("title")),
+        ("HALBERT_SENT_AT", field("sent_at")),
+    ]
+}
+
+// Append what still fits under `limit`; true when something was dropped
+pub fn append_bounded(buffer: &mut Vec<u8>, chunk: &[u8], limit: usize) -> bool {
+    let room = limit.saturating_sub(buffer.len());
+    buffer.extend_from_slice(&chunk[..chunk.len().min(room)]);
+    chunk.len() > room
+}
+
+#[cfg(unix)]
+fn own_process_group(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+    command.process_group(0);
+}
+
+#[cfg(not(unix))]
+fn own_process_group(_command: &mut Command) {}
+
+#[cfg(unix)]
+fn kill_tree(child: &mut Child) {
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_tree(child: &mut Child) {
+    let _ = child.kill();
+}
+
+type Captured = Arc<Mutex<(Vec<u8>, bool)>>;
+
+// Keeps reading after the cap so the script never blocks on a full pipe
+fn capture(mut stream: impl Read + Send + 'static, captured: Captured) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut chunk = [0u8; 4096];
+        while let Ok(n) = stream.read(&mut chunk) {
+            if n == 0 {
+                break;
+            }
+            let mut captured = captured.lock().unwrap();
+            let (buffer, truncated) = &mut *captured;
+            *truncated |= append_bounded(buffer, &chunk[..n], MAX_OUTPUT_BYTES);
+        }
+    })
+}
+
+fn run_script(hook: &Hook, message: &Value) -> Outcome {
+    let started = Instant::now();
+    let mut command = Command::new(&hook.script_path);
+    command
+        .envs(hook_env(hook, message))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    own_process_group(&mut command);
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            return Outcome {
+                status: "failed",
+                exit_code: None,
+                output: format!("failed to start {}: {}", hook.script_path, e).into_bytes(),
+                truncated: false,
+                duration: started.elapsed(),
+            }
+        }
+    };
+
+    // Written from a thread so a script that never reads stdin can't stall us
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = message.to_string();
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        });
+    }
+    let captured: Captured = Arc::default();
+    let readers: Vec<_> = [
+        child.stdout.take().map(|out| capture(out, captured.clone())),
+        child.stderr.take().map(|err| capture(err, captured.clone())),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    let deadline = started + Duration::from_secs(hook.timeout_s as u64);
+    let mut timed_out = false;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Ok(status),
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(POLL_INTERVAL),
+            Ok(None) => {
+                timed_out = true;
+                kill_tree(&mut child);
+                break child.wait();
+            }
+            Err(e) => break Err(e),
+        }
+    };
+    for reader in readers {
+        let _ = reader.join();
+    }
+    let (mut output, truncated) = std::mem::take(&mut *captured.lock().unwrap());
+
+    let (status, exit_code) = match status {
+        _ if timed_out => {
+            output.extend_from_slice(format!("\nkilled after the {}s timeout", hook.timeout_s).as_bytes());
+            ("timed_out", None)
+        }
+        Ok(status) if status.success() => ("succeeded", status.code()),
+        Ok(status) => ("failed", status.code()),
+        Err(e) => {
+            output.extend_from_slice(format!("\nfailed to wait for the script: {}", e).as_bytes());
+            ("failed", None)
+        }
+    };
+    Outcome {
+        status,
+        exit_code,
+        output,
+        truncated,
+        duration: started.elapsed(),
+    }
+}
+
+// Store the run and update the failure streak; true when this run
+// disabled the hook
+fn finish(db: &Database, hook: &Hook, started_at: &str, outcome: &Outcome) -> CommandResult<bool> {
+    let output = String::from_utf8_lossy(&outcome.output).into_owned();
+    let succeeded = outcome.status == "succeeded";
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO hook_runs (hook_id, event, started_at, duration_ms, status, exit_code, output, truncated)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                hook.id,
+                hook.event,
+                started_at,
+                outcome.duration.as_millis() as i64,
+                outcome.status,
+                outcome.exit_code,
+                output,
+                outcome.truncated
+            ],
+        )?;
+        tx.execute(
+            "DELETE FROM hook_runs WHERE hook_id = ?1 AND id NOT IN
+                (SELECT id FROM hook_runs WHERE hook_id = ?1 ORDER BY id DESC LIMIT ?2)",
+            params![hook.id, RUNS_KEPT],
+        )?;
+        let streak = if succeeded { "0" } else { "consecutive_failures + 1" };
+        tx.execute(
+            &format!("UPDATE hooks SET consecutive_failures = {} WHERE id = ?1", streak),
+            params![hook.id],
+        )?;
+        let disabled = !succeeded
+            && tx.execute(
+                "UPDATE hooks SET enabled = 0 WHERE id = ?1 AND enabled = 1 AND consecutive_failures >= ?2",
+                params![hook.id, MAX_FAILURES],
+            )? > 0;
+        tx.commit()?;
+        Ok(disabled)
+    })
+}
+
+fn run_event(app: &AppHandle, event: &str, message: &Value) {
+    let Some(db) = app.try_state::<Database>() else {
+        return;
+    };
+    let hooks = match enabled_for(&db, event) {
+        Ok(hooks) => hooks,
+        Err(e) => {
+            println!("[Halbert] Can't load hooks for {}: {}", event, e);
+            return;
+        }
+    };
+    if hooks.is_empty() {
+        return;
+    }
+    if app.state::<SettingsStore>().mode() == Mode::ReadOnly {
+        println!("[Halbert] Read-only mode; not running {} hook(s) for {}", hooks.len(), event);
+        return;
+    }
+    if !app.state::<Gate>().allows(Entry::Hook, &format!("hooks for {}", event)) {
+        println!("[Halbert] Automation is paused; not running {} hook(s) for {}", hooks.len(), event);
+        return;
+    }
+    for hook in hooks {
+        let started_at = chrono::Utc::now().to_rfc3339();
+        let outcome = run_script(&hook, message);
+        println!(
+            "[Halbert] Hook {} ({}) {} in {} ms",
+            hook.id,
+            hook.script_path,
+            outcome.status,
+            outcome.duration.as_millis()
+        );
+        match finish(&db, &hook, &started_at, &outcome) {
+            Ok(true) => {
+                let Ok(disabled) = load(&db, hook.id) else { continue };
+                println!("[Halbert] Disabled hook {} after {} failures", hook.id, disabled.consecutive_failures);
+                let last_error = String::from_utf8_lossy(&outcome.output);
+                let last_line = last_error.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or(outcome.status);
+                let _ = app.emit("hooks://disabled", &disabled);
+                notifications::hook_disabled(app, &disabled, last_line);
+            }
+            Ok(false) => {}
+            Err(e) => println!("[Halbert] Failed to record run of hook {}: {}", hook.id, e),
+        }
+    }
+}
+
+// Called for every notification event; the hooks run on their own thread
+// so a slow script never holds up the caller
+pub fn fire(app: &AppHandle, event: &str, message: Value) {
+    let app = app.clone();
+    let event = event.to_string();
+    std::thread::spawn(move || run_event(&app, &event, &message));
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn create_hook(
+    db: State<'_, Database>,
+    settings: State<'_, SettingsStore>,
+    event: String,
+    script_path: String,
+    timeout_s: u32,
+    enabled: bool,
+) -> CommandResult<Hook> {
+    let event = event.trim().to_string();
+    let script_path = script_path.trim().to_string();
+    check_event(&event)?;
+    check_script(&settings.get(), &script_path)?;
+    if !(1..=MAX_TIMEOUT_S).contains(&timeout_s) {
+        return Err(CommandError::InvalidInput(format!(
+            "timeout must be between 1 and {} seconds",
+            MAX_TIMEOUT_S
+        )));
+    }
+    let now = chrono::Utc::now().to_rfc3339();
+    let id = db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO hooks (event, script_path, timeout_s, enabled, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![event, script_path, timeout_s, enabled, now],
+        )?;
+        Ok(conn.last_insert_rowid())
+    })?;
+    println!("[Halbert] Added hook {}: {} on {}", id, script_path, event);
+    load(&db, id)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn list_hooks(db: State<'_, Database>) -> CommandResult<Vec<Hook>> {
+    let sql = format!("SELECT {} FROM hooks ORDER BY id", HOOK_COLUMNS);
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map([], read_hook)?;
+        rows.collect()
+    })
+}
+
+// Enabling starts a fresh failure streak; the script is checked again
+// since it may have changed while the hook was off
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn set_hook_enabled(
+    db: State<'_, Database>,
+    settings: State<'_, SettingsStore>,
+    hook_id: i64,
+    enabled: bool,
+) -> CommandResult<Hook> {
+    let hook = load(&db, hook_id)?;
+    if enabled {
+        check_script(&settings.get(), &hook.script_path)?;
+    }
+    db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE hooks SET enabled = ?2, consecutive_failures = 0 WHERE id = ?1",
+            params![hook_id, enabled],
+        )
+    })?;
+    load(&db, hook_id)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn delete_hook(db: State<'_, Database>, hook_id: i64) -> CommandResult<()> {
+    let deleted = db.with_conn(|conn| conn.execute("DELETE FROM hooks WHERE id = ?1", params![hook_id]))?;
+    if deleted == 0 {
+        return Err(CommandError::NotFound(format!("hook {}", hook_id)));
+    }
+    Ok(())
+}
+
+// Newest first
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_hook_runs(db: State<'_, Database>, hook_id: i64) -> CommandResult<Vec<HookRun>> {
+    load(&db, hook_id)?;
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, hook_id, event, started_at, duration_ms, status, exit_code, output, truncated
+             FROM hook_runs WHERE hook_id = ?1 ORDER BY id DESC",
+        )?;
+        let rows = stmt.query_map(params![hook_id], |r| {
+            Ok(HookRun {
+                id: r.get(0)?,
+                hook_id: r.get(1)?,
+                event: r.get(2)?,
+                started_at: r.get(3)?,
+                duration_ms: r.get::<_, i64>(4)? as u64,
+                status: r.get(5)?,
+                exit_code: r.get(6)?,
+                output: r.get(7)?,
+                truncated: r.get(8)?,
+            })
+        })?;
+        rows.collect()
+    })
+}
@@ -0,0 +1,617 @@
+// Host registry: the local machine plus remote Halbert agents.
+//
+// When a remote host is active, the data-gathering commands fetch from its
+// HTTP API instead of reading local state. Remote paths mirror the local
+// commands (see `fetch_*` below). Every response carries `host_id` so the
+// UI can cache per host. Hosts with a MAC address can be woken with
+// wake-on-LAN (see wol.rs) while their API is unreachable. Hosts without
+// an agent can be polled over SSH for basic metrics only (see ssh_hosts.rs).
+// Approving or rejecting while a remote host is active decides the request
+// (or approves the group) on that host, whether it's the Python agent or another Halbert serving
+// the same routes (see remote_access). Another Halbert serves them apart
+// from its read-only API, so such a host has a `changes_url` and token of
+// its own (set_host_changes).
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::app::{AppHandle, Emitter, State};
+use crate::approvals::ApprovalRequest;
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::http::Endpoint;
+use crate::jobs::Job;
+use crate::secrets;
+use crate::settings::{Settings, SettingsStore};
+use crate::ssh_hosts::{self, SshHosts, SshTarget, Transport};
+use crate::wol;
+
+pub const LOCAL_HOST_ID: &str = "local";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HostEntry {
+    pub id: String,
+    pub name: String,
+    // Empty for SSH hosts
+    #[serde(default)]
+    pub base_url: String,
+    #[serde(default)]
+    pub transport: Transport,
+    // Set when transport is "ssh"
+    #[serde(default)]
+    pub ssh: Option<SshTarget>,
+    // Lowercase, colon separated
+    #[serde(default)]
+    pub mac: Option<String>,
+    // Where to send the magic packet; the global broadcast if unset
+    #[serde(default)]
+    pub wol_broadcast: Option<String>,
+    // Where approvals are decided when that isn't base_url
+    #[serde(default)]
+    pub changes_url: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct HostSummary {
+    pub id: String,
+    pub name: String,
+    pub base_url: Option<String>,
+    pub transport: Transport,
+    // "user@address:port" for SSH hosts
+    pub ssh_target: Option<String>,
+    pub is_local: bool,
+    pub active: bool,
+    pub has_token: bool,
+    pub mac: Option<String>,
+    // "online", "offline", "offline_wake_available", or "unknown" when not probed
+    pub status: String,
+}
+
+#[allow(clippy::large_enum_variant)]
+pub enum ActiveHost {
+    Local,
+    Remote(HostEntry),
+}
+
+// A stale active_host (e.g. the host was removed) falls back to local
+pub fn active_host(settings: &Settings) -> ActiveHost {
+    match settings.active_host.as_deref() {
+        Some(id) if id != LOCAL_HOST_ID => settings
+            .hosts
+            .iter()
+            .find(|h| h.id == id)
+            .cloned()
+            .map(ActiveHost::Remote)
+            .unwrap_or(ActiveHost::Local),
+        _ => ActiveHost::Local,
+    }
+}
+
+pub fn active_host_id(settings: &Settings) -> String {
+    match active_host(settings) {
+        ActiveHost::Local => LOCAL_HOST_ID.to_string(),
+        ActiveHost::Remote(host) => host.id,
+    }
+}
+
+pub fn token_secret_name(host_id: &str) -> String {
+    format!("host:{}", host_id)
+}
+
+pub fn changes_secret_name(host_id: &str) -> String {
+    format!("host:{}:changes", host_id)
+}
+
+fn require_agent(host: &HostEntry) -> CommandResult<()> {
+    if host.transport == Transport::Ssh {
+        return Err(CommandError::NotSupported(format!(
+            "{} is reached over SSH, which only provides system metrics",
+            host.name
+        )));
+    }
+    Ok(())
+}
+
+pub fn get_json<T: DeserializeOwned>(host: &HostEntry, path: &str) -> CommandResult<T> {
+    require_agent(host)?;
+    endpoint(host).get_json(path)
+}
+
+// The token is read per request so a rotated token applies immediately
+fn endpoint(host: &HostEntry) -> Endpoint<'_> {
+    Endpoint {
+        label: &host.name,
+        base_url: &host.base_url,
+        token: secrets::read(&token_secret_name(&host.id)).ok().flatten(),
+        measured: false,
+    }
+}
+
+// Where decisions go: the changes listener with its token when the host has
+// one, else the API itself
+pub fn changes_endpoint(host: &HostEntry) -> Endpoint<'_> {
+    match &host.changes_url {
+        Some(url) => Endpoint {
+            label: &host.name,
+            base_url: url,
+            token: secrets::read(&changes_secret_name(&host.id)).ok().flatten(),
+            measured: false,
+        },
+        None => endpoint(host),
+    }
+}
+
+pub fn fetch_metrics(host: &HostEntry) -> CommandResult<crate::SystemMetrics> {
+    let mut metrics: crate::SystemMetrics = get_json(host, "/api/system/metrics")?;
+    metrics.host_id = host.id.clone();
+    Ok(metrics)
+}
+
+pub fn fetch_memory_stats(host: &HostEntry, corpus: Option<&str>) -> CommandResult<crate::MemoryStats> {
+    let path = match corpus {
+        Some(name) if !crate::corpora::valid_name(name) => {
+            return Err(CommandError::InvalidInput(format!("invalid corpus name '{}'", name)))
+        }
+        Some(name) => format!("/api/memory/stats?corpus={}", name),
+        None => "/api/memory/stats".to_string(),
+    };
+    let mut stats: crate::MemoryStats = get_json(host, &path)?;
+    stats.host_id = host.id.clone();
+    Ok(stats)
+}
+
+// Corpus names are limited to characters that need no escaping
+pub fn fetch_documents(host: &HostEntry, corpus: &str) -> CommandResult<Vec<crate::documents::Document>> {
+    let path = format!("/api/memory/documents?corpus={}", corpus);
+    let mut docs: Vec<crate::documents::Document> = get_json(host, &path)?;
+    for doc in &mut docs {
+        doc.host_id = host.id.clone();
+    }
+    Ok(docs)
+}
+
+pub fn fetch_jobs(host: &HostEntry) -> CommandResult<Vec<Job>> {
+    let mut jobs: Vec<Job> = get_json(host, "/api/jobs/active")?;
+    for job in &mut jobs {
+        job.host_id = host.id.clone();
+    }
+    Ok(jobs)
+}
+
+pub fn fetch_approvals(host: &HostEntry) -> CommandResult<Vec<ApprovalRequest>> {
+    let mut requests: Vec<ApprovalRequest> = get_json(host, "/api/approvals/pending")?;
+    for request in &mut requests {
+        request.host_id = host.id.clone();
+    }
+    Ok(requests)
+}
+
+#[derive(Deserialize)]
+struct Decided {
+    message: String,
+}
+
+// The host's answer, with the host named so it's clear where it happened
+pub fn decide_approval(
+    host: &HostEntry,
+    request_id: &str,
+    approved: bool,
+    reason: Option<&str>,
+) -> CommandResult<String> {
+    require_agent(host)?;
+    let plain = request_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if request_id.is_empty() || !plain {
+        return Err(CommandError::InvalidInput(format!("invalid request id '{}'", request_id)));
+    }
+    let verb = if approved { "approve" } else { "reject" };
+    let body = serde_json::json!({ "approved": approved, "reason": reason });
+    let decided: Decided = changes_endpoint(host).post_json(&format!("/api/approvals/{}/{}", request_id, verb), &body)?;
+    Ok(format!("{} on {}", decided.message, host.name))
+}
+
+// The group's members as the host approved them
+pub fn approve_group(host: &HostEntry, group_id: &str) -> CommandResult<Vec<ApprovalRequest>> {
+    require_agent(host)?;
+    let body = serde_json::json!({ "group_id": group_id });
+    let mut approved: Vec<ApprovalRequest> = changes_endpoint(host).post_json("/api/approvals/groups/approve", &body)?;
+    for request in &mut approved {
+        request.host_id = host.id.clone();
+    }
+    Ok(approved)
+}
+
+fn validate_base_url(base_url: &str) -> CommandResult<String> {
+    let url = base_url.trim();
+    let valid_scheme = url.starts_with("http://") || url.starts_with("https://");
+    if !valid_scheme || url.chars().any(char::is_whitespace) {
+        return Err(CommandError::InvalidInput(format!(
+            "base_url must be an http(s) URL, got '{}'",
+            base_url
+        )));
+    }
+    Ok(url.trim_end_matches('/').to_string())
+}
+
+// Derive a readable, unique id from the host name ("Backup NAS" -> "backup-nas")
+fn host_id_for(name: &str, existing: &[HostEntry]) -> String {
+    let slug: String = name
+        .trim()
+        .to_ascii_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let slug = slug.trim_matches('-');
+    let base = if slug.is_empty() { "host" } else { slug };
+
+    let mut id = base.to_string();
+    let mut n = 2;
+    while id == LOCAL_HOST_ID || existing.iter().any(|h| h.id == id) {
+        id = format!("{}-{}", base, n);
+        n += 1;
+    }
+    id
+}
+
+// Any HTTP answer counts; an auth error still means the machine is up.
+// For SSH hosts, an open SSH port does.
+pub fn probe(host: &HostEntry) -> bool {
+    if let Some(target) = host.ssh.as_ref().filter(|_| host.transport == Transport::Ssh) {
+        return ssh_hosts::port_open(target, PROBE_TIMEOUT);
+    }
+    endpoint(host).probe("/api/status", PROBE_TIMEOUT).is_ok()
+}
+
+fn status(host: &HostEntry, reachable: Option<bool>) -> &'static str {
+    match reachable {
+        None => "unknown",
+        Some(true) => "online",
+        Some(false) if host.mac.is_some() => "offline_wake_available",
+        Some(false) => "offline",
+    }
+}
+
+fn summarize(host: &HostEntry, active_id: &str, reachable: Option<bool>) -> HostSummary {
+    HostSummary {
+        id: host.id.clone(),
+        name: host.name.clone(),
+        base_url: (host.transport == Transport::Http).then(|| host.base_url.clone()),
+        transport: host.transport,
+        ssh_target: host.ssh.as_ref().map(|t| format!("{}@{}:{}", t.user, t.address, t.port)),
+        is_local: false,
+        active: host.id == active_id,
+        has_token: matches!(secrets::read(&token_secret_name(&host.id)), Ok(Some(_))),
+        mac: host.mac.clone(),
+        status: status(host, reachable).to_string(),
+    }
+}
+
+fn local_summary(active_id: &str) -> HostSummary {
+    HostSummary {
+        id: LOCAL_HOST_ID.to_string(),
+        name: sysinfo::System::host_name().unwrap_or_else(|| "This machine".to_string()),
+        base_url: None,
+        transport: Transport::Http,
+        ssh_target: None,
+        is_local: true,
+        active: active_id == LOCAL_HOST_ID,
+        has_token: false,
+        mac: None,
+        status: "online".to_string(),
+    }
+}
+
+fn parse_host_mac(mac: Option<String>) -> CommandResult<Option<String>> {
+    match mac.as_deref().map(str::trim).filter(|m| !m.is_empty()) {
+        Some(mac) => Ok(Some(wol::format_mac(&wol::parse_mac(mac)?))),
+        None => Ok(None),
+    }
+}
+
+// Remote hosts are probed in parallel; one that doesn't answer within
+// PROBE_TIMEOUT is offline. SSH hosts report their last poll instead.
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn list_hosts(
+    settings: State<'_, SettingsStore>,
+    ssh: State<'_, SshHosts>,
+) -> CommandResult<Vec<HostSummary>> {
+    let settings = settings.get();
+    let active_id = active_host_id(&settings);
+    let deadline = Instant::now() + PROBE_TIMEOUT + Duration::from_millis(500);
+    let probes: Vec<_> = settings
+        .hosts
+        .iter()
+        .map(|host| {
+            let host = host.clone();
+            (host.transport == Transport::Http).then(|| {
+                std::thread::spawn(move || exec::bounded(deadline, move || probe(&host)).unwrap_or(false))
+            })
+        })
+        .collect();
+    let mut hosts = vec![local_summary(&active_id)];
+    for (host, probe) in settings.hosts.iter().zip(probes) {
+        let reachable = match probe {
+            Some(probe) => Some(probe.join().unwrap_or(false)),
+            None => ssh.reachable(&host.id),
+        };
+        hosts.push(summarize(host, &active_id, reachable));
+    }
+    Ok(hosts)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn add_host(
+    settings: State<'_, SettingsStore>,
+    name: String,
+    base_url: String,
+    token: Option<String>,
+    mac: Option<String>,
+) -> CommandResult<HostSummary> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(CommandError::InvalidInput("host name is required".to_string()));
+    }
+    let base_url = validate_base_url(&base_url)?;
+    let mac = parse_host_mac(mac)?;
+
+    let mut added = None;
+    let updated = settings.update(|current| {
+        let mut next = current.clone();
+        let host = HostEntry {
+            id: host_id_for(&name, &next.hosts),
+            name: name.clone(),
+            base_url: base_url.clone(),
+            transport: Transport::Http,
+            ssh: None,
+            mac: mac.clone(),
+            wol_broadcast: None,
+            changes_url: None,
+        };
+        next.hosts.push(host.clone());
+        added = Some(host);
+        Ok(next)
+    })?;
+    let host = added.ok_or_else(|| CommandError::Internal("host was not added".to_string()))?;
+
+    if let Some(token) = token.filter(|t| !t.is_empty()) {
+        secrets::store(&token_secret_name(&host.id), &token)?;
+    }
+    Ok(summarize(&host, &active_host_id(&updated), None))
+}
+
+// A host without an agent, polled over SSH with the given key. The key
+// must already be authorized on the host; nothing is installed there.
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn add_ssh_host(
+    settings: State<'_, SettingsStore>,
+    name: String,
+    address: String,
+    user: String,
+    key_path: String,
+    port: Option<u16>,
+    mac: Option<String>,
+) -> CommandResult<HostSummary> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(CommandError::InvalidInput("host name is required".to_string()));
+    }
+    let target = SshTarget {
+        address: address.trim().to_string(),
+        port: port.unwrap_or(22),
+        user: user.trim().to_string(),
+        key_path: key_path.trim().to_string(),
+    };
+    ssh_hosts::check_target(&settings.get(), &target)?;
+    if target.port == 0 {
+        return Err(CommandError::InvalidInput("port must be between 1 and 65535".to_string()));
+    }
+    let mac = parse_host_mac(mac)?;
+
+    let mut added = None;
+    let updated = settings.update(|current| {
+        let mut next = current.clone();
+        let host = HostEntry {
+            id: host_id_for(&name, &next.hosts),
+            name: name.clone(),
+            base_url: String::new(),
+            transport: Transport::Ssh,
+            ssh: Some(target.clone()),
+            mac: mac.clone(),
+            wol_broadcast: None,
+            changes_url: None,
+        };
+        next.hosts.push(host.clone());
+        added = Some(host);
+        Ok(next)
+    })?;
+    let host = added.ok_or_else(|| CommandError::Internal("host was not added".to_string()))?;
+    println!("[Halbert] Added SSH host {} ({}@{})", host.id, target.user, target.address);
+    Ok(summarize(&host, &active_host_id(&updated), None))
+}
+
+// None (or empty) clears the MAC or broadcast address
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn set_host_wake(
+    settings: State<'_, SettingsStore>,
+    host_id: String,
+    mac: Option<String>,
+    broadcast: Option<String>,
+) -> CommandResult<HostSummary> {
+    let mac = parse_host_mac(mac)?;
+    let broadcast = broadcast.map(|b| b.trim().to_string()).filter(|b| !b.is_empty());
+    if let Some(addr) = &broadcast {
+        wol::parse_broadcast(Some(addr))?;
+    }
+    let updated = settings.update(|current| {
+        let mut next = current.clone();
+        let host = next
+            .hosts
+            .iter_mut()
+            .find(|h| h.id == host_id)
+            .ok_or_else(|| CommandError::NotFound(format!("host {}", host_id)))?;
+        host.mac = mac.clone();
+        host.wol_broadcast = broadcast.clone();
+        Ok(next)
+    })?;
+    let host = updated
+        .hosts
+        .iter()
+        .find(|h| h.id == host_id)
+        .ok_or_else(|| CommandError::Internal("host vanished during update".to_string()))?;
+    Ok(summarize(host, &active_host_id(&updated), None))
+}
+
+// Points decisions for the host at another Halbert's changes listener, with
+// the token generated there; a None url sends them to base_url again
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn set_host_changes(
+    settings: State<'_, SettingsStore>,
+    host_id: String,
+    changes_url: Option<String>,
+    token: Option<String>,
+) -> CommandResult<HostSummary> {
+    let changes_url = changes_url
+        .filter(|url| !url.trim().is_empty())
+        .map(|url| validate_base_url(&url))
+        .transpose()?;
+    let updated = settings.update(|current| {
+        let mut next = current.clone();
+        let host = next
+            .hosts
+            .iter_mut()
+            .find(|h| h.id == host_id)
+            .ok_or_else(|| CommandError::NotFound(format!("host {}", host_id)))?;
+        require_agent(host)?;
+        host.changes_url = changes_url.clone();
+        Ok(next)
+    })?;
+    match (&changes_url, token.filter(|t| !t.is_empty())) {
+        (Some(_), Some(token)) => secrets::store(&changes_secret_name(&host_id), &token)?,
+        (Some(_), None) => {}
+        (None, _) => secrets::delete(&changes_secret_name(&host_id))?,
+    }
+    let host = updated
+        .hosts
+        .iter()
+        .find(|h| h.id == host_id)
+        .ok_or_else(|| CommandError::Internal("host vanished during update".to_string()))?;
+    Ok(summarize(host, &active_host_id(&updated), None))
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn remove_host(settings: State<'_, SettingsStore>, host_id: String) -> CommandResult<()> {
+    if host_id == LOCAL_HOST_ID {
+        return Err(CommandError::InvalidInput("the local host can't be removed".to_string()));
+    }
+    settings.update(|current| {
+        if !current.hosts.iter().any(|h| h.id == host_id) {
+            return Err(CommandError::NotFound(format!("host {}", host_id)));
+        }
+        let mut next = current.clone();
+        next.hosts.retain(|h| h.id != host_id);
+        if next.active_host.as_deref() == Some(host_id.as_str()) {
+            next.active_host = None;
+        }
+        if next.core_host.as_deref() == Some(host_id.as_str()) {
+            next.core_host = None;
+        }
+        Ok(next)
+    })?;
+    secrets::delete(&changes_secret_name(&host_id))?;
+    secrets::delete(&token_secret_name(&host_id))
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn set_active_host(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    host_id: String,
+) -> CommandResult<HostSummary> {
+    let updated = settings.update(|current| {
+        let mut next = current.clone();
+        if host_id == LOCAL_HOST_ID {
+            next.active_host = None;
+        } else if current.hosts.iter().any(|h| h.id == host_id) {
+            next.active_host = Some(host_id.clone());
+        } else {
+            return Err(CommandError::NotFound(format!("host {}", host_id)));
+        }
+        Ok(next)
+    })?;
+
+    let summary = match active_host(&updated) {
+        ActiveHost::Local => local_summary(LOCAL_HOST_ID),
+        ActiveHost::Remote(host) => summarize(&host, &host.id, None),
+    };
+    let _ = app.emit("hosts://active-changed", &summary);
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::approvals::ApprovalStore;
+    use crate::http::MockServer;
+    use serde_json::{json, Value};
+
+    fn host(id: &str, api: &MockServer, changes: Option<&MockServer>) -> HostEntry {
+        HostEntry {
+            id: id.to_string(),
+            name: "Server".to_string(),
+            base_url: api.base_url.clone(),
+            transport: Transport::Http,
+            ssh: None,
+            mac: None,
+            wol_broadcast: None,
+            changes_url: changes.map(|c| c.base_url.clone()),
+        }
+    }
+
+    fn pending() -> Value {
+        serde_json::to_value(ApprovalStore::with_mock_requests().pending()).unwrap()
+    }
+
+    #[test]
+    fn decisions_go_to_the_changes_listener_when_there_is_one() {
+        let api = MockServer::start(|_| (404, json!({})));
+        let changes = MockServer::start(|_| (200, json!({ "success": true, "message": "Request req-1 approved" })));
+        let said = decide_approval(&host("hosts-test-changes", &api, Some(&changes)), "req-1", true, None).unwrap();
+        assert_eq!(said, "Request req-1 approved on Server");
+        assert!(api.received().is_empty());
+        let received = changes.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!((received[0].method.as_str(), received[0].path.as_str()), ("POST", "/api/approvals/req-1/approve"));
+    }
+
+    #[test]
+    fn without_a_changes_listener_decisions_go_to_the_api() {
+        let api = MockServer::start(|_| (200, json!({ "success": true, "message": "Request req-1 rejected" })));
+        decide_approval(&host("hosts-test-api", &api, None), "req-1", false, Some("no")).unwrap();
+        let received = api.received();
+        assert_eq!(received[0].path, "/api/approvals/req-1/reject");
+        assert_eq!(received[0].body["reason"], "no");
+    }
+
+    #[test]
+    fn a_group_is_approved_on_the_host() {
+        let api = MockServer::start(|_| (404, json!({})));
+        let changes = MockServer::start(|_| (200, pending()));
+        let server = host("hosts-test-group", &api, Some(&changes));
+        let approved = approve_group(&server, "nightly / cleanup").unwrap();
+        assert!(!approved.is_empty());
+        assert!(approved.iter().all(|request| request.host_id == "hosts-test-group"));
+        let received = changes.received();
+        assert_eq!(received[0].path, "/api/approvals/groups/approve");
+        assert_eq!(received[0].body, json!({ "group_id": "nightly / cleanup" }));
+        assert!(api.received().is_empty());
+    }
+
+    #[test]
+    fn ssh_hosts_decide_nothing() {
+        let api = MockServer::start(|_| (200, pending()));
+        let mut server = host("hosts-test-ssh", &api, None);
+        server.transport = Transport::Ssh;
+        assert!(matches!(approve_group(&server, "g"), Err(CommandError::NotSupported(_))));
+        assert!(api.received().is_empty());
+    }
+}
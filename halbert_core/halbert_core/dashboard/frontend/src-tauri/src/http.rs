@@ -0,0 +1,340 @@
+// Shared blocking HTTP plumbing for the backend and remote host clients
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::time::{Duration, Instant};
+
+use crate::backend_latency::{self, Outcome};
+use crate::error::{CommandError, CommandResult};
+
+// Short timeouts: a dead endpoint should fail its panel quickly, not hang it
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(8);
+// Marks a request Halbert made on its own behalf, so another Halbert that
+// would only pass it on to its active host can refuse (see remote_access)
+pub const HOP_HEADER: &str = "X-Halbert-Hop";
+
+pub struct Endpoint<'a> {
+    // Used in error messages, e.g. "backend" or a host name
+    pub label: &'a str,
+    pub base_url: &'a str,
+    pub token: Option<String>,
+    // Requests are recorded in backend_latency
+    pub measured: bool,
+}
+
+// ureq::Error is what ureq returns; boxing it here would only move the size
+#[allow(clippy::result_large_err)]
+impl Endpoint<'_> {
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url.trim_end_matches('/'), path)
+    }
+
+    fn request(&self, method: &str, path: &str, timeout: Duration) -> ureq::Request {
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(CONNECT_TIMEOUT.min(timeout))
+            .timeout(timeout)
+            .build();
+        let mut request = agent.request(method, &self.url(path)).set(HOP_HEADER, "1");
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        request
+    }
+
+    // Runs the exchange, timing it when the endpoint is measured
+    fn timed<F>(&self, path: &str, call: F) -> Result<ureq::Response, ureq::Error>
+    where
+        F: FnOnce() -> Result<ureq::Response, ureq::Error>,
+    {
+        if !self.measured {
+            return call();
+        }
+        let started = Instant::now();
+        let result = call();
+        let outcome = match &result {
+            Ok(response) => Outcome::Status(response.status()),
+            Err(ureq::Error::Status(code, _)) => Outcome::Status(*code),
+            Err(e) if e.to_string().contains("timed out") => Outcome::Timeout,
+            Err(_) => Outcome::Failed,
+        };
+        backend_latency::record(path, started.elapsed(), outcome);
+        result
+    }
+
+    pub fn get_json<T: DeserializeOwned>(&self, path: &str) -> CommandResult<T> {
+        self.finish(path, self.timed(path, || self.request("GET", path, REQUEST_TIMEOUT).call()))
+    }
+
+    pub fn post_json<T: DeserializeOwned>(&self, path: &str, body: &serde_json::Value) -> CommandResult<T> {
+        self.finish(path, self.timed(path, || self.request("POST", path, REQUEST_TIMEOUT).send_json(body)))
+    }
+
+    // Status-only probe with a caller-chosen timeout; returns the HTTP status
+    pub fn probe(&self, path: &str, timeout: Duration) -> CommandResult<u16> {
+        match self.timed(path, || self.request("GET", path, timeout).call()) {
+            Ok(response) => Ok(response.status()),
+            Err(ureq::Error::Status(code, _)) => Ok(code),
+            Err(e) => Err(CommandError::HostUnreachable(format!(
+                "{} ({}): {}",
+                self.label, self.base_url, e
+            ))),
+        }
+    }
+
+    // POST returning the raw response so the caller can stream the body.
+    // `timeout` covers the whole exchange, including reading the body.
+    pub fn post(&self, path: &str, body: &serde_json::Value, timeout: Duration) -> CommandResult<ureq::Response> {
+        self.timed(path, || self.request("POST", path, timeout).send_json(body))
+            .map_err(|e| self.error(path, e))
+    }
+
+    // POST to another Halbert's command route (see remote_core), whose
+    // errors come back as the CommandError it answered with
+    pub fn post_command(&self, path: &str, body: &Value, timeout: Duration) -> CommandResult<Value> {
+        match self.timed(path, || self.request("POST", path, timeout).send_json(body)) {
+            Err(ureq::Error::Status(code, response)) => {
+                Err(response.into_json::<CommandError>().unwrap_or_else(|_| self.status_error(path, code)))
+            }
+            result => self.finish(path, result),
+        }
+    }
+
+    fn finish<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        result: Result<ureq::Response, ureq::Error>,
+    ) -> CommandResult<T> {
+        let response = result.map_err(|e| self.error(path, e))?;
+        response.into_json::<T>().map_err(|e| {
+            CommandError::Remote(format!(
+                "{} sent an unexpected response for {}: {}",
+                self.label, path, e
+            ))
+        })
+    }
+
+    fn error(&self, path: &str, e: ureq::Error) -> CommandError {
+        match e {
+            ureq::Error::Status(code, _) => self.status_error(path, code),
+            e => CommandError::HostUnreachable(format!("{} ({}): {}", self.label, self.base_url, e)),
+        }
+    }
+
+    fn status_error(&self, path: &str, code: u16) -> CommandError {
+        CommandError::Remote(format!("{} answered {} with HTTP {}", self.label, path, code))
+    }
+}
+
+// What request logic needs from the backend, so it can run against a
+// stand-in instead of a live endpoint; Endpoint is the real one
+pub trait BackendApi {
+    fn post_value(&self, path: &str, body: &Value) -> CommandResult<Value>;
+}
+
+impl BackendApi for Endpoint<'_> {
+    fn post_value(&self, path: &str, body: &Value) -> CommandResult<Value> {
+        self.post_json(path, body)
+    }
+}
+
+// Tests drive request logic through StubBackend, or a real Endpoint
+// through MockServer, a loopback HTTP server with canned answers
+#[cfg(test)]
+type Answer = Box<dyn Fn(&Value) -> CommandResult<Value> + Send + Sync>;
+
+// Answers each path with the closure set for it, recording every call
+#[cfg(test)]
+#[derive(Default)]
+pub struct StubBackend {
+    answers: std::collections::HashMap<String, Answer>,
+    calls: std::sync::Mutex<Vec<(String, Value)>>,
+}
+
+#[cfg(test)]
+impl StubBackend {
+    pub fn on<F>(mut self, path: &str, answer: F) -> Self
+    where
+        F: Fn(&Value) -> CommandResult<Value> + Send + Sync + 'static,
+    {
+        self.answers.insert(path.to_string(), Box::new(answer));
+        self
+    }
+
+    pub fn calls(&self) -> Vec<(String, Value)> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl BackendApi for StubBackend {
+    fn post_value(&self, path: &str, body: &Value) -> CommandResult<Value> {
+        self.calls.lock().unwrap().push((path.to_string(), body.clone()));
+        match self.answers.get(path) {
+            Some(answer) => answer(body),
+            None => Err(CommandError::HostUnreachable(format!("stub backend has no answer for {}", path))),
+        }
+    }
+}
+
+// A request as MockServer saw it
+#[cfg(test)]
+#[derive(Clone, Debug)]
+pub struct Received {
+    pub method: String,
+    pub path: String,
+    pub authorization: Option<String>,
+    pub hop: bool,
+    pub body: Value,
+}
+
+#[cfg(test)]
+type Respond = dyn Fn(&Received) -> (u16, Value) + Send + Sync;
+
+// Serves each connection on its own thread until the test process exits. A
+// request is logged before it's answered, so a client that waits for each
+// answer sees them logged in order.
+#[cfg(test)]
+pub struct MockServer {
+    pub base_url: String,
+    received: std::sync::Arc<std::sync::Mutex<Vec<Received>>>,
+}
+
+#[cfg(test)]
+impl MockServer {
+    pub fn start<F>(respond: F) -> Self
+    where
+        F: Fn(&Received) -> (u16, Value) + Send + Sync + 'static,
+    {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("loopback listener");
+        let base_url = format!("http://{}", listener.local_addr().expect("local address"));
+        let received = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = received.clone();
+        let respond: std::sync::Arc<Respond> = std::sync::Arc::new(respond);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().map_while(Result::ok) {
+                let (respond, log) = (respond.clone(), log.clone());
+                std::thread::spawn(move || Self::serve(stream, respond.as_ref(), &log));
+            }
+        });
+        MockServer { base_url, received }
+    }
+
+    fn serve(stream: std::net::TcpStream, respond: &Respond, log: &std::sync::Mutex<Vec<Received>>) -> Option<()> {
+        use std::io::{BufRead, Read, Write};
+        let mut reader = std::io::BufReader::new(stream.try_clone().ok()?);
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let mut parts = line.split_whitespace();
+        let (method, path) = (parts.next()?.to_string(), parts.next()?.to_string());
+        let (mut length, mut authorization, mut hop) = (0, None, false);
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).ok()?;
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            let (name, value) = header.split_once(':')?;
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => length = value.trim().parse().ok()?,
+                "authorization" => authorization = Some(value.trim().to_string()),
+                name if name == HOP_HEADER.to_ascii_lowercase() => hop = true,
+                _ => {}
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).ok()?;
+        let request = Received {
+            method,
+            path,
+            authorization,
+            hop,
+            body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+        };
+        log.lock().unwrap().push(request.clone());
+        let (status, answer) = respond(&request);
+        let answer = answer.to_string();
+        let mut stream = stream;
+        let _ = write!(
+            stream,
+            "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            answer.len(),
+            answer
+        );
+        Some(())
+    }
+
+    pub fn endpoint(&self) -> Endpoint<'_> {
+        Endpoint {
+            label: "mock",
+            base_url: &self.base_url,
+            token: Some("secret".to_string()),
+            measured: false,
+        }
+    }
+
+    pub fn received(&self) -> Vec<Received> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn posts_json_with_the_token() {
+        let server = MockServer::start(|request| (200, json!({ "echo": request.body })));
+        let answer: Value = server.endpoint().post_json("/api/echo", &json!({ "n": 1 })).unwrap();
+        assert_eq!(answer, json!({ "echo": { "n": 1 } }));
+        let received = server.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].method, "POST");
+        assert_eq!(received[0].path, "/api/echo");
+        assert_eq!(received[0].authorization.as_deref(), Some("Bearer secret"));
+        assert!(received[0].hop);
+    }
+
+    #[test]
+    fn an_error_status_is_a_remote_error() {
+        let server = MockServer::start(|_| (500, json!({ "detail": "boom" })));
+        match server.endpoint().get_json::<Value>("/api/status") {
+            Err(CommandError::Remote(message)) => assert!(message.contains("HTTP 500"), "{}", message),
+            other => panic!("expected a remote error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn probe_reports_the_status_or_unreachable() {
+        let server = MockServer::start(|_| (503, Value::Null));
+        assert_eq!(server.endpoint().probe("/api/status", Duration::from_secs(2)).unwrap(), 503);
+
+        // Nothing listens on a port that was just released
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let base_url = format!("http://127.0.0.1:{}", port);
+        let closed = Endpoint {
+            label: "mock",
+            base_url: &base_url,
+            token: None,
+            measured: false,
+        };
+        assert!(matches!(
+            closed.probe("/api/status", Duration::from_secs(2)),
+            Err(CommandError::HostUnreachable(_))
+        ));
+    }
+
+    #[test]
+    fn stub_records_calls_and_refuses_unknown_paths() {
+        let stub = StubBackend::default().on("/api/known", |body| Ok(json!({ "got": body })));
+        assert_eq!(stub.post_value("/api/known", &json!(1)).unwrap(), json!({ "got": 1 }));
+        assert!(matches!(
+            stub.post_value("/api/unknown", &json!(2)),
+            Err(CommandError::HostUnreachable(_))
+        ));
+        let paths: Vec<String> = stub.calls().into_iter().map(|(path, _)| path).collect();
+        assert_eq!(paths, ["/api/known", "/api/unknown"]);
+    }
+}
@@ -0,0 +1,271 @@
+// Expands an approval's `affected_resources` into what it would touch.
+//
+// Resource strings are classified by shape:
+//   "/var/log/*.gz", "~/.cache/*"  file globs (first MAX_GLOB_MATCHES paths)
+//   "service:nginx.service"        systemd unit state
+//   "package:openssl", "systemd"   installed vs candidate version
+//   "1234:firefox"                 process still running
+// Everything shares one deadline. Lookups still running when it passes are
+// abandoned and the result is flagged `incomplete` instead of blocking. A
+// cancelled token (see cancellation) does the same at once and leaves the
+// remaining resources out.
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use crate::cancellation::{self, CancellationToken, CHECK_INTERVAL};
+use crate::packages::{self, UpdateInventory};
+use crate::processes;
+use crate::services;
+
+const MAX_GLOB_MATCHES: usize = 500;
+const EXPANSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Clone)]
+pub struct PathImpact {
+    pub path: String,
+    pub size_bytes: u64,
+    pub is_dir: bool,
+    pub modified: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ResourceImpact {
+    Files {
+        resource: String,
+        pattern: String,
+        paths: Vec<PathImpact>,
+        total_bytes: u64,
+        // More than MAX_GLOB_MATCHES paths matched
+        truncated: bool,
+        incomplete: bool,
+    },
+    Package {
+        resource: String,
+        name: String,
+        current_version: Option<String>,
+        candidate_version: Option<String>,
+        incomplete: bool,
+    },
+    Service {
+        resource: String,
+        unit: String,
+        active_state: Option<String>,
+        incomplete: bool,
+    },
+    Process {
+        resource: String,
+        pid: u32,
+        name: String,
+        running: Option<bool>,
+        incomplete: bool,
+    },
+    Unknown {
+        resource: String,
+    },
+}
+
+#[derive(Serialize, Clone)]
+pub struct ImpactSummary {
+    pub resources: Vec<ResourceImpact>,
+    pub total_files: usize,
+    pub total_bytes: u64,
+    pub incomplete: bool,
+    pub generated_at: String,
+}
+
+pub fn expand(resources: &[String], token: &CancellationToken) -> ImpactSummary {
+    let deadline = Instant::now() + EXPANSION_TIMEOUT;
+    // Fetched at most once, and only if a package resource needs it
+    let mut inventory: Option<Option<UpdateInventory>> = None;
+
+    let resources: Vec<ResourceImpact> = resources
+        .iter()
+        .take_while(|_| !token.is_cancelled())
+        .map(|resource| expand_one(resource, deadline, token, &mut inventory))
+        .collect();
+
+    let mut total_files = 0;
+    let mut total_bytes = 0;
+    let mut incomplete = false;
+    for impact in &resources {
+        match impact {
+            ResourceImpact::Files {
+                paths,
+                total_bytes: bytes,
+                incomplete: partial,
+                ..
+            } => {
+                total_files += paths.len();
+                total_bytes += bytes;
+                incomplete |= partial;
+            }
+            ResourceImpact::Package { incomplete: partial, .. }
+            | ResourceImpact::Service { incomplete: partial, .. }
+            | ResourceImpact::Process { incomplete: partial, .. } => incomplete |= partial,
+            ResourceImpact::Unknown { .. } => {}
+        }
+    }
+
+    ImpactSummary {
+        resources,
+        total_files,
+        total_bytes,
+        incomplete,
+        generated_at: chrono::Utc::now().to_rfc3339(),
+    }
+}
+
+fn expand_one(
+    resource: &str,
+    deadline: Instant,
+    token: &CancellationToken,
+    inventory: &mut Option<Option<UpdateInventory>>,
+) -> ResourceImpact {
+    let owned = resource.to_string();
+
+    if resource.starts_with('/') || resource.starts_with("~/") {
+        return expand_glob(owned, deadline, token);
+    }
+    if let Some(unit) = resource.strip_prefix("service:") {
+        let unit = unit.to_string();
+        let lookup = unit.clone();
+        let state = cancellation::bounded(deadline, token, move || services::unit_active_state(&lookup));
+        return ResourceImpact::Service {
+            resource: owned,
+            unit,
+            incomplete: state.is_none(),
+            active_state: state.flatten(),
+        };
+    }
+    if let Some((pid, name)) = resource.split_once(':') {
+        if let Ok(pid) = pid.parse::<u32>() {
+            let expected = name.to_string();
+            let running = cancellation::bounded(deadline, token, move || {
+                processes::lookup(pid).map(|t| t.name == expected).unwrap_or(false)
+            });
+            return ResourceImpact::Process {
+                resource: owned,
+                pid,
+                name: name.to_string(),
+                incomplete: running.is_none(),
+                running,
+            };
+        }
+    }
+
+    let name = resource.strip_prefix("package:").unwrap_or(resource);
+    if !is_package_name(name) {
+        return ResourceImpact::Unknown { resource: owned };
+    }
+    let mut incomplete = false;
+    if inventory.is_none() {
+        match cancellation::bounded(deadline, token, packages::pending_updates) {
+            Some(found) => *inventory = Some(found),
+            None => incomplete = true,
+        }
+    }
+    let update = inventory
+        .as_ref()
+        .and_then(|inv| inv.as_ref())
+        .and_then(|inv| inv.updates.iter().find(|u| u.name == name).cloned());
+
+    let current_version = match update.as_ref().and_then(|u| u.current_version.clone()) {
+        Some(version) => Some(version),
+        None => {
+            let lookup = name.to_string();
+            let installed = cancellation::bounded(deadline, token, move || packages::installed_version(&lookup));
+            incomplete |= installed.is_none();
+            installed.flatten()
+        }
+    };
+
+    ResourceImpact::Package {
+        resource: owned,
+        name: name.to_string(),
+        current_version,
+        candidate_version: update.map(|u| u.candidate_version),
+        incomplete,
+    }
+}
+
+fn expand_glob(resource: String, deadline: Instant, token: &CancellationToken) -> ResourceImpact {
+    let pattern = expand_home(&resource);
+
+    let (tx, rx) = mpsc::channel();
+    let cancel = Arc::new(AtomicBool::new(false));
+    let walker_cancel = cancel.clone();
+    let walker_pattern = pattern.clone();
+    std::thread::spawn(move || {
+        let Ok(paths) = glob::glob(&walker_pattern) else {
+            return;
+        };
+        // One extra match tells us the list was truncated
+        for path in paths.filter_map(Result::ok).take(MAX_GLOB_MATCHES + 1) {
+            if walker_cancel.load(Ordering::Relaxed) || tx.send(path_impact(path)).is_err() {
+                return;
+            }
+        }
+    });
+
+    let mut paths = Vec::new();
+    let mut truncated = false;
+    let mut incomplete = false;
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() || token.is_cancelled() {
+            incomplete = true;
+            break;
+        }
+        match rx.recv_timeout(left.min(CHECK_INTERVAL)) {
+            Ok(_) if paths.len() == MAX_GLOB_MATCHES => {
+                truncated = true;
+                break;
+            }
+            Ok(path) => paths.push(path),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    cancel.store(true, Ordering::Relaxed);
+
+    ResourceImpact::Files {
+        resource,
+        pattern,
+        total_bytes: paths.iter().map(|p| p.size_bytes).sum(),
+        paths,
+        truncated,
+        incomplete,
+    }
+}
+
+pub fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}/{}", home.trim_end_matches('/'), rest),
+        _ => path.to_string(),
+    }
+}
+
+// Symlinks are described, not followed
+fn path_impact(path: PathBuf) -> PathImpact {
+    let meta = std::fs::symlink_metadata(&path).ok();
+    PathImpact {
+        path: path.display().to_string(),
+        size_bytes: meta.as_ref().map(|m| m.len()).unwrap_or(0),
+        is_dir: meta.as_ref().map(|m| m.is_dir()).unwrap_or(false),
+        modified: meta
+            .and_then(|m| m.modified().ok())
+            .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339()),
+    }
+}
+
+fn is_package_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-' | '_'))
+}
@@ -0,0 +1,534 @@
+// OOM kills, segfaults and crashed units, recorded as incidents.
+//
+// A background thread polls the journal every POLL_INTERVAL for kernel
+// messages and systemd "unit failed" entries, resuming after the cursor it
+// persisted last time so a restart doesn't report anything twice. On the
+// very first run it starts from now rather than replaying old boots. Each
+// incident carries the memory use from the nearest local history sample,
+// is announced as `incidents://new` and goes to webhooks subscribed to its
+// kind. Alert rules see the `incident.count` signal per kind.
+//
+// The kernel's OOM report changed over the years: before 5.x the victim
+// line was "Killed process N (name) total-vm:..."; later kernels print
+// "Out of memory: Killed process N (name) ..." and, since 4.19, a
+// preceding "oom-kill:...,task_memcg=...,pid=N" line naming the cgroup.
+// Only the "Killed process" line makes an incident; the oom-kill line just
+// lends it the unit.
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::app::{AppHandle, Emitter, Manager, State};
+use crate::changes;
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::sampler::MetricsHistory;
+use crate::settings::SettingsStore;
+use crate::timestamps::{self, Clock, Localize, TimeDisplay};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+const CURSOR_NAME: &str = "incidents";
+// systemd's "unit entered failed state" journal message
+const UNIT_FAILED_MESSAGE_ID: &str = "d9b373ed55a64feb8242e02dbe79a49c";
+// A history sample further away than this doesn't describe the incident
+const MEMORY_SAMPLE_WINDOW_SECS: i64 = 300;
+const MAX_INCIDENTS: u32 = 500;
+
+pub const KINDS: &[&str] = &["oom_kill", "segfault", "unit_crash"];
+
+#[derive(Serialize, Clone)]
+pub struct Incident {
+    pub id: i64,
+    pub at: String,
+    // One of KINDS
+    pub kind: String,
+    // Process name, or the unit for unit_crash
+    pub victim: String,
+    pub pid: Option<u32>,
+    // The systemd unit the victim belonged to, where known
+    pub unit: Option<String>,
+    pub detail: String,
+    // Host memory use at the nearest history sample
+    pub memory_percent: Option<f32>,
+    // Filled in per response; see timestamps
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub at_display: Option<TimeDisplay>,
+}
+
+impl Localize for Incident {
+    fn localize(&mut self, clock: &Clock) {
+        self.at_display = clock.display(&self.at);
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum KernelEvent {
+    OomKilled {
+        pid: u32,
+        process: String,
+        // Resident anonymous memory of the victim, kB
+        anon_rss_kb: Option<u64>,
+        // Killed for a cgroup limit rather than the whole machine
+        cgroup: bool,
+    },
+    // The 4.19+ summary line; only contributes the cgroup
+    OomContext {
+        pid: u32,
+        memcg: String,
+    },
+    Segfault {
+        pid: u32,
+        process: String,
+        detail: String,
+    },
+}
+
+// "1234 (java) total-vm:..." -> (1234, "java", rest)
+fn pid_and_name(text: &str) -> Option<(u32, String, &str)> {
+    let (pid, rest) = text.split_once(" (")?;
+    let (name, rest) = rest.split_once(')')?;
+    Some((pid.trim().parse().ok()?, name.to_string(), rest))
+}
+
+fn kb_field(text: &str, key: &str) -> Option<u64> {
+    let start = text.find(key)? + key.len();
+    text[start..].split("kB").next()?.trim().parse().ok()
+}
+
+pub fn parse_kernel_line(message: &str) -> Option<KernelEvent> {
+    if let Some(at) = message.find("Killed process ") {
+        let (pid, process, rest) = pid_and_name(&message[at + "Killed process ".len()..])?;
+        return Some(KernelEvent::OomKilled {
+            pid,
+            process,
+            anon_rss_kb: kb_field(rest, "anon-rss:"),
+            cgroup: message.starts_with("Memory cgroup"),
+        });
+    }
+    if let Some(fields) = message.strip_prefix("oom-kill:") {
+        let fields: HashMap<&str, &str> = fields.split(',').filter_map(|f| f.split_once('=')).collect();
+        return Some(KernelEvent::OomContext {
+            pid: fields.get("pid")?.parse().ok()?,
+            memcg: fields.get("task_memcg")?.to_string(),
+        });
+    }
+    // "java[1234]: segfault at 0 ip 00007f... sp 00007ff... error 4 in libc.so.6[7f..+1a000]"
+    // 6.x appends " likely on CPU 3 (core 3, socket 0)"
+    let (who, rest) = message.split_once(": segfault at ")?;
+    let (process, pid) = who.trim().rsplit_once('[')?;
+    let pid = pid.strip_suffix(']')?.parse().ok()?;
+    let address = rest.split_whitespace().next().unwrap_or_default();
+    let object = rest
+        .split_once(" in ")
+        .map(|(_, obj)| obj.split('[').next().unwrap_or(obj).trim());
+    let detail = match object {
+        Some(object) => format!("segfault at {} in {}", address, object),
+        None => format!("segfault at {}", address),
+    };
+    Some(KernelEvent::Segfault {
+        pid,
+        process: process.to_string(),
+        detail,
+    })
+}
+
+// "/system.slice/postgresql.service" -> "postgresql.service"; None for
+// cgroups that aren't a unit ("/", "/user.slice")
+pub fn unit_from_cgroup(memcg: &str) -> Option<String> {
+    memcg
+        .rsplit('/')
+        .find(|part| part.ends_with(".service") || part.ends_with(".scope"))
+        .map(str::to_string)
+}
+
+// An incident before it's stored
+#[derive(Debug, PartialEq)]
+pub struct Draft {
+    pub at_micros: i64,
+    pub kind: &'static str,
+    pub victim: String,
+    pub pid: Option<u32>,
+    pub unit: Option<String>,
+    pub detail: String,
+}
+
+fn field<'a>(entry: &'a Value, name: &str) -> Option<&'a str> {
+    entry.get(name).and_then(Value::as_str)
+}
+
+// Turn one batch of journal entries into incidents, in order
+pub fn drafts_from_entries(entries: &[Value]) -> Vec<Draft> {
+    let mut memcgs: HashMap<u32, String> = HashMap::new();
+    let mut drafts = Vec::new();
+    for entry in entries {
+        let at_micros = field(entry, "__REALTIME_TIMESTAMP").and_then(|t| t.parse().ok()).unwrap_or(0);
+        let message = field(entry, "MESSAGE").unwrap_or_default();
+        if field(entry, "MESSAGE_ID") == Some(UNIT_FAILED_MESSAGE_ID) {
+            // Only crashes; plain non-zero exits are the change digest's business
+            if field(entry, "UNIT_RESULT") != Some("core-dump") {
+                continue;
+            }
+            let Some(unit) = field(entry, "UNIT").or_else(|| field(entry, "USER_UNIT")) else {
+                continue;
+            };
+            drafts.push(Draft {
+                at_micros,
+                kind: "unit_crash",
+                victim: unit.to_string(),
+                pid: None,
+                unit: Some(unit.to_string()),
+                detail: message.to_string(),
+            });
+            continue;
+        }
+        if field(entry, "_TRANSPORT") != Some("kernel") {
+            continue;
+        }
+        match parse_kernel_line(message) {
+            Some(KernelEvent::OomContext { pid, memcg }) => {
+                memcgs.insert(pid, memcg);
+            }
+            Some(KernelEvent::OomKilled {
+                pid,
+                process,
+                anon_rss_kb,
+                cgroup,
+            }) => {
+                let scope = if cgroup { "cgroup memory limit" } else { "system out of memory" };
+                let detail = match anon_rss_kb {
+                    Some(kb) => format!("{}; victim had {} MiB resident", scope, kb / 1024),
+                    None => scope.to_string(),
+                };
+                drafts.push(Draft {
+                    at_micros,
+                    kind: "oom_kill",
+                    victim: process,
+                    pid: Some(pid),
+                    unit: memcgs.get(&pid).and_then(|m| unit_from_cgroup(m)),
+                    detail,
+                });
+            }
+            Some(KernelEvent::Segfault { pid, process, detail }) => drafts.push(Draft {
+                at_micros,
+                kind: "segfault",
+                victim: process,
+                pid: Some(pid),
+                unit: None,
+                detail,
+            }),
+            None => {}
+        }
+    }
+    drafts
+}
+
+fn load_cursor(db: &Database) -> CommandResult<Option<String>> {
+    db.with_conn(|conn| {
+        conn.query_row("SELECT cursor FROM journal_cursors WHERE name = ?1", [CURSOR_NAME], |r| r.get(0))
+            .optional()
+    })
+}
+
+fn save_cursor(db: &Database, cursor: &str) -> CommandResult<()> {
+    db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO journal_cursors (name, cursor) VALUES (?1, ?2)
+             ON CONFLICT(name) DO UPDATE SET cursor = excluded.cursor",
+            params![CURSOR_NAME, cursor],
+        )
+    })?;
+    Ok(())
+}
+
+// Entries after `cursor`, or since `fallback_since` (unix seconds) without one
+fn read_journal(cursor: Option<&str>, fallback_since: i64) -> Option<Vec<Value>> {
+    let position = match cursor {
+        Some(cursor) => format!("--after-cursor={}", cursor),
+        None => format!("--since=@{}", fallback_since),
+    };
+    let unit_failed = format!("MESSAGE_ID={}", UNIT_FAILED_MESSAGE_ID);
+    let args = ["-q", "--no-pager", "-o", "json", &position, "_TRANSPORT=kernel", "+", &unit_failed];
+    let out = exec::stdout("journalctl", &args)?;
+    Some(out.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+}
+
+fn record(app: &AppHandle, db: &Database, draft: Draft) -> CommandResult<Incident> {
+    let Draft {
+        at_micros,
+        kind,
+        victim,
+        pid,
+        unit,
+        detail,
+    } = draft;
+    let memory_percent = app
+        .state::<MetricsHistory>()
+        .nearest(at_micros / 1_000_000, MEMORY_SAMPLE_WINDOW_SECS)
+        .map(|p| p.memory_percent);
+    let at = chrono::DateTime::from_timestamp_micros(at_micros)
+        .map(|t| t.to_rfc3339())
+        .unwrap_or_default();
+    let id = db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO incidents (at, kind, victim, pid, unit, detail, memory_percent)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![at, kind, victim, pid, unit, detail, memory_percent],
+        )?;
+        Ok(conn.last_insert_rowid())
+    })?;
+    Ok(Incident {
+        id,
+        at,
+        kind: kind.to_string(),
+        victim,
+        pid,
+        unit,
+        detail,
+        memory_percent,
+        at_display: None,
+    })
+}
+
+fn poll(app: &AppHandle, fallback_since: i64) -> Result<(), String> {
+    let db = app.state::<Database>();
+    let cursor = load_cursor(&db).map_err(|e| e.to_string())?;
+    let entries = read_journal(cursor.as_deref(), fallback_since).ok_or_else(|| "journalctl failed".to_string())?;
+    let Some(last_cursor) = entries.last().and_then(|e| field(e, "__CURSOR")) else {
+        return Ok(());
+    };
+    for draft in drafts_from_entries(&entries) {
+        let incident = record(app, &db, draft).map_err(|e| e.to_string())?;
+        println!("[Halbert] Incident: {} {} ({})", incident.kind, incident.victim, incident.detail);
+        let _ = app.emit("incidents://new", &incident);
+        crate::notifications::incident_new(app, &incident);
+    }
+    // Saved after recording, so a crash in between repeats rather than loses
+    save_cursor(&db, last_cursor).map_err(|e| e.to_string())
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        if exec::find_in_path("journalctl").is_none() {
+            println!("[Halbert] journalctl not found; OOM and crash incidents are unavailable");
+            return;
+        }
+        let started = chrono::Utc::now().timestamp();
+        let mut reported = false;
+        loop {
+            match poll(&app, started) {
+                Ok(()) => reported = false,
+                Err(e) if !reported => {
+                    println!("[Halbert] Incident watcher: {}", e);
+                    reported = true;
+                }
+                Err(_) => {}
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
+
+// Incidents per kind since `since` (RFC 3339), for alert signals
+pub fn counts_since(db: &Database, since: &str) -> CommandResult<Vec<(String, u32)>> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare("SELECT kind, COUNT(*) FROM incidents WHERE at >= ?1 GROUP BY kind")?;
+        let rows = stmt.query_map([since], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        rows.collect()
+    })
+}
+
+// Newest first
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_incidents(
+    db: State<'_, Database>,
+    settings: State<'_, SettingsStore>,
+    since: Option<String>,
+    kind: Option<String>,
+) -> CommandResult<Vec<Incident>> {
+    let since = since.map(|s| changes::parse_since(&s)).transpose()?.map(|t| t.to_rfc3339());
+    if let Some(kind) = &kind {
+        if !KINDS.contains(&kind.as_str()) {
+            return Err(CommandError::InvalidInput(format!(
+                "unknown incident kind '{}', expected one of {}",
+                kind,
+                KINDS.join(", ")
+            )));
+        }
+    }
+    let incidents = list(&db, since.as_deref(), kind.as_deref())?;
+    Ok(timestamps::localized(&settings.get(), incidents))
+}
+
+// Newest first, at most MAX_INCIDENTS; `since` is RFC 3339
+pub fn list(db: &Database, since: Option<&str>, kind: Option<&str>) -> CommandResult<Vec<Incident>> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, at, kind, victim, pid, unit, detail, memory_percent FROM incidents
+             WHERE (?1 IS NULL OR at >= ?1) AND (?2 IS NULL OR kind = ?2)
+             ORDER BY at DESC, id DESC LIMIT ?3",
+        )?;
+        let rows = stmt.query_map(params![since, kind, MAX_INCIDENTS], |r| {
+            Ok(Incident {
+                id: r.get(0)?,
+                at: r.get(1)?,
+                kind: r.get(2)?,
+                victim: r.get(3)?,
+                pid: r.get(4)?,
+                unit: r.get(5)?,
+                detail: r.get(6)?,
+                memory_percent: r.get(7)?,
+                at_display: None,
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn oom(pid: u32, process: &str, anon_rss_kb: Option<u64>, cgroup: bool) -> Option<KernelEvent> {
+        Some(KernelEvent::OomKilled {
+            pid,
+            process: process.to_string(),
+            anon_rss_kb,
+            cgroup,
+        })
+    }
+
+    #[test]
+    fn oom_victim_lines_across_kernel_versions() {
+        // 3.10 (CentOS 7): the selection line says "Kill process" and isn't the kill
+        assert_eq!(
+            parse_kernel_line("Out of memory: Kill process 1234 (java) score 902 or sacrifice child"),
+            None
+        );
+        assert_eq!(
+            parse_kernel_line("Killed process 1234 (java) total-vm:8388608kB, anon-rss:4194304kB, file-rss:0kB, shmem-rss:0kB"),
+            oom(1234, "java", Some(4194304), false)
+        );
+        // 5.4 (Ubuntu 20.04)
+        assert_eq!(
+            parse_kernel_line(
+                "Out of memory: Killed process 4321 (postgres) total-vm:2254560kB, anon-rss:1843200kB, \
+                 file-rss:0kB, shmem-rss:12kB, UID:26 pgtables:3816kB oom_score_adj:0"
+            ),
+            oom(4321, "postgres", Some(1843200), false)
+        );
+        // 6.x, for a cgroup limit
+        assert_eq!(
+            parse_kernel_line(
+                "Memory cgroup out of memory: Killed process 777 (node) total-vm:1206940kB, anon-rss:524288kB, \
+                 file-rss:40960kB, shmem-rss:0kB, UID:1000 pgtables:1744kB oom_score_adj:200"
+            ),
+            oom(777, "node", Some(524288), true)
+        );
+        // A process name with a space in it
+        assert_eq!(
+            parse_kernel_line("Out of memory: Killed process 55 (Web Content) total-vm:1kB, anon-rss:2kB, file-rss:0kB"),
+            oom(55, "Web Content", Some(2), false)
+        );
+    }
+
+    #[test]
+    fn oom_context_names_the_cgroup() {
+        let line = "oom-kill:constraint=CONSTRAINT_NONE,nodemask=(null),cpuset=/,mems_allowed=0,global_oom,\
+                    task_memcg=/system.slice/postgresql.service,task=postgres,pid=4321,uid=26";
+        assert_eq!(
+            parse_kernel_line(line),
+            Some(KernelEvent::OomContext {
+                pid: 4321,
+                memcg: "/system.slice/postgresql.service".to_string(),
+            })
+        );
+        assert_eq!(unit_from_cgroup("/system.slice/postgresql.service").as_deref(), Some("postgresql.service"));
+        assert_eq!(
+            unit_from_cgroup("/user.slice/user-1000.slice/user@1000.service/app.slice/app-code-1234.scope").as_deref(),
+            Some("app-code-1234.scope")
+        );
+        assert_eq!(unit_from_cgroup("/"), None);
+        assert_eq!(unit_from_cgroup("/user.slice"), None);
+    }
+
+    #[test]
+    fn segfaults_with_and_without_the_cpu_suffix() {
+        let expected = Some(KernelEvent::Segfault {
+            pid: 9876,
+            process: "python3".to_string(),
+            detail: "segfault at 0 in libc.so.6".to_string(),
+        });
+        let old = "python3[9876]: segfault at 0 ip 00007f3a1c2b4e50 sp 00007ffd5a3c1e08 error 4 in libc.so.6[7f3a1c200000+195000]";
+        assert_eq!(parse_kernel_line(old), expected);
+        let new = format!("{} likely on CPU 3 (core 3, socket 0)", old);
+        assert_eq!(parse_kernel_line(&new), expected);
+        assert_eq!(parse_kernel_line("usb 1-2: new high-speed USB device number 4"), None);
+    }
+
+    fn kernel(at: u64, message: &str) -> Value {
+        json!({ "__REALTIME_TIMESTAMP": at.to_string(), "_TRANSPORT": "kernel", "MESSAGE": message })
+    }
+
+    #[test]
+    fn journal_entries_become_incidents_in_order() {
+        let entries = [
+            kernel(
+                1_000,
+                "oom-kill:constraint=CONSTRAINT_MEMCG,nodemask=(null),cpuset=/,mems_allowed=0,\
+                 oom_memcg=/system.slice/redis.service,task_memcg=/system.slice/redis.service,task=redis-server,pid=42,uid=999",
+            ),
+            kernel(1_001, "Memory cgroup out of memory: Killed process 42 (redis-server) total-vm:1kB, anon-rss:2048kB, file-rss:0kB"),
+            // Someone's own log line isn't the kernel
+            json!({ "__REALTIME_TIMESTAMP": "1002", "_TRANSPORT": "syslog", "MESSAGE": "Killed process 7 (fake) anon-rss:1kB" }),
+            json!({
+                "__REALTIME_TIMESTAMP": "1003",
+                "MESSAGE_ID": UNIT_FAILED_MESSAGE_ID,
+                "UNIT": "nginx.service",
+                "UNIT_RESULT": "core-dump",
+                "MESSAGE": "nginx.service: Failed with result 'core-dump'.",
+            }),
+            json!({
+                "__REALTIME_TIMESTAMP": "1004",
+                "MESSAGE_ID": UNIT_FAILED_MESSAGE_ID,
+                "UNIT": "backup.service",
+                "UNIT_RESULT": "exit-code",
+                "MESSAGE": "backup.service: Failed with result 'exit-code'.",
+            }),
+            kernel(1_005, "Out of memory: Killed process 9 (stress) total-vm:1kB, file-rss:0kB"),
+        ];
+        let drafts = drafts_from_entries(&entries);
+        assert_eq!(
+            drafts,
+            [
+                Draft {
+                    at_micros: 1_001,
+                    kind: "oom_kill",
+                    victim: "redis-server".to_string(),
+                    pid: Some(42),
+                    unit: Some("redis.service".to_string()),
+                    detail: "cgroup memory limit; victim had 2 MiB resident".to_string(),
+                },
+                Draft {
+                    at_micros: 1_003,
+                    kind: "unit_crash",
+                    victim: "nginx.service".to_string(),
+                    pid: None,
+                    unit: Some("nginx.service".to_string()),
+                    detail: "nginx.service: Failed with result 'core-dump'.".to_string(),
+                },
+                Draft {
+                    at_micros: 1_005,
+                    kind: "oom_kill",
+                    victim: "stress".to_string(),
+                    pid: Some(9),
+                    unit: None,
+                    detail: "system out of memory".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,368 @@
+// Live progress for jobs running on remote hosts.
+//
+// Remote agents offer no push channel, so every active job get_active_jobs
+// sees on a remote host is tracked here and its /api/jobs/<id> polled. Each
+// job has its own interval (see Backoff): FAST while its status, progress
+// or log keeps changing, doubling up to SLOW while it sits still, and back
+// to FAST after any change. A change goes out as `jobs://update`, the same
+// event local jobs use, so the UI doesn't care where a job runs. A job is
+// dropped once it finishes, disappears, or its host is removed. At most
+// MAX_IN_FLIGHT polls run at once; the rest wait their turn, most overdue
+// first.
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::app::{AppHandle, Emitter, Manager};
+use crate::error::CommandError;
+use crate::hosts;
+use crate::jobs::Job;
+use crate::settings::SettingsStore;
+
+pub const FAST: Duration = Duration::from_secs(1);
+pub const SLOW: Duration = Duration::from_secs(30);
+const MAX_IN_FLIGHT: usize = 4;
+// How long the loop sleeps with nothing tracked
+const IDLE_WAIT: Duration = Duration::from_secs(60);
+
+// What counts as a change
+#[derive(Clone, Debug, PartialEq)]
+struct Snapshot {
+    status: String,
+    progress: f32,
+    log_lines: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Backoff {
+    interval: Duration,
+    last: Option<Snapshot>,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            interval: FAST,
+            last: None,
+        }
+    }
+}
+
+impl Backoff {
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    // After a poll that answered; true when the job changed. The first
+    // observation always counts as a change.
+    pub fn observe(&mut self, status: &str, progress: f32, log_lines: usize) -> bool {
+        let snapshot = Snapshot {
+            status: status.to_string(),
+            progress,
+            log_lines,
+        };
+        let changed = self.last.as_ref() != Some(&snapshot);
+        self.interval = if changed { FAST } else { (self.interval * 2).min(SLOW) };
+        self.last = Some(snapshot);
+        changed
+    }
+
+    // A poll that failed waits as if nothing had changed
+    pub fn failed(&mut self) {
+        self.interval = (self.interval * 2).min(SLOW);
+    }
+}
+
+struct Tracked {
+    host_id: String,
+    job_id: String,
+    backoff: Backoff,
+    due: Instant,
+    in_flight: bool,
+}
+
+#[derive(Default)]
+pub struct JobPoller {
+    tracked: Mutex<Vec<Tracked>>,
+    changed: Condvar,
+}
+
+impl JobPoller {
+    // An active job from a remote host; ones already tracked keep their pace
+    pub fn track(&self, job: &Job) {
+        if job.host_id == hosts::LOCAL_HOST_ID || !job.is_active() {
+            return;
+        }
+        let mut tracked = self.tracked.lock().unwrap();
+        if tracked.iter().any(|t| t.host_id == job.host_id && t.job_id == job.id) {
+            return;
+        }
+        let mut backoff = Backoff::default();
+        backoff.observe(&job.status, job.progress, job.logs.len());
+        tracked.push(Tracked {
+            host_id: job.host_id.clone(),
+            job_id: job.id.clone(),
+            due: Instant::now() + backoff.interval(),
+            backoff,
+            in_flight: false,
+        });
+        self.changed.notify_one();
+    }
+
+    // Every tracked job is due now and back at FAST, e.g. after a resume
+    pub fn poll_all(&self) {
+        let mut tracked = self.tracked.lock().unwrap();
+        let now = Instant::now();
+        for t in tracked.iter_mut() {
+            t.backoff.interval = FAST;
+            t.due = now;
+        }
+        self.changed.notify_one();
+    }
+
+    // Marks up to the free slots' worth of due jobs in flight and returns
+    // them, with how long to wait before looking again
+    fn take_due(&self, now: Instant) -> (Vec<(String, String)>, Duration) {
+        let mut tracked = self.tracked.lock().unwrap();
+        let slots = MAX_IN_FLIGHT.saturating_sub(tracked.iter().filter(|t| t.in_flight).count());
+        let mut due: Vec<&mut Tracked> = tracked.iter_mut().filter(|t| !t.in_flight && t.due <= now).collect();
+        due.sort_by_key(|t| t.due);
+        let taken: Vec<(String, String)> = due
+            .into_iter()
+            .take(slots)
+            .map(|t| {
+                t.in_flight = true;
+                (t.host_id.clone(), t.job_id.clone())
+            })
+            .collect();
+        let wait = tracked
+            .iter()
+            .filter(|t| !t.in_flight)
+            .map(|t| t.due.saturating_duration_since(now))
+            .min()
+            .unwrap_or(IDLE_WAIT);
+        (taken, wait)
+    }
+
+    fn wait(&self, timeout: Duration) {
+        let tracked = self.tracked.lock().unwrap();
+        drop(self.changed.wait_timeout(tracked, timeout).unwrap());
+    }
+
+    // `job` is None when the poll failed; false when the job was dropped
+    fn finish(&self, now: Instant, host_id: &str, job_id: &str, job: Option<&Job>, gone: bool) -> bool {
+        let mut tracked = self.tracked.lock().unwrap();
+        let Some(index) = tracked.iter().position(|t| t.host_id == host_id && t.job_id == job_id) else {
+            return false;
+        };
+        let changed = match job {
+            _ if gone => {
+                tracked.remove(index);
+                self.changed.notify_one();
+                return false;
+            }
+            Some(job) if !job.is_active() => {
+                tracked.remove(index);
+                true
+            }
+            Some(job) => {
+                let entry = &mut tracked[index];
+                let changed = entry.backoff.observe(&job.status, job.progress, job.logs.len());
+                entry.due = now + entry.backoff.interval();
+                entry.in_flight = false;
+                changed
+            }
+            None => {
+                let entry = &mut tracked[index];
+                entry.backoff.failed();
+                entry.due = now + entry.backoff.interval();
+                entry.in_flight = false;
+                false
+            }
+        };
+        self.changed.notify_one();
+        changed
+    }
+}
+
+fn poll(app: &AppHandle, host_id: &str, job_id: &str) {
+    let settings = app.state::<SettingsStore>().get();
+    let poller = app.state::<JobPoller>();
+    let Some(host) = settings.hosts.iter().find(|h| h.id == host_id) else {
+        poller.finish(Instant::now(), host_id, job_id, None, true);
+        return;
+    };
+    match hosts::get_json::<Job>(host, &format!("/api/jobs/{}", job_id)) {
+        Ok(mut job) => {
+            job.host_id = host.id.clone();
+            let finished = !job.is_active();
+            if poller.finish(Instant::now(), host_id, job_id, Some(&job), false) {
+                let _ = app.emit("jobs://update", &job);
+            }
+            if finished {
+                println!("[Halbert] Remote job {} on {} {}", job_id, host.name, job.status);
+            }
+        }
+        Err(CommandError::NotFound(_)) => {
+            println!("[Halbert] Remote job {} on {} is gone; no longer polled", job_id, host.name);
+            poller.finish(Instant::now(), host_id, job_id, None, true);
+        }
+        Err(_) => {
+            poller.finish(Instant::now(), host_id, job_id, None, false);
+        }
+    }
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        let (due, wait) = app.state::<JobPoller>().take_due(Instant::now());
+        for (host_id, job_id) in due {
+            let app = app.clone();
+            std::thread::spawn(move || poll(&app, &host_id, &job_id));
+        }
+        app.state::<JobPoller>().wait(wait);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(host_id: &str, id: &str, status: &str, progress: f32) -> Job {
+        let mut job: Job = serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": "Backup",
+            "status": status,
+            "started_at": "2026-10-14T12:00:00Z",
+            "progress": progress,
+            "logs": [],
+            "task_type": "backup",
+        }))
+        .unwrap();
+        job.host_id = host_id.to_string();
+        job
+    }
+
+    #[test]
+    fn backoff_doubles_while_still_up_to_the_cap() {
+        let mut backoff = Backoff::default();
+        assert_eq!(backoff.interval(), FAST);
+        assert!(backoff.observe("running", 0.1, 3));
+        assert_eq!(backoff.interval(), FAST);
+        let mut seen = Vec::new();
+        for _ in 0..7 {
+            assert!(!backoff.observe("running", 0.1, 3));
+            seen.push(backoff.interval().as_secs());
+        }
+        assert_eq!(seen, [2, 4, 8, 16, 30, 30, 30]);
+    }
+
+    #[test]
+    fn any_change_goes_back_to_fast() {
+        let mut backoff = Backoff::default();
+        backoff.observe("running", 0.1, 3);
+        backoff.observe("running", 0.1, 3);
+        backoff.observe("running", 0.1, 3);
+        assert_eq!(backoff.interval(), Duration::from_secs(4));
+        // Progress, a new log line and the status each count
+        assert!(backoff.observe("running", 0.2, 3));
+        assert_eq!(backoff.interval(), FAST);
+        backoff.observe("running", 0.2, 3);
+        assert!(backoff.observe("running", 0.2, 4));
+        assert_eq!(backoff.interval(), FAST);
+        backoff.observe("running", 0.2, 4);
+        assert!(backoff.observe("pending", 0.2, 4));
+        assert_eq!(backoff.interval(), FAST);
+    }
+
+    #[test]
+    fn failed_polls_back_off_without_forgetting_the_last_state() {
+        let mut backoff = Backoff::default();
+        backoff.observe("running", 0.5, 1);
+        for _ in 0..10 {
+            backoff.failed();
+        }
+        assert_eq!(backoff.interval(), SLOW);
+        // The same answer after the outage is still no change
+        assert!(!backoff.observe("running", 0.5, 1));
+        assert_eq!(backoff.interval(), SLOW);
+    }
+
+    #[test]
+    fn only_active_remote_jobs_are_tracked_once() {
+        let poller = JobPoller::default();
+        poller.track(&job(hosts::LOCAL_HOST_ID, "1", "running", 0.0));
+        poller.track(&job("nas", "2", "completed", 1.0));
+        poller.track(&job("nas", "3", "running", 0.0));
+        poller.track(&job("nas", "3", "running", 0.5));
+        let now = Instant::now();
+        assert_eq!(poller.take_due(now + FAST).0, [("nas".to_string(), "3".to_string())]);
+    }
+
+    #[test]
+    fn polls_are_scheduled_by_each_jobs_interval() {
+        let poller = JobPoller::default();
+        poller.track(&job("nas", "1", "running", 0.0));
+        let start = Instant::now();
+        assert!(poller.take_due(start).0.is_empty());
+
+        let t = start + FAST;
+        assert_eq!(poller.take_due(t).0.len(), 1);
+        // In flight: not handed out twice, and nothing else to wait for
+        assert_eq!(poller.take_due(t), (Vec::new(), IDLE_WAIT));
+
+        // No change: next poll in 2s, then 4s
+        assert!(!poller.finish(t, "nas", "1", Some(&job("nas", "1", "running", 0.0)), false));
+        assert_eq!(poller.take_due(t).1, Duration::from_secs(2));
+        let t = t + Duration::from_secs(2);
+        assert_eq!(poller.take_due(t).0.len(), 1);
+        poller.finish(t, "nas", "1", None, false);
+        assert_eq!(poller.take_due(t).1, Duration::from_secs(4));
+
+        // A change is reported and brings it back to FAST
+        let t = t + Duration::from_secs(4);
+        poller.take_due(t);
+        assert!(poller.finish(t, "nas", "1", Some(&job("nas", "1", "running", 0.4)), false));
+        assert_eq!(poller.take_due(t).1, FAST);
+
+        // Finishing reports the final state and drops the job
+        let t = t + FAST;
+        poller.take_due(t);
+        assert!(poller.finish(t, "nas", "1", Some(&job("nas", "1", "completed", 1.0)), false));
+        assert_eq!(poller.take_due(t + SLOW), (Vec::new(), IDLE_WAIT));
+        assert!(!poller.finish(t, "nas", "1", None, false));
+    }
+
+    #[test]
+    fn at_most_max_in_flight_and_most_overdue_first() {
+        let poller = JobPoller::default();
+        for id in 0..MAX_IN_FLIGHT + 2 {
+            poller.track(&job("nas", &id.to_string(), "running", 0.0));
+        }
+        let t = Instant::now() + FAST;
+        let (first, _) = poller.take_due(t);
+        let ids: Vec<&str> = first.iter().map(|(_, id)| id.as_str()).collect();
+        assert_eq!(ids, ["0", "1", "2", "3"]);
+        assert!(poller.take_due(t).0.is_empty());
+
+        // A slot frees up when a poll ends
+        poller.finish(t, "nas", "0", None, true);
+        assert_eq!(poller.take_due(t).0, [("nas".to_string(), "4".to_string())]);
+    }
+
+    #[test]
+    fn poll_all_makes_everything_due_at_fast() {
+        let poller = JobPoller::default();
+        poller.track(&job("nas", "1", "running", 0.0));
+        let t = Instant::now() + FAST;
+        poller.take_due(t);
+        for _ in 0..4 {
+            poller.finish(t, "nas", "1", None, false);
+        }
+        assert_eq!(poller.take_due(t).1, Duration::from_secs(16));
+        poller.poll_all();
+        let (due, _) = poller.take_due(Instant::now());
+        assert_eq!(due.len(), 1);
+        poller.finish(t, "nas", "1", Some(&job("nas", "1", "running", 0.0)), false);
+        assert_eq!(poller.take_due(t).1, Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,1165 @@
+// Job templates for maintenance task types.
+//
+// A template turns a task's JSON params into argv command lines (never a
+// shell string). Built-in templates are code; templates with a `dry_run`
+// builder can show what they would do before an approval is decided.
+// User templates live in SQLite (see the registry section at the bottom).
+// Either kind can name mounts to snapshot before it runs (see snapshots).
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::app::{AppHandle, State};
+use crate::approvals::{self, ApprovalAction, ApprovalRequest, NewApproval};
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::impact;
+use crate::jobs::{Job, JobHandle, JobManager};
+use crate::policy::RiskLevel;
+use crate::secrets;
+use crate::snapshots::{self, SnapshotPolicy};
+
+#[derive(Clone, Debug)]
+pub struct CommandLine {
+    pub program: String,
+    pub args: Vec<String>,
+    pub environment: JobEnvironment,
+}
+
+impl CommandLine {
+    fn new(program: &str, args: &[&str]) -> Self {
+        CommandLine {
+            program: program.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            environment: JobEnvironment::default(),
+        }
+    }
+
+    pub fn display(&self) -> String {
+        format!("{} {}", self.program, self.args.join(" "))
+    }
+}
+
+type Builder = fn(&Value) -> CommandResult<Vec<CommandLine>>;
+
+pub struct JobTemplate {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub risk_level: RiskLevel,
+    pub command: Builder,
+    pub dry_run: Option<Builder>,
+    // One-line summary of dry-run output for the approval card
+    pub summarize_dry_run: fn(&[String]) -> String,
+    // Mounts snapshotted before it runs, and whether it runs without them
+    pub snapshot_before: &'static [&'static str],
+    pub snapshot_optional: bool,
+}
+
+const BUILTIN_TEMPLATES: &[JobTemplate] = &[
+    JobTemplate {
+        name: "disk_cleanup",
+        risk_level: RiskLevel::Medium,
+        description: "Delete files matching glob patterns, optionally older than N days",
+        command: disk_cleanup_command,
+        dry_run: Some(disk_cleanup_dry_run),
+        summarize_dry_run: summarize_disk_cleanup,
+        snapshot_before: &[],
+        snapshot_optional: false,
+    },
+    JobTemplate {
+        name: "system_update",
+        risk_level: RiskLevel::High,
+        description: "Upgrade installed packages with apt or dnf",
+        command: system_update_command,
+        dry_run: Some(system_update_dry_run),
+        summarize_dry_run: summarize_system_update,
+        // Upgrades can't be undone otherwise; a root that can't be
+        // snapshotted still gets its updates
+        snapshot_before: &["/"],
+        snapshot_optional: true,
+    },
+    JobTemplate {
+        name: "backup",
+        risk_level: RiskLevel::Medium,
+        description: "Mirror a directory to a destination with rsync",
+        command: backup_command,
+        dry_run: Some(backup_dry_run),
+        summarize_dry_run: summarize_backup,
+        snapshot_before: &[],
+        snapshot_optional: false,
+    },
+];
+
+pub fn builtin(name: &str) -> Option<&'static JobTemplate> {
+    BUILTIN_TEMPLATES.iter().find(|t| t.name == name)
+}
+
+impl JobTemplate {
+    fn snapshot_policy(&self) -> SnapshotPolicy {
+        SnapshotPolicy {
+            snapshot_before: self.snapshot_before.iter().map(|m| m.to_string()).collect(),
+            snapshot_optional: self.snapshot_optional,
+        }
+    }
+}
+
+fn str_param<'a>(params: &'a Value, key: &str) -> CommandResult<&'a str> {
+    params
+        .get(key)
+        .and_then(Value::as_str)
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| CommandError::InvalidInput(format!("missing string param '{}'", key)))
+}
+
+// Reject values that find/rsync would read as options
+fn not_an_option(value: &str) -> CommandResult<&str> {
+    if value.starts_with('-') {
+        return Err(CommandError::InvalidInput(format!("'{}' looks like an option", value)));
+    }
+    Ok(value)
+}
+
+// --- disk_cleanup: {"patterns": ["/var/log/*.gz"], "older_than_days": 90} ---
+
+fn disk_cleanup_find(params: &Value, action: &[&str]) -> CommandResult<Vec<CommandLine>> {
+    let patterns = params
+        .get("patterns")
+        .and_then(Value::as_array)
+        .filter(|p| !p.is_empty())
+        .ok_or_else(|| CommandError::InvalidInput("missing param 'patterns'".to_string()))?;
+    let mtime = params
+        .get("older_than_days")
+        .and_then(Value::as_u64)
+        .map(|days| format!("+{}", days));
+
+    patterns
+        .iter()
+        .map(|pattern| {
+            let pattern = pattern
+                .as_str()
+                .ok_or_else(|| CommandError::InvalidInput("patterns must be strings".to_string()))?;
+            let pattern = impact::expand_home(pattern);
+            // Only the last path component may contain wildcards
+            let (dir, name) = pattern
+                .rsplit_once('/')
+                .filter(|(dir, name)| dir.starts_with('/') && !name.is_empty())
+                .filter(|(dir, _)| !dir.contains(['*', '?', '[']))
+                .ok_or_else(|| {
+                    CommandError::InvalidInput(format!("unsupported cleanup pattern '{}'", pattern))
+                })?;
+
+            let mut args = vec![dir, "-xdev", "-maxdepth", "1", "-type", "f", "-name", name];
+            if let Some(mtime) = &mtime {
+                args.extend(["-mtime", mtime.as_str()]);
+            }
+            args.extend(action);
+            Ok(CommandLine::new("find", &args))
+        })
+        .collect()
+}
+
+fn disk_cleanup_command(params: &Value) -> CommandResult<Vec<CommandLine>> {
+    disk_cleanup_find(params, &["-printf", "%s\\t%p\\n", "-delete"])
+}
+
+fn disk_cleanup_dry_run(params: &Value) -> CommandResult<Vec<CommandLine>> {
+    disk_cleanup_find(params, &["-printf", "%s\\t%p\\n"])
+}
+
+fn summarize_disk_cleanup(output: &[String]) -> String {
+    let (count, bytes) = output
+        .iter()
+        .filter_map(|line| line.split_once('\t')?.0.parse::<u64>().ok())
+        .fold((0u64, 0u64), |(n, total), size| (n + 1, total + size));
+    format!(
+        "{} files, {:.1} MB would be deleted",
+        count,
+        bytes as f64 / 1024.0 / 1024.0
+    )
+}
+
+// --- system_update: {} ---
+
+fn has_apt() -> bool {
+    exec::stdout("apt-get", &["--version"]).is_some()
+}
+
+fn system_update_command(_params: &Value) -> CommandResult<Vec<CommandLine>> {
+    if has_apt() {
+        return Ok(vec![CommandLine::new("apt-get", &["-y", "upgrade"])]);
+    }
+    if exec::stdout("dnf", &["--version"]).is_some() {
+        return Ok(vec![CommandLine::new("dnf", &["-y", "upgrade"])]);
+    }
+    Err(CommandError::NotSupported("no supported package manager (apt, dnf) found".to_string()))
+}
+
+fn system_update_dry_run(_params: &Value) -> CommandResult<Vec<CommandLine>> {
+    if has_apt() {
+        return Ok(vec![CommandLine::new("apt-get", &["-s", "upgrade"])]);
+    }
+    if exec::stdout("dnf", &["--version"]).is_some() {
+        return Ok(vec![CommandLine::new("dnf", &["upgrade", "--assumeno"])]);
+    }
+    Err(CommandError::NotSupported("no supported package manager (apt, dnf) found".to_string()))
+}
+
+// apt prints "Inst pkg [old] (new ...)", dnf a transaction table row per package
+fn summarize_system_update(output: &[String]) -> String {
+    let apt = output.iter().filter(|l| l.starts_with("Inst ")).count();
+    let count = if apt > 0 {
+        apt
+    } else {
+        output
+            .iter()
+            .skip_while(|l| !l.starts_with("Upgrading:"))
+            .skip(1)
+            .take_while(|l| l.starts_with(' '))
+            .count()
+    };
+    format!("{} packages would be upgraded", count)
+}
+
+// --- backup: {"source": "/home/me/", "destination": "/mnt/backup/me/"} ---
+
+fn backup_args(params: &Value) -> CommandResult<(&str, &str)> {
+    let source = not_an_option(str_param(params, "source")?)?;
+    let destination = not_an_option(str_param(params, "destination")?)?;
+    Ok((source, destination))
+}
+
+fn backup_command(params: &Value) -> CommandResult<Vec<CommandLine>> {
+    let (source, destination) = backup_args(params)?;
+    Ok(vec![CommandLine::new(
+        "rsync",
+        &["-a", "--delete", "--itemize-changes", "--", source, destination],
+    )])
+}
+
+fn backup_dry_run(params: &Value) -> CommandResult<Vec<CommandLine>> {
+    let (source, destination) = backup_args(params)?;
+    Ok(vec![CommandLine::new(
+        "rsync",
+        &["-a", "--delete", "--itemize-changes", "--dry-run", "--", source, destination],
+    )])
+}
+
+// --itemize-changes lines start with e.g. ">f+++++++++" or "*deleting"
+fn summarize_backup(output: &[String]) -> String {
+    let deleting = output.iter().filter(|l| l.starts_with("*deleting")).count();
+    let transfers = output
+        .iter()
+        .filter(|l| l.starts_with(">f") || l.starts_with("cd"))
+        .count();
+    format!("{} items would be copied, {} deleted", transfers, deleting)
+}
+
+// --- User template registry ---
+//
+// A user template is one program plus an argv template. Each argument is
+// either a literal or exactly "{param}", replaced by that param's value as
+// its own argument(s); nothing is ever spliced into a string or handed to
+// a shell. Params are checked against the template's schema before any
+// command line is built.
+
+// Programs that would turn an argument back into shell code
+const SHELLS: &[&str] = &["sh", "bash", "dash", "zsh", "ksh", "fish", "csh", "tcsh", "env"];
+// Variables that would change which code the program loads
+const RESERVED_ENV: &[&str] = &["LD_PRELOAD", "LD_LIBRARY_PATH", "LD_AUDIT", "DYLD_INSERT_LIBRARIES"];
+// Job secrets live in the secret store under this prefix
+const JOB_SECRET_PREFIX: &str = "job_secret:";
+
+// Where and with what environment a user template's program runs. Secret
+// variables name a job secret (see set_job_secret) rather than holding a
+// value: it's read from the secret store when the job starts, never
+// written to SQLite or the job log.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JobEnvironment {
+    // Absolute or starting with ~/; None keeps Halbert's own
+    pub cwd: Option<String>,
+    pub env: BTreeMap<String, String>,
+    // Variable -> job secret name
+    pub secret_env: BTreeMap<String, String>,
+}
+
+pub fn secret_name(name: &str) -> String {
+    format!("{}{}", JOB_SECRET_PREFIX, name)
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn valid_env_var(var: &str) -> bool {
+    var.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && var.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn validate_environment(environment: &JobEnvironment) -> CommandResult<()> {
+    let invalid = |message: String| Err(CommandError::InvalidInput(message));
+    for var in environment.env.keys().chain(environment.secret_env.keys()) {
+        if !valid_env_var(var) {
+            return invalid(format!("'{}' isn't a valid environment variable name", var));
+        }
+        if RESERVED_ENV.contains(&var.as_str()) {
+            return invalid(format!("{} would change which code the program loads", var));
+        }
+    }
+    if let Some(var) = environment.env.keys().find(|v| environment.secret_env.contains_key(*v)) {
+        return invalid(format!("{} is set both as a value and from a secret", var));
+    }
+    if let Some(name) = environment.secret_env.values().find(|n| !valid_name(n)) {
+        return invalid(format!("secret name '{}' may only contain letters, digits, '_' and '-'", name));
+    }
+    if let Some(cwd) = &environment.cwd {
+        if !Path::new(&impact::expand_home(cwd)).is_absolute() {
+            return invalid(format!("working directory '{}' must be absolute or start with ~/", cwd));
+        }
+    }
+    Ok(())
+}
+
+// Referenced secrets that aren't stored on this machine
+fn missing_secrets(environment: &JobEnvironment) -> Vec<String> {
+    let mut missing: Vec<String> = environment
+        .secret_env
+        .values()
+        .filter(|name| matches!(secrets::read(&secret_name(name)), Ok(None)))
+        .cloned()
+        .collect();
+    missing.sort();
+    missing.dedup();
+    missing
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    // Array of strings, passed as one argument per item
+    Array,
+}
+
+impl ParamType {
+    fn accepts(self, value: &Value) -> bool {
+        match self {
+            ParamType::String => value.is_string(),
+            ParamType::Integer => value.is_i64() || value.is_u64(),
+            ParamType::Number => value.is_number(),
+            ParamType::Boolean => value.is_boolean(),
+            ParamType::Array => value.as_array().is_some_and(|items| items.iter().all(Value::is_string)),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            ParamType::String => "a string",
+            ParamType::Integer => "an integer",
+            ParamType::Number => "a number",
+            ParamType::Boolean => "a boolean",
+            ParamType::Array => "an array of strings",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ParamSpec {
+    #[serde(rename = "type")]
+    pub kind: ParamType,
+    // Allowed values; None allows any value of the type
+    #[serde(default, rename = "enum")]
+    pub allowed: Option<Vec<Value>>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+// e.g. {"properties": {"path": {"type": "string"}}, "required": ["path"]}.
+// Params not in `properties` are rejected.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ParamSchema {
+    #[serde(default)]
+    pub properties: BTreeMap<String, ParamSpec>,
+    #[serde(default)]
+    pub required: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct TemplateInfo {
+    pub name: String,
+    pub description: String,
+    // Empty for built-ins, whose command lines are built in code
+    pub command: String,
+    pub args_template: Vec<String>,
+    pub param_schema: Option<ParamSchema>,
+    // Per command; None runs until done
+    pub timeout_secs: Option<u64>,
+    // Where the command leaves its output files; `~` and whole-component
+    // {param} placeholders are filled in per run
+    pub artifacts_dir: Option<String>,
+    pub risk_level: RiskLevel,
+    #[serde(flatten)]
+    pub environment: JobEnvironment,
+    #[serde(flatten)]
+    pub snapshot: SnapshotPolicy,
+    // Secrets `environment` refers to that aren't set; a job would fail
+    pub missing_secrets: Vec<String>,
+    pub builtin: bool,
+    // Set on built-ins, which are listed with the saved templates but can't
+    // be updated or deleted
+    pub read_only: bool,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+pub type Resolved = (Vec<CommandLine>, RiskLevel, Option<Duration>, Option<PathBuf>, SnapshotPolicy);
+
+#[derive(Serialize)]
+pub struct StartedJob {
+    // Set when the template ran straight away
+    pub job: Option<Job>,
+    // Set when its risk level needs an approval first
+    pub approval: Option<ApprovalRequest>,
+}
+
+fn placeholder(arg: &str) -> Option<&str> {
+    arg.strip_prefix('{')?.strip_suffix('}')
+}
+
+fn validate_params(schema: &ParamSchema, params: &Value) -> CommandResult<()> {
+    let empty = serde_json::Map::new();
+    let values = match params {
+        Value::Null => &empty,
+        Value::Object(values) => values,
+        _ => return Err(CommandError::InvalidInput("params must be an object".to_string())),
+    };
+    for key in &schema.required {
+        if values.get(key).unwrap_or(&Value::Null).is_null() {
+            return Err(CommandError::InvalidInput(format!("missing required param '{}'", key)));
+        }
+    }
+    for (key, value) in values {
+        let spec = schema
+            .properties
+            .get(key)
+            .ok_or_else(|| CommandError::InvalidInput(format!("unknown param '{}'", key)))?;
+        if value.is_null() {
+            continue;
+        }
+        if !spec.kind.accepts(value) {
+            return Err(CommandError::InvalidInput(format!("param '{}' must be {}", key, spec.kind.as_str())));
+        }
+        if let Some(allowed) = &spec.allowed {
+            if !allowed.contains(value) {
+                let choices: Vec<String> = allowed.iter().map(Value::to_string).collect();
+                return Err(CommandError::InvalidInput(format!(
+                    "param '{}' must be one of {}",
+                    key,
+                    choices.join(", ")
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_template(info: &TemplateInfo) -> CommandResult<()> {
+    let invalid = |message: String| Err(CommandError::InvalidInput(message));
+    if !valid_name(&info.name) {
+        return invalid("template names may only contain letters, digits, '_' and '-'".to_string());
+    }
+    if builtin(&info.name).is_some() {
+        return Err(CommandError::Conflict(format!("'{}' is a built-in template", info.name)));
+    }
+    let program = info.command.as_str();
+    if program.is_empty() || program.contains(char::is_whitespace) {
+        return invalid("command must be a single program name or path, without arguments".to_string());
+    }
+    let base = program.rsplit('/').next().unwrap_or(program);
+    if SHELLS.contains(&base) {
+        return invalid(format!("'{}' would run its arguments as shell code", base));
+    }
+
+    let schema = info.param_schema.clone().unwrap_or_default();
+    for key in &schema.required {
+        if !schema.properties.contains_key(key) {
+            return invalid(format!("required param '{}' isn't in properties", key));
+        }
+    }
+    for (key, spec) in &schema.properties {
+        if let Some(value) = spec.allowed.iter().flatten().find(|v| !spec.kind.accepts(v)) {
+            return invalid(format!("enum value {} of '{}' isn't {}", value, key, spec.kind.as_str()));
+        }
+    }
+    for arg in &info.args_template {
+        match placeholder(arg) {
+            Some(key) if schema.properties.contains_key(key) => {}
+            Some(key) => return invalid(format!("argument '{}' refers to undefined param '{}'", arg, key)),
+            // Substitution is whole-argument only
+            None if arg.contains(['{', '}']) => {
+                return invalid(format!(
+                    "argument '{}' mixes text and a placeholder; use a separate argument",
+                    arg
+                ))
+            }
+            None => {}
+        }
+    }
+    if info.timeout_secs == Some(0) {
+        return invalid("timeout must be at least 1 second".to_string());
+    }
+    if let Some(dir) = &info.artifacts_dir {
+        let home = impact::expand_home(dir);
+        if !Path::new(&home).is_absolute() {
+            return invalid(format!("artifacts directory '{}' must be absolute or start with ~/", dir));
+        }
+        for part in dir.split('/') {
+            match placeholder(part) {
+                Some(key) if schema.properties.contains_key(key) => {}
+                Some(key) => return invalid(format!("artifacts directory refers to undefined param '{}'", key)),
+                None if part.contains(['{', '}']) => {
+                    return invalid(format!(
+                        "artifacts directory part '{}' mixes text and a placeholder",
+                        part
+                    ))
+                }
+                None => {}
+            }
+        }
+    }
+    validate_environment(&info.environment)?;
+    snapshots::check_policy(&info.snapshot)
+}
+
+// None when a placeholder's param wasn't given, since there's nowhere
+// to look then
+fn build_artifacts_dir(info: &TemplateInfo, params: &Value) -> Option<PathBuf> {
+    let dir = info.artifacts_dir.as_deref()?;
+    let mut parts = Vec::new();
+    for part in impact::expand_home(dir).split('/') {
+        match placeholder(part) {
+            Some(key) => {
+                let value = params.get(key)?.as_str().filter(|v| !v.is_empty())?;
+                parts.push(value.trim_end_matches('/').to_string());
+            }
+            None => parts.push(part.to_string()),
+        }
+    }
+    Some(PathBuf::from(parts.join("/")))
+}
+
+// Optional params that weren't given drop their argument entirely
+fn build_command(info: &TemplateInfo, params: &Value) -> CommandResult<CommandLine> {
+    let mut args = Vec::new();
+    for arg in &info.args_template {
+        let Some(key) = placeholder(arg) else {
+            args.push(arg.clone());
+            continue;
+        };
+        match params.get(key) {
+            None | Some(Value::Null) => {}
+            Some(Value::String(value)) => args.push(not_an_option(value)?.to_string()),
+            Some(Value::Array(items)) => {
+                for item in items {
+                    args.push(not_an_option(item.as_str().unwrap_or_default())?.to_string());
+                }
+            }
+            // Validated against the schema already, so a negative number is
+            // a value here, not an option
+            Some(Value::Number(number)) => args.push(number.to_string()),
+            Some(other) => args.push(not_an_option(&other.to_string())?.to_string()),
+        }
+    }
+    Ok(CommandLine {
+        program: info.command.clone(),
+        args,
+        environment: info.environment.clone(),
+    })
+}
+
+fn builtin_info(template: &JobTemplate) -> TemplateInfo {
+    TemplateInfo {
+        name: template.name.to_string(),
+        description: template.description.to_string(),
+        command: String::new(),
+        args_template: Vec::new(),
+        param_schema: None,
+        timeout_secs: None,
+        artifacts_dir: None,
+        risk_level: template.risk_level,
+        environment: JobEnvironment::default(),
+        snapshot: template.snapshot_policy(),
+        missing_secrets: Vec::new(),
+        builtin: true,
+        read_only: true,
+        created_at: None,
+        updated_at: None,
+    }
+}
+
+type TemplateRow = (
+    String,
+    String,
+    String,
+    String,
+    String,
+    Option<i64>,
+    String,
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    String,
+    String,
+    String,
+    bool,
+);
+
+fn read_row(r: &rusqlite::Row) -> rusqlite::Result<TemplateRow> {
+    Ok((
+        r.get(0)?,
+        r.get(1)?,
+        r.get(2)?,
+        r.get(3)?,
+        r.get(4)?,
+        r.get(5)?,
+        r.get(6)?,
+        r.get(7)?,
+        r.get(8)?,
+        r.get(9)?,
+        r.get(10)?,
+        r.get(11)?,
+        r.get(12)?,
+        r.get(13)?,
+        r.get(14)?,
+    ))
+}
+
+fn from_row(row: TemplateRow) -> CommandResult<TemplateInfo> {
+    let (
+        name,
+        description,
+        command,
+        args,
+        schema,
+        timeout,
+        risk,
+        created_at,
+        updated_at,
+        artifacts_dir,
+        cwd,
+        env,
+        secret_env,
+        snapshot_before,
+        snapshot_optional,
+    ) = row;
+    let corrupt = |e: String| CommandError::Internal(format!("job template {} is corrupt: {}", name, e));
+    Ok(TemplateInfo {
+        args_template: serde_json::from_str(&args).map_err(|e| corrupt(e.to_string()))?,
+        param_schema: serde_json::from_str(&schema).map_err(|e| corrupt(e.to_string()))?,
+        risk_level: RiskLevel::parse(&risk).ok_or_else(|| corrupt(format!("unknown risk level '{}'", risk)))?,
+        timeout_secs: timeout.map(|t| t as u64),
+        artifacts_dir,
+        environment: JobEnvironment {
+            cwd,
+            env: serde_json::from_str(&env).map_err(|e| corrupt(e.to_string()))?,
+            secret_env: serde_json::from_str(&secret_env).map_err(|e| corrupt(e.to_string()))?,
+        },
+        snapshot: SnapshotPolicy {
+            snapshot_before: serde_json::from_str(&snapshot_before).map_err(|e| corrupt(e.to_string()))?,
+            snapshot_optional,
+        },
+        missing_secrets: Vec::new(),
+        created_at: Some(created_at),
+        updated_at: Some(updated_at),
+        builtin: false,
+        read_only: false,
+        name,
+        description,
+        command,
+    })
+}
+
+const TEMPLATE_COLUMNS: &str = "name, description, command, args_template, param_schema, timeout_secs, risk_level, \
+     created_at, updated_at, artifacts_dir, cwd, env, secret_env, snapshot_before, snapshot_optional";
+
+fn load_custom(db: &Database, name: &str) -> CommandResult<Option<TemplateInfo>> {
+    let sql = format!("SELECT {} FROM job_templates WHERE name = ?1", TEMPLATE_COLUMNS);
+    let row: Option<TemplateRow> = db.with_conn(|conn| conn.query_row(&sql, params![name], read_row).optional())?;
+    row.map(from_row).transpose()
+}
+
+fn encode<T: Serialize>(value: &T) -> CommandResult<String> {
+    serde_json::to_string(value).map_err(|e| CommandError::Internal(e.to_string()))
+}
+
+fn save_custom(db: &Database, info: &TemplateInfo, insert: bool) -> CommandResult<()> {
+    let args = encode(&info.args_template)?;
+    let schema = encode(&info.param_schema)?;
+    let env = encode(&info.environment.env)?;
+    let secret_env = encode(&info.environment.secret_env)?;
+    let snapshot_before = encode(&info.snapshot.snapshot_before)?;
+    let timeout = info.timeout_secs.map(|t| t as i64);
+    let risk = info.risk_level.as_str();
+    let now = chrono::Utc::now().to_rfc3339();
+    let values = params![
+        info.name,
+        info.description,
+        info.command,
+        args,
+        schema,
+        timeout,
+        risk,
+        now,
+        info.artifacts_dir,
+        info.environment.cwd,
+        env,
+        secret_env,
+        snapshot_before,
+        info.snapshot.snapshot_optional
+    ];
+    let changed = db.with_conn(|conn| {
+        if insert {
+            conn.execute(
+                "INSERT OR IGNORE INTO job_templates (name, description, command, args_template, param_schema,
+                    timeout_secs, risk_level, created_at, updated_at, artifacts_dir, cwd, env, secret_env,
+                    snapshot_before, snapshot_optional)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                values,
+            )
+        } else {
+            conn.execute(
+                "UPDATE job_templates SET description = ?2, command = ?3, args_template = ?4, param_schema = ?5,
+                    timeout_secs = ?6, risk_level = ?7, updated_at = ?8, artifacts_dir = ?9, cwd = ?10, env = ?11,
+                    secret_env = ?12, snapshot_before = ?13, snapshot_optional = ?14
+                 WHERE name = ?1",
+                values,
+            )
+        }
+    })?;
+    match (changed, insert) {
+        (0, true) => Err(CommandError::Conflict(format!("job template '{}' already exists", info.name))),
+        (0, false) => Err(CommandError::NotFound(format!("job template '{}'", info.name))),
+        _ => Ok(()),
+    }
+}
+
+// Secrets are read here, as the job starts, and registered for redaction
+// before the program can print them
+fn prepare(handle: &JobHandle, command: &CommandLine) -> Result<Command, String> {
+    let mut process = Command::new(&command.program);
+    process.args(&command.args);
+    let environment = &command.environment;
+    if let Some(cwd) = &environment.cwd {
+        process.current_dir(impact::expand_home(cwd));
+    }
+    process.envs(&environment.env);
+    for (var, name) in &environment.secret_env {
+        let value = secrets::read(&secret_name(name))
+            .map_err(|e| format!("couldn't read job secret '{}': {}", name, e))?
+            .ok_or_else(|| format!("job secret '{}' isn't set (see set_job_secret)", name))?;
+        handle.redact_value(&value);
+        process.env(var, value);
+    }
+    Ok(process)
+}
+
+// Takes the template's snapshots, then runs each command in turn,
+// stopping at the first failure
+pub fn spawn_template_job(
+    jobs: &JobManager,
+    template: &str,
+    commands: Vec<CommandLine>,
+    timeout: Option<Duration>,
+    artifacts_dir: Option<PathBuf>,
+    snapshot: SnapshotPolicy,
+) -> Job {
+    let label = template.to_string();
+    jobs.spawn(&format!("Template: {}", template), template, move |handle| {
+        if let Some(dir) = &artifacts_dir {
+            handle.set_artifacts_dir(dir);
+        }
+        if !snapshot.is_empty() {
+            let made = snapshots::before_job(&snapshot, &label, &|line| handle.log(line))?;
+            handle.add_snapshots(made);
+        }
+        let total = commands.len();
+        for (i, command) in commands.iter().enumerate() {
+            let mut process = prepare(handle, command)?;
+            let (status, _) = handle.run_prepared_limited(&mut process, timeout)?;
+            if !status.success() {
+                return Err(format!("{} exited with {}", command.program, status));
+            }
+            handle.set_progress((i + 1) as f32 / total as f32);
+        }
+        Ok(())
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn definition(
+    name: String,
+    command: String,
+    args_template: Vec<String>,
+    param_schema: Option<ParamSchema>,
+    timeout: Option<u64>,
+    artifacts_dir: Option<String>,
+    risk_level: RiskLevel,
+    description: Option<String>,
+    environment: JobEnvironment,
+    snapshot: SnapshotPolicy,
+) -> CommandResult<TemplateInfo> {
+    let environment = JobEnvironment {
+        cwd: environment.cwd.map(|d| d.trim().to_string()).filter(|d| !d.is_empty()),
+        ..environment
+    };
+    let info = TemplateInfo {
+        name: name.trim().to_string(),
+        description: description.unwrap_or_default(),
+        command: command.trim().to_string(),
+        args_template,
+        param_schema,
+        timeout_secs: timeout,
+        artifacts_dir: artifacts_dir.map(|d| d.trim().to_string()).filter(|d| !d.is_empty()),
+        risk_level,
+        environment,
+        snapshot: SnapshotPolicy {
+            snapshot_before: snapshot.snapshot_before.iter().map(|m| m.trim().to_string()).collect(),
+            ..snapshot
+        },
+        missing_secrets: Vec::new(),
+        builtin: false,
+        read_only: false,
+        created_at: None,
+        updated_at: None,
+    };
+    validate_template(&info)?;
+    Ok(info)
+}
+
+pub fn custom_templates(db: &Database) -> CommandResult<Vec<TemplateInfo>> {
+    let sql = format!("SELECT {} FROM job_templates ORDER BY name", TEMPLATE_COLUMNS);
+    let rows: Vec<TemplateRow> = db.with_conn(|conn| {
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map([], read_row)?;
+        rows.collect()
+    })?;
+    rows.into_iter().map(from_row).collect()
+}
+
+// Writes already-validated templates and removes `remove`, all or nothing.
+// An existing template keeps its created_at.
+pub fn import_templates(db: &Database, templates: &[TemplateInfo], remove: &[String]) -> CommandResult<()> {
+    let mut rows = Vec::with_capacity(templates.len());
+    for info in templates {
+        let json = [
+            encode(&info.args_template)?,
+            encode(&info.param_schema)?,
+            encode(&info.environment.env)?,
+            encode(&info.environment.secret_env)?,
+            encode(&info.snapshot.snapshot_before)?,
+        ];
+        rows.push((info, json));
+    }
+    let now = chrono::Utc::now().to_rfc3339();
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        for name in remove {
+            tx.execute("DELETE FROM job_templates WHERE name = ?1", params![name])?;
+        }
+        for (info, [args, schema, env, secret_env, snapshot_before]) in &rows {
+            tx.execute(
+                "INSERT INTO job_templates (name, description, command, args_template, param_schema,
+                    timeout_secs, risk_level, created_at, updated_at, artifacts_dir, cwd, env, secret_env,
+                    snapshot_before, snapshot_optional)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+                 ON CONFLICT(name) DO UPDATE SET description = ?2, command = ?3, args_template = ?4,
+                    param_schema = ?5, timeout_secs = ?6, risk_level = ?7, updated_at = ?8, artifacts_dir = ?9,
+                    cwd = ?10, env = ?11, secret_env = ?12, snapshot_before = ?13, snapshot_optional = ?14",
+                params![
+                    info.name,
+                    info.description,
+                    info.command,
+                    args,
+                    schema,
+                    info.timeout_secs.map(|t| t as i64),
+                    info.risk_level.as_str(),
+                    now,
+                    info.artifacts_dir,
+                    info.environment.cwd,
+                    env,
+                    secret_env,
+                    snapshot_before,
+                    info.snapshot.snapshot_optional
+                ],
+            )?;
+        }
+        tx.commit()
+    })
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn list_job_templates(db: State<'_, Database>) -> CommandResult<Vec<TemplateInfo>> {
+    templates(&db)
+}
+
+// Built-ins first, then the saved ones by name
+fn templates(db: &Database) -> CommandResult<Vec<TemplateInfo>> {
+    let mut templates: Vec<TemplateInfo> = BUILTIN_TEMPLATES.iter().map(builtin_info).collect();
+    templates.extend(custom_templates(db)?.into_iter().map(with_secret_status));
+    Ok(templates)
+}
+
+fn with_secret_status(mut info: TemplateInfo) -> TemplateInfo {
+    info.missing_secrets = missing_secrets(&info.environment);
+    info
+}
+
+// A template whose secrets aren't set yet is still saved; the response
+// lists them under missing_secrets
+fn warn_missing(info: TemplateInfo) -> TemplateInfo {
+    let info = with_secret_status(info);
+    if !info.missing_secrets.is_empty() {
+        println!(
+            "[Halbert] Job template {} refers to unset secret(s): {}",
+            info.name,
+            info.missing_secrets.join(", ")
+        );
+    }
+    info
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+#[allow(clippy::too_many_arguments)]
+pub fn create_job_template(
+    db: State<'_, Database>,
+    name: String,
+    command: String,
+    args_template: Vec<String>,
+    param_schema: Option<ParamSchema>,
+    timeout: Option<u64>,
+    artifacts_dir: Option<String>,
+    risk_level: RiskLevel,
+    description: Option<String>,
+    environment: Option<JobEnvironment>,
+    snapshot: Option<SnapshotPolicy>,
+) -> CommandResult<TemplateInfo> {
+    let environment = environment.unwrap_or_default();
+    let info = definition(
+        name,
+        command,
+        args_template,
+        param_schema,
+        timeout,
+        artifacts_dir,
+        risk_level,
+        description,
+        environment,
+        snapshot.unwrap_or_default(),
+    )?;
+    save_custom(&db, &info, true)?;
+    load_custom(&db, &info.name)?
+        .map(warn_missing)
+        .ok_or_else(|| CommandError::Internal("template vanished after insert".to_string()))
+}
+
+// Replaces the whole definition
+#[cfg_attr(feature = "gui", tauri::command)]
+#[allow(clippy::too_many_arguments)]
+pub fn update_job_template(
+    db: State<'_, Database>,
+    name: String,
+    command: String,
+    args_template: Vec<String>,
+    param_schema: Option<ParamSchema>,
+    timeout: Option<u64>,
+    artifacts_dir: Option<String>,
+    risk_level: RiskLevel,
+    description: Option<String>,
+    environment: Option<JobEnvironment>,
+    snapshot: Option<SnapshotPolicy>,
+) -> CommandResult<TemplateInfo> {
+    if builtin(name.trim()).is_some() {
+        return Err(CommandError::PermissionDenied(format!("built-in template '{}' is read-only", name)));
+    }
+    let environment = environment.unwrap_or_default();
+    let info = definition(
+        name,
+        command,
+        args_template,
+        param_schema,
+        timeout,
+        artifacts_dir,
+        risk_level,
+        description,
+        environment,
+        snapshot.unwrap_or_default(),
+    )?;
+    save_custom(&db, &info, false)?;
+    load_custom(&db, &info.name)?
+        .map(warn_missing)
+        .ok_or_else(|| CommandError::NotFound(format!("job template '{}'", info.name)))
+}
+
+#[derive(Serialize)]
+pub struct JobSecretStatus {
+    pub name: String,
+    pub set: bool,
+}
+
+// Stores the value job templates reach as `name` in secret_env; no value
+// (or an empty one) removes it. The value is never returned.
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn set_job_secret(name: String, value: Option<String>) -> CommandResult<JobSecretStatus> {
+    let name = name.trim().to_string();
+    if !valid_name(&name) {
+        return Err(CommandError::InvalidInput(
+            "secret names may only contain letters, digits, '_' and '-'".to_string(),
+        ));
+    }
+    match value.filter(|v| !v.is_empty()) {
+        Some(value) => secrets::store(&secret_name(&name), &value)?,
+        None => secrets::delete(&secret_name(&name))?,
+    }
+    let set = secrets::read(&secret_name(&name))?.is_some();
+    Ok(JobSecretStatus { name, set })
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn delete_job_template(db: State<'_, Database>, name: String) -> CommandResult<()> {
+    if builtin(&name).is_some() {
+        return Err(CommandError::PermissionDenied(format!("built-in template '{}' is read-only", name)));
+    }
+    let deleted = db.with_conn(|conn| conn.execute("DELETE FROM job_templates WHERE name = ?1", params![name]))?;
+    if deleted == 0 {
+        return Err(CommandError::NotFound(format!("job template '{}'", name)));
+    }
+    Ok(())
+}
+
+// Command lines, risk, timeout and artifacts directory for running
+// `template` with `params`
+pub fn resolve(db: &Database, template: &str, params: &Value) -> CommandResult<Resolved> {
+    match builtin(template) {
+        Some(t) => Ok(((t.command)(params)?, t.risk_level, None, None, t.snapshot_policy())),
+        None => {
+            let info = load_custom(db, template)?
+                .ok_or_else(|| CommandError::NotFound(format!("job template '{}'", template)))?;
+            validate_params(&info.param_schema.clone().unwrap_or_default(), params)?;
+            let command = build_command(&info, params)?;
+            let artifacts_dir = build_artifacts_dir(&info, params);
+            let timeout = info.timeout_secs.map(Duration::from_secs);
+            Ok((vec![command], info.risk_level, timeout, artifacts_dir, info.snapshot))
+        }
+    }
+}
+
+// Low-risk templates start right away; medium and above become an approval
+// request that runs the already-built command lines once approved
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn start_job(
+    app: AppHandle,
+    db: State<'_, Database>,
+    jobs: State<'_, JobManager>,
+    template: String,
+    params: Option<Value>,
+) -> CommandResult<StartedJob> {
+    let params = params.unwrap_or(Value::Null);
+    let (commands, risk, timeout, artifacts_dir, snapshot) = resolve(&db, &template, &params)?;
+
+    if risk == RiskLevel::Low {
+        let job = spawn_template_job(&jobs, &template, commands, timeout, artifacts_dir, snapshot);
+        return Ok(StartedJob {
+            job: Some(job),
+            approval: None,
+        });
+    }
+    let new = NewApproval {
+        task: format!("Job: {}", template),
+        action: commands.iter().map(CommandLine::display).collect::<Vec<_>>().join("; "),
+        reasoning: format!("Started from the dashboard with the {} template", template),
+        confidence: 1.0,
+        risk_level: risk.as_str().to_string(),
+        affected_resources: vec![format!("job_template:{}", template)],
+        execution: None,
+        group_id: None,
+        depends_on: Vec::new(),
+    };
+    let action = ApprovalAction::RunTemplate {
+        template,
+        params,
+        commands,
+        timeout,
+        artifacts_dir,
+        snapshot,
+    };
+    let request = approvals::submit(&app, new, Some(action))?;
+    Ok(StartedJob {
+        job: None,
+        approval: Some(request),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn offset_template() -> TemplateInfo {
+        let schema: ParamSchema = serde_json::from_value(json!({
+            "properties": { "offset": { "type": "integer" }, "label": { "type": "string" } },
+            "required": ["offset"]
+        }))
+        .unwrap();
+        definition(
+            "shift".to_string(),
+            "/usr/bin/shift".to_string(),
+            vec!["--by".to_string(), "{offset}".to_string(), "{label}".to_string()],
+            Some(schema),
+            None,
+            None,
+            RiskLevel::Low,
+            None,
+            JobEnvironment::default(),
+            SnapshotPolicy::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn negative_integers_are_values_not_options() {
+        let info = offset_template();
+        let schema = info.param_schema.clone().unwrap();
+        let params = json!({ "offset": -5 });
+        validate_params(&schema, &params).unwrap();
+        assert_eq!(build_command(&info, &params).unwrap().args, ["--by", "-5"]);
+
+        // Checked as a number, so a string or a fraction is still refused
+        assert!(validate_params(&schema, &json!({ "offset": "-5" })).is_err());
+        assert!(validate_params(&schema, &json!({ "offset": -5.5 })).is_err());
+        // and a string that looks like an option still can't pass as one
+        let sneaky = json!({ "offset": 1, "label": "-rf" });
+        validate_params(&schema, &sneaky).unwrap();
+        assert!(matches!(build_command(&info, &sneaky), Err(CommandError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn built_ins_are_listed_read_only_with_the_saved_ones() {
+        let db = Database::in_memory();
+        save_custom(&db, &offset_template(), true).unwrap();
+        let listed = templates(&db).unwrap();
+        assert_eq!(listed.len(), BUILTIN_TEMPLATES.len() + 1);
+        for template in &listed {
+            assert_eq!(template.read_only, builtin(&template.name).is_some(), "{}", template.name);
+        }
+        assert_eq!(listed.last().map(|t| t.name.as_str()), Some("shift"));
+    }
+}
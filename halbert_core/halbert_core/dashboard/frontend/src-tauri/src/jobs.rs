@@ -0,0 +1,1249 @@
+// Background jobs.
+//
+// Each job runs its work closure on its own thread and reports log lines,
+// progress and a JSON result through a `JobHandle`. Every change is pushed
+// to the `on_update` callback (the app wires it to `jobs://update`).
+// Programs started through a handle are tracked until they exit, so
+// shutdown can stop them instead of leaving them orphaned. A job that names
+// an artifacts directory has it handed to the `on_artifacts` callback once
+// its work returns, successful or not (see artifacts). While automation is
+// paused, new jobs are held as "queued" until it resumes (see automation).
+// Secret values a job hands to its programs are registered with
+// `redact_value` and masked in every log line, output and error it records,
+// also where one runs across lines (see redact_lines).
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::io::{BufRead, BufReader, Read};
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::app::State;
+use crate::automation::{Entry, Gate};
+use crate::error::{CommandError, CommandResult};
+use crate::hosts::{self, ActiveHost};
+use crate::job_poller::JobPoller;
+use crate::settings::SettingsStore;
+use crate::snapshots::Snapshot;
+use crate::timestamps::{self, Clock, Localize, TimeDisplay};
+use crate::transfers::{self, MaybeTransfer, TransferStore};
+
+// Oldest lines are dropped past this so a chatty job can't grow unbounded
+const MAX_LOG_LINES: usize = 1000;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Job {
+    #[serde(default)]
+    pub host_id: String,
+    pub id: String,
+    pub name: String,
+    pub status: String,
+    pub started_at: String,
+    pub progress: f32,
+    pub logs: Vec<String>,
+    pub task_type: String,
+    #[serde(default)]
+    pub finished_at: Option<String>,
+    #[serde(default)]
+    pub result: Option<serde_json::Value>,
+    #[serde(default)]
+    pub error: Option<String>,
+    // Scanned for output files when the job finishes
+    #[serde(default)]
+    pub artifacts_dir: Option<String>,
+    #[serde(default)]
+    pub artifact_count: usize,
+    // Taken before it started; the way back if it goes wrong
+    #[serde(default)]
+    pub snapshots: Vec<Snapshot>,
+    // Filled in per response; see timestamps
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub started_at_display: Option<TimeDisplay>,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub finished_at_display: Option<TimeDisplay>,
+}
+
+impl Localize for Job {
+    fn localize(&mut self, clock: &Clock) {
+        self.started_at_display = clock.display(&self.started_at);
+        self.finished_at_display = clock.display_opt(self.finished_at.as_deref());
+    }
+}
+
+impl Job {
+    pub fn is_active(&self) -> bool {
+        matches!(self.status.as_str(), "running" | "pending" | "queued")
+    }
+}
+
+type UpdateFn = Arc<dyn Fn(&Job) + Send + Sync>;
+// Records a finished job's artifacts; `partial` when the job didn't
+// complete. Returns how many were recorded.
+type ArtifactsFn = Arc<dyn Fn(&Job, bool) -> Result<usize, String> + Send + Sync>;
+type Work = Box<dyn FnOnce(&JobHandle) -> Result<(), String> + Send>;
+
+// A job that was asked for while automation was paused
+struct Held {
+    id: String,
+    work: Work,
+}
+
+struct JobsInner {
+    jobs: Vec<Job>,
+    next_id: u64,
+    // Pids of programs jobs are waiting on
+    children: HashSet<u32>,
+    // Set by shutdown; no new programs are started after that
+    stopping: bool,
+    held: Vec<Held>,
+}
+
+#[derive(Clone)]
+pub struct JobManager {
+    inner: Arc<Mutex<JobsInner>>,
+    on_update: UpdateFn,
+    on_artifacts: ArtifactsFn,
+    gate: Gate,
+}
+
+impl JobManager {
+    pub fn with_mock_jobs<F, A>(gate: Gate, on_update: F, on_artifacts: A) -> Self
+    where
+        F: Fn(&Job) + Send + Sync + 'static,
+        A: Fn(&Job, bool) -> Result<usize, String> + Send + Sync + 'static,
+    {
+        let jobs = mock_jobs();
+        let next_id = jobs.len() as u64 + 1;
+        JobManager {
+            inner: Arc::new(Mutex::new(JobsInner {
+                jobs,
+                next_id,
+                children: HashSet::new(),
+                stopping: false,
+                held: Vec::new(),
+            })),
+            on_update: Arc::new(on_update),
+            on_artifacts: Arc::new(on_artifacts),
+            gate,
+        }
+    }
+
+    // No mock jobs; updates and artifacts go nowhere
+    #[cfg(test)]
+    pub fn empty(gate: Gate) -> Self {
+        JobManager {
+            inner: Arc::new(Mutex::new(JobsInner {
+                jobs: Vec::new(),
+                next_id: 1,
+                children: HashSet::new(),
+                stopping: false,
+                held: Vec::new(),
+            })),
+            on_update: Arc::new(|_: &Job| {}),
+            on_artifacts: Arc::new(|_: &Job, _: bool| Ok(0)),
+            gate,
+        }
+    }
+
+    // Polls until the job is no longer active
+    #[cfg(test)]
+    pub fn wait_finished(&self, job_id: &str) -> Job {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let job = self.get(job_id).expect("job exists");
+            if !job.is_active() {
+                return job;
+            }
+            assert!(Instant::now() < deadline, "job {} still {}", job_id, job.status);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    pub fn all(&self) -> Vec<Job> {
+        self.inner.lock().unwrap().jobs.clone()
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<Job> {
+        let inner = self.inner.lock().unwrap();
+        inner.jobs.iter().find(|j| j.id == job_id).cloned()
+    }
+
+    // Drop finished jobs that ended before `before`; returns how many
+    pub fn prune_finished(&self, before: chrono::DateTime<chrono::Utc>) -> usize {
+        let cutoff = before.to_rfc3339();
+        let mut inner = self.inner.lock().unwrap();
+        let count = inner.jobs.len();
+        inner
+            .jobs
+            .retain(|job| job.is_active() || job.finished_at.as_deref().is_none_or(|at| at >= cutoff.as_str()));
+        count - inner.jobs.len()
+    }
+
+    // Stop every running job program: SIGTERM first, SIGKILL for whatever
+    // is still running after `grace`. Returns how many were signalled.
+    pub fn terminate_children(&self, grace: Duration) -> usize {
+        self.inner.lock().unwrap().stopping = true;
+        self.stop_running(grace)
+    }
+
+    // Like terminate_children, without refusing new programs afterwards;
+    // the jobs waiting on them fail
+    pub fn stop_running(&self, grace: Duration) -> usize {
+        let pids: Vec<u32> = self.inner.lock().unwrap().children.iter().copied().collect();
+        for &pid in &pids {
+            terminate(pid);
+        }
+        let deadline = Instant::now() + grace;
+        while Instant::now() < deadline && !self.inner.lock().unwrap().children.is_empty() {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        for pid in self.inner.lock().unwrap().children.iter() {
+            kill(*pid);
+        }
+        pids.len()
+    }
+
+    pub fn held_jobs(&self) -> Vec<Job> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .held
+            .iter()
+            .filter_map(|held| inner.jobs.iter().find(|j| j.id == held.id).cloned())
+            .collect()
+    }
+
+    // Starts every held job, counting its start time from now
+    pub fn release_held(&self) -> Vec<Job> {
+        let held = std::mem::take(&mut self.inner.lock().unwrap().held);
+        let mut started = Vec::new();
+        for Held { id, work } in held {
+            let handle = self.handle(id);
+            handle.update(|job| {
+                job.status = "running".to_string();
+                job.started_at = chrono::Utc::now().to_rfc3339();
+            });
+            started.extend(self.get(&handle.id));
+            self.run(handle, work);
+        }
+        started
+    }
+
+    // Fails every held job without running it
+    pub fn discard_held(&self, reason: &str) -> Vec<Job> {
+        let held = std::mem::take(&mut self.inner.lock().unwrap().held);
+        let mut discarded = Vec::new();
+        for Held { id, .. } in held {
+            let handle = self.handle(id);
+            handle.update(|job| {
+                job.status = "failed".to_string();
+                job.error = Some(reason.to_string());
+                job.finished_at = Some(chrono::Utc::now().to_rfc3339());
+            });
+            discarded.extend(self.get(&handle.id));
+        }
+        discarded
+    }
+
+    // Register a job and run `work` on a background thread. The job is
+    // "completed" when `work` returns Ok and "failed" with the message
+    // otherwise. While automation is paused it is held as "queued" instead.
+    pub fn spawn<F>(&self, name: &str, task_type: &str, work: F) -> Job
+    where
+        F: FnOnce(&JobHandle) -> Result<(), String> + Send + 'static,
+    {
+        let held = !self.gate.allows(Entry::Job, name);
+        let mut work: Option<Work> = Some(Box::new(work));
+        let job = {
+            let mut inner = self.inner.lock().unwrap();
+            let job = Job {
+                host_id: hosts::LOCAL_HOST_ID.to_string(),
+                id: format!("job_{:03}", inner.next_id),
+                name: name.to_string(),
+                status: if held { "queued" } else { "running" }.to_string(),
+                started_at: chrono::Utc::now().to_rfc3339(),
+                progress: 0.0,
+                logs: Vec::new(),
+                task_type: task_type.to_string(),
+                finished_at: None,
+                result: None,
+                error: None,
+                artifacts_dir: None,
+                artifact_count: 0,
+                snapshots: Vec::new(),
+                started_at_display: None,
+                finished_at_display: None,
+            };
+            inner.next_id += 1;
+            inner.jobs.push(job.clone());
+            if held {
+                if let Some(work) = work.take() {
+                    inner.held.push(Held {
+                        id: job.id.clone(),
+                        work,
+                    });
+                }
+            }
+            job
+        };
+        (self.on_update)(&job);
+        let Some(work) = work else {
+            println!("[Halbert] Holding job {} ({}): automation is paused", job.id, job.name);
+            return job;
+        };
+        self.run(self.handle(job.id.clone()), work);
+        job
+    }
+
+    fn handle(&self, id: String) -> JobHandle {
+        JobHandle {
+            id,
+            manager: self.clone(),
+            secrets: Arc::new(Mutex::new(Vec::new())),
+            recent: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    // A panic in `work` fails the job like an Err would
+    fn run(&self, handle: JobHandle, work: Work) {
+        std::thread::spawn(move || {
+            let outcome = std::panic::catch_unwind(AssertUnwindSafe(|| work(&handle)))
+                .unwrap_or_else(|panic| Err(format!("the job panicked: {}", panic_message(panic.as_ref()))));
+            let artifacts = handle.capture_artifacts(outcome.is_err());
+            handle.update(|job| {
+                if let Some(count) = artifacts {
+                    job.artifact_count = count;
+                }
+                job.finished_at = Some(chrono::Utc::now().to_rfc3339());
+                match outcome {
+                    Ok(()) => {
+                        job.status = "completed".to_string();
+                        job.progress = 1.0;
+                    }
+                    Err(e) => {
+                        job.status = "failed".to_string();
+                        job.error = Some(handle.redact(&e));
+                    }
+                }
+            });
+        });
+    }
+}
+
+// Given to a job's work closure for reporting back into the manager
+#[derive(Clone)]
+pub struct JobHandle {
+    id: String,
+    manager: JobManager,
+    // Values masked out of everything the job logs (see redact_value)
+    secrets: Arc<Mutex<Vec<String>>>,
+    // The latest lines logged: enough for a secret that runs on into the
+    // next line
+    recent: Arc<Mutex<VecDeque<Logged>>>,
+}
+
+// A line as logged, unmasked, and which of its bytes are masked so far
+type Logged = (String, Vec<bool>);
+
+const REDACTED: &str = "[redacted]";
+// A line of a multi-line secret shorter than this is masked only where the
+// rest of the secret is next to it; on its own it could be anything
+const MIN_REDACTED_PART: usize = 4;
+
+// Which bytes of `lines` belong to a secret. A secret is looked for in the
+// lines joined by newlines, so a multi-line value is found across them as
+// it was printed, and joined with nothing, for one value split between two
+// log calls. The lines of a multi-line value are also looked for one by
+// one, down to MIN_REDACTED_PART.
+pub fn secret_bytes(lines: &[&str], secrets: &[String]) -> Vec<Vec<bool>> {
+    let mut covered: Vec<Vec<bool>> = lines.iter().map(|line| vec![false; line.len()]).collect();
+    let secrets: Vec<&str> = secrets.iter().map(String::as_str).filter(|s| !s.is_empty()).collect();
+    for separator in ["\n", ""] {
+        let mut joined = String::new();
+        let mut starts = Vec::with_capacity(lines.len());
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                joined.push_str(separator);
+            }
+            starts.push(joined.len());
+            joined.push_str(line);
+        }
+        for secret in &secrets {
+            for (at, _) in joined.match_indices(secret) {
+                // The part of [at, at + len) in each line it touches
+                for (covered, start) in covered.iter_mut().zip(&starts) {
+                    let from = at.max(*start) - start;
+                    let to = (at + secret.len()).min(start + covered.len()).saturating_sub(*start);
+                    if from < to {
+                        covered[from..to].fill(true);
+                    }
+                }
+            }
+        }
+    }
+    let parts = secrets
+        .iter()
+        .filter(|s| s.contains('\n'))
+        .flat_map(|s| s.lines())
+        .filter(|part| part.trim().len() >= MIN_REDACTED_PART);
+    for part in parts {
+        for (line, covered) in lines.iter().zip(covered.iter_mut()) {
+            for (at, _) in line.match_indices(part) {
+                covered[at..at + part.len()].fill(true);
+            }
+        }
+    }
+    covered
+}
+
+// Each run of covered bytes replaced by [redacted]
+fn masked(line: &str, covered: &[bool]) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut in_secret = false;
+    for (i, c) in line.char_indices() {
+        if covered[i] {
+            if !in_secret {
+                out.push_str(REDACTED);
+            }
+            in_secret = true;
+        } else {
+            out.push(c);
+            in_secret = false;
+        }
+    }
+    out
+}
+
+// The lines with every secret masked, including across them
+pub fn redact_lines(lines: &[String], secrets: &[String]) -> Vec<String> {
+    let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+    let covered = secret_bytes(&lines, secrets);
+    lines.iter().zip(covered).map(|(line, covered)| masked(line, &covered)).collect()
+}
+
+pub fn redact(text: &str, secrets: &[String]) -> String {
+    redact_lines(&text.split('\n').map(str::to_string).collect::<Vec<_>>(), secrets).join("\n")
+}
+
+impl JobHandle {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn update<F: FnOnce(&mut Job)>(&self, change: F) {
+        let snapshot = {
+            let mut inner = self.manager.inner.lock().unwrap();
+            let Some(job) = inner.jobs.iter_mut().find(|j| j.id == self.id) else {
+                return;
+            };
+            change(job);
+            job.clone()
+        };
+        (self.manager.on_update)(&snapshot);
+    }
+
+    // From now on `value` is replaced by [redacted] wherever the job logs it
+    pub fn redact_value(&self, value: &str) {
+        // As it comes out a line at a time: no \r, no final newline
+        let value = value.replace("\r\n", "\n");
+        let value = value.trim_end_matches('\n');
+        if !value.is_empty() {
+            self.secrets.lock().unwrap().push(value.to_string());
+        }
+    }
+
+    fn redact(&self, text: &str) -> String {
+        redact(text, &self.secrets.lock().unwrap())
+    }
+
+    // A line that completes a secret begun in the lines before it masks
+    // those again in place; the update carries them
+    pub fn log<S: Into<String>>(&self, line: S) {
+        let mut recent = self.recent.lock().unwrap();
+        let secrets = self.secrets.lock().unwrap().clone();
+        let line = line.into();
+        let width = line.len();
+        recent.push_back((line, vec![false; width]));
+        let spanned = secrets.iter().map(|s| s.lines().count()).max().unwrap_or(0);
+        while recent.len() > (spanned + 1).min(MAX_LOG_LINES) {
+            recent.pop_front();
+        }
+        let lines: Vec<&str> = recent.iter().map(|(line, _)| line.as_str()).collect();
+        let found = secret_bytes(&lines, &secrets);
+        let lines: Vec<String> = recent
+            .iter_mut()
+            .zip(found)
+            .map(|((line, covered), found)| {
+                covered.iter_mut().zip(found).for_each(|(b, found)| *b |= found);
+                masked(line, covered)
+            })
+            .collect();
+        self.update(|job| {
+            let (earlier, line) = lines.split_at(lines.len() - 1);
+            let earlier = &earlier[earlier.len().saturating_sub(job.logs.len())..];
+            let from = job.logs.len() - earlier.len();
+            job.logs[from..].clone_from_slice(earlier);
+            job.logs.push(line[0].clone());
+            if job.logs.len() > MAX_LOG_LINES {
+                let excess = job.logs.len() - MAX_LOG_LINES;
+                job.logs.drain(..excess);
+            }
+        });
+    }
+
+    pub fn set_progress(&self, progress: f32) {
+        self.update(|job| job.progress = progress.clamp(0.0, 1.0));
+    }
+
+    pub fn set_result(&self, result: serde_json::Value) {
+        self.update(|job| job.result = Some(result));
+    }
+
+    // Files that turn up here while the job runs are kept as its artifacts
+    pub fn set_artifacts_dir(&self, dir: &Path) {
+        let dir = dir.display().to_string();
+        self.update(|job| job.artifacts_dir = Some(dir));
+    }
+
+    pub fn add_snapshots(&self, made: Vec<Snapshot>) {
+        self.update(|job| job.snapshots.extend(made));
+    }
+
+    fn capture_artifacts(&self, partial: bool) -> Option<usize> {
+        let job = self.manager.get(&self.id).filter(|j| j.artifacts_dir.is_some())?;
+        match (self.manager.on_artifacts)(&job, partial) {
+            Ok(count) => {
+                self.log(format!("Kept {} artifact(s){}", count, if partial { " (partial)" } else { "" }));
+                Some(count)
+            }
+            Err(e) => {
+                self.log(format!("Artifacts not captured: {}", e));
+                None
+            }
+        }
+    }
+
+    // Start `command` and track its pid until `wait_child` reaps it
+    fn start_child(&self, command: &mut Command, program: &str) -> Result<Child, String> {
+        let mut inner = self.manager.inner.lock().unwrap();
+        if inner.stopping {
+            return Err("Halbert is shutting down".to_string());
+        }
+        let child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to start {}: {}", program, e))?;
+        inner.children.insert(child.id());
+        Ok(child)
+    }
+
+    // Reap the child, then stop tracking its pid
+    fn wait_child(&self, child: &mut Child) -> std::io::Result<ExitStatus> {
+        let status = child.wait();
+        self.manager.inner.lock().unwrap().children.remove(&child.id());
+        status
+    }
+
+    // Run a program (argv only, no shell), streaming stdout and stderr into
+    // the job log as lines arrive
+    pub fn run_command(&self, program: &str, args: &[&str]) -> Result<ExitStatus, String> {
+        self.run_command_captured(program, args).map(|(status, _)| status)
+    }
+
+    // Like `run_command`, also returning the output lines in arrival order
+    pub fn run_command_captured(
+        &self,
+        program: &str,
+        args: &[&str],
+    ) -> Result<(ExitStatus, Vec<String>), String> {
+        self.run_command_limited(program, args, None)
+    }
+
+    // Like `run_command_captured`, killing the program if it's still running
+    // after `timeout`
+    pub fn run_command_limited(
+        &self,
+        program: &str,
+        args: &[&str],
+        timeout: Option<Duration>,
+    ) -> Result<(ExitStatus, Vec<String>), String> {
+        self.run_prepared_limited(Command::new(program).args(args), timeout)
+    }
+
+    // Like `run_command_limited` for a prepared command (env and working
+    // directory already set). The env isn't logged.
+    pub fn run_prepared_limited(
+        &self,
+        command: &mut Command,
+        timeout: Option<Duration>,
+    ) -> Result<(ExitStatus, Vec<String>), String> {
+        let program = command.get_program().to_string_lossy().into_owned();
+        let args: Vec<String> = command.get_args().map(|a| a.to_string_lossy().into_owned()).collect();
+        self.log(format!("$ {} {}", program, args.join(" ")));
+        let mut child = self.start_child(command, &program)?;
+
+        // The child isn't reaped until wait() below, so its pid can't be reused
+        // before the watchdog fires
+        let finished = Arc::new(AtomicBool::new(false));
+        let timed_out = Arc::new(AtomicBool::new(false));
+        if let Some(timeout) = timeout {
+            let pid = child.id();
+            let finished = finished.clone();
+            let timed_out = timed_out.clone();
+            std::thread::spawn(move || {
+                let deadline = Instant::now() + timeout;
+                while Instant::now() < deadline {
+                    if finished.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    std::thread::sleep(Duration::from_millis(200));
+                }
+                if !finished.load(Ordering::SeqCst) {
+                    timed_out.store(true, Ordering::SeqCst);
+                    kill(pid);
+                }
+            });
+        }
+
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let record = |handle: &JobHandle, captured: &Mutex<Vec<String>>, line: String| {
+            captured.lock().unwrap().push(line.clone());
+            handle.log(line);
+        };
+
+        let stderr_thread = child.stderr.take().map(|stderr| {
+            let handle = self.clone();
+            let captured = captured.clone();
+            std::thread::spawn(move || {
+                for_each_line(stderr, |line| record(&handle, &captured, line));
+            })
+        });
+        if let Some(stdout) = child.stdout.take() {
+            for_each_line(stdout, |line| record(self, &captured, line));
+        }
+        if let Some(thread) = stderr_thread {
+            let _ = thread.join();
+        }
+
+        let status = self.wait_child(&mut child);
+        finished.store(true, Ordering::SeqCst);
+        let status = status.map_err(|e| format!("failed to wait for {}: {}", program, e))?;
+        if timed_out.load(Ordering::SeqCst) {
+            let limit = timeout.unwrap_or_default().as_secs();
+            return Err(format!("{} was killed after the {}s timeout", program, limit));
+        }
+        let output = std::mem::take(&mut *captured.lock().unwrap());
+        Ok((status, redact_lines(&output, &self.secrets.lock().unwrap())))
+    }
+
+    // Run a prepared command (env and working directory already set),
+    // passing each stdout line to `on_stdout` instead of the log. Stderr is
+    // still logged.
+    pub fn run_prepared<F>(&self, command: &mut Command, mut on_stdout: F) -> Result<ExitStatus, String>
+    where
+        F: FnMut(&str),
+    {
+        let program = command.get_program().to_string_lossy().into_owned();
+        let args: Vec<String> = command.get_args().map(|a| a.to_string_lossy().into_owned()).collect();
+        self.log(format!("$ {} {}", program, args.join(" ")));
+        let mut child = self.start_child(command, &program)?;
+
+        let stderr_thread = child.stderr.take().map(|stderr| {
+            let handle = self.clone();
+            std::thread::spawn(move || {
+                for_each_line(stderr, |line| handle.log(line));
+            })
+        });
+        if let Some(stdout) = child.stdout.take() {
+            for_each_line(stdout, |line| on_stdout(&line));
+        }
+        if let Some(thread) = stderr_thread {
+            let _ = thread.join();
+        }
+        self.wait_child(&mut child)
+            .map_err(|e| format!("failed to wait for {}: {}", program, e))
+    }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("no message")
+}
+
+// Every line of a program's output until EOF, invalid UTF-8 replaced rather
+// than ending the stream. After a read error the rest is drained unread, so
+// the program never blocks on a full pipe.
+fn for_each_line<R: Read>(stream: R, mut each: impl FnMut(String)) {
+    let mut reader = BufReader::new(stream);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) => return,
+            Ok(_) => {
+                let line = buf.strip_suffix(b"\n").unwrap_or(&buf);
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                each(String::from_utf8_lossy(line).into_owned());
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(_) => {
+                let _ = std::io::copy(&mut reader, &mut std::io::sink());
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn kill(pid: u32) {
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+fn kill(pid: u32) {
+    let _ = Command::new("taskkill").args(["/F", "/PID", &pid.to_string()]).status();
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32) {
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGTERM);
+    }
+}
+
+// Windows has no polite signal for console programs
+#[cfg(not(unix))]
+fn terminate(pid: u32) {
+    kill(pid);
+}
+
+fn mock_jobs() -> Vec<Job> {
+    // Mock active jobs
+    let mock = |id: &str, name: &str, status: &str, progress: f32, logs: &[&str], task_type: &str| Job {
+        host_id: hosts::LOCAL_HOST_ID.to_string(),
+        id: id.to_string(),
+        name: name.to_string(),
+        status: status.to_string(),
+        started_at: chrono::Utc::now().to_rfc3339(),
+        progress,
+        logs: logs.iter().map(|l| l.to_string()).collect(),
+        task_type: task_type.to_string(),
+        finished_at: None,
+        result: None,
+        error: None,
+        artifacts_dir: None,
+        artifact_count: 0,
+        snapshots: Vec::new(),
+        started_at_display: None,
+        finished_at_display: None,
+    };
+
+    vec![
+        mock(
+            "job_001",
+            "System Health Monitor",
+            "running",
+            0.0,
+            &[
+                "Started health monitoring",
+                "Checking CPU temperature...",
+                "CPU temp: 45°C (normal)",
+            ],
+            "monitoring",
+        ),
+        mock(
+            "job_002",
+            "RAG Document Indexing",
+            "running",
+            0.67,
+            &[
+                "Loading documents from data/",
+                "Found 1,247 markdown files",
+                "Indexed 834 / 1247 documents",
+                "Building BM25 index...",
+            ],
+            "indexing",
+        ),
+    ]
+}
+
+const JOB_STATUSES: &[&str] = &["running", "pending", "queued", "completed", "failed"];
+
+#[derive(Serialize, Default)]
+pub struct JobCounts {
+    pub running: usize,
+    pub pending: usize,
+    pub queued: usize,
+    // Failed jobs that finished since local midnight
+    pub failed_today: usize,
+}
+
+#[derive(Serialize)]
+pub struct JobList {
+    pub items: Vec<Job>,
+    // Over every job, regardless of the filters
+    pub counts: JobCounts,
+}
+
+fn count_jobs(jobs: &[Job]) -> JobCounts {
+    let today = chrono::Local::now().date_naive();
+    let mut counts = JobCounts::default();
+    for job in jobs {
+        match job.status.as_str() {
+            "running" => counts.running += 1,
+            "pending" => counts.pending += 1,
+            "queued" => counts.queued += 1,
+            "failed" => {
+                let finished_today = job
+                    .finished_at
+                    .as_deref()
+                    .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+                    .map(|t| t.with_timezone(&chrono::Local).date_naive() == today)
+                    .unwrap_or(false);
+                if finished_today {
+                    counts.failed_today += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    counts
+}
+
+fn started_at(job: &Job) -> i64 {
+    chrono::DateTime::parse_from_rfc3339(&job.started_at)
+        .map(|t| t.timestamp_millis())
+        .unwrap_or(0)
+}
+
+// No status filter means every active job; results default to newest first
+fn filter_jobs(
+    jobs: Vec<Job>,
+    status: Option<&str>,
+    task_type: Option<&str>,
+    sort_by: Option<&str>,
+) -> CommandResult<JobList> {
+    if let Some(status) = status {
+        if !JOB_STATUSES.contains(&status) {
+            return Err(CommandError::InvalidInput(format!(
+                "unknown status '{}', expected one of {}",
+                status,
+                JOB_STATUSES.join(", ")
+            )));
+        }
+    }
+    if task_type.is_some_and(|t| t.trim().is_empty()) {
+        return Err(CommandError::InvalidInput("task_type must not be empty".to_string()));
+    }
+
+    let counts = count_jobs(&jobs);
+    let mut items: Vec<Job> = jobs
+        .into_iter()
+        .filter(|job| match status {
+            Some(status) => job.status == status,
+            None => job.is_active(),
+        })
+        .filter(|job| task_type.is_none() || task_type == Some(job.task_type.as_str()))
+        .collect();
+
+    match sort_by.unwrap_or("started_at") {
+        "started_at" => items.sort_by_key(|job| std::cmp::Reverse(started_at(job))),
+        "progress" => items.sort_by(|a, b| b.progress.total_cmp(&a.progress)),
+        "name" => items.sort_by_key(|job| job.name.to_lowercase()),
+        other => {
+            return Err(CommandError::InvalidInput(format!(
+                "unknown sort_by '{}', expected started_at, progress or name",
+                other
+            )));
+        }
+    }
+    Ok(JobList { items, counts })
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_active_jobs(
+    jobs: State<'_, JobManager>,
+    settings: State<'_, SettingsStore>,
+    poller: State<'_, JobPoller>,
+    status: Option<String>,
+    task_type: Option<String>,
+    sort_by: Option<String>,
+) -> CommandResult<JobList> {
+    // Remote agents only report their active jobs
+    let settings = settings.get();
+    let all = match hosts::active_host(&settings) {
+        ActiveHost::Local => jobs.all(),
+        ActiveHost::Remote(host) => {
+            let jobs = hosts::fetch_jobs(&host)?;
+            // Their progress comes from polling from here on
+            jobs.iter().for_each(|job| poller.track(job));
+            jobs
+        }
+    };
+    let mut list = filter_jobs(all, status.as_deref(), task_type.as_deref(), sort_by.as_deref())?;
+    list.items = timestamps::localized(&settings, list.items);
+    Ok(list)
+}
+
+#[derive(Serialize)]
+pub struct JobLogs {
+    pub job_id: String,
+    pub status: String,
+    // Index of the first line returned
+    pub from: usize,
+    pub lines: Vec<String>,
+}
+
+// Replays the log from line `from` (0 by default), as a transfer when it's
+// large (see transfers)
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_job_logs(
+    jobs: State<'_, JobManager>,
+    transfers: State<'_, TransferStore>,
+    job_id: String,
+    from: Option<usize>,
+) -> CommandResult<MaybeTransfer<JobLogs>> {
+    let job = jobs
+        .get(&job_id)
+        .ok_or_else(|| CommandError::NotFound(format!("job {}", job_id)))?;
+    let from = from.unwrap_or(0).min(job.logs.len());
+    transfers::respond(
+        &transfers,
+        JobLogs {
+            job_id: job.id,
+            status: job.status,
+            from,
+            lines: job.logs[from..].to_vec(),
+        },
+    )
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_job(jobs: State<'_, JobManager>, settings: State<'_, SettingsStore>, job_id: String) -> CommandResult<Job> {
+    let job = jobs
+        .get(&job_id)
+        .ok_or_else(|| CommandError::NotFound(format!("job {}", job_id)))?;
+    Ok(timestamps::localized(&settings.get(), job))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A manager without mock jobs, and every update it pushed
+    fn manager(gate: Gate) -> (JobManager, Arc<Mutex<Vec<Job>>>) {
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let seen = updates.clone();
+        let mut manager = JobManager::empty(gate);
+        manager.on_update = Arc::new(move |job: &Job| seen.lock().unwrap().push(job.clone()));
+        (manager, updates)
+    }
+
+    fn open_gate(dir: &tempfile::TempDir) -> Gate {
+        Gate::load(dir.path().join("automation.json"))
+    }
+
+    fn paused_gate(dir: &tempfile::TempDir) -> Gate {
+        Gate::paused(dir.path().join("automation.json"))
+    }
+
+    #[test]
+    fn ok_work_completes_and_err_work_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, updates) = manager(open_gate(&dir));
+        let ok = manager.spawn("Ok", "test", |handle| {
+            handle.set_progress(0.5);
+            handle.log("halfway");
+            handle.set_result(serde_json::json!({ "answer": 42 }));
+            Ok(())
+        });
+        let err = manager.spawn("Err", "test", |_| Err("it broke".to_string()));
+        assert_eq!(ok.status, "running");
+        assert_ne!(ok.id, err.id);
+
+        let ok = manager.wait_finished(&ok.id);
+        assert_eq!(ok.status, "completed");
+        assert_eq!(ok.progress, 1.0);
+        assert_eq!(ok.logs, ["halfway"]);
+        assert_eq!(ok.result, Some(serde_json::json!({ "answer": 42 })));
+        assert!(ok.finished_at.is_some());
+
+        let err = manager.wait_finished(&err.id);
+        assert_eq!(err.status, "failed");
+        assert_eq!(err.error.as_deref(), Some("it broke"));
+
+        let updates = updates.lock().unwrap();
+        let statuses: Vec<&str> = updates.iter().filter(|j| j.id == ok.id).map(|j| j.status.as_str()).collect();
+        assert_eq!(statuses.first(), Some(&"running"));
+        assert_eq!(statuses.last(), Some(&"completed"));
+    }
+
+    #[test]
+    fn a_panic_fails_the_job() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, _) = manager(open_gate(&dir));
+        let job = manager.spawn("Panics", "test", |handle| {
+            handle.log("about to panic");
+            panic!("index out of bounds");
+        });
+        let job = manager.wait_finished(&job.id);
+        assert_eq!(job.status, "failed");
+        assert_eq!(job.error.as_deref(), Some("the job panicked: index out of bounds"));
+        assert!(job.finished_at.is_some());
+
+        let formatted = manager.spawn("Formatted", "test", |_| panic!("{} went wrong", 2));
+        let formatted = manager.wait_finished(&formatted.id);
+        assert_eq!(formatted.error.as_deref(), Some("the job panicked: 2 went wrong"));
+    }
+
+    #[test]
+    fn progress_is_clamped_and_the_log_is_bounded() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, _) = manager(open_gate(&dir));
+        let job = manager.spawn("Chatty", "test", |handle| {
+            handle.set_progress(7.0);
+            for i in 0..MAX_LOG_LINES + 5 {
+                handle.log(format!("line {}", i));
+            }
+            Err("stop before completing".to_string())
+        });
+        let job = manager.wait_finished(&job.id);
+        assert_eq!(job.progress, 1.0);
+        assert_eq!(job.logs.len(), MAX_LOG_LINES);
+        assert_eq!(job.logs[0], "line 5");
+    }
+
+    #[test]
+    fn paused_jobs_are_held_until_released() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, _) = manager(paused_gate(&dir));
+        let ran = Arc::new(AtomicBool::new(false));
+        let flag = ran.clone();
+        let job = manager.spawn("Held", "test", move |_| {
+            flag.store(true, Ordering::SeqCst);
+            Ok(())
+        });
+        assert_eq!(job.status, "queued");
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!ran.load(Ordering::SeqCst));
+        assert_eq!(manager.held_jobs().len(), 1);
+
+        let started = manager.release_held();
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].status, "running");
+        assert_eq!(manager.wait_finished(&job.id).status, "completed");
+        assert!(ran.load(Ordering::SeqCst));
+        assert!(manager.held_jobs().is_empty());
+    }
+
+    #[test]
+    fn discarded_jobs_fail_without_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, _) = manager(paused_gate(&dir));
+        let ran = Arc::new(AtomicBool::new(false));
+        let flag = ran.clone();
+        let job = manager.spawn("Held", "test", move |_| {
+            flag.store(true, Ordering::SeqCst);
+            Ok(())
+        });
+        let discarded = manager.discard_held("discarded on resume");
+        assert_eq!(discarded.len(), 1);
+        let job = manager.get(&job.id).unwrap();
+        assert_eq!(job.status, "failed");
+        assert_eq!(job.error.as_deref(), Some("discarded on resume"));
+        assert!(!ran.load(Ordering::SeqCst));
+        assert!(manager.release_held().is_empty());
+    }
+
+    #[test]
+    fn prune_keeps_active_and_recent_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, _) = manager(open_gate(&dir));
+        let done = manager.spawn("Done", "test", |_| Ok(()));
+        manager.wait_finished(&done.id);
+        let (tx, rx) = std::sync::mpsc::channel::<()>();
+        let running = manager.spawn("Running", "test", move |_| {
+            let _ = rx.recv();
+            Ok(())
+        });
+
+        let an_hour_ago = chrono::Utc::now() - chrono::Duration::hours(1);
+        assert_eq!(manager.prune_finished(an_hour_ago), 0);
+        let soon = chrono::Utc::now() + chrono::Duration::seconds(1);
+        assert_eq!(manager.prune_finished(soon), 1);
+        assert!(manager.get(&done.id).is_none());
+        assert!(manager.get(&running.id).is_some());
+        tx.send(()).unwrap();
+        manager.wait_finished(&running.id);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commands_stream_output_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, _) = manager(open_gate(&dir));
+        let job = manager.spawn("Echo", "test", |handle| {
+            let (status, lines) = handle.run_command_captured("sh", &["-c", "echo one; echo two"])?;
+            assert!(status.success());
+            assert_eq!(lines, ["one", "two"]);
+            Ok(())
+        });
+        let job = manager.wait_finished(&job.id);
+        assert_eq!(job.status, "completed", "{:?}", job.error);
+        assert_eq!(job.logs, ["$ sh -c echo one; echo two", "one", "two"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn invalid_utf8_output_is_replaced_and_read_to_the_end() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = JobManager::empty(open_gate(&dir));
+        // More than a pipe buffer after the bad bytes, on both streams
+        let script = "printf 'a\\377b\\r\\n'; printf '\\376\\n' >&2; seq 1 15000; seq 1 15000 >&2";
+        let job = manager.spawn("Binary", "test", move |handle| {
+            let (status, lines) = handle.run_command_captured("sh", &["-c", script])?;
+            assert!(status.success());
+            assert!(lines.contains(&"a\u{FFFD}b".to_string()));
+            assert!(lines.contains(&"\u{FFFD}".to_string()));
+            assert_eq!(lines.iter().filter(|l| l.as_str() == "15000").count(), 2);
+            Ok(())
+        });
+        let job = manager.wait_finished(&job.id);
+        assert_eq!(job.status, "completed", "{:?}", job.error);
+    }
+
+    #[test]
+    fn lines_split_on_lf_and_crlf() {
+        let mut lines = Vec::new();
+        for_each_line(&b"one\r\ntwo\n\nlast"[..], |line| lines.push(line));
+        assert_eq!(lines, ["one", "two", "", "last"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn timed_out_commands_are_killed() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, _) = manager(open_gate(&dir));
+        let job = manager.spawn("Slow", "test", |handle| {
+            handle
+                .run_command_limited("sleep", &["30"], Some(Duration::from_millis(200)))
+                .map(|_| ())
+        });
+        let job = manager.wait_finished(&job.id);
+        assert_eq!(job.status, "failed");
+        assert_eq!(job.error.as_deref(), Some("sleep was killed after the 0s timeout"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn terminate_children_stops_programs_and_refuses_new_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, _) = manager(open_gate(&dir));
+        let job = manager.spawn("Sleeper", "test", |handle| handle.run_command("sleep", &["30"]).map(|_| ()));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while manager.inner.lock().unwrap().children.is_empty() {
+            assert!(Instant::now() < deadline, "sleep never started");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(manager.terminate_children(Duration::from_secs(2)), 1);
+        manager.wait_finished(&job.id);
+
+        let refused = manager.spawn("Late", "test", |handle| handle.run_command("true", &[]).map(|_| ()));
+        let refused = manager.wait_finished(&refused.id);
+        assert_eq!(refused.error.as_deref(), Some("Halbert is shutting down"));
+    }
+
+    const KEY: &str = "-----BEGIN KEY-----\nMIIEvQIBADANBgkqhkiG9w0BAQEFAASC\nab\n-----END KEY-----\n";
+
+    fn strings(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    // The job's log once `work` has run with `secret` registered
+    fn logged(secret: &'static str, work: fn(&JobHandle) -> Result<(), String>) -> Job {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, _) = manager(open_gate(&dir));
+        let job = manager.spawn("Secretive", "test", move |handle| {
+            handle.redact_value(secret);
+            work(handle)
+        });
+        manager.wait_finished(&job.id)
+    }
+
+    #[test]
+    fn a_secret_is_masked_wherever_it_appears_in_a_line() {
+        let secrets = strings(&["hunter2", "hunter2-longer"]);
+        let lines = redact_lines(&strings(&["pw=hunter2-longer x", "hunter2hunter2", "nothing"]), &secrets);
+        assert_eq!(lines, ["pw=[redacted] x", "[redacted]", "nothing"]);
+    }
+
+    #[test]
+    fn a_multi_line_secret_is_masked_across_lines_short_ones_too() {
+        let secrets = strings(&[KEY.trim_end()]);
+        let printed = strings(&["key:", "-----BEGIN KEY-----", "MIIEvQIBADANBgkqhkiG9w0BAQEFAASC", "ab", "-----END KEY-----", "ab"]);
+        assert_eq!(
+            redact_lines(&printed, &secrets),
+            ["key:", "[redacted]", "[redacted]", "[redacted]", "[redacted]", "ab"]
+        );
+        // Its longer lines on their own, but not a short one out of context
+        let alone = strings(&["seen MIIEvQIBADANBgkqhkiG9w0BAQEFAASC here", "ab"]);
+        assert_eq!(redact_lines(&alone, &secrets), ["seen [redacted] here", "ab"]);
+    }
+
+    #[test]
+    fn a_secret_split_between_two_log_calls_is_masked_in_both() {
+        let job = logged("s3cr3t-t0ken", |handle| {
+            handle.log("token: s3cr3t");
+            handle.log("-t0ken and more");
+            handle.log("s3cr3t alone");
+            Ok(())
+        });
+        assert_eq!(job.logs, ["token: [redacted]", "[redacted] and more", "s3cr3t alone"]);
+    }
+
+    #[test]
+    fn a_multi_line_secret_logged_line_by_line_is_masked() {
+        let job = logged(KEY, |handle| {
+            for line in KEY.lines() {
+                handle.log(line);
+            }
+            handle.log("done");
+            Err(format!("couldn't use\n{}", KEY))
+        });
+        assert_eq!(job.logs, ["[redacted]", "[redacted]", "[redacted]", "[redacted]", "done"]);
+        assert_eq!(job.error.as_deref(), Some("couldn't use\n[redacted]\n[redacted]\n[redacted]\n[redacted]\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_secret_across_a_read_boundary_in_program_output_is_masked() {
+        // The secret straddles the 8 KiB reader buffer, in output written in
+        // two pieces, and a multi-line one comes out with \r\n
+        let job = logged("p4ssw0rd", |handle| {
+            let script = "printf '%8190s' x | tr ' ' y; printf 'p4ss'; sleep 0.1; printf 'w0rd\\n'";
+            let (_, lines) = handle.run_command_captured("sh", &["-c", script])?;
+            assert_eq!(lines.len(), 1);
+            assert!(lines[0].ends_with("yx[redacted]"), "{}", &lines[0][8180..]);
+            Ok(())
+        });
+        assert_eq!(job.status, "completed", "{:?}", job.error);
+        assert!(job.logs[1].ends_with("yx[redacted]"));
+
+        let job = logged(KEY, |handle| {
+            let (_, lines) = handle.run_command_captured("sh", &["-c", "printf 'before\\r\\n'; printf '%s' \"$0\" | sed 's/$/\\r/'", KEY])?;
+            assert_eq!(lines, ["before", "[redacted]", "[redacted]", "[redacted]", "[redacted]"]);
+            Ok(())
+        });
+        assert_eq!(job.status, "completed", "{:?}", job.error);
+        assert_eq!(&job.logs[1..], ["before", "[redacted]", "[redacted]", "[redacted]", "[redacted]"]);
+    }
+}
@@ -0,0 +1,309 @@
+// Following the systemd journal live.
+//
+// `follow_journal` starts `journalctl -f -o json` and emits what it prints
+// as `journal://entries` batches, flushed every FLUSH_INTERVAL or at
+// MAX_BATCH entries. The filter can change while following: a different
+// unit or priority restarts journalctl with new arguments from the cursor
+// of the last entry read, so nothing is lost or repeated, while the grep
+// pattern is matched against each message here and applies to the very
+// next batch. Each batch carries the filter it was made with. One follow
+// runs at a time; starting another replaces it.
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::app::{AppHandle, Emitter, State};
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+
+const FLUSH_INTERVAL: Duration = Duration::from_millis(250);
+const MAX_BATCH: usize = 200;
+// Entries shown from before the follow started
+const BACKLOG_LINES: u32 = 50;
+// Lowest priority syslog knows (debug)
+const MAX_PRIORITY: u8 = 7;
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct JournalFilter {
+    pub unit: Option<String>,
+    // Entries at this priority or more severe (0 emerg .. 7 debug)
+    pub priority_max: Option<u8>,
+    // Regex matched against the message
+    pub grep: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct JournalEntry {
+    // RFC 3339
+    pub at: String,
+    pub unit: Option<String>,
+    pub identifier: Option<String>,
+    pub priority: Option<u8>,
+    pub pid: Option<u32>,
+    pub message: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct JournalBatch {
+    pub filter: JournalFilter,
+    pub entries: Vec<JournalEntry>,
+}
+
+// Journal JSON has every field as a string; MESSAGE is an array of bytes
+// when it isn't valid UTF-8
+fn text(entry: &Value, name: &str) -> Option<String> {
+    match entry.get(name)? {
+        Value::String(s) => Some(s.clone()),
+        Value::Array(bytes) => {
+            let bytes: Vec<u8> = bytes.iter().filter_map(|b| b.as_u64().map(|b| b as u8)).collect();
+            Some(String::from_utf8_lossy(&bytes).into_owned())
+        }
+        _ => None,
+    }
+}
+
+pub fn parse_entry(entry: &Value) -> Option<JournalEntry> {
+    let micros: i64 = text(entry, "__REALTIME_TIMESTAMP")?.parse().ok()?;
+    Some(JournalEntry {
+        at: chrono::DateTime::from_timestamp_micros(micros)?.to_rfc3339(),
+        unit: text(entry, "_SYSTEMD_UNIT"),
+        identifier: text(entry, "SYSLOG_IDENTIFIER"),
+        priority: text(entry, "PRIORITY").and_then(|p| p.parse().ok()),
+        pid: text(entry, "_PID").and_then(|p| p.parse().ok()),
+        message: text(entry, "MESSAGE").unwrap_or_default(),
+    })
+}
+
+// Unit names as systemd allows them, and nothing journalctl would read as
+// an option
+fn check_unit(unit: &str) -> CommandResult<()> {
+    let valid = !unit.is_empty()
+        && !unit.starts_with('-')
+        && unit.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '@' | '.' | '_' | ':' | '-' | '\\'));
+    if valid {
+        Ok(())
+    } else {
+        Err(CommandError::InvalidInput(format!("'{}' is not a unit name", unit)))
+    }
+}
+
+// A checked filter and its compiled grep pattern
+fn compile(
+    unit: Option<String>,
+    priority_max: Option<u8>,
+    grep: Option<String>,
+) -> CommandResult<(JournalFilter, Option<Regex>)> {
+    let unit = unit.map(|u| u.trim().to_string()).filter(|u| !u.is_empty());
+    if let Some(unit) = &unit {
+        check_unit(unit)?;
+    }
+    if priority_max.is_some_and(|p| p > MAX_PRIORITY) {
+        return Err(CommandError::InvalidInput(format!(
+            "priority_max must be 0 to {}",
+            MAX_PRIORITY
+        )));
+    }
+    let grep = grep.filter(|g| !g.is_empty());
+    let regex = grep
+        .as_deref()
+        .map(Regex::new)
+        .transpose()
+        .map_err(|e| CommandError::InvalidInput(format!("invalid grep pattern: {}", e)))?;
+    Ok((JournalFilter { unit, priority_max, grep }, regex))
+}
+
+// Where a journalctl run picks up
+pub enum Start {
+    // With some entries from before
+    Backlog,
+    After(String),
+    // A restart before anything was read
+    Now,
+}
+
+pub fn journalctl_args(filter: &JournalFilter, start: &Start) -> Vec<String> {
+    let mut args: Vec<String> = ["-q", "--no-pager", "-f", "-o", "json"].iter().map(|s| s.to_string()).collect();
+    args.push(match start {
+        Start::Backlog => format!("--lines={}", BACKLOG_LINES),
+        Start::After(cursor) => format!("--after-cursor={}", cursor),
+        Start::Now => "--lines=0".to_string(),
+    });
+    if let Some(priority) = filter.priority_max {
+        args.push(format!("--priority={}", priority));
+    }
+    if let Some(unit) = &filter.unit {
+        args.push(format!("--unit={}", unit));
+    }
+    args
+}
+
+// What the emitter reads on every entry; swapped whole by a filter change
+struct Current {
+    filter: JournalFilter,
+    regex: Option<Regex>,
+}
+
+struct Running {
+    child: Child,
+    // Returns the cursor of the last entry it read
+    emitter: JoinHandle<Option<String>>,
+}
+
+#[derive(Default)]
+struct Follow {
+    running: Option<Running>,
+    current: Option<Arc<Mutex<Current>>>,
+}
+
+#[derive(Default)]
+pub struct JournalFollower {
+    follow: Mutex<Follow>,
+}
+
+fn spawn(app: &AppHandle, current: Arc<Mutex<Current>>, start: Start) -> CommandResult<Running> {
+    let args = journalctl_args(&current.lock().unwrap().filter, &start);
+    let mut child = Command::new("journalctl")
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| CommandError::Internal("journalctl has no stdout".to_string()))?;
+
+    let (lines, received) = mpsc::channel::<String>();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if lines.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    // Runs until journalctl exits and its output is drained
+    let app = app.clone();
+    let emitter = std::thread::spawn(move || {
+        let mut cursor = None;
+        let mut batch = Vec::new();
+        let mut flushed = Instant::now();
+        loop {
+            let next = received.recv_timeout(FLUSH_INTERVAL);
+            if let Ok(line) = &next {
+                if let Ok(entry) = serde_json::from_str::<Value>(line) {
+                    cursor = text(&entry, "__CURSOR").or(cursor);
+                    let current = current.lock().unwrap();
+                    let wanted = parse_entry(&entry).filter(|e| match &current.regex {
+                        Some(regex) => regex.is_match(&e.message),
+                        None => true,
+                    });
+                    batch.extend(wanted);
+                }
+            }
+            let done = matches!(next, Err(RecvTimeoutError::Disconnected));
+            if !batch.is_empty() && (done || batch.len() >= MAX_BATCH || flushed.elapsed() >= FLUSH_INTERVAL) {
+                let filter = current.lock().unwrap().filter.clone();
+                let entries = std::mem::take(&mut batch);
+                let _ = app.emit("journal://entries", JournalBatch { filter, entries });
+                flushed = Instant::now();
+            }
+            if done {
+                return cursor;
+            }
+        }
+    });
+    Ok(Running { child, emitter })
+}
+
+// Stops journalctl and waits until everything it printed was emitted
+fn stop(running: Running) -> Option<String> {
+    let Running { mut child, emitter } = running;
+    let _ = child.kill();
+    let _ = child.wait();
+    emitter.join().ok().flatten()
+}
+
+impl JournalFollower {
+    pub fn stop_all(&self) -> bool {
+        let mut follow = self.follow.lock().unwrap();
+        follow.current = None;
+        follow.running.take().map(stop).is_some()
+    }
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn follow_journal(
+    app: AppHandle,
+    follower: State<'_, JournalFollower>,
+    unit: Option<String>,
+    priority_max: Option<u8>,
+    grep: Option<String>,
+) -> CommandResult<JournalFilter> {
+    if exec::find_in_path("journalctl").is_none() {
+        return Err(CommandError::ToolMissing("journalctl".to_string()));
+    }
+    let (filter, regex) = compile(unit, priority_max, grep)?;
+    let mut follow = follower.follow.lock().unwrap();
+    if let Some(running) = follow.running.take() {
+        stop(running);
+    }
+    let current = Arc::new(Mutex::new(Current {
+        filter: filter.clone(),
+        regex,
+    }));
+    follow.running = Some(spawn(&app, current.clone(), Start::Backlog)?);
+    follow.current = Some(current);
+    println!("[Halbert] Following the journal ({:?})", filter);
+    Ok(filter)
+}
+
+// Unit and priority changes restart journalctl from the last entry read;
+// a grep change alone doesn't. An invalid filter changes nothing.
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn update_journal_filter(
+    app: AppHandle,
+    follower: State<'_, JournalFollower>,
+    unit: Option<String>,
+    priority_max: Option<u8>,
+    grep: Option<String>,
+) -> CommandResult<JournalFilter> {
+    let (filter, regex) = compile(unit, priority_max, grep)?;
+    let mut follow = follower.follow.lock().unwrap();
+    let current = follow
+        .current
+        .clone()
+        .ok_or_else(|| CommandError::NotFound("no journal follow is running".to_string()))?;
+    let previous = current.lock().unwrap().filter.clone();
+    if previous.unit != filter.unit || previous.priority_max != filter.priority_max {
+        let cursor = follow.running.take().and_then(stop);
+        *current.lock().unwrap() = Current {
+            filter: filter.clone(),
+            regex,
+        };
+        let start = cursor.map_or(Start::Now, Start::After);
+        follow.running = Some(spawn(&app, current, start)?);
+    } else {
+        *current.lock().unwrap() = Current {
+            filter: filter.clone(),
+            regex,
+        };
+    }
+    Ok(filter)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn stop_journal_follow(follower: State<'_, JournalFollower>) {
+    if follower.stop_all() {
+        println!("[Halbert] Stopped following the journal");
+    }
+}
@@ -0,0 +1,385 @@
+// Kernel modules, DKMS builds and module signing.
+//
+// Three sources, each on its own: /proc/modules for what is loaded, `dkms
+// status` for out-of-tree modules per installed kernel, and `mokutil
+// --sb-state` (or the SecureBoot EFI variable) plus securityfs for secure
+// boot and signature enforcement. A source that's absent or unreadable
+// leaves its section None, with the reason under `unavailable`. A DKMS
+// module with no "installed" build for the newest installed kernel is
+// flagged, since booting that kernel would come up without it; reboot
+// status lists those and the alert engine gets kernel.dkms_not_installed.
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::process::Command;
+use sysinfo::System;
+
+use crate::exec;
+use crate::reboot;
+
+const PROC_MODULES: &str = "/proc/modules";
+const SECURE_BOOT_VAR: &str = "/sys/firmware/efi/efivars/SecureBoot-8be4df61-93ca-11d2-aa0d-00e098032b8c";
+const LOCKDOWN: &str = "/sys/kernel/security/lockdown";
+const SIG_ENFORCE: &str = "/sys/module/module/parameters/sig_enforce";
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct LoadedModule {
+    pub name: String,
+    pub size_bytes: u64,
+    pub use_count: u32,
+    pub used_by: Vec<String>,
+    // "Live", "Loading" or "Unloading"
+    pub state: String,
+    // Taint letters, e.g. "OE" for out-of-tree and unsigned
+    pub taints: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DkmsModule {
+    pub module: String,
+    pub version: String,
+    // None for a module that's only been added
+    pub kernel: Option<String>,
+    pub arch: Option<String>,
+    // "added", "built", "installed", ...
+    pub state: String,
+    // What dkms said after the state, e.g. a WARNING in parentheses
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Default)]
+pub struct SigningStatus {
+    // None on a system without EFI
+    pub secure_boot: Option<bool>,
+    // "mokutil" or "efivars"
+    pub source: Option<String>,
+    // Shim can be told not to validate; mokutil says so
+    pub shim_validation_disabled: bool,
+    // The bracketed lockdown mode, e.g. "integrity"
+    pub lockdown: Option<String>,
+    pub signatures_enforced: Option<bool>,
+    // Loaded modules carrying the unsigned taint (E)
+    pub unsigned_loaded: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct KernelModules {
+    pub running_kernel: Option<String>,
+    pub newest_installed_kernel: Option<String>,
+    pub loaded: Option<Vec<LoadedModule>>,
+    pub dkms: Option<Vec<DkmsModule>>,
+    pub signing: Option<SigningStatus>,
+    // DKMS modules not installed for the newest kernel
+    pub flagged: Vec<String>,
+    // Why a section is missing, by section
+    pub unavailable: BTreeMap<String, String>,
+    pub checked_at: String,
+}
+
+// Lines of `name size refcount deps state offset [taints]`; deps is "-" or
+// a comma-terminated list
+pub fn parse_proc_modules(text: &str) -> Vec<LoadedModule> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 5 {
+                return None;
+            }
+            let used_by = match fields[3] {
+                "-" => Vec::new(),
+                deps => deps.split(',').filter(|d| !d.is_empty()).map(str::to_string).collect(),
+            };
+            let taints = fields
+                .get(6)
+                .and_then(|t| t.strip_prefix('(')?.strip_suffix(')'))
+                .map(str::to_string);
+            Some(LoadedModule {
+                name: fields[0].to_string(),
+                size_bytes: fields[1].parse().ok()?,
+                use_count: fields[2].parse().ok()?,
+                used_by,
+                state: fields[4].to_string(),
+                taints,
+            })
+        })
+        .collect()
+}
+
+// Both layouts dkms has used:
+//   zfs/2.2.2, 6.5.0-14-generic, x86_64: installed     (dkms 3)
+//   zfs, 2.2.2, 6.5.0-14-generic, x86_64: installed    (dkms 2)
+//   v4l2loopback/0.12.7: added
+// Warnings and errors on their own lines are skipped.
+pub fn parse_dkms_status(text: &str) -> Vec<DkmsModule> {
+    text.lines()
+        .filter_map(|line| {
+            let (left, right) = line.trim().split_once(": ")?;
+            let mut fields: Vec<&str> = left.split(',').map(str::trim).collect();
+            let first = fields.remove(0);
+            let (module, version) = match first.split_once('/') {
+                Some((module, version)) => (module, version.to_string()),
+                None if !fields.is_empty() => (first, fields.remove(0).to_string()),
+                None => return None,
+            };
+            if module.is_empty() || module.contains(' ') {
+                return None;
+            }
+            let (state, detail) = match right.trim().split_once(' ') {
+                Some((state, detail)) => (state, Some(detail.trim().to_string())),
+                None => (right.trim(), None),
+            };
+            Some(DkmsModule {
+                module: module.to_string(),
+                version,
+                kernel: fields.first().map(|k| k.to_string()),
+                arch: fields.get(1).map(|a| a.to_string()),
+                state: state.trim_end_matches(',').to_string(),
+                detail,
+            })
+        })
+        .collect()
+}
+
+// Modules with no version installed for `kernel`, sorted
+pub fn not_installed_for(modules: &[DkmsModule], kernel: &str) -> Vec<String> {
+    let mut names: Vec<String> = modules.iter().map(|m| m.module.clone()).collect();
+    names.sort();
+    names.dedup();
+    names.retain(|name| {
+        !modules
+            .iter()
+            .any(|m| m.module == *name && m.kernel.as_deref() == Some(kernel) && m.state == "installed")
+    });
+    names
+}
+
+// Some(true/false) for "SecureBoot enabled/disabled"; None when mokutil
+// says the system has no secure boot or no EFI variables. The second value
+// is whether shim validation has been turned off.
+pub fn parse_sb_state(text: &str) -> (Option<bool>, bool) {
+    let mut secure_boot = None;
+    let mut validation_disabled = false;
+    for line in text.lines().map(|l| l.trim().to_ascii_lowercase()) {
+        match line.as_str() {
+            "secureboot enabled" => secure_boot = Some(true),
+            "secureboot disabled" => secure_boot = Some(false),
+            _ if line.contains("validation is disabled") => validation_disabled = true,
+            _ => {}
+        }
+    }
+    (secure_boot, validation_disabled)
+}
+
+// "none [integrity] confidentiality" -> "integrity"
+pub fn parse_lockdown(text: &str) -> Option<String> {
+    let start = text.find('[')?;
+    let end = text[start..].find(']')? + start;
+    Some(text[start + 1..end].to_string())
+}
+
+// Stdout and stderr together, whatever the exit code; mokutil reports
+// "not supported" with a failure status
+fn run(program: &str, args: &[&str]) -> Result<String, String> {
+    if exec::find_in_path(program).is_none() {
+        return Err(format!("{} is not installed", program));
+    }
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("{} failed: {}", program, e))?;
+    Ok(format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    ))
+}
+
+fn dkms_modules() -> Result<Vec<DkmsModule>, String> {
+    run("dkms", &["status"]).map(|text| parse_dkms_status(&text))
+}
+
+// For reboot status: None when dkms isn't available or no kernel is found
+pub fn dkms_not_installed() -> Option<Vec<String>> {
+    let kernel = reboot::newest_installed_kernel()?;
+    dkms_modules().ok().map(|modules| not_installed_for(&modules, &kernel))
+}
+
+fn signing(loaded: Option<&[LoadedModule]>) -> Result<SigningStatus, String> {
+    let mut status = SigningStatus::default();
+    match run("mokutil", &["--sb-state"]) {
+        Ok(text) => {
+            (status.secure_boot, status.shim_validation_disabled) = parse_sb_state(&text);
+            status.source = status.secure_boot.map(|_| "mokutil".to_string());
+        }
+        // The variable's last byte is the state
+        Err(_) => {
+            let var = std::fs::read(SECURE_BOOT_VAR).unwrap_or_default();
+            if let Some(&value) = var.last() {
+                status.secure_boot = Some(value == 1);
+                status.source = Some("efivars".to_string());
+            }
+        }
+    }
+    status.lockdown = std::fs::read_to_string(LOCKDOWN).ok().and_then(|t| parse_lockdown(&t));
+    status.signatures_enforced = std::fs::read_to_string(SIG_ENFORCE).ok().map(|t| t.trim() == "Y");
+    status.unsigned_loaded = loaded
+        .unwrap_or_default()
+        .iter()
+        .filter(|m| m.taints.as_deref().is_some_and(|t| t.contains('E')))
+        .map(|m| m.name.clone())
+        .collect();
+    let nothing_read = status.source.is_none() && status.lockdown.is_none() && status.signatures_enforced.is_none();
+    if nothing_read {
+        return Err("neither mokutil, the SecureBoot EFI variable nor securityfs is readable".to_string());
+    }
+    Ok(status)
+}
+
+fn section<T>(unavailable: &mut BTreeMap<String, String>, name: &str, result: Result<T, String>) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            unavailable.insert(name.to_string(), e);
+            None
+        }
+    }
+}
+
+pub fn probe() -> KernelModules {
+    let mut unavailable = BTreeMap::new();
+    let loaded = std::fs::read_to_string(PROC_MODULES)
+        .map(|text| parse_proc_modules(&text))
+        .map_err(|e| format!("{}: {}", PROC_MODULES, e));
+    let loaded = section(&mut unavailable, "loaded", loaded);
+    let dkms = section(&mut unavailable, "dkms", dkms_modules());
+    let signing = section(&mut unavailable, "signing", signing(loaded.as_deref()));
+    let newest = reboot::newest_installed_kernel();
+    let flagged = match (&dkms, &newest) {
+        (Some(modules), Some(kernel)) => not_installed_for(modules, kernel),
+        _ => Vec::new(),
+    };
+    KernelModules {
+        running_kernel: System::kernel_version(),
+        newest_installed_kernel: newest,
+        loaded,
+        dkms,
+        signing,
+        flagged,
+        unavailable,
+        checked_at: chrono::Utc::now().to_rfc3339(),
+    }
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_kernel_modules() -> KernelModules {
+    probe()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // dkms 3.0 on Ubuntu 23.10, with a warning line and a module whose
+    // installed copy differs from the build
+    const DKMS_3: &str = "nvidia/535.129.03, 6.5.0-14-generic, x86_64: installed
+nvidia/535.129.03, 6.5.0-15-generic, x86_64: built
+virtualbox/7.0.12, 6.5.0-14-generic, x86_64: installed (WARNING! Diff between built and installed module!)
+v4l2loopback/0.12.7: added
+Warning: v4l2loopback has no kernel version set
+";
+    // dkms 2.8 on Ubuntu 20.04
+    const DKMS_2: &str = "zfs, 0.8.3, 5.4.0-150-generic, x86_64: installed
+zfs, 0.8.3, 5.4.0-155-generic, x86_64: installed
+wireguard, 1.0.20201112, 5.4.0-150-generic, x86_64: installed
+Error! Could not locate dkms.conf file.
+";
+
+    const PROC_MODULES_SAMPLE: &str = "nvidia_drm 77824 4 - Live 0x0000000000000000 (POE)
+drm_kms_helper 270336 1 nvidia_drm, Live 0x0000000000000000
+snd 135168 3 snd_hda_codec,snd_hda_core, Live 0xffffffffc0a00000
+vboxdrv 614400 0 - Loading 0x0000000000000000 (OE)
+";
+
+    fn dkms(module: &str, version: &str, kernel: Option<&str>, state: &str, detail: Option<&str>) -> DkmsModule {
+        DkmsModule {
+            module: module.to_string(),
+            version: version.to_string(),
+            kernel: kernel.map(str::to_string),
+            arch: kernel.map(|_| "x86_64".to_string()),
+            state: state.to_string(),
+            detail: detail.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn dkms_3_status() {
+        assert_eq!(
+            parse_dkms_status(DKMS_3),
+            [
+                dkms("nvidia", "535.129.03", Some("6.5.0-14-generic"), "installed", None),
+                dkms("nvidia", "535.129.03", Some("6.5.0-15-generic"), "built", None),
+                dkms(
+                    "virtualbox",
+                    "7.0.12",
+                    Some("6.5.0-14-generic"),
+                    "installed",
+                    Some("(WARNING! Diff between built and installed module!)")
+                ),
+                dkms("v4l2loopback", "0.12.7", None, "added", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn dkms_2_status() {
+        let modules = parse_dkms_status(DKMS_2);
+        assert_eq!(modules.len(), 3);
+        assert_eq!(modules[0], dkms("zfs", "0.8.3", Some("5.4.0-150-generic"), "installed", None));
+        assert_eq!(modules[2].module, "wireguard");
+        assert_eq!(modules[2].version, "1.0.20201112");
+    }
+
+    #[test]
+    fn modules_not_installed_for_the_newest_kernel_are_flagged() {
+        let modules = parse_dkms_status(DKMS_3);
+        // Only built for -15, and never built at all
+        assert_eq!(not_installed_for(&modules, "6.5.0-15-generic"), ["nvidia", "v4l2loopback", "virtualbox"]);
+        assert_eq!(not_installed_for(&modules, "6.5.0-14-generic"), ["v4l2loopback"]);
+        let modules = parse_dkms_status(DKMS_2);
+        assert_eq!(not_installed_for(&modules, "5.4.0-155-generic"), ["wireguard"]);
+        assert!(not_installed_for(&[], "6.5.0-15-generic").is_empty());
+    }
+
+    #[test]
+    fn mokutil_sb_state() {
+        assert_eq!(parse_sb_state("SecureBoot enabled\n"), (Some(true), false));
+        assert_eq!(parse_sb_state("SecureBoot disabled\nPlatform is in Setup Mode\n"), (Some(false), false));
+        assert_eq!(
+            parse_sb_state("SecureBoot enabled\nSecureBoot validation is disabled in shim\n"),
+            (Some(true), true)
+        );
+        assert_eq!(parse_sb_state("EFI variables are not supported on this system\n"), (None, false));
+        assert_eq!(parse_sb_state("This system doesn't support Secure Boot\n"), (None, false));
+    }
+
+    #[test]
+    fn lockdown_mode_is_the_bracketed_one() {
+        assert_eq!(parse_lockdown("none [integrity] confidentiality\n").as_deref(), Some("integrity"));
+        assert_eq!(parse_lockdown("[none] integrity confidentiality\n").as_deref(), Some("none"));
+        assert_eq!(parse_lockdown(""), None);
+    }
+
+    #[test]
+    fn proc_modules_with_dependents_and_taints() {
+        let loaded = parse_proc_modules(PROC_MODULES_SAMPLE);
+        assert_eq!(loaded.len(), 4);
+        assert_eq!(loaded[0].name, "nvidia_drm");
+        assert_eq!(loaded[0].size_bytes, 77824);
+        assert_eq!(loaded[0].use_count, 4);
+        assert!(loaded[0].used_by.is_empty());
+        assert_eq!(loaded[0].taints.as_deref(), Some("POE"));
+        assert_eq!(loaded[1].used_by, ["nvidia_drm"]);
+        assert_eq!(loaded[1].taints, None);
+        assert_eq!(loaded[2].used_by, ["snd_hda_codec", "snd_hda_core"]);
+        assert_eq!(loaded[3].state, "Loading");
+    }
+}
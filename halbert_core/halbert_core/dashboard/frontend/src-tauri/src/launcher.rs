@@ -0,0 +1,125 @@
+// Open a file manager or terminal at a path from the dashboard.
+//
+// Paths are checked by the sandbox and must sit under home, the corpus or
+// a mount the metrics report. Terminals are started from a command
+// template where `{path}` is replaced per argument; no shell is involved,
+// so paths with spaces or quotes are passed through intact.
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+#[cfg(feature = "gui")]
+use tauri_plugin_opener::OpenerExt;
+
+#[cfg(feature = "gui")]
+use crate::app::{AppHandle, State};
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+#[cfg(feature = "gui")]
+use crate::hosts::{self, ActiveHost};
+use crate::sandbox;
+use crate::settings::Settings;
+#[cfg(feature = "gui")]
+use crate::settings::SettingsStore;
+
+// Tried in order when terminal_command isn't set
+const BUILTIN_TERMINALS: &[&str] = &[
+    "gnome-terminal --working-directory={path}",
+    "konsole --workdir {path}",
+    "xfce4-terminal --working-directory={path}",
+    "kitty --directory {path}",
+    "alacritty --working-directory {path}",
+    "wezterm start --cwd {path}",
+    "foot --working-directory={path}",
+    "xterm",
+];
+
+fn program_of(template: &str) -> Option<&str> {
+    template.split_whitespace().next()
+}
+
+// The configured template, or the first built-in whose program is installed
+pub fn terminal_template(settings: &Settings) -> Option<String> {
+    if let Some(template) = settings.terminal_command.as_deref().filter(|t| !t.trim().is_empty()) {
+        return Some(template.to_string());
+    }
+    BUILTIN_TERMINALS
+        .iter()
+        .find(|t| program_of(t).and_then(exec::find_in_path).is_some())
+        .map(|t| t.to_string())
+}
+
+// Home, the corpus and the mounted filesystems (see sandbox::OPEN_PATH)
+fn resolve(settings: &Settings, path: &str) -> CommandResult<PathBuf> {
+    let resolved = sandbox::OPEN_PATH
+        .validate(settings, Path::new(&crate::impact::expand_home(path)))?
+        .into_path_buf();
+    if !resolved.exists() {
+        return Err(CommandError::NotFound(path.to_string()));
+    }
+    Ok(resolved)
+}
+
+fn spawn_terminal(template: &str, dir: &Path) -> CommandResult<()> {
+    let dir_text = dir.to_string_lossy();
+    let mut parts = template.split_whitespace().map(|part| part.replace("{path}", &dir_text));
+    let program = parts
+        .next()
+        .ok_or_else(|| CommandError::InvalidInput("terminal_command is empty".to_string()))?;
+    if exec::find_in_path(&program).is_none() && !Path::new(&program).is_file() {
+        return Err(CommandError::NotSupported(format!(
+            "terminal {} is not installed; set terminal_command in Settings",
+            program
+        )));
+    }
+
+    Command::new(&program)
+        .args(parts)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| CommandError::Internal(format!("failed to start {}: {}", program, e)))?;
+    Ok(())
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn open_path(
+    handle: AppHandle,
+    settings: State<'_, SettingsStore>,
+    path: String,
+    // "file_manager" or "terminal"
+    app: String,
+) -> CommandResult<()> {
+    let settings = settings.get();
+    if let ActiveHost::Remote(host) = hosts::active_host(&settings) {
+        return Err(CommandError::NotSupported(format!(
+            "paths on {} can't be opened from this machine",
+            host.name
+        )));
+    }
+    let resolved = resolve(&settings, &path)?;
+    // Given a file, open its folder
+    let dir = if resolved.is_dir() {
+        resolved.clone()
+    } else {
+        resolved.parent().map(Path::to_path_buf).unwrap_or(resolved)
+    };
+
+    match app.as_str() {
+        "file_manager" => handle
+            .opener()
+            .open_path(dir.to_string_lossy(), None::<&str>)
+            .map_err(|e| CommandError::Internal(format!("failed to open {}: {}", dir.display(), e))),
+        "terminal" => {
+            let template = terminal_template(&settings).ok_or_else(|| {
+                CommandError::NotSupported("no terminal emulator found; set terminal_command in Settings".to_string())
+            })?;
+            spawn_terminal(&template, &dir)
+        }
+        other => Err(CommandError::InvalidInput(format!(
+            "unknown app '{}', expected file_manager or terminal",
+            other
+        ))),
+    }
+}
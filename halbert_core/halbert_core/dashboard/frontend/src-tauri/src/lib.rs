@@ -1,11 +1,125 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
-use serde::Serialize;
+// Without the window most commands have no caller; halbert-agent runs the
+// ones another window can send it (see remote_core)
+#![cfg_attr(not(feature = "gui"), allow(dead_code))]
+
+#[cfg(all(feature = "agent", feature = "gui"))]
+compile_error!("halbert-agent is built without the window: cargo build --no-default-features --features agent");
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Instant;
 use sysinfo::System;
 
-#[tauri::command]
-fn greet(name: &str) -> String {
-    format!("Hello, {}! You've been greeted from Rust!", name)
-}
+use crate::app::{AppHandle, Emitter, Manager, Runtime, State};
+
+mod accounts;
+mod activity;
+#[cfg(feature = "agent")]
+pub mod agent;
+mod alerts;
+mod app;
+mod approval_templates;
+mod approvals;
+mod artifacts;
+mod audit;
+mod automation;
+mod backend;
+mod backend_import;
+mod backend_latency;
+mod backup;
+mod baselines;
+mod bundles;
+mod calibration;
+mod cancellation;
+mod certificates;
+mod changes;
+mod cleanup;
+mod collectors;
+#[cfg(feature = "gui")]
+mod command_stats;
+mod config_transfer;
+mod connections;
+mod consent;
+mod containers;
+mod conversations;
+mod corpora;
+mod corpus_health;
+mod corpus_import;
+mod corpus_sources;
+mod corpus_watch;
+mod db;
+mod desktop_notify;
+mod diagnostics;
+mod disk_history;
+mod documents;
+mod error;
+mod exec;
+mod firewall;
+mod gpu;
+#[cfg(not(feature = "gui"))]
+mod headless;
+mod hooks;
+mod hosts;
+mod http;
+mod impact;
+mod incidents;
+mod job_poller;
+mod job_templates;
+mod jobs;
+mod journal_follow;
+mod kernel_modules;
+mod launcher;
+mod liveness;
+mod network;
+mod notifications;
+#[cfg(feature = "gui")]
+mod onboarding;
+mod packages;
+mod palette;
+mod phrasing;
+mod policy;
+mod preview;
+mod process_tree;
+mod processes;
+mod ratelimit;
+mod readonly;
+mod reboot;
+mod reindex;
+mod remote_access;
+mod remote_core;
+mod report;
+mod retrieval_feedback;
+mod sampler;
+mod sandbox;
+mod scrape;
+mod secrets;
+mod selfcheck;
+mod selfusage;
+mod services;
+mod session;
+mod settings;
+mod settings_revisions;
+mod shutdown;
+mod smart;
+mod snapshots;
+mod ssh_hosts;
+mod startup;
+mod storage;
+mod suspend;
+mod text_feed;
+mod thermal;
+mod timestamps;
+mod timesync;
+mod transfers;
+mod units;
+mod usage_summary;
+mod user_usage;
+#[cfg(feature = "gui")]
+mod widget;
+#[cfg(feature = "gui")]
+mod window_capture;
+mod wol;
 
 #[derive(Serialize)]
 struct SystemInfo {
@@ -18,7 +132,7 @@ struct SystemInfo {
     cpu_count: usize,
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "gui", tauri::command)]
 fn get_system_info() -> SystemInfo {
     let mut sys = System::new_all();
     sys.refresh_all();
@@ -34,29 +148,116 @@ fn get_system_info() -> SystemInfo {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Clone)]
 struct DiskInfo {
     mount_point: String,
     fs_type: String,
+    // GiB or GB, per the units setting
     total_gb: f32,
     used_gb: f32,
     available_gb: f32,
+    #[serde(default)]
+    total_bytes: u64,
+    #[serde(default)]
+    used_bytes: u64,
+    #[serde(default)]
+    available_bytes: u64,
+    // Preformatted sizes, e.g. "931.3 GiB"
+    #[serde(default)]
+    total_display: String,
+    #[serde(default)]
+    used_display: String,
+    #[serde(default)]
+    available_display: String,
     usage_percent: f32,
+    // None where the filesystem doesn't track inodes (btrfs reports 0) or
+    // the platform has no statvfs
+    inodes_total: Option<u64>,
+    inodes_used: Option<u64>,
+    inodes_usage_percent: Option<f32>,
+    // Projected from the local usage history; None for remote hosts and
+    // mounts without enough samples
+    #[serde(default)]
+    days_until_full: Option<f64>,
+    // From the cached SMART data of the disk(s) under the mount
+    #[serde(default)]
+    temperature_c: Option<f32>,
+    #[serde(default)]
+    wear_percent: Option<f32>,
 }
 
-#[derive(Serialize)]
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+fn inode_usage(mount: &str) -> Option<(u64, u64)> {
+    use std::ffi::CString;
+
+    let path = CString::new(mount).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    let total = stat.f_files as u64;
+    if total == 0 {
+        return None;
+    }
+    Some((total, total.saturating_sub(stat.f_ffree as u64)))
+}
+
+#[cfg(not(unix))]
+fn inode_usage(_mount: &str) -> Option<(u64, u64)> {
+    None
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 struct SystemMetrics {
+    #[serde(default)]
+    host_id: String,
     cpu_percent: f32,
     memory_percent: f32,
+    // GiB or GB, per the units setting
     memory_used_gb: f32,
     memory_total_gb: f32,
     memory_available_gb: f32,
+    #[serde(default)]
+    memory_used_bytes: u64,
+    #[serde(default)]
+    memory_total_bytes: u64,
+    #[serde(default)]
+    memory_available_bytes: u64,
+    #[serde(default)]
+    memory_used_display: String,
+    #[serde(default)]
+    memory_total_display: String,
+    #[serde(default)]
+    memory_available_display: String,
     disks: Vec<DiskInfo>,
     uptime_seconds: u64,
 }
 
-#[tauri::command]
-fn get_system_metrics() -> SystemMetrics {
+#[cfg_attr(feature = "gui", tauri::command)]
+fn get_system_metrics(
+    settings: State<'_, settings::SettingsStore>,
+    disk_history: State<'_, disk_history::DiskHistory>,
+    smart: State<'_, smart::SmartCache>,
+    ssh: State<'_, ssh_hosts::SshHosts>,
+    limiter: State<'_, ratelimit::RateLimiter>,
+) -> error::CommandResult<ratelimit::Throttled<SystemMetrics>> {
+    let settings = settings.get();
+    let key = format!("{:?} {:?}", settings.active_host, settings.units);
+    let min_interval = ratelimit::interval(&settings.rate_limits_ms, "get_system_metrics");
+    limiter.call("get_system_metrics", &key, min_interval, || match hosts::active_host(&settings) {
+        hosts::ActiveHost::Local => {
+            let mut metrics = local_system_metrics(settings.units);
+            disk_history.annotate(&mut metrics);
+            smart.want();
+            smart.annotate(&mut metrics);
+            Ok(metrics)
+        }
+        hosts::ActiveHost::Remote(host) => ssh_hosts::remote_metrics(&ssh, &host, settings.units),
+    })
+}
+
+fn local_system_metrics(units: units::Units) -> SystemMetrics {
     let mut sys = System::new_all();
     sys.refresh_all();
     
@@ -65,7 +266,7 @@ fn get_system_metrics() -> SystemMetrics {
         .map(|cpu| cpu.cpu_usage())
         .sum::<f32>() / sys.cpus().len() as f32;
     
-    // Memory stats (convert KB to GB properly)
+    // Memory stats, in bytes
     let total_mem = sys.total_memory();
     let used_mem = sys.used_memory();
     let available_mem = sys.available_memory();
@@ -98,19 +299,32 @@ fn get_system_metrics() -> SystemMetrics {
             0.0
         };
         
+        let inodes = inode_usage(mount);
+        
         let disk_info = DiskInfo {
             mount_point: mount.to_string(),
             fs_type: format!("{:?}", d.file_system()).trim_matches('"').to_string(),
-            total_gb: (total as f32) / 1024.0 / 1024.0 / 1024.0,
-            used_gb: (used as f32) / 1024.0 / 1024.0 / 1024.0,
-            available_gb: (available as f32) / 1024.0 / 1024.0 / 1024.0,
+            total_gb: units.gb(total),
+            used_gb: units.gb(used),
+            available_gb: units.gb(available),
+            total_bytes: total,
+            used_bytes: used,
+            available_bytes: available,
+            total_display: units.format_bytes(total),
+            used_display: units.format_bytes(used),
+            available_display: units.format_bytes(available),
             usage_percent,
+            inodes_total: inodes.map(|(total, _)| total),
+            inodes_used: inodes.map(|(_, used)| used),
+            inodes_usage_percent: inodes.map(|(total, used)| (used as f32 / total as f32) * 100.0),
+            days_until_full: None,
+            temperature_c: None,
+            wear_percent: None,
         };
         
         // Use total_space as a simple hash for deduplication
         // If duplicate, prefer shorter mount point (e.g., "/" over "/btrfs/root")
-        let key = (total, available); // Use size combo as unique identifier
-        let hash_key = ((total >> 32) ^ (total & 0xFFFFFFFF)) as u64;
+        let hash_key = (total >> 32) ^ (total & 0xFFFFFFFF);
         
         if let Some(existing) = disk_map.get(&hash_key) {
             // Keep the shorter mount point
@@ -127,276 +341,428 @@ fn get_system_metrics() -> SystemMetrics {
     disks.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
     
     SystemMetrics {
+        host_id: hosts::LOCAL_HOST_ID.to_string(),
         cpu_percent,
         memory_percent,
-        memory_used_gb: (used_mem as f32) / 1024.0 / 1024.0 / 1024.0,  // bytes to GB
-        memory_total_gb: (total_mem as f32) / 1024.0 / 1024.0 / 1024.0,  // bytes to GB
-        memory_available_gb: (available_mem as f32) / 1024.0 / 1024.0 / 1024.0,  // bytes to GB
+        memory_used_gb: units.gb(used_mem),
+        memory_total_gb: units.gb(total_mem),
+        memory_available_gb: units.gb(available_mem),
+        memory_used_bytes: used_mem,
+        memory_total_bytes: total_mem,
+        memory_available_bytes: available_mem,
+        memory_used_display: units.format_bytes(used_mem),
+        memory_total_display: units.format_bytes(total_mem),
+        memory_available_display: units.format_bytes(available_mem),
         disks,
         uptime_seconds: System::uptime(),
     }
 }
 
-#[derive(Serialize)]
-struct ApprovalRequest {
-    id: String,
-    task: String,
-    action: String,
-    reasoning: String,
-    confidence: f32,
-    risk_level: String,
-    affected_resources: Vec<String>,
-    requested_at: String,
-    status: String,
-}
-
-#[tauri::command]
-fn get_pending_approvals() -> Vec<ApprovalRequest> {
-    // Mock approval requests for UI development
-    vec![
-        ApprovalRequest {
-            id: "req_001".to_string(),
-            task: "System Update".to_string(),
-            action: "Update 47 packages including kernel 6.14.0-37".to_string(),
-            reasoning: "Security patches available. 12 critical CVEs fixed in this update.".to_string(),
-            confidence: 0.92,
-            risk_level: "medium".to_string(),
-            affected_resources: vec![
-                "linux-image-6.14.0-37-generic".to_string(),
-                "systemd".to_string(),
-                "openssh-server".to_string(),
-            ],
-            requested_at: chrono::Utc::now().to_rfc3339(),
-            status: "pending".to_string(),
-        },
-        ApprovalRequest {
-            id: "req_002".to_string(),
-            task: "Disk Cleanup".to_string(),
-            action: "Delete 15.2 GB of old logs and cache files".to_string(),
-            reasoning: "Root partition at 25.2% - cleaning old logs older than 90 days.".to_string(),
-            confidence: 0.88,
-            risk_level: "low".to_string(),
-            affected_resources: vec![
-                "/var/log/*.gz".to_string(),
-                "~/.cache/thumbnails/*".to_string(),
-            ],
-            requested_at: chrono::Utc::now().to_rfc3339(),
-            status: "pending".to_string(),
-        },
-    ]
-}
-
-#[tauri::command]
-fn approve_request(request_id: String) -> Result<String, String> {
-    // Mock approval - in real system would call Python backend
-    println!("Approved request: {}", request_id);
-    Ok(format!("Request {} approved", request_id))
-}
-
-#[tauri::command]
-fn reject_request(request_id: String, reason: String) -> Result<String, String> {
-    // Mock rejection - in real system would call Python backend
-    println!("Rejected request {}: {}", request_id, reason);
-    Ok(format!("Request {} rejected", request_id))
-}
-
-#[derive(Serialize)]
-struct Job {
-    id: String,
-    name: String,
-    status: String,
-    started_at: String,
-    progress: f32,
-    logs: Vec<String>,
-    task_type: String,
-}
-
-#[tauri::command]
-fn get_active_jobs() -> Vec<Job> {
-    // Mock active jobs
-    vec![
-        Job {
-            id: "job_001".to_string(),
-            name: "System Health Monitor".to_string(),
-            status: "running".to_string(),
-            started_at: chrono::Utc::now().to_rfc3339(),
-            progress: 0.0,
-            logs: vec![
-                "Started health monitoring".to_string(),
-                "Checking CPU temperature...".to_string(),
-                "CPU temp: 45°C (normal)".to_string(),
-            ],
-            task_type: "monitoring".to_string(),
-        },
-        Job {
-            id: "job_002".to_string(),
-            name: "RAG Document Indexing".to_string(),
-            status: "running".to_string(),
-            started_at: chrono::Utc::now().to_rfc3339(),
-            progress: 0.67,
-            logs: vec![
-                "Loading documents from data/".to_string(),
-                "Found 1,247 markdown files".to_string(),
-                "Indexed 834 / 1247 documents".to_string(),
-                "Building BM25 index...".to_string(),
-            ],
-            task_type: "indexing".to_string(),
-        },
-        Job {
-            id: "job_003".to_string(),
-            name: "Weekly Backup".to_string(),
-            status: "pending".to_string(),
-            started_at: chrono::Utc::now().to_rfc3339(),
-            progress: 0.0,
-            logs: vec![
-                "Scheduled for 02:00 AM".to_string(),
-            ],
-            task_type: "backup".to_string(),
-        },
-    ]
-}
-
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 struct MemoryStats {
+    #[serde(default)]
+    host_id: String,
     total_documents: u32,
     total_chunks: u32,
     index_size_mb: f32,
     last_indexed: String,
     corpus_status: String,
+    // Each corpus the totals above add up
+    #[serde(default)]
+    corpora: Vec<corpora::CorpusStats>,
 }
 
-#[derive(Serialize)]
-struct Document {
-    id: String,
-    title: String,
-    source: String,
-    doc_type: String,
-    chunk_count: u32,
-    indexed_at: String,
-    size_kb: f32,
+// Without a corpus, all of them and their total. Corpus stats ask the
+// backend, so they're gathered on a blocking thread.
+#[cfg_attr(feature = "gui", tauri::command)]
+async fn get_memory_stats<R: Runtime>(
+    app: AppHandle<R>,
+    corpus: Option<String>,
+) -> error::CommandResult<MemoryStats> {
+    tokio::task::spawn_blocking(move || {
+        memory_stats(&app.state::<settings::SettingsStore>().get(), &app.state(), &app.state(), corpus)
+    })
+    .await
+    .map_err(|e| error::CommandError::Internal(format!("memory stats failed: {}", e)))?
 }
 
-#[tauri::command]
-fn get_memory_stats() -> MemoryStats {
-    // Mock memory/RAG stats
-    MemoryStats {
-        total_documents: 1247,
-        total_chunks: 8934,
-        index_size_mb: 156.8,
-        last_indexed: chrono::Utc::now().to_rfc3339(),
-        corpus_status: "healthy".to_string(),
+fn memory_stats(
+    settings: &settings::Settings,
+    db: &db::Database,
+    cache: &corpora::IndexCache,
+    corpus: Option<String>,
+) -> error::CommandResult<MemoryStats> {
+    match hosts::active_host(settings) {
+        hosts::ActiveHost::Local => local_memory_stats(settings, db, cache, corpus),
+        hosts::ActiveHost::Remote(host) => hosts::fetch_memory_stats(&host, corpus.as_deref()),
     }
 }
 
-#[tauri::command]
-fn get_documents() -> Vec<Document> {
-    // Mock document list
-    vec![
-        Document {
-            id: "doc_001".to_string(),
-            title: "Linux System Administration Guide".to_string(),
-            source: "docs/linux/sysadmin.md".to_string(),
-            doc_type: "markdown".to_string(),
-            chunk_count: 87,
-            indexed_at: chrono::Utc::now().to_rfc3339(),
-            size_kb: 124.5,
-        },
-        Document {
-            id: "doc_002".to_string(),
-            title: "Rust Programming Best Practices".to_string(),
-            source: "docs/rust/best-practices.md".to_string(),
-            doc_type: "markdown".to_string(),
-            chunk_count: 62,
-            indexed_at: chrono::Utc::now().to_rfc3339(),
-            size_kb: 89.2,
-        },
-        Document {
-            id: "doc_003".to_string(),
-            title: "Tauri Desktop Development".to_string(),
-            source: "docs/tauri/desktop.md".to_string(),
-            doc_type: "markdown".to_string(),
-            chunk_count: 45,
-            indexed_at: chrono::Utc::now().to_rfc3339(),
-            size_kb: 67.8,
-        },
-        Document {
-            id: "doc_004".to_string(),
-            title: "man: systemctl (System Control)".to_string(),
-            source: "scraped/man/systemctl.txt".to_string(),
-            doc_type: "manpage".to_string(),
-            chunk_count: 134,
-            indexed_at: chrono::Utc::now().to_rfc3339(),
-            size_kb: 234.1,
-        },
-        Document {
-            id: "doc_005".to_string(),
-            title: "Phase 8 UI/UX Design Spec".to_string(),
-            source: "docs/Phase8/ui-spec.md".to_string(),
-            doc_type: "markdown".to_string(),
-            chunk_count: 56,
-            indexed_at: chrono::Utc::now().to_rfc3339(),
-            size_kb: 78.9,
+fn local_memory_stats(
+    settings: &settings::Settings,
+    db: &db::Database,
+    cache: &corpora::IndexCache,
+    corpus: Option<String>,
+) -> error::CommandResult<MemoryStats> {
+    let names = match corpus {
+        Some(_) => vec![corpora::pick(settings, corpus)?],
+        None => corpora::names(settings),
+    };
+    if names.iter().all(|name| corpora::configured_path(settings, name).is_none()) {
+        // Mock memory/RAG stats until there's a corpus
+        return Ok(MemoryStats {
+            host_id: hosts::LOCAL_HOST_ID.to_string(),
+            total_documents: 1247,
+            total_chunks: 8934,
+            index_size_mb: 156.8,
+            last_indexed: chrono::Utc::now().to_rfc3339(),
+            corpus_status: "healthy".to_string(),
+            corpora: Vec::new(),
+        });
+    }
+    let corpora = names
+        .iter()
+        .map(|name| corpora::stats(settings, db, cache, name))
+        .collect::<error::CommandResult<Vec<_>>>()?;
+    let healthy = corpora.iter().all(|c| c.status == "healthy");
+    Ok(MemoryStats {
+        host_id: hosts::LOCAL_HOST_ID.to_string(),
+        total_documents: corpora.iter().map(|c| c.documents).sum(),
+        total_chunks: corpora.iter().filter_map(|c| c.chunks).sum(),
+        index_size_mb: corpora.iter().filter_map(|c| c.index_size_mb).sum(),
+        last_indexed: corpora.iter().filter_map(|c| c.last_modified.clone()).max().unwrap_or_default(),
+        corpus_status: if healthy { "healthy" } else { "degraded" }.to_string(),
+        corpora,
+    })
+}
+
+// Defines command_handler and COMMANDS, the commands' names, from one list
+macro_rules! commands {
+    (@name $name:ident) => {
+        stringify!($name)
+    };
+    (@name $module:ident :: $($rest:tt)+) => {
+        commands!(@name $($rest)+)
+    };
+    ($($($segment:ident)::+),* $(,)?) => {
+        const COMMANDS: &[&str] = &[$(commands!(@name $($segment)::+)),*];
+
+        // Wry, not generic: commands take AppHandle, which is AppHandle<Wry>
+        #[cfg(feature = "gui")]
+        fn command_handler() -> impl Fn(tauri::ipc::Invoke<tauri::Wry>) -> bool + Send + Sync + 'static {
+            tauri::generate_handler![$($($segment)::+),*]
+        }
+    };
+}
+
+// Every command the frontend can invoke. New ones are blocked in read-only
+// mode until they're added to readonly::READ_ONLY_COMMANDS, and flagged at
+// startup until palette has them as an action or in NOT_IN_PALETTE.
+commands![
+    session::greet,
+    session::get_session_info,
+    get_system_info,
+    get_system_metrics,
+    approvals::get_pending_approvals,
+    approvals::get_approval_detail,
+    approvals::get_approval_history,
+    approvals::dry_run_approval,
+    approvals::approve_request,
+    approvals::reject_request,
+    approvals::approve_group,
+    calibration::record_approval_outcome,
+    calibration::get_confidence_report,
+    approval_templates::create_approval_template,
+    approval_templates::list_approval_templates,
+    approval_templates::delete_approval_template,
+    approval_templates::instantiate_approval_template,
+    policy::get_risk_policies,
+    policy::set_risk_policies,
+    policy::test_risk_policy,
+    jobs::get_active_jobs,
+    jobs::get_job,
+    jobs::get_job_logs,
+    artifacts::get_job_artifacts,
+    artifacts::open_job_artifact,
+    artifacts::delete_job_artifacts,
+    automation::set_automation_paused,
+    automation::get_automation_status,
+    job_templates::list_job_templates,
+    job_templates::create_job_template,
+    job_templates::update_job_template,
+    job_templates::delete_job_template,
+    job_templates::set_job_secret,
+    job_templates::start_job,
+    backup::configure_backup,
+    backup::run_backup_now,
+    backup::list_backup_snapshots,
+    backup::get_last_backup_status,
+    snapshots::list_snapshots,
+    snapshots::create_snapshot,
+    storage::get_storage_stats,
+    storage::run_storage_maintenance,
+    cleanup::plan_cleanup,
+    cleanup::get_cleanup_plan,
+    cleanup::get_cleanup_items,
+    cleanup::execute_cleanup,
+    get_memory_stats,
+    disk_history::get_disk_trend,
+    usage_summary::get_usage_summary,
+    corpora::list_corpora,
+    corpora::add_corpus,
+    corpora::remove_corpus,
+    documents::get_documents,
+    documents::search_documents,
+    retrieval_feedback::submit_retrieval_feedback,
+    retrieval_feedback::get_feedback_summary,
+    documents::set_document_tags,
+    documents::get_tags,
+    documents::get_document_content,
+    documents::delete_document,
+    reindex::reindex_document,
+    reindex::reindex_corpus,
+    preview::render_document_preview,
+    launcher::open_path,
+    corpus_health::run_corpus_health_check,
+    corpus_import::import_documents,
+    corpus_sources::add_corpus_source,
+    corpus_sources::list_corpus_sources,
+    corpus_sources::remove_corpus_source,
+    corpus_sources::sync_corpus_source,
+    scrape::scrape_manpages,
+    scrape::scrape_command_help,
+    corpus_health::get_corpus_health_report,
+    selfcheck::run_self_check,
+    selfcheck::get_self_check,
+    palette::search_actions,
+    palette::get_action,
+    selfusage::get_self_usage,
+    startup::get_startup_status,
+    liveness::get_liveness,
+    remote_access::get_remote_access_status,
+    remote_access::generate_remote_access_token,
+    remote_access::generate_remote_access_changes_token,
+    activity::set_ui_active,
+    widget::toggle_widget_window,
+    widget::get_widget_state,
+    sandbox::get_sandbox_roots,
+    command_stats::get_command_stats,
+    command_stats::reset_command_stats,
+    collectors::list_profiles,
+    collectors::save_profile,
+    collectors::apply_profile,
+    collectors::get_collector_status,
+    config_transfer::preview_configuration_export,
+    config_transfer::export_configuration,
+    transfers::get_transfer_chunk,
+    transfers::cancel_transfer,
+    config_transfer::import_configuration,
+    user_usage::get_usage_by_user,
+    accounts::get_user_accounts,
+    gpu::get_gpu_processes,
+    thermal::get_thermal_status,
+    sampler::get_metrics_history,
+    sampler::get_metric_sparkline,
+    containers::get_container_metrics_history,
+    settings::get_settings,
+    settings::update_settings,
+    settings::validate_settings,
+    settings_revisions::list_settings_revisions,
+    settings_revisions::rollback_settings,
+    alerts::get_alert_rules,
+    alerts::set_alert_rules,
+    alerts::evaluate_alerts,
+    timesync::get_time_sync_status,
+    firewall::get_firewall_status,
+    certificates::scan_certificates,
+    certificates::get_certificate_status,
+    certificates::add_certificate_target,
+    certificates::remove_certificate_target,
+    process_tree::get_process_tree,
+    processes::request_kill_process,
+    processes::request_renice_process,
+    services::request_service_action,
+    reboot::get_reboot_status,
+    kernel_modules::get_kernel_modules,
+    changes::get_change_summary,
+    journal_follow::follow_journal,
+    journal_follow::update_journal_filter,
+    journal_follow::stop_journal_follow,
+    incidents::get_incidents,
+    text_feed::get_dashboard_summary_text,
+    text_feed::get_event_feed,
+    network::get_network_interfaces,
+    network::get_listening_ports,
+    connections::get_connections,
+    network::check_network_connectivity,
+    network::get_ipv6_status,
+    baselines::create_baseline,
+    baselines::list_baselines,
+    baselines::compare_baseline,
+    baselines::delete_baseline,
+    packages::get_update_inventory,
+    report::copy_system_report,
+    window_capture::capture_window_screenshot,
+    diagnostics::generate_diagnostic_bundle,
+    cancellation::cancel_operation,
+    bundles::get_dashboard_bundle,
+    bundles::get_memory_panel_bundle,
+    hosts::list_hosts,
+    hosts::add_host,
+    hosts::remove_host,
+    hosts::set_active_host,
+    hosts::set_host_wake,
+    hosts::set_host_changes,
+    hosts::add_ssh_host,
+    wol::send_wake_on_lan,
+    remote_core::get_core_status,
+    backend::set_backend_token,
+    backend::get_backend_token_status,
+    backend::get_backend_status,
+    backend_import::import_backend_history,
+    backend_latency::get_backend_performance,
+    notifications::add_webhook,
+    notifications::list_webhooks,
+    notifications::test_webhook,
+    notifications::remove_webhook,
+    desktop_notify::get_desktop_notify_status,
+    hooks::create_hook,
+    hooks::list_hooks,
+    hooks::set_hook_enabled,
+    hooks::delete_hook,
+    hooks::get_hook_runs,
+    onboarding::get_onboarding_state,
+    onboarding::complete_onboarding_step,
+    onboarding::skip_onboarding,
+    consent::list_consents,
+    consent::grant_consent,
+    consent::deny_consent,
+    consent::revoke_consent,
+    conversations::ask_question,
+    conversations::list_conversations,
+    conversations::get_conversation,
+    conversations::rename_conversation,
+    conversations::delete_conversation,
+];
+
+// The stores the core keeps, whether a window runs it or halbert-agent
+fn manage_core(app: &AppHandle, launched: Instant, config_dir: &Path, data_dir: &Path) {
+    secrets::init(data_dir.join("secrets.enc"));
+    app.manage(startup::Startup::new(launched));
+    app.manage(db::Database::new(&data_dir.join("halbert.db")));
+    let settings_path = config_dir.join("settings.toml");
+    backend::migrate_legacy_token(&settings_path);
+    app.manage(settings::SettingsStore::load(settings_path));
+    app.manage(approvals::ApprovalStore::with_mock_requests());
+    app.manage(corpus_health::CorpusHealthStore::default());
+    app.manage(selfcheck::SelfCheckStore::default());
+    app.manage(disk_history::DiskHistory::default());
+    app.manage(smart::SmartCache::default());
+    app.manage(ssh_hosts::SshHosts::new(data_dir.join("ssh")));
+    app.manage(sampler::MetricsHistory::default());
+    app.manage(usage_summary::UsageRecorder::default());
+    app.manage(transfers::TransferStore::default());
+    app.manage(containers::ContainerHistory::default());
+    app.manage(selfusage::SelfLimiter::default());
+    app.manage(ratelimit::RateLimiter::default());
+    app.manage(reindex::Reindexer::default());
+    app.manage(corpora::IndexCache::default());
+    app.manage(corpus_sources::CorpusSources::default());
+    app.manage(corpus_watch::CorpusWatcher::default());
+    app.manage(journal_follow::JournalFollower::default());
+    app.manage(collectors::CollectorRegistry::default());
+    app.manage(activity::UiActivity::default());
+    app.manage(liveness::Liveness::default());
+    app.manage(remote_access::RemoteAccess::default());
+    app.manage(notifications::Notifier::start(app.clone()));
+    app.manage(job_poller::JobPoller::default());
+    app.manage(alerts::AlertLog::default());
+    app.manage(suspend::Suspend::default());
+    app.manage(consent::ConsentStore::default());
+    app.manage(cancellation::Operations::default());
+    let gate = automation::Gate::load(data_dir.join("automation.json"));
+    app.manage(gate.clone());
+    let handle = app.clone();
+    let capture_handle = app.clone();
+    app.manage(jobs::JobManager::with_mock_jobs(
+        gate,
+        move |job| {
+            let _ = handle.emit("jobs://update", job);
+            notifications::job_updated(&handle, job);
+            usage_summary::record_job(&handle.state::<db::Database>(), job);
+            approvals::job_updated(&handle, job);
         },
-    ]
+        move |job, partial| artifacts::capture(&capture_handle, job, partial),
+    ));
+}
+
+// The core's background tasks, started once the database is open
+fn start_core(app: &AppHandle) {
+    sampler::start(app.clone());
+    containers::start(app.clone());
+    disk_history::start(app.clone());
+    smart::start(app.clone());
+    ssh_hosts::start(app.clone());
+    certificates::start(app.clone());
+    packages::start(app.clone());
+    changes::start(app.clone());
+    incidents::start(app.clone());
+    network::start(app.clone());
+    storage::start(app.clone());
+    reindex::start(app.clone());
+    corpus_sources::start(app.clone());
+    corpus_watch::start(app.clone());
+    calibration::start(app.clone());
+    selfcheck::start(app.clone());
+    liveness::start(app.clone());
+    remote_access::start(app.clone());
+    job_poller::start(app.clone());
+    suspend::start(app.clone());
+    backend_latency::start(app.clone());
+    shutdown::watch_signals(app.clone());
 }
 
+#[cfg(feature = "gui")]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let launched = Instant::now();
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![
-            greet,
-            get_system_info,
-            get_system_metrics,
-            get_pending_approvals,
-            approve_request,
-            reject_request,
-            get_active_jobs,
-            get_memory_stats,
-            get_documents
-        ])
-        .setup(|app| {
-            // Set window icon for Linux taskbar
-            #[cfg(target_os = "linux")]
-            {
-                use tauri::Manager;
-                use image::ImageReader;
-                use std::io::Cursor;
-                
-                println!("[Halbert] Setting up window icon...");
-                
-                if let Some(window) = app.get_webview_window("main") {
-                    // Embed icon at compile time for reliability
-                    let icon_bytes = include_bytes!("../icons/icon.png");
-                    println!("[Halbert] Icon bytes loaded: {} bytes", icon_bytes.len());
-                    
-                    if let Some(img) = ImageReader::new(Cursor::new(icon_bytes))
-                        .with_guessed_format()
-                        .ok()
-                        .and_then(|r| r.decode().ok())
-                    {
-                        let rgba = img.to_rgba8();
-                        let (width, height) = rgba.dimensions();
-                        println!("[Halbert] Icon decoded: {}x{}", width, height);
-                        
-                        let icon = tauri::image::Image::new_owned(
-                            rgba.into_raw(),
-                            width,
-                            height,
-                        );
-                        match window.set_icon(icon) {
-                            Ok(_) => println!("[Halbert] Window icon set successfully!"),
-                            Err(e) => println!("[Halbert] Failed to set icon: {:?}", e),
-                        }
-                    } else {
-                        println!("[Halbert] Failed to decode icon image");
-                    }
-                } else {
-                    println!("[Halbert] Could not get main window");
-                }
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_notification::init())
+        .invoke_handler(command_stats::measured(remote_core::routed(readonly::guarded(command_handler()))))
+        // Focus means someone is looking; blur alone doesn't mean hidden
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Focused(true) = event {
+                window.app_handle().state::<activity::UiActivity>().set_active(true);
             }
+            widget::on_window_event(window, event);
+        })
+        .setup(move |app| {
+            let config_dir = app.path().app_config_dir()?;
+            let data_dir = app.path().app_data_dir()?;
+            manage_core(app.handle(), launched, &config_dir, &data_dir);
+            app.manage(command_stats::CommandStats::new(COMMANDS));
+            app.manage(widget::Widget::default());
+            app.manage(desktop_notify::DesktopNotifier::default());
+            let registry = palette::registry();
+            palette::check(&registry, COMMANDS);
+            app.manage(registry);
+            if let Err(e) = widget::build_tray(app.handle()) {
+                println!("[Halbert] Tray icon unavailable: {}", e);
+            }
+            startup::run(app.handle().clone(), |app| {
+                start_core(app);
+                onboarding::start(app.clone());
+                desktop_notify::start(app.clone());
+            });
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                shutdown::run(app);
+            }
+        });
 }
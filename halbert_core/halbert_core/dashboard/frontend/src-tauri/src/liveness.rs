@@ -0,0 +1,448 @@
+// Liveness for outside supervisors (systemd, healthchecks.io, a cron job).
+//
+// The core loops check in with `beat`, saying how soon they'll check in
+// again: the metrics sampler, the notification worker and the storage
+// maintenance scheduler. A watchdog thread looks at them every
+// `liveness.interval_secs`; a loop more than GRACE past its deadline is
+// stalled, the status turns "degraded" naming it, and `liveness://degraded`
+// fires whenever the stalled set changes to a non-empty one. A loop that's
+// parked on purpose (its collector is off) doesn't count. Each check can
+// write a heartbeat file (one line of compact JSON, replaced atomically, so
+// its mtime works too) and ping a healthchecks-style URL, with `/fail`
+// appended while degraded. Started by systemd with NOTIFY_SOCKET set,
+// READY=1 goes out once startup finishes and WATCHDOG=1 from the watchdog
+// task, only while healthy, so WatchdogSec= restarts a stalled instance;
+// STOPPING=1 goes out when shutdown starts.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::app::{AppHandle, Emitter, Manager, State};
+use crate::error::{CommandError, CommandResult};
+use crate::impact;
+use crate::notifications;
+use crate::settings::{Settings, SettingsStore};
+
+pub const COMPONENTS: &[&str] = &["sampler", "notifications", "maintenance"];
+// Slack on every deadline, for a slow sample or a webhook that times out
+const GRACE: Duration = Duration::from_secs(60);
+// A loop that hasn't checked in once by then is stalled
+const FIRST_BEAT_DEADLINE: Duration = Duration::from_secs(120);
+const MIN_INTERVAL_SECS: u64 = 5;
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LivenessSettings {
+    // Absolute or starting with ~/; None writes no file
+    pub heartbeat_path: Option<String>,
+    pub interval_secs: u64,
+    // Pinged every check, e.g. https://hc-ping.com/<uuid>
+    pub healthcheck_url: Option<String>,
+}
+
+impl Default for LivenessSettings {
+    fn default() -> Self {
+        LivenessSettings {
+            heartbeat_path: None,
+            interval_secs: 30,
+            healthcheck_url: None,
+        }
+    }
+}
+
+pub fn validate(settings: &LivenessSettings) -> CommandResult<()> {
+    if settings.interval_secs < MIN_INTERVAL_SECS {
+        return Err(CommandError::InvalidInput(format!(
+            "liveness interval must be at least {} seconds",
+            MIN_INTERVAL_SECS
+        )));
+    }
+    if let Some(path) = &settings.heartbeat_path {
+        if !Path::new(&impact::expand_home(path)).is_absolute() {
+            return Err(CommandError::InvalidInput(format!(
+                "heartbeat path '{}' must be absolute or start with ~/",
+                path
+            )));
+        }
+    }
+    if let Some(url) = &settings.healthcheck_url {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(CommandError::InvalidInput(
+                "healthcheck_url must start with http:// or https://".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Beat {
+    pub at: Instant,
+    pub next_within: Duration,
+    pub parked: bool,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ComponentLiveness {
+    pub name: String,
+    // "ok", "stalled", "parked" or "waiting" (no beat yet, still in time)
+    pub status: String,
+    pub seconds_since_beat: Option<u64>,
+    // How long it had, grace included
+    pub deadline_secs: Option<u64>,
+}
+
+pub fn assess(beats: &HashMap<&str, Beat>, started: Instant, now: Instant) -> Vec<ComponentLiveness> {
+    COMPONENTS
+        .iter()
+        .map(|&name| {
+            let (status, since, deadline) = match beats.get(name) {
+                None if now.saturating_duration_since(started) > FIRST_BEAT_DEADLINE => ("stalled", None, None),
+                None => ("waiting", None, None),
+                Some(beat) => {
+                    let since = now.saturating_duration_since(beat.at);
+                    let deadline = beat.next_within + GRACE;
+                    let status = if beat.parked {
+                        "parked"
+                    } else if since > deadline {
+                        "stalled"
+                    } else {
+                        "ok"
+                    };
+                    (status, Some(since.as_secs()), Some(deadline.as_secs()))
+                }
+            };
+            ComponentLiveness {
+                name: name.to_string(),
+                status: status.to_string(),
+                seconds_since_beat: since,
+                deadline_secs: deadline,
+            }
+        })
+        .collect()
+}
+
+pub fn stalled(components: &[ComponentLiveness]) -> Vec<String> {
+    components
+        .iter()
+        .filter(|c| c.status == "stalled")
+        .map(|c| c.name.clone())
+        .collect()
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct PingResult {
+    pub at: String,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LivenessReport {
+    pub at: String,
+    // "ok" or "degraded"
+    pub status: String,
+    pub stalled: Vec<String>,
+    pub components: Vec<ComponentLiveness>,
+    pub heartbeat_path: Option<String>,
+    // Why the last heartbeat couldn't be written
+    pub heartbeat_error: Option<String>,
+    pub healthcheck: Option<PingResult>,
+    // Running under systemd with a notify socket
+    pub systemd: bool,
+}
+
+#[derive(Default)]
+struct Outputs {
+    stalled: Vec<String>,
+    heartbeat_error: Option<String>,
+    healthcheck: Option<PingResult>,
+}
+
+pub struct Liveness {
+    started: Instant,
+    beats: Mutex<HashMap<&'static str, Beat>>,
+    outputs: Mutex<Outputs>,
+}
+
+impl Default for Liveness {
+    fn default() -> Self {
+        Liveness {
+            started: Instant::now(),
+            beats: Mutex::new(HashMap::new()),
+            outputs: Mutex::new(Outputs::default()),
+        }
+    }
+}
+
+impl Liveness {
+    // `next_within`: how long until this loop checks in again
+    pub fn beat(&self, component: &'static str, next_within: Duration) {
+        self.beats.lock().unwrap().insert(
+            component,
+            Beat {
+                at: Instant::now(),
+                next_within,
+                parked: false,
+            },
+        );
+    }
+
+    // Until the next beat, e.g. while a disabled collector waits
+    pub fn park(&self, component: &'static str) {
+        self.beats.lock().unwrap().insert(
+            component,
+            Beat {
+                at: Instant::now(),
+                next_within: Duration::ZERO,
+                parked: true,
+            },
+        );
+    }
+
+    fn components(&self) -> Vec<ComponentLiveness> {
+        assess(&self.beats.lock().unwrap(), self.started, Instant::now())
+    }
+
+    fn report(&self, settings: &Settings) -> LivenessReport {
+        let components = self.components();
+        let stalled = stalled(&components);
+        let outputs = self.outputs.lock().unwrap();
+        LivenessReport {
+            at: chrono::Utc::now().to_rfc3339(),
+            status: if stalled.is_empty() { "ok" } else { "degraded" }.to_string(),
+            stalled,
+            components,
+            heartbeat_path: settings.liveness.heartbeat_path.clone(),
+            heartbeat_error: outputs.heartbeat_error.clone(),
+            healthcheck: outputs.healthcheck.clone(),
+            systemd: std::env::var_os("NOTIFY_SOCKET").is_some(),
+        }
+    }
+}
+
+fn write_heartbeat(path: &str, report: &LivenessReport) -> Result<(), String> {
+    let path = impact::expand_home(path);
+    let line = serde_json::json!({
+        "at": report.at,
+        "unix": chrono::Utc::now().timestamp(),
+        "status": report.status,
+        "stalled": report.stalled,
+    });
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, format!("{}\n", line)).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp, &path).map_err(|e| e.to_string())
+}
+
+fn ping(url: &str, healthy: bool) -> PingResult {
+    let url = if healthy {
+        url.to_string()
+    } else {
+        format!("{}/fail", url.trim_end_matches('/'))
+    };
+    let agent = ureq::AgentBuilder::new().timeout(PING_TIMEOUT).build();
+    // The URL is a credential, so errors keep only the kind
+    let error = match agent.get(&url).call() {
+        Ok(_) => None,
+        Err(ureq::Error::Status(code, _)) => Some(format!("HTTP {}", code)),
+        Err(ureq::Error::Transport(t)) => Some(t.kind().to_string()),
+    };
+    if let Some(e) = &error {
+        println!("[Halbert] Healthcheck ping to {} failed: {}", notifications::redact_url(&url), e);
+    }
+    PingResult {
+        at: chrono::Utc::now().to_rfc3339(),
+        ok: error.is_none(),
+        error,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn sd_notify(state: &str) -> bool {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+        return false;
+    };
+    let Ok(datagram) = UnixDatagram::unbound() else {
+        return false;
+    };
+    // A leading '@' names a socket in the abstract namespace
+    let sent = match socket.as_bytes().strip_prefix(b"@") {
+        Some(name) => {
+            SocketAddr::from_abstract_name(name).and_then(|addr| datagram.send_to_addr(state.as_bytes(), &addr))
+        }
+        None => datagram.send_to(state.as_bytes(), Path::new(&socket)),
+    };
+    sent.is_ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sd_notify(_state: &str) -> bool {
+    false
+}
+
+// Half of WatchdogSec=, as systemd recommends, when it's watching this process
+fn systemd_watchdog() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    Some(Duration::from_micros(usec / 2))
+}
+
+// Called when startup has finished
+pub fn ready() {
+    if sd_notify("READY=1") {
+        println!("[Halbert] Told systemd we're ready");
+    }
+}
+
+// Called when shutdown starts
+pub fn stopping() {
+    sd_notify("STOPPING=1");
+}
+
+fn check(app: &AppHandle) {
+    let settings = app.state::<SettingsStore>().get();
+    let liveness = app.state::<Liveness>();
+    let report = liveness.report(&settings);
+    let healthy = report.stalled.is_empty();
+    let heartbeat_error = settings
+        .liveness
+        .heartbeat_path
+        .as_deref()
+        .and_then(|path| write_heartbeat(path, &report).err());
+    let healthcheck = settings.liveness.healthcheck_url.as_deref().map(|url| ping(url, healthy));
+    if healthy {
+        sd_notify("WATCHDOG=1");
+    } else {
+        sd_notify(&format!("STATUS=Degraded: {} stalled", report.stalled.join(", ")));
+    }
+    let mut outputs = liveness.outputs.lock().unwrap();
+    if report.stalled != outputs.stalled {
+        if healthy {
+            println!("[Halbert] Liveness recovered");
+        } else {
+            println!("[Halbert] Liveness degraded: {} stalled", report.stalled.join(", "));
+            let _ = app.emit("liveness://degraded", &report);
+        }
+    }
+    outputs.stalled = report.stalled.clone();
+    outputs.heartbeat_error = heartbeat_error;
+    if healthcheck.is_some() {
+        outputs.healthcheck = healthcheck;
+    }
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        check(&app);
+        let interval_secs = app.state::<SettingsStore>().get().liveness.interval_secs;
+        let interval = Duration::from_secs(interval_secs.max(MIN_INTERVAL_SECS));
+        std::thread::sleep(systemd_watchdog().map_or(interval, |w| interval.min(w)));
+    });
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_liveness(settings: State<'_, SettingsStore>, liveness: State<'_, Liveness>) -> LivenessReport {
+    liveness.report(&settings.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::MockServer;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn statuses(components: &[ComponentLiveness]) -> Vec<&str> {
+        components.iter().map(|c| c.status.as_str()).collect()
+    }
+
+    fn beat(at: Instant, next_within: Duration, parked: bool) -> Beat {
+        Beat { at, next_within, parked }
+    }
+
+    #[test]
+    fn settings_are_checked() {
+        validate(&LivenessSettings::default()).unwrap();
+        let fast = LivenessSettings { interval_secs: 1, ..Default::default() };
+        assert!(matches!(validate(&fast), Err(CommandError::InvalidInput(_))));
+        let relative = LivenessSettings { heartbeat_path: Some("halbert.beat".to_string()), ..Default::default() };
+        assert!(matches!(validate(&relative), Err(CommandError::InvalidInput(_))));
+        let home = LivenessSettings { heartbeat_path: Some("~/.halbert.beat".to_string()), ..Default::default() };
+        validate(&home).unwrap();
+        let ftp = LivenessSettings { healthcheck_url: Some("ftp://hc".to_string()), ..Default::default() };
+        assert!(matches!(validate(&ftp), Err(CommandError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn loops_that_never_beat_are_stalled_after_the_first_deadline() {
+        let started = Instant::now();
+        let none = HashMap::new();
+        assert_eq!(statuses(&assess(&none, started, started + FIRST_BEAT_DEADLINE)), ["waiting"; 3]);
+        let late = assess(&none, started, started + FIRST_BEAT_DEADLINE + SECOND);
+        assert_eq!(statuses(&late), ["stalled"; 3]);
+        assert_eq!(stalled(&late), COMPONENTS);
+    }
+
+    #[test]
+    fn a_beat_is_due_by_its_own_deadline_plus_grace() {
+        let started = Instant::now();
+        let at = started + Duration::from_secs(10);
+        let mut beats = HashMap::new();
+        beats.insert("sampler", beat(at, Duration::from_secs(5), false));
+        beats.insert("notifications", beat(at, Duration::from_secs(300), false));
+        beats.insert("maintenance", beat(at, Duration::ZERO, true));
+
+        let components = assess(&beats, started, at + Duration::from_secs(5) + GRACE);
+        assert_eq!(statuses(&components), ["ok", "ok", "parked"]);
+        assert_eq!(components[0].seconds_since_beat, Some(65));
+        assert_eq!(components[0].deadline_secs, Some(65));
+
+        // A second later the sampler is stalled; a parked loop never is
+        let components = assess(&beats, started, at + Duration::from_secs(6) + GRACE);
+        assert_eq!(statuses(&components), ["stalled", "ok", "parked"]);
+        assert_eq!(stalled(&components), ["sampler"]);
+        let much_later = assess(&beats, started, at + Duration::from_secs(86_400));
+        assert_eq!(statuses(&much_later), ["stalled", "stalled", "parked"]);
+    }
+
+    #[test]
+    fn the_heartbeat_file_is_one_json_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("halbert.beat");
+        let report = Liveness::default().report(&Settings::default());
+        write_heartbeat(path.to_str().unwrap(), &report).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), 1);
+        let line: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(line["status"], "ok");
+        assert_eq!(line["stalled"], serde_json::json!([]));
+        assert!(!dir.path().join("halbert.beat.tmp").exists());
+        assert!(write_heartbeat(dir.path().join("missing/beat").to_str().unwrap(), &report).is_err());
+    }
+
+    #[test]
+    fn pings_say_fail_while_degraded() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/ping/abc" | "/ping/abc/fail" => (200, serde_json::Value::Null),
+            _ => (404, serde_json::Value::Null),
+        });
+        let url = format!("{}/ping/abc", server.base_url);
+        assert_eq!(ping(&url, true).error, None);
+        assert!(ping(&format!("{}/", url), false).ok);
+        let paths: Vec<String> = server.received().into_iter().map(|r| r.path).collect();
+        assert_eq!(paths, ["/ping/abc", "/ping/abc/fail"]);
+        let missing = ping(&format!("{}/ping/other", server.base_url), true);
+        assert_eq!((missing.ok, missing.error.as_deref()), (false, Some("HTTP 404")));
+    }
+}
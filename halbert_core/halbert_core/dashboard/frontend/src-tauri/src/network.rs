@@ -0,0 +1,786 @@
+// Network interfaces: addresses, link state and WiFi signal.
+//
+// Addresses come from getifaddrs; MAC, MTU, link state and speed from
+// /sys/class/net; the default route from /proc/net/route and
+// /proc/net/ipv6_route. Interfaces under /sys/devices/virtual (loopback,
+// veth pairs, bridges, tunnels) are flagged `virtual` so the UI can fold
+// them away. IPv6 scope and the temporary/deprecated flags come from
+// /proc/net/if_inet6. WiFi details come from `iw dev <if> link` when iw is
+// installed. A watcher compares link state and the default route every
+// few seconds and announces changes as `network://changed`.
+//
+// Listening sockets are read from /proc/net/{tcp,tcp6,udp,udp6}, and the
+// connectivity check resolves a probe target and connects over IPv4 and
+// IPv6 separately, so a dual-stack host shows which family works. The
+// lookup and each connect can be cancelled (see cancellation); a cancelled
+// check returns the families it finished.
+//
+// Byte order differs between the /proc files: if_inet6 prints addresses
+// in network order, while the tcp/udp tables print each 32-bit word of
+// the address as the host stores it. Ports are printed in host order.
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::app::{AppHandle, Emitter, State};
+use crate::cancellation::{self, CancellationToken, Operations};
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+// Reached over HTTPS when no probe target is given
+const PROBE_TARGET: &str = "one.one.one.one:443";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+// Past a connect's own timeout before giving up on it
+const CANCEL_SLACK: Duration = Duration::from_millis(500);
+
+// if_inet6 flag bits (IFA_F_*)
+const IFA_F_TEMPORARY: u32 = 0x01;
+const IFA_F_DEPRECATED: u32 = 0x20;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct InterfaceAddress {
+    // "ipv4" or "ipv6"
+    pub family: String,
+    pub address: String,
+    pub prefix: u8,
+    // IPv6 only: "global", "link", "site" or "host"
+    pub scope: Option<String>,
+    // Privacy extension address (RFC 8981)
+    pub temporary: bool,
+    // Past its preferred lifetime; kept for existing connections only
+    pub deprecated: bool,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ListeningPort {
+    // "tcp" or "udp"
+    pub proto: String,
+    // "ipv4" or "ipv6"
+    pub family: String,
+    pub address: String,
+    pub port: u16,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct FamilyCheck {
+    // "ipv4" or "ipv6"
+    pub family: String,
+    // What the probe target resolved to in this family (A or AAAA)
+    pub addresses: Vec<String>,
+    pub reachable: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ConnectivityCheck {
+    pub target: String,
+    pub ipv4: FamilyCheck,
+    pub ipv6: FamilyCheck,
+    // "ipv4", "ipv6", "both" or "none"
+    pub working: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Ipv6Status {
+    // Some interface has a global IPv6 address
+    pub configured: bool,
+    pub global_addresses: Vec<String>,
+    pub default_route: bool,
+    pub default_route_interfaces: Vec<String>,
+    pub probe: FamilyCheck,
+    pub probe_target: String,
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct WirelessLink {
+    pub connected: bool,
+    pub ssid: Option<String>,
+    pub bssid: Option<String>,
+    pub signal_dbm: Option<i32>,
+    pub frequency_mhz: Option<u32>,
+    pub tx_bitrate: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub mac: Option<String>,
+    pub mtu: Option<u32>,
+    pub up: bool,
+    // Raw operstate, e.g. "up", "down", "dormant", "unknown"
+    pub operstate: String,
+    // Mbit/s; None when the driver doesn't report it or the link is down
+    pub speed_mbps: Option<u32>,
+    pub addresses: Vec<InterfaceAddress>,
+    pub default_route: bool,
+    #[serde(rename = "virtual")]
+    pub is_virtual: bool,
+    pub wireless: bool,
+    // Only for wireless interfaces, and only when iw is installed
+    pub wifi: Option<WirelessLink>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct NetworkChange {
+    // "link_up", "link_down", "default_route_added", "default_route_removed",
+    // "added" or "removed"
+    pub kind: String,
+    pub interface: String,
+}
+
+// Interfaces with a default route in /proc/net/route (hex, little endian;
+// destination and mask both zero)
+pub fn parse_ipv4_default_routes(text: &str) -> BTreeSet<String> {
+    text.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (iface, destination, mask) = (fields.first()?, fields.get(1)?, fields.get(7)?);
+            (*destination == "00000000" && *mask == "00000000").then(|| iface.to_string())
+        })
+        .collect()
+}
+
+// /proc/net/ipv6_route: destination, prefix length, ..., interface last.
+// Loopback carries reject routes for ::/0 that aren't real defaults.
+pub fn parse_ipv6_default_routes(text: &str) -> BTreeSet<String> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (destination, prefix, iface) = (fields.first()?, fields.get(1)?, fields.last()?);
+            let default = destination.chars().all(|c| c == '0') && *prefix == "00";
+            (default && fields.len() >= 10 && *iface != "lo").then(|| iface.to_string())
+        })
+        .collect()
+}
+
+// 32 hex digits in network byte order, as in /proc/net/if_inet6
+pub fn ipv6_from_hex(hex: &str) -> Option<Ipv6Addr> {
+    if hex.len() != 32 {
+        return None;
+    }
+    u128::from_str_radix(hex, 16).ok().map(Ipv6Addr::from)
+}
+
+// 8 hex digits of a u32 as the host stores it, as in /proc/net/tcp; the
+// in-memory bytes are the address in network order
+pub fn ipv4_from_proc_net(hex: &str) -> Option<Ipv4Addr> {
+    if hex.len() != 8 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok().map(|word| Ipv4Addr::from(word.to_ne_bytes()))
+}
+
+// 32 hex digits, four u32 words each printed as the host stores it, as in
+// /proc/net/tcp6
+pub fn ipv6_from_proc_net(hex: &str) -> Option<Ipv6Addr> {
+    if hex.len() != 32 {
+        return None;
+    }
+    let mut bytes = [0u8; 16];
+    for (i, chunk) in bytes.chunks_mut(4).enumerate() {
+        let word = u32::from_str_radix(hex.get(i * 8..i * 8 + 8)?, 16).ok()?;
+        chunk.copy_from_slice(&word.to_ne_bytes());
+    }
+    Some(Ipv6Addr::from(bytes))
+}
+
+// "0100007F:0277" -> (127.0.0.1 or ::1, 631)
+pub fn parse_proc_net_endpoint(text: &str) -> Option<(IpAddr, u16)> {
+    let (address, port) = text.split_once(':')?;
+    let address = match address.len() {
+        8 => IpAddr::V4(ipv4_from_proc_net(address)?),
+        _ => IpAddr::V6(ipv6_from_proc_net(address)?),
+    };
+    Some((address, u16::from_str_radix(port, 16).ok()?))
+}
+
+// Listening TCP sockets (state 0A) and unconnected UDP sockets (state 07)
+// from one /proc/net table; `proto` is "tcp" or "udp"
+pub fn parse_proc_net_listeners(text: &str, proto: &str) -> Vec<ListeningPort> {
+    let listening = if proto == "tcp" { "0A" } else { "07" };
+    text.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (local, state) = (fields.get(1)?, fields.get(3)?);
+            if *state != listening {
+                return None;
+            }
+            let (address, port) = parse_proc_net_endpoint(local)?;
+            Some(ListeningPort {
+                proto: proto.to_string(),
+                family: if address.is_ipv4() { "ipv4" } else { "ipv6" }.to_string(),
+                address: address.to_string(),
+                port,
+            })
+        })
+        .collect()
+}
+
+// "tcp 0.0.0.0:22" / "tcp [::]:22", the form `ss` prints
+pub fn format_listener(port: &ListeningPort) -> String {
+    match port.family.as_str() {
+        "ipv6" => format!("{} [{}]:{}", port.proto, port.address, port.port),
+        _ => format!("{} {}:{}", port.proto, port.address, port.port),
+    }
+}
+
+pub fn listening_ports() -> Vec<ListeningPort> {
+    let mut ports = Vec::new();
+    for (file, proto) in [("tcp", "tcp"), ("tcp6", "tcp"), ("udp", "udp"), ("udp6", "udp")] {
+        if let Ok(text) = std::fs::read_to_string(format!("/proc/net/{}", file)) {
+            ports.extend(parse_proc_net_listeners(&text, proto));
+        }
+    }
+    ports.sort_by(|a, b| (&a.proto, a.port, &a.family, &a.address).cmp(&(&b.proto, b.port, &b.family, &b.address)));
+    ports.dedup();
+    ports
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Inet6Details {
+    pub scope: String,
+    pub temporary: bool,
+    pub deprecated: bool,
+}
+
+pub fn inet6_scope(scope: u32) -> &'static str {
+    match scope {
+        0x00 => "global",
+        0x10 => "host",
+        0x20 => "link",
+        0x40 => "site",
+        _ => "other",
+    }
+}
+
+// /proc/net/if_inet6: address, ifindex, prefix length, scope, flags and
+// interface name, all hex but the name; keyed by (interface, address)
+pub fn parse_if_inet6(text: &str) -> BTreeMap<(String, String), Inet6Details> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let address = ipv6_from_hex(fields.first()?)?;
+            let scope = u32::from_str_radix(fields.get(3)?, 16).ok()?;
+            let flags = u32::from_str_radix(fields.get(4)?, 16).ok()?;
+            let details = Inet6Details {
+                scope: inet6_scope(scope).to_string(),
+                temporary: flags & IFA_F_TEMPORARY != 0,
+                deprecated: flags & IFA_F_DEPRECATED != 0,
+            };
+            Some(((fields.get(5)?.to_string(), address.to_string()), details))
+        })
+        .collect()
+}
+
+// `iw dev wlan0 link`:
+//   Connected to aa:bb:cc:dd:ee:ff (on wlan0)
+//   	SSID: Home
+//   	freq: 5180
+//   	signal: -52 dBm
+//   	tx bitrate: 866.7 MBit/s VHT-MCS 9 80MHz short GI VHT-NSS 2
+// or "Not connected."
+pub fn parse_iw_link(text: &str) -> WirelessLink {
+    let mut link = WirelessLink::default();
+    for line in text.lines().map(str::trim) {
+        if let Some(rest) = line.strip_prefix("Connected to ") {
+            link.connected = true;
+            link.bssid = rest.split_whitespace().next().map(str::to_string);
+        } else if let Some(ssid) = line.strip_prefix("SSID: ") {
+            link.ssid = Some(ssid.to_string());
+        } else if let Some(freq) = line.strip_prefix("freq: ") {
+            // Newer iw prints "5180.0"
+            link.frequency_mhz = freq.split('.').next().and_then(|f| f.trim().parse().ok());
+        } else if let Some(signal) = line.strip_prefix("signal: ") {
+            link.signal_dbm = signal.split_whitespace().next().and_then(|s| s.parse().ok());
+        } else if let Some(rate) = line.strip_prefix("tx bitrate: ") {
+            link.tx_bitrate = Some(rate.to_string());
+        }
+    }
+    link
+}
+
+// Leading one bits of a netmask
+pub fn prefix_len(mask: &[u8]) -> u8 {
+    let mut bits = 0;
+    for byte in mask {
+        bits += byte.leading_ones() as u8;
+        if *byte != 0xff {
+            break;
+        }
+    }
+    bits
+}
+
+#[cfg(unix)]
+fn interface_addresses() -> BTreeMap<String, Vec<InterfaceAddress>> {
+    use std::ffi::CStr;
+
+    let inet6 = std::fs::read_to_string("/proc/net/if_inet6")
+        .map(|text| parse_if_inet6(&text))
+        .unwrap_or_default();
+    let mut addresses: BTreeMap<String, Vec<InterfaceAddress>> = BTreeMap::new();
+    let mut head: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut head) } != 0 {
+        return addresses;
+    }
+    let mut cursor = head;
+    while !cursor.is_null() {
+        // Safety: getifaddrs returned a valid list, freed below
+        let entry = unsafe { &*cursor };
+        cursor = entry.ifa_next;
+        if entry.ifa_addr.is_null() || entry.ifa_name.is_null() {
+            continue;
+        }
+        let name = unsafe { CStr::from_ptr(entry.ifa_name) }.to_string_lossy().into_owned();
+        let family = unsafe { (*entry.ifa_addr).sa_family } as i32;
+        let address = match family {
+            libc::AF_INET => {
+                let addr = unsafe { &*(entry.ifa_addr as *const libc::sockaddr_in) };
+                let prefix = (!entry.ifa_netmask.is_null())
+                    .then(|| unsafe { &*(entry.ifa_netmask as *const libc::sockaddr_in) })
+                    .map(|mask| prefix_len(&mask.sin_addr.s_addr.to_ne_bytes()))
+                    .unwrap_or(32);
+                InterfaceAddress {
+                    family: "ipv4".to_string(),
+                    address: Ipv4Addr::from(addr.sin_addr.s_addr.to_ne_bytes()).to_string(),
+                    prefix,
+                    scope: None,
+                    temporary: false,
+                    deprecated: false,
+                }
+            }
+            libc::AF_INET6 => {
+                let addr = unsafe { &*(entry.ifa_addr as *const libc::sockaddr_in6) };
+                let prefix = (!entry.ifa_netmask.is_null())
+                    .then(|| unsafe { &*(entry.ifa_netmask as *const libc::sockaddr_in6) })
+                    .map(|mask| prefix_len(&mask.sin6_addr.s6_addr))
+                    .unwrap_or(128);
+                let address = Ipv6Addr::from(addr.sin6_addr.s6_addr).to_string();
+                let details = inet6.get(&(name.clone(), address.clone()));
+                InterfaceAddress {
+                    family: "ipv6".to_string(),
+                    scope: details.map(|d| d.scope.clone()),
+                    temporary: details.is_some_and(|d| d.temporary),
+                    deprecated: details.is_some_and(|d| d.deprecated),
+                    address,
+                    prefix,
+                }
+            }
+            _ => continue,
+        };
+        addresses.entry(name).or_default().push(address);
+    }
+    unsafe { libc::freeifaddrs(head) };
+    addresses
+}
+
+#[cfg(not(unix))]
+fn interface_addresses() -> BTreeMap<String, Vec<InterfaceAddress>> {
+    BTreeMap::new()
+}
+
+fn sys_value(dir: &Path, name: &str) -> Option<String> {
+    std::fs::read_to_string(dir.join(name))
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+fn ipv6_default_route_interfaces() -> BTreeSet<String> {
+    std::fs::read_to_string("/proc/net/ipv6_route")
+        .map(|text| parse_ipv6_default_routes(&text))
+        .unwrap_or_default()
+}
+
+fn default_route_interfaces() -> BTreeSet<String> {
+    let mut interfaces = std::fs::read_to_string("/proc/net/route")
+        .map(|text| parse_ipv4_default_routes(&text))
+        .unwrap_or_default();
+    interfaces.extend(ipv6_default_route_interfaces());
+    interfaces
+}
+
+// Everything but addresses and WiFi, which the watcher doesn't need
+fn link_states() -> Vec<NetworkInterface> {
+    let Ok(entries) = std::fs::read_dir("/sys/class/net") else {
+        return Vec::new();
+    };
+    let defaults = default_route_interfaces();
+    let mut interfaces: Vec<NetworkInterface> = entries
+        .flatten()
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let dir = entry.path();
+            let operstate = sys_value(&dir, "operstate").unwrap_or_else(|| "unknown".to_string());
+            // Loopback and some tunnels say "unknown" while carrying traffic
+            let up = operstate == "up" || (operstate == "unknown" && sys_value(&dir, "carrier").as_deref() == Some("1"));
+            // Reading speed on a down link fails or gives -1
+            let speed_mbps = sys_value(&dir, "speed").and_then(|s| s.parse::<i64>().ok()).filter(|s| *s > 0).map(|s| s as u32);
+            let mac = sys_value(&dir, "address").filter(|m| m != "00:00:00:00:00:00");
+            let is_virtual = std::fs::canonicalize(&dir).is_ok_and(|real| real.starts_with("/sys/devices/virtual"));
+            NetworkInterface {
+                default_route: defaults.contains(&name),
+                wireless: dir.join("wireless").exists() || dir.join("phy80211").exists(),
+                mtu: sys_value(&dir, "mtu").and_then(|m| m.parse().ok()),
+                addresses: Vec::new(),
+                wifi: None,
+                name,
+                mac,
+                up,
+                operstate,
+                speed_mbps,
+                is_virtual,
+            }
+        })
+        .collect();
+    interfaces.sort_by(|a, b| a.is_virtual.cmp(&b.is_virtual).then_with(|| a.name.cmp(&b.name)));
+    interfaces
+}
+
+pub fn interfaces() -> Vec<NetworkInterface> {
+    let mut addresses = interface_addresses();
+    let has_iw = exec::find_in_path("iw").is_some();
+    let mut interfaces = link_states();
+    for interface in &mut interfaces {
+        interface.addresses = addresses.remove(&interface.name).unwrap_or_default();
+        if interface.wireless && has_iw {
+            interface.wifi = exec::stdout("iw", &["dev", &interface.name, "link"]).map(|text| parse_iw_link(&text));
+        }
+    }
+    interfaces
+}
+
+// What changed between two watcher samples
+pub fn diff(before: &[NetworkInterface], after: &[NetworkInterface]) -> Vec<NetworkChange> {
+    let change = |kind: &str, interface: &str| NetworkChange {
+        kind: kind.to_string(),
+        interface: interface.to_string(),
+    };
+    let mut changes = Vec::new();
+    for now in after {
+        match before.iter().find(|b| b.name == now.name) {
+            None => changes.push(change("added", &now.name)),
+            Some(was) => {
+                if was.up != now.up {
+                    changes.push(change(if now.up { "link_up" } else { "link_down" }, &now.name));
+                }
+                if was.default_route != now.default_route {
+                    changes.push(change(
+                        if now.default_route { "default_route_added" } else { "default_route_removed" },
+                        &now.name,
+                    ));
+                }
+            }
+        }
+    }
+    for was in before.iter().filter(|b| !after.iter().any(|a| a.name == b.name)) {
+        changes.push(change("removed", &was.name));
+    }
+    changes
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        let mut previous = link_states();
+        loop {
+            std::thread::sleep(WATCH_INTERVAL);
+            let current = link_states();
+            let changes = diff(&previous, &current);
+            if !changes.is_empty() {
+                for change in &changes {
+                    println!("[Halbert] Network: {} {}", change.interface, change.kind);
+                }
+                let _ = app.emit("network://changed", &changes);
+            }
+            previous = current;
+        }
+    });
+}
+
+// "host" or "host:port" (443 by default); IPv6 literals go in brackets
+pub fn probe_endpoint(target: &str) -> Option<(String, u16)> {
+    let target = target.trim();
+    let (host, port) = match target.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') || host.ends_with(']') => (host, port.parse().ok()?),
+        _ => (target, 443),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let plain = !host.is_empty()
+        && !host.starts_with('-')
+        && host.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':'));
+    plain.then(|| (host.to_string(), port))
+}
+
+fn check_family(
+    family: &str,
+    addresses: &[SocketAddr],
+    resolve_error: Option<&str>,
+    token: &CancellationToken,
+) -> FamilyCheck {
+    let mut check = FamilyCheck {
+        family: family.to_string(),
+        addresses: addresses.iter().map(|a| a.ip().to_string()).collect(),
+        reachable: false,
+        error: None,
+    };
+    if addresses.is_empty() {
+        check.error = Some(match resolve_error {
+            Some(e) => format!("lookup failed: {}", e),
+            None => format!("no {} record", if family == "ipv4" { "A" } else { "AAAA" }),
+        });
+        return check;
+    }
+    let mut last_error = None;
+    for &address in addresses {
+        let deadline = Instant::now() + PROBE_TIMEOUT + CANCEL_SLACK;
+        match cancellation::bounded(deadline, token, move || TcpStream::connect_timeout(&address, PROBE_TIMEOUT)) {
+            Some(Ok(_)) => {
+                check.reachable = true;
+                return check;
+            }
+            Some(Err(e)) => last_error = Some(e.to_string()),
+            None if token.is_cancelled() => {
+                last_error = Some("cancelled".to_string());
+                break;
+            }
+            None => last_error = Some("timed out".to_string()),
+        }
+    }
+    check.error = last_error;
+    check
+}
+
+pub fn check_connectivity(target: &str, token: &CancellationToken) -> CommandResult<ConnectivityCheck> {
+    let (host, port) = probe_endpoint(target)
+        .ok_or_else(|| CommandError::InvalidInput(format!("'{}' is not a host or host:port", target)))?;
+    let what = format!("Checking connectivity to {}", target.trim());
+    let lookup = cancellation::bounded(Instant::now() + LOOKUP_TIMEOUT, token, move || {
+        (host.as_str(), port).to_socket_addrs().map(|addrs| addrs.collect::<Vec<_>>())
+    });
+    token.check(&what, || None)?;
+    let (resolved, error) = match lookup {
+        Some(Ok(addrs)) => (addrs, None),
+        Some(Err(e)) => (Vec::new(), Some(e.to_string())),
+        None => (Vec::new(), Some("timed out".to_string())),
+    };
+    let (v4, v6): (Vec<SocketAddr>, Vec<SocketAddr>) = resolved.into_iter().partition(SocketAddr::is_ipv4);
+    let ipv4 = check_family("ipv4", &v4, error.as_deref(), token);
+    token.check(&what, || serde_json::to_value([&ipv4]).ok())?;
+    let ipv6 = check_family("ipv6", &v6, error.as_deref(), token);
+    token.check(&what, || serde_json::to_value([&ipv4, &ipv6]).ok())?;
+    let working = match (ipv4.reachable, ipv6.reachable) {
+        (true, true) => "both",
+        (true, false) => "ipv4",
+        (false, true) => "ipv6",
+        (false, false) => "none",
+    };
+    Ok(ConnectivityCheck {
+        target: target.trim().to_string(),
+        working: working.to_string(),
+        ipv4,
+        ipv6,
+    })
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_network_interfaces() -> Vec<NetworkInterface> {
+    interfaces()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_listening_ports() -> Vec<ListeningPort> {
+    listening_ports()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn check_network_connectivity(
+    operations: State<'_, Operations>,
+    target: Option<String>,
+    operation_id: Option<String>,
+) -> CommandResult<ConnectivityCheck> {
+    let operation = operations.start(operation_id)?;
+    check_connectivity(target.as_deref().unwrap_or(PROBE_TARGET), operation.token())
+}
+
+// Whether IPv6 is set up (a global address), routed (a default route) and
+// working (the probe target answers over it)
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_ipv6_status(target: Option<String>) -> CommandResult<Ipv6Status> {
+    let probe_target = target.unwrap_or_else(|| PROBE_TARGET.to_string());
+    let global_addresses: Vec<String> = interfaces()
+        .into_iter()
+        .flat_map(|i| i.addresses)
+        .filter(|a| a.family == "ipv6" && a.scope.as_deref() == Some("global") && !a.deprecated)
+        .map(|a| format!("{}/{}", a.address, a.prefix))
+        .collect();
+    let routes = ipv6_default_route_interfaces();
+    let probe = check_connectivity(&probe_target, &CancellationToken::default())?.ipv6;
+    Ok(Ipv6Status {
+        configured: !global_addresses.is_empty(),
+        global_addresses,
+        default_route: !routes.is_empty(),
+        default_route_interfaces: routes.into_iter().collect(),
+        probe,
+        probe_target,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancellation::{self, NOTICED_WITHIN};
+
+    // A local port whose connects hang: its one-deep accept queue is full
+    #[cfg(target_os = "linux")]
+    fn unanswered_port() -> (std::net::TcpListener, TcpStream, u16) {
+        use std::os::fd::AsRawFd;
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        unsafe {
+            libc::listen(listener.as_raw_fd(), 0);
+        }
+        let port = listener.local_addr().unwrap().port();
+        let queued = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        (listener, queued, port)
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn a_cancelled_check_stops_mid_probe_with_what_it_has() {
+        let (_listener, _queued, port) = unanswered_port();
+        let target = format!("127.0.0.1:{}", port);
+        let operations = Operations::default();
+        let operation = operations.start(Some("probe".to_string())).unwrap();
+        let (check, overran) =
+            cancellation::cancel_during(&operations, "probe", || check_connectivity(&target, operation.token()));
+        assert!(overran < NOTICED_WITHIN, "{:?}", overran);
+        let Err(CommandError::Cancelled { partial: Some(partial), .. }) = check else {
+            panic!("the check wasn't cancelled");
+        };
+        assert_eq!(partial[0]["family"], "ipv4");
+        assert_eq!(partial[0]["error"], "cancelled");
+    }
+
+    #[test]
+    fn probe_targets_take_a_host_and_an_optional_port() {
+        assert_eq!(probe_endpoint("example.com"), Some(("example.com".to_string(), 443)));
+        assert_eq!(probe_endpoint(" example.com:80 "), Some(("example.com".to_string(), 80)));
+        assert_eq!(probe_endpoint("[2606:4700::1111]:53"), Some(("2606:4700::1111".to_string(), 53)));
+        assert_eq!(probe_endpoint("2606:4700::1111"), Some(("2606:4700::1111".to_string(), 443)));
+        assert_eq!(probe_endpoint("-oProxy=x"), None);
+        assert_eq!(probe_endpoint("host:port"), None);
+        assert_eq!(probe_endpoint("a b"), None);
+    }
+
+    // `iw dev wlp3s0 link` from iw 5.9 (integer freq) and 6.7 ("5955.0")
+    const IW_CONNECTED_OLD: &str = "\
+Connected to 3c:37:86:1a:2b:4c (on wlp3s0)
+\tSSID: Home Network 5G
+\tfreq: 5180
+\tRX: 123456789 bytes (98765 packets)
+\tTX: 2345678 bytes (12345 packets)
+\tsignal: -52 dBm
+\trx bitrate: 780.0 MBit/s VHT-MCS 8 80MHz short GI VHT-NSS 2
+\ttx bitrate: 866.7 MBit/s VHT-MCS 9 80MHz short GI VHT-NSS 2
+
+\tbss flags:\tshort-slot-time
+\tdtim period:\t1
+\tbeacon int:\t100
+";
+
+    const IW_CONNECTED_NEW: &str = "\
+Connected to f0:9f:c2:00:11:22 (on wlan0)
+\tSSID: office
+\tfreq: 5955.0
+\tRX: 5000 bytes (40 packets)
+\tTX: 4000 bytes (30 packets)
+\tsignal: -71 dBm
+\ttx bitrate: 1200.9 MBit/s 160MHz HE-MCS 5 HE-NSS 2 HE-GI 0 HE-DCM 0
+";
+
+    #[test]
+    fn iw_link_output() {
+        assert_eq!(
+            parse_iw_link(IW_CONNECTED_OLD),
+            WirelessLink {
+                connected: true,
+                ssid: Some("Home Network 5G".to_string()),
+                bssid: Some("3c:37:86:1a:2b:4c".to_string()),
+                signal_dbm: Some(-52),
+                frequency_mhz: Some(5180),
+                tx_bitrate: Some("866.7 MBit/s VHT-MCS 9 80MHz short GI VHT-NSS 2".to_string()),
+            }
+        );
+        let new = parse_iw_link(IW_CONNECTED_NEW);
+        assert_eq!((new.frequency_mhz, new.signal_dbm), (Some(5955), Some(-71)));
+        assert_eq!(new.bssid.as_deref(), Some("f0:9f:c2:00:11:22"));
+        assert_eq!(parse_iw_link("Not connected.\n"), WirelessLink::default());
+    }
+
+    #[test]
+    fn if_inet6_addresses_are_network_order_and_compressed() {
+        let text = "\
+00000000000000000000000000000001 01 80 10 80       lo
+fe800000000000000a0027fffe4f2a9c 02 40 20 80     eth0
+20010db8000000000000000000000001 02 40 00 80     eth0
+20010db800000000f1e2d3c4b5a69788 02 40 00 01     eth0
+20010db8000000001111222233334444 02 40 00 21     eth0
+20010db8000000000001000000000001 03 40 00 00   wlp3s0
+not an address 02 40 00 00 eth0
+";
+        let parsed = parse_if_inet6(text);
+        let details = |iface: &str, address: &str| parsed.get(&(iface.to_string(), address.to_string())).cloned();
+        let detail = |scope: &str, temporary: bool, deprecated: bool| {
+            Some(Inet6Details {
+                scope: scope.to_string(),
+                temporary,
+                deprecated,
+            })
+        };
+        assert_eq!(parsed.len(), 6);
+        assert_eq!(details("lo", "::1"), detail("host", false, false));
+        assert_eq!(details("eth0", "fe80::a00:27ff:fe4f:2a9c"), detail("link", false, false));
+        assert_eq!(details("eth0", "2001:db8::1"), detail("global", false, false));
+        assert_eq!(details("eth0", "2001:db8::f1e2:d3c4:b5a6:9788"), detail("global", true, false));
+        assert_eq!(details("eth0", "2001:db8::1111:2222:3333:4444"), detail("global", true, true));
+        // Only the first of two equally long zero runs becomes "::"
+        assert_eq!(details("wlp3s0", "2001:db8::1:0:0:1"), detail("global", false, false));
+    }
+
+    #[test]
+    fn hex_addresses_reject_the_wrong_length() {
+        assert_eq!(ipv6_from_hex("0000000000000000000000000000001"), None);
+        assert_eq!(ipv6_from_hex("zz000000000000000000000000000001"), None);
+        assert_eq!(ipv6_from_proc_net("00000000"), None);
+        assert_eq!(ipv4_from_proc_net("7F000001FF"), None);
+    }
+
+    // /proc/net/tcp6 prints each 32-bit word as the host stores it, so on
+    // a little-endian machine the bytes of every word are reversed
+    #[cfg(target_endian = "little")]
+    #[test]
+    fn proc_net_addresses_are_host_order_words() {
+        assert_eq!(ipv4_from_proc_net("0100007F"), Some(Ipv4Addr::LOCALHOST));
+        assert_eq!(ipv4_from_proc_net("0101A8C0"), Some(Ipv4Addr::new(192, 168, 1, 1)));
+        assert_eq!(ipv6_from_proc_net("00000000000000000000000001000000"), Some(Ipv6Addr::LOCALHOST));
+        assert_eq!(ipv6_from_proc_net("00000000000000000000000000000000"), Some(Ipv6Addr::UNSPECIFIED));
+        let documentation = ipv6_from_proc_net("B80D0120000000000000000001000000").unwrap();
+        assert_eq!(documentation.to_string(), "2001:db8::1");
+        let link_local = ipv6_from_proc_net("000080FE00000000FF27000A9C2A4FFE").unwrap();
+        assert_eq!(link_local.to_string(), "fe80::a00:27ff:fe4f:2a9c");
+        // An IPv4 client on a dual-stack socket
+        let mapped = ipv6_from_proc_net("0000000000000000FFFF00000100007F").unwrap();
+        assert_eq!(mapped.to_string(), "::ffff:127.0.0.1");
+
+        assert_eq!(
+            parse_proc_net_endpoint("B80D0120000000000000000001000000:01BB"),
+            Some((IpAddr::V6("2001:db8::1".parse().unwrap()), 443))
+        );
+        assert_eq!(parse_proc_net_endpoint("0100007F:0277"), Some((IpAddr::V4(Ipv4Addr::LOCALHOST), 631)));
+    }
+}
@@ -0,0 +1,549 @@
+// Outbound webhook notifications (JSON, ntfy-style, or the one-line text
+// from phrasing).
+//
+// Callers only enqueue; a single worker thread fans each event out to the
+// subscribed webhooks and retries failures with backoff, so a dead webhook
+// never blocks the code that raised the event. Webhook URLs usually carry
+// a token, so they live in the secret store and are only ever shown or
+// logged redacted. Nothing is sent, and queued retries are dropped, while
+// the webhooks capability isn't granted (see consent). The worker also
+// hands each event to desktop_notify.
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::alerts::Alert;
+use crate::app::{AppHandle, Manager, State};
+use crate::approvals::ApprovalRequest;
+use crate::calibration::OutcomeDue;
+use crate::consent::{self, ConsentStore};
+use crate::db::Database;
+#[cfg(feature = "gui")]
+use crate::desktop_notify;
+use crate::error::{CommandError, CommandResult};
+use crate::hooks::{self, Hook};
+use crate::incidents::Incident;
+use crate::jobs::Job;
+use crate::liveness::Liveness;
+use crate::phrasing::{self, Line};
+use crate::secrets;
+use crate::settings::SettingsStore;
+
+pub const EVENTS: &[&str] = &[
+    "alert_triggered",
+    "job_failed",
+    "job_completed",
+    "approval_new",
+    "approval_decided",
+    "approval_execution_failed",
+    "incident_oom_kill",
+    "incident_segfault",
+    "incident_unit_crash",
+    "hook_disabled",
+    "approval_outcomes_due",
+];
+const FORMATS: &[&str] = &["json", "ntfy", "text"];
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_BACKOFF: Duration = Duration::from_secs(5);
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+const IDLE_WAIT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WebhookEntry {
+    pub id: String,
+    pub events: Vec<String>,
+    // "json", "ntfy" or "text"
+    pub format: String,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct DeliveryStats {
+    pub last_attempt_at: Option<String>,
+    pub last_success_at: Option<String>,
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+    pub delivered: u64,
+    pub failed: u64,
+}
+
+#[derive(Serialize)]
+pub struct WebhookSummary {
+    pub id: String,
+    // Scheme and host only
+    pub url: String,
+    pub events: Vec<String>,
+    pub format: String,
+    pub stats: DeliveryStats,
+}
+
+struct Message {
+    event: String,
+    title: String,
+    body: String,
+    // The event as one sentence, e.g. "WARNING: Job backup failed: ..."
+    line: String,
+    payload: Value,
+    // "default" or "low"
+    priority: &'static str,
+    sent_at: String,
+}
+
+struct Attempt {
+    webhook: WebhookEntry,
+    url: String,
+    message: Arc<Message>,
+    attempt: u32,
+    due: Instant,
+}
+
+type Stats = Arc<Mutex<HashMap<String, DeliveryStats>>>;
+
+pub struct Notifier {
+    queue: Mutex<Sender<Message>>,
+    stats: Stats,
+    // Jobs already announced, since the update callback fires repeatedly
+    finished_jobs: Mutex<HashSet<String>>,
+}
+
+impl Notifier {
+    pub fn start(app: AppHandle) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let stats: Stats = Arc::default();
+        let worker_stats = stats.clone();
+        std::thread::spawn(move || run_worker(app, rx, worker_stats));
+        Notifier {
+            queue: Mutex::new(tx),
+            stats,
+            finished_jobs: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn stats_for(&self, id: &str) -> DeliveryStats {
+        self.stats.lock().unwrap().get(id).cloned().unwrap_or_default()
+    }
+}
+
+pub fn url_secret_name(id: &str) -> String {
+    format!("webhook:{}", id)
+}
+
+// "https://hooks.slack.com/services/T0/B0/XYZ" -> "https://hooks.slack.com/…"
+pub fn redact_url(url: &str) -> String {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    // Drop any user:password@
+    let host = authority.rsplit('@').next().unwrap_or(authority);
+    let hidden = rest.len() > authority.len() || authority.contains('@');
+    format!(
+        "{}{}{}",
+        if scheme.is_empty() { String::new() } else { format!("{}://", scheme) },
+        host,
+        if hidden { "/…" } else { "" }
+    )
+}
+
+fn record(stats: &Stats, id: &str, outcome: &Result<(), String>) {
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut stats = stats.lock().unwrap();
+    let entry = stats.entry(id.to_string()).or_default();
+    entry.last_attempt_at = Some(now.clone());
+    match outcome {
+        Ok(()) => {
+            entry.last_success_at = Some(now);
+            entry.last_error = None;
+            entry.consecutive_failures = 0;
+            entry.delivered += 1;
+        }
+        Err(e) => {
+            entry.last_error = Some(e.clone());
+            entry.consecutive_failures += 1;
+            entry.failed += 1;
+        }
+    }
+}
+
+// Header values must be printable ASCII
+fn header_safe(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_ascii() && !c.is_ascii_control() { c } else { '?' })
+        .collect()
+}
+
+// The JSON webhook body, which event hooks also get on stdin
+fn message_json(message: &Message) -> Value {
+    json!({
+        "event": message.event,
+        "title": message.title,
+        "body": message.body,
+        "text": message.line,
+        "payload": message.payload,
+        "priority": message.priority,
+        "sent_at": message.sent_at,
+    })
+}
+
+fn deliver(format: &str, url: &str, message: &Message) -> Result<(), String> {
+    let agent = ureq::AgentBuilder::new().timeout(DELIVERY_TIMEOUT).build();
+    let result = match format {
+        "ntfy" => agent
+            .post(url)
+            .set("Title", &header_safe(&message.title))
+            .set("Tags", &header_safe(&message.event))
+            .set("Priority", message.priority)
+            .send_string(&message.body),
+        "text" => agent
+            .post(url)
+            .set("Content-Type", "text/plain; charset=utf-8")
+            .send_string(&message.line),
+        _ => agent.post(url).send_json(message_json(message)),
+    };
+    match result {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(code, _)) => Err(format!("HTTP {}", code)),
+        // Transport errors can echo the URL, so keep only the kind
+        Err(ureq::Error::Transport(t)) => Err(t.kind().to_string()),
+    }
+}
+
+fn webhooks_allowed(app: &AppHandle) -> bool {
+    app.state::<ConsentStore>().allows(&app.state::<Database>(), consent::WEBHOOKS)
+}
+
+fn run_worker(app: AppHandle, rx: Receiver<Message>, stats: Stats) {
+    let mut pending: Vec<Attempt> = Vec::new();
+    loop {
+        // Wakes at least every IDLE_WAIT to tell liveness it's alive
+        let wait = pending
+            .iter()
+            .map(|a| a.due.saturating_duration_since(Instant::now()))
+            .min()
+            .unwrap_or(IDLE_WAIT)
+            .min(IDLE_WAIT);
+        app.state::<Liveness>().beat("notifications", wait);
+        match rx.recv_timeout(wait) {
+            Ok(message) => {
+                let message = Arc::new(message);
+                #[cfg(feature = "gui")]
+                desktop_notify::notify(&app, &message.event, &message.title, &message.body, &message.payload);
+                let mut webhooks = app.state::<SettingsStore>().get().webhooks;
+                webhooks.retain(|w| w.events.contains(&message.event));
+                if !webhooks.is_empty() && !webhooks_allowed(&app) {
+                    println!("[Halbert] Not sending {} to webhooks: not consented", message.event);
+                    webhooks.clear();
+                }
+                for webhook in webhooks {
+                    match secrets::read(&url_secret_name(&webhook.id)) {
+                        Ok(Some(url)) => pending.push(Attempt {
+                            webhook,
+                            url,
+                            message: message.clone(),
+                            attempt: 1,
+                            due: Instant::now(),
+                        }),
+                        Ok(None) => println!("[Halbert] Webhook {} has no stored URL", webhook.id),
+                        Err(e) => println!("[Halbert] Can't read URL for webhook {}: {}", webhook.id, e),
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        if !pending.is_empty() && !webhooks_allowed(&app) {
+            println!("[Halbert] Dropping {} queued webhook deliveries: consent revoked", pending.len());
+            pending.clear();
+        }
+        let now = Instant::now();
+        let (due, later): (Vec<Attempt>, Vec<Attempt>) = pending.drain(..).partition(|a| a.due <= now);
+        pending = later;
+        for mut attempt in due {
+            let outcome = deliver(&attempt.webhook.format, &attempt.url, &attempt.message);
+            record(&stats, &attempt.webhook.id, &outcome);
+            let Err(e) = outcome else { continue };
+            if attempt.attempt >= MAX_ATTEMPTS {
+                println!(
+                    "[Halbert] Giving up on {} to webhook {} ({}): {}",
+                    attempt.message.event,
+                    attempt.webhook.id,
+                    redact_url(&attempt.url),
+                    e
+                );
+                continue;
+            }
+            attempt.due = Instant::now() + RETRY_BACKOFF * 2u32.pow(attempt.attempt - 1);
+            attempt.attempt += 1;
+            pending.push(attempt);
+        }
+    }
+}
+
+fn enqueue(app: &AppHandle, event: &str, title: String, body: String, line: Line, payload: Value) {
+    enqueue_with_priority(app, event, title, body, line, payload, "default");
+}
+
+// Hands the event to the webhooks and to the event hooks
+fn enqueue_with_priority(
+    app: &AppHandle,
+    event: &str,
+    title: String,
+    body: String,
+    line: Line,
+    payload: Value,
+    priority: &'static str,
+) {
+    let message = Message {
+        event: event.to_string(),
+        title,
+        body,
+        line: line.text,
+        payload,
+        priority,
+        sent_at: chrono::Utc::now().to_rfc3339(),
+    };
+    hooks::fire(app, event, message_json(&message));
+    // Not managed yet during setup; nothing can be subscribed then anyway
+    let Some(notifier) = app.try_state::<Notifier>() else {
+        return;
+    };
+    let _ = notifier.queue.lock().unwrap().send(message);
+}
+
+pub fn alert_triggered(app: &AppHandle, alert: &Alert) {
+    let subject = alert.subject.as_deref().map(|s| format!(" on {}", s)).unwrap_or_default();
+    enqueue(
+        app,
+        "alert_triggered",
+        format!("[{}] {}", alert.severity, alert.rule_name),
+        format!("{}{} is {} (threshold {})", alert.signal, subject, alert.value, alert.threshold),
+        phrasing::alert(alert),
+        serde_json::to_value(alert).unwrap_or(Value::Null),
+    );
+}
+
+pub fn approval_new(app: &AppHandle, request: &ApprovalRequest) {
+    enqueue(
+        app,
+        "approval_new",
+        format!("Approval needed: {}", request.task),
+        format!("{} ({} risk)", request.action, request.risk_level),
+        phrasing::approval_requested(request),
+        serde_json::to_value(request).unwrap_or(Value::Null),
+    );
+}
+
+pub fn approval_decided(app: &AppHandle, request: &ApprovalRequest) {
+    enqueue(
+        app,
+        "approval_decided",
+        format!("Request {}: {}", request.status, request.task),
+        request.decision_note.clone().unwrap_or_else(|| request.action.clone()),
+        phrasing::approval_decided(request),
+        serde_json::to_value(request).unwrap_or(Value::Null),
+    );
+}
+
+// The request stays approved; it's the job it was meant to run that failed
+pub fn approval_execution_failed(app: &AppHandle, request: &ApprovalRequest) {
+    enqueue(
+        app,
+        "approval_execution_failed",
+        format!("Approved request didn't run: {}", request.task),
+        request.execution_error.clone().unwrap_or_else(|| request.action.clone()),
+        phrasing::approval_execution_failed(request),
+        serde_json::to_value(request).unwrap_or(Value::Null),
+    );
+}
+
+pub fn incident_new(app: &AppHandle, incident: &Incident) {
+    let pid = incident.pid.map(|p| format!(" (pid {})", p)).unwrap_or_default();
+    enqueue(
+        app,
+        &format!("incident_{}", incident.kind),
+        format!("{}: {}{}", incident.kind, incident.victim, pid),
+        incident.detail.clone(),
+        phrasing::incident(incident),
+        serde_json::to_value(incident).unwrap_or(Value::Null),
+    );
+}
+
+pub fn hook_disabled(app: &AppHandle, hook: &Hook, last_error: &str) {
+    enqueue(
+        app,
+        "hook_disabled",
+        format!("Hook {} disabled", hook.id),
+        format!(
+            "{} failed {} times in a row on {}: {}",
+            hook.script_path, hook.consecutive_failures, hook.event, last_error
+        ),
+        phrasing::hook_disabled(hook.id, hook.consecutive_failures, last_error),
+        serde_json::to_value(hook).unwrap_or(Value::Null),
+    );
+}
+
+// A reminder, so it goes out at low priority
+pub fn approval_outcomes_due(app: &AppHandle, due: &[OutcomeDue]) {
+    let tasks: Vec<String> = due.iter().map(|d| format!("{} ({})", d.task, d.request_id)).collect();
+    enqueue_with_priority(
+        app,
+        "approval_outcomes_due",
+        format!("How did {} approved request(s) turn out?", due.len()),
+        tasks.join("\n"),
+        phrasing::outcomes_due(due.len()),
+        serde_json::to_value(due).unwrap_or(Value::Null),
+        "low",
+    );
+}
+
+// Called for every job update; only the first finished state is announced
+pub fn job_updated(app: &AppHandle, job: &Job) {
+    let event = match job.status.as_str() {
+        "failed" => "job_failed",
+        "completed" => "job_completed",
+        _ => return,
+    };
+    let Some(notifier) = app.try_state::<Notifier>() else {
+        return;
+    };
+    if !notifier.finished_jobs.lock().unwrap().insert(job.id.clone()) {
+        return;
+    }
+    let body = match &job.error {
+        Some(e) => e.clone(),
+        None => format!("{} finished", job.name),
+    };
+    enqueue(
+        app,
+        event,
+        format!("Job {}: {}", job.status, job.name),
+        body,
+        phrasing::job_finished(job),
+        serde_json::to_value(job).unwrap_or(Value::Null),
+    );
+}
+
+fn summarize(notifier: &Notifier, webhook: &WebhookEntry) -> WebhookSummary {
+    let url = secrets::read(&url_secret_name(&webhook.id))
+        .ok()
+        .flatten()
+        .map(|u| redact_url(&u))
+        .unwrap_or_default();
+    WebhookSummary {
+        id: webhook.id.clone(),
+        url,
+        events: webhook.events.clone(),
+        format: webhook.format.clone(),
+        stats: notifier.stats_for(&webhook.id),
+    }
+}
+
+pub fn check_webhook(events: &[String], format: &str) -> CommandResult<()> {
+    if events.is_empty() {
+        return Err(CommandError::InvalidInput("pick at least one event".to_string()));
+    }
+    if let Some(unknown) = events.iter().find(|e| !EVENTS.contains(&e.as_str())) {
+        return Err(CommandError::InvalidInput(format!(
+            "unknown event '{}', expected one of {}",
+            unknown,
+            EVENTS.join(", ")
+        )));
+    }
+    if !FORMATS.contains(&format) {
+        return Err(CommandError::InvalidInput(format!(
+            "unknown format '{}', expected one of {}",
+            format,
+            FORMATS.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn add_webhook(
+    settings: State<'_, SettingsStore>,
+    notifier: State<'_, Notifier>,
+    url: String,
+    events: Vec<String>,
+    format: String,
+) -> CommandResult<WebhookSummary> {
+    let url = url.trim().to_string();
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return Err(CommandError::InvalidInput("webhook url must start with http:// or https://".to_string()));
+    }
+    check_webhook(&events, &format)?;
+
+    let mut added = None;
+    settings.update(|current| {
+        let mut next = current.clone();
+        let n = (1..).find(|n| !next.webhooks.iter().any(|w| w.id == format!("wh_{}", n))).unwrap_or(1);
+        let webhook = WebhookEntry {
+            id: format!("wh_{}", n),
+            events: events.clone(),
+            format: format.clone(),
+        };
+        next.webhooks.push(webhook.clone());
+        added = Some(webhook);
+        Ok(next)
+    })?;
+    let webhook = added.ok_or_else(|| CommandError::Internal("webhook was not added".to_string()))?;
+    secrets::store(&url_secret_name(&webhook.id), &url)?;
+    println!("[Halbert] Added webhook {} ({})", webhook.id, redact_url(&url));
+    Ok(summarize(&notifier, &webhook))
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn list_webhooks(settings: State<'_, SettingsStore>, notifier: State<'_, Notifier>) -> Vec<WebhookSummary> {
+    settings
+        .get()
+        .webhooks
+        .iter()
+        .map(|w| summarize(&notifier, w))
+        .collect()
+}
+
+// One immediate delivery, without retries, so the UI can show the outcome
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn test_webhook(
+    settings: State<'_, SettingsStore>,
+    notifier: State<'_, Notifier>,
+    id: String,
+) -> CommandResult<WebhookSummary> {
+    let webhook = settings
+        .get()
+        .webhooks
+        .into_iter()
+        .find(|w| w.id == id)
+        .ok_or_else(|| CommandError::NotFound(format!("webhook {}", id)))?;
+    let url = secrets::read(&url_secret_name(&id))?
+        .ok_or_else(|| CommandError::NotFound(format!("no stored URL for webhook {}", id)))?;
+    let message = Message {
+        event: "test".to_string(),
+        title: "Halbert test notification".to_string(),
+        body: "If you can read this, the webhook works.".to_string(),
+        line: phrasing::webhook_test().text,
+        payload: Value::Null,
+        priority: "default",
+        sent_at: chrono::Utc::now().to_rfc3339(),
+    };
+    let outcome = deliver(&webhook.format, &url, &message);
+    record(&notifier.stats, &id, &outcome);
+    outcome.map_err(|e| CommandError::Remote(format!("{}: {}", redact_url(&url), e)))?;
+    Ok(summarize(&notifier, &webhook))
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn remove_webhook(settings: State<'_, SettingsStore>, notifier: State<'_, Notifier>, id: String) -> CommandResult<()> {
+    settings.update(|current| {
+        if !current.webhooks.iter().any(|w| w.id == id) {
+            return Err(CommandError::NotFound(format!("webhook {}", id)));
+        }
+        let mut next = current.clone();
+        next.webhooks.retain(|w| w.id != id);
+        Ok(next)
+    })?;
+    secrets::delete(&url_secret_name(&id))?;
+    notifier.stats.lock().unwrap().remove(&id);
+    Ok(())
+}
@@ -0,0 +1,235 @@
+// First-run onboarding.
+//
+// There's no stored checklist: every call re-probes the machine, reusing the
+// self-check probes where one exists, so a step stays complete only while
+// it's actually true. Completing a step applies the matching settings change
+// and probes again. At startup the main window gets `onboarding://required`
+// if a mandatory step is incomplete and the user hasn't skipped the guide.
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tauri_plugin_notification::{NotificationExt, PermissionState};
+
+use crate::alerts::{self, AlertRule, Comparison};
+use crate::app::{AppHandle, Emitter, Manager};
+use crate::backend;
+use crate::error::{CommandError, CommandResult};
+use crate::sandbox;
+use crate::selfcheck::{self, CheckStatus, Outcome};
+use crate::settings::{Settings, SettingsStore};
+
+#[derive(Serialize, Clone)]
+pub struct OnboardingStep {
+    pub id: String,
+    pub label: String,
+    // Startup only prompts for mandatory steps
+    pub mandatory: bool,
+    pub complete: bool,
+    pub detail: String,
+    pub remediation: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct OnboardingState {
+    pub steps: Vec<OnboardingStep>,
+    // Every mandatory step is complete
+    pub ready: bool,
+    pub skipped: bool,
+}
+
+#[derive(Deserialize)]
+struct BackendPayload {
+    backend_url: String,
+    // None leaves the stored token alone; "" clears it
+    #[serde(default)]
+    token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CorpusPayload {
+    corpus_path: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct AlertRulesPayload {
+    // None adds the starter rules
+    rules: Option<Vec<AlertRule>>,
+}
+
+fn notifications_probe(app: &AppHandle) -> Outcome {
+    match app.notification().permission_state() {
+        Ok(PermissionState::Granted) => Outcome::ok("desktop notifications are allowed"),
+        Ok(PermissionState::Denied) => Outcome::missing(
+            "desktop notifications were denied",
+            "Allow notifications for Halbert in your desktop's settings",
+        ),
+        Ok(_) => Outcome::missing(
+            "notification permission hasn't been requested",
+            "Complete this step to ask for permission",
+        ),
+        Err(e) => Outcome::missing(e.to_string(), "Desktop notifications are unavailable"),
+    }
+}
+
+fn alert_rules_probe(settings: &Settings) -> Outcome {
+    let enabled = settings.alert_rules.iter().filter(|r| r.enabled).count();
+    match (settings.alert_rules.len(), enabled) {
+        (0, _) => Outcome::missing("no alert rules defined", "Add the starter rules or define your own"),
+        (total, 0) => Outcome::degraded(format!("all {} alert rules are disabled", total), "Enable at least one rule"),
+        (total, enabled) => Outcome::ok(format!("{} of {} alert rules enabled", enabled, total)),
+    }
+}
+
+fn step(id: &str, label: &str, mandatory: bool, outcome: Outcome) -> OnboardingStep {
+    OnboardingStep {
+        id: id.to_string(),
+        label: label.to_string(),
+        mandatory,
+        complete: outcome.status == CheckStatus::Ok,
+        detail: outcome.detail,
+        remediation: outcome.remediation,
+    }
+}
+
+fn probe_state(app: &AppHandle) -> OnboardingState {
+    let settings = app.state::<SettingsStore>().get();
+    let steps = vec![
+        step("backend", "Connect the Halbert backend", true, selfcheck::backend_probe(&settings)),
+        step("corpus", "Choose a document corpus", true, selfcheck::corpus_probe(&settings)),
+        step("notifications", "Allow desktop notifications", false, notifications_probe(app)),
+        step("alert_rules", "Set up alert rules", false, alert_rules_probe(&settings)),
+    ];
+    OnboardingState {
+        ready: steps.iter().all(|s| s.complete || !s.mandatory),
+        skipped: settings.onboarding_skipped,
+        steps,
+    }
+}
+
+fn starter_rules() -> Vec<AlertRule> {
+    let rule = |id: &str, name: &str, signal: &str, comparison: Comparison, threshold: f64| AlertRule {
+        id: id.to_string(),
+        name: name.to_string(),
+        signal: signal.to_string(),
+        subject: None,
+        comparison,
+        threshold,
+        severity: "warning".to_string(),
+        enabled: true,
+        for_secs: 0,
+    };
+    vec![
+        rule("disk-nearly-full", "Disk nearly full", "disk.usage_percent", Comparison::Ge, 90.0),
+        rule("memory-pressure", "Memory pressure", "memory_percent", Comparison::Ge, 95.0),
+        rule("certificate-expiring", "Certificate expiring", "cert.expiring", Comparison::Eq, 1.0),
+        AlertRule {
+            for_secs: 600,
+            ..rule("backend-slow", "Backend slower than its SLO", "backend.slo_violated", Comparison::Eq, 1.0)
+        },
+    ]
+}
+
+fn parse_payload<T: for<'de> Deserialize<'de>>(step: &str, payload: Option<serde_json::Value>) -> CommandResult<T> {
+    let payload = payload.ok_or_else(|| CommandError::InvalidInput(format!("step '{}' needs a payload", step)))?;
+    serde_json::from_value(payload).map_err(|e| CommandError::InvalidInput(format!("step '{}': {}", step, e)))
+}
+
+fn update_settings<F>(app: &AppHandle, change: F) -> CommandResult<()>
+where
+    F: FnOnce(&mut Settings),
+{
+    let updated = app.state::<SettingsStore>().update(|current| {
+        let mut next = current.clone();
+        change(&mut next);
+        Ok(next)
+    })?;
+    let _ = app.emit("settings://changed", &updated);
+    Ok(())
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        let state = probe_state(&app);
+        if state.ready || state.skipped {
+            return;
+        }
+        let missing: Vec<&str> = state
+            .steps
+            .iter()
+            .filter(|s| s.mandatory && !s.complete)
+            .map(|s| s.id.as_str())
+            .collect();
+        println!("[Halbert] Onboarding required: {}", missing.join(", "));
+        let _ = app.emit_to("main", "onboarding://required", &state);
+    });
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_onboarding_state(app: AppHandle) -> CommandResult<OnboardingState> {
+    Ok(probe_state(&app))
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn complete_onboarding_step(
+    app: AppHandle,
+    step: String,
+    payload: Option<serde_json::Value>,
+) -> CommandResult<OnboardingState> {
+    match step.as_str() {
+        "backend" => {
+            let payload: BackendPayload = parse_payload(&step, payload)?;
+            let url = payload.backend_url.trim().trim_end_matches('/').to_string();
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(CommandError::InvalidInput(
+                    "backend_url must start with http:// or https://".to_string(),
+                ));
+            }
+            if let Some(token) = payload.token {
+                backend::set_backend_token(token)?;
+            }
+            update_settings(&app, |s| s.backend_url = url)?;
+        }
+        "corpus" => {
+            let payload: CorpusPayload = parse_payload(&step, payload)?;
+            let settings = app.state::<SettingsStore>().get();
+            let path = sandbox::CORPUS_PATH
+                .validate(&settings, Path::new(payload.corpus_path.trim()))?
+                .into_path_buf();
+            if !path.exists() {
+                return Err(CommandError::NotFound(format!("corpus path {}", payload.corpus_path)));
+            }
+            if !path.is_dir() {
+                return Err(CommandError::InvalidInput(format!("{} is not a directory", path.display())));
+            }
+            update_settings(&app, |s| s.corpus_path = Some(path.display().to_string()))?;
+        }
+        "notifications" => {
+            app.notification()
+                .request_permission()
+                .map_err(|e| CommandError::Internal(format!("notification permission request failed: {}", e)))?;
+        }
+        "alert_rules" => {
+            let payload: AlertRulesPayload = match payload {
+                Some(_) => parse_payload(&step, payload)?,
+                None => AlertRulesPayload::default(),
+            };
+            // Added next to any existing rules; ids already in use are left alone
+            let mut rules = app.state::<SettingsStore>().get().alert_rules;
+            for rule in payload.rules.unwrap_or_else(starter_rules) {
+                if !rules.iter().any(|r| r.id == rule.id) {
+                    rules.push(rule);
+                }
+            }
+            alerts::validate_rules(&rules)?;
+            update_settings(&app, |s| s.alert_rules = rules)?;
+        }
+        other => return Err(CommandError::InvalidInput(format!("unknown onboarding step '{}'", other))),
+    }
+    Ok(probe_state(&app))
+}
+
+// Only stops the startup prompt; get_onboarding_state keeps reporting steps
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn skip_onboarding(app: AppHandle) -> CommandResult<()> {
+    update_settings(&app, |s| s.onboarding_skipped = true)
+}
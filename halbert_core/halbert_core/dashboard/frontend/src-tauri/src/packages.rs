@@ -0,0 +1,154 @@
+// Package update inventory (apt and dnf)
+//
+// The update_inventory collector refreshes it in the background and
+// announces each result as `updates://inventory`.
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::app::{AppHandle, Emitter, Manager, State};
+use crate::collectors::{self, CollectorRegistry};
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::ratelimit::{self, RateLimiter, Throttled};
+use crate::settings::SettingsStore;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PackageUpdate {
+    pub name: String,
+    pub current_version: Option<String>,
+    pub candidate_version: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct UpdateInventory {
+    // "apt" or "dnf"
+    pub manager: String,
+    pub updates: Vec<PackageUpdate>,
+    pub checked_at: String,
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_update_inventory(
+    settings: State<'_, SettingsStore>,
+    limiter: State<'_, RateLimiter>,
+) -> CommandResult<Throttled<UpdateInventory>> {
+    let min_interval = ratelimit::interval(&settings.get().rate_limits_ms, "get_update_inventory");
+    limiter.call("get_update_inventory", "", min_interval, || {
+        pending_updates().ok_or_else(|| {
+            CommandError::NotSupported("no supported package manager (apt, dnf) found".to_string())
+        })
+    })
+}
+
+// get_update_inventory answers on demand, so the first background run
+// waits a full interval instead of running at boot
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        let registry = app.state::<CollectorRegistry>();
+        let settings = app.state::<SettingsStore>().get();
+        registry.sleep(Duration::from_secs(collectors::interval_secs(&settings, "update_inventory", 6 * 60 * 60)));
+        registry.wait_enabled(&app, "update_inventory");
+        let inventory = registry.run("update_inventory", || {
+            pending_updates().ok_or("no supported package manager (apt, dnf) found")
+        });
+        match inventory {
+            Ok(inventory) => {
+                let _ = app.emit("updates://inventory", &inventory);
+            }
+            Err(e) => println!("[Halbert] Update inventory: {}", e),
+        }
+    });
+}
+
+pub fn pending_updates() -> Option<UpdateInventory> {
+    let (manager, updates) = if let Some(out) = exec::stdout("apt", &["list", "--upgradable"]) {
+        ("apt", parse_apt_upgradable(&out))
+    } else {
+        ("dnf", dnf_check_update()?)
+    };
+    Some(UpdateInventory {
+        manager: manager.to_string(),
+        updates,
+        checked_at: chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+// openssh-server/noble-updates 1:9.6p1-3ubuntu13.5 amd64 [upgradable from: 1:9.6p1-3ubuntu13.4]
+fn parse_apt_upgradable(output: &str) -> Vec<PackageUpdate> {
+    output
+        .lines()
+        .filter(|line| line.contains("[upgradable from:"))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let name = fields.next()?.split('/').next()?.to_string();
+            let candidate_version = fields.next()?.to_string();
+            let current_version = line
+                .split("[upgradable from:")
+                .nth(1)
+                .map(|rest| rest.trim().trim_end_matches(']').trim().to_string());
+            Some(PackageUpdate {
+                name,
+                current_version,
+                candidate_version,
+            })
+        })
+        .collect()
+}
+
+// `dnf check-update` exits 100 when updates are available and 0 when not
+fn dnf_check_update() -> Option<Vec<PackageUpdate>> {
+    let output = Command::new("dnf").args(["check-update", "-q"]).output().ok()?;
+    match output.status.code() {
+        Some(0) => Some(Vec::new()),
+        Some(100) => Some(parse_dnf_check_update(&String::from_utf8_lossy(&output.stdout))),
+        _ => None,
+    }
+}
+
+// openssl-libs.x86_64    1:3.2.2-3.fc41    updates
+fn parse_dnf_check_update(output: &str) -> Vec<PackageUpdate> {
+    output
+        .lines()
+        .take_while(|line| !line.starts_with("Obsoleting"))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 3 {
+                return None;
+            }
+            let name = fields[0].rsplit_once('.').map(|(n, _arch)| n).unwrap_or(fields[0]);
+            Some(PackageUpdate {
+                name: name.to_string(),
+                current_version: None,
+                candidate_version: fields[1].to_string(),
+            })
+        })
+        .collect()
+}
+
+// Every installed package and its version, with the manager that owns them
+pub fn installed_packages() -> Option<(&'static str, BTreeMap<String, String>)> {
+    let (manager, out) = if let Some(out) = exec::stdout("dpkg-query", &["-W", "-f=${Package}\t${Version}\n"]) {
+        ("apt", out)
+    } else {
+        ("dnf", exec::stdout("rpm", &["-qa", "--qf", "%{NAME}\t%{EPOCHNUM}:%{VERSION}-%{RELEASE}\n"])?)
+    };
+    let packages = out
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(name, version)| (name.to_string(), version.to_string()))
+        .collect();
+    Some((manager, packages))
+}
+
+// Installed version of one package, via dpkg or rpm
+pub fn installed_version(name: &str) -> Option<String> {
+    if name.starts_with('-') {
+        return None;
+    }
+    exec::stdout("dpkg-query", &["-W", "-f=${Version}", name])
+        .or_else(|| exec::stdout("rpm", &["-q", "--qf", "%{EPOCHNUM}:%{VERSION}-%{RELEASE}", name]))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
@@ -0,0 +1,849 @@
+// Actions for the command palette.
+//
+// Every Tauri command is either an action here, with a title, keywords and
+// the params the palette should prompt for, or listed in NOT_IN_PALETTE;
+// startup checks the invoke handler's commands against both and says which
+// haven't been decided, so a new command can't slip in unnoticed. An
+// action's id is the command it invokes. Whether it's mutating comes from
+// readonly (anything not on its allow-list), and whether it's enabled is
+// worked out per request from the mode, the automation pause, declined
+// consents and the last self-check. Matching is skim-style: the query's characters must appear in
+// order, with bonuses for runs and word starts.
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::app::State;
+use crate::automation::Gate;
+use crate::consent::{self, ConsentStore};
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::job_templates::{ParamSchema, ParamSpec, ParamType};
+use crate::readonly::{self, Mode};
+use crate::selfcheck::{CheckStatus, SelfCheck, SelfCheckReport, SelfCheckStore};
+use crate::settings::SettingsStore;
+
+const DEFAULT_LIMIT: usize = 20;
+
+// Scoring, per matched character
+const MATCH: i64 = 16;
+const CONSECUTIVE: i64 = 8;
+const WORD_START: i64 = 8;
+const FIRST_CHAR: i64 = 4;
+// Per character skipped between two matches
+const GAP: i64 = 1;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Approvals,
+    Jobs,
+    Storage,
+    Documents,
+    Conversations,
+    System,
+    Network,
+    Hosts,
+    Notifications,
+    Settings,
+}
+
+// Commands that aren't palette actions: ones the UI calls while showing
+// something, or that need context the palette can't prompt for
+pub const NOT_IN_PALETTE: &[&str] = &[
+    "greet",
+    "get_session_info",
+    "get_system_info",
+    "get_system_metrics",
+    "get_approval_detail",
+    "get_approval_history",
+    "dry_run_approval",
+    "approve_request",
+    "reject_request",
+    "approve_group",
+    "record_approval_outcome",
+    "get_confidence_report",
+    "create_approval_template",
+    "list_approval_templates",
+    "delete_approval_template",
+    "instantiate_approval_template",
+    "get_risk_policies",
+    "set_risk_policies",
+    "test_risk_policy",
+    "get_active_jobs",
+    "get_job",
+    "get_job_logs",
+    "get_job_artifacts",
+    "open_job_artifact",
+    "delete_job_artifacts",
+    "get_automation_status",
+    "list_job_templates",
+    "create_job_template",
+    "update_job_template",
+    "delete_job_template",
+    "set_job_secret",
+    "configure_backup",
+    "list_backup_snapshots",
+    "get_last_backup_status",
+    "list_snapshots",
+    "get_storage_stats",
+    "plan_cleanup",
+    "get_cleanup_plan",
+    "get_cleanup_items",
+    "execute_cleanup",
+    "get_memory_stats",
+    "get_disk_trend",
+    "get_usage_summary",
+    "list_corpora",
+    "add_corpus",
+    "remove_corpus",
+    "get_documents",
+    "submit_retrieval_feedback",
+    "get_feedback_summary",
+    "set_document_tags",
+    "get_tags",
+    "get_document_content",
+    "delete_document",
+    "reindex_document",
+    "render_document_preview",
+    "open_path",
+    "import_documents",
+    "add_corpus_source",
+    "list_corpus_sources",
+    "remove_corpus_source",
+    "sync_corpus_source",
+    "scrape_manpages",
+    "scrape_command_help",
+    "get_corpus_health_report",
+    "get_self_check",
+    "get_self_usage",
+    "get_startup_status",
+    "get_liveness",
+    "get_remote_access_status",
+    "set_ui_active",
+    "get_widget_state",
+    "get_sandbox_roots",
+    "get_command_stats",
+    "list_profiles",
+    "save_profile",
+    "apply_profile",
+    "get_collector_status",
+    "preview_configuration_export",
+    "export_configuration",
+    "get_transfer_chunk",
+    "cancel_transfer",
+    "import_configuration",
+    "get_usage_by_user",
+    "get_user_accounts",
+    "get_gpu_processes",
+    "get_thermal_status",
+    "get_metrics_history",
+    "get_metric_sparkline",
+    "get_container_metrics_history",
+    "get_settings",
+    "update_settings",
+    "validate_settings",
+    "list_settings_revisions",
+    "rollback_settings",
+    "get_alert_rules",
+    "set_alert_rules",
+    "get_certificate_status",
+    "add_certificate_target",
+    "remove_certificate_target",
+    "get_process_tree",
+    "request_kill_process",
+    "request_renice_process",
+    "request_service_action",
+    "follow_journal",
+    "update_journal_filter",
+    "stop_journal_follow",
+    "get_event_feed",
+    "get_network_interfaces",
+    "get_listening_ports",
+    "get_connections",
+    "get_ipv6_status",
+    "list_baselines",
+    "compare_baseline",
+    "delete_baseline",
+    "list_hosts",
+    "add_host",
+    "remove_host",
+    "set_host_wake",
+    "set_host_changes",
+    "add_ssh_host",
+    "get_core_status",
+    "set_backend_token",
+    "get_backend_token_status",
+    "get_backend_status",
+    "add_webhook",
+    "list_webhooks",
+    "remove_webhook",
+    "get_desktop_notify_status",
+    "create_hook",
+    "list_hooks",
+    "set_hook_enabled",
+    "delete_hook",
+    "get_hook_runs",
+    "get_onboarding_state",
+    "complete_onboarding_step",
+    "skip_onboarding",
+    "grant_consent",
+    "deny_consent",
+    "revoke_consent",
+    "cancel_operation",
+    "get_dashboard_bundle",
+    "get_memory_panel_bundle",
+    "list_conversations",
+    "get_conversation",
+    "rename_conversation",
+    "delete_conversation",
+    "search_actions",
+    "get_action",
+];
+
+pub struct Action {
+    pub id: &'static str,
+    pub title: &'static str,
+    pub category: Category,
+    pub keywords: &'static [&'static str],
+    pub description: &'static str,
+    // Self-check id; the action is greyed out while that check is missing
+    pub capability: Option<&'static str>,
+    // Starts a job, which a pause would hold
+    pub starts_job: bool,
+    // Keyed as the frontend passes them
+    pub params: ParamSchema,
+}
+
+fn action(id: &'static str, title: &'static str, category: Category) -> Action {
+    Action {
+        id,
+        title,
+        category,
+        keywords: &[],
+        description: "",
+        capability: None,
+        starts_job: false,
+        params: ParamSchema::default(),
+    }
+}
+
+impl Action {
+    fn keywords(self, keywords: &'static [&'static str]) -> Self {
+        Action { keywords, ..self }
+    }
+
+    fn describe(self, description: &'static str) -> Self {
+        Action { description, ..self }
+    }
+
+    fn needs(self, capability: &'static str) -> Self {
+        Action {
+            capability: Some(capability),
+            ..self
+        }
+    }
+
+    fn starts_job(self) -> Self {
+        Action {
+            starts_job: true,
+            ..self
+        }
+    }
+
+    fn param(mut self, name: &str, kind: ParamType, description: &str, required: bool) -> Self {
+        let spec = ParamSpec {
+            kind,
+            allowed: None,
+            description: Some(description.to_string()),
+        };
+        self.params.properties.insert(name.to_string(), spec);
+        if required {
+            self.params.required.push(name.to_string());
+        }
+        self
+    }
+
+    fn one_of(mut self, name: &str, values: &[&str]) -> Self {
+        if let Some(spec) = self.params.properties.get_mut(name) {
+            spec.allowed = Some(values.iter().map(|v| serde_json::json!(v)).collect());
+        }
+        self
+    }
+
+    pub fn mutating(&self) -> bool {
+        !readonly::is_allowed(Mode::ReadOnly, self.id)
+    }
+}
+
+pub struct Registry {
+    actions: Vec<Action>,
+}
+
+impl Registry {
+    pub fn get(&self, id: &str) -> Option<&Action> {
+        self.actions.iter().find(|a| a.id == id)
+    }
+
+    // Commands with no decision either way, then action ids that aren't
+    // commands
+    pub fn undecided<'a>(&self, commands: &[&'a str]) -> (Vec<&'a str>, Vec<&'static str>) {
+        let missing = commands
+            .iter()
+            .copied()
+            .filter(|c| self.get(c).is_none() && !NOT_IN_PALETTE.iter().any(|n| n == c))
+            .collect();
+        let unknown = self.actions.iter().map(|a| a.id).filter(|id| !commands.contains(id)).collect();
+        (missing, unknown)
+    }
+}
+
+pub fn registry() -> Registry {
+    use Category::*;
+    use ParamType::{Array, Boolean, String as Text};
+    let actions = vec![
+        action("get_pending_approvals", "Show pending approvals", Approvals).keywords(&["review", "requests", "queue"]),
+        action("set_automation_paused", "Pause or resume automation", Jobs)
+            .keywords(&["stop", "halt", "emergency", "resume"])
+            .describe("Holds new jobs, auto-approvals, schedules and hooks until resumed")
+            .param("paused", Boolean, "Pause (true) or resume (false)", true)
+            .param("reason", Text, "Why, for the audit log", true)
+            .param("alsoCancelRunning", Boolean, "Also cancel running jobs when pausing", false)
+            .param("discardHeld", Boolean, "Drop held jobs instead of starting them when resuming", false),
+        action("start_job", "Run a job template", Jobs)
+            .keywords(&["template", "task", "execute"])
+            .describe("The template's own params are listed with it in list_job_templates")
+            .starts_job()
+            .param("template", Text, "Template name", true),
+        action("run_backup_now", "Back up now", Jobs).keywords(&["restic", "backup"]).starts_job(),
+        action("run_storage_maintenance", "Run storage maintenance", Storage)
+            .keywords(&["vacuum", "prune", "retention", "database"])
+            .starts_job(),
+        action("create_snapshot", "Take a filesystem snapshot", Storage)
+            .keywords(&["btrfs", "zfs", "lvm", "rollback"])
+            .needs("snapshots")
+            .param("mount", Text, "Mount point, e.g. /", true)
+            .param("label", Text, "What the snapshot is for", true),
+        action("create_baseline", "Save a configuration baseline", Storage)
+            .keywords(&["drift", "compare", "config"])
+            .param("name", Text, "Baseline name", true),
+        action("search_documents", "Search documents", Documents)
+            .keywords(&["find", "rag", "corpus", "docs"])
+            .needs("corpus")
+            .param("query", Text, "What to look for", true)
+            .param("corpus", Text, "Corpus to search; all when empty", false),
+        action("reindex_corpus", "Reindex a corpus", Documents)
+            .keywords(&["rebuild", "embeddings", "index"])
+            .needs("corpus")
+            .starts_job()
+            .param("corpus", Text, "Corpus to reindex; the primary one when empty", false),
+        action("run_corpus_health_check", "Check corpus health", Documents)
+            .keywords(&["stale", "broken", "orphans"])
+            .needs("corpus")
+            .starts_job(),
+        action("ask_question", "Ask Halbert", Conversations)
+            .keywords(&["chat", "question", "help", "llm"])
+            .needs("backend")
+            .param("question", Text, "Your question", true),
+        action("run_self_check", "Run self-check", System).keywords(&["health", "diagnostics", "doctor"]),
+        action("get_dashboard_summary_text", "Read the dashboard summary", System)
+            .keywords(&["screen reader", "text", "accessibility", "status"]),
+        action("get_backend_performance", "Show backend latency", System)
+            .keywords(&["slow", "p95", "slo", "rag", "performance"])
+            .param("window", Text, "5m, 1h or 24h", false)
+            .one_of("window", &["5m", "1h", "24h"]),
+        action("evaluate_alerts", "Evaluate alert rules now", System).keywords(&["alerts", "thresholds", "check"]),
+        action("get_reboot_status", "Check whether a reboot is needed", System)
+            .keywords(&["restart", "kernel", "pending"]),
+        action("get_kernel_modules", "Show kernel modules", System)
+            .keywords(&["dkms", "drivers", "secure boot", "signing"]),
+        action("get_update_inventory", "List available updates", System)
+            .keywords(&["packages", "upgrade", "apt", "dnf", "pacman"]),
+        action("get_firewall_status", "Show firewall status", System).keywords(&["ufw", "nftables", "iptables"]),
+        action("get_time_sync_status", "Check time sync", System).keywords(&["ntp", "chrony", "clock", "drift"]),
+        action("scan_certificates", "Scan TLS certificates", System).keywords(&["ssl", "expiry", "https"]),
+        action("get_incidents", "Show crashes and OOM kills", System)
+            .keywords(&["incidents", "segfault", "oom", "crash"])
+            .param("since", Text, "RFC 3339 time or \"boot\"", false),
+        action("get_change_summary", "What changed since…", System)
+            .keywords(&["changes", "diff", "history"])
+            .param("since", Text, "RFC 3339 time or \"boot\"", true),
+        action("copy_system_report", "Copy a system report", System)
+            .keywords(&["clipboard", "share", "support"])
+            .param("format", Text, "Report format", true)
+            .one_of("format", &["markdown", "text"])
+            .param("redactHostname", Boolean, "Leave the hostname out", false),
+        action("capture_window_screenshot", "Capture a window for a bug report", System)
+            .keywords(&["screenshot", "png", "rendering", "report"])
+            .param("windowLabel", Text, "Window to capture; the main one when empty", false),
+        action("generate_diagnostic_bundle", "Put together a diagnostic bundle", System)
+            .keywords(&["bug", "support", "report", "logs"])
+            .describe("Writes the report, settings, webhooks and recent jobs to a folder, secrets masked, for you to look over")
+            .param("includeScreenshot", Boolean, "Add a screenshot of a window", false)
+            .param("windowLabel", Text, "Window to capture; the main one when empty", false),
+        action("import_backend_history", "Import history from the backend", Settings)
+            .keywords(&["migrate", "python", "approvals", "audit"])
+            .describe("Copies the standalone backend's approvals, job runs and audit entries into the local store")
+            .needs("backend")
+            .starts_job()
+            .param("kinds", Array, "approvals, jobs and/or audit; all when empty", false),
+        action("list_consents", "Review permissions", Settings)
+            .keywords(&["consent", "privacy", "revoke", "allow"]),
+        action("toggle_widget_window", "Toggle the desktop widget", Settings).keywords(&["widget", "overlay"]),
+        action("reset_command_stats", "Reset command timings", Settings).keywords(&["performance", "latency"]),
+        action("generate_remote_access_token", "Generate a remote access token", Settings)
+            .keywords(&["api", "token", "remote"]),
+        action("generate_remote_access_changes_token", "Generate a remote approvals token", Settings)
+            .keywords(&["api", "token", "remote", "approve"]),
+        action("check_network_connectivity", "Check network connectivity", Network)
+            .keywords(&["ping", "dns", "internet", "online"])
+            .param("target", Text, "Host to check; the default targets when empty", false),
+        action("set_active_host", "Switch host", Hosts)
+            .keywords(&["remote", "machine", "server"])
+            .param("hostId", Text, "Host to switch to", true),
+        action("send_wake_on_lan", "Wake a machine", Hosts)
+            .keywords(&["wol", "wake on lan", "power on"])
+            .param("mac", Text, "MAC address", true)
+            .param("broadcast", Text, "Broadcast address; the local one when empty", false),
+        action("test_webhook", "Send a test webhook", Notifications)
+            .keywords(&["notify", "slack", "ping"])
+            .param("id", Text, "Webhook id", true),
+    ];
+    Registry { actions }
+}
+
+// At startup: logs commands without a palette decision, and in debug
+// builds refuses to go on
+pub fn check(registry: &Registry, commands: &[&str]) {
+    let (missing, unknown) = registry.undecided(commands);
+    for command in &missing {
+        println!("[Halbert] Command {} is neither a palette action nor in NOT_IN_PALETTE", command);
+    }
+    for id in &unknown {
+        println!("[Halbert] Palette action {} has no command", id);
+    }
+    debug_assert!(missing.is_empty() && unknown.is_empty(), "palette registry is out of date");
+}
+
+// What enablement depends on, read fresh for each request
+pub struct Conditions<'a> {
+    pub mode: Mode,
+    pub paused: bool,
+    // Capabilities the user declined; undecided ones ask on first use
+    pub declined: Vec<&'static str>,
+    // None before the first self-check has finished
+    pub checks: Option<&'a [SelfCheck]>,
+}
+
+// None when the action can run now, otherwise why not. Mode, pause and
+// consent come in the order readonly::admit checks them.
+pub fn disabled_reason(action: &Action, conditions: &Conditions) -> Option<String> {
+    if conditions.mode == Mode::ReadOnly && action.mutating() {
+        return Some("Halbert is in read-only mode".to_string());
+    }
+    if conditions.paused && action.starts_job {
+        return Some("automation is paused; the job would be held until it resumes".to_string());
+    }
+    if let Some(capability) = consent::capability_for(action.id).filter(|c| conditions.declined.contains(&c.id)) {
+        return Some(format!(
+            "\"{}\" was declined; revoke that in Review permissions to be asked again",
+            capability.title
+        ));
+    }
+    let missing = action.capability.and_then(|id| {
+        conditions
+            .checks?
+            .iter()
+            .find(|c| c.id == id && c.status == CheckStatus::Missing)
+    });
+    missing.map(|check| format!("{}: {}", check.label, check.detail))
+}
+
+// Best match of `query` in `candidate`, ignoring case and the query's
+// spaces: the score and the matched characters' indices
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+    let chars: Vec<char> = candidate.chars().collect();
+    let lower: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+    let (n, m) = (query.len(), chars.len());
+    // best[i][j]: query[..=i] matched with query[i] at j; from[i][j] is
+    // where query[i - 1] went
+    let mut best = vec![vec![None::<i64>; m]; n];
+    let mut from = vec![vec![0; m]; n];
+    for (j, c) in lower.iter().enumerate() {
+        if *c == query[0] {
+            best[0][j] = Some(MATCH + position_bonus(&chars, j));
+        }
+    }
+    for i in 1..n {
+        for (j, c) in lower.iter().enumerate().skip(i) {
+            if *c != query[i] {
+                continue;
+            }
+            let mut top: Option<(i64, usize)> = None;
+            for (k, previous) in best[i - 1].iter().enumerate().take(j).skip(i - 1) {
+                let Some(previous) = *previous else {
+                    continue;
+                };
+                let step = if k + 1 == j { CONSECUTIVE } else { -GAP * (j - k - 1) as i64 };
+                if top.is_none_or(|(score, _)| previous + step > score) {
+                    top = Some((previous + step, k));
+                }
+            }
+            if let Some((score, k)) = top {
+                best[i][j] = Some(score + MATCH + position_bonus(&chars, j));
+                from[i][j] = k;
+            }
+        }
+    }
+    // The earliest end wins a tie
+    let mut end: Option<(usize, i64)> = None;
+    for (j, score) in best[n - 1].iter().enumerate() {
+        if let Some(score) = *score {
+            if end.is_none_or(|(_, top)| score > top) {
+                end = Some((j, score));
+            }
+        }
+    }
+    let (mut j, score) = end?;
+    let mut indices = vec![j];
+    for i in (1..n).rev() {
+        j = from[i][j];
+        indices.push(j);
+    }
+    indices.reverse();
+    Some((score, indices))
+}
+
+fn position_bonus(chars: &[char], j: usize) -> i64 {
+    let Some(&previous) = j.checked_sub(1).and_then(|p| chars.get(p)) else {
+        return WORD_START + FIRST_CHAR;
+    };
+    let camel = previous.is_lowercase() && chars[j].is_uppercase();
+    if !previous.is_alphanumeric() || camel {
+        WORD_START
+    } else {
+        0
+    }
+}
+
+// Matched indices as [start, end) runs
+pub fn runs(indices: &[usize]) -> Vec<[usize; 2]> {
+    let mut runs: Vec<[usize; 2]> = Vec::new();
+    for &i in indices {
+        match runs.last_mut() {
+            Some(run) if run[1] == i => run[1] = i + 1,
+            _ => runs.push([i, i + 1]),
+        }
+    }
+    runs
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Hit {
+    pub score: i64,
+    // "title", "keyword" or "id"
+    pub field: &'static str,
+    // The text that matched, with highlights as character runs
+    pub matched: String,
+    pub highlights: Vec<[usize; 2]>,
+}
+
+fn best_hit(action: &Action, query: &str) -> Option<Hit> {
+    let id = action.id.replace('_', " ");
+    let fields = std::iter::once(("title", action.title))
+        .chain(action.keywords.iter().map(|k| ("keyword", *k)))
+        .chain(std::iter::once(("id", id.as_str())));
+    let mut best: Option<Hit> = None;
+    for (field, text) in fields {
+        let Some((score, indices)) = fuzzy_match(query, text) else {
+            continue;
+        };
+        // The title wins a tie, being what's shown
+        if best.as_ref().is_none_or(|b| score > b.score) {
+            best = Some(Hit {
+                score,
+                field,
+                matched: text.to_string(),
+                highlights: runs(&indices),
+            });
+        }
+    }
+    best
+}
+
+// Registry indices and hits, best first; equal scores keep registry order
+pub fn rank(registry: &Registry, query: &str) -> Vec<(usize, Hit)> {
+    let mut hits: Vec<(usize, Hit)> = registry
+        .actions
+        .iter()
+        .enumerate()
+        .filter_map(|(i, action)| best_hit(action, query).map(|hit| (i, hit)))
+        .collect();
+    hits.sort_by_key(|(_, hit)| std::cmp::Reverse(hit.score));
+    hits
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PaletteEntry {
+    pub id: String,
+    pub title: String,
+    pub category: Category,
+    pub keywords: Vec<String>,
+    pub mutating: bool,
+    pub capability: Option<String>,
+    pub enabled: bool,
+    pub disabled_reason: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ActionMatch {
+    #[serde(flatten)]
+    pub entry: PaletteEntry,
+    #[serde(flatten)]
+    pub hit: Hit,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ActionDescriptor {
+    #[serde(flatten)]
+    pub entry: PaletteEntry,
+    pub description: String,
+    pub starts_job: bool,
+    pub params: BTreeMap<String, ParamSpec>,
+    pub required: Vec<String>,
+}
+
+fn entry(action: &Action, conditions: &Conditions) -> PaletteEntry {
+    let disabled_reason = disabled_reason(action, conditions);
+    PaletteEntry {
+        id: action.id.to_string(),
+        title: action.title.to_string(),
+        category: action.category,
+        keywords: action.keywords.iter().map(|k| k.to_string()).collect(),
+        mutating: action.mutating(),
+        capability: action.capability.map(str::to_string),
+        enabled: disabled_reason.is_none(),
+        disabled_reason,
+    }
+}
+
+fn declined(consents: &ConsentStore, db: &Database) -> Vec<&'static str> {
+    consent::CAPABILITIES
+        .iter()
+        .filter(|c| matches!(consents.decision(db, c.id), Ok(Some(d)) if !d.granted))
+        .map(|c| c.id)
+        .collect()
+}
+
+fn conditions<'a>(
+    settings: &SettingsStore,
+    gate: &Gate,
+    consents: &ConsentStore,
+    db: &Database,
+    report: Option<&'a SelfCheckReport>,
+) -> Conditions<'a> {
+    Conditions {
+        mode: settings.mode(),
+        paused: gate.is_paused(),
+        declined: declined(consents, db),
+        checks: report.map(|r| r.checks.as_slice()),
+    }
+}
+
+// An empty query lists every action in registry order
+#[cfg_attr(feature = "gui", tauri::command)]
+#[allow(clippy::too_many_arguments)]
+pub fn search_actions(
+    registry: State<'_, Registry>,
+    settings: State<'_, SettingsStore>,
+    gate: State<'_, Gate>,
+    consents: State<'_, ConsentStore>,
+    db: State<'_, Database>,
+    selfcheck: State<'_, SelfCheckStore>,
+    query: String,
+    limit: Option<usize>,
+) -> Vec<ActionMatch> {
+    let report = selfcheck.last();
+    let conditions = conditions(&settings, &gate, &consents, &db, report.as_ref());
+    rank(&registry, &query)
+        .into_iter()
+        .take(limit.unwrap_or(DEFAULT_LIMIT))
+        .map(|(i, hit)| ActionMatch {
+            entry: entry(&registry.actions[i], &conditions),
+            hit,
+        })
+        .collect()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_action(
+    registry: State<'_, Registry>,
+    settings: State<'_, SettingsStore>,
+    gate: State<'_, Gate>,
+    consents: State<'_, ConsentStore>,
+    db: State<'_, Database>,
+    selfcheck: State<'_, SelfCheckStore>,
+    id: String,
+) -> CommandResult<ActionDescriptor> {
+    let action = registry
+        .get(&id)
+        .ok_or_else(|| CommandError::NotFound(format!("palette action {}", id)))?;
+    let report = selfcheck.last();
+    let conditions = conditions(&settings, &gate, &consents, &db, report.as_ref());
+    Ok(ActionDescriptor {
+        entry: entry(action, &conditions),
+        description: action.description.to_string(),
+        starts_job: action.starts_job,
+        params: action.params.properties.clone(),
+        required: action.params.required.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(registry: &Registry, query: &str) -> Vec<&'static str> {
+        rank(registry, query).into_iter().map(|(i, _)| registry.actions[i].id).collect()
+    }
+
+    #[test]
+    fn every_command_has_a_palette_decision() {
+        let (missing, unknown) = registry().undecided(crate::COMMANDS);
+        assert!(missing.is_empty(), "undecided: {:?}", missing);
+        assert!(unknown.is_empty(), "not commands: {:?}", unknown);
+    }
+
+    #[test]
+    fn ranking_is_stable() {
+        let registry = registry();
+        // An empty query is the whole registry in order
+        let all: Vec<&str> = registry.actions.iter().map(|a| a.id).collect();
+        assert_eq!(ids(&registry, ""), all);
+        assert_eq!(ids(&registry, "   "), all);
+
+        for query in ["back", "job", "show", "sn", "remote token"] {
+            let first = rank(&registry, query);
+            assert_eq!(first, rank(&registry, query), "{}", query);
+            // Best first, and ties in registry order
+            for pair in first.windows(2) {
+                let ((a, hit_a), (b, hit_b)) = (&pair[0], &pair[1]);
+                assert!(hit_a.score > hit_b.score || (hit_a.score == hit_b.score && a < b), "{}", query);
+            }
+        }
+
+        // A word start beats the same letters scattered through a title
+        assert_eq!(ids(&registry, "back up now")[0], "run_backup_now");
+        assert_eq!(ids(&registry, "wol")[0], "send_wake_on_lan");
+        assert_eq!(ids(&registry, "snapshot")[0], "create_snapshot");
+        assert!(ids(&registry, "qqqq").is_empty());
+    }
+
+    #[test]
+    fn matches_are_highlighted_by_field() {
+        let registry = registry();
+        let (i, hit) = rank(&registry, "dkms").remove(0);
+        assert_eq!(registry.actions[i].id, "get_kernel_modules");
+        assert_eq!((hit.field, hit.matched.as_str(), hit.highlights.as_slice()), ("keyword", "dkms", &[[0, 4]][..]));
+
+        let (score, indices) = fuzzy_match("rsc", "Run self-check").unwrap();
+        assert_eq!(indices, [0, 4, 9]);
+        assert_eq!(runs(&indices), [[0, 1], [4, 5], [9, 10]]);
+        assert!(score > fuzzy_match("rsc", "parse scores").unwrap().0);
+        assert_eq!(fuzzy_match("xyz", "Run self-check"), None);
+    }
+
+    #[test]
+    fn enablement_by_mode_pause_and_consent() {
+        let registry = registry();
+        // Read-only; mutating and starts a job; consent-gated
+        let (look, run, send) = (
+            registry.get("get_reboot_status").unwrap(),
+            registry.get("run_backup_now").unwrap(),
+            registry.get("test_webhook").unwrap(),
+        );
+        assert!(!look.mutating() && run.mutating() && !send.mutating());
+        let enabled = |action: &Action, mode: Mode, paused: bool, declined: bool| {
+            let conditions = Conditions {
+                mode,
+                paused,
+                declined: if declined { vec![consent::WEBHOOKS] } else { Vec::new() },
+                checks: None,
+            };
+            disabled_reason(action, &conditions).is_none()
+        };
+        for mode in [Mode::Full, Mode::ReadOnly] {
+            for paused in [false, true] {
+                for declined in [false, true] {
+                    let case = format!("{:?} paused={} declined={}", mode, paused, declined);
+                    assert!(enabled(look, mode, paused, declined), "{}", case);
+                    assert_eq!(enabled(run, mode, paused, declined), mode == Mode::Full && !paused, "{}", case);
+                    assert_eq!(enabled(send, mode, paused, declined), !declined, "{}", case);
+                }
+            }
+        }
+
+        // The first refusal admit would give is the reason shown
+        let all = Conditions {
+            mode: Mode::ReadOnly,
+            paused: true,
+            declined: vec![consent::WEBHOOKS],
+            checks: None,
+        };
+        assert_eq!(disabled_reason(run, &all).unwrap(), "Halbert is in read-only mode");
+        let full = Conditions { mode: Mode::Full, ..all };
+        assert!(disabled_reason(run, &full).unwrap().starts_with("automation is paused"));
+        assert!(disabled_reason(send, &full).unwrap().contains("was declined"));
+    }
+
+    #[test]
+    fn a_missing_self_check_disables_what_needs_it() {
+        let registry = registry();
+        let search = registry.get("search_documents").unwrap();
+        let check = |status: CheckStatus| SelfCheck {
+            id: "corpus".to_string(),
+            label: "Document corpus".to_string(),
+            status,
+            detail: "no corpus configured".to_string(),
+            remediation: None,
+        };
+        fn conditions(checks: Option<&[SelfCheck]>) -> Conditions<'_> {
+            Conditions {
+                mode: Mode::Full,
+                paused: false,
+                declined: Vec::new(),
+                checks,
+            }
+        }
+        // Unknown until the first self-check finishes
+        assert_eq!(disabled_reason(search, &conditions(None)), None);
+        assert_eq!(disabled_reason(search, &conditions(Some(&[check(CheckStatus::Degraded)]))), None);
+        assert_eq!(
+            disabled_reason(search, &conditions(Some(&[check(CheckStatus::Missing)]))).unwrap(),
+            "Document corpus: no corpus configured"
+        );
+    }
+
+    #[test]
+    fn only_declined_consents_disable() {
+        let (store, db) = (ConsentStore::default(), Database::in_memory());
+        assert!(declined(&store, &db).is_empty());
+        db.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO consents (capability, granted, decided_by, decided_at) VALUES
+                 ('webhooks', 0, 'alice', '2026-10-14T12:00:00Z'),
+                 ('system_journal', 1, 'alice', '2026-10-14T12:00:00Z')",
+                [],
+            )
+        })
+        .unwrap();
+        assert_eq!(declined(&store, &db), [consent::WEBHOOKS]);
+    }
+}
@@ -0,0 +1,452 @@
+// One-line sentences for the text views, and the wording they share.
+//
+// The screen-reader summary, the event feed and the plain-text webhook
+// format all phrase things through here, so the same event reads the same
+// everywhere. Every line starts with its level ("WARNING: /home at 91%")
+// and sizes go through units. Everything here is pure; collection lives in
+// text_feed.
+use serde::Serialize;
+
+use crate::alerts::Alert;
+use crate::approvals::ApprovalRequest;
+use crate::incidents::Incident;
+use crate::jobs::Job;
+use crate::units::Units;
+
+// Usage at or above these is a warning / critical
+const WARN_PERCENT: f32 = 90.0;
+const CRITICAL_PERCENT: f32 = 95.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Level {
+    Ok,
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Level {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Level::Ok => "OK",
+            Level::Info => "INFO",
+            Level::Warning => "WARNING",
+            Level::Critical => "CRITICAL",
+        }
+    }
+
+    // An alert rule's severity; anything unrecognised is a warning
+    pub fn from_severity(severity: &str) -> Self {
+        match severity.trim().to_ascii_lowercase().as_str() {
+            "critical" => Level::Critical,
+            "info" => Level::Info,
+            _ => Level::Warning,
+        }
+    }
+
+    fn for_percent(percent: f32) -> Self {
+        if percent >= CRITICAL_PERCENT {
+            Level::Critical
+        } else if percent >= WARN_PERCENT {
+            Level::Warning
+        } else {
+            Level::Ok
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Line {
+    pub level: Level,
+    // The whole sentence, prefix included
+    pub text: String,
+}
+
+fn line(level: Level, body: String) -> Line {
+    Line {
+        level,
+        text: format!("{}: {}", level.prefix(), body),
+    }
+}
+
+// "91%"
+pub fn percent(value: f32) -> String {
+    format!("{:.0}%", value)
+}
+
+// Alert values and thresholds: "90", "0.5", "12.25"
+pub fn number(value: f64) -> String {
+    let text = format!("{:.2}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+// "1 job", "3 jobs"
+fn count(n: usize, noun: &str) -> String {
+    format!("{} {}{}", n, noun, if n == 1 { "" } else { "s" })
+}
+
+fn on(subject: Option<&str>) -> String {
+    subject.map(|s| format!(" on {}", s)).unwrap_or_default()
+}
+
+// A section the summary couldn't fill
+pub fn unavailable(what: &str, error: &str) -> Line {
+    line(Level::Warning, format!("{} unavailable: {}", what, error))
+}
+
+pub fn cpu(percent_used: f32) -> Line {
+    line(Level::for_percent(percent_used), format!("CPU at {}", percent(percent_used)))
+}
+
+pub fn memory(used_bytes: u64, total_bytes: u64, percent_used: f32, units: Units) -> Line {
+    line(
+        Level::for_percent(percent_used),
+        format!(
+            "Memory at {} ({} of {})",
+            percent(percent_used),
+            units.format_bytes(used_bytes),
+            units.format_bytes(total_bytes)
+        ),
+    )
+}
+
+pub fn disk(mount_point: &str, used_bytes: u64, total_bytes: u64, percent_used: f32, units: Units) -> Line {
+    line(
+        Level::for_percent(percent_used),
+        format!(
+            "{} at {} ({} of {})",
+            mount_point,
+            percent(percent_used),
+            units.format_bytes(used_bytes),
+            units.format_bytes(total_bytes)
+        ),
+    )
+}
+
+pub fn approvals_pending(n: usize) -> Line {
+    match n {
+        0 => line(Level::Ok, "No approvals pending".to_string()),
+        n => line(Level::Info, format!("{} pending", count(n, "approval"))),
+    }
+}
+
+pub fn no_jobs_running() -> Line {
+    line(Level::Ok, "No jobs running".to_string())
+}
+
+// An active job: "Job Backup home running, 40% done" or "Job Backup home queued"
+pub fn job_active(job: &Job) -> Line {
+    let body = match job.status.as_str() {
+        "running" => format!("Job {} running, {} done", job.name, percent(job.progress * 100.0)),
+        status => format!("Job {} {}", job.name, status),
+    };
+    line(Level::Info, body)
+}
+
+pub fn no_alerts() -> Line {
+    line(Level::Ok, "No active alerts".to_string())
+}
+
+// "WARNING: Alert Disk almost full: disk.usage_percent on /home is 91 (threshold 90)"
+pub fn alert(alert: &Alert) -> Line {
+    line(
+        Level::from_severity(&alert.severity),
+        format!(
+            "Alert {}: {}{} is {} (threshold {})",
+            alert.rule_name,
+            alert.signal,
+            on(alert.subject.as_deref()),
+            number(alert.value),
+            number(alert.threshold)
+        ),
+    )
+}
+
+pub fn alert_cleared(alert: &Alert) -> Line {
+    line(
+        Level::Ok,
+        format!("Alert {} cleared{}", alert.rule_name, on(alert.subject.as_deref())),
+    )
+}
+
+pub fn job_started(job: &Job) -> Line {
+    line(Level::Info, format!("Job {} started", job.name))
+}
+
+pub fn job_finished(job: &Job) -> Line {
+    match (job.status.as_str(), &job.error) {
+        ("failed", Some(error)) => line(Level::Warning, format!("Job {} failed: {}", job.name, error)),
+        ("failed", None) => line(Level::Warning, format!("Job {} failed", job.name)),
+        ("completed", _) => line(Level::Ok, format!("Job {} completed", job.name)),
+        (status, _) => line(Level::Info, format!("Job {} {}", job.name, status)),
+    }
+}
+
+pub fn approval_requested(request: &ApprovalRequest) -> Line {
+    line(
+        Level::Info,
+        format!("Approval requested for {} ({} risk)", request.task, request.risk_level),
+    )
+}
+
+pub fn approval_decided(request: &ApprovalRequest) -> Line {
+    let note = request.decision_note.as_deref().map(|n| format!(": {}", n)).unwrap_or_default();
+    line(Level::Info, format!("Request {} {}{}", request.task, request.status, note))
+}
+
+// The request stays approved; it's the job it was meant to run that failed
+pub fn approval_execution_failed(request: &ApprovalRequest) -> Line {
+    let error = request.execution_error.as_deref().unwrap_or(&request.action);
+    line(
+        Level::Warning,
+        format!("Approved request {} didn't run: {}", request.task, error),
+    )
+}
+
+pub fn incident(incident: &Incident) -> Line {
+    let what = match incident.kind.as_str() {
+        "oom_kill" => "Out of memory kill",
+        "segfault" => "Segfault",
+        "unit_crash" => "Unit crashed",
+        other => other,
+    };
+    let pid = incident.pid.map(|p| format!(" (pid {})", p)).unwrap_or_default();
+    let unit = match &incident.unit {
+        Some(unit) if *unit != incident.victim => format!(" in {}", unit),
+        _ => String::new(),
+    };
+    line(Level::Warning, format!("{}: {}{}{}", what, incident.victim, pid, unit))
+}
+
+pub fn hook_disabled(hook_id: i64, failures: u32, last_error: &str) -> Line {
+    line(
+        Level::Warning,
+        format!("Hook {} disabled after {}: {}", hook_id, count(failures as usize, "failure"), last_error),
+    )
+}
+
+pub fn webhook_test() -> Line {
+    line(Level::Info, "Halbert test notification".to_string())
+}
+
+pub fn outcomes_due(n: usize) -> Line {
+    line(
+        Level::Info,
+        format!("{} awaiting an outcome", count(n, "approved request")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn texts(lines: &[Line]) -> Vec<&str> {
+        lines.iter().map(|l| l.text.as_str()).collect()
+    }
+
+    fn job(status: &str, progress: f32, error: Option<&str>) -> Job {
+        serde_json::from_value(json!({
+            "id": "1",
+            "name": "Backup home",
+            "status": status,
+            "started_at": "2026-10-14T12:00:00Z",
+            "progress": progress,
+            "logs": [],
+            "task_type": "backup",
+            "error": error,
+        }))
+        .unwrap()
+    }
+
+    fn request(status: &str, note: Option<&str>, execution_error: Option<&str>) -> ApprovalRequest {
+        serde_json::from_value(json!({
+            "id": "req-1",
+            "task": "Clean apt cache",
+            "action": "apt-get clean",
+            "reasoning": "",
+            "confidence": 0.9,
+            "risk_level": "low",
+            "affected_resources": [],
+            "requested_at": "2026-10-14T12:00:00Z",
+            "status": status,
+            "decision_note": note,
+            "execution_error": execution_error,
+        }))
+        .unwrap()
+    }
+
+    fn triggered(severity: &str, subject: Option<&str>, value: f64) -> Alert {
+        Alert {
+            rule_id: "disk".to_string(),
+            rule_name: "Disk almost full".to_string(),
+            severity: severity.to_string(),
+            signal: "disk.usage_percent".to_string(),
+            subject: subject.map(str::to_string),
+            value,
+            threshold: 90.0,
+            triggered_at: "2026-10-14T12:00:00Z".to_string(),
+            triggered_at_display: None,
+        }
+    }
+
+    fn recorded(kind: &str, victim: &str, pid: Option<u32>, unit: Option<&str>) -> Incident {
+        Incident {
+            id: 1,
+            at: "2026-10-14T12:00:00Z".to_string(),
+            kind: kind.to_string(),
+            victim: victim.to_string(),
+            pid,
+            unit: unit.map(str::to_string),
+            detail: String::new(),
+            memory_percent: None,
+            at_display: None,
+        }
+    }
+
+    #[test]
+    fn usage_levels_by_threshold() {
+        let levels: Vec<Level> = [0.0, 89.9, 90.0, 94.9, 95.0, 100.0].iter().map(|&p| cpu(p).level).collect();
+        assert_eq!(
+            levels,
+            [Level::Ok, Level::Ok, Level::Warning, Level::Warning, Level::Critical, Level::Critical]
+        );
+        assert_eq!(cpu(91.4).text, "WARNING: CPU at 91%");
+        assert_eq!(
+            memory(3 << 30, 16 << 30, 18.75, Units::Binary).text,
+            "OK: Memory at 19% (3.0 GiB of 16.0 GiB)"
+        );
+        assert_eq!(
+            disk("/home", 900_000_000_000, 1_000_000_000_000, 90.0, Units::Decimal).text,
+            "WARNING: /home at 90% (900.0 GB of 1.0 TB)"
+        );
+    }
+
+    #[test]
+    fn severities_and_numbers() {
+        assert_eq!(Level::from_severity(" Critical "), Level::Critical);
+        assert_eq!(Level::from_severity("info"), Level::Info);
+        assert_eq!(Level::from_severity("page someone"), Level::Warning);
+        assert!(Level::Ok < Level::Info && Level::Info < Level::Warning && Level::Warning < Level::Critical);
+        assert_eq!(number(90.0), "90");
+        assert_eq!(number(0.5), "0.5");
+        assert_eq!(number(12.254), "12.25");
+        assert_eq!(number(100.0), "100");
+        assert_eq!(number(0.0), "0");
+    }
+
+    #[test]
+    fn alerts_read_with_their_subject() {
+        assert_eq!(
+            alert(&triggered("warning", Some("/home"), 91.0)).text,
+            "WARNING: Alert Disk almost full: disk.usage_percent on /home is 91 (threshold 90)"
+        );
+        assert_eq!(
+            alert(&triggered("critical", None, 97.5)).text,
+            "CRITICAL: Alert Disk almost full: disk.usage_percent is 97.5 (threshold 90)"
+        );
+        assert_eq!(
+            alert_cleared(&triggered("critical", Some("/"), 0.0)).text,
+            "OK: Alert Disk almost full cleared on /"
+        );
+    }
+
+    #[test]
+    fn jobs_by_status() {
+        let lines = [
+            job_active(&job("running", 0.4, None)),
+            job_active(&job("queued", 0.0, None)),
+            job_started(&job("running", 0.0, None)),
+            job_finished(&job("completed", 1.0, None)),
+            job_finished(&job("failed", 0.5, Some("disk full"))),
+            job_finished(&job("failed", 0.5, None)),
+            job_finished(&job("cancelled", 0.5, None)),
+            no_jobs_running(),
+        ];
+        assert_eq!(
+            texts(&lines),
+            [
+                "INFO: Job Backup home running, 40% done",
+                "INFO: Job Backup home queued",
+                "INFO: Job Backup home started",
+                "OK: Job Backup home completed",
+                "WARNING: Job Backup home failed: disk full",
+                "WARNING: Job Backup home failed",
+                "INFO: Job Backup home cancelled",
+                "OK: No jobs running",
+            ]
+        );
+    }
+
+    #[test]
+    fn approvals_and_counts() {
+        let lines = [
+            approvals_pending(0),
+            approvals_pending(1),
+            approvals_pending(3),
+            approval_requested(&request("pending", None, None)),
+            approval_decided(&request("rejected", Some("not now"), None)),
+            approval_decided(&request("approved", None, None)),
+            approval_execution_failed(&request("approved", None, Some("exit status 100"))),
+            approval_execution_failed(&request("approved", None, None)),
+            outcomes_due(1),
+            outcomes_due(2),
+        ];
+        assert_eq!(
+            texts(&lines),
+            [
+                "OK: No approvals pending",
+                "INFO: 1 approval pending",
+                "INFO: 3 approvals pending",
+                "INFO: Approval requested for Clean apt cache (low risk)",
+                "INFO: Request Clean apt cache rejected: not now",
+                "INFO: Request Clean apt cache approved",
+                "WARNING: Approved request Clean apt cache didn't run: exit status 100",
+                "WARNING: Approved request Clean apt cache didn't run: apt-get clean",
+                "INFO: 1 approved request awaiting an outcome",
+                "INFO: 2 approved requests awaiting an outcome",
+            ]
+        );
+    }
+
+    #[test]
+    fn incidents_name_the_unit_only_when_it_adds_something() {
+        let lines = [
+            incident(&recorded("oom_kill", "firefox", Some(4242), Some("app-firefox.scope"))),
+            incident(&recorded("segfault", "python3", Some(7), None)),
+            incident(&recorded("unit_crash", "nginx.service", None, Some("nginx.service"))),
+            incident(&recorded("kernel_panic", "kernel", None, None)),
+        ];
+        assert_eq!(
+            texts(&lines),
+            [
+                "WARNING: Out of memory kill: firefox (pid 4242) in app-firefox.scope",
+                "WARNING: Segfault: python3 (pid 7)",
+                "WARNING: Unit crashed: nginx.service",
+                "WARNING: kernel_panic: kernel",
+            ]
+        );
+    }
+
+    #[test]
+    fn the_rest() {
+        let lines = [
+            hook_disabled(3, 1, "timeout"),
+            hook_disabled(3, 5, "HTTP 500"),
+            unavailable("Disk usage", "permission denied"),
+            no_alerts(),
+            webhook_test(),
+        ];
+        assert_eq!(
+            texts(&lines),
+            [
+                "WARNING: Hook 3 disabled after 1 failure: timeout",
+                "WARNING: Hook 3 disabled after 5 failures: HTTP 500",
+                "WARNING: Disk usage unavailable: permission denied",
+                "OK: No active alerts",
+                "INFO: Halbert test notification",
+            ]
+        );
+    }
+}
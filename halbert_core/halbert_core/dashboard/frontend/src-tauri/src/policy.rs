@@ -0,0 +1,424 @@
+// Local risk policy for approval requests.
+//
+// Whoever creates a request proposes a risk level; these rules get the
+// final say. Rules are checked in order and the first enabled one whose
+// matchers all hold wins. It can raise the risk to a minimum level and/or
+// attach an auto-decision. Rules never lower a proposed risk.
+use glob::Pattern;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::app::{AppHandle, Emitter, State};
+use crate::error::{CommandError, CommandResult};
+use crate::settings::SettingsStore;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskLevel {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl RiskLevel {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "low" => Some(RiskLevel::Low),
+            "medium" => Some(RiskLevel::Medium),
+            "high" => Some(RiskLevel::High),
+            "critical" => Some(RiskLevel::Critical),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RiskLevel::Low => "low",
+            RiskLevel::Medium => "medium",
+            RiskLevel::High => "high",
+            RiskLevel::Critical => "critical",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoDecision {
+    // Approve without asking, but only if the effective risk is low
+    AutoApproveLow,
+    // Always wait for a person, even for forced process actions
+    AlwaysRequireConfirmation,
+    AutoReject,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RiskPolicy {
+    pub id: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    // Matchers: every one that is set must hold, and at least one must be set
+    // Exact task name, case-insensitive
+    #[serde(default)]
+    pub task: Option<String>,
+    // Case-insensitive substring of the action text
+    #[serde(default)]
+    pub action_contains: Option<String>,
+    #[serde(default)]
+    pub action_regex: Option<String>,
+    // Matches if any affected resource matches
+    #[serde(default)]
+    pub resource_glob: Option<String>,
+    // Effects
+    #[serde(default)]
+    pub min_risk: Option<RiskLevel>,
+    #[serde(default)]
+    pub decision: Option<AutoDecision>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Pending,
+    Approve,
+    Reject,
+}
+
+// The parts of a request policy looks at
+pub struct Subject<'a> {
+    pub task: &'a str,
+    pub action: &'a str,
+    pub risk_level: &'a str,
+    pub affected_resources: &'a [String],
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PolicyOutcome {
+    pub original_risk: String,
+    pub effective_risk: String,
+    // The rule that matched, if any
+    pub rule_id: Option<String>,
+    pub decision: Option<AutoDecision>,
+    pub verdict: Verdict,
+    // False when the matching rule insists on a human decision
+    pub allows_force: bool,
+}
+
+// A synthetic request for test_risk_policy
+#[derive(Deserialize)]
+pub struct SampleRequest {
+    pub task: String,
+    pub action: String,
+    pub risk_level: String,
+    #[serde(default)]
+    pub affected_resources: Vec<String>,
+}
+
+impl RiskPolicy {
+    fn has_matcher(&self) -> bool {
+        self.task.is_some() || self.action_contains.is_some() || self.action_regex.is_some() || self.resource_glob.is_some()
+    }
+
+    // Patterns are checked by validate_policies; a bad one here never matches
+    fn matches(&self, subject: &Subject) -> bool {
+        if !self.enabled || !self.has_matcher() {
+            return false;
+        }
+        if let Some(task) = &self.task {
+            if !task.eq_ignore_ascii_case(subject.task.trim()) {
+                return false;
+            }
+        }
+        if let Some(needle) = &self.action_contains {
+            if !subject.action.to_lowercase().contains(&needle.to_lowercase()) {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.action_regex {
+            match Regex::new(pattern) {
+                Ok(re) if re.is_match(subject.action) => {}
+                _ => return false,
+            }
+        }
+        if let Some(pattern) = &self.resource_glob {
+            match Pattern::new(pattern) {
+                Ok(glob) if subject.affected_resources.iter().any(|r| glob.matches(r)) => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+pub fn evaluate(rules: &[RiskPolicy], subject: &Subject) -> PolicyOutcome {
+    let original = subject.risk_level.to_string();
+    let Some(rule) = rules.iter().find(|rule| rule.matches(subject)) else {
+        return PolicyOutcome {
+            effective_risk: original.clone(),
+            original_risk: original,
+            rule_id: None,
+            decision: None,
+            verdict: Verdict::Pending,
+            allows_force: true,
+        };
+    };
+
+    // An unrecognised proposed level is replaced by the rule's minimum
+    let proposed = RiskLevel::parse(subject.risk_level);
+    let effective = match (proposed, rule.min_risk) {
+        (Some(level), Some(min)) => Some(level.max(min)),
+        (None, Some(min)) => Some(min),
+        (level, None) => level,
+    };
+    let effective_risk = effective.map(|l| l.as_str().to_string()).unwrap_or_else(|| original.clone());
+
+    let verdict = match rule.decision {
+        Some(AutoDecision::AutoApproveLow) if effective == Some(RiskLevel::Low) => Verdict::Approve,
+        Some(AutoDecision::AutoReject) => Verdict::Reject,
+        _ => Verdict::Pending,
+    };
+    PolicyOutcome {
+        original_risk: original,
+        effective_risk,
+        rule_id: Some(rule.id.clone()),
+        decision: rule.decision,
+        verdict,
+        allows_force: !matches!(
+            rule.decision,
+            Some(AutoDecision::AlwaysRequireConfirmation) | Some(AutoDecision::AutoReject)
+        ),
+    }
+}
+
+pub fn validate_policies(rules: &[RiskPolicy]) -> CommandResult<()> {
+    let mut ids = HashSet::new();
+    for rule in rules {
+        if rule.id.trim().is_empty() {
+            return Err(CommandError::InvalidInput("risk policies need an id".to_string()));
+        }
+        if !ids.insert(rule.id.as_str()) {
+            return Err(CommandError::InvalidInput(format!("duplicate risk policy id '{}'", rule.id)));
+        }
+        if !rule.has_matcher() {
+            return Err(CommandError::InvalidInput(format!(
+                "risk policy '{}' needs at least one of task, action_contains, action_regex or resource_glob",
+                rule.id
+            )));
+        }
+        if rule.min_risk.is_none() && rule.decision.is_none() {
+            return Err(CommandError::InvalidInput(format!(
+                "risk policy '{}' needs a min_risk or a decision",
+                rule.id
+            )));
+        }
+        if let Some(pattern) = &rule.action_regex {
+            Regex::new(pattern).map_err(|e| {
+                CommandError::InvalidInput(format!("risk policy '{}': bad action_regex: {}", rule.id, e))
+            })?;
+        }
+        if let Some(pattern) = &rule.resource_glob {
+            Pattern::new(pattern).map_err(|e| {
+                CommandError::InvalidInput(format!("risk policy '{}': bad resource_glob: {}", rule.id, e))
+            })?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_risk_policies(settings: State<'_, SettingsStore>) -> Vec<RiskPolicy> {
+    settings.get().risk_policies
+}
+
+// Replaces the whole list; order matters, the first match wins
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn set_risk_policies(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    rules: Vec<RiskPolicy>,
+) -> CommandResult<Vec<RiskPolicy>> {
+    validate_policies(&rules)?;
+    let updated = settings.update(|current| {
+        let mut next = current.clone();
+        next.risk_policies = rules;
+        Ok(next)
+    })?;
+    let _ = app.emit("settings://changed", &updated);
+    Ok(updated.risk_policies)
+}
+
+// Dry-run a request against `rules` (the saved policies if omitted)
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn test_risk_policy(
+    settings: State<'_, SettingsStore>,
+    sample_request: SampleRequest,
+    rules: Option<Vec<RiskPolicy>>,
+) -> CommandResult<PolicyOutcome> {
+    let rules = match rules {
+        Some(rules) => {
+            validate_policies(&rules)?;
+            rules
+        }
+        None => settings.get().risk_policies,
+    };
+    let subject = Subject {
+        task: &sample_request.task,
+        action: &sample_request.action,
+        risk_level: &sample_request.risk_level,
+        affected_resources: &sample_request.affected_resources,
+    };
+    Ok(evaluate(&rules, &subject))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: &str) -> RiskPolicy {
+        RiskPolicy {
+            id: id.to_string(),
+            description: None,
+            task: None,
+            action_contains: None,
+            action_regex: None,
+            resource_glob: None,
+            min_risk: None,
+            decision: None,
+            enabled: true,
+        }
+    }
+
+    fn subject<'a>(action: &'a str, risk_level: &'a str, resources: &'a [String]) -> Subject<'a> {
+        Subject {
+            task: "Restart service",
+            action,
+            risk_level,
+            affected_resources: resources,
+        }
+    }
+
+    #[test]
+    fn the_first_matching_rule_wins() {
+        let resources = vec!["/etc/nginx/nginx.conf".to_string()];
+        let rules = vec![
+            RiskPolicy {
+                action_contains: Some("NGINX".to_string()),
+                min_risk: Some(RiskLevel::High),
+                ..rule("nginx")
+            },
+            RiskPolicy {
+                resource_glob: Some("/etc/**".to_string()),
+                decision: Some(AutoDecision::AutoReject),
+                ..rule("etc")
+            },
+        ];
+        let outcome = evaluate(&rules, &subject("restart nginx", "low", &resources));
+        assert_eq!(outcome.rule_id.as_deref(), Some("nginx"));
+        assert_eq!(outcome.effective_risk, "high");
+        assert_eq!(outcome.verdict, Verdict::Pending);
+
+        // Swapped, the other rule decides instead
+        let swapped: Vec<RiskPolicy> = rules.into_iter().rev().collect();
+        let outcome = evaluate(&swapped, &subject("restart nginx", "low", &resources));
+        assert_eq!(outcome.rule_id.as_deref(), Some("etc"));
+        assert_eq!(outcome.verdict, Verdict::Reject);
+        assert!(!outcome.allows_force);
+    }
+
+    #[test]
+    fn disabled_and_partial_matches_fall_through() {
+        let resources = vec!["/var/log/syslog".to_string()];
+        let rules = vec![
+            RiskPolicy {
+                action_contains: Some("restart".to_string()),
+                enabled: false,
+                decision: Some(AutoDecision::AutoReject),
+                ..rule("disabled")
+            },
+            // Every matcher that is set must hold
+            RiskPolicy {
+                action_contains: Some("restart".to_string()),
+                resource_glob: Some("/etc/*".to_string()),
+                decision: Some(AutoDecision::AutoReject),
+                ..rule("both")
+            },
+            RiskPolicy {
+                task: Some("restart SERVICE".to_string()),
+                decision: Some(AutoDecision::AutoApproveLow),
+                ..rule("task")
+            },
+        ];
+        let outcome = evaluate(&rules, &subject("restart cron", "low", &resources));
+        assert_eq!(outcome.rule_id.as_deref(), Some("task"));
+        assert_eq!(outcome.verdict, Verdict::Approve);
+    }
+
+    #[test]
+    fn rules_raise_risk_but_never_lower_it() {
+        let raise = vec![RiskPolicy {
+            action_regex: Some("^rm ".to_string()),
+            min_risk: Some(RiskLevel::Medium),
+            decision: Some(AutoDecision::AutoApproveLow),
+            ..rule("rm")
+        }];
+        let outcome = evaluate(&raise, &subject("rm -rf /tmp/x", "critical", &[]));
+        assert_eq!(outcome.effective_risk, "critical");
+        // Raised above low, so the auto-approval no longer applies
+        let outcome = evaluate(&raise, &subject("rm -rf /tmp/x", "low", &[]));
+        assert_eq!(outcome.effective_risk, "medium");
+        assert_eq!(outcome.verdict, Verdict::Pending);
+        // An unknown proposal takes the minimum
+        let outcome = evaluate(&raise, &subject("rm -rf /tmp/x", "whatever", &[]));
+        assert_eq!(outcome.effective_risk, "medium");
+        assert_eq!(outcome.original_risk, "whatever");
+    }
+
+    #[test]
+    fn no_match_keeps_the_proposal() {
+        let outcome = evaluate(&[rule("empty")], &subject("restart nginx", "medium", &[]));
+        assert_eq!(outcome.rule_id, None);
+        assert_eq!(outcome.effective_risk, "medium");
+        assert_eq!(outcome.verdict, Verdict::Pending);
+        assert!(outcome.allows_force);
+    }
+
+    #[test]
+    fn always_confirm_blocks_forcing() {
+        let rules = vec![RiskPolicy {
+            action_contains: Some("kill".to_string()),
+            decision: Some(AutoDecision::AlwaysRequireConfirmation),
+            ..rule("kill")
+        }];
+        let outcome = evaluate(&rules, &subject("kill 1234", "low", &[]));
+        assert_eq!(outcome.verdict, Verdict::Pending);
+        assert!(!outcome.allows_force);
+    }
+
+    #[test]
+    fn validation_refuses_ambiguous_or_broken_rules() {
+        let ok = RiskPolicy {
+            task: Some("x".to_string()),
+            min_risk: Some(RiskLevel::Low),
+            ..rule("ok")
+        };
+        validate_policies(std::slice::from_ref(&ok)).unwrap();
+        let cases = [
+            vec![ok.clone(), ok.clone()],
+            vec![RiskPolicy { id: " ".to_string(), ..ok.clone() }],
+            vec![RiskPolicy { task: None, ..ok.clone() }],
+            vec![RiskPolicy { min_risk: None, ..ok.clone() }],
+            vec![RiskPolicy { action_regex: Some("(".to_string()), ..ok.clone() }],
+            vec![RiskPolicy { resource_glob: Some("[".to_string()), ..ok.clone() }],
+        ];
+        for rules in cases {
+            assert!(matches!(validate_policies(&rules), Err(CommandError::InvalidInput(_))));
+        }
+    }
+}
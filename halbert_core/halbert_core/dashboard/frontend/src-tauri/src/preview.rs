@@ -0,0 +1,208 @@
+// Document previews rendered to HTML on the Rust side.
+//
+// Corpus content is untrusted (much of it is scraped), so raw HTML in
+// markdown is escaped rather than passed through, and links with script
+// schemes are neutralized. Plain text and man pages are shown verbatim
+// in a <pre>.
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag, TagEnd};
+use serde::Serialize;
+use std::io::Read;
+
+use crate::app::State;
+use crate::db::Database;
+use crate::documents;
+use crate::error::{CommandError, CommandResult};
+use crate::settings::SettingsStore;
+
+const MAX_PREVIEW_BYTES: u64 = 5 * 1024 * 1024;
+
+#[derive(Serialize)]
+pub struct PreviewHeading {
+    pub level: u8,
+    pub text: String,
+    // The heading's id attribute in `html`
+    pub anchor: String,
+}
+
+#[derive(Serialize)]
+pub struct DocumentPreview {
+    pub doc_id: String,
+    pub doc_type: String,
+    pub html: String,
+    pub headings: Vec<PreviewHeading>,
+    pub truncated: bool,
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// "Getting Started!" -> "getting-started"
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.trim().to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if (c.is_whitespace() || c == '-' || c == '_') && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_matches('-').to_string();
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug
+    }
+}
+
+fn safe_url(url: CowStr<'_>) -> CowStr<'_> {
+    let scheme = url.trim_start().to_ascii_lowercase();
+    if ["javascript:", "vbscript:", "data:"].iter().any(|s| scheme.starts_with(s)) {
+        CowStr::Borrowed("#")
+    } else {
+        url
+    }
+}
+
+fn render_markdown(source: &str) -> (String, Vec<PreviewHeading>) {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    let mut events: Vec<Event> = Parser::new_ext(source, options)
+        .map(|event| match event {
+            // Raw HTML is shown as text, never interpreted
+            Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => Event::Start(Tag::Link {
+                link_type,
+                dest_url: safe_url(dest_url),
+                title,
+                id,
+            }),
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => Event::Start(Tag::Image {
+                link_type,
+                dest_url: safe_url(dest_url),
+                title,
+                id,
+            }),
+            other => other,
+        })
+        .collect();
+
+    // Give every heading a unique anchor derived from its text
+    let mut headings = Vec::new();
+    let mut used: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < events.len() {
+        if let Event::Start(Tag::Heading { level, .. }) = &events[i] {
+            let level = *level as u8;
+            let text: String = events[i + 1..]
+                .iter()
+                .take_while(|e| !matches!(e, Event::End(TagEnd::Heading(_))))
+                .filter_map(|e| match e {
+                    Event::Text(t) | Event::Code(t) => Some(t.as_ref()),
+                    _ => None,
+                })
+                .collect();
+
+            let base = slugify(&text);
+            let mut anchor = base.clone();
+            let mut n = 2;
+            while used.contains(&anchor) {
+                anchor = format!("{}-{}", base, n);
+                n += 1;
+            }
+            used.push(anchor.clone());
+
+            if let Event::Start(Tag::Heading { id, .. }) = &mut events[i] {
+                *id = Some(CowStr::from(anchor.clone()));
+            }
+            headings.push(PreviewHeading {
+                level,
+                text: text.trim().to_string(),
+                anchor,
+            });
+        }
+        i += 1;
+    }
+
+    let mut out = String::new();
+    html::push_html(&mut out, events.into_iter());
+    (out, headings)
+}
+
+// Man pages rendered with overstrike ("N\x08N") for bold/underline
+fn strip_overstrike(text: &str) -> String {
+    let mut out: Vec<char> = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\u{8}' {
+            out.pop();
+        } else {
+            out.push(c);
+        }
+    }
+    out.into_iter().collect()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn render_document_preview(
+    settings: State<'_, SettingsStore>,
+    db: State<'_, Database>,
+    doc_id: String,
+    max_bytes: u64,
+) -> CommandResult<DocumentPreview> {
+    if max_bytes == 0 || max_bytes > MAX_PREVIEW_BYTES {
+        return Err(CommandError::InvalidInput(format!(
+            "max_bytes must be between 1 and {}",
+            MAX_PREVIEW_BYTES
+        )));
+    }
+    let settings = settings.get();
+    let doc = documents::find_document(&settings, &db, &doc_id)?;
+    let path = documents::resolve_in_corpus(&settings, &doc.corpus, &doc.source)?;
+
+    // One extra byte tells us whether the file was cut
+    let mut bytes = Vec::new();
+    std::fs::File::open(&path)?
+        .take(max_bytes + 1)
+        .read_to_end(&mut bytes)?;
+    let truncated = bytes.len() as u64 > max_bytes;
+    bytes.truncate(max_bytes as usize);
+    let text = String::from_utf8_lossy(&bytes);
+
+    let (html, headings) = match doc.doc_type.as_str() {
+        "markdown" => render_markdown(&text),
+        "manpage" => (format!("<pre>{}</pre>", escape_html(&strip_overstrike(&text))), Vec::new()),
+        _ => (format!("<pre>{}</pre>", escape_html(&text)), Vec::new()),
+    };
+
+    Ok(DocumentPreview {
+        doc_id: doc.id,
+        doc_type: doc.doc_type,
+        html,
+        headings,
+        truncated,
+    })
+}
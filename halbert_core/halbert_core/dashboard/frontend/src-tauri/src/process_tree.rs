@@ -0,0 +1,204 @@
+// Parent/child process tree with rolled-up CPU and memory.
+//
+// Built from one process table snapshot, so processes that exit while the
+// tree is assembled simply aren't in it. A parent link is only trusted if
+// the parent started no later than the child; otherwise the parent pid was
+// reused and the child is treated as a root.
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use sysinfo::{System, Uid};
+
+use crate::app::State;
+use crate::error::{CommandError, CommandResult};
+use crate::ratelimit::{self, RateLimiter, Throttled};
+use crate::settings::SettingsStore;
+
+const MAX_DEPTH: usize = 32;
+const MAX_NODES: usize = 2000;
+
+#[derive(Serialize, Clone)]
+pub struct ProcessNode {
+    pub pid: u32,
+    pub name: String,
+    // Per-core percent, as in top (can exceed 100)
+    pub cpu_percent: f32,
+    pub memory_mb: f64,
+    // This process plus every descendant, including ones cut by the limits
+    pub subtree_cpu_percent: f32,
+    pub subtree_memory_mb: f64,
+    pub children: Vec<ProcessNode>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ProcessTree {
+    pub roots: Vec<ProcessNode>,
+    pub node_count: usize,
+    // Depth or node limits hid part of the tree
+    pub truncated: bool,
+}
+
+pub struct Entry {
+    pub name: String,
+    pub cpu_percent: f32,
+    pub memory_bytes: u64,
+    // Effective user, falling back to the real one
+    pub uid: Option<Uid>,
+}
+
+pub struct Snapshot {
+    pub entries: HashMap<u32, Entry>,
+    pub children: HashMap<u32, Vec<u32>>,
+    pub roots: Vec<u32>,
+}
+
+pub fn snapshot() -> Snapshot {
+    // CPU usage is a delta between two refreshes
+    let mut sys = System::new();
+    sys.refresh_processes();
+    std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+    sys.refresh_processes();
+
+    let processes = sys.processes();
+    let mut entries = HashMap::new();
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    let mut roots = Vec::new();
+
+    for (pid, process) in processes {
+        let pid = pid.as_u32();
+        entries.insert(
+            pid,
+            Entry {
+                name: process.name().to_string(),
+                cpu_percent: process.cpu_usage(),
+                memory_bytes: process.memory(),
+                uid: process.effective_user_id().or(process.user_id()).cloned(),
+            },
+        );
+        let parent = process
+            .parent()
+            .filter(|ppid| ppid.as_u32() != pid)
+            .and_then(|ppid| processes.get(&ppid).map(|p| (ppid.as_u32(), p)))
+            .filter(|(_, parent)| parent.start_time() <= process.start_time());
+        match parent {
+            Some((ppid, _)) => children.entry(ppid).or_default().push(pid),
+            None => roots.push(pid),
+        }
+    }
+    for list in children.values_mut() {
+        list.sort_unstable();
+    }
+    roots.sort_unstable();
+    Snapshot {
+        entries,
+        children,
+        roots,
+    }
+}
+
+// (cpu, memory bytes) for each pid's whole subtree
+fn subtree_totals(snap: &Snapshot) -> HashMap<u32, (f32, u64)> {
+    let mut totals = HashMap::new();
+    let mut visited = HashSet::new();
+    // Iterative post-order, so deep chains can't overflow the stack
+    for &root in &snap.roots {
+        let mut stack = vec![(root, false)];
+        while let Some((pid, expanded)) = stack.pop() {
+            if expanded {
+                let entry = &snap.entries[&pid];
+                let mut total = (entry.cpu_percent, entry.memory_bytes);
+                for child in snap.children.get(&pid).into_iter().flatten() {
+                    if let Some((cpu, mem)) = totals.get(child) {
+                        total.0 += cpu;
+                        total.1 += mem;
+                    }
+                }
+                totals.insert(pid, total);
+            } else if visited.insert(pid) {
+                stack.push((pid, true));
+                for &child in snap.children.get(&pid).into_iter().flatten() {
+                    stack.push((child, false));
+                }
+            }
+        }
+    }
+    totals
+}
+
+struct Builder<'a> {
+    snap: &'a Snapshot,
+    totals: HashMap<u32, (f32, u64)>,
+    count: usize,
+    truncated: bool,
+}
+
+fn mb(bytes: u64) -> f64 {
+    bytes as f64 / 1024.0 / 1024.0
+}
+
+impl Builder<'_> {
+    fn node(&mut self, pid: u32, depth: usize) -> Option<ProcessNode> {
+        if self.count >= MAX_NODES {
+            self.truncated = true;
+            return None;
+        }
+        let entry = self.snap.entries.get(&pid)?;
+        self.count += 1;
+        let (subtree_cpu, subtree_mem) = self.totals.get(&pid).copied().unwrap_or((0.0, 0));
+
+        let child_pids = self.snap.children.get(&pid).map(Vec::as_slice).unwrap_or(&[]);
+        let mut children = Vec::new();
+        if depth + 1 >= MAX_DEPTH {
+            self.truncated |= !child_pids.is_empty();
+        } else {
+            for &child in child_pids {
+                if let Some(node) = self.node(child, depth + 1) {
+                    children.push(node);
+                }
+            }
+        }
+
+        Some(ProcessNode {
+            pid,
+            name: entry.name.clone(),
+            cpu_percent: entry.cpu_percent,
+            memory_mb: mb(entry.memory_bytes),
+            subtree_cpu_percent: subtree_cpu,
+            subtree_memory_mb: mb(subtree_mem),
+            children,
+        })
+    }
+}
+
+// With no root_pid, the whole forest (pid 1, kthreadd, and any orphans)
+fn build(root_pid: Option<u32>) -> CommandResult<ProcessTree> {
+    let snap = snapshot();
+    let roots = match root_pid {
+        Some(pid) if snap.entries.contains_key(&pid) => vec![pid],
+        Some(pid) => return Err(CommandError::NotFound(format!("no process with pid {}", pid))),
+        None => snap.roots.clone(),
+    };
+
+    let mut builder = Builder {
+        totals: subtree_totals(&snap),
+        snap: &snap,
+        count: 0,
+        truncated: false,
+    };
+    let nodes = roots.into_iter().filter_map(|pid| builder.node(pid, 0)).collect();
+    Ok(ProcessTree {
+        roots: nodes,
+        node_count: builder.count,
+        truncated: builder.truncated,
+    })
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_process_tree(
+    settings: State<'_, SettingsStore>,
+    limiter: State<'_, RateLimiter>,
+    root_pid: Option<u32>,
+) -> CommandResult<Throttled<ProcessTree>> {
+    let min_interval = ratelimit::interval(&settings.get().rate_limits_ms, "get_process_tree");
+    let key = format!("{:?}", root_pid);
+    limiter.call("get_process_tree", &key, min_interval, || build(root_pid))
+}
@@ -0,0 +1,231 @@
+// Process actions (kill / renice) gated through the approval store
+use std::process::Command;
+use sysinfo::{Pid, Signal, System, Users};
+
+use crate::app::{AppHandle, State};
+use crate::approvals::{self, ApprovalAction, ApprovalRequest, ApprovalStore, NewApproval};
+use crate::error::{CommandError, CommandResult};
+use crate::policy::{self, PolicyOutcome};
+use crate::settings::SettingsStore;
+
+// A process as it was when the request was made. The start time lets us
+// detect pid reuse between request and approval.
+#[derive(Clone, Debug)]
+pub struct ProcessTarget {
+    pub pid: u32,
+    pub name: String,
+    pub owner: Option<String>,
+    pub start_time: u64,
+}
+
+impl ProcessTarget {
+    pub fn resource(&self) -> String {
+        format!("{}:{}", self.pid, self.name)
+    }
+
+    fn owned_by_root(&self) -> bool {
+        self.owner.as_deref() == Some("root")
+    }
+}
+
+pub fn lookup(pid: u32) -> CommandResult<ProcessTarget> {
+    let mut sys = System::new();
+    sys.refresh_processes();
+    let users = Users::new_with_refreshed_list();
+
+    let process = sys
+        .process(Pid::from_u32(pid))
+        .ok_or_else(|| CommandError::NotFound(format!("no process with pid {}", pid)))?;
+    let owner = process
+        .user_id()
+        .and_then(|uid| users.get_user_by_id(uid))
+        .map(|user| user.name().to_string());
+
+    Ok(ProcessTarget {
+        pid,
+        name: process.name().to_string(),
+        owner,
+        start_time: process.start_time(),
+    })
+}
+
+// Accepts "TERM", "sigterm", "SIGTERM", ...; returns the canonical name
+fn parse_signal(signal: &str) -> CommandResult<(&'static str, Signal)> {
+    let upper = signal.trim().to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    match name {
+        "TERM" => Ok(("SIGTERM", Signal::Term)),
+        "KILL" => Ok(("SIGKILL", Signal::Kill)),
+        "HUP" => Ok(("SIGHUP", Signal::Hangup)),
+        "INT" => Ok(("SIGINT", Signal::Interrupt)),
+        "QUIT" => Ok(("SIGQUIT", Signal::Quit)),
+        "STOP" => Ok(("SIGSTOP", Signal::Stop)),
+        "CONT" => Ok(("SIGCONT", Signal::Continue)),
+        "USR1" => Ok(("SIGUSR1", Signal::User1)),
+        "USR2" => Ok(("SIGUSR2", Signal::User2)),
+        _ => Err(CommandError::InvalidInput(format!("unsupported signal '{}'", signal))),
+    }
+}
+
+// Re-read the process table right before acting and refuse if the pid now
+// belongs to something else
+fn ensure_unchanged(sys: &System, target: &ProcessTarget) -> CommandResult<()> {
+    match sys.process(Pid::from_u32(target.pid)) {
+        Some(p) if p.start_time() == target.start_time && p.name() == target.name => Ok(()),
+        Some(_) => Err(CommandError::Conflict(format!(
+            "pid {} has been reused since the request was made; {} was not touched",
+            target.pid, target.name
+        ))),
+        None => Err(CommandError::Conflict(format!(
+            "process {} ({}) has already exited",
+            target.pid, target.name
+        ))),
+    }
+}
+
+pub fn send_signal(target: &ProcessTarget, signal: &str) -> CommandResult<String> {
+    let (name, sig) = parse_signal(signal)?;
+    let mut sys = System::new();
+    sys.refresh_processes();
+    ensure_unchanged(&sys, target)?;
+
+    let process = sys
+        .process(Pid::from_u32(target.pid))
+        .ok_or_else(|| CommandError::Conflict(format!("process {} has already exited", target.pid)))?;
+    match process.kill_with(sig) {
+        Some(true) => Ok(format!("Sent {} to {}", name, target.resource())),
+        Some(false) => Err(CommandError::PermissionDenied(format!(
+            "could not signal {}",
+            target.resource()
+        ))),
+        None => Err(CommandError::NotSupported(format!(
+            "{} is not supported on this platform",
+            name
+        ))),
+    }
+}
+
+pub fn renice(target: &ProcessTarget, nice: i32) -> CommandResult<String> {
+    let mut sys = System::new();
+    sys.refresh_processes();
+    ensure_unchanged(&sys, target)?;
+
+    let output = Command::new("renice")
+        .args(["-n", &nice.to_string(), "-p", &target.pid.to_string()])
+        .output()?;
+    if !output.status.success() {
+        return Err(CommandError::PermissionDenied(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(format!("Set nice {} on {}", nice, target.resource()))
+}
+
+fn check_force(settings: &SettingsStore, force: Option<bool>) -> CommandResult<bool> {
+    let force = force.unwrap_or(false);
+    if force && !settings.get().allow_forced_process_actions {
+        return Err(CommandError::PermissionDenied(
+            "forced process actions are disabled in settings".to_string(),
+        ));
+    }
+    Ok(force)
+}
+
+// A forced action skips the queue unless a risk policy insists on a
+// decision, in which case it's submitted like any other request
+fn forced(settings: &SettingsStore, force: bool, new: &NewApproval) -> Option<PolicyOutcome> {
+    if !force {
+        return None;
+    }
+    let outcome = policy::evaluate(&settings.get().risk_policies, &new.subject());
+    if !outcome.allows_force {
+        println!(
+            "[Halbert] Risk policy {} requires a decision; queueing forced action",
+            outcome.rule_id.as_deref().unwrap_or_default()
+        );
+        return None;
+    }
+    Some(outcome)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn request_kill_process(
+    app: AppHandle,
+    store: State<'_, ApprovalStore>,
+    settings: State<'_, SettingsStore>,
+    pid: u32,
+    signal: String,
+    force: Option<bool>,
+) -> CommandResult<ApprovalRequest> {
+    let (name, sig) = parse_signal(&signal)?;
+    let force = check_force(&settings, force)?;
+    let target = lookup(pid)?;
+
+    let risk_level = if sig == Signal::Kill || target.owned_by_root() {
+        "high"
+    } else {
+        "medium"
+    };
+    let new = NewApproval {
+        task: "Process Signal".to_string(),
+        action: format!("Send {} to {} (pid {})", name, target.name, pid),
+        reasoning: format!(
+            "Requested from the dashboard; process owned by {}",
+            target.owner.as_deref().unwrap_or("unknown user")
+        ),
+        confidence: 1.0,
+        risk_level: risk_level.to_string(),
+        affected_resources: vec![target.resource()],
+        execution: None,
+        group_id: None,
+        depends_on: Vec::new(),
+    };
+
+    let signal = name.to_string();
+    if let Some(outcome) = forced(&settings, force, &new) {
+        let note = send_signal(&target, &signal)?;
+        return store.record_executed(new, &outcome, note);
+    }
+    approvals::submit(&app, new, Some(ApprovalAction::KillProcess { target, signal }))
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn request_renice_process(
+    app: AppHandle,
+    store: State<'_, ApprovalStore>,
+    settings: State<'_, SettingsStore>,
+    pid: u32,
+    nice: i32,
+    force: Option<bool>,
+) -> CommandResult<ApprovalRequest> {
+    if !(-20..=19).contains(&nice) {
+        return Err(CommandError::InvalidInput(format!(
+            "nice must be between -20 and 19, got {}",
+            nice
+        )));
+    }
+    let force = check_force(&settings, force)?;
+    let target = lookup(pid)?;
+
+    let risk_level = if target.owned_by_root() { "high" } else { "medium" };
+    let new = NewApproval {
+        task: "Process Priority".to_string(),
+        action: format!("Set nice {} on {} (pid {})", nice, target.name, pid),
+        reasoning: format!(
+            "Requested from the dashboard; process owned by {}",
+            target.owner.as_deref().unwrap_or("unknown user")
+        ),
+        confidence: 1.0,
+        risk_level: risk_level.to_string(),
+        affected_resources: vec![target.resource()],
+        execution: None,
+        group_id: None,
+        depends_on: Vec::new(),
+    };
+
+    if let Some(outcome) = forced(&settings, force, &new) {
+        let note = renice(&target, nice)?;
+        return store.record_executed(new, &outcome, note);
+    }
+    approvals::submit(&app, new, Some(ApprovalAction::ReniceProcess { target, nice }))
+}
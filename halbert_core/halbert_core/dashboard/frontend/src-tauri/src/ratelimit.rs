@@ -0,0 +1,229 @@
+// Minimum intervals for expensive commands.
+//
+// A call that arrives sooner than the command's interval after the last
+// computed result gets that result back, marked `cached` with its age,
+// instead of redoing the work. It is never an error, so a runaway frontend
+// loop just sees the same answer. Each command has one slot, holding the
+// result for the last arguments it was called with; different arguments
+// recompute. The slot stays locked while a result is computed, so callers
+// arriving together wait for that result instead of each computing it.
+// Errors are not cached. Intervals come from `rate_limits_ms` in settings;
+// 0 or a missing entry turns the limit off for that command.
+use serde::Serialize;
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::CommandResult;
+
+#[derive(Serialize)]
+pub struct Throttled<T> {
+    #[serde(flatten)]
+    pub value: T,
+    pub cached: bool,
+    // Age of the returned result; 0 when it was just computed
+    pub age_ms: u64,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct RateLimitStats {
+    pub command: String,
+    pub min_interval_ms: u64,
+    // Calls answered from the cache
+    pub hits: u64,
+    // Calls that computed a result
+    pub misses: u64,
+}
+
+struct Cached {
+    key: String,
+    at: Instant,
+    value: Arc<dyn Any + Send + Sync>,
+}
+
+#[derive(Default)]
+struct Slot {
+    cached: Mutex<Option<Cached>>,
+}
+
+#[derive(Default)]
+pub struct RateLimiter {
+    slots: Mutex<HashMap<String, Arc<Slot>>>,
+    counters: Mutex<BTreeMap<String, (u64, u64)>>,
+}
+
+impl RateLimiter {
+    fn slot(&self, command: &str) -> Arc<Slot> {
+        self.slots.lock().unwrap().entry(command.to_string()).or_default().clone()
+    }
+
+    fn count(&self, command: &str, hit: bool) {
+        let mut counters = self.counters.lock().unwrap();
+        let (hits, misses) = counters.entry(command.to_string()).or_default();
+        if hit {
+            *hits += 1;
+        } else {
+            *misses += 1;
+        }
+    }
+
+    // `key` identifies the arguments (and any settings the result depends
+    // on); a cached result is only reused for the same key
+    pub fn call<T, F>(&self, command: &str, key: &str, min_interval: Duration, compute: F) -> CommandResult<Throttled<T>>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce() -> CommandResult<T>,
+    {
+        if min_interval.is_zero() {
+            self.count(command, false);
+            return compute().map(|value| Throttled {
+                value,
+                cached: false,
+                age_ms: 0,
+            });
+        }
+        let slot = self.slot(command);
+        let mut cached = slot.cached.lock().unwrap();
+        if let Some(entry) = cached.as_ref() {
+            let age = entry.at.elapsed();
+            if entry.key == key && age < min_interval {
+                if let Some(value) = entry.value.downcast_ref::<T>() {
+                    self.count(command, true);
+                    return Ok(Throttled {
+                        value: value.clone(),
+                        cached: true,
+                        age_ms: age.as_millis() as u64,
+                    });
+                }
+            }
+        }
+        self.count(command, false);
+        let value = compute()?;
+        *cached = Some(Cached {
+            key: key.to_string(),
+            at: Instant::now(),
+            value: Arc::new(value.clone()),
+        });
+        Ok(Throttled {
+            value,
+            cached: false,
+            age_ms: 0,
+        })
+    }
+
+    // Drop a command's cached result after a change it didn't see
+    pub fn invalidate(&self, command: &str) {
+        if let Some(slot) = self.slots.lock().unwrap().get(command).cloned() {
+            *slot.cached.lock().unwrap() = None;
+        }
+    }
+
+    // Every configured command, plus any that were called without a limit
+    pub fn stats(&self, limits: &BTreeMap<String, u64>) -> Vec<RateLimitStats> {
+        let counters = self.counters.lock().unwrap();
+        let mut stats: BTreeMap<&str, RateLimitStats> = limits
+            .iter()
+            .map(|(command, ms)| {
+                (
+                    command.as_str(),
+                    RateLimitStats {
+                        command: command.clone(),
+                        min_interval_ms: *ms,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        for (command, (hits, misses)) in counters.iter() {
+            let entry = stats.entry(command.as_str()).or_insert_with(|| RateLimitStats {
+                command: command.clone(),
+                ..Default::default()
+            });
+            entry.hits = *hits;
+            entry.misses = *misses;
+        }
+        stats.into_values().collect()
+    }
+}
+
+pub fn interval(limits: &BTreeMap<String, u64>, command: &str) -> Duration {
+    Duration::from_millis(limits.get(command).copied().unwrap_or(0))
+}
+
+pub fn default_limits() -> BTreeMap<String, u64> {
+    [
+        ("get_system_metrics", 1_000),
+        ("get_process_tree", 2_000),
+        ("get_documents", 2_000),
+        ("get_update_inventory", 60_000),
+    ]
+    .into_iter()
+    .map(|(command, ms)| (command.to_string(), ms))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CommandError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Barrier;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn concurrent_callers_share_one_computation() {
+        let limiter = Arc::new(RateLimiter::default());
+        let computed = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(8));
+        let callers: Vec<_> = (0..8)
+            .map(|_| {
+                let (limiter, computed, barrier) = (limiter.clone(), computed.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    limiter
+                        .call("get_process_tree", "", SECOND * 10, || {
+                            computed.fetch_add(1, Ordering::SeqCst);
+                            std::thread::sleep(Duration::from_millis(100));
+                            Ok(42u32)
+                        })
+                        .unwrap()
+                })
+            })
+            .collect();
+        let results: Vec<Throttled<u32>> = callers.into_iter().map(|caller| caller.join().unwrap()).collect();
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+        assert!(results.iter().all(|result| result.value == 42));
+        assert_eq!(results.iter().filter(|result| !result.cached).count(), 1);
+        let stats = limiter.stats(&BTreeMap::new());
+        assert_eq!((stats[0].hits, stats[0].misses), (7, 1));
+    }
+
+    #[test]
+    fn a_different_key_or_an_expired_result_recomputes() {
+        let limiter = RateLimiter::default();
+        let computed = AtomicUsize::new(0);
+        let compute = || Ok(computed.fetch_add(1, Ordering::SeqCst));
+        assert_eq!(limiter.call("get_documents", "a", SECOND, compute).unwrap().value, 0);
+        assert!(limiter.call("get_documents", "a", SECOND, compute).unwrap().cached);
+        assert_eq!(limiter.call("get_documents", "b", SECOND, compute).unwrap().value, 1);
+        std::thread::sleep(Duration::from_millis(30));
+        let expired = limiter.call("get_documents", "b", Duration::from_millis(20), compute).unwrap();
+        assert_eq!((expired.value, expired.cached), (2, false));
+        limiter.invalidate("get_documents");
+        assert_eq!(limiter.call("get_documents", "b", SECOND, compute).unwrap().value, 3);
+    }
+
+    #[test]
+    fn errors_and_unlimited_commands_are_not_cached() {
+        let limiter = RateLimiter::default();
+        let failed: CommandResult<Throttled<u32>> =
+            limiter.call("get_system_metrics", "", SECOND, || Err(CommandError::Internal("boom".into())));
+        assert!(failed.is_err());
+        assert!(!limiter.call("get_system_metrics", "", SECOND, || Ok(1u32)).unwrap().cached);
+        for _ in 0..2 {
+            assert!(!limiter.call("get_update_inventory", "", Duration::ZERO, || Ok(1u32)).unwrap().cached);
+        }
+    }
+}
@@ -0,0 +1,287 @@
+// Read-only mode: observe the machine, never change it.
+//
+// Enforced once, in front of the invoke handler, rather than inside each
+// command. The list below is an allow-list: in read-only mode a command
+// that isn't on it is rejected with `ReadOnlyMode` before it runs, so a
+// newly added command is blocked until someone decides it is safe. The
+// same guard then refuses the commands automation::PAUSED_COMMANDS names
+// while automation is paused, and last asks consent whether the command's
+// capability has been agreed to (see `admit`).
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "gui")]
+use tauri::{ipc::Invoke, Manager, Runtime};
+
+use crate::automation::{self, Gate};
+use crate::consent::{self, ConsentStore};
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+#[cfg(feature = "gui")]
+use crate::settings::SettingsStore;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    #[default]
+    Full,
+    ReadOnly,
+}
+
+// Commands that only observe. update_settings is here because it checks
+// the patch itself (see ensure_settings_patch_allowed).
+pub const READ_ONLY_COMMANDS: &[&str] = &[
+    "greet",
+    "get_session_info",
+    "get_system_info",
+    "get_system_metrics",
+    "get_pending_approvals",
+    "get_approval_detail",
+    "get_approval_history",
+    "get_confidence_report",
+    "get_active_jobs",
+    "get_job",
+    "get_job_logs",
+    "get_job_artifacts",
+    "get_automation_status",
+    "open_job_artifact",
+    "list_job_templates",
+    "list_approval_templates",
+    "list_backup_snapshots",
+    "get_last_backup_status",
+    "list_snapshots",
+    "get_storage_stats",
+    "get_cleanup_plan",
+    "get_cleanup_items",
+    "get_memory_stats",
+    "get_disk_trend",
+    "get_usage_summary",
+    "list_corpora",
+    "get_documents",
+    "get_document_content",
+    "search_documents",
+    "get_feedback_summary",
+    "get_tags",
+    "preview_configuration_export",
+    "get_transfer_chunk",
+    "cancel_transfer",
+    "render_document_preview",
+    "open_path",
+    "run_corpus_health_check",
+    "get_corpus_health_report",
+    "list_corpus_sources",
+    "run_self_check",
+    "get_self_usage",
+    "get_startup_status",
+    "get_liveness",
+    "get_remote_access_status",
+    // Only slows or resumes Halbert's own sampling
+    "set_ui_active",
+    "get_command_stats",
+    "list_profiles",
+    "get_collector_status",
+    "get_metrics_history",
+    "get_metric_sparkline",
+    "get_container_metrics_history",
+    "get_self_check",
+    "search_actions",
+    "get_action",
+    "get_settings",
+    "update_settings",
+    "validate_settings",
+    "list_settings_revisions",
+    "get_alert_rules",
+    "get_risk_policies",
+    "test_risk_policy",
+    "evaluate_alerts",
+    "get_time_sync_status",
+    "get_firewall_status",
+    "get_process_tree",
+    "get_usage_by_user",
+    "get_user_accounts",
+    "get_gpu_processes",
+    "get_thermal_status",
+    "get_network_interfaces",
+    "get_listening_ports",
+    "get_connections",
+    "check_network_connectivity",
+    "get_ipv6_status",
+    "scan_certificates",
+    "get_certificate_status",
+    "get_reboot_status",
+    "get_kernel_modules",
+    "get_change_summary",
+    "get_incidents",
+    "get_dashboard_summary_text",
+    "get_event_feed",
+    "follow_journal",
+    "update_journal_filter",
+    "stop_journal_follow",
+    // Baselines only write Halbert's own database
+    "create_baseline",
+    "list_baselines",
+    "compare_baseline",
+    "get_update_inventory",
+    "copy_system_report",
+    "list_hosts",
+    "set_active_host",
+    "get_core_status",
+    "get_backend_token_status",
+    "list_webhooks",
+    "test_webhook",
+    "get_desktop_notify_status",
+    "list_hooks",
+    "get_hook_runs",
+    "toggle_widget_window",
+    "get_widget_state",
+    "get_sandbox_roots",
+    "get_onboarding_state",
+    "skip_onboarding",
+    // Consent decisions are Halbert's own
+    "list_consents",
+    "grant_consent",
+    "deny_consent",
+    "revoke_consent",
+    "get_backend_status",
+    "get_backend_performance",
+    // Only writes a PNG into Halbert's data dir
+    "capture_window_screenshot",
+    "generate_diagnostic_bundle",
+    // Only stops work already under way
+    "cancel_operation",
+    "get_dashboard_bundle",
+    "get_memory_panel_bundle",
+    "ask_question",
+    "list_conversations",
+    "get_conversation",
+];
+
+// Settings that only affect what the dashboard shows; everything else
+// (including `mode` itself) needs full mode to change
+const UI_SETTINGS: &[&str] = &[
+    "active_host",
+    "metrics_interval_secs",
+    "time_drift_threshold_ms",
+    "onboarding_skipped",
+    "units",
+    "widget",
+];
+
+pub fn is_allowed(mode: Mode, command: &str) -> bool {
+    mode == Mode::Full || READ_ONLY_COMMANDS.contains(&command)
+}
+
+pub fn ensure_settings_patch_allowed(mode: Mode, patch: &serde_json::Value) -> CommandResult<()> {
+    if mode == Mode::Full {
+        return Ok(());
+    }
+    let blocked: Vec<&str> = patch
+        .as_object()
+        .map(|fields| {
+            fields
+                .keys()
+                .map(|k| k.as_str())
+                .filter(|k| !UI_SETTINGS.contains(k))
+                .collect()
+        })
+        .unwrap_or_default();
+    if blocked.is_empty() {
+        Ok(())
+    } else {
+        Err(CommandError::ReadOnlyMode(format!(
+            "settings {} can't be changed in read-only mode",
+            blocked.join(", ")
+        )))
+    }
+}
+
+// Whether a command may run, checked in this order: the mode, the
+// automation pause, then consent for its capability. The first refusal is
+// the one returned, so a read-only window never gets as far as a consent
+// prompt.
+pub fn admit(mode: Mode, gate: &Gate, consents: &ConsentStore, db: &Database, command: &str) -> CommandResult<()> {
+    if !is_allowed(mode, command) {
+        return Err(CommandError::ReadOnlyMode(format!(
+            "{} is disabled in read-only mode",
+            command
+        )));
+    }
+    if automation::PAUSED_COMMANDS.contains(&command) {
+        if let Some(pause) = gate.pause() {
+            return Err(CommandError::AutomationPaused(format!(
+                "{} waits until automation is resumed (paused by {}: {})",
+                command, pause.actor, pause.reason
+            )));
+        }
+    }
+    consent::check(consents, db, command)
+}
+
+// Wrap the generated invoke handler with admit
+#[cfg(feature = "gui")]
+pub fn guarded<R, F>(handler: F) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static
+where
+    R: Runtime,
+    F: Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+{
+    move |invoke: Invoke<R>| {
+        let webview = invoke.message.webview();
+        let mode = webview.state::<SettingsStore>().mode();
+        let command = invoke.message.command().to_string();
+        let gate = webview.state::<Gate>();
+        if let Err(e) = admit(mode, &gate, &webview.state::<ConsentStore>(), &webview.state::<Database>(), &command) {
+            println!("[Halbert] Blocked {}: {}", command, e);
+            invoke.resolver.reject(e);
+            return true;
+        }
+        handler(invoke)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn the_allow_list_names_registered_commands_once() {
+        let registered: HashSet<&str> = crate::COMMANDS.iter().copied().collect();
+        let mut seen = HashSet::new();
+        for command in READ_ONLY_COMMANDS {
+            assert!(registered.contains(command), "{} is allowed but not registered", command);
+            assert!(seen.insert(command), "{} is listed twice", command);
+        }
+        for command in automation::PAUSED_COMMANDS {
+            assert!(registered.contains(command), "{} is held while paused but not registered", command);
+        }
+    }
+
+    #[test]
+    fn read_only_mode_rejects_every_other_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let gate = Gate::load(dir.path().join("automation.json"));
+        let (consents, db) = (ConsentStore::default(), Database::in_memory());
+        for command in crate::COMMANDS {
+            let admitted = admit(Mode::ReadOnly, &gate, &consents, &db, command);
+            if READ_ONLY_COMMANDS.contains(command) {
+                assert!(!matches!(admitted, Err(CommandError::ReadOnlyMode(_))), "{} was refused", command);
+            } else {
+                assert!(matches!(admitted, Err(CommandError::ReadOnlyMode(_))), "{} got through", command);
+            }
+            assert!(!matches!(
+                admit(Mode::Full, &gate, &consents, &db, command),
+                Err(CommandError::ReadOnlyMode(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn read_only_settings_patches_touch_only_ui_settings() {
+        let ui = serde_json::json!({ "units": "decimal", "widget": { "enabled": true } });
+        ensure_settings_patch_allowed(Mode::ReadOnly, &ui).unwrap();
+        let mode = serde_json::json!({ "units": "decimal", "mode": "full" });
+        match ensure_settings_patch_allowed(Mode::ReadOnly, &mode) {
+            Err(CommandError::ReadOnlyMode(message)) => assert!(message.contains("mode"), "{}", message),
+            _ => panic!("changing the mode must be refused"),
+        }
+        ensure_settings_patch_allowed(Mode::Full, &mode).unwrap();
+    }
+}
@@ -0,0 +1,159 @@
+// Pending reboot detection.
+//
+// Each probe is independent: a missing tool leaves its fields as None
+// instead of failing the whole status. DKMS modules the newest kernel has
+// no build of don't ask for a reboot, but are listed as a reason to hold
+// off on one (see kernel_modules).
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use sysinfo::System;
+
+use crate::exec;
+use crate::kernel_modules;
+
+#[derive(Serialize, Default, Clone)]
+pub struct RebootStatus {
+    pub reboot_required: bool,
+    // Which probes asked for the reboot, e.g. "reboot-required", "kernel"
+    pub reasons: Vec<String>,
+    pub packages: Option<Vec<String>>,
+    pub running_kernel: Option<String>,
+    pub newest_installed_kernel: Option<String>,
+    pub outdated_services: Option<u32>,
+    // DKMS modules not installed for the newest kernel; None without dkms
+    pub dkms_not_installed: Option<Vec<String>>,
+    pub checked_at: String,
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_reboot_status() -> RebootStatus {
+    probe_reboot_status()
+}
+
+pub fn probe_reboot_status() -> RebootStatus {
+    let mut status = RebootStatus {
+        checked_at: chrono::Utc::now().to_rfc3339(),
+        ..Default::default()
+    };
+
+    // Debian/Ubuntu: update-notifier drops a flag file plus the packages
+    if Path::new("/var/run/reboot-required").exists() {
+        status.reasons.push("reboot-required".to_string());
+        status.packages = std::fs::read_to_string("/var/run/reboot-required.pkgs")
+            .ok()
+            .map(|text| {
+                let mut pkgs: Vec<String> = text
+                    .lines()
+                    .map(|l| l.trim().to_string())
+                    .filter(|l| !l.is_empty())
+                    .collect();
+                pkgs.sort();
+                pkgs.dedup();
+                pkgs
+            });
+    }
+
+    // Fedora/RHEL: `dnf needs-restarting -r` exits 1 when a reboot is needed
+    if let Some(needed) = dnf_needs_reboot() {
+        if needed {
+            status.reasons.push("needs-restarting".to_string());
+        }
+    }
+
+    status.running_kernel = System::kernel_version();
+    status.newest_installed_kernel = newest_installed_kernel();
+    if let (Some(running), Some(newest)) = (&status.running_kernel, &status.newest_installed_kernel) {
+        if compare_kernel_versions(newest, running) == Ordering::Greater {
+            status.reasons.push("kernel".to_string());
+        }
+    }
+
+    status.outdated_services = outdated_service_count();
+    status.dkms_not_installed = kernel_modules::dkms_not_installed();
+    status.reboot_required = !status.reasons.is_empty();
+    status
+}
+
+fn dnf_needs_reboot() -> Option<bool> {
+    let code = Command::new("dnf")
+        .args(["needs-restarting", "-r"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .ok()?
+        .code()?;
+    match code {
+        0 => Some(false),
+        1 => Some(true),
+        _ => None,
+    }
+}
+
+// Installed kernels are the release directories under /lib/modules,
+// falling back to /boot/vmlinuz-<release>
+fn installed_kernels() -> Vec<String> {
+    let mut kernels: Vec<String> = std::fs::read_dir("/lib/modules")
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().join("modules.dep").exists() || e.path().join("kernel").is_dir())
+                .filter_map(|e| e.file_name().into_string().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if kernels.is_empty() {
+        kernels = std::fs::read_dir("/boot")
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .filter_map(|e| e.file_name().into_string().ok())
+                    .filter_map(|name| name.strip_prefix("vmlinuz-").map(|v| v.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+
+    kernels
+}
+
+pub fn newest_installed_kernel() -> Option<String> {
+    installed_kernels()
+        .into_iter()
+        .max_by(|a, b| compare_kernel_versions(a, b))
+}
+
+// Compare release strings like "6.14.0-37-generic" numerically, component
+// by component; non-numeric suffixes are ignored
+pub fn compare_kernel_versions(a: &str, b: &str) -> Ordering {
+    fn numbers(v: &str) -> Vec<u64> {
+        v.split(|c: char| !c.is_ascii_digit())
+            .filter(|part| !part.is_empty())
+            .filter_map(|part| part.parse().ok())
+            .collect()
+    }
+    numbers(a).cmp(&numbers(b))
+}
+
+// Services still running binaries or libraries replaced by an update
+fn outdated_service_count() -> Option<u32> {
+    if let Some(out) = exec::stdout("needs-restarting", &["-s"])
+        .or_else(|| exec::stdout("dnf", &["needs-restarting", "-s"]))
+    {
+        return Some(out.lines().filter(|l| !l.trim().is_empty()).count() as u32);
+    }
+    // needrestart batch mode: one NEEDRESTART-SVC line per service
+    if let Some(out) = exec::stdout("needrestart", &["-b"]) {
+        return Some(out.lines().filter(|l| l.starts_with("NEEDRESTART-SVC:")).count() as u32);
+    }
+    // checkrestart (debian-goodies): "Found 3 processes using old versions of upgraded files"
+    if let Some(out) = exec::stdout("checkrestart", &[]) {
+        return out.lines().find_map(|line| {
+            let rest = line.trim().strip_prefix("Found ")?;
+            rest.split_whitespace().next()?.parse().ok()
+        });
+    }
+    None
+}
@@ -0,0 +1,271 @@
+// Reindexing changed documents in the backend.
+//
+// Corpus changes (see documents::corpus_changed), from Halbert's own
+// imports, syncs and deletes and from the corpus watcher (see
+// corpus_watch) for everything else, are queued here. Once the
+// first arrives, changes are collected for DEBOUNCE, and everything queued
+// by then goes to the backend as one job per corpus: POST
+// /api/index/documents with the corpus name and the corpus-relative
+// sources, which the backend (re)indexes or drops if they are gone.
+// `reindex_document` sends a single document at once, `reindex_corpus` a
+// whole corpus. A
+// backend whose /api/status doesn't list the `incremental_index`
+// capability gets a full rebuild (POST /api/index/rebuild) instead; the
+// job is named and marked so the UI can say why it takes long. Either way
+// the job result lists exactly which sources were sent and what the
+// backend reports it indexed and removed. The chunk delta it reports is
+// applied to the kept index counts (corpora::IndexCache); without one they
+// are dropped, to be fetched again.
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use crate::app::{AppHandle, Manager, State};
+use crate::backend;
+use crate::corpora::{self, IndexCache};
+use crate::db::Database;
+use crate::documents;
+use crate::error::CommandResult;
+use crate::jobs::{Job, JobManager};
+use crate::settings::{Settings, SettingsStore};
+
+// Changes this close together go into one job
+const DEBOUNCE: Duration = Duration::from_secs(3);
+const CAPABILITY: &str = "incremental_index";
+
+#[derive(Default)]
+pub struct Reindexer {
+    // Sources by corpus
+    pending: Mutex<BTreeMap<String, BTreeSet<String>>>,
+    queued: Condvar,
+}
+
+impl Reindexer {
+    pub fn queue(&self, corpus: &str, sources: &[String]) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.entry(corpus.to_string()).or_default().extend(sources.iter().cloned());
+        self.queued.notify_one();
+    }
+
+    // Blocks until something is queued, then waits out the debounce window
+    fn next_batch(&self) -> BTreeMap<String, BTreeSet<String>> {
+        let pending = self.pending.lock().unwrap();
+        drop(self.queued.wait_while(pending, |p| p.is_empty()).unwrap());
+        std::thread::sleep(DEBOUNCE);
+        std::mem::take(&mut *self.pending.lock().unwrap())
+    }
+}
+
+// `capabilities` is either {"incremental_index": true, ...} or a list of
+// names; anything else means the backend predates incremental updates
+pub fn supports_incremental(status: &serde_json::Value) -> bool {
+    match status.get("capabilities") {
+        Some(serde_json::Value::Object(flags)) => flags.get(CAPABILITY).and_then(|v| v.as_bool()).unwrap_or(false),
+        Some(serde_json::Value::Array(names)) => names.iter().any(|n| n.as_str() == Some(CAPABILITY)),
+        _ => false,
+    }
+}
+
+// What the backend says it did; older backends answer with less
+#[derive(Deserialize)]
+struct IndexResponse {
+    #[serde(default)]
+    indexed: Option<Vec<String>>,
+    #[serde(default)]
+    removed: Vec<String>,
+    #[serde(default)]
+    chunk_delta: Option<i64>,
+}
+
+// No sources rebuilds the whole corpus
+fn submit(
+    jobs: &JobManager,
+    cache: &IndexCache,
+    settings: Settings,
+    corpus: String,
+    sources: Vec<String>,
+) -> CommandResult<Job> {
+    let root = corpora::root(&settings, &corpus)?;
+    let endpoint = backend::endpoint(&settings);
+    let incremental = endpoint
+        .get_json::<serde_json::Value>("/api/status")
+        .map(|status| supports_incremental(&status))?;
+    let name = if sources.is_empty() {
+        format!("Full reindex of corpus {}", corpus)
+    } else if incremental {
+        format!("Reindex {} document(s) in {}", sources.len(), corpus)
+    } else {
+        format!("Full reindex of {} for {} changed document(s)", corpus, sources.len())
+    };
+    let cache = cache.clone();
+    Ok(jobs.spawn(&name, "reindex", move |handle| {
+        let endpoint = backend::endpoint(&settings);
+        let root = root.to_string_lossy();
+        let (path, body) = if sources.is_empty() {
+            ("/api/index/rebuild", json!({ "corpus": corpus, "root": root }))
+        } else if incremental {
+            ("/api/index/documents", json!({ "corpus": corpus, "root": root, "paths": sources }))
+        } else {
+            handle.log("The backend can't update its index incrementally; rebuilding all of it");
+            ("/api/index/rebuild", json!({ "corpus": corpus, "root": root }))
+        };
+        for source in &sources {
+            handle.log(format!("Queued {}", source));
+        }
+        let response: IndexResponse = endpoint.post_json(path, &body).map_err(|e| e.to_string())?;
+        let indexed = response.indexed.unwrap_or_else(|| {
+            let removed: BTreeSet<&String> = response.removed.iter().collect();
+            sources.iter().filter(|s| !removed.contains(s)).cloned().collect()
+        });
+        handle.log(format!("Indexed {}, removed {}", indexed.len(), response.removed.len()));
+        match response.chunk_delta {
+            Some(delta) => {
+                if let Some(chunks) = cache.apply_delta(&settings, &corpus, delta).and_then(|c| c.chunks) {
+                    handle.log(format!("The index of {} now has {} chunks", corpus, chunks));
+                }
+            }
+            None => cache.forget(&settings, &corpus),
+        }
+        let full = !incremental || sources.is_empty();
+        handle.set_result(json!({
+            "corpus": corpus,
+            "mode": if full { "full" } else { "incremental" },
+            "fallback_reason": (!incremental && !sources.is_empty())
+                .then_some("backend has no incremental_index capability"),
+            "sources": sources,
+            "indexed": indexed,
+            "removed": response.removed,
+            "chunk_delta": response.chunk_delta,
+        }));
+        println!("[Halbert] Reindexed {} document(s) in {} ({})", indexed.len(), corpus, path);
+        Ok(())
+    }))
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        let batch = app.state::<Reindexer>().next_batch();
+        let settings = app.state::<SettingsStore>().get();
+        for (corpus, sources) in batch {
+            let sources: Vec<String> = sources.into_iter().collect();
+            if sources.is_empty() {
+                continue;
+            }
+            let (jobs, cache) = (app.state::<JobManager>(), app.state::<IndexCache>());
+            if let Err(e) = submit(&jobs, &cache, settings.clone(), corpus.clone(), sources) {
+                println!("[Halbert] Auto-reindex of {} skipped: {}", corpus, e);
+            }
+        }
+    });
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn reindex_document(
+    jobs: State<'_, JobManager>,
+    cache: State<'_, IndexCache>,
+    settings: State<'_, SettingsStore>,
+    db: State<'_, Database>,
+    doc_id: String,
+) -> CommandResult<Job> {
+    let settings = settings.get();
+    let doc = documents::find_document(&settings, &db, &doc_id)?;
+    submit(&jobs, &cache, settings, doc.corpus, vec![doc.source])
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn reindex_corpus(
+    jobs: State<'_, JobManager>,
+    cache: State<'_, IndexCache>,
+    settings: State<'_, SettingsStore>,
+    corpus: Option<String>,
+) -> CommandResult<Job> {
+    let settings = settings.get();
+    let corpus = corpora::pick(&settings, corpus)?;
+    submit(&jobs, &cache, settings, corpus, Vec::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automation::Gate;
+    use crate::http::{MockServer, Received};
+    use std::time::Instant;
+
+    #[test]
+    fn incremental_support_is_read_from_flags_or_a_list() {
+        assert!(supports_incremental(&json!({ "capabilities": { "incremental_index": true } })));
+        assert!(!supports_incremental(&json!({ "capabilities": { "incremental_index": false } })));
+        assert!(!supports_incremental(&json!({ "capabilities": { "incremental_index": "yes" } })));
+        assert!(supports_incremental(&json!({ "capabilities": ["search", "incremental_index"] })));
+        assert!(!supports_incremental(&json!({ "capabilities": ["search"] })));
+        assert!(!supports_incremental(&json!({ "capabilities": "incremental_index" })));
+        assert!(!supports_incremental(&json!({ "status": "ok" })));
+    }
+
+    // A backend that indexes incrementally when `incremental`, answering
+    // index requests with `answer`
+    fn backend(incremental: bool, answer: serde_json::Value) -> MockServer {
+        MockServer::start(move |request: &Received| match request.path.as_str() {
+            "/api/status" => (200, json!({ "capabilities": { "incremental_index": incremental } })),
+            _ => (200, answer.clone()),
+        })
+    }
+
+    fn run(backend: &MockServer, cache: &IndexCache, sources: &[&str]) -> (Settings, Job) {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = Settings {
+            backend_url: backend.base_url.clone(),
+            corpus_path: Some(dir.path().display().to_string()),
+            ..Settings::default()
+        };
+        let jobs = JobManager::empty(Gate::load(dir.path().join("automation.json")));
+        let sources = sources.iter().map(|s| s.to_string()).collect();
+        let job = submit(&jobs, cache, settings.clone(), corpora::PRIMARY.to_string(), sources).unwrap();
+        let job = jobs.wait_finished(&job.id);
+        assert_eq!(job.status, "completed", "{:?}", job.error);
+        (settings, job)
+    }
+
+    fn counts(chunks: u32) -> corpora::IndexCounts {
+        corpora::IndexCounts {
+            chunks: Some(chunks),
+            index_size_mb: None,
+        }
+    }
+
+    #[test]
+    fn the_chunk_delta_updates_the_kept_stats() {
+        let backend = backend(true, json!({ "indexed": ["a.md", "b.md"], "removed": [], "chunk_delta": 7 }));
+        let cache = IndexCache::default();
+        let settings = Settings {
+            backend_url: backend.base_url.clone(),
+            ..Settings::default()
+        };
+        cache.store(&settings, corpora::PRIMARY, counts(100), Instant::now());
+        let (settings, job) = run(&backend, &cache, &["a.md", "b.md"]);
+        let result = job.result.unwrap();
+        assert_eq!((result["mode"].as_str(), result["chunk_delta"].as_i64()), (Some("incremental"), Some(7)));
+        assert_eq!(cache.get(&settings, corpora::PRIMARY, Instant::now()), Some(counts(107)));
+        let paths: Vec<String> = backend.received().iter().map(|r| r.path.clone()).collect();
+        assert_eq!(paths, ["/api/status", "/api/index/documents"]);
+    }
+
+    #[test]
+    fn without_a_delta_the_kept_stats_are_dropped() {
+        let backend = backend(false, json!({ "removed": ["gone.md"] }));
+        let cache = IndexCache::default();
+        let settings = Settings {
+            backend_url: backend.base_url.clone(),
+            ..Settings::default()
+        };
+        cache.store(&settings, corpora::PRIMARY, counts(100), Instant::now());
+        let (settings, job) = run(&backend, &cache, &["kept.md", "gone.md"]);
+        let result = job.result.unwrap();
+        assert_eq!(result["mode"], "full");
+        assert_eq!(result["indexed"], json!(["kept.md"]));
+        assert_eq!(cache.get(&settings, corpora::PRIMARY, Instant::now()), None);
+        assert_eq!(backend.received().last().unwrap().path, "/api/index/rebuild");
+    }
+}
@@ -0,0 +1,881 @@
+// Dashboard over HTTP, for a glance from a phone or another Halbert.
+//
+// Off unless `remote_access.listen` is set (e.g. "0.0.0.0:8787"). An axum
+// server then serves a bare status page at / and JSON under /api/v1:
+// metrics, metrics/history, jobs, approvals, documents and incidents, each
+// what the command of the same name returns for the active host; a request
+// another Halbert sent (marked with http::HOP_HEADER) is refused rather
+// than passed on to a remote active host. It also
+// answers the routes a host entry is read through (hosts::fetch_*), so a
+// desktop can add this machine as a host and show its core, and
+// /api/v1/commands/<command>, which runs a read-only command for a window
+// whose core this is (see remote_core). Nothing on that listener changes
+// state. Approving and rejecting a request and
+// approving a group, the routes hosts::decide_approval and
+// hosts::approve_group send, are served on a listener of their own at
+// `remote_access.changes_listen` (off unless set), behind a token of their
+// own, along with the rest of the commands such a window sends. That
+// listener speaks plain HTTP, so it only binds to loopback;
+// reaching it from elsewhere takes an SSH tunnel or a TLS proxy in front.
+// Its routes go through the same read-only-mode and consent checks as the
+// window, voting as "remote:<token fingerprint>", so one token is one
+// approver wherever it connects from. Every API call needs
+// `Authorization: Bearer <token>`. generate_remote_access_token and
+// generate_remote_access_changes_token each make a new token for their
+// listener, show it once and keep only its SHA-256 (in the secret store); a
+// token gets `requests_per_minute` calls. A missing or wrong token is
+// logged with the caller's address, audited as remote_access.denied and
+// counted per address in get_remote_access_status. Both servers follow
+// settings: they start, move or stop as soon as their address changes (see
+// settings::announce_change).
+use axum::extract::{ConnectInfo, Path as UrlPath, Query, State as Shared};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use base64::Engine;
+use chacha20poly1305::aead::{KeyInit, OsRng};
+use chacha20poly1305::ChaCha20Poly1305;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+use crate::app::{async_runtime, AppHandle, Manager, State};
+use crate::approvals::{self, ApprovalStore, Approver};
+use crate::audit;
+use crate::automation::Gate;
+use crate::consent::ConsentStore;
+use crate::db::Database;
+use crate::documents;
+use crate::error::{CommandError, CommandResult};
+use crate::hosts::{self, ActiveHost};
+use crate::http::HOP_HEADER;
+use crate::incidents;
+use crate::jobs;
+use crate::readonly;
+use crate::remote_core;
+use crate::sampler;
+use crate::secrets;
+use crate::settings::{Settings, SettingsStore};
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+const NOT_LOOPBACK: &str =
+    "the approve and reject routes only listen on loopback (e.g. 127.0.0.1:8788); tunnel or proxy them with TLS";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteAccessSettings {
+    // host:port to listen on; None keeps the server off
+    pub listen: Option<String>,
+    // host:port for the approve and reject routes; None keeps them off
+    pub changes_listen: Option<String>,
+    pub requests_per_minute: u32,
+}
+
+impl Default for RemoteAccessSettings {
+    fn default() -> Self {
+        RemoteAccessSettings {
+            listen: None,
+            changes_listen: None,
+            requests_per_minute: 120,
+        }
+    }
+}
+
+pub fn validate(settings: &RemoteAccessSettings) -> CommandResult<()> {
+    let parse = |listen: &Option<String>| {
+        listen.as_deref().map(str::parse::<SocketAddr>).transpose().map_err(|_| {
+            let listen = listen.as_deref().unwrap_or_default();
+            CommandError::InvalidInput(format!("remote access address '{}' should look like 0.0.0.0:8787", listen))
+        })
+    };
+    let (reads, changes) = (parse(&settings.listen)?, parse(&settings.changes_listen)?);
+    if changes.is_some_and(|addr| !addr.ip().is_loopback()) {
+        return Err(CommandError::InvalidInput(NOT_LOOPBACK.to_string()));
+    }
+    if reads.is_some() && reads == changes {
+        return Err(CommandError::InvalidInput(
+            "the approve and reject routes need an address of their own".to_string(),
+        ));
+    }
+    if settings.requests_per_minute == 0 {
+        return Err(CommandError::InvalidInput("requests_per_minute must be at least 1".to_string()));
+    }
+    Ok(())
+}
+
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Compares every byte so the time taken says nothing about the stored hash
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+pub fn bearer(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+}
+
+struct Running {
+    addr: SocketAddr,
+    shutdown: oneshot::Sender<()>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DeniedAddress {
+    pub address: String,
+    pub attempts: u64,
+    pub last_at: String,
+}
+
+// The read-only API, or the approve and reject routes
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Surface {
+    Reads,
+    Changes,
+}
+
+impl Surface {
+    const ALL: [Surface; 2] = [Surface::Reads, Surface::Changes];
+
+    fn label(self) -> &'static str {
+        match self {
+            Surface::Reads => "Remote access",
+            Surface::Changes => "Remote changes",
+        }
+    }
+
+    fn token_secret(self) -> &'static str {
+        match self {
+            Surface::Reads => "remote_access_token_sha256",
+            Surface::Changes => "remote_access_changes_token_sha256",
+        }
+    }
+
+    fn token_prefix(self) -> &'static str {
+        match self {
+            Surface::Reads => "hlb_",
+            Surface::Changes => "hlbc_",
+        }
+    }
+
+    fn address(self, settings: &RemoteAccessSettings) -> Option<SocketAddr> {
+        let listen = match self {
+            Surface::Reads => &settings.listen,
+            Surface::Changes => &settings.changes_listen,
+        };
+        let addr: SocketAddr = listen.as_deref()?.parse().ok()?;
+        (self == Surface::Reads || addr.ip().is_loopback()).then_some(addr)
+    }
+}
+
+#[derive(Default)]
+struct Listener {
+    running: Option<Running>,
+    // Why the last start failed, e.g. the port was taken
+    error: Option<String>,
+    token_hash: Option<String>,
+}
+
+#[derive(Default)]
+pub struct RemoteAccess {
+    reads: Mutex<Listener>,
+    changes: Mutex<Listener>,
+    // Token hash -> start of its window and calls in it
+    windows: Mutex<BTreeMap<String, (Instant, u32)>>,
+    denied: Mutex<BTreeMap<String, DeniedAddress>>,
+    served: Mutex<u64>,
+}
+
+enum Refusal {
+    Denied(&'static str),
+    Limited(Duration),
+}
+
+impl RemoteAccess {
+    fn listener(&self, surface: Surface) -> &Mutex<Listener> {
+        match surface {
+            Surface::Reads => &self.reads,
+            Surface::Changes => &self.changes,
+        }
+    }
+
+    // Only the surface's own token opens it
+    fn check(&self, surface: Surface, token: Option<&str>, per_minute: u32) -> Result<(), Refusal> {
+        let Some(token) = token else {
+            return Err(Refusal::Denied("no bearer token"));
+        };
+        let hash = hash_token(token);
+        let stored = self.listener(surface).lock().unwrap().token_hash.clone();
+        if !stored.is_some_and(|stored| same(&stored, &hash)) {
+            return Err(Refusal::Denied("wrong token"));
+        }
+        let mut windows = self.windows.lock().unwrap();
+        let now = Instant::now();
+        let window = windows.entry(hash).or_insert((now, 0));
+        if now.duration_since(window.0) >= RATE_WINDOW {
+            *window = (now, 0);
+        }
+        if window.1 >= per_minute {
+            return Err(Refusal::Limited(RATE_WINDOW.saturating_sub(now.duration_since(window.0))));
+        }
+        window.1 += 1;
+        *self.served.lock().unwrap() += 1;
+        Ok(())
+    }
+
+    fn count_denied(&self, address: &str) {
+        let mut denied = self.denied.lock().unwrap();
+        let entry = denied.entry(address.to_string()).or_insert_with(|| DeniedAddress {
+            address: address.to_string(),
+            attempts: 0,
+            last_at: String::new(),
+        });
+        entry.attempts += 1;
+        entry.last_at = chrono::Utc::now().to_rfc3339();
+    }
+}
+
+fn status_for(error: &CommandError) -> StatusCode {
+    match error {
+        CommandError::NotFound(_) => StatusCode::NOT_FOUND,
+        CommandError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+        CommandError::Conflict(_) => StatusCode::CONFLICT,
+        CommandError::PermissionDenied(_) | CommandError::ReadOnlyMode(_) | CommandError::ConsentRequired { .. } => {
+            StatusCode::FORBIDDEN
+        }
+        CommandError::NotReady(_) | CommandError::HostUnreachable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        CommandError::Remote(_) => StatusCode::BAD_GATEWAY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+fn deny(app: &AppHandle, peer: SocketAddr, path: &str, reason: &str) -> Response {
+    let address = peer.ip().to_string();
+    println!("[Halbert] Remote access to {} denied for {}: {}", path, address, reason);
+    app.state::<RemoteAccess>().count_denied(&address);
+    let detail = serde_json::json!({ "address": address, "path": path, "reason": reason });
+    let actor = format!("remote:{}", address);
+    if let Err(e) = audit::record(&app.state::<Database>(), &actor, "remote_access.denied", path, &detail) {
+        println!("[Halbert] Failed to audit a denied remote access: {}", e);
+    }
+    (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Bearer")], "unauthorized").into_response()
+}
+
+// A read-only route
+async fn serve<T, F>(app: AppHandle, peer: SocketAddr, headers: HeaderMap, path: &str, read: F) -> Response
+where
+    T: Serialize + Send + 'static,
+    F: FnOnce(&AppHandle) -> CommandResult<T> + Send + 'static,
+{
+    answer(app, Surface::Reads, peer, headers, path, read).await
+}
+
+// Checks the token for `surface`, then runs `run` (one of the commands) off
+// the async runtime
+async fn answer<T, F>(app: AppHandle, surface: Surface, peer: SocketAddr, headers: HeaderMap, path: &str, run: F) -> Response
+where
+    T: Serialize + Send + 'static,
+    F: FnOnce(&AppHandle) -> CommandResult<T> + Send + 'static,
+{
+    let settings = app.state::<SettingsStore>().get();
+    match app.state::<RemoteAccess>().check(surface, bearer(&headers), settings.remote_access.requests_per_minute) {
+        Ok(()) => {}
+        Err(Refusal::Denied(reason)) => return deny(&app, peer, path, reason),
+        Err(Refusal::Limited(wait)) => {
+            let retry = wait.as_secs().max(1).to_string();
+            return (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, retry)], "rate limited").into_response();
+        }
+    }
+    if passes_on(&headers, &settings) {
+        println!("[Halbert] Refused {} from {}: it came through another Halbert", path, peer.ip());
+        let error = CommandError::Conflict(
+            "this Halbert is showing another host, and a request that came through one Halbert isn't passed on"
+                .to_string(),
+        );
+        return (StatusCode::LOOP_DETECTED, Json(error)).into_response();
+    }
+    match tokio::task::spawn_blocking(move || run(&app)).await {
+        Ok(Ok(value)) => Json(value).into_response(),
+        Ok(Err(e)) => (status_for(&e), Json(e)).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+// Whether answering would send a request another Halbert made on to the
+// active host. Two Halberts showing each other, or a longer chain, stop at
+// the first hop.
+fn passes_on(headers: &HeaderMap, settings: &Settings) -> bool {
+    headers.contains_key(HOP_HEADER) && matches!(hosts::active_host(settings), ActiveHost::Remote(_))
+}
+
+type Peer = ConnectInfo<SocketAddr>;
+
+async fn get_metrics(Shared(app): Shared<AppHandle>, ConnectInfo(peer): Peer, headers: HeaderMap) -> Response {
+    serve(app, peer, headers, "/api/v1/metrics", |app| {
+        crate::get_system_metrics(app.state(), app.state(), app.state(), app.state(), app.state())
+    })
+    .await
+}
+
+async fn get_metrics_history(Shared(app): Shared<AppHandle>, ConnectInfo(peer): Peer, headers: HeaderMap) -> Response {
+    serve(app, peer, headers, "/api/v1/metrics/history", |app| {
+        Ok(sampler::get_metrics_history(app.state()))
+    })
+    .await
+}
+
+async fn get_jobs(Shared(app): Shared<AppHandle>, ConnectInfo(peer): Peer, headers: HeaderMap) -> Response {
+    serve(app, peer, headers, "/api/v1/jobs", |app| {
+        jobs::get_active_jobs(app.state(), app.state(), app.state(), None, None, None)
+    })
+    .await
+}
+
+async fn get_approvals(Shared(app): Shared<AppHandle>, ConnectInfo(peer): Peer, headers: HeaderMap) -> Response {
+    serve(app, peer, headers, "/api/v1/approvals", |app| {
+        approvals::get_pending_approvals(app.state(), app.state())
+    })
+    .await
+}
+
+async fn get_documents(Shared(app): Shared<AppHandle>, ConnectInfo(peer): Peer, headers: HeaderMap) -> Response {
+    serve(app, peer, headers, "/api/v1/documents", |app| {
+        documents::get_documents(app.state(), app.state(), app.state(), None, None)
+    })
+    .await
+}
+
+async fn get_incidents(Shared(app): Shared<AppHandle>, ConnectInfo(peer): Peer, headers: HeaderMap) -> Response {
+    serve(app, peer, headers, "/api/v1/incidents", |app| {
+        incidents::get_incidents(app.state(), app.state(), None, None)
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct CorpusQuery {
+    corpus: Option<String>,
+}
+
+// The routes hosts::fetch_* read; same data as above, in the shapes a host
+// entry expects
+async fn agent_metrics(Shared(app): Shared<AppHandle>, ConnectInfo(peer): Peer, headers: HeaderMap) -> Response {
+    serve(app, peer, headers, "/api/system/metrics", |app| {
+        crate::get_system_metrics(app.state(), app.state(), app.state(), app.state(), app.state()).map(|t| t.value)
+    })
+    .await
+}
+
+async fn agent_jobs(Shared(app): Shared<AppHandle>, ConnectInfo(peer): Peer, headers: HeaderMap) -> Response {
+    serve(app, peer, headers, "/api/jobs/active", |app| {
+        jobs::get_active_jobs(app.state(), app.state(), app.state(), None, None, None).map(|list| list.items)
+    })
+    .await
+}
+
+async fn agent_approvals(Shared(app): Shared<AppHandle>, ConnectInfo(peer): Peer, headers: HeaderMap) -> Response {
+    serve(app, peer, headers, "/api/approvals/pending", |app| {
+        approvals::pending_requests(&app.state::<ApprovalStore>(), &app.state::<SettingsStore>().get())
+    })
+    .await
+}
+
+async fn agent_memory_stats(
+    Shared(app): Shared<AppHandle>,
+    ConnectInfo(peer): Peer,
+    Query(query): Query<CorpusQuery>,
+    headers: HeaderMap,
+) -> Response {
+    serve(app, peer, headers, "/api/memory/stats", move |app| {
+        crate::memory_stats(&app.state::<SettingsStore>().get(), &app.state(), &app.state(), query.corpus)
+    })
+    .await
+}
+
+async fn agent_documents(
+    Shared(app): Shared<AppHandle>,
+    ConnectInfo(peer): Peer,
+    Query(query): Query<CorpusQuery>,
+    headers: HeaderMap,
+) -> Response {
+    serve(app, peer, headers, "/api/memory/documents", move |app| {
+        documents::get_documents(app.state(), app.state(), app.state(), None, query.corpus).map(|t| t.value.documents)
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct DecisionBody {
+    reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GroupBody {
+    group_id: String,
+}
+
+// Who a call made with the token votes as: the token, not the address it
+// came from
+fn voter(token: &str) -> String {
+    format!("remote:{}", &hash_token(token)[..12])
+}
+
+// The checks the window runs before a command (see readonly::guarded)
+fn admit(app: &AppHandle, command: &str) -> CommandResult<()> {
+    readonly::admit(
+        app.state::<SettingsStore>().mode(),
+        &app.state::<Gate>(),
+        &app.state::<ConsentStore>(),
+        &app.state::<Database>(),
+        command,
+    )
+}
+
+// Runs `command` under the same checks as the window, voting as the
+// token that was checked
+fn change<T, F>(app: &AppHandle, peer: SocketAddr, voter: Option<String>, command: &str, run: F) -> CommandResult<T>
+where
+    F: FnOnce(&Approver, &Settings) -> CommandResult<T>,
+{
+    let settings = app.state::<SettingsStore>();
+    admit(app, command)?;
+    let voter = voter.ok_or_else(|| CommandError::PermissionDenied("no bearer token".to_string()))?;
+    let approver = Approver::remote(voter);
+    let done = run(&approver, &settings.get())?;
+    println!("[Halbert] {} over remote access from {} as {}", command, peer.ip(), approver.identity);
+    Ok(done)
+}
+
+fn decided(message: String) -> serde_json::Value {
+    serde_json::json!({ "success": true, "message": message })
+}
+
+async fn approve(
+    Shared(app): Shared<AppHandle>,
+    ConnectInfo(peer): Peer,
+    UrlPath(request_id): UrlPath<String>,
+    headers: HeaderMap,
+) -> Response {
+    let caller = bearer(&headers).map(voter);
+    answer(app, Surface::Changes, peer, headers, "/api/approvals/approve", move |app| {
+        change(app, peer, caller, "approve_request", |approver, settings| {
+            let (store, jobs, db) = (app.state(), app.state(), app.state());
+            approvals::approve_as(app, &store, &jobs, &db, settings, &request_id, approver)
+        })
+        .map(decided)
+    })
+    .await
+}
+
+async fn reject(
+    Shared(app): Shared<AppHandle>,
+    ConnectInfo(peer): Peer,
+    UrlPath(request_id): UrlPath<String>,
+    headers: HeaderMap,
+    Json(body): Json<DecisionBody>,
+) -> Response {
+    let caller = bearer(&headers).map(voter);
+    answer(app, Surface::Changes, peer, headers, "/api/approvals/reject", move |app| {
+        change(app, peer, caller, "reject_request", |approver, settings| {
+            let reason = body.reason.unwrap_or_default();
+            let (store, db) = (app.state(), app.state());
+            approvals::reject_as(app, &store, &db, settings, &request_id, &reason, approver)
+        })
+        .map(decided)
+    })
+    .await
+}
+
+// The group id goes in the body, as it's free text
+async fn approve_group(
+    Shared(app): Shared<AppHandle>,
+    ConnectInfo(peer): Peer,
+    headers: HeaderMap,
+    Json(body): Json<GroupBody>,
+) -> Response {
+    let caller = bearer(&headers).map(voter);
+    answer(app, Surface::Changes, peer, headers, "/api/approvals/groups/approve", move |app| {
+        change(app, peer, caller, "approve_group", |approver, settings| {
+            let (store, jobs, db) = (app.state(), app.state(), app.state());
+            approvals::approve_group_as(app, &store, &jobs, &db, settings, &body.group_id, approver)
+        })
+    })
+    .await
+}
+
+// A command another window sends its core (see remote_core). The reads
+// listener runs only what read-only mode allows.
+async fn read_command(
+    Shared(app): Shared<AppHandle>,
+    ConnectInfo(peer): Peer,
+    UrlPath(command): UrlPath<String>,
+    headers: HeaderMap,
+    Json(args): Json<serde_json::Value>,
+) -> Response {
+    serve(app, peer, headers, remote_core::COMMANDS_PATH, move |app| {
+        if !readonly::READ_ONLY_COMMANDS.contains(&command.as_str()) {
+            return Err(CommandError::PermissionDenied(format!("{} needs the changes listener and its token", command)));
+        }
+        admit(app, &command)?;
+        remote_core::serve(app, &command, &args, None)
+    })
+    .await
+}
+
+async fn change_command(
+    Shared(app): Shared<AppHandle>,
+    ConnectInfo(peer): Peer,
+    UrlPath(command): UrlPath<String>,
+    headers: HeaderMap,
+    Json(args): Json<serde_json::Value>,
+) -> Response {
+    let caller = bearer(&headers).map(voter);
+    answer(app, Surface::Changes, peer, headers, remote_core::COMMANDS_PATH, move |app| {
+        change(app, peer, caller, &command, |approver, _| remote_core::serve(app, &command, &args, Some(approver)))
+    })
+    .await
+}
+
+// Asks for the token and keeps it for the tab only
+const STATUS_PAGE: &str = r#"<!doctype html>
+<html><head><meta charset="utf-8"><meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Halbert</title>
+<style>body{font-family:system-ui,sans-serif;margin:1.5rem;max-width:32rem}dd{margin:0 0 .75rem}</style>
+</head><body>
+<h1>Halbert</h1>
+<form id="login"><input id="token" type="password" placeholder="Access token" autocomplete="off">
+<button>Show</button></form>
+<dl id="status"></dl>
+<script>
+const get = async (path) => {
+  const r = await fetch('/api/v1/' + path, {headers: {Authorization: 'Bearer ' + sessionStorage.token}});
+  if (!r.ok) throw new Error(r.status + ' ' + r.statusText);
+  return r.json();
+};
+const row = (name, value) => {
+  const dt = document.createElement('dt'), dd = document.createElement('dd');
+  dt.textContent = name; dd.textContent = value;
+  return [dt, dd];
+};
+async function refresh() {
+  const out = document.getElementById('status');
+  try {
+    const [m, a, j] = await Promise.all([get('metrics'), get('approvals'), get('jobs')]);
+    out.replaceChildren(...row('CPU', m.cpu_percent.toFixed(1) + ' %'),
+      ...row('Memory', m.memory_percent.toFixed(1) + ' %'),
+      ...row('Pending approvals', a.length), ...row('Jobs', j.items.length),
+      ...row('Updated', new Date().toLocaleTimeString()));
+  } catch (e) {
+    out.replaceChildren(...row('Error', e.message));
+  }
+}
+document.getElementById('login').onsubmit = (e) => {
+  e.preventDefault();
+  sessionStorage.token = document.getElementById('token').value;
+  refresh();
+};
+if (sessionStorage.token) refresh();
+setInterval(() => sessionStorage.token && refresh(), 10000);
+</script>
+</body></html>
+"#;
+
+async fn status_page() -> Html<&'static str> {
+    Html(STATUS_PAGE)
+}
+
+fn router(app: AppHandle) -> Router {
+    Router::new()
+        .route("/", get(status_page))
+        .route("/api/v1/metrics", get(get_metrics))
+        .route("/api/v1/metrics/history", get(get_metrics_history))
+        .route("/api/v1/jobs", get(get_jobs))
+        .route("/api/v1/approvals", get(get_approvals))
+        .route("/api/v1/documents", get(get_documents))
+        .route("/api/v1/incidents", get(get_incidents))
+        .route("/api/system/metrics", get(agent_metrics))
+        .route("/api/jobs/active", get(agent_jobs))
+        .route("/api/approvals/pending", get(agent_approvals))
+        .route("/api/memory/stats", get(agent_memory_stats))
+        .route("/api/memory/documents", get(agent_documents))
+        .route("/api/v1/commands/:command", post(read_command))
+        .with_state(app)
+}
+
+fn changes_router(app: AppHandle) -> Router {
+    Router::new()
+        .route("/api/approvals/:request_id/approve", post(approve))
+        .route("/api/approvals/:request_id/reject", post(reject))
+        .route("/api/approvals/groups/approve", post(approve_group))
+        .route("/api/v1/commands/:command", post(change_command))
+        .with_state(app)
+}
+
+fn launch(app: &AppHandle, surface: Surface, addr: SocketAddr) -> oneshot::Sender<()> {
+    let (shutdown, stopped) = oneshot::channel::<()>();
+    let app = app.clone();
+    let failed = move |app: &AppHandle, error: String| {
+        app.state::<RemoteAccess>().listener(surface).lock().unwrap().error = Some(error);
+    };
+    async_runtime::spawn(async move {
+        let listener = match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                println!("[Halbert] {} can't listen on {}: {}", surface.label(), addr, e);
+                failed(&app, e.to_string());
+                return;
+            }
+        };
+        println!("[Halbert] {} listening on {}", surface.label(), addr);
+        let router = match surface {
+            Surface::Reads => router(app.clone()),
+            Surface::Changes => changes_router(app.clone()),
+        };
+        let service = router.into_make_service_with_connect_info::<SocketAddr>();
+        let served = axum::serve(listener, service).with_graceful_shutdown(async {
+            let _ = stopped.await;
+        });
+        if let Err(e) = served.await {
+            println!("[Halbert] {} server stopped: {}", surface.label(), e);
+            failed(&app, e.to_string());
+        }
+    });
+    shutdown
+}
+
+// Brings both servers in line with `settings`; a running one is only
+// touched when its address changes
+pub fn apply(app: &AppHandle, settings: &Settings) {
+    let access = app.state::<RemoteAccess>();
+    for surface in Surface::ALL {
+        let wanted = surface.address(&settings.remote_access);
+        let mut listener = access.listener(surface).lock().unwrap();
+        if listener.running.as_ref().map(|r| r.addr) == wanted {
+            continue;
+        }
+        if let Some(old) = listener.running.take() {
+            let _ = old.shutdown.send(());
+            println!("[Halbert] {} on {} stopped", surface.label(), old.addr);
+        }
+        listener.error = None;
+        if surface == Surface::Changes && wanted.is_none() && settings.remote_access.changes_listen.is_some() {
+            listener.error = Some(NOT_LOOPBACK.to_string());
+        }
+        if let Some(addr) = wanted {
+            listener.running = Some(Running {
+                addr,
+                shutdown: launch(app, surface, addr),
+            });
+        }
+    }
+}
+
+pub fn start(app: AppHandle) {
+    let access = app.state::<RemoteAccess>();
+    for surface in Surface::ALL {
+        match secrets::read(surface.token_secret()) {
+            Ok(hash) => access.listener(surface).lock().unwrap().token_hash = hash,
+            Err(e) => println!("[Halbert] Can't read the {} token: {}", surface.label().to_lowercase(), e),
+        }
+    }
+    apply(&app, &app.state::<SettingsStore>().get());
+}
+
+// The only time the token is shown; it replaces any earlier one for the
+// same surface
+fn new_token(access: &RemoteAccess, db: &Database, surface: Surface) -> CommandResult<String> {
+    let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+    let token = format!(
+        "{}{}",
+        surface.token_prefix(),
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(key.as_slice())
+    );
+    let hash = hash_token(&token);
+    secrets::store(surface.token_secret(), &hash)?;
+    let old = access.listener(surface).lock().unwrap().token_hash.replace(hash);
+    if let Some(old) = old {
+        access.windows.lock().unwrap().remove(&old);
+    }
+    let action = match surface {
+        Surface::Reads => "remote_access.token",
+        Surface::Changes => "remote_access.changes_token",
+    };
+    let detail = serde_json::json!({});
+    if let Err(e) = audit::record(db, &audit::local_actor(), action, "remote_access", &detail) {
+        println!("[Halbert] Failed to audit the new {} token: {}", surface.label().to_lowercase(), e);
+    }
+    Ok(token)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn generate_remote_access_token(access: State<'_, RemoteAccess>, db: State<'_, Database>) -> CommandResult<String> {
+    new_token(&access, &db, Surface::Reads)
+}
+
+// The token for the approve and reject routes; the read-only token doesn't
+// open them
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn generate_remote_access_changes_token(
+    access: State<'_, RemoteAccess>,
+    db: State<'_, Database>,
+) -> CommandResult<String> {
+    new_token(&access, &db, Surface::Changes)
+}
+
+#[derive(Serialize)]
+pub struct RemoteAccessStatus {
+    // Address the server is (meant to be) on; None when it's off
+    pub listening: Option<String>,
+    pub error: Option<String>,
+    pub token_set: bool,
+    // The same for the approve and reject routes
+    pub changes_listening: Option<String>,
+    pub changes_error: Option<String>,
+    pub changes_token_set: bool,
+    pub requests_served: u64,
+    // Callers turned away for a missing or wrong token, most attempts first
+    pub denied: Vec<DeniedAddress>,
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_remote_access_status(access: State<'_, RemoteAccess>) -> RemoteAccessStatus {
+    let mut denied: Vec<DeniedAddress> = access.denied.lock().unwrap().values().cloned().collect();
+    denied.sort_by_key(|d| std::cmp::Reverse(d.attempts));
+    let reads = access.reads.lock().unwrap();
+    let changes = access.changes.lock().unwrap();
+    RemoteAccessStatus {
+        listening: reads.running.as_ref().map(|r| r.addr.to_string()),
+        error: reads.error.clone(),
+        token_set: reads.token_hash.is_some(),
+        changes_listening: changes.running.as_ref().map(|r| r.addr.to_string()),
+        changes_error: changes.error.clone(),
+        changes_token_set: changes.token_hash.is_some(),
+        requests_served: *access.served.lock().unwrap(),
+        denied,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_tokens(reads: &str, changes: &str) -> RemoteAccess {
+        let access = RemoteAccess::default();
+        access.reads.lock().unwrap().token_hash = Some(hash_token(reads));
+        access.changes.lock().unwrap().token_hash = Some(hash_token(changes));
+        access
+    }
+
+    #[test]
+    fn each_token_opens_only_its_own_listener() {
+        let access = with_tokens("hlb_reads", "hlbc_changes");
+        assert!(access.check(Surface::Reads, Some("hlb_reads"), 10).is_ok());
+        assert!(access.check(Surface::Changes, Some("hlbc_changes"), 10).is_ok());
+        assert!(matches!(
+            access.check(Surface::Changes, Some("hlb_reads"), 10),
+            Err(Refusal::Denied("wrong token"))
+        ));
+        assert!(matches!(
+            access.check(Surface::Reads, Some("hlbc_changes"), 10),
+            Err(Refusal::Denied("wrong token"))
+        ));
+    }
+
+    #[test]
+    fn changes_stay_shut_without_their_own_token() {
+        let access = RemoteAccess::default();
+        access.reads.lock().unwrap().token_hash = Some(hash_token("hlb_reads"));
+        assert!(matches!(
+            access.check(Surface::Changes, Some("hlb_reads"), 10),
+            Err(Refusal::Denied("wrong token"))
+        ));
+        assert!(matches!(access.check(Surface::Changes, None, 10), Err(Refusal::Denied("no bearer token"))));
+    }
+
+    #[test]
+    fn each_token_has_its_own_rate_window() {
+        let access = with_tokens("hlb_reads", "hlbc_changes");
+        assert!(access.check(Surface::Reads, Some("hlb_reads"), 1).is_ok());
+        assert!(matches!(access.check(Surface::Reads, Some("hlb_reads"), 1), Err(Refusal::Limited(_))));
+        assert!(access.check(Surface::Changes, Some("hlbc_changes"), 1).is_ok());
+    }
+
+    #[test]
+    fn changes_only_listen_on_loopback() {
+        let changes = |addr: &str| RemoteAccessSettings {
+            changes_listen: Some(addr.to_string()),
+            ..RemoteAccessSettings::default()
+        };
+        assert!(validate(&changes("127.0.0.1:8788")).is_ok());
+        assert!(validate(&changes("[::1]:8788")).is_ok());
+        for open in ["0.0.0.0:8788", "192.168.1.20:8788", "[::]:8788"] {
+            assert!(matches!(validate(&changes(open)), Err(CommandError::InvalidInput(_))), "{}", open);
+            // A settings file that skipped validation doesn't open it either
+            assert_eq!(Surface::Changes.address(&changes(open)), None, "{}", open);
+        }
+        let reads = RemoteAccessSettings {
+            listen: Some("0.0.0.0:8787".to_string()),
+            ..RemoteAccessSettings::default()
+        };
+        assert!(Surface::Reads.address(&reads).is_some());
+    }
+
+    #[test]
+    fn a_proxied_request_stops_at_the_first_hop() {
+        let mut settings = Settings::default();
+        settings.hosts.push(hosts::HostEntry {
+            id: "other".to_string(),
+            name: "Other".to_string(),
+            base_url: "http://127.0.0.1:8787".to_string(),
+            transport: Default::default(),
+            ssh: None,
+            mac: None,
+            wol_broadcast: None,
+            changes_url: None,
+        });
+        let mut proxied = HeaderMap::new();
+        proxied.insert(HOP_HEADER, "1".parse().unwrap());
+        // Showing itself, it answers either way
+        assert!(!passes_on(&proxied, &settings));
+        settings.active_host = Some("other".to_string());
+        assert!(passes_on(&proxied, &settings));
+        // A browser or the phone page isn't another Halbert
+        assert!(!passes_on(&HeaderMap::new(), &settings));
+    }
+
+    #[test]
+    fn a_token_votes_as_one_approver() {
+        assert_eq!(voter("hlbc_one"), voter("hlbc_one"));
+        assert_ne!(voter("hlbc_one"), voter("hlbc_two"));
+        assert!(voter("hlbc_one").starts_with("remote:"));
+        // The fingerprint doesn't give the token away
+        assert!(!voter("hlbc_one").contains("one"));
+    }
+
+    #[test]
+    fn the_two_listeners_need_different_addresses() {
+        let settings = |listen: Option<&str>, changes: Option<&str>| RemoteAccessSettings {
+            listen: listen.map(str::to_string),
+            changes_listen: changes.map(str::to_string),
+            ..RemoteAccessSettings::default()
+        };
+        assert!(validate(&settings(Some("0.0.0.0:8787"), Some("127.0.0.1:8788"))).is_ok());
+        assert!(validate(&settings(None, Some("127.0.0.1:8788"))).is_ok());
+        assert!(validate(&settings(Some("127.0.0.1:8787"), Some("127.0.0.1:8787"))).is_err());
+        assert!(validate(&settings(Some("0.0.0.0:8787"), Some("nowhere"))).is_err());
+        assert_eq!(Surface::Changes.address(&settings(None, Some("127.0.0.1:8788"))).map(|a| a.port()), Some(8788));
+        assert_eq!(Surface::Reads.address(&settings(None, Some("127.0.0.1:8788"))), None);
+    }
+}
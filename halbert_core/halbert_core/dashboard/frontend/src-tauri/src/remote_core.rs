@@ -0,0 +1,682 @@
+// Running this window's commands on another Halbert's core.
+//
+// With `core_host` set in settings to a host entry, the window is a client
+// of that host's halbert-agent (or of another desktop serving remote
+// access). `routed` sends every command the frontend invokes, apart from
+// the window's own in LOCAL, to the host's changes endpoint and token (see
+// hosts::set_host_changes) as POST /api/v1/commands/<command>, with the
+// arguments as the frontend gave them, and answers with what the agent
+// answered, its errors included. The agent checks the call as the window
+// would its own (read-only mode, paused automation, consent; see
+// readonly::admit) and runs it through `serve`: SERVED below lists every
+// command it runs that way, and the approvals, which vote as the token
+// (see remote_access), are decided through approvals::*_as. On the
+// read-only listener only READ_ONLY_COMMANDS are served. A chunked answer
+// (see transfers) is sent whole and chunked again on this side. Events the
+// agent emits don't reach the window; paths a command takes are the
+// agent's. get_core_status says which core the window is showing.
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::future::Future;
+use std::time::Duration;
+#[cfg(feature = "gui")]
+use tauri::ipc::{Invoke, InvokeBody};
+
+#[cfg(feature = "gui")]
+use crate::app::Runtime;
+use crate::app::{async_runtime, AppHandle, Manager, State};
+use crate::approvals::{self, Approver};
+use crate::error::{CommandError, CommandResult};
+use crate::hosts::{self, HostEntry};
+use crate::settings::{Settings, SettingsStore};
+use crate::ssh_hosts::Transport;
+use crate::transfers::{self, TransferStore};
+
+pub const COMMANDS_PATH: &str = "/api/v1/commands";
+// A scan or a question to the backend runs longer than a panel's fetch
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(120);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// The window's own commands, run here whichever core it shows: its
+// settings (core_host among them) and host list, what it shows and what it
+// measures about itself, and what it opens or copies on this desktop
+pub const LOCAL: &[&str] = &[
+    "greet",
+    "get_settings",
+    "update_settings",
+    "validate_settings",
+    "list_settings_revisions",
+    "rollback_settings",
+    "list_hosts",
+    "add_host",
+    "remove_host",
+    "set_active_host",
+    "set_host_wake",
+    "set_host_changes",
+    "add_ssh_host",
+    "send_wake_on_lan",
+    "get_core_status",
+    "toggle_widget_window",
+    "get_widget_state",
+    "get_command_stats",
+    "reset_command_stats",
+    "capture_window_screenshot",
+    "generate_diagnostic_bundle",
+    "get_onboarding_state",
+    "complete_onboarding_step",
+    "skip_onboarding",
+    "set_ui_active",
+    "search_actions",
+    "get_action",
+    "get_startup_status",
+    "get_desktop_notify_status",
+    "open_path",
+    "open_job_artifact",
+    "copy_system_report",
+    "get_transfer_chunk",
+    "cancel_transfer",
+];
+
+// Decided as the token that sent them, not this machine's user
+const APPROVALS: &[&str] = &["approve_request", "reject_request", "approve_group"];
+
+// Commands whose answer may be a transfer handle
+const TRANSFERRED: &[&str] = &["get_job_logs", "get_document_content", "preview_configuration_export"];
+
+// The host the window's commands go to, when it isn't this machine. A
+// stale id falls back to this machine, as active_host does.
+pub fn core_host(settings: &Settings) -> Option<HostEntry> {
+    let id = settings.core_host.as_deref()?;
+    settings.hosts.iter().find(|h| h.id == id && h.transport == Transport::Http).cloned()
+}
+
+pub fn check(settings: &Settings) -> CommandResult<()> {
+    let Some(id) = settings.core_host.as_deref() else {
+        return Ok(());
+    };
+    let host = settings
+        .hosts
+        .iter()
+        .find(|h| h.id == id)
+        .ok_or_else(|| CommandError::NotFound(format!("host {}", id)))?;
+    if host.transport == Transport::Ssh {
+        return Err(CommandError::InvalidInput(format!(
+            "{} is reached over SSH, which runs no Halbert core",
+            host.name
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CoreStatus {
+    // None when the window runs the core itself
+    pub host_id: Option<String>,
+    pub name: String,
+    // Where its commands go
+    pub url: Option<String>,
+    // None when not probed
+    pub reachable: Option<bool>,
+}
+
+pub fn status(settings: &Settings, reachable: Option<bool>) -> CoreStatus {
+    match core_host(settings) {
+        None => CoreStatus {
+            host_id: None,
+            name: sysinfo::System::host_name().unwrap_or_else(|| "This machine".to_string()),
+            url: None,
+            reachable: Some(true),
+        },
+        Some(host) => CoreStatus {
+            url: Some(hosts::changes_endpoint(&host).base_url.to_string()),
+            host_id: Some(host.id),
+            name: host.name,
+            reachable,
+        },
+    }
+}
+
+// Any HTTP answer from the command route counts, as for hosts::probe
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_core_status(settings: State<'_, SettingsStore>) -> CommandResult<CoreStatus> {
+    let settings = settings.get();
+    let Some(host) = core_host(&settings) else {
+        return Ok(status(&settings, None));
+    };
+    let probe = move || hosts::changes_endpoint(&host).probe(COMMANDS_PATH, PROBE_TIMEOUT).is_ok();
+    let reachable = tokio::task::spawn_blocking(probe).await.unwrap_or(false);
+    Ok(status(&settings, Some(reachable)))
+}
+
+// Sends `command` to the host's agent; an error it answers with comes back
+// as that error
+pub fn call(host: &HostEntry, command: &str, args: &Value) -> CommandResult<Value> {
+    let path = format!("{}/{}", COMMANDS_PATH, command);
+    hosts::changes_endpoint(host).post_command(&path, args, COMMAND_TIMEOUT)
+}
+
+// What the frontend gets back: the agent's answer, with a large one handed
+// over in chunks from this side
+fn relay(store: &TransferStore, command: &str, value: Value) -> CommandResult<Value> {
+    if !TRANSFERRED.contains(&command) {
+        return Ok(value);
+    }
+    json(transfers::respond(store, value)?)
+}
+
+// Wraps the invoke handler (see run). With a core_host set, a command
+// outside LOCAL goes to its agent on a blocking thread and skips the
+// window's own checks, which the agent runs against its own settings.
+#[cfg(feature = "gui")]
+pub fn routed<R, F>(handler: F) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static
+where
+    R: Runtime,
+    F: Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+{
+    move |invoke: Invoke<R>| {
+        let command = invoke.message.command().to_string();
+        if LOCAL.contains(&command.as_str()) || !crate::COMMANDS.contains(&command.as_str()) {
+            return handler(invoke);
+        }
+        let app = invoke.message.webview().app_handle().clone();
+        let Some(host) = core_host(&app.state::<SettingsStore>().get()) else {
+            return handler(invoke);
+        };
+        let InvokeBody::Json(args) = invoke.message.payload().clone() else {
+            invoke.resolver.reject(CommandError::NotSupported(format!(
+                "{} was sent raw bytes, which aren't passed on to {}",
+                command, host.name
+            )));
+            return true;
+        };
+        let resolver = invoke.resolver;
+        async_runtime::spawn_blocking(move || {
+            let answer = call(&host, &command, &args).and_then(|value| relay(&app.state(), &command, value));
+            match answer {
+                Ok(value) => resolver.resolve(value),
+                Err(e) => resolver.reject(e),
+            }
+        });
+        true
+    }
+}
+
+fn json<T: Serialize>(value: T) -> CommandResult<Value> {
+    serde_json::to_value(value).map_err(|e| CommandError::Internal(format!("failed to encode response: {}", e)))
+}
+
+fn camel_case(name: &str) -> String {
+    let mut parts = name.split('_');
+    let first = parts.next().unwrap_or_default().to_string();
+    parts.fold(first, |mut key, part| {
+        let mut chars = part.chars();
+        if let Some(c) = chars.next() {
+            key.push(c.to_ascii_uppercase());
+            key.push_str(chars.as_str());
+        }
+        key
+    })
+}
+
+// Tauri hands a command its arguments by their camelCase names; a missing
+// one is null, which an Option reads as None
+fn arg<T: DeserializeOwned>(args: &Value, name: &str) -> CommandResult<T> {
+    let key = camel_case(name);
+    let value = args.get(&key).cloned().unwrap_or(Value::Null);
+    serde_json::from_value(value).map_err(|e| CommandError::InvalidInput(format!("argument {}: {}", key, e)))
+}
+
+// A command in the table returns a CommandResult or a plain value, from a
+// plain or an async fn; one form of entry covers all four. Each call picks
+// its tag by how many references it takes to reach a `kind` impl, fewest
+// first, so the most specific one that applies wins. An async command runs
+// to the end here, on the blocking thread the request is answered on.
+struct Returned<T>(T);
+
+struct AsyncResult;
+struct AsyncValue;
+struct SyncResult;
+struct SyncValue;
+
+trait AsyncResultKind {
+    fn kind(&self) -> AsyncResult {
+        AsyncResult
+    }
+}
+impl<T: Serialize, F: Future<Output = CommandResult<T>>> AsyncResultKind for &&Returned<&F> {}
+
+trait AsyncValueKind {
+    fn kind(&self) -> AsyncValue {
+        AsyncValue
+    }
+}
+impl<T: Serialize, F: Future<Output = T>> AsyncValueKind for &&&Returned<&F> {}
+
+trait SyncResultKind {
+    fn kind(&self) -> SyncResult {
+        SyncResult
+    }
+}
+impl<T: Serialize> SyncResultKind for &Returned<&CommandResult<T>> {}
+
+trait SyncValueKind {
+    fn kind(&self) -> SyncValue {
+        SyncValue
+    }
+}
+impl<T: Serialize> SyncValueKind for Returned<&T> {}
+
+impl AsyncResult {
+    fn json<T: Serialize, F: Future<Output = CommandResult<T>>>(self, returned: F) -> CommandResult<Value> {
+        SyncResult.json(async_runtime::block_on(returned))
+    }
+}
+
+impl AsyncValue {
+    fn json<T: Serialize, F: Future<Output = T>>(self, returned: F) -> CommandResult<Value> {
+        SyncValue.json(async_runtime::block_on(returned))
+    }
+}
+
+impl SyncResult {
+    fn json<T: Serialize>(self, returned: CommandResult<T>) -> CommandResult<Value> {
+        returned.and_then(json)
+    }
+}
+
+impl SyncValue {
+    fn json<T: Serialize>(self, returned: T) -> CommandResult<Value> {
+        json(returned)
+    }
+}
+
+// Each entry is a command with its parameters: App for the app handle,
+// State for a managed store, and the name of each argument
+macro_rules! served {
+    (@name $name:ident) => {
+        stringify!($name)
+    };
+    (@name $module:ident :: $($rest:tt)+) => {
+        served!(@name $($rest)+)
+    };
+    (@arg $app:ident $args:ident App) => {
+        $app.clone()
+    };
+    (@arg $app:ident $args:ident State) => {
+        $app.state()
+    };
+    (@arg $app:ident $args:ident $name:ident) => {
+        arg($args, stringify!($name))?
+    };
+    ($($($segment:ident)::+ ($($param:ident),* $(,)?)),* $(,)?) => {
+        #[cfg(test)]
+        const SERVED: &[&str] = &[$(served!(@name $($segment)::+)),*];
+
+        fn dispatch(app: &AppHandle, command: &str, args: &Value) -> Option<CommandResult<Value>> {
+            $(
+                if command == served!(@name $($segment)::+) {
+                    let run = || -> CommandResult<Value> {
+                        let returned = crate::$($segment)::+($(served!(@arg app args $param)),*);
+                        (&&&Returned(&returned)).kind().json(returned)
+                    };
+                    return Some(run());
+                }
+            )*
+            None
+        }
+    };
+}
+
+served![
+    session::get_session_info(App, State, State, State),
+    get_system_info(),
+    get_system_metrics(State, State, State, State, State),
+    approvals::get_pending_approvals(State, State),
+    approvals::get_approval_detail(App, request_id, operation_id),
+    approvals::get_approval_history(State, State, limit),
+    approvals::dry_run_approval(App, State, State, request_id),
+    calibration::record_approval_outcome(App, State, request_id, outcome, notes),
+    calibration::get_confidence_report(State),
+    approval_templates::create_approval_template(
+        State, name, task, action, affected_resources, risk_level, linked_job_template, job_params,
+    ),
+    approval_templates::list_approval_templates(State),
+    approval_templates::delete_approval_template(State, template_id),
+    approval_templates::instantiate_approval_template(App, template_id, group_id, depends_on),
+    policy::get_risk_policies(State),
+    policy::set_risk_policies(App, State, rules),
+    policy::test_risk_policy(State, sample_request, rules),
+    jobs::get_active_jobs(State, State, State, status, task_type, sort_by),
+    jobs::get_job(State, State, job_id),
+    jobs::get_job_logs(State, State, job_id, from),
+    artifacts::get_job_artifacts(State, State, job_id),
+    artifacts::delete_job_artifacts(State, State, job_id),
+    automation::set_automation_paused(
+        App, State, State, State, State, paused, reason, also_cancel_running, discard_held,
+    ),
+    automation::get_automation_status(State, State),
+    job_templates::list_job_templates(State),
+    job_templates::create_job_template(
+        State, name, command, args_template, param_schema, timeout, artifacts_dir, risk_level, description,
+        environment, snapshot,
+    ),
+    job_templates::update_job_template(
+        State, name, command, args_template, param_schema, timeout, artifacts_dir, risk_level, description,
+        environment, snapshot,
+    ),
+    job_templates::delete_job_template(State, name),
+    job_templates::set_job_secret(name, value),
+    job_templates::start_job(App, State, State, template, params),
+    backup::configure_backup(App, State, repo_url, password, include_paths, exclude_patterns),
+    backup::run_backup_now(State, State),
+    backup::list_backup_snapshots(State),
+    backup::get_last_backup_status(State),
+    snapshots::list_snapshots(mount),
+    snapshots::create_snapshot(State, mount, label),
+    storage::get_storage_stats(State, State),
+    storage::run_storage_maintenance(App, State, retention),
+    cleanup::plan_cleanup(App, State, State, State, targets, operation_id),
+    cleanup::get_cleanup_plan(State, plan_id),
+    cleanup::get_cleanup_items(State, plan_id, category),
+    cleanup::execute_cleanup(App, State, State, State, plan_id, categories),
+    get_memory_stats(App, corpus),
+    disk_history::get_disk_trend(State, mount_point, days),
+    usage_summary::get_usage_summary(State, State, period),
+    corpora::list_corpora(State),
+    corpora::add_corpus(App, State, name, path),
+    corpora::remove_corpus(App, State, name),
+    documents::get_documents(State, State, State, tags, corpus),
+    documents::search_documents(State, State, query, tags, limit, corpus),
+    retrieval_feedback::submit_retrieval_feedback(App, State, State, query_id, chunk_id, rating, note),
+    retrieval_feedback::get_feedback_summary(State, doc_id),
+    documents::set_document_tags(State, State, State, doc_id, tags),
+    documents::get_tags(State, corpus),
+    documents::get_document_content(State, State, State, doc_id),
+    documents::delete_document(App, State, State, doc_id),
+    reindex::reindex_document(State, State, State, State, doc_id),
+    reindex::reindex_corpus(State, State, State, corpus),
+    preview::render_document_preview(State, State, doc_id, max_bytes),
+    corpus_health::run_corpus_health_check(App, State, State, State, operation_id),
+    corpus_import::import_documents(App, State, State, paths, target_subdir, on_conflict),
+    corpus_sources::add_corpus_source(State, State, kind, url, branch, subdir, auth_ref),
+    corpus_sources::list_corpus_sources(State),
+    corpus_sources::remove_corpus_source(State, source_id),
+    corpus_sources::sync_corpus_source(App, State, source_id),
+    scrape::scrape_manpages(App, State, State, sections, commands),
+    scrape::scrape_command_help(App, State, State, command),
+    corpus_health::get_corpus_health_report(State),
+    selfcheck::run_self_check(App),
+    selfcheck::get_self_check(State, State),
+    selfusage::get_self_usage(State, State, State, State, State, State, State),
+    liveness::get_liveness(State, State),
+    remote_access::get_remote_access_status(State),
+    remote_access::generate_remote_access_token(State, State),
+    remote_access::generate_remote_access_changes_token(State, State),
+    sandbox::get_sandbox_roots(State),
+    collectors::list_profiles(State),
+    collectors::save_profile(State, name),
+    collectors::apply_profile(App, State, State, name),
+    collectors::get_collector_status(State, State),
+    config_transfer::preview_configuration_export(State, State, State, include),
+    config_transfer::export_configuration(State, State, path, include),
+    config_transfer::import_configuration(App, State, State, path, mode, force),
+    user_usage::get_usage_by_user(State, collapse_system),
+    accounts::get_user_accounts(State),
+    gpu::get_gpu_processes(),
+    thermal::get_thermal_status(),
+    sampler::get_metrics_history(State),
+    sampler::get_metric_sparkline(State, metric, seconds, points),
+    containers::get_container_metrics_history(State, container, seconds),
+    alerts::get_alert_rules(State),
+    alerts::set_alert_rules(State, rules),
+    alerts::evaluate_alerts(App, State, State),
+    timesync::get_time_sync_status(State),
+    firewall::get_firewall_status(),
+    certificates::scan_certificates(App),
+    certificates::get_certificate_status(State, State),
+    certificates::add_certificate_target(State, target),
+    certificates::remove_certificate_target(State, State, target),
+    process_tree::get_process_tree(State, State, root_pid),
+    processes::request_kill_process(App, State, State, pid, signal, force),
+    processes::request_renice_process(App, State, State, pid, nice, force),
+    services::request_service_action(App, State, unit, action),
+    reboot::get_reboot_status(),
+    kernel_modules::get_kernel_modules(),
+    changes::get_change_summary(State, since),
+    journal_follow::follow_journal(App, State, unit, priority_max, grep),
+    journal_follow::update_journal_filter(App, State, unit, priority_max, grep),
+    journal_follow::stop_journal_follow(State),
+    incidents::get_incidents(State, State, since, kind),
+    text_feed::get_dashboard_summary_text(App, State),
+    text_feed::get_event_feed(State, State, State, State, State, since, limit),
+    network::get_network_interfaces(),
+    network::get_listening_ports(),
+    connections::get_connections(State, state, sort, limit, resolve_hosts),
+    network::check_network_connectivity(State, target, operation_id),
+    network::get_ipv6_status(target),
+    baselines::create_baseline(State, name),
+    baselines::list_baselines(State),
+    baselines::compare_baseline(State, baseline_id),
+    baselines::delete_baseline(State, baseline_id),
+    packages::get_update_inventory(State, State),
+    cancellation::cancel_operation(State, operation_id),
+    bundles::get_dashboard_bundle(App),
+    bundles::get_memory_panel_bundle(App, corpus),
+    backend::set_backend_token(token),
+    backend::get_backend_token_status(),
+    backend::get_backend_status(State),
+    backend_import::import_backend_history(App, State, State, kinds),
+    backend_latency::get_backend_performance(State, State, window),
+    notifications::add_webhook(State, State, url, events, format),
+    notifications::list_webhooks(State, State),
+    notifications::test_webhook(State, State, id),
+    notifications::remove_webhook(State, State, id),
+    hooks::create_hook(State, State, event, script_path, timeout_s, enabled),
+    hooks::list_hooks(State),
+    hooks::set_hook_enabled(State, State, hook_id, enabled),
+    hooks::delete_hook(State, hook_id),
+    hooks::get_hook_runs(State, hook_id),
+    consent::list_consents(State, State),
+    consent::grant_consent(State, State, capability, remember),
+    consent::deny_consent(State, State, capability, remember),
+    consent::revoke_consent(State, State, capability),
+    conversations::ask_question(App, question, conversation_id),
+    conversations::list_conversations(State, offset, limit),
+    conversations::get_conversation(State, id),
+    conversations::rename_conversation(State, id, title),
+    conversations::delete_conversation(State, id),
+];
+
+fn decide(app: &AppHandle, command: &str, args: &Value, approver: &Approver) -> CommandResult<Value> {
+    let settings = app.state::<SettingsStore>().get();
+    let (store, jobs, db) = (app.state(), app.state(), app.state());
+    match command {
+        "approve_request" => {
+            let request_id: String = arg(args, "request_id")?;
+            json(approvals::approve_as(app, &store, &jobs, &db, &settings, &request_id, approver)?)
+        }
+        "reject_request" => {
+            let (request_id, reason): (String, String) = (arg(args, "request_id")?, arg(args, "reason")?);
+            json(approvals::reject_as(app, &store, &db, &settings, &request_id, &reason, approver)?)
+        }
+        _ => {
+            let group_id: String = arg(args, "group_id")?;
+            json(approvals::approve_group_as(app, &store, &jobs, &db, &settings, &group_id, approver)?)
+        }
+    }
+}
+
+// A transfer handle is swapped for what it stands for
+fn whole(app: &AppHandle, value: Value) -> CommandResult<Value> {
+    let Some(id) = value.get("transfer_id").and_then(Value::as_str) else {
+        return Ok(value);
+    };
+    let data = app
+        .state::<TransferStore>()
+        .take(id)
+        .ok_or_else(|| CommandError::NotFound(format!("transfer {} (it may have expired)", id)))?;
+    serde_json::from_slice(&data).map_err(|e| CommandError::Internal(format!("transfer {} didn't parse: {}", id, e)))
+}
+
+// Runs `command` on this core for another window, once remote_access has
+// checked it. Approvals are decided as `approver`; without one they're
+// refused.
+pub fn serve(app: &AppHandle, command: &str, args: &Value, approver: Option<&Approver>) -> CommandResult<Value> {
+    let value = if APPROVALS.contains(&command) {
+        let approver = approver
+            .ok_or_else(|| CommandError::PermissionDenied(format!("{} needs the changes token", command)))?;
+        decide(app, command, args, approver)?
+    } else {
+        match dispatch(app, command, args) {
+            Some(answer) => answer?,
+            None => return Err(CommandError::NotFound(format!("command {}, which isn't served", command))),
+        }
+    };
+    if TRANSFERRED.contains(&command) {
+        return whole(app, value);
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::MockServer;
+    use serde_json::json;
+
+    fn host(id: &str, base_url: &str, transport: Transport) -> HostEntry {
+        HostEntry {
+            id: id.to_string(),
+            name: "Server".to_string(),
+            base_url: base_url.to_string(),
+            transport,
+            ssh: None,
+            mac: None,
+            wol_broadcast: None,
+            changes_url: None,
+        }
+    }
+
+    fn showing(core_host: &str, hosts: Vec<HostEntry>) -> Settings {
+        Settings {
+            core_host: Some(core_host.to_string()),
+            hosts,
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn every_command_is_served_or_kept_local() {
+        for command in crate::COMMANDS {
+            let places = [SERVED, APPROVALS, LOCAL].iter().filter(|list| list.contains(command)).count();
+            assert_eq!(places, 1, "{} is in {} of SERVED, APPROVALS and LOCAL", command, places);
+        }
+        for command in SERVED.iter().chain(TRANSFERRED) {
+            assert!(crate::COMMANDS.contains(command), "{} is served but not registered", command);
+        }
+    }
+
+    #[test]
+    fn arguments_are_read_by_their_camel_case_names() {
+        assert_eq!(camel_case("also_cancel_running"), "alsoCancelRunning");
+        assert_eq!(camel_case("from"), "from");
+        let args = json!({ "requestId": "req-1", "limit": null });
+        assert_eq!(arg::<String>(&args, "request_id").unwrap(), "req-1");
+        assert_eq!(arg::<Option<u32>>(&args, "limit").unwrap(), None);
+        assert_eq!(arg::<Option<String>>(&args, "reason").unwrap(), None);
+        let missing = arg::<String>(&args, "group_id").unwrap_err();
+        assert!(missing.to_string().contains("groupId"), "{}", missing);
+    }
+
+    #[test]
+    fn only_a_known_http_host_can_run_the_core() {
+        let server = host("core-test-http", "http://10.0.0.2:8787", Transport::Http);
+        let ssh = host("core-test-ssh", "", Transport::Ssh);
+        let settings = showing("core-test-http", vec![server.clone(), ssh]);
+        check(&settings).unwrap();
+        assert_eq!(core_host(&settings).map(|h| h.id), Some(server.id));
+        let status = status(&settings, Some(false));
+        assert_eq!(status.url.as_deref(), Some("http://10.0.0.2:8787"));
+        assert_eq!(status.reachable, Some(false));
+
+        let over_ssh = showing("core-test-ssh", settings.hosts.clone());
+        assert!(matches!(check(&over_ssh), Err(CommandError::InvalidInput(_))));
+        assert!(core_host(&over_ssh).is_none());
+        let unknown = showing("core-test-gone", settings.hosts.clone());
+        assert!(matches!(check(&unknown), Err(CommandError::NotFound(_))));
+        assert_eq!(super::status(&Settings::default(), None).host_id, None);
+    }
+
+    #[test]
+    fn the_agents_answer_and_errors_come_back_as_sent() {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/api/v1/commands/get_job" => (200, json!({ "id": req.body["jobId"] })),
+            "/api/v1/commands/cancel_job" => (403, json!({ "kind": "ReadOnlyMode", "message": "read-only" })),
+            _ => (502, json!("bad gateway")),
+        });
+        let agent = host("core-test-call", &server.base_url, Transport::Http);
+        assert_eq!(call(&agent, "get_job", &json!({ "jobId": "job-7" })).unwrap(), json!({ "id": "job-7" }));
+        let refused = call(&agent, "cancel_job", &json!({})).unwrap_err();
+        assert!(matches!(refused, CommandError::ReadOnlyMode(ref m) if m == "read-only"), "{:?}", refused);
+        let failed = call(&agent, "get_job_logs", &json!({})).unwrap_err();
+        assert!(matches!(failed, CommandError::Remote(ref m) if m.ends_with("HTTP 502")), "{:?}", failed);
+        assert!(server.received().iter().all(|r| r.method == "POST" && r.hop));
+    }
+
+    #[test]
+    fn a_large_transferred_answer_is_chunked_again() {
+        let store = TransferStore::default();
+        let small = relay(&store, "get_job_logs", json!({ "lines": [] })).unwrap();
+        assert_eq!(small, json!({ "lines": [] }));
+        let large = json!({ "lines": ["x".repeat(transfers::THRESHOLD_BYTES)] });
+        let handle = relay(&store, "get_job_logs", large.clone()).unwrap();
+        let id = handle["transfer_id"].as_str().unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&store.take(id).unwrap()).unwrap(), large);
+        assert_eq!(relay(&store, "get_job", large.clone()).unwrap(), large, "only a transferred command's");
+    }
+
+    // The agent's side, on the headless runtime it runs
+    #[cfg(not(feature = "gui"))]
+    fn agent_core(dir: &std::path::Path) -> AppHandle {
+        let app = AppHandle::new(dir.to_path_buf(), dir.to_path_buf());
+        crate::manage_core(&app, std::time::Instant::now(), dir, dir);
+        app.state::<crate::db::Database>().open().unwrap();
+        app
+    }
+
+    #[cfg(not(feature = "gui"))]
+    #[test]
+    fn the_agent_runs_served_commands_of_every_shape() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = agent_core(dir.path());
+        let template = json!({
+            "name": "Restart nginx",
+            "task": "restart",
+            "action": "systemctl restart nginx",
+            "affectedResources": ["nginx.service"],
+            "riskLevel": "low",
+        });
+        let created = serve(&app, "create_approval_template", &template, None).unwrap();
+        assert_eq!(created["affected_resources"], json!(["nginx.service"]));
+        let listed = serve(&app, "list_approval_templates", &json!({}), None).unwrap();
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+
+        let gone = serve(&app, "delete_approval_template", &json!({ "templateId": 999 }), None).unwrap_err();
+        assert!(matches!(gone, CommandError::NotFound(_)), "{:?}", gone);
+        let compared = serve(&app, "compare_baseline", &json!({ "baselineId": 999 }), None).unwrap_err();
+        assert!(matches!(compared, CommandError::NotFound(_)), "{:?}", compared);
+        assert!(serve(&app, "get_kernel_modules", &json!({}), None).unwrap().is_object());
+    }
+
+    #[cfg(not(feature = "gui"))]
+    #[test]
+    fn approvals_need_a_voter_and_local_commands_are_not_served() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = agent_core(dir.path());
+        let unvoted = serve(&app, "approve_request", &json!({ "requestId": "req-1" }), None).unwrap_err();
+        assert!(matches!(unvoted, CommandError::PermissionDenied(_)), "{:?}", unvoted);
+        let local = serve(&app, "update_settings", &json!({ "patch": {} }), None).unwrap_err();
+        assert!(matches!(local, CommandError::NotFound(_)), "{:?}", local);
+    }
+}
@@ -0,0 +1,403 @@
+// Formatted system report for pasting into tickets.
+//
+// Collection and formatting are separate: `build_report` is a pure function
+// of `ReportData` so output only changes when the formatter does.
+use serde::Serialize;
+use sysinfo::System;
+#[cfg(feature = "gui")]
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+#[cfg(feature = "gui")]
+use crate::app::{AppHandle, State};
+use crate::error::{CommandError, CommandResult};
+#[cfg(feature = "gui")]
+use crate::settings::SettingsStore;
+use crate::units::Units;
+
+const TOP_PROCESS_COUNT: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReportFormat {
+    Markdown,
+    Text,
+}
+
+impl ReportFormat {
+    pub fn parse(format: &str) -> CommandResult<Self> {
+        match format.trim().to_ascii_lowercase().as_str() {
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            "text" | "plain" => Ok(ReportFormat::Text),
+            other => Err(CommandError::InvalidInput(format!(
+                "unknown report format '{}' (expected markdown or text)",
+                other
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ReportDisk {
+    pub mount_point: String,
+    pub fs_type: String,
+    pub used_bytes: u64,
+    pub total_bytes: u64,
+    pub usage_percent: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReportProcess {
+    pub pid: u32,
+    pub name: String,
+    pub cpu_percent: f32,
+    pub memory_bytes: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReportData {
+    pub hostname: String,
+    pub os: String,
+    pub kernel: String,
+    pub cpu_model: String,
+    pub cpu_count: usize,
+    pub memory_used_bytes: u64,
+    pub memory_total_bytes: u64,
+    pub disks: Vec<ReportDisk>,
+    pub uptime_seconds: u64,
+    pub top_cpu: Vec<ReportProcess>,
+    pub top_memory: Vec<ReportProcess>,
+    pub pending_updates: Option<usize>,
+    pub units: Units,
+}
+
+#[derive(Serialize)]
+pub struct SystemReport {
+    pub format: String,
+    pub text: String,
+}
+
+pub fn collect_report_data(units: Units) -> ReportData {
+    let mut sys = System::new_all();
+    // Process CPU usage is a delta, so it needs two samples
+    std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+    sys.refresh_all();
+
+    let metrics = crate::local_system_metrics(units);
+    let mut processes: Vec<ReportProcess> = sys
+        .processes()
+        .iter()
+        .map(|(pid, p)| ReportProcess {
+            pid: pid.as_u32(),
+            name: p.name().to_string(),
+            cpu_percent: p.cpu_usage(),
+            memory_bytes: p.memory(),
+        })
+        .collect();
+
+    processes.sort_by(|a, b| b.cpu_percent.total_cmp(&a.cpu_percent));
+    let top_cpu = processes.iter().take(TOP_PROCESS_COUNT).cloned().collect();
+    processes.sort_by_key(|p| std::cmp::Reverse(p.memory_bytes));
+    let top_memory = processes.iter().take(TOP_PROCESS_COUNT).cloned().collect();
+
+    ReportData {
+        hostname: System::host_name().unwrap_or_default(),
+        os: System::long_os_version().unwrap_or_default(),
+        kernel: System::kernel_version().unwrap_or_default(),
+        cpu_model: sys
+            .cpus()
+            .first()
+            .map(|cpu| cpu.brand().trim().to_string())
+            .unwrap_or_default(),
+        cpu_count: sys.cpus().len(),
+        memory_used_bytes: metrics.memory_used_bytes,
+        memory_total_bytes: metrics.memory_total_bytes,
+        disks: metrics
+            .disks
+            .iter()
+            .map(|d| ReportDisk {
+                mount_point: d.mount_point.clone(),
+                fs_type: d.fs_type.clone(),
+                used_bytes: d.used_bytes,
+                total_bytes: d.total_bytes,
+                usage_percent: d.usage_percent,
+            })
+            .collect(),
+        uptime_seconds: metrics.uptime_seconds,
+        top_cpu,
+        top_memory,
+        pending_updates: crate::packages::pending_updates().map(|inv| inv.updates.len()),
+        units,
+    }
+}
+
+pub fn format_uptime(seconds: u64) -> String {
+    let days = seconds / 86_400;
+    let hours = (seconds % 86_400) / 3_600;
+    let minutes = (seconds % 3_600) / 60;
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+fn md_cell(value: &str) -> String {
+    value.replace('|', "\\|")
+}
+
+pub fn build_report(data: &ReportData, format: ReportFormat, redact_hostname: bool) -> String {
+    let hostname = if redact_hostname { "REDACTED" } else { data.hostname.as_str() };
+    let size = |bytes: u64| data.units.format_bytes(bytes);
+    let memory_percent = if data.memory_total_bytes > 0 {
+        data.memory_used_bytes as f64 / data.memory_total_bytes as f64 * 100.0
+    } else {
+        0.0
+    };
+    let summary = [
+        ("Hostname", hostname.to_string()),
+        ("OS", data.os.clone()),
+        ("Kernel", data.kernel.clone()),
+        ("CPU", format!("{} ({} threads)", data.cpu_model, data.cpu_count)),
+        (
+            "Memory",
+            format!(
+                "{} / {} ({:.1}%)",
+                size(data.memory_used_bytes),
+                size(data.memory_total_bytes),
+                memory_percent
+            ),
+        ),
+        ("Uptime", format_uptime(data.uptime_seconds)),
+        (
+            "Pending updates",
+            data.pending_updates
+                .map(|n| n.to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
+        ),
+    ];
+
+    let mut out = String::new();
+    match format {
+        ReportFormat::Markdown => {
+            out.push_str("## System Report\n\n| Field | Value |\n|---|---|\n");
+            for (key, value) in &summary {
+                out.push_str(&format!("| {} | {} |\n", key, md_cell(value)));
+            }
+
+            out.push_str("\n### Disks\n\n| Mount | FS | Used | Total | Usage |\n|---|---|---|---|---|\n");
+            for d in &data.disks {
+                out.push_str(&format!(
+                    "| {} | {} | {} | {} | {:.1}% |\n",
+                    md_cell(&d.mount_point),
+                    md_cell(&d.fs_type),
+                    size(d.used_bytes),
+                    size(d.total_bytes),
+                    d.usage_percent
+                ));
+            }
+
+            out.push_str("\n### Top processes by CPU\n\n| PID | Name | CPU % |\n|---|---|---|\n");
+            for p in &data.top_cpu {
+                out.push_str(&format!("| {} | {} | {:.1} |\n", p.pid, md_cell(&p.name), p.cpu_percent));
+            }
+
+            out.push_str("\n### Top processes by memory\n\n| PID | Name | Memory |\n|---|---|---|\n");
+            for p in &data.top_memory {
+                out.push_str(&format!("| {} | {} | {} |\n", p.pid, md_cell(&p.name), size(p.memory_bytes)));
+            }
+        }
+        ReportFormat::Text => {
+            out.push_str("System Report\n=============\n");
+            for (key, value) in &summary {
+                out.push_str(&format!("{:<16} {}\n", format!("{}:", key), value));
+            }
+
+            out.push_str("\nDisks\n-----\n");
+            for d in &data.disks {
+                out.push_str(&format!(
+                    "{:<24} {:<8} {:>10} / {:>10}  {:>5.1}%\n",
+                    d.mount_point,
+                    d.fs_type,
+                    size(d.used_bytes),
+                    size(d.total_bytes),
+                    d.usage_percent
+                ));
+            }
+
+            out.push_str("\nTop processes by CPU\n--------------------\n");
+            for p in &data.top_cpu {
+                out.push_str(&format!("{:>7}  {:<24} {:>6.1}%\n", p.pid, p.name, p.cpu_percent));
+            }
+
+            out.push_str("\nTop processes by memory\n-----------------------\n");
+            for p in &data.top_memory {
+                out.push_str(&format!("{:>7}  {:<24} {:>10}\n", p.pid, p.name, size(p.memory_bytes)));
+            }
+        }
+    }
+    out
+}
+
+// async so the CPU sampling delay and package query stay off the main thread
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub async fn copy_system_report(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    format: String,
+    redact_hostname: Option<bool>,
+) -> CommandResult<SystemReport> {
+    let report_format = ReportFormat::parse(&format)?;
+    let text = build_report(
+        &collect_report_data(settings.get().units),
+        report_format,
+        redact_hostname.unwrap_or(false),
+    );
+    app.clipboard()
+        .write_text(text.clone())
+        .map_err(|e| CommandError::Internal(format!("failed to write clipboard: {}", e)))?;
+    Ok(SystemReport {
+        format: match report_format {
+            ReportFormat::Markdown => "markdown".to_string(),
+            ReportFormat::Text => "text".to_string(),
+        },
+        text,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: u64 = 1 << 30;
+
+    fn sample() -> ReportData {
+        ReportData {
+            hostname: "build-01".to_string(),
+            os: "Linux 24.04 Ubuntu".to_string(),
+            kernel: "6.8.0-45-generic".to_string(),
+            cpu_model: "AMD Ryzen 7 5800X".to_string(),
+            cpu_count: 16,
+            memory_used_bytes: 6 * GIB,
+            memory_total_bytes: 32 * GIB,
+            disks: vec![
+                ReportDisk {
+                    mount_point: "/".to_string(),
+                    fs_type: "ext4".to_string(),
+                    used_bytes: 120 * GIB,
+                    total_bytes: 480 * GIB,
+                    usage_percent: 25.0,
+                },
+                ReportDisk {
+                    mount_point: "/mnt/a|b".to_string(),
+                    fs_type: "btrfs".to_string(),
+                    used_bytes: 900 * GIB,
+                    total_bytes: 1000 * GIB,
+                    usage_percent: 90.0,
+                },
+            ],
+            uptime_seconds: 3 * 86_400 + 4 * 3_600 + 5 * 60,
+            top_cpu: vec![ReportProcess {
+                pid: 4242,
+                name: "cargo".to_string(),
+                cpu_percent: 97.25,
+                memory_bytes: GIB,
+            }],
+            top_memory: vec![ReportProcess {
+                pid: 77,
+                name: "firefox".to_string(),
+                cpu_percent: 3.0,
+                memory_bytes: 3 * GIB / 2,
+            }],
+            pending_updates: Some(12),
+            units: Units::Binary,
+        }
+    }
+
+    #[test]
+    fn markdown_snapshot() {
+        let expected = r"## System Report
+
+| Field | Value |
+|---|---|
+| Hostname | build-01 |
+| OS | Linux 24.04 Ubuntu |
+| Kernel | 6.8.0-45-generic |
+| CPU | AMD Ryzen 7 5800X (16 threads) |
+| Memory | 6.0 GiB / 32.0 GiB (18.8%) |
+| Uptime | 3d 4h 5m |
+| Pending updates | 12 |
+
+### Disks
+
+| Mount | FS | Used | Total | Usage |
+|---|---|---|---|---|
+| / | ext4 | 120.0 GiB | 480.0 GiB | 25.0% |
+| /mnt/a\|b | btrfs | 900.0 GiB | 1000.0 GiB | 90.0% |
+
+### Top processes by CPU
+
+| PID | Name | CPU % |
+|---|---|---|
+| 4242 | cargo | 97.2 |
+
+### Top processes by memory
+
+| PID | Name | Memory |
+|---|---|---|
+| 77 | firefox | 1.5 GiB |
+";
+        assert_eq!(build_report(&sample(), ReportFormat::Markdown, false), expected);
+    }
+
+    #[test]
+    fn text_snapshot() {
+        let expected = r"System Report
+=============
+Hostname:        build-01
+OS:              Linux 24.04 Ubuntu
+Kernel:          6.8.0-45-generic
+CPU:             AMD Ryzen 7 5800X (16 threads)
+Memory:          6.0 GiB / 32.0 GiB (18.8%)
+Uptime:          3d 4h 5m
+Pending updates: 12
+
+Disks
+-----
+/                        ext4      120.0 GiB /  480.0 GiB   25.0%
+/mnt/a|b                 btrfs     900.0 GiB / 1000.0 GiB   90.0%
+
+Top processes by CPU
+--------------------
+   4242  cargo                      97.2%
+
+Top processes by memory
+-----------------------
+     77  firefox                     1.5 GiB
+";
+        assert_eq!(build_report(&sample(), ReportFormat::Text, false), expected);
+    }
+
+    #[test]
+    fn redaction_and_unknowns() {
+        let mut data = sample();
+        data.pending_updates = None;
+        data.memory_total_bytes = 0;
+        let report = build_report(&data, ReportFormat::Text, true);
+        assert!(!report.contains("build-01"));
+        assert!(report.contains("Hostname:        REDACTED\n"));
+        assert!(report.contains("Pending updates: unknown\n"));
+        assert!(report.contains("(0.0%)"));
+    }
+
+    #[test]
+    fn uptime_and_format_names() {
+        assert_eq!(format_uptime(59), "0m");
+        assert_eq!(format_uptime(3_600), "1h 0m");
+        assert_eq!(format_uptime(86_400 + 61), "1d 0h 1m");
+        assert_eq!(ReportFormat::parse(" MD ").unwrap(), ReportFormat::Markdown);
+        assert_eq!(ReportFormat::parse("plain").unwrap(), ReportFormat::Text);
+        assert!(ReportFormat::parse("html").is_err());
+    }
+}
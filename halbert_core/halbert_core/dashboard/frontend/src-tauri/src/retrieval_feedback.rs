@@ -0,0 +1,345 @@
+// Relevance feedback on the chunks that searches and answers cite.
+//
+// Each search_documents call and each answered question with sources is
+// kept as a retrieval query: a query_id plus the chunk ids it returned (the
+// backend's, or a hash of source and text when it sends none, so the same
+// chunk keeps its id). `submit_retrieval_feedback` takes one rating per
+// chunk per query, -1 (wrong) to 1 (useful); rating again replaces it.
+// Ratings go to the backend's feedback endpoint through the outcome
+// forwarding queue (see calibration). Queries are kept for
+// `retention.retrieval_days`, and feedback on an older one is refused.
+// Ratings outlive their query: `get_feedback_summary` adds them up per
+// document and flags one that is mostly rated wrong, a candidate for
+// corpus_ignore.
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::app::{AppHandle, State};
+use crate::calibration;
+use crate::corpora;
+use crate::db::Database;
+use crate::documents;
+use crate::error::{CommandError, CommandResult};
+use crate::settings::SettingsStore;
+
+pub const MIN_RATING: i8 = -1;
+pub const MAX_RATING: i8 = 1;
+const MAX_NOTE_CHARS: usize = 1000;
+// A document needs this many ratings before it can be flagged
+const FLAG_MIN_RATINGS: u32 = 3;
+// ...and this share of them rated wrong
+const FLAG_BAD_SHARE: f64 = 0.6;
+const RECENT_NOTES: usize = 10;
+
+static QUERY_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetrievedChunk {
+    pub chunk_id: String,
+    pub corpus: String,
+    pub source: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RetrievalFeedback {
+    pub query_id: String,
+    pub chunk_id: String,
+    pub doc_id: String,
+    pub source: String,
+    pub rating: i8,
+    pub note: Option<String>,
+    pub submitted_at: String,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct FeedbackNote {
+    pub rating: i8,
+    pub note: String,
+    pub submitted_at: String,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DocumentFeedback {
+    pub doc_id: String,
+    // None for a document nobody has rated
+    pub source: Option<String>,
+    pub ratings: u32,
+    pub useful: u32,
+    pub neutral: u32,
+    pub wrong: u32,
+    pub mean_rating: Option<f32>,
+    // Consistently rated wrong; worth excluding from the corpus
+    pub flagged: bool,
+    // Newest first
+    pub recent_notes: Vec<FeedbackNote>,
+}
+
+// The backend's id when it sends one
+pub fn chunk_id(given: Option<&str>, source: &str, text: &str) -> String {
+    if let Some(id) = given.map(str::trim).filter(|id| !id.is_empty()) {
+        return id.to_string();
+    }
+    let digest = Sha256::digest(format!("{}\0{}", source, text).as_bytes());
+    let hex: String = digest.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+    format!("chunk_{}", hex)
+}
+
+// Gives every source object of an answer a chunk_id and returns the chunks;
+// sources that aren't objects naming a document can't be rated, and
+// sources that don't name their corpus are from the primary one
+pub fn tag_sources(sources: &mut Value) -> Vec<RetrievedChunk> {
+    let Some(list) = sources.as_array_mut() else {
+        return Vec::new();
+    };
+    let mut chunks = Vec::new();
+    for entry in list.iter_mut() {
+        let Some(object) = entry.as_object_mut() else {
+            continue;
+        };
+        let field = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| object.get(*name).and_then(Value::as_str))
+                .unwrap_or_default()
+                .to_string()
+        };
+        let source = field(&["source", "path"]);
+        if source.is_empty() {
+            continue;
+        }
+        let corpus = Some(field(&["corpus"]))
+            .filter(|c| !c.is_empty())
+            .unwrap_or_else(|| corpora::PRIMARY.to_string());
+        let id = chunk_id(Some(&field(&["chunk_id"])), &source, &field(&["snippet", "text", "content"]));
+        object.insert("chunk_id".to_string(), Value::from(id.clone()));
+        if !chunks.iter().any(|c: &RetrievedChunk| c.chunk_id == id) {
+            chunks.push(RetrievedChunk {
+                chunk_id: id,
+                corpus,
+                source,
+            });
+        }
+    }
+    chunks
+}
+
+pub fn flagged(ratings: u32, wrong: u32) -> bool {
+    ratings >= FLAG_MIN_RATINGS && wrong as f64 / ratings as f64 >= FLAG_BAD_SHARE
+}
+
+fn unrated(doc_id: String, source: Option<String>) -> DocumentFeedback {
+    DocumentFeedback {
+        doc_id,
+        source,
+        ratings: 0,
+        useful: 0,
+        neutral: 0,
+        wrong: 0,
+        mean_rating: None,
+        flagged: false,
+        recent_notes: Vec::new(),
+    }
+}
+
+fn new_query_id(query: &str) -> String {
+    let n = QUERY_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let seed = format!("{}\0{}\0{}\0{}", chrono::Utc::now().to_rfc3339(), std::process::id(), n, query);
+    let digest = Sha256::digest(seed.as_bytes());
+    let hex: String = digest.iter().take(12).map(|b| format!("{:02x}", b)).collect();
+    format!("q_{}", hex)
+}
+
+// `kind` is "search" or "answer"; returns the new query_id
+pub fn record_query(db: &Database, kind: &str, query: &str, chunks: &[RetrievedChunk]) -> CommandResult<String> {
+    let query_id = new_query_id(query);
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO retrieval_queries (query_id, kind, query, asked_at) VALUES (?1, ?2, ?3, ?4)",
+            params![query_id, kind, query, chrono::Utc::now().to_rfc3339()],
+        )?;
+        for (rank, chunk) in chunks.iter().enumerate() {
+            tx.execute(
+                "INSERT OR IGNORE INTO retrieval_results (query_id, chunk_id, corpus, source, rank)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![query_id, chunk.chunk_id, chunk.corpus, chunk.source, rank as i64],
+            )?;
+        }
+        tx.commit()
+    })?;
+    Ok(query_id)
+}
+
+// Ratings the backend hasn't taken yet, for calibration's forwarding pass
+pub fn unforwarded(db: &Database) -> CommandResult<Vec<(i64, Value)>> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT f.id, f.query_id, q.kind, q.query, f.chunk_id, f.doc_id, f.source, f.rating, f.note,
+                    f.submitted_at
+             FROM retrieval_feedback f LEFT JOIN retrieval_queries q ON q.query_id = f.query_id
+             WHERE f.forwarded = 0",
+        )?;
+        let rows = stmt.query_map([], |r| {
+            Ok((
+                r.get::<_, i64>(0)?,
+                json!({
+                    "query_id": r.get::<_, String>(1)?,
+                    "kind": r.get::<_, Option<String>>(2)?,
+                    "query": r.get::<_, Option<String>>(3)?,
+                    "chunk_id": r.get::<_, String>(4)?,
+                    "doc_id": r.get::<_, String>(5)?,
+                    "source": r.get::<_, String>(6)?,
+                    "rating": r.get::<_, i64>(7)?,
+                    "note": r.get::<_, Option<String>>(8)?,
+                    "submitted_at": r.get::<_, String>(9)?,
+                }),
+            ))
+        })?;
+        rows.collect()
+    })
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn submit_retrieval_feedback(
+    app: AppHandle,
+    db: State<'_, Database>,
+    settings: State<'_, SettingsStore>,
+    query_id: String,
+    chunk_id: String,
+    rating: i8,
+    note: Option<String>,
+) -> CommandResult<RetrievalFeedback> {
+    if !(MIN_RATING..=MAX_RATING).contains(&rating) {
+        return Err(CommandError::InvalidInput(format!(
+            "rating must be between {} and {}",
+            MIN_RATING, MAX_RATING
+        )));
+    }
+    let note = note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    if note.as_ref().is_some_and(|n| n.chars().count() > MAX_NOTE_CHARS) {
+        return Err(CommandError::InvalidInput(format!(
+            "note is longer than {} characters",
+            MAX_NOTE_CHARS
+        )));
+    }
+    let days = settings.get().retention.retrieval_days;
+    let asked_at: Option<String> = db.with_conn(|conn| {
+        conn.query_row("SELECT asked_at FROM retrieval_queries WHERE query_id = ?1", [&query_id], |r| r.get(0))
+            .optional()
+    })?;
+    // Maintenance only prunes weekly, so the window is checked here too
+    let expired = days > 0
+        && asked_at.as_deref().is_some_and(|at| {
+            chrono::DateTime::parse_from_rfc3339(at)
+                .is_ok_and(|at| at < chrono::Utc::now() - chrono::Duration::days(days as i64))
+        });
+    if asked_at.is_none() || expired {
+        let window = if days > 0 {
+            format!("the last {} days", days)
+        } else {
+            "queries still on record".to_string()
+        };
+        return Err(CommandError::NotFound(format!(
+            "query {} (feedback is only taken on {})",
+            query_id, window
+        )));
+    }
+    let (corpus, source): (String, String) = db
+        .with_conn(|conn| {
+            conn.query_row(
+                "SELECT corpus, source FROM retrieval_results WHERE query_id = ?1 AND chunk_id = ?2",
+                params![query_id, chunk_id],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .optional()
+        })?
+        .ok_or_else(|| CommandError::NotFound(format!("chunk {} in the results of query {}", chunk_id, query_id)))?;
+    let feedback = RetrievalFeedback {
+        doc_id: documents::document_id(&corpus, &source),
+        query_id,
+        chunk_id,
+        source,
+        rating,
+        note,
+        submitted_at: chrono::Utc::now().to_rfc3339(),
+    };
+    // A changed rating is forwarded again
+    db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO retrieval_feedback (query_id, chunk_id, doc_id, source, rating, note, submitted_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT (query_id, chunk_id) DO UPDATE SET
+                 rating = excluded.rating, note = excluded.note, submitted_at = excluded.submitted_at,
+                 forwarded = 0",
+            params![
+                feedback.query_id,
+                feedback.chunk_id,
+                feedback.doc_id,
+                feedback.source,
+                feedback.rating,
+                feedback.note,
+                feedback.submitted_at
+            ],
+        )
+        .map(|_| ())
+    })?;
+    println!(
+        "[Halbert] Rated chunk {} of query {}: {}",
+        feedback.chunk_id, feedback.query_id, feedback.rating
+    );
+    std::thread::spawn(move || calibration::forward_pending(&app));
+    Ok(feedback)
+}
+
+// Without a doc_id, every rated document, flagged and most often wrong first
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_feedback_summary(db: State<'_, Database>, doc_id: Option<String>) -> CommandResult<Vec<DocumentFeedback>> {
+    let rows: Vec<(String, String, i8, Option<String>, String)> = db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT doc_id, source, rating, note, submitted_at FROM retrieval_feedback
+             WHERE ?1 IS NULL OR doc_id = ?1
+             ORDER BY submitted_at DESC",
+        )?;
+        let rows = stmt.query_map([&doc_id], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)))?;
+        rows.collect()
+    })?;
+    let mut summaries: Vec<DocumentFeedback> = Vec::new();
+    for (id, source, rating, note, submitted_at) in rows {
+        let index = match summaries.iter().position(|s| s.doc_id == id) {
+            Some(index) => index,
+            None => {
+                summaries.push(unrated(id, Some(source)));
+                summaries.len() - 1
+            }
+        };
+        let summary = &mut summaries[index];
+        summary.ratings += 1;
+        match rating {
+            r if r > 0 => summary.useful += 1,
+            0 => summary.neutral += 1,
+            _ => summary.wrong += 1,
+        }
+        if let Some(note) = note {
+            if summary.recent_notes.len() < RECENT_NOTES {
+                summary.recent_notes.push(FeedbackNote {
+                    rating,
+                    note,
+                    submitted_at,
+                });
+            }
+        }
+    }
+    for summary in &mut summaries {
+        summary.mean_rating = Some((summary.useful as f32 - summary.wrong as f32) / summary.ratings as f32);
+        summary.flagged = flagged(summary.ratings, summary.wrong);
+    }
+    if let (Some(id), true) = (doc_id, summaries.is_empty()) {
+        summaries.push(unrated(id, None));
+    }
+    let wrong_share = |s: &DocumentFeedback| s.wrong as f64 / s.ratings.max(1) as f64;
+    summaries.sort_by(|a, b| b.flagged.cmp(&a.flagged).then(wrong_share(b).total_cmp(&wrong_share(a))));
+    Ok(summaries)
+}
@@ -0,0 +1,360 @@
+// Background metrics sampler.
+//
+// Pushes the active host's metrics to the UI as `metrics://update` so the
+// dashboard doesn't have to poll. Failures (e.g. an unreachable remote)
+// go out as `metrics://error` with the host id instead. Local samples are
+// also kept in a bounded in-memory ring for the dashboard's sparklines and
+// stored for period summaries (see usage_summary).
+// While the dashboard is hidden samples are taken at the idle interval and
+// alert rules are evaluated here, since nothing is polling them; points
+// after a stretch like that, or after a suspend, are flagged as gaps.
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::activity::UiActivity;
+use crate::alerts;
+use crate::app::{AppHandle, Emitter, Manager, State};
+use crate::collectors::{self, CollectorRegistry};
+use crate::db::Database;
+use crate::disk_history::DiskHistory;
+use crate::error::{CommandError, CommandResult};
+use crate::hosts::{self, ActiveHost};
+use crate::liveness::Liveness;
+use crate::selfusage::{self, SelfLimiter};
+use crate::settings::SettingsStore;
+use crate::smart::SmartCache;
+use crate::ssh_hosts::{self, SshHosts};
+use crate::usage_summary;
+
+#[derive(Serialize, Clone, Copy)]
+pub struct MetricsPoint {
+    // Unix seconds
+    pub at: i64,
+    pub cpu_percent: f32,
+    pub memory_percent: f32,
+    // More than GAP_INTERVALS normal intervals since the previous point, or
+    // the first point after a resume
+    pub gap: bool,
+}
+
+const GAP_INTERVALS: i64 = 3;
+
+#[derive(Default)]
+pub struct MetricsHistory {
+    points: Mutex<VecDeque<MetricsPoint>>,
+    capacity: Mutex<usize>,
+    // Set on resume; the next point is a gap whatever its timing
+    gap_next: AtomicBool,
+}
+
+impl MetricsHistory {
+    fn push(&self, mut point: MetricsPoint, interval_secs: u64) {
+        let capacity = *self.capacity.lock().unwrap();
+        let mut points = self.points.lock().unwrap();
+        let resumed = self.gap_next.swap(false, Ordering::Relaxed);
+        point.gap = points.back().is_some_and(|last| {
+            resumed || point.at - last.at > interval_secs as i64 * GAP_INTERVALS
+        });
+        points.push_back(point);
+        while points.len() > capacity {
+            points.pop_front();
+        }
+    }
+
+    pub fn mark_gap(&self) {
+        self.gap_next.store(true, Ordering::Relaxed);
+    }
+
+    // Drops the oldest points when shrinking
+    pub fn set_capacity(&self, capacity: usize) {
+        *self.capacity.lock().unwrap() = capacity;
+        let mut points = self.points.lock().unwrap();
+        while points.len() > capacity {
+            points.pop_front();
+        }
+        if points.capacity() > capacity * 2 {
+            points.shrink_to(capacity);
+        }
+    }
+
+    // Also the per-series limit for container history
+    pub fn capacity(&self) -> usize {
+        *self.capacity.lock().unwrap()
+    }
+
+    // The sample closest to `at` (unix seconds), if one is within `window`
+    pub fn nearest(&self, at: i64, window: i64) -> Option<MetricsPoint> {
+        self.points
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| (p.at - at).abs() <= window)
+            .min_by_key(|p| (p.at - at).abs())
+            .copied()
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.points.lock().unwrap().len()
+    }
+
+    // Allocated size of the ring, not just the used part
+    pub fn footprint_bytes(&self) -> usize {
+        self.points.lock().unwrap().capacity() * std::mem::size_of::<MetricsPoint>()
+    }
+}
+
+#[derive(Serialize, Clone)]
+struct SampleError {
+    host_id: String,
+    error: String,
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        let registry = app.state::<CollectorRegistry>();
+        let liveness = app.state::<Liveness>();
+        if !collectors::enabled(&app.state::<SettingsStore>().get(), "metrics") {
+            liveness.park("sampler");
+        }
+        registry.wait_enabled(&app, "metrics");
+        // Applies any history cap before the sample is stored
+        selfusage::enforce(&app);
+        let settings = app.state::<SettingsStore>().get();
+        let host_id = hosts::active_host_id(&settings);
+        let normal = collectors::interval_secs(&settings, "metrics", settings.metrics_interval_secs);
+
+        let sample = registry.run("metrics", || match hosts::active_host(&settings) {
+            ActiveHost::Local => {
+                let mut metrics = crate::local_system_metrics(settings.units);
+                app.state::<DiskHistory>().annotate(&mut metrics);
+                app.state::<SmartCache>().annotate(&mut metrics);
+                let at = chrono::Utc::now().timestamp();
+                app.state::<MetricsHistory>().push(
+                    MetricsPoint {
+                        at,
+                        cpu_percent: metrics.cpu_percent,
+                        memory_percent: metrics.memory_percent,
+                        gap: false,
+                    },
+                    normal,
+                );
+                usage_summary::record_sample(&app, at, metrics.cpu_percent, metrics.memory_percent);
+                Ok(metrics)
+            }
+            ActiveHost::Remote(host) => ssh_hosts::remote_metrics(&app.state::<SshHosts>(), &host, settings.units),
+        });
+        match sample {
+            Ok(metrics) => {
+                let _ = app.emit("metrics://update", &metrics);
+            }
+            Err(e) => {
+                let _ = app.emit(
+                    "metrics://error",
+                    SampleError {
+                        host_id,
+                        error: e.to_string(),
+                    },
+                );
+            }
+        }
+
+        let activity = app.state::<UiActivity>();
+        if !activity.is_active() {
+            alerts::evaluate_and_notify(&app, &settings, &app.state::<Database>());
+        }
+        let interval = activity.interval_secs(normal, settings.idle_metrics_interval_secs);
+        let backoff = app.state::<SelfLimiter>().interval_factor();
+        let pause = Duration::from_secs(interval * backoff);
+        liveness.beat("sampler", pause);
+        activity.sleep(pause);
+    });
+}
+
+// Oldest first; only local samples are kept
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_metrics_history(history: State<'_, MetricsHistory>) -> Vec<MetricsPoint> {
+    history.points.lock().unwrap().iter().copied().collect()
+}
+
+// --- Sparklines ---
+//
+// [start, end) is cut into `points` buckets by integer arithmetic: a sample
+// at t falls in bucket (t - start) * points / (end - start), rounded down,
+// so every second belongs to exactly one bucket and none is skipped or
+// counted twice. Empty buckets stay None rather than being interpolated.
+
+const MAX_SPARKLINE_POINTS: u32 = 1000;
+const MAX_SPARKLINE_SECS: u64 = 31 * 86_400;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct SparkBucket {
+    pub min: f32,
+    pub avg: f32,
+    pub max: f32,
+    pub samples: u32,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Sparkline {
+    pub metric: String,
+    // Unix seconds; `end` is exclusive
+    pub start: i64,
+    pub end: i64,
+    pub bucket_secs: f64,
+    // Exactly `points` entries; null where there were no samples
+    pub buckets: Vec<Option<SparkBucket>>,
+    // Over every sample in the range
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+}
+
+pub fn bucketize(samples: &[(i64, f32)], start: i64, end: i64, points: u32) -> Vec<Option<SparkBucket>> {
+    let mut buckets: Vec<Option<SparkBucket>> = vec![None; points as usize];
+    let span = (end - start) as i128;
+    if span <= 0 || points == 0 {
+        return buckets;
+    }
+    // Running sums, turned into averages at the end
+    let mut sums = vec![0f64; points as usize];
+    for &(at, value) in samples {
+        if at < start || at >= end {
+            continue;
+        }
+        let index = ((at - start) as i128 * points as i128 / span) as usize;
+        sums[index] += value as f64;
+        let bucket = buckets[index].get_or_insert(SparkBucket {
+            min: value,
+            avg: 0.0,
+            max: value,
+            samples: 0,
+        });
+        bucket.min = bucket.min.min(value);
+        bucket.max = bucket.max.max(value);
+        bucket.samples += 1;
+    }
+    for (bucket, sum) in buckets.iter_mut().zip(sums) {
+        if let Some(bucket) = bucket {
+            bucket.avg = (sum / bucket.samples as f64) as f32;
+        }
+    }
+    buckets
+}
+
+pub fn sparkline(metric: &str, samples: &[(i64, f32)], start: i64, end: i64, points: u32) -> Sparkline {
+    let buckets = bucketize(samples, start, end, points);
+    let filled = || buckets.iter().flatten();
+    Sparkline {
+        metric: metric.to_string(),
+        start,
+        end,
+        bucket_secs: (end - start) as f64 / points.max(1) as f64,
+        min: filled().map(|b| b.min).reduce(f32::min),
+        max: filled().map(|b| b.max).reduce(f32::max),
+        buckets,
+    }
+}
+
+// Ranges longer than the in-memory history come back with leading nulls
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_metric_sparkline(
+    history: State<'_, MetricsHistory>,
+    metric: String,
+    seconds: u64,
+    points: u32,
+) -> CommandResult<Sparkline> {
+    let value: fn(&MetricsPoint) -> f32 = match metric.as_str() {
+        "cpu_percent" => |p| p.cpu_percent,
+        "memory_percent" => |p| p.memory_percent,
+        other => {
+            return Err(CommandError::InvalidInput(format!(
+                "unknown metric '{}', expected cpu_percent or memory_percent",
+                other
+            )))
+        }
+    };
+    if !(1..=MAX_SPARKLINE_SECS).contains(&seconds) || !(1..=MAX_SPARKLINE_POINTS).contains(&points) {
+        return Err(CommandError::InvalidInput(format!(
+            "seconds must be between 1 and {} and points between 1 and {}",
+            MAX_SPARKLINE_SECS, MAX_SPARKLINE_POINTS
+        )));
+    }
+    let end = chrono::Utc::now().timestamp() + 1;
+    let start = end - seconds as i64;
+    let samples: Vec<(i64, f32)> = history
+        .points
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|p| p.at >= start)
+        .map(|p| (p.at, value(p)))
+        .collect();
+    Ok(sparkline(&metric, &samples, start, end, points))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn one(value: f32) -> Option<SparkBucket> {
+        Some(SparkBucket {
+            min: value,
+            avg: value,
+            max: value,
+            samples: 1,
+        })
+    }
+
+    #[test]
+    fn the_first_and_last_second_land_in_the_end_buckets() {
+        let buckets = bucketize(&[(1000, 1.0), (1059, 2.0)], 1000, 1060, 6);
+        assert_eq!(buckets, [one(1.0), None, None, None, None, one(2.0)]);
+    }
+
+    #[test]
+    fn samples_outside_the_range_are_dropped() {
+        // `end` is exclusive
+        let buckets = bucketize(&[(999, 5.0), (1060, 5.0), (1030, 3.0)], 1000, 1060, 2);
+        assert_eq!(buckets, [None, one(3.0)]);
+    }
+
+    #[test]
+    fn a_bucket_summarizes_its_samples() {
+        let buckets = bucketize(&[(0, 2.0), (1, 6.0), (2, 4.0), (5, 9.0)], 0, 10, 2);
+        let expected = SparkBucket {
+            min: 2.0,
+            avg: 4.0,
+            max: 6.0,
+            samples: 3,
+        };
+        assert_eq!(buckets, [Some(expected), one(9.0)]);
+    }
+
+    #[test]
+    fn more_points_than_seconds_leaves_gaps_as_null() {
+        let samples: Vec<(i64, f32)> = (0..4).map(|at| (at, at as f32)).collect();
+        let buckets = bucketize(&samples, 0, 4, 10);
+        assert_eq!(buckets.len(), 10);
+        // Second n starts bucket n * 10 / 4
+        let filled: Vec<usize> = buckets.iter().enumerate().filter(|(_, b)| b.is_some()).map(|(i, _)| i).collect();
+        assert_eq!(filled, [0, 2, 5, 7]);
+        assert_eq!(buckets.iter().flatten().map(|b| b.samples).sum::<u32>(), 4);
+    }
+
+    #[test]
+    fn empty_or_backwards_ranges_give_all_null_buckets() {
+        assert_eq!(bucketize(&[], 0, 60, 3), [None, None, None]);
+        assert_eq!(bucketize(&[(10, 1.0)], 60, 0, 2), [None, None]);
+        assert!(bucketize(&[(10, 1.0)], 0, 60, 0).is_empty());
+    }
+
+    #[test]
+    fn the_sparkline_range_covers_every_sample() {
+        let line = sparkline("cpu", &[(0, 5.0), (1, 1.0), (30, 8.0)], 0, 60, 4);
+        assert_eq!((line.min, line.max, line.bucket_secs), (Some(1.0), Some(8.0), 15.0));
+        let empty = sparkline("cpu", &[], 0, 60, 0);
+        assert_eq!((empty.min, empty.max, empty.bucket_secs), (None, None, 60.0));
+    }
+}
@@ -0,0 +1,448 @@
+// Where path arguments may point.
+//
+// Every command that takes a path from the frontend checks it here against
+// a policy that names the roots it may reach (see POLICIES). The path is
+// refused outright if it has a NUL byte, is relative, or starts with two
+// separators (a Windows UNC or device prefix such as \\server\share,
+// \\?\C:\ or //?/ - and implementation-defined on POSIX). It is then
+// resolved one component at a time: existing components are canonicalized,
+// so `..` and symlinks are followed to where they really lead, and once a
+// component doesn't exist the rest is applied lexically, so a file that is
+// about to be created can be checked too. A dangling symlink is refused,
+// since where it leads can change. The result must sit under one of the
+// policy's roots after they are canonicalized as well. `get_sandbox_roots`
+// lists each policy with its roots as they resolve right now.
+use serde::Serialize;
+use std::path::{Component, Path, PathBuf};
+
+use crate::app::State;
+use crate::corpora;
+use crate::error::{CommandError, CommandResult};
+use crate::settings::{Settings, SettingsStore};
+
+// Where removable and extra disks are mounted. A fixed list rather than
+// the mount table, which includes / and system mounts and would be read on
+// every check; other places go in the `mount_roots` setting.
+const MOUNT_ROOTS: &[&str] = &["/media", "/mnt", "/run/media"];
+
+#[derive(Clone, Copy, Debug)]
+pub enum Root {
+    Home,
+    Corpus,
+    // MOUNT_ROOTS and the settings' mount_roots
+    Mounts,
+    Temp,
+    Fixed(&'static str),
+}
+
+impl Root {
+    fn label(&self) -> String {
+        match self {
+            Root::Home => "home".to_string(),
+            Root::Corpus => "corpus".to_string(),
+            Root::Mounts => "mounts".to_string(),
+            Root::Temp => "temp".to_string(),
+            Root::Fixed(path) => path.to_string(),
+        }
+    }
+
+    // Unconfigured or missing roots resolve to nothing
+    fn paths(&self, settings: &Settings) -> Vec<PathBuf> {
+        match self {
+            Root::Home => std::env::var("HOME").map(PathBuf::from).into_iter().collect(),
+            Root::Corpus => corpora::roots(settings),
+            Root::Mounts => MOUNT_ROOTS
+                .iter()
+                .map(PathBuf::from)
+                .chain(settings.mount_roots.iter().map(|root| PathBuf::from(root.trim())))
+                .filter(|p| p.is_absolute() && p.parent().is_some())
+                .collect(),
+            Root::Temp => vec![std::env::temp_dir()],
+            Root::Fixed(path) => vec![PathBuf::from(path)],
+        }
+    }
+}
+
+pub struct Policy {
+    pub commands: &'static [&'static str],
+    pub argument: &'static str,
+    pub roots: &'static [Root],
+}
+
+impl Policy {
+    pub fn roots(&self, settings: &Settings) -> Vec<PathBuf> {
+        self.roots.iter().flat_map(|r| r.paths(settings)).collect()
+    }
+
+    pub fn validate(&self, settings: &Settings, path: &Path) -> CommandResult<CanonicalPath> {
+        validate_path(path, &self.roots(settings))
+    }
+}
+
+pub const OPEN_PATH: Policy = Policy {
+    commands: &["open_path"],
+    argument: "path",
+    roots: &[Root::Home, Root::Corpus, Root::Mounts],
+};
+
+pub const CORPUS_DOCUMENT: Policy = Policy {
+    commands: &["render_document_preview", "delete_document"],
+    argument: "doc_id (its source)",
+    roots: &[Root::Corpus],
+};
+
+pub const IMPORT_SOURCE: Policy = Policy {
+    commands: &["import_documents"],
+    argument: "paths",
+    roots: &[Root::Home, Root::Mounts, Root::Temp],
+};
+
+pub const IMPORT_TARGET: Policy = Policy {
+    commands: &["import_documents", "sync_corpus_source"],
+    argument: "target_subdir, and a corpus source's folder",
+    roots: &[Root::Corpus],
+};
+
+pub const CONFIG_FILE: Policy = Policy {
+    commands: &["export_configuration", "import_configuration"],
+    argument: "path",
+    roots: &[Root::Home, Root::Mounts],
+};
+
+pub const HOOK_SCRIPT: Policy = Policy {
+    commands: &["create_hook", "set_hook_enabled"],
+    argument: "script_path",
+    roots: &[Root::Home, Root::Fixed("/usr/local/bin"), Root::Fixed("/opt"), Root::Fixed("/etc/halbert")],
+};
+
+pub const SSH_KEY: Policy = Policy {
+    commands: &["add_ssh_host", "add_corpus_source", "sync_corpus_source"],
+    argument: "key_path, and the key an auth_ref names",
+    roots: &[Root::Home, Root::Mounts],
+};
+
+pub const CORPUS_PATH: Policy = Policy {
+    commands: &["update_settings", "import_configuration", "complete_onboarding_step", "add_corpus"],
+    argument: "corpus_path, and a named corpus's path",
+    roots: &[Root::Home, Root::Mounts],
+};
+
+pub const JOB_ARTIFACT: Policy = Policy {
+    commands: &["open_job_artifact", "delete_job_artifacts"],
+    argument: "artifact_id (its path), and the artifacts directory a template names",
+    roots: &[Root::Home, Root::Mounts, Root::Temp, Root::Fixed("/var/tmp")],
+};
+
+pub const CLEANUP_PATH: Policy = Policy {
+    commands: &["plan_cleanup", "execute_cleanup"],
+    argument: "targets (each pattern's folder), and every file deleted",
+    roots: &[
+        Root::Home,
+        Root::Temp,
+        Root::Fixed("/var/tmp"),
+        Root::Fixed("/var/log"),
+        Root::Fixed("/var/cache"),
+    ],
+};
+
+pub const POLICIES: &[&Policy] = &[
+    &OPEN_PATH,
+    &CORPUS_DOCUMENT,
+    &IMPORT_SOURCE,
+    &IMPORT_TARGET,
+    &CONFIG_FILE,
+    &HOOK_SCRIPT,
+    &SSH_KEY,
+    &CORPUS_PATH,
+    &JOB_ARTIFACT,
+    &CLEANUP_PATH,
+];
+
+// A path that passed validate_path: absolute, free of `.`, `..` and
+// symlinks up to the first component that doesn't exist yet
+#[derive(Clone, Debug, PartialEq)]
+pub struct CanonicalPath(PathBuf);
+
+impl CanonicalPath {
+    pub fn as_path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn into_path_buf(self) -> PathBuf {
+        self.0
+    }
+}
+
+impl AsRef<Path> for CanonicalPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+// What is refused before the path is looked at on disk
+pub fn check_input(path: &Path) -> CommandResult<()> {
+    let text = path.to_string_lossy();
+    if path.as_os_str().as_encoded_bytes().contains(&0) {
+        return Err(CommandError::InvalidInput("path contains a NUL byte".to_string()));
+    }
+    let separators = text.chars().take(2).filter(|c| matches!(c, '/' | '\\')).count();
+    if separators == 2 || path.components().any(|c| matches!(c, Component::Prefix(_))) {
+        return Err(CommandError::InvalidInput(format!("'{}' is a network or device path", text)));
+    }
+    if !path.is_absolute() {
+        return Err(CommandError::InvalidInput(format!("'{}' is not an absolute path", text)));
+    }
+    Ok(())
+}
+
+// Canonical as far as the path exists, then lexical; popping past the
+// missing part carries on in the canonical part, where `..` is the real
+// parent
+pub fn resolve(path: &Path) -> CommandResult<PathBuf> {
+    let mut resolved = PathBuf::new();
+    let mut missing: usize = 0;
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) => {
+                return Err(CommandError::InvalidInput(format!("'{}' is a network or device path", path.display())))
+            }
+            Component::RootDir => resolved.push(component),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+                missing = missing.saturating_sub(1);
+            }
+            Component::Normal(name) => {
+                resolved.push(name);
+                if missing > 0 {
+                    missing += 1;
+                    continue;
+                }
+                match std::fs::symlink_metadata(&resolved) {
+                    Ok(_) => {
+                        resolved = std::fs::canonicalize(&resolved).map_err(|e| {
+                            CommandError::PermissionDenied(format!("{} is a dangling link: {}", resolved.display(), e))
+                        })?
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => missing = 1,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+pub fn validate_path(path: &Path, allowed_roots: &[PathBuf]) -> CommandResult<CanonicalPath> {
+    check_input(path)?;
+    let resolved = resolve(path)?;
+    // Compare canonical forms, e.g. when $HOME is behind a symlink. A root
+    // that resolves to / would allow everything, so it allows nothing.
+    let inside = allowed_roots
+        .iter()
+        .filter_map(|root| std::fs::canonicalize(root).ok())
+        .filter(|root| root.parent().is_some())
+        .any(|root| resolved.starts_with(root));
+    if !inside {
+        return Err(CommandError::PermissionDenied(format!(
+            "{} is outside the folders this command may use",
+            resolved.display()
+        )));
+    }
+    Ok(CanonicalPath(resolved))
+}
+
+// For the `mount_roots` setting: absolute, no `..`, and not / itself
+pub fn check_mount_roots(roots: &[String]) -> CommandResult<()> {
+    for root in roots {
+        let path = Path::new(root.trim());
+        check_input(path)?;
+        if path.components().any(|c| c == Component::ParentDir) {
+            return Err(CommandError::InvalidInput(format!("mount root '{}' contains '..'", root)));
+        }
+        let resolved = resolve(path)?;
+        if resolved.parent().is_none() {
+            return Err(CommandError::InvalidInput(format!("mount root '{}' is the whole filesystem", root)));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Clone)]
+pub struct SandboxRoot {
+    pub kind: String,
+    // Canonical; empty when the root isn't configured or doesn't exist
+    pub paths: Vec<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SandboxPolicy {
+    pub commands: Vec<String>,
+    pub argument: String,
+    pub roots: Vec<SandboxRoot>,
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_sandbox_roots(settings: State<'_, SettingsStore>) -> Vec<SandboxPolicy> {
+    let settings = settings.get();
+    POLICIES
+        .iter()
+        .map(|policy| SandboxPolicy {
+            commands: policy.commands.iter().map(|c| c.to_string()).collect(),
+            argument: policy.argument.to_string(),
+            roots: policy
+                .roots
+                .iter()
+                .map(|root| SandboxRoot {
+                    kind: root.label(),
+                    paths: root
+                        .paths(&settings)
+                        .into_iter()
+                        .filter_map(|p| std::fs::canonicalize(p).ok())
+                        .map(|p| p.display().to_string())
+                        .collect(),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+// Symlinks and byte paths make these unix-only
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::symlink;
+
+    // A canonical allowed root with a file in it, and a sibling outside it
+    struct Tree {
+        _dir: tempfile::TempDir,
+        root: PathBuf,
+        outside: PathBuf,
+    }
+
+    fn tree() -> Tree {
+        let dir = tempfile::tempdir().unwrap();
+        let base = std::fs::canonicalize(dir.path()).unwrap();
+        let (root, outside) = (base.join("allowed"), base.join("outside"));
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(root.join("docs/notes.txt"), "notes").unwrap();
+        std::fs::write(outside.join("secret.txt"), "secret").unwrap();
+        Tree { _dir: dir, root, outside }
+    }
+
+    fn check(tree: &Tree, path: impl AsRef<Path>) -> CommandResult<PathBuf> {
+        validate_path(path.as_ref(), std::slice::from_ref(&tree.root)).map(CanonicalPath::into_path_buf)
+    }
+
+    fn denied(result: CommandResult<PathBuf>) -> bool {
+        matches!(result, Err(CommandError::PermissionDenied(_)))
+    }
+
+    fn invalid(result: CommandResult<PathBuf>) -> bool {
+        matches!(result, Err(CommandError::InvalidInput(_)))
+    }
+
+    #[test]
+    fn paths_inside_the_root_resolve_to_their_canonical_form() {
+        let tree = tree();
+        let notes = tree.root.join("docs/notes.txt");
+        assert_eq!(check(&tree, &notes).unwrap(), notes);
+        assert_eq!(check(&tree, tree.root.join("docs/./../docs/notes.txt")).unwrap(), notes);
+        // Not created yet, so checked lexically past docs/
+        assert_eq!(check(&tree, tree.root.join("docs/new/report.txt")).unwrap(), tree.root.join("docs/new/report.txt"));
+    }
+
+    #[test]
+    fn dot_dot_cannot_climb_out() {
+        let tree = tree();
+        assert!(denied(check(&tree, tree.root.join("../outside/secret.txt"))));
+        assert!(denied(check(&tree, tree.root.join("docs/../../outside"))));
+        assert!(denied(check(&tree, tree.root.join("docs/../../../../../../etc/passwd"))));
+        // Through a part that doesn't exist yet
+        assert!(denied(check(&tree, tree.root.join("missing/../../outside/secret.txt"))));
+        assert!(denied(check(&tree, tree.root.join("a/b/../../../outside"))));
+    }
+
+    #[test]
+    fn symlinks_are_judged_by_where_they_lead() {
+        let tree = tree();
+        symlink(&tree.outside, tree.root.join("escape")).unwrap();
+        symlink(tree.root.join("docs"), tree.root.join("shortcut")).unwrap();
+        symlink("/etc/passwd", tree.root.join("passwd")).unwrap();
+        assert!(denied(check(&tree, tree.root.join("escape/secret.txt"))));
+        assert!(denied(check(&tree, tree.root.join("escape/new-file"))));
+        assert!(denied(check(&tree, tree.root.join("passwd"))));
+        assert_eq!(check(&tree, tree.root.join("shortcut/notes.txt")).unwrap(), tree.root.join("docs/notes.txt"));
+        // `..` after a link is the link target's parent
+        assert!(denied(check(&tree, tree.root.join("shortcut/../../outside"))));
+        assert!(denied(check(&tree, tree.root.join("escape/../allowed/../outside"))));
+    }
+
+    #[test]
+    fn dangling_and_looping_links_are_refused() {
+        let tree = tree();
+        symlink(tree.root.join("nowhere"), tree.root.join("dangling")).unwrap();
+        symlink(tree.root.join("loop"), tree.root.join("loop")).unwrap();
+        assert!(denied(check(&tree, tree.root.join("dangling"))));
+        assert!(denied(check(&tree, tree.root.join("dangling/file"))));
+        assert!(denied(check(&tree, tree.root.join("loop"))));
+    }
+
+    #[test]
+    fn double_separators_as_network_or_device_paths() {
+        let tree = tree();
+        let doubled = format!("/{}", tree.root.join("docs/notes.txt").display());
+        for path in [doubled.as_str(), "//server/share/file", r"\\server\share\file", r"\\?\C:\Windows", "//?/C:/Windows", r"/\etc"] {
+            assert!(invalid(check(&tree, path)), "{}", path);
+        }
+        // Doubled separators later on are just one
+        let inner = format!("{}//docs///notes.txt", tree.root.display());
+        assert_eq!(check(&tree, inner).unwrap(), tree.root.join("docs/notes.txt"));
+    }
+
+    #[test]
+    fn nul_bytes_and_relative_paths_are_refused() {
+        let tree = tree();
+        let mut with_nul = tree.root.join("docs/notes.txt").as_os_str().as_bytes().to_vec();
+        with_nul.extend_from_slice(b"\0.png");
+        assert!(invalid(check(&tree, OsStr::from_bytes(&with_nul))));
+        assert!(invalid(check(&tree, "docs/notes.txt")));
+        assert!(invalid(check(&tree, "../etc/passwd")));
+        assert!(invalid(check(&tree, "")));
+    }
+
+    #[test]
+    fn a_root_that_resolves_to_slash_allows_nothing() {
+        let tree = tree();
+        symlink("/", tree.root.join("top")).unwrap();
+        for root in [PathBuf::from("/"), tree.root.join("top"), PathBuf::from("/tmp/..")] {
+            let result = validate_path(&tree.outside.join("secret.txt"), std::slice::from_ref(&root));
+            assert!(matches!(result, Err(CommandError::PermissionDenied(_))), "{}", root.display());
+        }
+    }
+
+    #[test]
+    fn mounts_are_the_fixed_roots_and_the_configured_ones() {
+        let settings = Settings {
+            mount_roots: vec![" /data ".to_string(), "/".to_string(), "relative".to_string()],
+            ..Settings::default()
+        };
+        let expected: Vec<PathBuf> = ["/media", "/mnt", "/run/media", "/data"].iter().map(PathBuf::from).collect();
+        assert_eq!(Root::Mounts.paths(&settings), expected);
+    }
+
+    #[test]
+    fn mount_roots_must_be_real_subfolders() {
+        assert!(check_mount_roots(&["/data".to_string(), "/srv/disks".to_string()]).is_ok());
+        for root in ["/", "//nas/share", "data", "/mnt/..", "/srv/../..", "/srv/\0"] {
+            assert!(check_mount_roots(&[root.to_string()]).is_err(), "{}", root);
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let top = dir.path().join("top");
+        symlink("/", &top).unwrap();
+        assert!(check_mount_roots(&[top.display().to_string()]).is_err());
+    }
+}
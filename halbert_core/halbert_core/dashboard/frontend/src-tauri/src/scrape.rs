@@ -0,0 +1,313 @@
+// Man pages and --help output scraped into the RAG corpus.
+//
+// Pages are found by walking each manpath directory's man<section>/ (the
+// locale subdirectories are skipped), rendered to plain text with mandoc,
+// or `man -l` where mandoc isn't installed, and written to
+// scraped/man/<command>.<section>.txt under the corpus root. Each file
+// starts with a small metadata header; a page whose source mtime matches
+// the header of its existing copy is not rendered again. Tools without a
+// man page can have their --help output captured to scraped/help/ instead.
+// Everything runs as a job and shells out with an argv, never a shell.
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::app::{AppHandle, State};
+use crate::corpora;
+use crate::documents;
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::jobs::{Job, JobHandle, JobManager};
+use crate::settings::SettingsStore;
+
+pub const MAN_DIR: &str = "scraped/man";
+pub const HELP_DIR: &str = "scraped/help";
+const DEFAULT_MANPATH: &str = "/usr/share/man";
+const COMPRESSED_SUFFIXES: &[&str] = &[".gz", ".bz2", ".xz", ".zst", ".lzma"];
+const RENDER_WIDTH: &str = "80";
+const HELP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManPage {
+    pub command: String,
+    // As in the file name: "1", "8", "3ssl"
+    pub section: String,
+    pub path: PathBuf,
+}
+
+// "systemctl.1.gz" -> ("systemctl", "1"); "openssl-req.1ssl" ->
+// ("openssl-req", "1ssl"). The section must start with a digit or 'n'.
+pub fn parse_page_name(file_name: &str) -> Option<(String, String)> {
+    let name = COMPRESSED_SUFFIXES
+        .iter()
+        .find_map(|suffix| file_name.strip_suffix(suffix))
+        .unwrap_or(file_name);
+    let (command, section) = name.rsplit_once('.')?;
+    let valid_section = section.starts_with(|c: char| c.is_ascii_digit() || c == 'n')
+        && section.chars().all(|c| c.is_ascii_alphanumeric());
+    (!command.is_empty() && valid_section).then(|| (command.to_string(), section.to_string()))
+}
+
+// Backspace overstrikes ("c\bc" bold, "_\bc" underline) and SGR escapes
+// that groff and mandoc leave in when not writing to a terminal
+pub fn plain_text(rendered: &str) -> String {
+    let mut out: Vec<char> = Vec::with_capacity(rendered.len());
+    let mut chars = rendered.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{8}' => {
+                out.pop();
+            }
+            '\u{1b}' if chars.peek() == Some(&'[') => {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    let text: String = out.into_iter().collect();
+    let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    lines.join("\n").trim().to_string() + "\n"
+}
+
+// A "---" delimited block of "key: value" lines at the top of the file
+pub fn header(fields: &[(&str, String)]) -> String {
+    let mut out = String::from("---\n");
+    for (key, value) in fields {
+        out.push_str(&format!("{}: {}\n", key, value));
+    }
+    out.push_str("---\n\n");
+    out
+}
+
+pub fn parse_header(text: &str) -> BTreeMap<String, String> {
+    let mut lines = text.lines();
+    if lines.next() != Some("---") {
+        return BTreeMap::new();
+    }
+    lines
+        .take_while(|line| *line != "---")
+        .filter_map(|line| line.split_once(": "))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+fn manpath() -> Vec<PathBuf> {
+    let dirs: Vec<PathBuf> = exec::stdout("manpath", &[])
+        .map(|out| std::env::split_paths(out.trim()).collect())
+        .unwrap_or_default();
+    if dirs.is_empty() {
+        vec![PathBuf::from(DEFAULT_MANPATH)]
+    } else {
+        dirs
+    }
+}
+
+// Installed pages in the given sections, first manpath entry winning for a
+// command and section found twice. Symlinked aliases are left out so each
+// page is scraped once.
+pub fn installed_pages(sections: &[String], commands: Option<&[String]>) -> Vec<ManPage> {
+    let mut pages: BTreeMap<(String, String), ManPage> = BTreeMap::new();
+    for root in manpath() {
+        for section in sections {
+            let Ok(entries) = std::fs::read_dir(root.join(format!("man{}", section))) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                if !entry.file_type().is_ok_and(|t| t.is_file()) {
+                    continue;
+                }
+                let Some((command, section)) = parse_page_name(&entry.file_name().to_string_lossy()) else {
+                    continue;
+                };
+                if commands.is_some_and(|wanted| !wanted.contains(&command)) {
+                    continue;
+                }
+                pages.entry((command.clone(), section.clone())).or_insert(ManPage {
+                    command,
+                    section,
+                    path: entry.path(),
+                });
+            }
+        }
+    }
+    pages.into_values().collect()
+}
+
+fn mtime_secs(path: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+// "systemd: /usr/share/man/man1/systemctl.1.gz" (dpkg) or "systemd" (rpm)
+fn owning_package(path: &Path) -> Option<String> {
+    let path = path.to_string_lossy();
+    if let Some(out) = exec::stdout("dpkg-query", &["-S", &path]) {
+        return out.split(':').next().map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+    }
+    exec::stdout("rpm", &["-qf", "--queryformat", "%{NAME}", &path])
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+}
+
+fn render(handle: &JobHandle, page: &Path, mandoc: bool) -> Result<String, String> {
+    let mut command = if mandoc {
+        let mut c = Command::new("mandoc");
+        c.args(["-T", "ascii", "-O", &format!("width={}", RENDER_WIDTH)]).arg(page);
+        c
+    } else {
+        let mut c = Command::new("man");
+        c.args(["-l", "-P", "cat"]).arg(page).env("MANWIDTH", RENDER_WIDTH);
+        c
+    };
+    let mut output = String::new();
+    let status = handle.run_prepared(&mut command, |line| {
+        output.push_str(line);
+        output.push('\n');
+    })?;
+    if !status.success() || output.trim().is_empty() {
+        return Err(format!("renderer exited with {}", status));
+    }
+    Ok(plain_text(&output))
+}
+
+fn is_current(target: &Path, mtime: Option<u64>) -> bool {
+    let Some(mtime) = mtime else {
+        return false;
+    };
+    std::fs::read_to_string(target)
+        .map(|text| parse_header(&text).get("source_mtime") == Some(&mtime.to_string()))
+        .unwrap_or(false)
+}
+
+fn scrape_page(handle: &JobHandle, page: &ManPage, target: &Path, mandoc: bool) -> Result<bool, String> {
+    let mtime = mtime_secs(&page.path);
+    if is_current(target, mtime) {
+        return Ok(false);
+    }
+    let text = render(handle, &page.path, mandoc)?;
+    let mut fields = vec![
+        ("command", page.command.clone()),
+        ("section", page.section.clone()),
+        ("source", page.path.display().to_string()),
+    ];
+    if let Some(package) = owning_package(&page.path) {
+        fields.push(("package", package));
+    }
+    if let Some(mtime) = mtime {
+        fields.push(("source_mtime", mtime.to_string()));
+    }
+    fields.push(("scraped_at", chrono::Utc::now().to_rfc3339()));
+    std::fs::write(target, header(&fields) + &text).map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+// Letters, digits and - _ . + only, so a name can't walk out of the
+// scrape directory or be mistaken for an option
+fn valid_command_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(['-', '.'])
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '+'))
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn scrape_manpages(
+    app: AppHandle,
+    jobs: State<'_, JobManager>,
+    settings: State<'_, SettingsStore>,
+    sections: Vec<String>,
+    commands: Option<Vec<String>>,
+) -> CommandResult<Job> {
+    let settings = settings.get();
+    let root = documents::corpus_root(&settings)?;
+    if sections.is_empty() {
+        return Err(CommandError::InvalidInput("pick at least one man section".to_string()));
+    }
+    if let Some(bad) = sections.iter().find(|s| s.is_empty() || !s.chars().all(|c| c.is_ascii_alphanumeric())) {
+        return Err(CommandError::InvalidInput(format!("'{}' is not a man section", bad)));
+    }
+    let mandoc = exec::find_in_path("mandoc").is_some();
+    if !mandoc && exec::find_in_path("man").is_none() {
+        return Err(CommandError::ToolMissing("neither mandoc nor man is installed".to_string()));
+    }
+
+    let job = jobs.spawn("Scrape man pages", "scrape_manpages", move |handle| {
+        let dir = root.join(MAN_DIR);
+        std::fs::create_dir_all(&dir).map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+        let pages = installed_pages(&sections, commands.as_deref());
+        handle.log(format!(
+            "Found {} pages in section(s) {}, rendering with {}",
+            pages.len(),
+            sections.join(", "),
+            if mandoc { "mandoc" } else { "man" }
+        ));
+        let total = pages.len().max(1);
+        let (mut written, mut unchanged, mut failed) = (0, 0, 0);
+        for (i, page) in pages.iter().enumerate() {
+            let target = dir.join(format!("{}.{}.txt", page.command, page.section));
+            match scrape_page(handle, page, &target, mandoc) {
+                Ok(true) => written += 1,
+                Ok(false) => unchanged += 1,
+                Err(e) => {
+                    failed += 1;
+                    handle.log(format!("Skipped {}({}): {}", page.command, page.section, e));
+                }
+            }
+            handle.set_progress((i + 1) as f32 / total as f32);
+        }
+        handle.log(format!("{} written, {} unchanged, {} failed", written, unchanged, failed));
+        handle.set_result(json!({ "written": written, "unchanged": unchanged, "failed": failed }));
+        if written > 0 {
+            documents::corpus_changed(&app, corpora::PRIMARY, vec![MAN_DIR.to_string()]);
+        }
+        Ok(())
+    });
+    Ok(job)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn scrape_command_help(
+    app: AppHandle,
+    jobs: State<'_, JobManager>,
+    settings: State<'_, SettingsStore>,
+    command: String,
+) -> CommandResult<Job> {
+    let settings = settings.get();
+    let root = documents::corpus_root(&settings)?;
+    let command = command.trim().to_string();
+    if !valid_command_name(&command) {
+        return Err(CommandError::InvalidInput(format!("'{}' is not a command name", command)));
+    }
+    let program = exec::find_in_path(&command)
+        .ok_or_else(|| CommandError::NotFound(format!("{} is not on PATH", command)))?;
+
+    let job = jobs.spawn(&format!("Scrape {} --help", command), "scrape_help", move |handle| {
+        let program_path = program.to_string_lossy().into_owned();
+        // Many tools print help to stderr or exit non-zero after it; the output decides
+        let (_, lines) = handle.run_command_limited(&program_path, &["--help"], Some(HELP_TIMEOUT))?;
+        let text = plain_text(&lines.join("\n"));
+        if text.trim().is_empty() {
+            return Err(format!("{} --help printed nothing", command));
+        }
+        let dir = root.join(HELP_DIR);
+        std::fs::create_dir_all(&dir).map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+        let mut fields = vec![("command", command.clone()), ("source", program_path.clone())];
+        if let Some(package) = owning_package(&program) {
+            fields.push(("package", package));
+        }
+        fields.push(("scraped_at", chrono::Utc::now().to_rfc3339()));
+        let target = dir.join(format!("{}.txt", command));
+        std::fs::write(&target, header(&fields) + &text).map_err(|e| e.to_string())?;
+        handle.log(format!("Wrote {}/{}.txt", HELP_DIR, command));
+        let source = format!("{}/{}.txt", HELP_DIR, command);
+        handle.set_result(json!({ "source": source }));
+        documents::corpus_changed(&app, corpora::PRIMARY, vec![source]);
+        Ok(())
+    });
+    Ok(job)
+}
@@ -0,0 +1,215 @@
+// Secrets kept in the OS keyring rather than in settings.toml.
+//
+// Sessions without a secret service (e.g. headless SSH) fall back to an
+// encrypted file in the app data dir, keyed from the machine id and user.
+// That only keeps the value out of plain sight; the keyring is preferred.
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use crate::error::{CommandError, CommandResult};
+
+const SERVICE: &str = "ai.halbert.dashboard";
+const NONCE_LEN: usize = 12;
+
+pub const STORAGE_KEYRING: &str = "keyring";
+pub const STORAGE_FILE: &str = "encrypted_file";
+
+static FALLBACK_PATH: OnceLock<PathBuf> = OnceLock::new();
+// Serializes read-modify-write of the fallback file
+static FILE_LOCK: Mutex<()> = Mutex::new(());
+// Secrets are read on every backend request; a broken keyring is logged
+// the first time only
+static READ_FAILURE_LOGGED: AtomicBool = AtomicBool::new(false);
+
+// Set the fallback file location; called once from setup
+pub fn init(path: PathBuf) {
+    let _ = FALLBACK_PATH.set(path);
+}
+
+fn entry(name: &str) -> Result<keyring::Entry, keyring::Error> {
+    keyring::Entry::new(SERVICE, name)
+}
+
+pub fn store(name: &str, value: &str) -> CommandResult<()> {
+    match entry(name).and_then(|e| e.set_password(value)) {
+        Ok(()) => {
+            // Don't leave an older copy behind in the file
+            file_remove(name)?;
+            Ok(())
+        }
+        Err(e) => {
+            println!("[Halbert] Keyring unavailable ({}), using encrypted file for {}", e, name);
+            file_store(name, value)
+        }
+    }
+}
+
+pub fn read(name: &str) -> CommandResult<Option<String>> {
+    Ok(locate(name)?.map(|(value, _)| value))
+}
+
+// Which backend currently holds the secret, if any
+pub fn storage_of(name: &str) -> CommandResult<Option<&'static str>> {
+    Ok(locate(name)?.map(|(_, storage)| storage))
+}
+
+fn locate(name: &str) -> CommandResult<Option<(String, &'static str)>> {
+    match entry(name).and_then(|e| e.get_password()) {
+        Ok(value) => return Ok(Some((value, STORAGE_KEYRING))),
+        Err(keyring::Error::NoEntry) => {}
+        Err(e) => {
+            if !READ_FAILURE_LOGGED.swap(true, Ordering::Relaxed) {
+                println!(
+                    "[Halbert] Keyring read failed for {}: {}; using the encrypted file (not logged again)",
+                    name, e
+                );
+            }
+        }
+    }
+    Ok(file_read(name)?.map(|value| (value, STORAGE_FILE)))
+}
+
+pub fn delete(name: &str) -> CommandResult<()> {
+    match entry(name).and_then(|e| e.delete_password()) {
+        Ok(()) | Err(keyring::Error::NoEntry) => {}
+        Err(e) => println!("[Halbert] Keyring delete failed for {}: {}", name, e),
+    }
+    file_remove(name)
+}
+
+// Whether the OS secret service answers at all; a missing probe entry is fine
+pub fn keyring_available() -> Result<(), String> {
+    match entry("availability-probe").and_then(|e| e.get_password()) {
+        Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// --- Encrypted file fallback ---
+//
+// The file is a JSON map of name -> hex(nonce || ciphertext).
+
+fn fallback_path() -> CommandResult<&'static PathBuf> {
+    FALLBACK_PATH
+        .get()
+        .ok_or_else(|| CommandError::Internal("secret store not initialized".to_string()))
+}
+
+fn machine_key() -> Key {
+    let machine_id = ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .find_map(|p| std::fs::read_to_string(p).ok())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .or_else(sysinfo::System::host_name)
+        .unwrap_or_default();
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default();
+
+    let mut hasher = Sha256::new();
+    hasher.update(b"halbert-secrets-v1\0");
+    hasher.update(machine_id.as_bytes());
+    hasher.update(b"\0");
+    hasher.update(user.as_bytes());
+    Key::clone_from_slice(&hasher.finalize())
+}
+
+fn load_file() -> CommandResult<BTreeMap<String, String>> {
+    match std::fs::read_to_string(fallback_path()?) {
+        Ok(text) => serde_json::from_str(&text)
+            .map_err(|e| CommandError::Internal(format!("secret file is corrupt: {}", e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn save_file(secrets: &BTreeMap<String, String>) -> CommandResult<()> {
+    let path = fallback_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let text = serde_json::to_string_pretty(secrets).map_err(|e| CommandError::Internal(e.to_string()))?;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    use std::io::Write;
+    options.open(path)?.write_all(text.as_bytes())?;
+    Ok(())
+}
+
+fn file_store(name: &str, value: &str) -> CommandResult<()> {
+    let cipher = ChaCha20Poly1305::new(&machine_key());
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, value.as_bytes())
+        .map_err(|_| CommandError::Internal(format!("failed to encrypt secret {}", name)))?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+
+    let _guard = FILE_LOCK.lock().unwrap();
+    let mut secrets = load_file()?;
+    secrets.insert(name.to_string(), to_hex(&sealed));
+    save_file(&secrets)
+}
+
+fn file_read(name: &str) -> CommandResult<Option<String>> {
+    let sealed = {
+        let _guard = FILE_LOCK.lock().unwrap();
+        match load_file()?.remove(name) {
+            Some(hex) => hex,
+            None => return Ok(None),
+        }
+    };
+    let bytes = from_hex(&sealed)
+        .filter(|b| b.len() > NONCE_LEN)
+        .ok_or_else(|| CommandError::Internal(format!("secret {} is corrupt", name)))?;
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+
+    // Fails if the machine id or user changed since the secret was written
+    let plaintext = ChaCha20Poly1305::new(&machine_key())
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| CommandError::Internal(format!("secret {} can't be decrypted on this machine", name)))?;
+    String::from_utf8(plaintext)
+        .map(Some)
+        .map_err(|_| CommandError::Internal(format!("secret {} is corrupt", name)))
+}
+
+fn file_remove(name: &str) -> CommandResult<()> {
+    let Some(path) = FALLBACK_PATH.get() else {
+        return Ok(());
+    };
+    if !path.exists() {
+        return Ok(());
+    }
+    let _guard = FILE_LOCK.lock().unwrap();
+    let mut secrets = load_file()?;
+    if secrets.remove(name).is_some() {
+        save_file(&secrets)?;
+    }
+    Ok(())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,352 @@
+// Startup self-check of optional integrations.
+//
+// Each probe checks one thing Halbert can use (a binary, a socket, the
+// backend, ...) and says what to do if it's missing. Probes run in
+// parallel under a shared deadline so one hung socket can't stall the
+// rest. The last report is cached and announced as `selfcheck://complete`.
+use serde::Serialize;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use crate::app::{AppHandle, Emitter, Manager, State};
+use crate::backend;
+use crate::documents;
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::launcher;
+use crate::readonly::Mode;
+use crate::secrets;
+use crate::settings::{Settings, SettingsStore};
+use crate::snapshots;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+const DOCKER_SOCKET: &str = "/var/run/docker.sock";
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Degraded,
+    Missing,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SelfCheck {
+    pub id: String,
+    pub label: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    // Set unless the check is ok
+    pub remediation: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SelfCheckReport {
+    // Current settings mode, so the UI can disable actions when read-only
+    pub mode: Mode,
+    pub checks: Vec<SelfCheck>,
+    pub checked_at: String,
+    pub duration_ms: u64,
+}
+
+#[derive(Default)]
+pub struct SelfCheckStore {
+    last: RwLock<Option<SelfCheckReport>>,
+}
+
+impl SelfCheckStore {
+    pub fn last(&self) -> Option<SelfCheckReport> {
+        self.last.read().unwrap().clone()
+    }
+}
+
+pub struct Outcome {
+    pub status: CheckStatus,
+    pub detail: String,
+    pub remediation: Option<String>,
+}
+
+impl Outcome {
+    pub fn ok<S: Into<String>>(detail: S) -> Self {
+        Outcome {
+            status: CheckStatus::Ok,
+            detail: detail.into(),
+            remediation: None,
+        }
+    }
+
+    pub fn degraded<S: Into<String>, R: Into<String>>(detail: S, remediation: R) -> Self {
+        Outcome {
+            status: CheckStatus::Degraded,
+            detail: detail.into(),
+            remediation: Some(remediation.into()),
+        }
+    }
+
+    pub fn missing<S: Into<String>, R: Into<String>>(detail: S, remediation: R) -> Self {
+        Outcome {
+            status: CheckStatus::Missing,
+            detail: detail.into(),
+            remediation: Some(remediation.into()),
+        }
+    }
+}
+
+struct Probe {
+    id: &'static str,
+    label: &'static str,
+    run: Box<dyn FnOnce() -> Outcome + Send>,
+}
+
+fn binary_probe(id: &'static str, label: &'static str, program: &'static str, hint: &'static str) -> Probe {
+    Probe {
+        id,
+        label,
+        run: Box::new(move || match exec::find_in_path(program) {
+            Some(path) => Outcome::ok(path.display().to_string()),
+            None => Outcome::missing(format!("{} not found on PATH", program), hint),
+        }),
+    }
+}
+
+fn journal_probe() -> Outcome {
+    if exec::find_in_path("journalctl").is_none() {
+        return Outcome::missing("journalctl not found on PATH", "Logs need systemd-journald");
+    }
+    // Without journal access, journalctl prints a hint on stderr and only the user's own entries
+    let output = std::process::Command::new("journalctl")
+        .args(["-n", "1", "-q", "--no-pager", "--system"])
+        .output();
+    match output {
+        Ok(out) if out.status.success() && !out.stdout.is_empty() => Outcome::ok("system journal is readable"),
+        Ok(_) => Outcome::degraded(
+            "the system journal isn't readable by this user",
+            "Add your user to the systemd-journal (or adm) group and log in again",
+        ),
+        Err(e) => Outcome::missing(e.to_string(), "Logs need systemd-journald"),
+    }
+}
+
+#[cfg(unix)]
+fn docker_probe() -> Outcome {
+    use std::os::unix::net::UnixStream;
+    if !std::path::Path::new(DOCKER_SOCKET).exists() {
+        return Outcome::missing(
+            format!("{} does not exist", DOCKER_SOCKET),
+            "Install Docker and start the docker service to see containers",
+        );
+    }
+    match UnixStream::connect(DOCKER_SOCKET) {
+        Ok(_) => Outcome::ok(format!("{} is connectable", DOCKER_SOCKET)),
+        Err(e) => Outcome::degraded(
+            format!("can't connect to {}: {}", DOCKER_SOCKET, e),
+            "Add your user to the docker group, or start the docker service",
+        ),
+    }
+}
+
+#[cfg(not(unix))]
+fn docker_probe() -> Outcome {
+    Outcome::missing("docker socket checks need a unix host", "Containers are unavailable on this platform")
+}
+
+pub fn corpus_probe(settings: &Settings) -> Outcome {
+    match documents::corpus_root(settings) {
+        Ok(root) => match std::fs::read_dir(&root).map(|mut entries| entries.next().is_some()) {
+            Ok(true) => Outcome::ok(root.display().to_string()),
+            Ok(_) => Outcome::degraded(
+                format!("{} is empty", root.display()),
+                "Add documents to the corpus directory",
+            ),
+            Err(e) => Outcome::degraded(
+                format!("{} isn't readable: {}", root.display(), e),
+                "Fix the permissions on the corpus directory",
+            ),
+        },
+        Err(CommandError::NotSupported(_)) => Outcome::missing(
+            "no corpus path configured",
+            "Set corpus_path in Settings to your documents directory",
+        ),
+        Err(e) => Outcome::missing(e.to_string(), "Point corpus_path at an existing directory"),
+    }
+}
+
+pub fn backend_probe(settings: &Settings) -> Outcome {
+    match backend::endpoint(settings).probe("/api/status", PROBE_TIMEOUT) {
+        Ok(code) if code < 400 => Outcome::ok(format!("{} answered HTTP {}", settings.backend_url, code)),
+        Ok(401) | Ok(403) => Outcome::degraded(
+            format!("{} rejected the token", settings.backend_url),
+            "Set a valid backend token in Settings",
+        ),
+        Ok(code) => Outcome::degraded(
+            format!("{} answered HTTP {}", settings.backend_url, code),
+            "Check the backend logs",
+        ),
+        Err(e) => Outcome::missing(e.to_string(), "Start the Halbert backend or fix backend_url in Settings"),
+    }
+}
+
+fn keyring_probe() -> Outcome {
+    match secrets::keyring_available() {
+        Ok(()) => Outcome::ok("OS secret service is available"),
+        Err(e) => Outcome::degraded(
+            format!("no secret service ({}); secrets use the encrypted file fallback", e),
+            "Install and unlock a secret service (e.g. gnome-keyring) to keep tokens in the OS keyring",
+        ),
+    }
+}
+
+fn terminal_probe(settings: &Settings) -> Outcome {
+    match launcher::terminal_template(settings) {
+        Some(template) => {
+            let program = template.split_whitespace().next().unwrap_or_default();
+            if exec::find_in_path(program).is_some() || std::path::Path::new(program).is_file() {
+                Outcome::ok(template)
+            } else {
+                Outcome::missing(
+                    format!("terminal_command runs {}, which is not installed", program),
+                    "Fix terminal_command in Settings",
+                )
+            }
+        }
+        None => Outcome::missing(
+            "no known terminal emulator found",
+            "Set terminal_command in Settings, e.g. \"gnome-terminal --working-directory={path}\"",
+        ),
+    }
+}
+
+fn probes(settings: &Settings) -> Vec<Probe> {
+    let corpus_settings = settings.clone();
+    let backend_settings = settings.clone();
+    let terminal_settings = settings.clone();
+    vec![
+        binary_probe("systemctl", "systemd", "systemctl", "Service management needs systemd"),
+        Probe {
+            id: "journal",
+            label: "System journal",
+            run: Box::new(journal_probe),
+        },
+        binary_probe(
+            "smartctl",
+            "SMART disk health",
+            "smartctl",
+            "Install smartmontools for disk health data",
+        ),
+        binary_probe(
+            "nvidia-smi",
+            "NVIDIA GPU",
+            "nvidia-smi",
+            "Install the NVIDIA driver utilities for GPU metrics",
+        ),
+        binary_probe("restic", "Backups", "restic", "Install restic to run backups"),
+        Probe {
+            id: "docker",
+            label: "Docker",
+            run: Box::new(docker_probe),
+        },
+        Probe {
+            id: "corpus",
+            label: "Document corpus",
+            run: Box::new(move || corpus_probe(&corpus_settings)),
+        },
+        Probe {
+            id: "backend",
+            label: "Halbert backend",
+            run: Box::new(move || backend_probe(&backend_settings)),
+        },
+        Probe {
+            id: "keyring",
+            label: "OS keyring",
+            run: Box::new(keyring_probe),
+        },
+        Probe {
+            id: "snapshots",
+            label: "Filesystem snapshots",
+            run: Box::new(snapshots::probe),
+        },
+        Probe {
+            id: "terminal",
+            label: "Terminal emulator",
+            run: Box::new(move || terminal_probe(&terminal_settings)),
+        },
+    ]
+}
+
+pub fn run_checks(settings: &Settings) -> SelfCheckReport {
+    let started = Instant::now();
+    let deadline = started + PROBE_TIMEOUT + Duration::from_millis(500);
+
+    // Start everything first, then collect under the shared deadline
+    let pending: Vec<_> = probes(settings)
+        .into_iter()
+        .map(|probe| {
+            let (tx, rx) = std::sync::mpsc::channel();
+            let run = probe.run;
+            std::thread::spawn(move || {
+                let _ = tx.send(run());
+            });
+            (probe.id, probe.label, rx)
+        })
+        .collect();
+
+    let checks = pending
+        .into_iter()
+        .map(|(id, label, rx)| {
+            let outcome = rx
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .unwrap_or_else(|_| {
+                    Outcome::degraded("probe timed out", "Run the self-check again; if it persists, check this integration")
+                });
+            SelfCheck {
+                id: id.to_string(),
+                label: label.to_string(),
+                status: outcome.status,
+                detail: outcome.detail,
+                remediation: outcome.remediation,
+            }
+        })
+        .collect();
+
+    SelfCheckReport {
+        mode: settings.mode,
+        checks,
+        checked_at: chrono::Utc::now().to_rfc3339(),
+        duration_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+fn run_and_publish(app: &AppHandle) -> SelfCheckReport {
+    let settings = app.state::<SettingsStore>().get();
+    let report = run_checks(&settings);
+    *app.state::<SelfCheckStore>().last.write().unwrap() = Some(report.clone());
+    let _ = app.emit("selfcheck://complete", &report);
+    report
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        let report = run_and_publish(&app);
+        let problems = report.checks.iter().filter(|c| c.status != CheckStatus::Ok).count();
+        println!("[Halbert] Self-check finished: {} of {} checks need attention", problems, report.checks.len());
+    });
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn run_self_check(app: AppHandle) -> CommandResult<SelfCheckReport> {
+    Ok(run_and_publish(&app))
+}
+
+// Cached result of the last run; NotFound until the startup run finishes
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_self_check(
+    store: State<'_, SelfCheckStore>,
+    settings: State<'_, SettingsStore>,
+) -> CommandResult<SelfCheckReport> {
+    let mut report = store
+        .last()
+        .ok_or_else(|| CommandError::NotFound("self-check hasn't completed yet".to_string()))?;
+    // The mode may have changed since the checks ran
+    report.mode = settings.mode();
+    Ok(report)
+}
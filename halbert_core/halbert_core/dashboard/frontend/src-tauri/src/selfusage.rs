@@ -0,0 +1,338 @@
+// Halbert's own resource usage, and the self-limits that keep it small.
+//
+// The sampler calls `enforce` every tick. When this process's RSS goes over
+// `self_rss_limit_mb`, the metrics history ring is cut down and background
+// samplers sleep longer, announced as `selfcheck://degraded`. Limits lift
+// once RSS has stayed well under the cap for a few readings, announced as
+// `selfcheck://recovered`. The state machine is `Governor`, which only sees
+// readings passed to it, so it can be driven with made-up numbers.
+use serde::Serialize;
+use std::sync::Mutex;
+use sysinfo::System;
+
+use crate::activity::{ActivityStatus, UiActivity};
+use crate::app::{AppHandle, Emitter, Manager, State};
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::process_tree;
+use crate::ratelimit::{RateLimitStats, RateLimiter};
+use crate::sampler::MetricsHistory;
+use crate::settings::SettingsStore;
+use crate::startup::{Startup, StartupReport};
+
+// While degraded: history keeps 1/4 of its configured length...
+const DEGRADED_HISTORY_DIVISOR: usize = 4;
+const MIN_DEGRADED_HISTORY: usize = 60;
+// ...and samplers sleep this many times longer
+const DEGRADED_INTERVAL_FACTOR: u64 = 4;
+// Recovery needs this many readings in a row below RECOVERY_RATIO of the cap
+const RECOVERY_READINGS: u32 = 5;
+const RECOVERY_RATIO: f64 = 0.8;
+
+// Child processes that host the webview, per platform
+const WEBVIEW_PROCESS_PREFIXES: &[&str] = &["WebKit", "msedgewebview2", "com.apple.WebKit"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Degraded,
+    Recovered,
+}
+
+#[derive(Default)]
+pub struct Governor {
+    degraded: bool,
+    calm_readings: u32,
+}
+
+impl Governor {
+    // A cap of 0 disables the limit (and lifts it if it was in force)
+    pub fn observe(&mut self, rss_mb: f64, cap_mb: u64) -> Option<Transition> {
+        if cap_mb == 0 {
+            self.calm_readings = 0;
+            return std::mem::take(&mut self.degraded).then_some(Transition::Recovered);
+        }
+        let cap = cap_mb as f64;
+        if !self.degraded {
+            if rss_mb > cap {
+                self.degraded = true;
+                self.calm_readings = 0;
+                return Some(Transition::Degraded);
+            }
+            return None;
+        }
+        if rss_mb < cap * RECOVERY_RATIO {
+            self.calm_readings += 1;
+        } else {
+            self.calm_readings = 0;
+        }
+        if self.calm_readings >= RECOVERY_READINGS {
+            self.degraded = false;
+            self.calm_readings = 0;
+            return Some(Transition::Recovered);
+        }
+        None
+    }
+
+    pub fn degraded(&self) -> bool {
+        self.degraded
+    }
+
+    pub fn history_capacity(&self, configured: usize) -> usize {
+        if self.degraded {
+            (configured / DEGRADED_HISTORY_DIVISOR).max(MIN_DEGRADED_HISTORY).min(configured)
+        } else {
+            configured
+        }
+    }
+
+    pub fn interval_factor(&self) -> u64 {
+        if self.degraded {
+            DEGRADED_INTERVAL_FACTOR
+        } else {
+            1
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SelfLimiter {
+    governor: Mutex<Governor>,
+}
+
+impl SelfLimiter {
+    pub fn interval_factor(&self) -> u64 {
+        self.governor.lock().unwrap().interval_factor()
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct SelfLimitEvent {
+    pub rss_mb: f64,
+    pub limit_mb: u64,
+    // What was cut back (or restored), in words
+    pub changes: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct DatabaseFile {
+    pub path: String,
+    pub bytes: u64,
+}
+
+#[derive(Serialize)]
+pub struct SelfUsage {
+    pub pid: u32,
+    // Halbert plus every child process (webview helpers included)
+    pub process_count: usize,
+    pub cpu_percent: f32,
+    pub rss_mb: f64,
+    // The main process alone; this is what the cap applies to
+    pub main_rss_mb: f64,
+    pub webview_rss_mb: f64,
+    // None where /proc isn't available
+    pub open_fds: Option<usize>,
+    pub database_files: Vec<DatabaseFile>,
+    pub metrics_history_samples: usize,
+    pub metrics_history_bytes: usize,
+    pub rss_limit_mb: u64,
+    pub degraded: bool,
+    // Hit and miss counts for rate-limited commands
+    pub rate_limits: Vec<RateLimitStats>,
+    // Whether sampling is slowed because the dashboard is hidden
+    pub ui_activity: ActivityStatus,
+    // How long each part of startup took
+    pub startup: StartupReport,
+}
+
+fn mb(bytes: u64) -> f64 {
+    bytes as f64 / 1024.0 / 1024.0
+}
+
+fn own_rss_bytes() -> Option<u64> {
+    let pid = sysinfo::get_current_pid().ok()?;
+    let mut sys = System::new();
+    sys.refresh_process(pid);
+    sys.process(pid).map(|p| p.memory())
+}
+
+#[cfg(target_os = "linux")]
+fn open_fds(pid: u32) -> Option<usize> {
+    std::fs::read_dir(format!("/proc/{}/fd", pid)).ok().map(|dir| dir.count())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_fds(_pid: u32) -> Option<usize> {
+    None
+}
+
+pub fn enforce(app: &AppHandle) {
+    let settings = app.state::<SettingsStore>().get();
+    let limiter = app.state::<SelfLimiter>();
+    let rss_mb = own_rss_bytes().map(mb);
+    let (transition, capacity) = {
+        let mut governor = limiter.governor.lock().unwrap();
+        let transition = rss_mb.and_then(|rss| governor.observe(rss, settings.self_rss_limit_mb));
+        (transition, governor.history_capacity(settings.metrics_history_len))
+    };
+    app.state::<MetricsHistory>().set_capacity(capacity);
+
+    let Some(transition) = transition else {
+        return;
+    };
+    let event = SelfLimitEvent {
+        rss_mb: rss_mb.unwrap_or_default(),
+        limit_mb: settings.self_rss_limit_mb,
+        changes: match transition {
+            Transition::Degraded => vec![
+                format!(
+                    "metrics history cut from {} to {} samples",
+                    settings.metrics_history_len, capacity
+                ),
+                format!("background sampling slowed {}x", DEGRADED_INTERVAL_FACTOR),
+            ],
+            Transition::Recovered => vec![
+                format!("metrics history restored to {} samples", capacity),
+                "background sampling back to normal".to_string(),
+            ],
+        },
+    };
+    let (name, verb) = match transition {
+        Transition::Degraded => ("selfcheck://degraded", "over"),
+        Transition::Recovered => ("selfcheck://recovered", "back under"),
+    };
+    println!(
+        "[Halbert] RSS {:.0} MB is {} the {} MB cap: {}",
+        event.rss_mb,
+        verb,
+        event.limit_mb,
+        event.changes.join("; ")
+    );
+    let _ = app.emit(name, &event);
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_self_usage(
+    settings: State<'_, SettingsStore>,
+    db: State<'_, Database>,
+    history: State<'_, MetricsHistory>,
+    limiter: State<'_, SelfLimiter>,
+    rate_limiter: State<'_, RateLimiter>,
+    activity: State<'_, UiActivity>,
+    startup: State<'_, Startup>,
+) -> CommandResult<SelfUsage> {
+    let pid = sysinfo::get_current_pid()
+        .map_err(|e| CommandError::NotSupported(e.to_string()))?
+        .as_u32();
+    let snap = process_tree::snapshot();
+    let main = snap
+        .entries
+        .get(&pid)
+        .ok_or_else(|| CommandError::Internal("own process missing from the process table".to_string()))?;
+
+    let mut tree = vec![pid];
+    let mut next = 0;
+    while next < tree.len() {
+        let parent = tree[next];
+        tree.extend(snap.children.get(&parent).into_iter().flatten());
+        next += 1;
+    }
+
+    let (mut cpu_percent, mut rss_bytes, mut webview_bytes) = (0.0, 0, 0);
+    for entry in tree.iter().filter_map(|p| snap.entries.get(p)) {
+        cpu_percent += entry.cpu_percent;
+        rss_bytes += entry.memory_bytes;
+        if WEBVIEW_PROCESS_PREFIXES.iter().any(|prefix| entry.name.starts_with(prefix)) {
+            webview_bytes += entry.memory_bytes;
+        }
+    }
+    let open_fds = tree.iter().map(|&p| open_fds(p)).sum::<Option<usize>>();
+
+    let database_files = db
+        .files()
+        .into_iter()
+        .filter_map(|path| {
+            let bytes = std::fs::metadata(&path).ok()?.len();
+            Some(DatabaseFile {
+                path: path.display().to_string(),
+                bytes,
+            })
+        })
+        .collect();
+
+    let settings = settings.get();
+    Ok(SelfUsage {
+        pid,
+        process_count: tree.len(),
+        cpu_percent,
+        rss_mb: mb(rss_bytes),
+        main_rss_mb: mb(main.memory_bytes),
+        webview_rss_mb: mb(webview_bytes),
+        open_fds,
+        database_files,
+        metrics_history_samples: history.sample_count(),
+        metrics_history_bytes: history.footprint_bytes(),
+        rss_limit_mb: settings.self_rss_limit_mb,
+        degraded: limiter.governor.lock().unwrap().degraded(),
+        rate_limits: rate_limiter.stats(&settings.rate_limits_ms),
+        ui_activity: activity.status(settings.metrics_interval_secs.max(1), settings.idle_metrics_interval_secs),
+        startup: startup.report(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAP: u64 = 500;
+
+    #[test]
+    fn degrades_only_above_the_cap() {
+        let mut governor = Governor::default();
+        assert_eq!(governor.observe(500.0, CAP), None);
+        assert!(!governor.degraded());
+        assert_eq!(governor.observe(500.1, CAP), Some(Transition::Degraded));
+        assert!(governor.degraded());
+        // Still over: no second announcement
+        assert_eq!(governor.observe(900.0, CAP), None);
+        assert_eq!(governor.interval_factor(), DEGRADED_INTERVAL_FACTOR);
+    }
+
+    #[test]
+    fn recovers_after_enough_calm_readings_in_a_row() {
+        let mut governor = Governor::default();
+        governor.observe(600.0, CAP);
+        let calm = CAP as f64 * RECOVERY_RATIO - 1.0;
+        for _ in 1..RECOVERY_READINGS {
+            assert_eq!(governor.observe(calm, CAP), None);
+        }
+        // Back near the cap: the count starts over
+        assert_eq!(governor.observe(CAP as f64 * RECOVERY_RATIO, CAP), None);
+        for _ in 1..RECOVERY_READINGS {
+            assert_eq!(governor.observe(calm, CAP), None);
+        }
+        assert_eq!(governor.observe(calm, CAP), Some(Transition::Recovered));
+        assert!(!governor.degraded());
+        assert_eq!(governor.interval_factor(), 1);
+        // And it can degrade again
+        assert_eq!(governor.observe(501.0, CAP), Some(Transition::Degraded));
+    }
+
+    #[test]
+    fn a_zero_cap_lifts_the_limit() {
+        let mut governor = Governor::default();
+        assert_eq!(governor.observe(10_000.0, 0), None);
+        governor.observe(600.0, CAP);
+        assert_eq!(governor.observe(600.0, 0), Some(Transition::Recovered));
+        assert_eq!(governor.observe(600.0, 0), None);
+    }
+
+    #[test]
+    fn degraded_history_is_cut_but_kept_usable() {
+        let mut governor = Governor::default();
+        assert_eq!(governor.history_capacity(3600), 3600);
+        governor.observe(600.0, CAP);
+        assert_eq!(governor.history_capacity(3600), 900);
+        assert_eq!(governor.history_capacity(100), MIN_DEGRADED_HISTORY);
+        assert_eq!(governor.history_capacity(30), 30);
+    }
+}
@@ -0,0 +1,143 @@
+// systemd service actions, approved first and then run as jobs
+use serde_json::json;
+use std::collections::HashSet;
+
+use crate::app::{AppHandle, State};
+use crate::approvals::{self, ApprovalAction, ApprovalRequest, NewApproval};
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::jobs::{Job, JobManager};
+use crate::settings::SettingsStore;
+
+const SERVICE_ACTIONS: &[&str] = &["start", "stop", "restart", "reload"];
+
+// Every service unit systemd knows about, loaded or merely installed
+fn known_units() -> HashSet<String> {
+    let mut units = HashSet::new();
+    let listings = [
+        exec::stdout(
+            "systemctl",
+            &["list-units", "--all", "--type=service", "--plain", "--no-legend", "--no-pager"],
+        ),
+        exec::stdout(
+            "systemctl",
+            &["list-unit-files", "--type=service", "--no-legend", "--no-pager"],
+        ),
+    ];
+    for output in listings.into_iter().flatten() {
+        for line in output.lines() {
+            if let Some(unit) = line.split_whitespace().next() {
+                units.insert(unit.to_string());
+            }
+        }
+    }
+    units
+}
+
+// Service units enabled to start at boot
+pub fn enabled_units() -> Option<Vec<String>> {
+    let out = exec::stdout(
+        "systemctl",
+        &["list-unit-files", "--type=service", "--state=enabled", "--no-legend", "--no-pager"],
+    )?;
+    Some(
+        out.lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .map(|unit| unit.to_string())
+            .collect(),
+    )
+}
+
+// Resolve user input to a unit name systemd actually listed. Only exact
+// matches are accepted, so nothing that looks like an option (or any other
+// arbitrary string) ever reaches the systemctl argv.
+fn resolve_unit(unit: &str) -> CommandResult<String> {
+    let unit = unit.trim();
+    if unit.is_empty() || unit.starts_with('-') {
+        return Err(CommandError::InvalidInput(format!("invalid unit name '{}'", unit)));
+    }
+    let candidate = if unit.ends_with(".service") {
+        unit.to_string()
+    } else {
+        format!("{}.service", unit)
+    };
+    if known_units().contains(&candidate) {
+        Ok(candidate)
+    } else {
+        Err(CommandError::NotFound(format!("unit {}", candidate)))
+    }
+}
+
+fn is_protected(unit: &str, protected: &[String]) -> bool {
+    let bare = unit.trim_end_matches(".service");
+    protected
+        .iter()
+        .any(|p| p == unit || p.trim_end_matches(".service") == bare)
+}
+
+pub fn unit_active_state(unit: &str) -> Option<String> {
+    exec::stdout("systemctl", &["show", "--property=ActiveState", "--value", unit])
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+// Run on approval: the systemctl call itself is a job so its output and
+// exit status are captured like any other task
+pub fn spawn_service_job(jobs: &JobManager, unit: &str, action: &str) -> Job {
+    let unit = unit.to_string();
+    let action = action.to_string();
+    jobs.spawn(&format!("systemctl {} {}", action, unit), "service", move |job| {
+        let status = job.run_command("systemctl", &[action.as_str(), unit.as_str()])?;
+        let active_state = unit_active_state(&unit);
+        job.set_result(json!({
+            "unit": unit,
+            "action": action,
+            "exit_code": status.code(),
+            "active_state": active_state,
+        }));
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("systemctl {} {} exited with {}", action, unit, status))
+        }
+    })
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn request_service_action(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    unit: String,
+    action: String,
+) -> CommandResult<ApprovalRequest> {
+    let action = action.trim().to_ascii_lowercase();
+    if !SERVICE_ACTIONS.contains(&action.as_str()) {
+        return Err(CommandError::InvalidInput(format!(
+            "action must be one of {}",
+            SERVICE_ACTIONS.join(", ")
+        )));
+    }
+    let unit = resolve_unit(&unit)?;
+
+    let risk_level = if is_protected(&unit, &settings.get().protected_units) {
+        "high"
+    } else if action == "start" || action == "reload" {
+        "low"
+    } else {
+        "medium"
+    };
+    let current_state = unit_active_state(&unit).unwrap_or_else(|| "unknown".to_string());
+
+    let new = NewApproval {
+        task: "Service Action".to_string(),
+        action: format!("{} {}", action, unit),
+        reasoning: format!("Requested from the dashboard; {} is currently {}", unit, current_state),
+        confidence: 1.0,
+        risk_level: risk_level.to_string(),
+        affected_resources: vec![format!("service:{}", unit)],
+        execution: None,
+        group_id: None,
+        depends_on: Vec::new(),
+    };
+    approvals::submit(&app, new, Some(ApprovalAction::ServiceAction { unit, action }))
+}
@@ -0,0 +1,202 @@
+// Who the app is running as and what it may do, for the frontend shell.
+//
+// get_session_info gathers in one call what the header and the action
+// buttons need: versions, the identity audit and approval records carry,
+// whether it's read-only or automation is paused, what self-check found
+// available, the active host and the on/off features from settings. The
+// frontend treats SessionInfo as a contract, so fields are only ever
+// added.
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::app::{AppHandle, State};
+use crate::audit;
+use crate::automation::Gate;
+use crate::hosts::{self, ActiveHost};
+use crate::readonly::Mode;
+use crate::selfcheck::{CheckStatus, SelfCheckStore};
+use crate::settings::{Settings, SettingsStore};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SessionHost {
+    pub id: String,
+    pub name: String,
+    pub remote: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SessionInfo {
+    // From tauri.conf.json
+    pub app_version: String,
+    // Of this crate, which can run ahead of the app's during development
+    pub crate_version: String,
+    // "debug" or "release"
+    pub build_profile: String,
+    // What audit and approval records from this machine are attributed to
+    pub identity: String,
+    pub login_name: String,
+    pub mode: Mode,
+    pub automation_paused: bool,
+    // Ids of self-checks that passed; None until the first run finishes
+    pub capabilities: Option<Vec<String>>,
+    // Ids of self-checks that passed with reduced function
+    pub degraded: Option<Vec<String>>,
+    pub active_host: SessionHost,
+    pub features: BTreeMap<String, bool>,
+}
+
+pub fn identity(settings: &Settings) -> String {
+    settings
+        .approver_identity
+        .clone()
+        .filter(|i| !i.trim().is_empty())
+        .map(|i| i.trim().to_string())
+        .unwrap_or_else(audit::local_actor)
+}
+
+pub fn features(settings: &Settings) -> BTreeMap<String, bool> {
+    [
+        ("forced_process_actions", settings.allow_forced_process_actions),
+        ("desktop_notifications", settings.desktop_notifications.enabled),
+        ("quiet_hours", settings.desktop_notifications.quiet_hours.is_some()),
+        ("backup", settings.backup.is_some()),
+        ("webhooks", !settings.webhooks.is_empty()),
+        ("remote_hosts", !settings.hosts.is_empty()),
+        ("outcome_reminders", settings.outcome_prompt_days > 0),
+    ]
+    .into_iter()
+    .map(|(name, on)| (name.to_string(), on))
+    .collect()
+}
+
+fn session_host(settings: &Settings) -> SessionHost {
+    match hosts::active_host(settings) {
+        ActiveHost::Local => SessionHost {
+            id: hosts::LOCAL_HOST_ID.to_string(),
+            name: "This computer".to_string(),
+            remote: false,
+        },
+        ActiveHost::Remote(host) => SessionHost {
+            id: host.id,
+            name: host.name,
+            remote: true,
+        },
+    }
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_session_info(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    gate: State<'_, Gate>,
+    checks: State<'_, SelfCheckStore>,
+) -> SessionInfo {
+    let settings = settings.get();
+    let report = checks.last();
+    let ids = |wanted: CheckStatus| {
+        report.as_ref().map(|r| {
+            r.checks
+                .iter()
+                .filter(|c| c.status == wanted)
+                .map(|c| c.id.clone())
+                .collect::<Vec<String>>()
+        })
+    };
+    SessionInfo {
+        app_version: app.package_info().version.to_string(),
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        build_profile: if cfg!(debug_assertions) { "debug" } else { "release" }.to_string(),
+        identity: identity(&settings),
+        login_name: audit::local_actor(),
+        mode: settings.mode,
+        automation_paused: gate.is_paused(),
+        capabilities: ids(CheckStatus::Ok),
+        degraded: ids(CheckStatus::Degraded),
+        active_host: session_host(&settings),
+        features: features(&settings),
+    }
+}
+
+// Kept for frontends built before get_session_info; drop from the invoke
+// handler after the next release
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn greet(name: &str) -> String {
+    println!("[Halbert] greet is deprecated, use get_session_info");
+    format!("Hello, {}! You've been greeted from Rust!", name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample() -> SessionInfo {
+        SessionInfo {
+            app_version: "0.3.0".to_string(),
+            crate_version: "0.3.1".to_string(),
+            build_profile: "release".to_string(),
+            identity: "alice@example.com".to_string(),
+            login_name: "alice".to_string(),
+            mode: Mode::ReadOnly,
+            automation_paused: true,
+            capabilities: Some(vec!["journal".to_string(), "systemd".to_string()]),
+            degraded: None,
+            active_host: SessionHost {
+                id: "nas".to_string(),
+                name: "Basement NAS".to_string(),
+                remote: true,
+            },
+            features: [("backup".to_string(), true), ("webhooks".to_string(), false)].into_iter().collect(),
+        }
+    }
+
+    // The shape the frontend reads; a change here breaks it
+    #[test]
+    fn session_info_json_contract() {
+        let expected = json!({
+            "app_version": "0.3.0",
+            "crate_version": "0.3.1",
+            "build_profile": "release",
+            "identity": "alice@example.com",
+            "login_name": "alice",
+            "mode": "read_only",
+            "automation_paused": true,
+            "capabilities": ["journal", "systemd"],
+            "degraded": null,
+            "active_host": {"id": "nas", "name": "Basement NAS", "remote": true},
+            "features": {"backup": true, "webhooks": false},
+        });
+        assert_eq!(serde_json::to_value(sample()).unwrap(), expected);
+        let back: SessionInfo = serde_json::from_value(expected).unwrap();
+        assert_eq!(back, sample());
+    }
+
+    #[test]
+    fn every_feature_is_reported_and_identity_falls_back_to_the_login() {
+        let settings = Settings::default();
+        let names: Vec<String> = features(&settings).into_keys().collect();
+        assert_eq!(
+            names,
+            [
+                "backup",
+                "desktop_notifications",
+                "forced_process_actions",
+                "outcome_reminders",
+                "quiet_hours",
+                "remote_hosts",
+                "webhooks"
+            ]
+        );
+        assert_eq!(identity(&settings), audit::local_actor());
+        let named = Settings {
+            approver_identity: Some("  alice@example.com ".to_string()),
+            ..Settings::default()
+        };
+        assert_eq!(identity(&named), "alice@example.com");
+        let blank = Settings {
+            approver_identity: Some(" ".to_string()),
+            ..Settings::default()
+        };
+        assert_eq!(identity(&blank), audit::local_actor());
+    }
+}
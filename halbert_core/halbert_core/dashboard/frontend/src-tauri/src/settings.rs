@@ -0,0 +1,716 @@
+// Persistent user settings (settings.toml in the app config dir)
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::alerts::AlertRule;
+use crate::app::{AppHandle, Emitter, Manager, State};
+use crate::backend;
+use crate::backup::BackupConfig;
+use crate::collectors::{CollectorConfig, CollectorProfile, CollectorRegistry};
+use crate::corpora::{self, NamedCorpus};
+use crate::corpus_watch::CorpusWatcher;
+use crate::desktop_notify::DesktopNotifySettings;
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::hosts::HostEntry;
+use crate::liveness::{self, LivenessSettings};
+use crate::notifications::WebhookEntry;
+use crate::policy::RiskPolicy;
+use crate::ratelimit::{self, RateLimiter};
+use crate::readonly::{self, Mode};
+use crate::remote_access::{self, RemoteAccessSettings};
+use crate::remote_core;
+use crate::sandbox;
+use crate::selfcheck;
+use crate::settings_revisions;
+use crate::storage::RetentionPolicy;
+use crate::timestamps::{self, TimeFormat};
+use crate::units::Units;
+#[cfg(feature = "gui")]
+use crate::widget;
+
+// How long a live check of a new value may take (see validate_settings)
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// The quick-glance window's settings. They're here rather than in widget,
+// which is only built with the window, as halbert-agent reads the same
+// settings file.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WidgetPosition {
+    // Logical pixels, top-left corner
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WidgetSettings {
+    // Mouse events pass through to whatever is underneath
+    pub click_through: bool,
+    pub draggable: bool,
+    // None lets the window manager place it
+    pub position: Option<WidgetPosition>,
+}
+
+impl Default for WidgetSettings {
+    fn default() -> Self {
+        WidgetSettings {
+            click_through: false,
+            draggable: true,
+            position: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // "read_only" blocks every command that would change the system
+    pub mode: Mode,
+    // Absolute clock offset above which time sync reports drift
+    pub time_drift_threshold_ms: f64,
+    pub alert_rules: Vec<AlertRule>,
+    // Lets process actions skip the approval queue when called with force
+    pub allow_forced_process_actions: bool,
+    // Service actions on these units are always high risk
+    pub protected_units: Vec<String>,
+    // Checked in order against new approval requests; first match wins
+    pub risk_policies: Vec<RiskPolicy>,
+    // Remote Halbert agents; tokens live in the keyring, not here
+    pub hosts: Vec<HostEntry>,
+    // None means this machine
+    pub active_host: Option<String>,
+    // The host whose halbert-agent runs the core this window shows (see
+    // remote_core); None runs it here
+    pub core_host: Option<String>,
+    pub metrics_interval_secs: u64,
+    // Sampling interval while the dashboard is hidden
+    pub idle_metrics_interval_secs: u64,
+    // Halbert backend API; its token is in the secret store
+    pub backend_url: String,
+    // The backend's 5-minute p95 above this raises backend.slo_violated;
+    // 0 turns it off
+    pub backend_slo_p95_ms: u64,
+    // Root of the primary RAG corpus; None serves mock documents
+    pub corpus_path: Option<String>,
+    // More corpora, each indexed separately (see corpora)
+    pub corpora: Vec<NamedCorpus>,
+    // Globs matched against file names and corpus-relative paths
+    pub corpus_ignore: Vec<String>,
+    // Corpus health check limits
+    pub corpus_near_empty_bytes: u64,
+    pub corpus_max_file_bytes: u64,
+    // Where paths may reach as mounts besides /media, /mnt and /run/media
+    // (see sandbox)
+    pub mount_roots: Vec<String>,
+    // e.g. "gnome-terminal --working-directory={path}"; None picks a
+    // built-in one that's installed
+    pub terminal_command: Option<String>,
+    // host:port endpoints whose TLS certificates are watched
+    pub certificate_targets: Vec<String>,
+    // Below this many days left, cert.expiring is raised for alert rules
+    pub certificate_warning_days: i64,
+    // restic repository and paths; the password is in the secret store
+    pub backup: Option<BackupConfig>,
+    // Outbound notifications; URLs are in the secret store
+    pub webhooks: Vec<WebhookEntry>,
+    // Above this RSS Halbert sheds history and samples less often; 0 disables
+    pub self_rss_limit_mb: u64,
+    // Local metrics samples kept in memory for the dashboard
+    pub metrics_history_len: usize,
+    // How long stored history is kept by storage maintenance
+    pub retention: RetentionPolicy,
+    // Stops the first-run guide from opening at startup
+    pub onboarding_skipped: bool,
+    // Users below this uid count as system accounts in the per-user view
+    pub system_uid_threshold: u32,
+    // "binary" (GiB, MiB) or "decimal" (GB, MB) for every displayed size
+    pub units: Units,
+    // UDP port for wake-on-LAN packets
+    pub wol_port: u16,
+    // How long to watch for a woken host's API before reporting it offline
+    pub wol_wait_secs: u64,
+    // Minimum ms between recomputations, by command name; 0 disables
+    pub rate_limits_ms: BTreeMap<String, u64>,
+    // Commands taking longer are logged; 0 turns the warning off
+    pub slow_command_ms: u64,
+    // Which background collectors run and how often; a missing collector
+    // runs at its default
+    pub collectors: BTreeMap<String, CollectorConfig>,
+    // Saved with save_profile; the built-in profiles aren't stored
+    pub collector_profiles: Vec<CollectorProfile>,
+    pub active_profile: Option<String>,
+    // Who approval votes from this machine are recorded as; None uses the
+    // login name
+    pub approver_identity: Option<String>,
+    // Distinct approvers a request needs before it runs, by risk level;
+    // a level missing here needs one
+    pub approvers_required: BTreeMap<String, u32>,
+    // "local", "utc" or an offset like "+05:30", for displayed timestamps
+    pub timezone: String,
+    pub time_format: TimeFormat,
+    // Approved requests without an outcome this many days after the
+    // decision are brought up in a reminder; 0 turns reminders off
+    pub outcome_prompt_days: u32,
+    // The quick-glance window (see widget)
+    pub widget: WidgetSettings,
+    // Which events show on the desktop, with sound, and when they wait
+    pub desktop_notifications: DesktopNotifySettings,
+    // Reverse-resolve remote addresses in the connection table
+    pub connection_dns_lookups: bool,
+    // Heartbeat file and healthcheck pings for outside supervisors
+    pub liveness: LivenessSettings,
+    // The read-only HTTP dashboard; off unless an address is set
+    pub remote_access: RemoteAccessSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            mode: Mode::Full,
+            time_drift_threshold_ms: 500.0,
+            alert_rules: Vec::new(),
+            allow_forced_process_actions: false,
+            protected_units: [
+                "sshd",
+                "ssh",
+                "systemd-networkd",
+                "NetworkManager",
+                "systemd-resolved",
+                "systemd-logind",
+                "dbus",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+            risk_policies: Vec::new(),
+            hosts: Vec::new(),
+            active_host: None,
+            core_host: None,
+            metrics_interval_secs: 2,
+            idle_metrics_interval_secs: 30,
+            backend_url: "http://127.0.0.1:8000".to_string(),
+            backend_slo_p95_ms: 800,
+            corpus_path: None,
+            corpora: Vec::new(),
+            corpus_ignore: ["node_modules", "__pycache__", "*.tmp", "*.swp", "*~"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            corpus_near_empty_bytes: 64,
+            corpus_max_file_bytes: 10 * 1024 * 1024,
+            mount_roots: Vec::new(),
+            terminal_command: None,
+            certificate_targets: Vec::new(),
+            certificate_warning_days: 14,
+            backup: None,
+            webhooks: Vec::new(),
+            self_rss_limit_mb: 512,
+            metrics_history_len: 1800,
+            retention: RetentionPolicy::default(),
+            onboarding_skipped: false,
+            system_uid_threshold: 1000,
+            units: Units::default(),
+            wol_port: 9,
+            wol_wait_secs: 180,
+            rate_limits_ms: ratelimit::default_limits(),
+            slow_command_ms: 500,
+            collectors: BTreeMap::new(),
+            collector_profiles: Vec::new(),
+            active_profile: None,
+            approver_identity: None,
+            approvers_required: BTreeMap::new(),
+            timezone: "local".to_string(),
+            time_format: TimeFormat::default(),
+            outcome_prompt_days: 3,
+            widget: WidgetSettings::default(),
+            desktop_notifications: DesktopNotifySettings::default(),
+            connection_dns_lookups: true,
+            liveness: LivenessSettings::default(),
+            remote_access: RemoteAccessSettings::default(),
+        }
+    }
+}
+
+pub struct SettingsStore {
+    path: PathBuf,
+    current: RwLock<Settings>,
+}
+
+impl SettingsStore {
+    pub fn load(path: PathBuf) -> Self {
+        let current = match std::fs::read_to_string(&path) {
+            Ok(text) => toml::from_str(&text).unwrap_or_else(|e| {
+                println!("[Halbert] Ignoring unreadable settings {}: {}", path.display(), e);
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        };
+        SettingsStore {
+            path,
+            current: RwLock::new(current),
+        }
+    }
+
+    pub fn get(&self) -> Settings {
+        self.current.read().unwrap().clone()
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.current.read().unwrap().mode
+    }
+
+    pub fn slow_command_ms(&self) -> u64 {
+        self.current.read().unwrap().slow_command_ms
+    }
+
+    // Apply a change under the write lock and persist it before it becomes
+    // visible, so a failed write leaves the old settings in place
+    pub fn update<F>(&self, change: F) -> CommandResult<Settings>
+    where
+        F: FnOnce(&Settings) -> CommandResult<Settings>,
+    {
+        self.replace(change).map(|(_, updated)| updated)
+    }
+
+    // As update, also returning the settings it replaced. They are read
+    // under the same lock, so two changes at once can't both start from
+    // the same old settings.
+    pub fn replace<F>(&self, change: F) -> CommandResult<(Settings, Settings)>
+    where
+        F: FnOnce(&Settings) -> CommandResult<Settings>,
+    {
+        let mut current = self.current.write().unwrap();
+        let updated = change(&current)?;
+        self.save(&updated)?;
+        settings_revisions::record(&self.revisions_dir(), &current, &updated);
+        let previous = std::mem::replace(&mut *current, updated.clone());
+        Ok((previous, updated))
+    }
+
+    pub fn revisions_dir(&self) -> PathBuf {
+        self.path.with_file_name("settings_revisions")
+    }
+
+    fn save(&self, settings: &Settings) -> CommandResult<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string_pretty(settings)
+            .map_err(|e| CommandError::Internal(format!("failed to encode settings: {}", e)))?;
+        std::fs::write(&self.path, text)?;
+        Ok(())
+    }
+}
+
+// Overlay the top-level keys of a JSON object onto the current settings.
+// Unknown keys are rejected so typos don't silently do nothing.
+pub fn apply_patch(current: &Settings, patch: &serde_json::Value) -> CommandResult<Settings> {
+    let patch = patch
+        .as_object()
+        .ok_or_else(|| CommandError::InvalidInput("settings patch must be an object".to_string()))?;
+    let mut value = serde_json::to_value(current).map_err(|e| CommandError::Internal(e.to_string()))?;
+    let fields = value
+        .as_object_mut()
+        .ok_or_else(|| CommandError::Internal("settings did not serialize to an object".to_string()))?;
+
+    for (key, v) in patch {
+        if !fields.contains_key(key) {
+            return Err(CommandError::InvalidInput(format!("unknown setting '{}'", key)));
+        }
+        fields.insert(key.clone(), v.clone());
+    }
+
+    serde_json::from_value(value).map_err(|e| CommandError::InvalidInput(e.to_string()))
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_settings(store: State<'_, SettingsStore>) -> Settings {
+    store.get()
+}
+
+// The corpus, backend and timezone are looked up from settings on every
+// call, so a new value takes effect at once; a backend request already
+// under way finishes against the settings it started with. Check the new
+// value first so a path that doesn't exist never replaces one that works.
+pub fn check_live_values(previous: &Settings, next: &Settings) -> CommandResult<()> {
+    corpora::check(previous, next)?;
+    if next.timezone != previous.timezone {
+        timestamps::parse_zone(&next.timezone)?;
+    }
+    let quiet_hours = &next.desktop_notifications.quiet_hours;
+    if quiet_hours != &previous.desktop_notifications.quiet_hours {
+        if let Some(quiet_hours) = quiet_hours {
+            quiet_hours.window()?;
+        }
+    }
+    if next.liveness != previous.liveness {
+        liveness::validate(&next.liveness)?;
+    }
+    if next.remote_access != previous.remote_access {
+        remote_access::validate(&next.remote_access)?;
+    }
+    if next.core_host != previous.core_host {
+        remote_core::check(next)?;
+    }
+    if next.mount_roots != previous.mount_roots {
+        sandbox::check_mount_roots(&next.mount_roots)?;
+    }
+    if next.backend_url != previous.backend_url {
+        let url = next.backend_url.trim();
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(CommandError::InvalidInput(
+                "backend_url must start with http:// or https://".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Clone)]
+pub struct RejectedChange {
+    pub error: String,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct FieldCheck {
+    pub field: String,
+    // "ok", "invalid" or "probe_failed"
+    pub status: String,
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SettingsValidation {
+    pub valid: bool,
+    pub fields: Vec<FieldCheck>,
+}
+
+// Tries out a value that can be checked live; None when there's nothing
+// to try
+fn probe_field(field: &str, next: &Settings) -> Option<Result<String, String>> {
+    match field {
+        "corpus_path" => {
+            let path = next.corpus_path.as_deref().filter(|p| !p.is_empty())?;
+            Some(match Path::new(path).is_dir() {
+                true => Ok(format!("{} exists", path)),
+                false => Err(format!("{} isn't a directory", path)),
+            })
+        }
+        "backend_url" => Some(match backend::endpoint(next).probe("/health", PROBE_TIMEOUT) {
+            Ok(status) if (200..300).contains(&status) => Ok(format!("/health answered {}", status)),
+            Ok(status) => Err(format!("/health answered {}", status)),
+            Err(e) => Err(e.to_string()),
+        }),
+        "terminal_command" => {
+            let program = next.terminal_command.as_deref()?.split_whitespace().next()?;
+            Some(match exec::find_in_path(program).is_some() || Path::new(program).is_file() {
+                true => Ok(format!("{} is installed", program)),
+                false => Err(format!("{} isn't installed", program)),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn field_check(field: &str, result: CommandResult<Option<Result<String, String>>>) -> FieldCheck {
+    let (status, detail) = match result {
+        Ok(None) => ("ok", None),
+        Ok(Some(Ok(detail))) => ("ok", Some(detail)),
+        Ok(Some(Err(detail))) => ("probe_failed", Some(detail)),
+        Err(e) => ("invalid", Some(e.to_string())),
+    };
+    FieldCheck {
+        field: field.to_string(),
+        status: status.to_string(),
+        detail,
+    }
+}
+
+// Each patched field on its own, as update_settings would check it, then
+// probed when `probe` is set; then the patch as a whole. Nothing is saved.
+pub fn validate_patch(current: &Settings, patch: &serde_json::Value, probe: bool) -> SettingsValidation {
+    let Some(fields) = patch.as_object() else {
+        let e = CommandError::InvalidInput("settings patch must be an object".to_string());
+        return SettingsValidation {
+            valid: false,
+            fields: vec![field_check("(patch)", Err(e))],
+        };
+    };
+    let mut checks: Vec<FieldCheck> = fields
+        .iter()
+        .map(|(key, value)| {
+            let single = serde_json::Value::Object([(key.clone(), value.clone())].into_iter().collect());
+            let result = readonly::ensure_settings_patch_allowed(current.mode, &single)
+                .and_then(|_| apply_patch(current, &single))
+                .and_then(|next| {
+                    check_live_values(current, &next)?;
+                    Ok(if probe { probe_field(key, &next) } else { None })
+                });
+            field_check(key, result)
+        })
+        .collect();
+    if checks.iter().all(|c| c.status == "ok") {
+        if let Err(e) = apply_patch(current, patch).and_then(|next| check_live_values(current, &next)) {
+            checks.push(field_check("(patch)", Err(e)));
+        }
+    }
+    SettingsValidation {
+        valid: checks.iter().all(|c| c.status == "ok"),
+        fields: checks,
+    }
+}
+
+// Dry run of update_settings with live probes
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn validate_settings(
+    store: State<'_, SettingsStore>,
+    patch: serde_json::Value,
+) -> CommandResult<SettingsValidation> {
+    Ok(validate_patch(&store.get(), &patch, true))
+}
+
+// The document listing and the corpus watcher follow a change to the
+// corpora. A root the watcher can't take puts the previous corpora back.
+pub fn reload_corpora(
+    store: &SettingsStore,
+    watcher: &CorpusWatcher,
+    limiter: &RateLimiter,
+    previous: &Settings,
+    updated: &Settings,
+) -> CommandResult<()> {
+    if updated.corpus_path == previous.corpus_path && updated.corpora == previous.corpora {
+        return Ok(());
+    }
+    limiter.invalidate("get_documents");
+    let Err(e) = watcher.retarget(updated) else {
+        return Ok(());
+    };
+    store.update(|current| {
+        let mut next = current.clone();
+        next.corpus_path = previous.corpus_path.clone();
+        next.corpora = previous.corpora.clone();
+        Ok(next)
+    })?;
+    println!("[Halbert] Kept the previous corpora: {}", e);
+    Err(e)
+}
+
+// Hot reload for a change that has been saved: announce it and refresh
+// what caches the old values. When the corpora had to be put back (see
+// reload_corpora) the rest of the change still applies, and the error is
+// announced as `settings://rejected` and returned.
+pub fn announce_change(app: &AppHandle, previous: &Settings, updated: &Settings) -> CommandResult<()> {
+    let store = app.state::<SettingsStore>();
+    let reloaded = reload_corpora(&store, &app.state(), &app.state(), previous, updated);
+    let updated = &match &reloaded {
+        Ok(()) => updated.clone(),
+        Err(e) => {
+            let _ = app.emit("settings://rejected", RejectedChange { error: e.to_string() });
+            store.get()
+        }
+    };
+    let _ = app.emit("settings://changed", updated);
+    // The cached self-check still describes the old corpus and backend
+    if updated.corpus_path != previous.corpus_path || updated.backend_url != previous.backend_url {
+        selfcheck::start(app.clone());
+    }
+    if updated.collectors != previous.collectors {
+        app.state::<CollectorRegistry>().reload();
+    }
+    #[cfg(feature = "gui")]
+    if updated.widget.click_through != previous.widget.click_through {
+        widget::apply_settings(app, &updated.widget);
+    }
+    let (now, before) = (&updated.remote_access, &previous.remote_access);
+    if now.listen != before.listen || now.changes_listen != before.changes_listen {
+        remote_access::apply(app, updated);
+    }
+    if updated.core_host != previous.core_host {
+        let _ = app.emit("core://changed", remote_core::status(updated, None));
+    }
+    reloaded
+}
+
+// A rejected patch leaves every setting as it was and is announced as
+// `settings://rejected`. With `probe`, the probes validate_settings runs
+// must pass too.
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn update_settings(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    patch: serde_json::Value,
+    probe: Option<bool>,
+) -> CommandResult<Settings> {
+    // The probes take a while, so they run against a copy; the change
+    // itself is checked again under the lock
+    let report = probe
+        .unwrap_or(false)
+        .then(|| validate_patch(&store.get(), &patch, true))
+        .filter(|report| !report.valid);
+    let result = match report {
+        Some(report) => {
+            let failed: Vec<String> = report
+                .fields
+                .iter()
+                .filter(|c| c.status != "ok")
+                .map(|c| format!("{}: {}", c.field, c.detail.as_deref().unwrap_or(&c.status)))
+                .collect();
+            Err(CommandError::InvalidInput(failed.join("; ")))
+        }
+        None => store.replace(|current| {
+            readonly::ensure_settings_patch_allowed(current.mode, &patch)?;
+            let next = apply_patch(current, &patch)?;
+            check_live_values(current, &next)?;
+            Ok(next)
+        }),
+    };
+    let (previous, updated) = match result {
+        Ok(change) => change,
+        Err(e) => {
+            let _ = app.emit("settings://rejected", RejectedChange { error: e.to_string() });
+            return Err(e);
+        }
+    };
+    announce_change(&app, &previous, &updated)?;
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::MockServer;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    // Corpora are only allowed under home and mount roots, so the temp dir
+    // is made one
+    fn store(dir: &Path, corpus: &Path) -> SettingsStore {
+        let store = SettingsStore::load(dir.join("settings.toml"));
+        store
+            .update(|current| {
+                let mut next = current.clone();
+                next.mount_roots = vec![std::env::temp_dir().canonicalize().unwrap().display().to_string()];
+                next.corpus_path = Some(corpus.display().to_string());
+                Ok(next)
+            })
+            .unwrap();
+        store
+    }
+
+    fn move_corpus(store: &SettingsStore, to: &Path) -> (Settings, Settings) {
+        store
+            .replace(|current| {
+                let mut next = apply_patch(current, &json!({ "corpus_path": to.display().to_string() }))?;
+                next.slow_command_ms = current.slow_command_ms + 1;
+                check_live_values(current, &next)?;
+                Ok(next)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn concurrent_changes_each_start_from_the_one_before() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(SettingsStore::load(dir.path().join("settings.toml")));
+        let start = store.slow_command_ms();
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let store = store.clone();
+                std::thread::spawn(move || {
+                    let (previous, updated) = store
+                        .replace(|current| {
+                            let mut next = current.clone();
+                            next.slow_command_ms = current.slow_command_ms + 1;
+                            Ok(next)
+                        })
+                        .unwrap();
+                    assert_eq!(updated.slow_command_ms, previous.slow_command_ms + 1);
+                    previous.slow_command_ms
+                })
+            })
+            .collect();
+        let mut seen: Vec<u64> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        seen.sort();
+        assert_eq!(seen, (start..start + 8).collect::<Vec<_>>());
+        assert_eq!(store.slow_command_ms(), start + 8);
+    }
+
+    #[test]
+    fn a_new_corpus_path_moves_the_watch_and_drops_the_cached_listing() {
+        let (dir, old, new) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let store = store(dir.path(), old.path());
+        let (watcher, limiter) = (CorpusWatcher::default(), RateLimiter::default());
+        watcher.retarget(&store.get()).unwrap();
+        let list = || limiter.call("get_documents", "primary", Duration::from_secs(60), || Ok(1)).unwrap().cached;
+        assert!(!list());
+        assert!(list());
+
+        let (previous, updated) = move_corpus(&store, new.path());
+        reload_corpora(&store, &watcher, &limiter, &previous, &updated).unwrap();
+        assert!(!list(), "the listing of the old corpus was reused");
+        std::fs::write(old.path().join("old.md"), "# Old\n").unwrap();
+        std::fs::write(new.path().join("new.md"), "# New\n").unwrap();
+        let sources = vec!["new.md".to_string()];
+        assert_eq!(watcher.poll(&store.get()), [(corpora::PRIMARY.to_string(), sources)]);
+    }
+
+    #[test]
+    fn a_corpus_the_watcher_cannot_take_is_put_back() {
+        let (dir, old, new) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let store = store(dir.path(), old.path());
+        let (watcher, limiter) = (CorpusWatcher::default(), RateLimiter::default());
+        watcher.retarget(&store.get()).unwrap();
+        let (previous, updated) = move_corpus(&store, new.path());
+        let new = new.path().to_path_buf();
+        std::fs::remove_dir(&new).unwrap();
+
+        assert!(reload_corpora(&store, &watcher, &limiter, &previous, &updated).is_err());
+        let now = store.get();
+        assert_eq!(now.corpus_path, previous.corpus_path);
+        assert_eq!(now.slow_command_ms, updated.slow_command_ms, "the rest of the change stands");
+        assert_eq!(watcher.roots(), [(corpora::PRIMARY.to_string(), old.path().canonicalize().unwrap())]);
+    }
+
+    #[test]
+    fn a_missing_corpus_path_is_rejected_before_it_is_saved() {
+        let (dir, old) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let store = store(dir.path(), old.path());
+        let missing = old.path().join("missing").display().to_string();
+        let result = store.update(|current| {
+            let next = apply_patch(current, &json!({ "corpus_path": missing }))?;
+            check_live_values(current, &next)?;
+            Ok(next)
+        });
+        assert!(matches!(result, Err(CommandError::NotFound(_))));
+        assert_eq!(store.get().corpus_path, Some(old.path().display().to_string()));
+    }
+
+    #[test]
+    fn a_new_backend_url_is_used_from_the_next_request() {
+        let dir = tempfile::tempdir().unwrap();
+        let (old, new) = (MockServer::start(|_| (200, json!({}))), MockServer::start(|_| (200, json!({}))));
+        let store = SettingsStore::load(dir.path().join("settings.toml"));
+        let patch = |url: &str| {
+            store.update(|current| {
+                let next = apply_patch(current, &json!({ "backend_url": url }))?;
+                check_live_values(current, &next)?;
+                Ok(next)
+            })
+        };
+        patch(&old.base_url).unwrap();
+        let in_flight = store.get();
+        patch(&new.base_url).unwrap();
+
+        assert_eq!(backend::endpoint(&store.get()).probe("/health", PROBE_TIMEOUT).unwrap(), 200);
+        assert_eq!(new.received().iter().map(|r| r.path.as_str()).collect::<Vec<_>>(), ["/health"]);
+        assert!(old.received().is_empty());
+        assert_eq!(backend::endpoint(&in_flight).probe("/health", PROBE_TIMEOUT).unwrap(), 200);
+        assert_eq!(old.received().len(), 1, "a request under way keeps the settings it started with");
+        assert!(patch("ftp://example.com").is_err());
+        assert_eq!(store.get().backend_url, new.base_url);
+    }
+}
@@ -0,0 +1,156 @@
+// Earlier versions of the settings, so a bad change is one call to undo.
+//
+// Every change saved through SettingsStore::update is also written to
+// settings_revisions/<id>.json next to settings.toml, with the top-level
+// keys it changed; the first one also keeps what was there before it.
+// Only the newest MAX_REVISIONS stay. Rolling back goes through the same
+// checks, save and hot reload as update_settings, so it is itself a new
+// revision and can be undone the same way.
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::app::{AppHandle, Emitter, State};
+use crate::error::{CommandError, CommandResult};
+use crate::settings::{self, Settings, SettingsStore};
+
+const MAX_REVISIONS: usize = 20;
+
+#[derive(Serialize, Deserialize)]
+struct StoredRevision {
+    id: String,
+    saved_at: String,
+    changed: Vec<String>,
+    settings: Settings,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct SettingsRevision {
+    pub id: String,
+    pub saved_at: String,
+    // Top-level settings this revision changed from the one before it
+    pub changed: Vec<String>,
+}
+
+pub fn changed_keys(previous: &Settings, next: &Settings) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(before)), Ok(serde_json::Value::Object(after))) =
+        (serde_json::to_value(previous), serde_json::to_value(next))
+    else {
+        return Vec::new();
+    };
+    after
+        .iter()
+        .filter(|(key, value)| before.get(*key) != Some(*value))
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+// Ids are timestamps, so they sort in the order they were saved
+fn check_id(id: &str) -> CommandResult<()> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.') {
+        return Err(CommandError::InvalidInput(format!("invalid settings revision id {}", id)));
+    }
+    Ok(())
+}
+
+fn write(dir: &Path, settings: &Settings, changed: Vec<String>) -> CommandResult<()> {
+    std::fs::create_dir_all(dir)?;
+    let now = chrono::Utc::now();
+    let stamp = now.format("%Y%m%dT%H%M%S%.3fZ").to_string();
+    let mut id = stamp.clone();
+    let mut n = 1;
+    while dir.join(format!("{}.json", id)).exists() {
+        n += 1;
+        id = format!("{}-{}", stamp, n);
+    }
+    let revision = StoredRevision {
+        id: id.clone(),
+        saved_at: now.to_rfc3339(),
+        changed,
+        settings: settings.clone(),
+    };
+    let text = serde_json::to_string_pretty(&revision).map_err(|e| CommandError::Internal(e.to_string()))?;
+    std::fs::write(dir.join(format!("{}.json", id)), text)?;
+    Ok(())
+}
+
+fn ids(dir: &Path) -> Vec<String> {
+    let mut ids: Vec<String> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|e| e.file_name().to_str()?.strip_suffix(".json").map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    ids.sort();
+    ids
+}
+
+// Called by SettingsStore::update once the change is saved. A revision
+// that can't be written is logged, not allowed to fail the change.
+pub fn record(dir: &Path, previous: &Settings, next: &Settings) {
+    let changed = changed_keys(previous, next);
+    if changed.is_empty() {
+        return;
+    }
+    let mut result = Ok(());
+    if ids(dir).is_empty() {
+        result = write(dir, previous, Vec::new());
+    }
+    if let Err(e) = result.and_then(|_| write(dir, next, changed)) {
+        println!("[Halbert] Couldn't keep a settings revision: {}", e);
+        return;
+    }
+    let all = ids(dir);
+    for id in &all[..all.len().saturating_sub(MAX_REVISIONS)] {
+        let _ = std::fs::remove_file(dir.join(format!("{}.json", id)));
+    }
+}
+
+fn read(dir: &Path, id: &str) -> CommandResult<StoredRevision> {
+    check_id(id)?;
+    let text = std::fs::read_to_string(dir.join(format!("{}.json", id)))
+        .map_err(|_| CommandError::NotFound(format!("settings revision {}", id)))?;
+    serde_json::from_str(&text)
+        .map_err(|e| CommandError::Internal(format!("settings revision {} is unreadable: {}", id, e)))
+}
+
+// Newest first
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn list_settings_revisions(store: State<'_, SettingsStore>) -> Vec<SettingsRevision> {
+    let dir = store.revisions_dir();
+    ids(&dir)
+        .iter()
+        .rev()
+        .filter_map(|id| read(&dir, id).ok())
+        .map(|r| SettingsRevision {
+            id: r.id,
+            saved_at: r.saved_at,
+            changed: r.changed,
+        })
+        .collect()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn rollback_settings(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    revision_id: String,
+) -> CommandResult<Settings> {
+    let restored = read(&store.revisions_dir(), &revision_id)?.settings;
+    let result = store.replace(|current| {
+        settings::check_live_values(current, &restored)?;
+        Ok(restored.clone())
+    });
+    match result {
+        Ok((previous, updated)) => {
+            println!("[Halbert] Settings rolled back to revision {}", revision_id);
+            settings::announce_change(&app, &previous, &updated)?;
+            Ok(updated)
+        }
+        Err(e) => {
+            let _ = app.emit("settings://rejected", settings::RejectedChange { error: e.to_string() });
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,236 @@
+// Orderly shutdown when the app exits.
+//
+// Steps run one after another, each on a helper thread, under a single
+// overall deadline. A step still running at the deadline is abandoned, the
+// rest are skipped, and the app exits anyway. `Coordinator` only sees names
+// and closures, so ordering and the timeout path can be driven with stand-in
+// steps. SIGTERM (systemctl stop, a plain kill) asks the app to exit, so it
+// goes through the same steps instead of dying mid-write.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::app::{async_runtime, AppHandle, Manager};
+use crate::backend_latency;
+use crate::db::Database;
+use crate::exec;
+use crate::jobs::JobManager;
+use crate::journal_follow::JournalFollower;
+use crate::liveness;
+
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+// How long job programs get after SIGTERM before they're killed
+const CHILD_GRACE: Duration = Duration::from_secs(2);
+
+// Exit can be requested more than once (window close, then process exit)
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+type Step = Box<dyn FnOnce() -> Result<(), String> + Send>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepOutcome {
+    Done,
+    Failed(String),
+    // Still running at the deadline
+    TimedOut,
+    // Never started because the deadline had passed
+    Skipped,
+}
+
+#[derive(Debug)]
+pub struct ShutdownReport {
+    // In the order the steps were added
+    pub steps: Vec<(String, StepOutcome)>,
+    pub elapsed: Duration,
+}
+
+impl ShutdownReport {
+    pub fn timed_out(&self) -> bool {
+        self.steps
+            .iter()
+            .any(|(_, outcome)| matches!(outcome, StepOutcome::TimedOut | StepOutcome::Skipped))
+    }
+}
+
+#[derive(Default)]
+pub struct Coordinator {
+    steps: Vec<(String, Step)>,
+}
+
+impl Coordinator {
+    pub fn step<F>(mut self, name: &str, work: F) -> Self
+    where
+        F: FnOnce() -> Result<(), String> + Send + 'static,
+    {
+        self.steps.push((name.to_string(), Box::new(work)));
+        self
+    }
+
+    pub fn run(self, timeout: Duration) -> ShutdownReport {
+        let started = Instant::now();
+        let deadline = started + timeout;
+        let mut steps = Vec::new();
+        for (name, work) in self.steps {
+            let outcome = if Instant::now() >= deadline {
+                StepOutcome::Skipped
+            } else {
+                match exec::bounded(deadline, work) {
+                    Some(Ok(())) => StepOutcome::Done,
+                    Some(Err(e)) => StepOutcome::Failed(e),
+                    None => StepOutcome::TimedOut,
+                }
+            };
+            steps.push((name, outcome));
+        }
+        ShutdownReport {
+            steps,
+            elapsed: started.elapsed(),
+        }
+    }
+}
+
+// Stop job programs before touching the database so nothing writes to it
+// mid-checkpoint
+fn coordinator(app: &AppHandle) -> Coordinator {
+    let jobs = app.state::<JobManager>().inner().clone();
+    let journal = app.clone();
+    let latency = app.clone();
+    let handle = app.clone();
+    Coordinator::default()
+        .step("jobs", move || {
+            let stopped = jobs.terminate_children(CHILD_GRACE);
+            if stopped > 0 {
+                println!("[Halbert] Stopped {} job process(es)", stopped);
+            }
+            Ok(())
+        })
+        .step("journal", move || {
+            journal.state::<JournalFollower>().stop_all();
+            Ok(())
+        })
+        .step("backend latency", move || {
+            backend_latency::flush(&latency.state::<Database>(), true)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })
+        .step("database", move || {
+            handle.state::<Database>().checkpoint().map_err(|e| e.to_string())
+        })
+}
+
+// Runs once; later calls return straight away
+pub fn run(app: &AppHandle) {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    liveness::stopping();
+    let report = coordinator(app).run(SHUTDOWN_TIMEOUT);
+    for (name, outcome) in &report.steps {
+        if *outcome != StepOutcome::Done {
+            println!("[Halbert] Shutdown step {}: {:?}", name, outcome);
+        }
+    }
+    println!(
+        "[Halbert] Shutdown {} in {} ms",
+        if report.timed_out() { "gave up" } else { "finished" },
+        report.elapsed.as_millis()
+    );
+}
+
+#[cfg(unix)]
+pub fn watch_signals(app: AppHandle) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    async_runtime::spawn(async move {
+        let mut terminate = match signal(SignalKind::terminate()) {
+            Ok(terminate) => terminate,
+            Err(e) => {
+                println!("[Halbert] Can't watch for SIGTERM, so it will skip the shutdown steps: {}", e);
+                return;
+            }
+        };
+        if terminate.recv().await.is_some() {
+            println!("[Halbert] SIGTERM received, exiting");
+            app.exit(0);
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn watch_signals(_app: AppHandle) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn recording(order: &Arc<Mutex<Vec<&'static str>>>, name: &'static str) -> impl FnOnce() -> Result<(), String> {
+        let order = order.clone();
+        move || {
+            order.lock().unwrap().push(name);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn steps_run_in_order_one_at_a_time() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let slow = order.clone();
+        let report = Coordinator::default()
+            .step("first", move || {
+                std::thread::sleep(Duration::from_millis(50));
+                slow.lock().unwrap().push("first");
+                Ok(())
+            })
+            .step("second", recording(&order, "second"))
+            .step("failing", || Err("disk full".to_string()))
+            .step("third", recording(&order, "third"))
+            .run(Duration::from_secs(5));
+        assert_eq!(*order.lock().unwrap(), ["first", "second", "third"]);
+        let outcomes: Vec<(&str, &StepOutcome)> = report.steps.iter().map(|(n, o)| (n.as_str(), o)).collect();
+        assert_eq!(
+            outcomes,
+            [
+                ("first", &StepOutcome::Done),
+                ("second", &StepOutcome::Done),
+                ("failing", &StepOutcome::Failed("disk full".to_string())),
+                ("third", &StepOutcome::Done),
+            ]
+        );
+        // A failure isn't a timeout
+        assert!(!report.timed_out());
+    }
+
+    #[test]
+    fn a_hung_step_is_abandoned_and_the_rest_skipped() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let started = Instant::now();
+        let report = Coordinator::default()
+            .step("quick", recording(&order, "quick"))
+            .step("hung", || {
+                std::thread::sleep(Duration::from_secs(30));
+                Ok(())
+            })
+            .step("after", recording(&order, "after"))
+            .run(Duration::from_millis(200));
+        assert!(started.elapsed() < Duration::from_secs(2), "took {:?}", started.elapsed());
+        assert_eq!(*order.lock().unwrap(), ["quick"]);
+        let outcomes: Vec<&StepOutcome> = report.steps.iter().map(|(_, o)| o).collect();
+        assert_eq!(outcomes, [&StepOutcome::Done, &StepOutcome::TimedOut, &StepOutcome::Skipped]);
+        assert!(report.timed_out());
+    }
+
+    #[test]
+    fn the_deadline_covers_all_steps_together() {
+        let step = || {
+            std::thread::sleep(Duration::from_millis(150));
+            Ok(())
+        };
+        let report = Coordinator::default()
+            .step("one", step)
+            .step("two", step)
+            .step("three", step)
+            .run(Duration::from_millis(250));
+        let outcomes: Vec<&StepOutcome> = report.steps.iter().map(|(_, o)| o).collect();
+        assert_eq!(outcomes, [&StepOutcome::Done, &StepOutcome::TimedOut, &StepOutcome::Skipped]);
+    }
+}
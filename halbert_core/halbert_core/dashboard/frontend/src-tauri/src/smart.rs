@@ -0,0 +1,246 @@
+// SMART temperature and wear per physical disk.
+//
+// smartctl is slow and usually needs root, so it only runs from a
+// background thread (every REFRESH_INTERVAL unless the smart collector
+// says otherwise); the metrics path reads the
+// cached results. The first run waits until get_system_metrics asks or
+// the first interval passes, so it stays out of startup. Mounts are traced back to their disk through /proc/mounts
+// and sysfs: a partition resolves to its parent, and a device-mapper device
+// (LVM, LUKS) to the disks under it. Mounts that don't resolve, or disks
+// smartctl couldn't read, keep the fields null.
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Condvar, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::app::{AppHandle, Manager};
+use crate::collectors::{self, CollectorRegistry};
+use crate::exec;
+use crate::settings::SettingsStore;
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30 * 60);
+const SMARTCTL_TIMEOUT: Duration = Duration::from_secs(15);
+// Virtual block devices with no SMART data
+const VIRTUAL_PREFIXES: &[&str] = &["loop", "ram", "zram", "dm-", "md", "sr", "nbd"];
+// ATA attributes whose normalized value is the life remaining, in percent
+const ATA_LIFE_ATTRIBUTES: &[&str] = &["Media_Wearout_Indicator", "Percent_Lifetime_Remain", "Wear_Leveling_Count"];
+
+#[derive(Clone, Copy, Default)]
+pub struct DiskHealth {
+    pub temperature_c: Option<f32>,
+    // Share of rated endurance used, 0-100 (may exceed 100 on NVMe)
+    pub wear_percent: Option<f32>,
+}
+
+// Latest reading per disk, keyed by kernel name ("nvme0n1", "sda")
+#[derive(Default)]
+pub struct SmartCache {
+    disks: RwLock<HashMap<String, DiskHealth>>,
+    // The first smartctl run waits for a reader or its first scheduled run
+    wanted: Mutex<bool>,
+    first_use: Condvar,
+}
+
+impl SmartCache {
+    pub fn want(&self) {
+        let mut wanted = self.wanted.lock().unwrap();
+        if !*wanted {
+            *wanted = true;
+            self.first_use.notify_all();
+        }
+    }
+
+    fn wait_first_use(&self, timeout: Duration) {
+        let wanted = self.wanted.lock().unwrap();
+        let _ = self.first_use.wait_timeout_while(wanted, timeout, |wanted| !*wanted);
+    }
+
+    // Fill in DiskInfo temperature and wear for local metrics. A mount on
+    // several disks (LVM across drives) reports the hottest and most worn.
+    pub fn annotate(&self, metrics: &mut crate::SystemMetrics) {
+        let disks = self.disks.read().unwrap();
+        if disks.is_empty() {
+            return;
+        }
+        let mounts = mount_devices();
+        for disk in &mut metrics.disks {
+            let Some(device) = mounts.get(&disk.mount_point) else {
+                continue;
+            };
+            let health: Vec<DiskHealth> = physical_disks(device).iter().filter_map(|name| disks.get(name).copied()).collect();
+            disk.temperature_c = health.iter().filter_map(|h| h.temperature_c).reduce(f32::max);
+            disk.wear_percent = health.iter().filter_map(|h| h.wear_percent).reduce(f32::max);
+        }
+    }
+}
+
+// /proc/mounts escapes spaces and friends as octal (\040)
+fn unescape_mount(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let digits: String = chars.by_ref().take(3).collect();
+        match u8::from_str_radix(&digits, 8) {
+            Ok(byte) => out.push(byte as char),
+            Err(_) => {
+                out.push('\\');
+                out.push_str(&digits);
+            }
+        }
+    }
+    out
+}
+
+// Mount point -> kernel block device name, for mounts backed by /dev nodes
+fn mount_devices() -> HashMap<String, String> {
+    let Ok(text) = std::fs::read_to_string("/proc/mounts") else {
+        return HashMap::new();
+    };
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = fields.next()?;
+            let mount = unescape_mount(fields.next()?);
+            if !device.starts_with("/dev/") {
+                return None;
+            }
+            // /dev/mapper/vg-root and /dev/disk/by-uuid/... are symlinks to the real node
+            let real = std::fs::canonicalize(device).ok()?;
+            Some((mount, real.file_name()?.to_string_lossy().into_owned()))
+        })
+        .collect()
+}
+
+// The whole disks under a block device. Depth-limited so a sysfs loop
+// can't hang the metrics path.
+fn physical_disks(name: &str) -> Vec<String> {
+    let mut disks = Vec::new();
+    let mut pending = vec![(name.to_string(), 0)];
+    while let Some((name, depth)) = pending.pop() {
+        if depth > 8 {
+            continue;
+        }
+        let sys = Path::new("/sys/class/block").join(&name);
+        if sys.join("partition").exists() {
+            // /sys/class/block/sda1 links into .../block/sda/sda1
+            if let Some(parent) = std::fs::canonicalize(&sys)
+                .ok()
+                .and_then(|p| p.parent().and_then(|p| p.file_name()).map(|n| n.to_string_lossy().into_owned()))
+            {
+                pending.push((parent, depth + 1));
+            }
+            continue;
+        }
+        let slaves: Vec<String> = std::fs::read_dir(sys.join("slaves"))
+            .map(|dir| dir.flatten().map(|e| e.file_name().to_string_lossy().into_owned()).collect())
+            .unwrap_or_default();
+        if slaves.is_empty() {
+            disks.push(name);
+        } else {
+            pending.extend(slaves.into_iter().map(|slave| (slave, depth + 1)));
+        }
+    }
+    disks.sort();
+    disks.dedup();
+    disks
+}
+
+// Physical disks worth asking smartctl about
+fn candidate_disks() -> Vec<String> {
+    let Ok(dir) = std::fs::read_dir("/sys/block") else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = dir
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .filter(|name| !VIRTUAL_PREFIXES.iter().any(|prefix| name.starts_with(prefix)))
+        .filter(|name| Path::new("/sys/block").join(name).join("device").exists())
+        .collect();
+    names.sort();
+    names
+}
+
+fn parse_health(report: &Value) -> DiskHealth {
+    let temperature_c = report.pointer("/temperature/current").and_then(Value::as_f64).map(|t| t as f32);
+    let nvme_wear = report
+        .pointer("/nvme_smart_health_information_log/percentage_used")
+        .and_then(Value::as_f64);
+    let ata_wear = || {
+        report
+            .pointer("/ata_smart_attributes/table")
+            .and_then(Value::as_array)?
+            .iter()
+            .find(|attr| {
+                attr.get("name")
+                    .and_then(Value::as_str)
+                    .is_some_and(|name| ATA_LIFE_ATTRIBUTES.contains(&name))
+            })
+            .and_then(|attr| attr.get("value")?.as_f64())
+            .map(|remaining| (100.0 - remaining).clamp(0.0, 100.0))
+    };
+    DiskHealth {
+        temperature_c,
+        wear_percent: nvme_wear.or_else(ata_wear).map(|w| w as f32),
+    }
+}
+
+// smartctl's exit status is a bit mask that is nonzero for plenty of
+// readable disks (e.g. a logged error), so only the JSON decides
+fn read_disk(name: &str) -> Option<DiskHealth> {
+    let device = format!("/dev/{}", name);
+    let output = exec::bounded(Instant::now() + SMARTCTL_TIMEOUT, move || {
+        std::process::Command::new("smartctl").args(["--json", "-i", "-A", &device]).output()
+    })?
+    .ok()?;
+    let report: Value = serde_json::from_slice(&output.stdout).ok()?;
+    let health = parse_health(&report);
+    (health.temperature_c.is_some() || health.wear_percent.is_some()).then_some(health)
+}
+
+fn refresh() -> HashMap<String, DiskHealth> {
+    candidate_disks()
+        .into_iter()
+        .filter_map(|name| read_disk(&name).map(|health| (name, health)))
+        .collect()
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        if exec::find_in_path("smartctl").is_none() {
+            println!("[Halbert] smartctl not found; disk temperature and wear are unavailable");
+            return;
+        }
+        let settings = app.state::<SettingsStore>().get();
+        let first_run = Duration::from_secs(collectors::interval_secs(&settings, "smart", REFRESH_INTERVAL.as_secs()));
+        app.state::<SmartCache>().wait_first_use(first_run);
+        let mut reported = false;
+        loop {
+            let registry = app.state::<CollectorRegistry>();
+            registry.wait_enabled(&app, "smart");
+            let disks = match registry.run("smart", || {
+                let disks = refresh();
+                if disks.is_empty() {
+                    return Err("smartctl returned no data (it usually needs root)");
+                }
+                Ok(disks)
+            }) {
+                Ok(disks) => disks,
+                Err(e) => {
+                    if !reported {
+                        println!("[Halbert] {}", e);
+                        reported = true;
+                    }
+                    HashMap::new()
+                }
+            };
+            *app.state::<SmartCache>().disks.write().unwrap() = disks;
+            let settings = app.state::<SettingsStore>().get();
+            registry.sleep(Duration::from_secs(collectors::interval_secs(&settings, "smart", REFRESH_INTERVAL.as_secs())));
+        }
+    });
+}
@@ -0,0 +1,700 @@
+// Filesystem snapshots, so an approved change has a rollback point.
+//
+// A mount can be snapshotted when it's btrfs (a read-only snapshot of the
+// mounted subvolume under <mount>/.halbert-snapshots), a ZFS dataset
+// (dataset@name) or an LVM thin volume (a thin snapshot beside it); the
+// self-check reports which mounts qualify. Job templates name mounts in
+// `snapshot_before`; the job snapshots each one before its first command
+// and fails if it can't, unless `snapshot_optional`. Snapshots Halbert takes
+// are all named halbert-<UTC time>-<label>, automatic ones halbert-auto-...,
+// and storage maintenance keeps only the newest
+// `retention.automatic_snapshots` automatic ones per mount. Every tool is
+// run with an argv built here from names Halbert made and devices the
+// system reported, never through a shell.
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use crate::app::State;
+use crate::audit;
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::selfcheck::Outcome;
+
+const PROC_MOUNTS: &str = "/proc/mounts";
+const BTRFS_DIR: &str = ".halbert-snapshots";
+const PREFIX: &str = "halbert-";
+const AUTO_PREFIX: &str = "halbert-auto-";
+const MAX_LABEL_LEN: usize = 40;
+// Snapshots are quick; a tool stuck on a busy pool is given up on
+const TOOL_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Which mounts a template snapshots before it runs
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnapshotPolicy {
+    pub snapshot_before: Vec<String>,
+    // Run anyway when a snapshot fails; otherwise the job fails first
+    pub snapshot_optional: bool,
+}
+
+impl SnapshotPolicy {
+    pub fn is_empty(&self) -> bool {
+        self.snapshot_before.is_empty()
+    }
+}
+
+// Where a mount's snapshots go
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+pub enum Target {
+    Btrfs { subvolume: String },
+    Zfs { dataset: String },
+    LvmThin { vg: String, lv: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MountCapability {
+    pub mount: String,
+    pub fs_type: String,
+    pub device: String,
+    pub target: Option<Target>,
+    // Why it can't be snapshotted
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub mount: String,
+    // "btrfs", "zfs" or "lvm_thin"
+    pub backend: String,
+    // What the tool calls it: a subvolume path, dataset@name or vg/lv
+    pub id: String,
+    pub name: String,
+    pub created_at: Option<String>,
+    pub automatic: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThinVolume {
+    pub vg: String,
+    pub lv: String,
+    // /dev/vg/lv and /dev/mapper/vg-lv
+    pub paths: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct SnapshotList {
+    pub capability: MountCapability,
+    // Halbert's snapshots only, oldest first
+    pub snapshots: Vec<Snapshot>,
+}
+
+// /proc/mounts escapes spaces and friends as octal (\040)
+fn unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(at) = rest.find('\\') {
+        out.push_str(&rest[..at]);
+        let code = rest.get(at + 1..at + 4).and_then(|d| u8::from_str_radix(d, 8).ok());
+        match code {
+            Some(byte) => {
+                out.push(byte as char);
+                rest = &rest[at + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[at + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// (device, mount point, fs type) for every mount backed by a device or a
+// ZFS dataset; pseudo filesystems are left out
+pub fn parse_mounts(text: &str) -> Vec<(String, String, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = unescape(fields.next()?);
+            let mount = unescape(fields.next()?);
+            let fs_type = fields.next()?.to_string();
+            (device.starts_with('/') || fs_type == "zfs").then_some((device, mount, fs_type))
+        })
+        .collect()
+}
+
+// `lvs --noheadings --separator '|' -o vg_name,lv_name,pool_lv,lv_path,lv_dm_path`;
+// only volumes in a thin pool can take thin snapshots
+pub fn parse_lvs(text: &str) -> Vec<ThinVolume> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.trim().split('|').map(str::trim).collect();
+            let [vg, lv, pool, paths @ ..] = fields.as_slice() else {
+                return None;
+            };
+            if pool.is_empty() || vg.is_empty() || lv.is_empty() {
+                return None;
+            }
+            Some(ThinVolume {
+                vg: vg.to_string(),
+                lv: lv.to_string(),
+                paths: paths.iter().filter(|p| !p.is_empty()).map(|p| p.to_string()).collect(),
+            })
+        })
+        .collect()
+}
+
+pub fn capability(device: &str, mount: &str, fs_type: &str, thin: &[ThinVolume]) -> MountCapability {
+    let (target, reason) = match fs_type {
+        "btrfs" => (
+            Some(Target::Btrfs {
+                subvolume: mount.to_string(),
+            }),
+            None,
+        ),
+        "zfs" => (
+            Some(Target::Zfs {
+                dataset: device.to_string(),
+            }),
+            None,
+        ),
+        _ => match thin.iter().find(|t| t.paths.iter().any(|p| p == device)) {
+            Some(volume) => (
+                Some(Target::LvmThin {
+                    vg: volume.vg.clone(),
+                    lv: volume.lv.clone(),
+                }),
+                None,
+            ),
+            None => (None, Some(format!("{} on {} isn't btrfs, ZFS or an LVM thin volume", fs_type, device))),
+        },
+    };
+    MountCapability {
+        mount: mount.to_string(),
+        fs_type: fs_type.to_string(),
+        device: device.to_string(),
+        target,
+        reason,
+    }
+}
+
+// "Before upgrade!" -> "before-upgrade"; empty when nothing usable is left
+pub fn clean_label(label: &str) -> String {
+    let mapped: String = label
+        .trim()
+        .to_ascii_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '-' })
+        .collect();
+    let mut cleaned = String::new();
+    for part in mapped.split('-').filter(|p| !p.is_empty()) {
+        if !cleaned.is_empty() {
+            cleaned.push('-');
+        }
+        cleaned.push_str(part);
+    }
+    cleaned.chars().take(MAX_LABEL_LEN).collect::<String>().trim_end_matches('-').to_string()
+}
+
+// Sorts by time, since the time comes first
+pub fn snapshot_name(label: &str, automatic: bool, at: chrono::DateTime<chrono::Utc>) -> String {
+    let prefix = if automatic { AUTO_PREFIX } else { PREFIX };
+    let label = match clean_label(label) {
+        label if label.is_empty() => "manual".to_string(),
+        label => label,
+    };
+    format!("{}{}-{}", prefix, at.format("%Y%m%dT%H%M%SZ"), label)
+}
+
+// The time in a name snapshot_name made
+fn name_time(name: &str) -> Option<String> {
+    let rest = name.strip_prefix(AUTO_PREFIX).or_else(|| name.strip_prefix(PREFIX))?;
+    let stamp = rest.get(..16)?;
+    chrono::NaiveDateTime::parse_from_str(stamp, "%Y%m%dT%H%M%SZ")
+        .ok()
+        .map(|t| t.and_utc().to_rfc3339())
+}
+
+// Values from the system that would read as an option or split an argument
+fn plain(value: &str) -> CommandResult<&str> {
+    if value.is_empty() || value.starts_with('-') || value.contains(char::is_whitespace) {
+        return Err(CommandError::InvalidInput(format!("'{}' can't be passed to a snapshot tool", value)));
+    }
+    Ok(value)
+}
+
+fn btrfs_dir(subvolume: &str) -> String {
+    format!("{}/{}", subvolume.trim_end_matches('/'), BTRFS_DIR)
+}
+
+fn backend_name(target: &Target) -> &'static str {
+    match target {
+        Target::Btrfs { .. } => "btrfs",
+        Target::Zfs { .. } => "zfs",
+        Target::LvmThin { .. } => "lvm_thin",
+    }
+}
+
+// The snapshot's id and the argv that makes it
+pub fn create_args(target: &Target, name: &str) -> CommandResult<(String, Vec<String>)> {
+    let name = plain(name)?;
+    let argv = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+    Ok(match target {
+        Target::Btrfs { subvolume } => {
+            let id = format!("{}/{}", btrfs_dir(subvolume), name);
+            let args = argv(&["btrfs", "subvolume", "snapshot", "-r", "--", plain(subvolume)?, &id]);
+            (id, args)
+        }
+        Target::Zfs { dataset } => {
+            let id = format!("{}@{}", plain(dataset)?, name);
+            let args = argv(&["zfs", "snapshot", &id]);
+            (id, args)
+        }
+        Target::LvmThin { vg, lv } => {
+            let origin = format!("{}/{}", plain(vg)?, plain(lv)?);
+            let args = argv(&["lvcreate", "--snapshot", "--name", name, &origin]);
+            (format!("{}/{}", vg, name), args)
+        }
+    })
+}
+
+pub fn list_args(target: &Target) -> CommandResult<Vec<String>> {
+    let argv = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+    Ok(match target {
+        Target::Btrfs { subvolume } => argv(&["btrfs", "subvolume", "list", "-s", "-o", plain(subvolume)?]),
+        Target::Zfs { dataset } => argv(&[
+            "zfs", "list", "-H", "-p", "-t", "snapshot", "-d", "1", "-o", "name,creation", plain(dataset)?,
+        ]),
+        Target::LvmThin { vg, .. } => argv(&[
+            "lvs", "--noheadings", "--separator", "|", "-o", "lv_name,origin,lv_time", plain(vg)?,
+        ]),
+    })
+}
+
+fn delete_args(target: &Target, snapshot: &Snapshot) -> CommandResult<Vec<String>> {
+    let argv = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+    // Only names Halbert made are ever deleted
+    if !snapshot.name.starts_with(PREFIX) {
+        return Err(CommandError::PermissionDenied(format!("{} wasn't made by Halbert", snapshot.id)));
+    }
+    Ok(match target {
+        Target::Btrfs { .. } => argv(&["btrfs", "subvolume", "delete", "--", plain(&snapshot.id)?]),
+        Target::Zfs { .. } if snapshot.id.contains('@') => argv(&["zfs", "destroy", plain(&snapshot.id)?]),
+        Target::Zfs { .. } => return Err(CommandError::Internal(format!("{} isn't a snapshot", snapshot.id))),
+        Target::LvmThin { .. } => argv(&["lvremove", "--yes", plain(&snapshot.id)?]),
+    })
+}
+
+fn snapshot(mount: &str, target: &Target, id: String, name: &str, created_at: Option<String>) -> Snapshot {
+    Snapshot {
+        mount: mount.to_string(),
+        backend: backend_name(target).to_string(),
+        id,
+        automatic: name.starts_with(AUTO_PREFIX),
+        created_at: created_at.or_else(|| name_time(name)),
+        name: name.to_string(),
+    }
+}
+
+// `btrfs subvolume list -s -o <mount>` lines:
+//   ID 260 gen 15 cgen 15 top level 256 otime 2024-05-01 10:00:00 path @/.halbert-snapshots/halbert-...
+pub fn parse_btrfs_list(text: &str, mount: &str, target: &Target) -> Vec<Snapshot> {
+    let Target::Btrfs { subvolume } = target else {
+        return Vec::new();
+    };
+    text.lines()
+        .filter_map(|line| {
+            let (_, path) = line.split_once(" path ")?;
+            let (dir, name) = path.trim().rsplit_once('/')?;
+            if dir.rsplit('/').next() != Some(BTRFS_DIR) || !name.starts_with(PREFIX) {
+                return None;
+            }
+            let id = format!("{}/{}", btrfs_dir(subvolume), name);
+            Some(snapshot(mount, target, id, name, None))
+        })
+        .collect()
+}
+
+// `zfs list -H -p -o name,creation` lines: "tank/home@halbert-...\t1714557600"
+pub fn parse_zfs_list(text: &str, mount: &str, target: &Target) -> Vec<Snapshot> {
+    text.lines()
+        .filter_map(|line| {
+            let (id, creation) = line.trim().split_once('\t')?;
+            let (_, name) = id.split_once('@')?;
+            if !name.starts_with(PREFIX) {
+                return None;
+            }
+            let created_at = creation
+                .trim()
+                .parse::<i64>()
+                .ok()
+                .and_then(|at| chrono::DateTime::from_timestamp(at, 0))
+                .map(|at| at.to_rfc3339());
+            Some(snapshot(mount, target, id.to_string(), name, created_at))
+        })
+        .collect()
+}
+
+// `lvs --separator '|' -o lv_name,origin,lv_time` lines:
+//   "halbert-...|root|2024-05-01 10:00:00 +0000"
+pub fn parse_lvs_snapshots(text: &str, mount: &str, target: &Target) -> Vec<Snapshot> {
+    let Target::LvmThin { vg, lv } = target else {
+        return Vec::new();
+    };
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.trim().split('|').map(str::trim).collect();
+            let [name, origin, time, ..] = fields.as_slice() else {
+                return None;
+            };
+            if origin != lv || !name.starts_with(PREFIX) {
+                return None;
+            }
+            let created_at = chrono::DateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S %z")
+                .ok()
+                .map(|at| at.with_timezone(&chrono::Utc).to_rfc3339());
+            Some(snapshot(mount, target, format!("{}/{}", vg, name), name, created_at))
+        })
+        .collect()
+}
+
+// The automatic snapshots past the newest `keep`, oldest first
+pub fn beyond_retention(snapshots: &[Snapshot], keep: usize) -> Vec<Snapshot> {
+    let mut automatic: Vec<&Snapshot> = snapshots.iter().filter(|s| s.automatic).collect();
+    automatic.sort_by(|a, b| a.name.cmp(&b.name));
+    let excess = automatic.len().saturating_sub(keep);
+    automatic.into_iter().take(excess).cloned().collect()
+}
+
+// argv[0] is the program. Stdout on success, else what the tool said.
+fn run(argv: &[String]) -> CommandResult<String> {
+    let (program, args) = argv.split_first().ok_or_else(|| CommandError::Internal("empty argv".to_string()))?;
+    if exec::find_in_path(program).is_none() {
+        return Err(CommandError::ToolMissing(format!("{} is not installed", program)));
+    }
+    let mut command = Command::new(program);
+    command.args(args);
+    let output = exec::bounded(Instant::now() + TOOL_TIMEOUT, move || command.output())
+        .ok_or_else(|| CommandError::Internal(format!("{} didn't finish in {}s", program, TOOL_TIMEOUT.as_secs())))?
+        .map_err(|e| CommandError::Io(format!("{}: {}", program, e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let message = format!("{} exited with {}: {}", program, output.status, stderr);
+        return Err(match stderr.to_ascii_lowercase() {
+            s if s.contains("permission denied") || s.contains("must be run as root") => {
+                CommandError::PermissionDenied(message)
+            }
+            _ => CommandError::Internal(message),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn thin_volumes() -> Vec<ThinVolume> {
+    exec::stdout(
+        "lvs",
+        &["--noheadings", "--separator", "|", "-o", "vg_name,lv_name,pool_lv,lv_path,lv_dm_path"],
+    )
+    .map(|text| parse_lvs(&text))
+    .unwrap_or_default()
+}
+
+// Every device-backed mount and whether it can be snapshotted
+pub fn detect() -> Vec<MountCapability> {
+    let Ok(text) = std::fs::read_to_string(PROC_MOUNTS) else {
+        return Vec::new();
+    };
+    let mounts = parse_mounts(&text);
+    // lvs is only worth running when something is on device-mapper
+    let thin = if mounts.iter().any(|(device, _, _)| device.starts_with("/dev/mapper/")) {
+        thin_volumes()
+    } else {
+        Vec::new()
+    };
+    mounts
+        .iter()
+        .map(|(device, mount, fs_type)| capability(device, mount, fs_type, &thin))
+        .collect()
+}
+
+fn mount_target(mount: &str) -> CommandResult<(MountCapability, Target)> {
+    let mount = mount.trim();
+    let mount = if mount.len() > 1 { mount.trim_end_matches('/') } else { mount };
+    let capability = detect()
+        .into_iter()
+        .rev()
+        .find(|c| c.mount == mount)
+        .ok_or_else(|| CommandError::NotFound(format!("{} is not a mount point", mount)))?;
+    match capability.target.clone() {
+        Some(target) => Ok((capability, target)),
+        None => Err(CommandError::NotSupported(capability.reason.clone().unwrap_or_default())),
+    }
+}
+
+pub fn create(mount: &str, label: &str, automatic: bool) -> CommandResult<Snapshot> {
+    let (capability, target) = mount_target(mount)?;
+    let name = snapshot_name(label, automatic, chrono::Utc::now());
+    let (id, argv) = create_args(&target, &name)?;
+    if let Target::Btrfs { subvolume } = &target {
+        std::fs::create_dir_all(btrfs_dir(subvolume))
+            .map_err(|e| CommandError::Io(format!("{}: {}", btrfs_dir(subvolume), e)))?;
+    }
+    run(&argv)?;
+    println!("[Halbert] Snapshot {} of {} created", id, capability.mount);
+    Ok(snapshot(&capability.mount, &target, id, &name, Some(chrono::Utc::now().to_rfc3339())))
+}
+
+pub fn list(mount: &str) -> CommandResult<SnapshotList> {
+    let (capability, target) = mount_target(mount)?;
+    let text = run(&list_args(&target)?)?;
+    let mut snapshots = match &target {
+        Target::Btrfs { .. } => parse_btrfs_list(&text, &capability.mount, &target),
+        Target::Zfs { .. } => parse_zfs_list(&text, &capability.mount, &target),
+        Target::LvmThin { .. } => parse_lvs_snapshots(&text, &capability.mount, &target),
+    };
+    snapshots.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(SnapshotList { capability, snapshots })
+}
+
+// Before a template job's first command; the snapshots made, or why the
+// job shouldn't go on
+pub fn before_job(policy: &SnapshotPolicy, label: &str, log: &dyn Fn(String)) -> Result<Vec<Snapshot>, String> {
+    let mut made = Vec::new();
+    for mount in &policy.snapshot_before {
+        match create(mount, label, true) {
+            Ok(snapshot) => {
+                log(format!("Snapshot of {} taken: {}", mount, snapshot.id));
+                made.push(snapshot);
+            }
+            Err(e) if policy.snapshot_optional => log(format!("No snapshot of {} ({}); going on anyway", mount, e)),
+            Err(e) => return Err(format!("couldn't snapshot {} before running: {}", mount, e)),
+        }
+    }
+    Ok(made)
+}
+
+// Storage maintenance: deletes automatic snapshots past the newest `keep`
+// on every snapshot-capable mount; 0 keeps them all. Returns how many went.
+pub fn enforce_retention(keep: u32) -> usize {
+    if keep == 0 {
+        return 0;
+    }
+    let mut deleted = 0;
+    for capability in detect() {
+        let Some(target) = capability.target.clone() else {
+            continue;
+        };
+        // Without the tool there's nothing Halbert could have made here
+        let Ok(listed) = list(&capability.mount) else {
+            continue;
+        };
+        for old in beyond_retention(&listed.snapshots, keep as usize) {
+            match delete_args(&target, &old).and_then(|argv| run(&argv)) {
+                Ok(_) => deleted += 1,
+                Err(e) => println!("[Halbert] Couldn't delete old snapshot {}: {}", old.id, e),
+            }
+        }
+    }
+    deleted
+}
+
+pub fn check_policy(policy: &SnapshotPolicy) -> CommandResult<()> {
+    for (i, mount) in policy.snapshot_before.iter().enumerate() {
+        if !mount.starts_with('/') {
+            return Err(CommandError::InvalidInput(format!("snapshot mount '{}' must be an absolute path", mount)));
+        }
+        if policy.snapshot_before[..i].contains(mount) {
+            return Err(CommandError::InvalidInput(format!("snapshot mount {} is listed twice", mount)));
+        }
+    }
+    Ok(())
+}
+
+// For the self-check
+pub fn probe() -> Outcome {
+    let capable: Vec<String> = detect()
+        .iter()
+        .filter_map(|c| c.target.as_ref().map(|t| format!("{} ({})", c.mount, backend_name(t))))
+        .collect();
+    match capable.as_slice() {
+        [] => Outcome::missing(
+            "no mount is on btrfs, ZFS or an LVM thin volume",
+            "Snapshots before approved actions need one of those filesystems",
+        ),
+        _ => Outcome::ok(capable.join(", ")),
+    }
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn list_snapshots(mount: String) -> CommandResult<SnapshotList> {
+    list(&mount)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn create_snapshot(
+    db: State<'_, Database>,
+    mount: String,
+    label: String,
+) -> CommandResult<Snapshot> {
+    let snapshot = create(&mount, &label, false)?;
+    let detail = serde_json::json!({ "mount": snapshot.mount, "label": label });
+    if let Err(e) = audit::record(&db, &audit::local_actor(), "snapshot.create", &snapshot.id, &detail) {
+        println!("[Halbert] Failed to audit snapshot {}: {}", snapshot.id, e);
+    }
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTS: &str = "\
+sysfs /sys sysfs rw,nosuid,nodev,noexec,relatime 0 0
+proc /proc proc rw,nosuid,nodev,noexec,relatime 0 0
+/dev/nvme0n1p2 / btrfs rw,relatime,ssd,subvol=/@ 0 0
+/dev/mapper/vg0-data /srv/data ext4 rw,relatime 0 0
+/dev/sdb1 /media/usb\\040stick vfat rw,relatime 0 0
+tank/home /home zfs rw,xattr,noacl 0 0
+tmpfs /run tmpfs rw,nosuid,nodev 0 0
+";
+
+    const LVS: &str = "  vg0|data|pool0|/dev/vg0/data|/dev/mapper/vg0-data
+  vg0|swap||/dev/vg0/swap|/dev/mapper/vg0-swap
+  vg0|pool0||| 
+";
+
+    fn thin() -> Vec<ThinVolume> {
+        parse_lvs(LVS)
+    }
+
+    #[test]
+    fn mounts_keep_devices_and_datasets_unescaped() {
+        let mounts = parse_mounts(MOUNTS);
+        let points: Vec<&str> = mounts.iter().map(|(_, mount, _)| mount.as_str()).collect();
+        assert_eq!(points, ["/", "/srv/data", "/media/usb stick", "/home"]);
+        assert_eq!(mounts[3], ("tank/home".to_string(), "/home".to_string(), "zfs".to_string()));
+        assert_eq!(unescape("a\\134b\\0"), "a\\b\\0");
+    }
+
+    #[test]
+    fn only_volumes_in_a_thin_pool_are_thin() {
+        assert_eq!(
+            thin(),
+            [ThinVolume {
+                vg: "vg0".to_string(),
+                lv: "data".to_string(),
+                paths: vec!["/dev/vg0/data".to_string(), "/dev/mapper/vg0-data".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn capability_follows_the_filesystem_or_the_thin_volume() {
+        let thin = thin();
+        let targets: Vec<Option<Target>> = parse_mounts(MOUNTS)
+            .iter()
+            .map(|(device, mount, fs_type)| capability(device, mount, fs_type, &thin).target)
+            .collect();
+        assert_eq!(
+            targets,
+            [
+                Some(Target::Btrfs { subvolume: "/".to_string() }),
+                Some(Target::LvmThin { vg: "vg0".to_string(), lv: "data".to_string() }),
+                None,
+                Some(Target::Zfs { dataset: "tank/home".to_string() }),
+            ]
+        );
+        let usb = capability("/dev/sdb1", "/media/usb stick", "vfat", &thin);
+        assert_eq!(usb.reason.as_deref(), Some("vfat on /dev/sdb1 isn't btrfs, ZFS or an LVM thin volume"));
+    }
+
+    #[test]
+    fn btrfs_lists_only_halbert_snapshots_in_its_directory() {
+        let target = Target::Btrfs { subvolume: "/".to_string() };
+        let text = "\
+ID 260 gen 15 cgen 15 top level 256 otime 2024-05-01 10:00:00 path @/.halbert-snapshots/halbert-20240501T100000Z-before-upgrade
+ID 261 gen 16 cgen 16 top level 256 otime 2024-05-02 03:00:00 path @/.halbert-snapshots/halbert-auto-20240502T030000Z-nightly
+ID 262 gen 17 cgen 17 top level 256 otime 2024-05-02 04:00:00 path @/.snapshots/halbert-20240502T040000Z-elsewhere
+ID 263 gen 18 cgen 18 top level 256 otime 2024-05-02 05:00:00 path @/.halbert-snapshots/snapper-1
+";
+        let snapshots = parse_btrfs_list(text, "/", &target);
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].id, "/.halbert-snapshots/halbert-20240501T100000Z-before-upgrade");
+        assert_eq!(snapshots[0].created_at.as_deref(), Some("2024-05-01T10:00:00+00:00"));
+        assert!(!snapshots[0].automatic && snapshots[1].automatic);
+        assert_eq!(snapshots[1].backend, "btrfs");
+        assert!(parse_btrfs_list(text, "/", &Target::Zfs { dataset: "tank".to_string() }).is_empty());
+    }
+
+    #[test]
+    fn zfs_lists_take_the_creation_time() {
+        let target = Target::Zfs { dataset: "tank/home".to_string() };
+        let text = "tank/home@halbert-20240501T100000Z-manual\t1714557600
+tank/home@zfs-auto-snap_daily\t1714557000
+tank/home@halbert-auto-20240502T030000Z-nightly\tnot-a-number
+";
+        let snapshots = parse_zfs_list(text, "/home", &target);
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].id, "tank/home@halbert-20240501T100000Z-manual");
+        assert_eq!(snapshots[0].created_at.as_deref(), Some("2024-05-01T10:00:00+00:00"));
+        // Falls back to the time in the name
+        assert_eq!(snapshots[1].created_at.as_deref(), Some("2024-05-02T03:00:00+00:00"));
+    }
+
+    #[test]
+    fn lvs_snapshots_must_be_of_the_mounted_volume() {
+        let target = Target::LvmThin { vg: "vg0".to_string(), lv: "data".to_string() };
+        let text = "  halbert-20240501T100000Z-manual|data|2024-05-01 12:00:00 +0200
+  halbert-20240501T110000Z-manual|root|2024-05-01 11:00:00 +0000
+  data|pool0|2024-01-01 00:00:00 +0000
+";
+        let snapshots = parse_lvs_snapshots(text, "/srv/data", &target);
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].id, "vg0/halbert-20240501T100000Z-manual");
+        assert_eq!(snapshots[0].created_at.as_deref(), Some("2024-05-01T10:00:00+00:00"));
+    }
+
+    #[test]
+    fn names_sort_by_time_and_labels_are_cleaned() {
+        let at = chrono::DateTime::parse_from_rfc3339("2024-05-01T10:00:00Z").unwrap().with_timezone(&chrono::Utc);
+        assert_eq!(snapshot_name("Before upgrade!", false, at), "halbert-20240501T100000Z-before-upgrade");
+        assert_eq!(snapshot_name("  ", true, at), "halbert-auto-20240501T100000Z-manual");
+        assert_eq!(clean_label(&"x".repeat(100)).len(), MAX_LABEL_LEN);
+        assert_eq!(name_time("halbert-auto-20240501T100000Z-x").as_deref(), Some("2024-05-01T10:00:00+00:00"));
+        assert!(name_time("halbert-garbage").is_none());
+    }
+
+    #[test]
+    fn retention_drops_the_oldest_automatic_ones() {
+        let target = Target::Zfs { dataset: "tank".to_string() };
+        let named = |name: &str| snapshot("/tank", &target, format!("tank@{}", name), name, None);
+        let snapshots = [
+            named("halbert-auto-20240503T030000Z-nightly"),
+            named("halbert-20240101T000000Z-manual"),
+            named("halbert-auto-20240501T030000Z-nightly"),
+            named("halbert-auto-20240502T030000Z-nightly"),
+        ];
+        let dropped: Vec<String> = beyond_retention(&snapshots, 2).into_iter().map(|s| s.name).collect();
+        assert_eq!(dropped, ["halbert-auto-20240501T030000Z-nightly"]);
+        assert!(beyond_retention(&snapshots, 5).is_empty());
+    }
+
+    #[test]
+    fn argv_refuses_values_that_read_as_options() {
+        let target = Target::LvmThin { vg: "-vg".to_string(), lv: "data".to_string() };
+        assert!(matches!(create_args(&target, "halbert-x"), Err(CommandError::InvalidInput(_))));
+        let target = Target::Btrfs { subvolume: "/srv/my data".to_string() };
+        assert!(create_args(&target, "halbert-x").is_err());
+        let target = Target::Zfs { dataset: "tank".to_string() };
+        assert_eq!(create_args(&target, "halbert-x").unwrap().1, ["zfs", "snapshot", "tank@halbert-x"]);
+
+        let foreign = snapshot("/tank", &target, "tank@nightly".to_string(), "nightly", None);
+        assert!(matches!(delete_args(&target, &foreign), Err(CommandError::PermissionDenied(_))));
+        let dataset = snapshot("/tank", &target, "tank".to_string(), "halbert-x", None);
+        assert!(delete_args(&target, &dataset).is_err());
+    }
+}
@@ -0,0 +1,657 @@
+// Basic metrics from hosts reached over SSH instead of a Halbert agent.
+//
+// A host with `transport = "ssh"` is polled by the system OpenSSH client,
+// which runs REMOTE_COMMAND and nothing else: /proc/stat, /proc/meminfo,
+// `df -P` and `uptime`, split by a marker line. The command is a constant;
+// nothing a user or a host entry says ends up in it. On unix, connections
+// are shared through a ControlMaster socket kept open between polls, with
+// keepalives so a dead link is noticed. Unknown host keys are accepted on
+// first use and checked after that; prompts are never shown (BatchMode).
+// Parsed samples are kept per host and turned into SystemMetrics when
+// asked, in the current units. CPU use needs two /proc/stat readings, so
+// it reads 0 until the second poll. Status changes are announced as
+// `hosts://status`. Everything but metrics is unavailable for these hosts.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::app::{AppHandle, Emitter, Manager};
+use crate::collectors::{self, CollectorRegistry};
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+use crate::hosts::{self, HostEntry};
+use crate::sandbox;
+use crate::settings::{Settings, SettingsStore};
+use crate::units::Units;
+use crate::{DiskInfo, SystemMetrics};
+
+const MARKER: &str = "@@halbert@@";
+const REMOTE_COMMAND: &str = "cat /proc/stat; echo @@halbert@@; cat /proc/meminfo; echo @@halbert@@; \
+                              env LC_ALL=C df -P; echo @@halbert@@; env LC_ALL=C uptime";
+const DEFAULT_INTERVAL_SECS: u64 = 30;
+const CONNECT_TIMEOUT_SECS: u64 = 10;
+// Whole round trip, connection included
+const POLL_TIMEOUT: Duration = Duration::from_secs(30);
+// How long the shared connection outlives the last poll
+const CONTROL_PERSIST_SECS: u64 = 300;
+// Filesystems df lists that aren't disks
+const PSEUDO_FILESYSTEMS: &[&str] = &["tmpfs", "devtmpfs", "udev", "overlay", "none", "shm", "efivarfs"];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    // A Halbert agent at base_url
+    #[default]
+    Http,
+    Ssh,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SshTarget {
+    pub address: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub user: String,
+    pub key_path: String,
+}
+
+fn default_port() -> u16 {
+    22
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CpuTimes {
+    pub busy: u64,
+    pub total: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DfEntry {
+    pub filesystem: String,
+    pub total_bytes: u64,
+    pub used_bytes: u64,
+    pub available_bytes: u64,
+    pub mount_point: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemoteSample {
+    pub cpu: CpuTimes,
+    pub memory_total_bytes: u64,
+    pub memory_available_bytes: u64,
+    pub disks: Vec<DfEntry>,
+    pub uptime_seconds: u64,
+}
+
+// First "cpu" line of /proc/stat: user nice system idle iowait irq softirq
+// steal, in ticks. Guest time is already counted in user and nice. Old
+// kernels stop after irq/softirq.
+pub fn parse_proc_stat(text: &str) -> Option<CpuTimes> {
+    let line = text.lines().find(|l| l.starts_with("cpu "))?;
+    let ticks: Vec<u64> = line.split_whitespace().skip(1).take(8).filter_map(|v| v.parse().ok()).collect();
+    if ticks.len() < 4 {
+        return None;
+    }
+    let total: u64 = ticks.iter().sum();
+    let idle = ticks[3] + ticks.get(4).copied().unwrap_or(0);
+    Some(CpuTimes {
+        busy: total.saturating_sub(idle),
+        total,
+    })
+}
+
+pub fn cpu_percent(before: CpuTimes, after: CpuTimes) -> f32 {
+    let total = after.total.saturating_sub(before.total);
+    if total == 0 {
+        return 0.0;
+    }
+    (after.busy.saturating_sub(before.busy) as f32 / total as f32 * 100.0).clamp(0.0, 100.0)
+}
+
+// (total, available) in bytes. Kernels before 3.14 have no MemAvailable;
+// free plus buffers and page cache is the usual stand-in.
+pub fn parse_meminfo(text: &str) -> Option<(u64, u64)> {
+    let mut fields: HashMap<&str, u64> = HashMap::new();
+    for line in text.lines() {
+        let Some((key, rest)) = line.split_once(':') else {
+            continue;
+        };
+        if let Some(kb) = rest.split_whitespace().next().and_then(|v| v.parse::<u64>().ok()) {
+            fields.insert(key.trim(), kb * 1024);
+        }
+    }
+    let total = *fields.get("MemTotal")?;
+    let available = fields.get("MemAvailable").copied().unwrap_or_else(|| {
+        ["MemFree", "Buffers", "Cached"].iter().filter_map(|k| fields.get(k)).sum()
+    });
+    Some((total, available.min(total)))
+}
+
+// `df -P`: "Filesystem 1024-blocks Used Available Capacity Mounted on".
+// The block size is in the header (512 on some BSDs, "1K" on busybox);
+// a mount point may contain spaces. Pseudo filesystems and the same mount
+// prefixes the local view skips are left out.
+pub fn parse_df(text: &str) -> Vec<DfEntry> {
+    let mut lines = text.lines();
+    let block = lines
+        .next()
+        .and_then(|header| header.split_whitespace().nth(1))
+        .and_then(|size| size.trim_end_matches("-blocks").parse::<u64>().ok().or_else(|| size.starts_with("1K").then_some(1024)))
+        .unwrap_or(1024);
+    lines
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 6 {
+                return None;
+            }
+            let number = |i: usize| fields[i].parse::<u64>().ok().map(|blocks| blocks * block);
+            let mount_point = fields[5..].join(" ");
+            let skipped = ["/snap", "/sys", "/proc", "/dev", "/run"].iter().any(|p| mount_point.starts_with(p));
+            if skipped || !mount_point.starts_with('/') || PSEUDO_FILESYSTEMS.contains(&fields[0]) {
+                return None;
+            }
+            Some(DfEntry {
+                filesystem: fields[0].to_string(),
+                total_bytes: number(1)?,
+                used_bytes: number(2)?,
+                available_bytes: number(3)?,
+                mount_point,
+            })
+        })
+        .collect()
+}
+
+// The "up ..." part of `uptime`, as procps, busybox and the BSDs print it:
+// " 14:02:03 up 5 days,  3:04,  2 users,  load average: 0.00, 0.01, 0.05"
+// " 14:02:03 up 35 min,  1 user,  load average: ..."
+// "10:01AM  up 2 days, 3 hrs, 1 user, load averages: ..."
+pub fn parse_uptime(text: &str) -> Option<u64> {
+    let (_, rest) = text.split_once(" up ")?;
+    let mut seconds = 0;
+    let mut found = false;
+    for part in rest.split(',').map(str::trim) {
+        if part.contains("user") || part.starts_with("load") {
+            break;
+        }
+        if let Some((hours, minutes)) = part.split_once(':') {
+            seconds += hours.trim().parse::<u64>().ok()? * 3600 + minutes.trim().parse::<u64>().ok()? * 60;
+            found = true;
+            continue;
+        }
+        let mut words = part.split_whitespace();
+        let value: u64 = words.next()?.parse().ok()?;
+        let unit = words.next().unwrap_or_default();
+        seconds += value
+            * match unit.trim_end_matches('s') {
+                "day" => 86_400,
+                "hr" | "hour" => 3_600,
+                "min" | "minute" => 60,
+                "sec" | "second" => 1,
+                _ => return None,
+            };
+        found = true;
+    }
+    found.then_some(seconds)
+}
+
+// REMOTE_COMMAND's output, section by section
+pub fn parse_output(text: &str) -> Result<RemoteSample, String> {
+    let sections: Vec<&str> = text.split(&format!("{}\n", MARKER)).collect();
+    let [stat, meminfo, df, uptime] = sections.as_slice() else {
+        return Err(format!("expected 4 sections of output, got {}", sections.len()));
+    };
+    let cpu = parse_proc_stat(stat).ok_or("no cpu line in /proc/stat")?;
+    let (memory_total_bytes, memory_available_bytes) = parse_meminfo(meminfo).ok_or("no MemTotal in /proc/meminfo")?;
+    Ok(RemoteSample {
+        cpu,
+        memory_total_bytes,
+        memory_available_bytes,
+        disks: parse_df(df),
+        uptime_seconds: parse_uptime(uptime).unwrap_or(0),
+    })
+}
+
+pub fn build_metrics(host_id: &str, sample: &RemoteSample, cpu_percent: f32, units: Units) -> SystemMetrics {
+    let total = sample.memory_total_bytes;
+    let available = sample.memory_available_bytes;
+    let used = total.saturating_sub(available);
+    let disks = sample
+        .disks
+        .iter()
+        .map(|d| DiskInfo {
+            mount_point: d.mount_point.clone(),
+            // df -P doesn't say
+            fs_type: String::new(),
+            total_gb: units.gb(d.total_bytes),
+            used_gb: units.gb(d.used_bytes),
+            available_gb: units.gb(d.available_bytes),
+            total_bytes: d.total_bytes,
+            used_bytes: d.used_bytes,
+            available_bytes: d.available_bytes,
+            total_display: units.format_bytes(d.total_bytes),
+            used_display: units.format_bytes(d.used_bytes),
+            available_display: units.format_bytes(d.available_bytes),
+            usage_percent: if d.total_bytes > 0 {
+                d.used_bytes as f32 / d.total_bytes as f32 * 100.0
+            } else {
+                0.0
+            },
+            inodes_total: None,
+            inodes_used: None,
+            inodes_usage_percent: None,
+            days_until_full: None,
+            temperature_c: None,
+            wear_percent: None,
+        })
+        .collect();
+    SystemMetrics {
+        host_id: host_id.to_string(),
+        cpu_percent,
+        memory_percent: if total > 0 { used as f32 / total as f32 * 100.0 } else { 0.0 },
+        memory_used_gb: units.gb(used),
+        memory_total_gb: units.gb(total),
+        memory_available_gb: units.gb(available),
+        memory_used_bytes: used,
+        memory_total_bytes: total,
+        memory_available_bytes: available,
+        memory_used_display: units.format_bytes(used),
+        memory_total_display: units.format_bytes(total),
+        memory_available_display: units.format_bytes(available),
+        disks,
+        uptime_seconds: sample.uptime_seconds,
+    }
+}
+
+// Only plain host names, addresses and user names, so nothing can be
+// read as an ssh option
+pub fn check_target(settings: &Settings, target: &SshTarget) -> CommandResult<()> {
+    let plain = |value: &str| {
+        !value.is_empty()
+            && !value.starts_with('-')
+            && value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | ':'))
+    };
+    if !plain(&target.address) {
+        return Err(CommandError::InvalidInput(format!("'{}' is not a host name or address", target.address)));
+    }
+    if !plain(&target.user) || target.user.contains(':') {
+        return Err(CommandError::InvalidInput(format!("'{}' is not a user name", target.user)));
+    }
+    let key = sandbox::SSH_KEY.validate(settings, Path::new(&target.key_path))?;
+    if !key.as_path().is_file() {
+        return Err(CommandError::NotFound(format!("SSH key {}", target.key_path)));
+    }
+    Ok(())
+}
+
+pub fn port_open(target: &SshTarget, timeout: Duration) -> bool {
+    let Ok(addrs) = (target.address.as_str(), target.port).to_socket_addrs() else {
+        return false;
+    };
+    addrs.into_iter().any(|addr| TcpStream::connect_timeout(&addr, timeout).is_ok())
+}
+
+fn ssh_args(target: &SshTarget, control_dir: &Path) -> Vec<String> {
+    let mut args: Vec<String> = [
+        "-o",
+        "BatchMode=yes",
+        "-o",
+        "StrictHostKeyChecking=accept-new",
+        "-o",
+        "IdentitiesOnly=yes",
+        "-o",
+        "ServerAliveInterval=15",
+        "-o",
+        "ServerAliveCountMax=3",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    args.extend(["-o".to_string(), format!("ConnectTimeout={}", CONNECT_TIMEOUT_SECS)]);
+    if cfg!(unix) {
+        args.extend([
+            "-o".to_string(),
+            "ControlMaster=auto".to_string(),
+            "-o".to_string(),
+            format!("ControlPath={}/%C", control_dir.display()),
+            "-o".to_string(),
+            format!("ControlPersist={}", CONTROL_PERSIST_SECS),
+        ]);
+    }
+    args.extend([
+        "-i".to_string(),
+        target.key_path.clone(),
+        "-p".to_string(),
+        target.port.to_string(),
+        "-l".to_string(),
+        target.user.clone(),
+        "--".to_string(),
+        target.address.clone(),
+        REMOTE_COMMAND.to_string(),
+    ]);
+    args
+}
+
+fn poll(target: &SshTarget, control_dir: &Path) -> Result<RemoteSample, String> {
+    let args = ssh_args(target, control_dir);
+    let output = exec::bounded(Instant::now() + POLL_TIMEOUT, move || Command::new("ssh").args(&args).output())
+        .ok_or_else(|| format!("no answer within {}s", POLL_TIMEOUT.as_secs()))?
+        .map_err(|e| format!("failed to run ssh: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("ssh failed");
+        return Err(reason.trim().to_string());
+    }
+    parse_output(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[derive(Serialize, Clone)]
+pub struct HostStatusEvent {
+    pub host_id: String,
+    // "online" or "offline"
+    pub status: String,
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+struct HostState {
+    sample: Option<RemoteSample>,
+    // From the last two samples
+    cpu_percent: f32,
+    online: Option<bool>,
+    last_error: Option<String>,
+}
+
+pub struct SshHosts {
+    control_dir: PathBuf,
+    hosts: Mutex<HashMap<String, HostState>>,
+}
+
+impl SshHosts {
+    pub fn new(control_dir: PathBuf) -> Self {
+        SshHosts {
+            control_dir,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Store a poll result; returns the status event when it changed
+    fn record(&self, host_id: &str, result: Result<RemoteSample, String>) -> Option<HostStatusEvent> {
+        let mut hosts = self.hosts.lock().unwrap();
+        let state = hosts.entry(host_id.to_string()).or_default();
+        let online = result.is_ok();
+        match result {
+            Ok(sample) => {
+                if let Some(previous) = &state.sample {
+                    state.cpu_percent = cpu_percent(previous.cpu, sample.cpu);
+                }
+                state.sample = Some(sample);
+                state.last_error = None;
+            }
+            Err(e) => state.last_error = Some(e),
+        }
+        let changed = state.online != Some(online);
+        state.online = Some(online);
+        changed.then(|| HostStatusEvent {
+            host_id: host_id.to_string(),
+            status: if online { "online" } else { "offline" }.to_string(),
+            error: state.last_error.clone(),
+        })
+    }
+
+    // None until the host has been polled
+    pub fn reachable(&self, host_id: &str) -> Option<bool> {
+        self.hosts.lock().unwrap().get(host_id).and_then(|s| s.online)
+    }
+
+    pub fn metrics(&self, host: &HostEntry, units: Units) -> CommandResult<SystemMetrics> {
+        let hosts = self.hosts.lock().unwrap();
+        let state = hosts.get(&host.id);
+        match state {
+            Some(HostState {
+                sample: Some(sample),
+                online: Some(true),
+                cpu_percent,
+                ..
+            }) => Ok(build_metrics(&host.id, sample, *cpu_percent, units)),
+            Some(HostState {
+                last_error: Some(e), ..
+            }) => Err(CommandError::HostUnreachable(format!("{}: {}", host.name, e))),
+            _ => Err(CommandError::HostUnreachable(format!("{} hasn't been reached over SSH yet", host.name))),
+        }
+    }
+
+    // Forget hosts that were removed or no longer use SSH
+    fn retain(&self, ids: &[String]) {
+        self.hosts.lock().unwrap().retain(|id, _| ids.contains(id));
+    }
+}
+
+// Metrics for a remote host, over whichever transport it uses
+pub fn remote_metrics(ssh: &SshHosts, host: &HostEntry, units: Units) -> CommandResult<SystemMetrics> {
+    match host.transport {
+        Transport::Ssh => ssh.metrics(host, units),
+        Transport::Http => hosts::fetch_metrics(host),
+    }
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        let mut warned = false;
+        loop {
+            let registry = app.state::<CollectorRegistry>();
+            registry.wait_enabled(&app, "ssh_metrics");
+            let settings = app.state::<SettingsStore>().get();
+            let targets: Vec<(String, SshTarget)> = settings
+                .hosts
+                .iter()
+                .filter(|h| h.transport == Transport::Ssh)
+                .filter_map(|h| h.ssh.clone().map(|ssh| (h.id.clone(), ssh)))
+                .collect();
+            let store = app.state::<SshHosts>();
+            store.retain(&targets.iter().map(|(id, _)| id.clone()).collect::<Vec<_>>());
+
+            if !targets.is_empty() && exec::find_in_path("ssh").is_none() {
+                if !warned {
+                    println!("[Halbert] ssh not found; SSH hosts can't be polled");
+                    warned = true;
+                }
+            } else if !targets.is_empty() {
+                if let Err(e) = std::fs::create_dir_all(&store.control_dir) {
+                    println!("[Halbert] Can't create {}: {}", store.control_dir.display(), e);
+                }
+                let _ = registry.run("ssh_metrics", || {
+                    // Hosts are polled side by side so one slow host doesn't delay the rest
+                    let polls: Vec<_> = targets
+                        .into_iter()
+                        .map(|(id, target)| {
+                            let control_dir = store.control_dir.clone();
+                            (id, std::thread::spawn(move || poll(&target, &control_dir)))
+                        })
+                        .collect();
+                    let total = polls.len();
+                    let mut failed = 0;
+                    for (id, poll) in polls {
+                        let result = poll.join().unwrap_or_else(|_| Err("poll panicked".to_string()));
+                        failed += result.is_err() as usize;
+                        if let Some(event) = store.record(&id, result) {
+                            println!(
+                                "[Halbert] SSH host {} is {}{}",
+                                id,
+                                event.status,
+                                event.error.as_deref().map(|e| format!(": {}", e)).unwrap_or_default()
+                            );
+                            let _ = app.emit("hosts://status", &event);
+                        }
+                    }
+                    if failed > 0 {
+                        return Err(format!("{} of {} SSH hosts unreachable", failed, total));
+                    }
+                    Ok(())
+                });
+            }
+            registry.sleep(Duration::from_secs(collectors::interval_secs(
+                &settings,
+                "ssh_metrics",
+                DEFAULT_INTERVAL_SECS,
+            )));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Debian 12, kernel 6.1: ten fields, guest time on the end
+    const STAT_DEBIAN: &str = "cpu  10132153 290696 3084719 46828483 16683 0 25195 0 175628 0
+cpu0 1393280 32966 572056 13343292 6130 0 17875 0 23933 0
+intr 1462898 0 0 0
+ctxt 91376772
+btime 1700000000
+";
+    // CentOS 5, kernel 2.6.18: no steal or guest columns
+    const STAT_CENTOS5: &str = "cpu  2255 34 2290 22625563 6290 127 456
+cpu0 1132 34 1441 11311718 3675 127 438
+intr 114930548 113199788 3 0 5 263 0 4 0
+";
+    // Debian 12: MemAvailable is there
+    const MEMINFO_DEBIAN: &str = "MemTotal:       16318208 kB
+MemFree:         1043588 kB
+MemAvailable:    9839424 kB
+Buffers:          412312 kB
+Cached:          8402628 kB
+SwapCached:            0 kB
+HugePages_Total:       0
+";
+    // CentOS 6, kernel 2.6.32: before MemAvailable
+    const MEMINFO_CENTOS6: &str = "MemTotal:        1922712 kB
+MemFree:          213344 kB
+Buffers:           96416 kB
+Cached:           913088 kB
+SwapCached:         1204 kB
+";
+    // GNU coreutils on Ubuntu 22.04
+    const DF_UBUNTU: &str = "Filesystem     1024-blocks     Used Available Capacity Mounted on
+tmpfs              1631824     2184   1629640       1% /run
+/dev/nvme0n1p2   490617784 196559900 269062184      43% /
+tmpfs              8159116        0   8159116       0% /dev/shm
+/dev/nvme0n1p1      523248     6220    517028       2% /boot/efi
+/dev/loop3          65536    65536         0     100% /snap/core20/2015
+/dev/sdb1        960302096 12582912 898864432       2% /media/user/Backup Disk
+";
+    // busybox on Alpine 3.19
+    const DF_ALPINE: &str = "Filesystem           1K-blocks      Used Available Use% Mounted on
+/dev/sda3              7735692   1031672   6289456  14% /
+devtmpfs                 10240         0     10240   0% /dev
+shm                     505076         0    505076   0% /dev/shm
+/dev/sda1               289293     24061    245232   9% /boot
+";
+    // FreeBSD 14
+    const DF_FREEBSD: &str = "Filesystem  512-blocks    Used   Avail Capacity  Mounted on
+/dev/ada0p2   58010392 9044456 44325112    17%    /
+devfs                2       2        0   100%    /dev
+";
+
+    #[test]
+    fn proc_stat_with_and_without_steal() {
+        let debian = parse_proc_stat(STAT_DEBIAN).unwrap();
+        // The two guest columns are already in user and nice
+        let total = 10132153 + 290696 + 3084719 + 46828483 + 16683 + 25195;
+        assert_eq!(debian, CpuTimes { busy: total - 46828483 - 16683, total });
+
+        let centos = parse_proc_stat(STAT_CENTOS5).unwrap();
+        let total = 2255 + 34 + 2290 + 22625563 + 6290 + 127 + 456;
+        assert_eq!(centos, CpuTimes { busy: total - 22625563 - 6290, total });
+
+        assert_eq!(parse_proc_stat("cpu0 1 2 3 4\n"), None);
+        assert_eq!(parse_proc_stat("cpu  1 2 3\n"), None);
+    }
+
+    #[test]
+    fn cpu_percent_is_busy_over_total_between_readings() {
+        let before = CpuTimes { busy: 100, total: 1000 };
+        assert_eq!(cpu_percent(before, CpuTimes { busy: 150, total: 1200 }), 25.0);
+        assert_eq!(cpu_percent(before, before), 0.0);
+        // Counters reset by a reboot between polls
+        assert_eq!(cpu_percent(before, CpuTimes { busy: 10, total: 20 }), 0.0);
+    }
+
+    #[test]
+    fn meminfo_before_and_after_mem_available() {
+        assert_eq!(parse_meminfo(MEMINFO_DEBIAN), Some((16318208 * 1024, 9839424 * 1024)));
+        assert_eq!(
+            parse_meminfo(MEMINFO_CENTOS6),
+            Some((1922712 * 1024, (213344 + 96416 + 913088) * 1024))
+        );
+        assert_eq!(parse_meminfo("MemFree: 10 kB\n"), None);
+    }
+
+    #[test]
+    fn df_skips_pseudo_filesystems_and_keeps_spaces_in_mount_points() {
+        let disks = parse_df(DF_UBUNTU);
+        let mounts: Vec<&str> = disks.iter().map(|d| d.mount_point.as_str()).collect();
+        assert_eq!(mounts, ["/", "/boot/efi", "/media/user/Backup Disk"]);
+        assert_eq!(
+            disks[0],
+            DfEntry {
+                filesystem: "/dev/nvme0n1p2".to_string(),
+                total_bytes: 490617784 * 1024,
+                used_bytes: 196559900 * 1024,
+                available_bytes: 269062184 * 1024,
+                mount_point: "/".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn df_block_size_comes_from_the_header() {
+        let alpine = parse_df(DF_ALPINE);
+        assert_eq!(alpine.iter().map(|d| d.mount_point.as_str()).collect::<Vec<_>>(), ["/", "/boot"]);
+        assert_eq!(alpine[0].total_bytes, 7735692 * 1024);
+
+        let freebsd = parse_df(DF_FREEBSD);
+        assert_eq!(freebsd.len(), 1);
+        assert_eq!(freebsd[0].total_bytes, 58010392 * 512);
+        assert_eq!(freebsd[0].available_bytes, 44325112 * 512);
+    }
+
+    #[test]
+    fn uptime_from_procps_busybox_and_bsd() {
+        assert_eq!(
+            parse_uptime(" 14:02:03 up 5 days,  3:04,  2 users,  load average: 0.00, 0.01, 0.05"),
+            Some(5 * 86_400 + 3 * 3600 + 4 * 60)
+        );
+        assert_eq!(parse_uptime(" 14:02:03 up 35 min,  1 user,  load average: 0.00, 0.01, 0.05"), Some(35 * 60));
+        assert_eq!(parse_uptime(" 09:12:44 up 1 day, 19 min,  load average: 0.08, 0.03, 0.01"), Some(86_400 + 19 * 60));
+        assert_eq!(
+            parse_uptime("10:01AM  up 2 days, 3 hrs, 1 user, load averages: 0.10, 0.12, 0.09"),
+            Some(2 * 86_400 + 3 * 3600)
+        );
+        assert_eq!(parse_uptime("14:02:03 up 3 fortnights, 1 user"), None);
+        assert_eq!(parse_uptime("garbage"), None);
+    }
+
+    #[test]
+    fn output_is_split_into_its_four_sections() {
+        let text = format!(
+            "{}{m}\n{}{m}\n{}{m}\n 14:02:03 up 35 min,  1 user,  load average: 0.00\n",
+            STAT_DEBIAN,
+            MEMINFO_CENTOS6,
+            DF_ALPINE,
+            m = MARKER
+        );
+        let sample = parse_output(&text).unwrap();
+        assert_eq!(sample.cpu, parse_proc_stat(STAT_DEBIAN).unwrap());
+        assert_eq!(sample.memory_total_bytes, 1922712 * 1024);
+        assert_eq!(sample.disks.len(), 2);
+        assert_eq!(sample.uptime_seconds, 35 * 60);
+
+        assert!(parse_output(STAT_DEBIAN).is_err());
+        let no_cpu = format!("{m}\n{}{m}\n{m}\n", MEMINFO_DEBIAN, m = MARKER);
+        assert_eq!(parse_output(&no_cpu).unwrap_err(), "no cpu line in /proc/stat");
+    }
+}
@@ -0,0 +1,135 @@
+// Startup in two halves, so the window is usable straight away.
+//
+// setup only registers state. The slow parts then run one component at a
+// time on a background thread: opening the database (with its migrations),
+// starting the background tasks, and decoding the window icon. Each
+// finished component is announced as `startup://progress` ({component,
+// status, duration_ms}) and the end as `startup://ready`. Until the
+// database is open its queries fail fast with NotReady (see db) instead of
+// blocking. SMART readings and the update inventory wait for first use or
+// their first scheduled run rather than starting at boot. The timings are
+// kept for get_startup_status and get_self_usage, so a slow start shows up.
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::app::{AppHandle, Emitter, Manager, State};
+use crate::db::Database;
+use crate::liveness;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ComponentTiming {
+    pub component: String,
+    // "ready" or "failed"
+    pub status: String,
+    pub detail: Option<String>,
+    pub duration_ms: u64,
+    // Since the process started
+    pub finished_ms: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StartupReport {
+    pub components: Vec<ComponentTiming>,
+    pub ready: bool,
+    // When the last component finished, since the process started
+    pub ready_ms: Option<u64>,
+}
+
+pub struct Startup {
+    launched: Instant,
+    components: Mutex<Vec<ComponentTiming>>,
+    ready_ms: Mutex<Option<u64>>,
+}
+
+impl Startup {
+    pub fn new(launched: Instant) -> Self {
+        Startup {
+            launched,
+            components: Mutex::new(Vec::new()),
+            ready_ms: Mutex::new(None),
+        }
+    }
+
+    fn finish(&self, component: &str, started: Instant, result: Result<(), String>) -> ComponentTiming {
+        let timing = ComponentTiming {
+            component: component.to_string(),
+            status: if result.is_ok() { "ready" } else { "failed" }.to_string(),
+            detail: result.err(),
+            duration_ms: started.elapsed().as_millis() as u64,
+            finished_ms: self.launched.elapsed().as_millis() as u64,
+        };
+        self.components.lock().unwrap().push(timing.clone());
+        timing
+    }
+
+    pub fn report(&self) -> StartupReport {
+        let ready_ms = *self.ready_ms.lock().unwrap();
+        StartupReport {
+            components: self.components.lock().unwrap().clone(),
+            ready: ready_ms.is_some(),
+            ready_ms,
+        }
+    }
+}
+
+fn step(app: &AppHandle, component: &str, work: impl FnOnce() -> Result<(), String>) {
+    let started = Instant::now();
+    let result = work();
+    if let Err(e) = &result {
+        println!("[Halbert] Startup: {} failed: {}", component, e);
+    }
+    let timing = app.state::<Startup>().finish(component, started, result);
+    let _ = app.emit("startup://progress", &timing);
+}
+
+#[cfg(all(feature = "gui", target_os = "linux"))]
+fn set_window_icon(app: &AppHandle) -> Result<(), String> {
+    use image::ImageReader;
+    use std::io::Cursor;
+
+    // Set for the Linux taskbar; embedded at compile time for reliability
+    let window = app.get_webview_window("main").ok_or("no main window")?;
+    let icon_bytes = include_bytes!("../icons/icon.png");
+    let img = ImageReader::new(Cursor::new(icon_bytes))
+        .with_guessed_format()
+        .ok()
+        .and_then(|r| r.decode().ok())
+        .ok_or("couldn't decode the icon image")?;
+    let rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let icon = tauri::image::Image::new_owned(rgba.into_raw(), width, height);
+    window.set_icon(icon).map_err(|e| e.to_string())
+}
+
+#[cfg(not(all(feature = "gui", target_os = "linux")))]
+fn set_window_icon(_app: &AppHandle) -> Result<(), String> {
+    Ok(())
+}
+
+// Everything up to now was setup; `background` starts the background
+// tasks once the database they rely on is open
+pub fn run(app: AppHandle, background: impl FnOnce(&AppHandle) + Send + 'static) {
+    let startup = app.state::<Startup>();
+    startup.finish("setup", startup.launched, Ok(()));
+    std::thread::spawn(move || {
+        step(&app, "database", || app.state::<Database>().open().map_err(|e| e.to_string()));
+        step(&app, "background_tasks", || {
+            background(&app);
+            Ok(())
+        });
+        step(&app, "window_icon", || set_window_icon(&app));
+        let startup = app.state::<Startup>();
+        let ready_ms = startup.launched.elapsed().as_millis() as u64;
+        *startup.ready_ms.lock().unwrap() = Some(ready_ms);
+        println!("[Halbert] Ready after {} ms", ready_ms);
+        liveness::ready();
+        let _ = app.emit("startup://ready", startup.report());
+    });
+}
+
+// For a frontend that loaded after the events went out
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_startup_status(startup: State<'_, Startup>) -> StartupReport {
+    startup.report()
+}
@@ -0,0 +1,260 @@
+// Database size reporting and retention.
+//
+// Maintenance prunes each category past its retention window, then VACUUMs
+// and truncates the WAL. It runs as a job because VACUUM rewrites the whole
+// file, and on its own once a week. The audit log is only pruned when the
+// policy opts in, and the pruning is itself written to the audit log. Job
+// artifacts over their quotas are deleted oldest first. Metrics samples
+// are rolled up into hourly rows first (see usage_summary).
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::app::{AppHandle, Manager, State};
+use crate::artifacts;
+use crate::audit;
+use crate::automation::{Entry, Gate};
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::jobs::{Job, JobManager};
+use crate::liveness::Liveness;
+use crate::settings::SettingsStore;
+use crate::snapshots;
+use crate::usage_summary;
+
+const MAINTENANCE_INTERVAL_DAYS: i64 = 7;
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// Days to keep each category; 0 keeps everything
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    // Disk usage samples and listening port snapshots
+    pub metrics_days: u32,
+    // Finished jobs kept in memory
+    pub job_history_days: u32,
+    pub conversations_days: u32,
+    // Search and answer results that can still be rated
+    pub retrieval_days: u32,
+    // audit_log_days is ignored unless this is set
+    pub prune_audit_log: bool,
+    pub audit_log_days: u32,
+    // Job artifact quotas in MiB, oldest deleted first; 0 is unlimited
+    pub artifacts_job_mib: u64,
+    pub artifacts_total_mib: u64,
+    // Automatic filesystem snapshots kept per mount; 0 keeps them all
+    pub automatic_snapshots: u32,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            metrics_days: 30,
+            job_history_days: 90,
+            conversations_days: 0,
+            retrieval_days: 30,
+            prune_audit_log: false,
+            audit_log_days: 365,
+            artifacts_job_mib: 512,
+            artifacts_total_mib: 4096,
+            automatic_snapshots: 5,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TableStats {
+    pub name: String,
+    pub rows: i64,
+}
+
+#[derive(Serialize)]
+pub struct StorageStats {
+    pub tables: Vec<TableStats>,
+    // Database, WAL and shared-memory files together
+    pub file_bytes: u64,
+    pub page_size: i64,
+    pub page_count: i64,
+    // Free pages VACUUM would give back
+    pub reclaimable_bytes: i64,
+    pub jobs_in_memory: usize,
+    pub last_maintenance: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct MaintenanceReport {
+    // Rows (or jobs) deleted per category
+    pub deleted: BTreeMap<String, usize>,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub bytes_reclaimed: u64,
+}
+
+fn file_bytes(db: &Database) -> u64 {
+    db.files()
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn cutoff(days: u32) -> Option<chrono::DateTime<chrono::Utc>> {
+    (days > 0).then(|| chrono::Utc::now() - chrono::Duration::days(days as i64))
+}
+
+fn last_run(db: &Database) -> CommandResult<Option<i64>> {
+    db.with_conn(|conn| conn.query_row("SELECT MAX(ran_at) FROM storage_maintenance", [], |r| r.get(0)))
+}
+
+fn prune(db: &Database, jobs: &JobManager, policy: &RetentionPolicy, actor: &str) -> CommandResult<BTreeMap<String, usize>> {
+    let mut deleted = usage_summary::rollup(db)?;
+    if let Some(before) = cutoff(policy.metrics_days) {
+        let at = before.timestamp();
+        let (disk, ports) = db.with_conn(|conn| {
+            let disk = conn.execute("DELETE FROM disk_usage_samples WHERE at < ?1", params![at])?;
+            let ports = conn.execute("DELETE FROM listening_port_snapshots WHERE at < ?1", params![at])?;
+            Ok((disk, ports))
+        })?;
+        deleted.insert("disk_usage_samples".to_string(), disk);
+        deleted.insert("listening_port_snapshots".to_string(), ports);
+    }
+    if let Some(before) = cutoff(policy.conversations_days) {
+        // Messages go with their conversation (ON DELETE CASCADE)
+        let count = db.with_conn(|conn| {
+            conn.execute("DELETE FROM conversations WHERE updated_at < ?1", params![before.to_rfc3339()])
+        })?;
+        deleted.insert("conversations".to_string(), count);
+    }
+    if let Some(before) = cutoff(policy.retrieval_days) {
+        // Results go with their query; ratings already given stay
+        let count = db.with_conn(|conn| {
+            conn.execute("DELETE FROM retrieval_queries WHERE asked_at < ?1", params![before.to_rfc3339()])
+        })?;
+        deleted.insert("retrieval_queries".to_string(), count);
+    }
+    if let Some(before) = cutoff(policy.job_history_days) {
+        deleted.insert("jobs".to_string(), jobs.prune_finished(before));
+    }
+    deleted.insert("job_artifacts".to_string(), artifacts::enforce_quotas(db, policy)?);
+    deleted.insert("snapshots".to_string(), snapshots::enforce_retention(policy.automatic_snapshots));
+    if policy.prune_audit_log {
+        if let Some(before) = cutoff(policy.audit_log_days) {
+            let count = db.with_conn(|conn| {
+                conn.execute("DELETE FROM audit_log WHERE at < ?1", params![before.to_rfc3339()])
+            })?;
+            let detail = serde_json::json!({ "deleted": count, "older_than": before.to_rfc3339() });
+            audit::record(db, actor, "storage.prune_audit_log", "audit_log", &detail)?;
+            deleted.insert("audit_log".to_string(), count);
+        }
+    }
+    Ok(deleted)
+}
+
+fn maintain(db: &Database, jobs: &JobManager, policy: &RetentionPolicy, actor: &str) -> CommandResult<MaintenanceReport> {
+    let bytes_before = file_bytes(db);
+    let deleted = prune(db, jobs, policy, actor)?;
+    db.with_conn(|conn| {
+        conn.execute_batch("VACUUM")?;
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        conn.execute(
+            "INSERT INTO storage_maintenance (ran_at) VALUES (?1)",
+            params![chrono::Utc::now().timestamp()],
+        )?;
+        Ok(())
+    })?;
+    let bytes_after = file_bytes(db);
+    Ok(MaintenanceReport {
+        deleted,
+        bytes_before,
+        bytes_after,
+        bytes_reclaimed: bytes_before.saturating_sub(bytes_after),
+    })
+}
+
+fn spawn_maintenance(app: &AppHandle, policy: RetentionPolicy, actor: String) -> Job {
+    let jobs = app.state::<JobManager>();
+    let app = app.clone();
+    jobs.spawn("Storage maintenance", "storage_maintenance", move |handle| {
+        handle.log("Pruning old rows, then compacting the database");
+        let report = maintain(&app.state::<Database>(), &app.state::<JobManager>(), &policy, &actor)
+            .map_err(|e| e.to_string())?;
+        for (category, count) in &report.deleted {
+            handle.log(format!("{}: {} deleted", category, count));
+        }
+        let units = app.state::<SettingsStore>().get().units;
+        handle.log(format!("Reclaimed {}", units.format_bytes(report.bytes_reclaimed)));
+        handle.set_result(serde_json::to_value(&report).unwrap_or_default());
+        Ok(())
+    })
+}
+
+// Checks hourly so a week is counted across restarts
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        app.state::<Liveness>().beat("maintenance", CHECK_INTERVAL);
+        let due = match last_run(&app.state::<Database>()) {
+            Ok(Some(at)) => chrono::Utc::now().timestamp() - at >= MAINTENANCE_INTERVAL_DAYS * 86_400,
+            Ok(None) => true,
+            Err(e) => {
+                println!("[Halbert] Skipping storage maintenance check: {}", e);
+                false
+            }
+        };
+        if due && app.state::<Gate>().allows(Entry::Schedule, "Weekly storage maintenance") {
+            let policy = app.state::<SettingsStore>().get().retention;
+            let job = spawn_maintenance(&app, policy, "maintenance:weekly".to_string());
+            println!("[Halbert] Weekly storage maintenance started as {}", job.id);
+        }
+        std::thread::sleep(CHECK_INTERVAL);
+    });
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_storage_stats(db: State<'_, Database>, jobs: State<'_, JobManager>) -> CommandResult<StorageStats> {
+    let (names, page_size, page_count, free_pages): (Vec<String>, i64, i64, i64) = db.with_conn(|conn| {
+        let mut stmt =
+            conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")?;
+        let names = stmt.query_map([], |r| r.get(0))?.collect::<rusqlite::Result<_>>()?;
+        let page_size = conn.pragma_query_value(None, "page_size", |r| r.get(0))?;
+        let page_count = conn.pragma_query_value(None, "page_count", |r| r.get(0))?;
+        let free_pages = conn.pragma_query_value(None, "freelist_count", |r| r.get(0))?;
+        Ok((names, page_size, page_count, free_pages))
+    })?;
+    let mut tables = Vec::new();
+    for name in names {
+        // Names come from sqlite_master, not from the caller
+        let rows = db.with_conn(|conn| {
+            conn.query_row(&format!("SELECT COUNT(*) FROM \"{}\"", name.replace('"', "\"\"")), [], |r| r.get(0))
+        })?;
+        tables.push(TableStats { name, rows });
+    }
+    let last_maintenance = last_run(&db)?
+        .and_then(|at| chrono::DateTime::from_timestamp(at, 0))
+        .map(|t| t.to_rfc3339());
+    Ok(StorageStats {
+        tables,
+        file_bytes: file_bytes(&db),
+        page_size,
+        page_count,
+        reclaimable_bytes: free_pages * page_size,
+        jobs_in_memory: jobs.all().len(),
+        last_maintenance,
+    })
+}
+
+// Without a policy, the retention settings apply
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn run_storage_maintenance(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    retention: Option<RetentionPolicy>,
+) -> CommandResult<Job> {
+    let policy = retention.unwrap_or_else(|| settings.get().retention);
+    if policy.prune_audit_log && policy.audit_log_days == 0 {
+        return Err(CommandError::InvalidInput(
+            "audit_log_days must be set when prune_audit_log is on".to_string(),
+        ));
+    }
+    Ok(spawn_maintenance(&app, policy, audit::local_actor()))
+}
@@ -0,0 +1,233 @@
+// Noticing when the machine was suspended.
+//
+// logind announces suspend and resume as PrepareForSleep(true / false);
+// `gdbus monitor` on the system bus relays them when it's available. As a
+// fallback, and for suspends logind never saw, a watcher compares how far
+// the boot clock (/proc/uptime, which keeps counting while suspended) and
+// the monotonic clock (which doesn't) moved between ticks; a difference of
+// at least JUMP_THRESHOLD is a sleep of that length. Without /proc/uptime
+// the wall clock stands in, so setting the clock forward reads as a sleep
+// too.
+//
+// On resume the metrics and container histories mark a gap, container
+// rates start over instead of dividing one huge counter delta by the whole
+// night, the self-check re-probes the backend, remote jobs are polled
+// again, and `system://resumed` goes out with how long the sleep was. Both
+// sources usually see the same resume; the second report within DEDUP is
+// ignored. Alert rules' `for_secs` is measured with `awake`, so a sleep
+// never counts toward it.
+use serde::Serialize;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::app::{AppHandle, Emitter, Manager};
+use crate::containers::ContainerHistory;
+use crate::exec;
+use crate::job_poller::JobPoller;
+use crate::sampler::MetricsHistory;
+
+const TICK: Duration = Duration::from_secs(5);
+// Less than this between the clocks is scheduling noise
+const JUMP_THRESHOLD: Duration = Duration::from_secs(30);
+const DEDUP: Duration = Duration::from_secs(30);
+// Before trying gdbus again after it exits
+const RETRY: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Resumed {
+    pub slept_secs: u64,
+    // "login1" or "clock"
+    pub source: String,
+    pub resumed_at: String,
+}
+
+#[derive(Default)]
+pub struct Suspend {
+    // Wall clock at PrepareForSleep(true)
+    asleep_since: Mutex<Option<SystemTime>>,
+    // `awake()` at the last resume handled
+    last_resume: Mutex<Option<Duration>>,
+}
+
+impl Suspend {
+    // False when this resume was already handled
+    fn claim(&self, now: Duration) -> bool {
+        let mut last = self.last_resume.lock().unwrap();
+        if last.is_some_and(|last| now.saturating_sub(last) < DEDUP) {
+            return false;
+        }
+        *last = Some(now);
+        true
+    }
+}
+
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+// Time the machine has been awake since Halbert started. Instant follows
+// CLOCK_MONOTONIC on Linux, which stands still while suspended, so
+// durations measured with this leave sleep out.
+pub fn awake() -> Duration {
+    STARTED.get_or_init(Instant::now).elapsed()
+}
+
+// A `gdbus monitor` line: Some(true) going to sleep, Some(false) resuming
+pub fn parse_prepare_for_sleep(line: &str) -> Option<bool> {
+    let (_, args) = line.split_once("PrepareForSleep (")?;
+    match args.split([',', ')']).next()?.trim() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+// How long the machine slept between two ticks, given how far the
+// monotonic clock moved and how far a clock that counts sleep moved
+pub fn slept(monotonic: Duration, reference: Duration) -> Option<Duration> {
+    let difference = reference.checked_sub(monotonic)?;
+    (difference >= JUMP_THRESHOLD).then_some(difference)
+}
+
+// Time since boot, suspend included
+fn boot_clock() -> Option<Duration> {
+    let uptime = std::fs::read_to_string("/proc/uptime").ok()?;
+    let secs: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+    Some(Duration::from_secs_f64(secs))
+}
+
+fn wall_clock() -> Duration {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default()
+}
+
+fn resumed(app: &AppHandle, slept: Duration, source: &str) {
+    if !app.state::<Suspend>().claim(awake()) {
+        return;
+    }
+    println!("[Halbert] Resumed after {}s asleep ({})", slept.as_secs(), source);
+    app.state::<MetricsHistory>().mark_gap();
+    app.state::<ContainerHistory>().reset_rates();
+    app.state::<JobPoller>().poll_all();
+    crate::selfcheck::start(app.clone());
+    let _ = app.emit(
+        "system://resumed",
+        Resumed {
+            slept_secs: slept.as_secs(),
+            source: source.to_string(),
+            resumed_at: chrono::Utc::now().to_rfc3339(),
+        },
+    );
+}
+
+fn watch_clocks(app: &AppHandle) {
+    let reference = || boot_clock().unwrap_or_else(wall_clock);
+    let mut last = (awake(), reference());
+    loop {
+        std::thread::sleep(TICK);
+        let now = (awake(), reference());
+        let moved = (now.0.saturating_sub(last.0), now.1.saturating_sub(last.1));
+        if let Some(slept) = slept(moved.0, moved.1) {
+            resumed(app, slept, "clock");
+        }
+        last = now;
+    }
+}
+
+// Until gdbus exits or can't start
+fn monitor_login1(app: &AppHandle) -> std::io::Result<()> {
+    let mut child = Command::new("gdbus")
+        .args(["monitor", "--system", "--dest", "org.freedesktop.login1"])
+        .args(["--object-path", "/org/freedesktop/login1"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let Some(stdout) = child.stdout.take() else {
+        let _ = child.kill();
+        return Ok(());
+    };
+    let suspend = app.state::<Suspend>();
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        match parse_prepare_for_sleep(&line) {
+            Some(true) => {
+                println!("[Halbert] Going to sleep");
+                *suspend.asleep_since.lock().unwrap() = Some(SystemTime::now());
+            }
+            Some(false) => {
+                let since = suspend.asleep_since.lock().unwrap().take();
+                let slept = since.and_then(|t| t.elapsed().ok()).unwrap_or_default();
+                resumed(app, slept, "login1");
+            }
+            None => {}
+        }
+    }
+    let _ = child.wait();
+    Ok(())
+}
+
+pub fn start(app: AppHandle) {
+    awake();
+    let clock_app = app.clone();
+    std::thread::spawn(move || watch_clocks(&clock_app));
+    if exec::find_in_path("gdbus").is_none() {
+        println!("[Halbert] gdbus not found; noticing suspend from the clocks alone");
+        return;
+    }
+    std::thread::spawn(move || loop {
+        if let Err(e) = monitor_login1(&app) {
+            println!("[Halbert] Can't watch logind for suspend: {}", e);
+            return;
+        }
+        std::thread::sleep(RETRY);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn prepare_for_sleep_signals() {
+        let line = "/org/freedesktop/login1: org.freedesktop.login1.Manager.PrepareForSleep (true,)";
+        assert_eq!(parse_prepare_for_sleep(line), Some(true));
+        let line = "/org/freedesktop/login1: org.freedesktop.login1.Manager.PrepareForSleep (false,)";
+        assert_eq!(parse_prepare_for_sleep(line), Some(false));
+        // Other signals on the same object
+        let line = "/org/freedesktop/login1: org.freedesktop.login1.Manager.SessionNew ('3', objectpath '/x')";
+        assert_eq!(parse_prepare_for_sleep(line), None);
+        assert_eq!(parse_prepare_for_sleep("PrepareForSleep (maybe,)"), None);
+    }
+
+    #[test]
+    fn a_jump_between_the_clocks_is_a_sleep() {
+        // A normal tick: both clocks moved about TICK
+        assert_eq!(slept(TICK, TICK), None);
+        assert_eq!(slept(TICK, TICK + 2 * SECOND), None);
+        // Just under and at the threshold
+        assert_eq!(slept(TICK, TICK + JUMP_THRESHOLD - SECOND), None);
+        assert_eq!(slept(TICK, TICK + JUMP_THRESHOLD), Some(JUMP_THRESHOLD));
+        // Eight hours overnight
+        let night = Duration::from_secs(8 * 3600);
+        assert_eq!(slept(TICK, TICK + night), Some(night));
+        // The wall clock set back reads as nothing, not a negative sleep
+        assert_eq!(slept(TICK, Duration::ZERO), None);
+    }
+
+    #[test]
+    fn both_sources_reporting_one_resume_count_once() {
+        let suspend = Suspend::default();
+        let resume = Duration::from_secs(3600);
+        assert!(suspend.claim(resume));
+        // The clock watcher's tick after logind's signal
+        assert!(!suspend.claim(resume + TICK));
+        assert!(!suspend.claim(resume + DEDUP - SECOND));
+        // A later suspend
+        assert!(suspend.claim(resume + DEDUP));
+        assert!(!suspend.claim(resume + DEDUP + SECOND));
+    }
+}
@@ -0,0 +1,199 @@
+// The dashboard as text, for screen readers and terminals.
+//
+// `get_dashboard_summary_text` describes the active host right now as short
+// sentences in a fixed order: CPU, memory, each disk, pending approvals,
+// active jobs, then alerts. `get_event_feed` merges what happened here since
+// a point in time (alert starts and clears, job starts and finishes,
+// approval requests and decisions, incidents) into one chronological feed.
+// All wording comes from phrasing. Alerts are evaluated on this machine, so
+// with a remote host active they still describe this one.
+use serde::Serialize;
+
+use crate::alerts::{self, AlertLog};
+use crate::app::{AppHandle, Manager, State};
+use crate::approvals::ApprovalStore;
+use crate::changes;
+use crate::db::Database;
+use crate::error::CommandResult;
+use crate::hosts::{self, ActiveHost};
+use crate::incidents;
+use crate::jobs::{Job, JobManager};
+use crate::phrasing::{self, Level, Line};
+use crate::settings::{Settings, SettingsStore};
+use crate::ssh_hosts::{self, SshHosts};
+use crate::timestamps::{self, Clock, Localize, TimeDisplay};
+use crate::SystemMetrics;
+
+const DEFAULT_FEED_LIMIT: usize = 100;
+const MAX_FEED_LIMIT: usize = 1000;
+
+#[derive(Serialize)]
+pub struct TextSummary {
+    pub host_id: String,
+    pub lines: Vec<Line>,
+    // The lines joined, one per line
+    pub text: String,
+    pub generated_at: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FeedEntry {
+    pub at: String,
+    // "alert", "job", "approval" or "incident"
+    pub kind: String,
+    pub level: Level,
+    pub text: String,
+    // Filled in per response; see timestamps
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub at_display: Option<TimeDisplay>,
+}
+
+impl Localize for FeedEntry {
+    fn localize(&mut self, clock: &Clock) {
+        self.at_display = clock.display(&self.at);
+    }
+}
+
+fn entry(at: &str, kind: &str, line: Line) -> FeedEntry {
+    FeedEntry {
+        at: at.to_string(),
+        kind: kind.to_string(),
+        level: line.level,
+        text: line.text,
+        at_display: None,
+    }
+}
+
+// The summary's sentences; alerts go most severe first
+fn summary_lines(
+    settings: &Settings,
+    metrics: Result<&SystemMetrics, String>,
+    pending_approvals: Result<usize, String>,
+    active_jobs: Result<&[Job], String>,
+    active_alerts: &[alerts::Alert],
+) -> Vec<Line> {
+    let units = settings.units;
+    let mut lines = Vec::new();
+    match metrics {
+        Ok(metrics) => {
+            lines.push(phrasing::cpu(metrics.cpu_percent));
+            lines.push(phrasing::memory(
+                metrics.memory_used_bytes,
+                metrics.memory_total_bytes,
+                metrics.memory_percent,
+                units,
+            ));
+            lines.extend(metrics.disks.iter().map(|d| {
+                phrasing::disk(&d.mount_point, d.used_bytes, d.total_bytes, d.usage_percent, units)
+            }));
+        }
+        Err(e) => lines.push(phrasing::unavailable("Metrics", &e)),
+    }
+    match pending_approvals {
+        Ok(n) => lines.push(phrasing::approvals_pending(n)),
+        Err(e) => lines.push(phrasing::unavailable("Approvals", &e)),
+    }
+    match active_jobs {
+        Ok([]) => lines.push(phrasing::no_jobs_running()),
+        Ok(jobs) => lines.extend(jobs.iter().map(phrasing::job_active)),
+        Err(e) => lines.push(phrasing::unavailable("Jobs", &e)),
+    }
+    let mut alert_lines: Vec<Line> = active_alerts.iter().map(phrasing::alert).collect();
+    alert_lines.sort_by_key(|line| std::cmp::Reverse(line.level));
+    if alert_lines.is_empty() {
+        alert_lines.push(phrasing::no_alerts());
+    }
+    lines.extend(alert_lines);
+    lines
+}
+
+// async so a remote host's metrics don't hold up the main thread
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_dashboard_summary_text(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+) -> CommandResult<TextSummary> {
+    let settings = settings.get();
+    let units = settings.units;
+    let (metrics, pending, active) = match hosts::active_host(&settings) {
+        ActiveHost::Local => (
+            Ok(crate::local_system_metrics(units)),
+            Ok(app.state::<ApprovalStore>().pending().len()),
+            Ok(app.state::<JobManager>().all()),
+        ),
+        ActiveHost::Remote(host) => (
+            ssh_hosts::remote_metrics(&app.state::<SshHosts>(), &host, units),
+            hosts::fetch_approvals(&host).map(|a| a.len()),
+            hosts::fetch_jobs(&host),
+        ),
+    };
+    let active = active.map(|jobs| jobs.into_iter().filter(Job::is_active).collect::<Vec<Job>>());
+    let matches = alerts::evaluate(
+        &settings.alert_rules,
+        &alerts::collect_signals(&settings, &app.state::<Database>()),
+    );
+    let active_alerts = app.state::<AlertLog>().sustained(&settings.alert_rules, matches);
+    let lines = summary_lines(
+        &settings,
+        metrics.as_ref().map_err(|e| e.to_string()),
+        pending.map_err(|e| e.to_string()),
+        active.as_deref().map_err(|e| e.to_string()),
+        &active_alerts,
+    );
+    Ok(TextSummary {
+        host_id: hosts::active_host_id(&settings),
+        text: lines.iter().map(|l| l.text.as_str()).collect::<Vec<_>>().join("\n"),
+        lines,
+        generated_at: chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+// The newest `limit` entries since `since` (RFC 3339 or "boot"), oldest
+// first
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_event_feed(
+    db: State<'_, Database>,
+    settings: State<'_, SettingsStore>,
+    alert_log: State<'_, AlertLog>,
+    jobs: State<'_, JobManager>,
+    approvals: State<'_, ApprovalStore>,
+    since: Option<String>,
+    limit: Option<usize>,
+) -> CommandResult<Vec<FeedEntry>> {
+    let since = since.map(|s| changes::parse_since(&s)).transpose()?.map(|t| t.to_rfc3339());
+    let since = since.as_deref();
+    let after = |at: &str| since.is_none_or(|since| at >= since);
+    let mut feed = Vec::new();
+
+    for transition in alert_log.since(since) {
+        let line = match transition.cleared {
+            true => phrasing::alert_cleared(&transition.alert),
+            false => phrasing::alert(&transition.alert),
+        };
+        feed.push(entry(&transition.at, "alert", line));
+    }
+    for job in jobs.all() {
+        if after(&job.started_at) {
+            feed.push(entry(&job.started_at, "job", phrasing::job_started(&job)));
+        }
+        if let Some(finished_at) = job.finished_at.as_deref().filter(|at| after(at)) {
+            feed.push(entry(finished_at, "job", phrasing::job_finished(&job)));
+        }
+    }
+    for request in approvals.pending().into_iter().chain(approvals.history(usize::MAX)) {
+        if after(&request.requested_at) {
+            feed.push(entry(&request.requested_at, "approval", phrasing::approval_requested(&request)));
+        }
+        if let Some(decided_at) = request.decided_at.as_deref().filter(|at| after(at)) {
+            feed.push(entry(decided_at, "approval", phrasing::approval_decided(&request)));
+        }
+    }
+    for incident in incidents::list(&db, since, None)? {
+        feed.push(entry(&incident.at, "incident", phrasing::incident(&incident)));
+    }
+
+    feed.sort_by(|a, b| a.at.cmp(&b.at));
+    let limit = limit.unwrap_or(DEFAULT_FEED_LIMIT).clamp(1, MAX_FEED_LIMIT);
+    let feed = feed.split_off(feed.len().saturating_sub(limit));
+    Ok(timestamps::localized(&settings.get(), feed))
+}
@@ -0,0 +1,283 @@
+// Temperatures, fans and CPU thermal throttling.
+//
+// Sensors are read from /sys/class/hwmon: tempN_* and fanN_* per chip. A
+// package's temperature is its coretemp "Package id N" reading on Intel,
+// or the k10temp/zenpower Tdie (else Tctl) of the matching chip on AMD.
+// Per-core frequency comes from cpufreq and the throttle event counters
+// from /sys/devices/system/cpu/cpuN/thermal_throttle, both read twice
+// across a short interval. A package counts as throttling when its
+// counters rose meanwhile, or when its cores run well below their maximum
+// and it is near its critical temperature; a low frequency alone is just as
+// likely the governor saving power. Machines whose fans are run by the
+// embedded controller have no fanN_input and get an empty fan list.
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::error::CommandResult;
+
+// Gap between the two frequency and counter readings
+const SAMPLE: Duration = Duration::from_millis(500);
+// Within this many degrees of critical counts as near it
+const NEAR_CRITICAL_C: f32 = 10.0;
+// Cores averaging below this share of their maximum count as held back
+const THROTTLED_RATIO: f32 = 0.85;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct TemperatureReading {
+    // hwmon chip name, e.g. "coretemp", "k10temp", "nvme"
+    pub chip: String,
+    pub label: String,
+    pub celsius: f32,
+    pub high_c: Option<f32>,
+    pub critical_c: Option<f32>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct FanReading {
+    pub chip: String,
+    pub label: String,
+    pub rpm: u32,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CoreFrequency {
+    pub cpu: u32,
+    pub current_mhz: u32,
+    pub max_mhz: u32,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PackageThermal {
+    pub package: u32,
+    pub temperature_c: Option<f32>,
+    pub critical_c: Option<f32>,
+    pub cores: Vec<CoreFrequency>,
+    // Average current / max frequency across the package's cores
+    pub frequency_ratio: Option<f32>,
+    // Since boot; None where the kernel has no thermal_throttle counters
+    pub core_throttle_events: Option<u64>,
+    pub package_throttle_events: Option<u64>,
+    pub throttling: bool,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ThermalStatus {
+    pub packages: Vec<PackageThermal>,
+    pub temperatures: Vec<TemperatureReading>,
+    pub fans: Vec<FanReading>,
+}
+
+// hwmon reports millidegrees
+pub fn millidegrees(text: &str) -> Option<f32> {
+    text.trim().parse::<i64>().ok().map(|m| m as f32 / 1000.0)
+}
+
+// Sensor numbers present in a hwmon directory, e.g. [1, 2] for
+// temp1_input and temp2_input
+pub fn sensor_numbers<'a>(files: impl Iterator<Item = &'a str>, kind: &str) -> Vec<u32> {
+    let mut numbers: Vec<u32> = files
+        .filter_map(|name| name.strip_prefix(kind)?.strip_suffix("_input")?.parse().ok())
+        .collect();
+    numbers.sort_unstable();
+    numbers.dedup();
+    numbers
+}
+
+// The reading behind package `package`. Readings carry the index of their
+// hwmon chip; AMD has one chip per package, in hwmon order.
+pub fn package_temperature(readings: &[(usize, TemperatureReading)], package: u32) -> Option<&TemperatureReading> {
+    let intel_label = format!("Package id {}", package);
+    if let Some((_, reading)) = readings.iter().find(|(_, r)| r.chip == "coretemp" && r.label == intel_label) {
+        return Some(reading);
+    }
+    let amd: Vec<(usize, &TemperatureReading)> = readings
+        .iter()
+        .filter(|(_, r)| r.chip == "k10temp" || r.chip == "zenpower")
+        .map(|(chip, r)| (*chip, r))
+        .collect();
+    let mut chips: Vec<usize> = amd.iter().map(|(chip, _)| *chip).collect();
+    chips.dedup();
+    let chip = *chips.get(package as usize)?;
+    let on_chip = |label: &str| amd.iter().find(|(c, r)| *c == chip && r.label == label).map(|(_, r)| *r);
+    // Tctl carries an offset on some parts; Tdie is the real die temperature
+    on_chip("Tdie").or_else(|| on_chip("Tctl"))
+}
+
+// Why a package counts as throttling, if it does. `throttle_events` is how
+// many counter increments were seen while sampling.
+pub fn judge(
+    temperature_c: Option<f32>,
+    critical_c: Option<f32>,
+    frequency_ratio: Option<f32>,
+    throttle_events: u64,
+) -> Option<String> {
+    if throttle_events > 0 {
+        return Some(format!("{} thermal throttle event(s) while sampling", throttle_events));
+    }
+    let (temperature, critical, ratio) = (temperature_c?, critical_c?, frequency_ratio?);
+    (ratio < THROTTLED_RATIO && temperature >= critical - NEAR_CRITICAL_C).then(|| {
+        format!(
+            "cores at {:.0}% of maximum frequency at {:.0}°C, {:.0}°C below critical",
+            ratio * 100.0,
+            temperature,
+            (critical - temperature).max(0.0)
+        )
+    })
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+// Temperatures tagged with the index of the chip they came from, and fans
+fn hwmon() -> (Vec<(usize, TemperatureReading)>, Vec<FanReading>) {
+    let mut temperatures = Vec::new();
+    let mut fans = Vec::new();
+    let Ok(entries) = std::fs::read_dir("/sys/class/hwmon") else {
+        return (temperatures, fans);
+    };
+    let mut dirs: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+    dirs.sort();
+    for (index, dir) in dirs.iter().enumerate() {
+        let chip = read_trimmed(&dir.join("name")).unwrap_or_else(|| "unknown".to_string());
+        let files: Vec<String> = std::fs::read_dir(dir)
+            .map(|entries| entries.flatten().map(|e| e.file_name().to_string_lossy().into_owned()).collect())
+            .unwrap_or_default();
+        let file = |name: String| read_trimmed(&dir.join(name));
+
+        for n in sensor_numbers(files.iter().map(String::as_str), "temp") {
+            let Some(celsius) = file(format!("temp{}_input", n)).and_then(|t| millidegrees(&t)) else {
+                continue;
+            };
+            temperatures.push((
+                index,
+                TemperatureReading {
+                    chip: chip.clone(),
+                    label: file(format!("temp{}_label", n)).unwrap_or_else(|| format!("temp{}", n)),
+                    celsius,
+                    high_c: file(format!("temp{}_max", n)).and_then(|t| millidegrees(&t)),
+                    critical_c: file(format!("temp{}_crit", n)).and_then(|t| millidegrees(&t)),
+                },
+            ));
+        }
+        for n in sensor_numbers(files.iter().map(String::as_str), "fan") {
+            let Some(rpm) = file(format!("fan{}_input", n)).and_then(|r| r.parse().ok()) else {
+                continue;
+            };
+            fans.push(FanReading {
+                chip: chip.clone(),
+                label: file(format!("fan{}_label", n)).unwrap_or_else(|| format!("fan{}", n)),
+                rpm,
+            });
+        }
+    }
+    (temperatures, fans)
+}
+
+struct CpuSample {
+    cpu: u32,
+    package: u32,
+    frequency: Option<CoreFrequency>,
+    core_throttles: Option<u64>,
+    package_throttles: Option<u64>,
+}
+
+fn cpu_samples() -> Vec<CpuSample> {
+    let Ok(entries) = std::fs::read_dir("/sys/devices/system/cpu") else {
+        return Vec::new();
+    };
+    let mut samples: Vec<CpuSample> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let cpu: u32 = entry.file_name().to_str()?.strip_prefix("cpu")?.parse().ok()?;
+            let dir = entry.path();
+            let number = |path: &str| read_trimmed(&dir.join(path)).and_then(|v| v.parse::<u64>().ok());
+            // cpufreq is in kHz
+            let frequency = number("cpufreq/scaling_cur_freq")
+                .zip(number("cpufreq/cpuinfo_max_freq"))
+                .filter(|(_, max)| *max > 0)
+                .map(|(current, max)| CoreFrequency {
+                    cpu,
+                    current_mhz: (current / 1000) as u32,
+                    max_mhz: (max / 1000) as u32,
+                });
+            Some(CpuSample {
+                cpu,
+                package: number("topology/physical_package_id").unwrap_or(0) as u32,
+                frequency,
+                core_throttles: number("thermal_throttle/core_throttle_count"),
+                package_throttles: number("thermal_throttle/package_throttle_count"),
+            })
+        })
+        .collect();
+    samples.sort_by_key(|s| s.cpu);
+    samples
+}
+
+pub fn thermal_status() -> ThermalStatus {
+    let before = cpu_samples();
+    std::thread::sleep(SAMPLE);
+    let after = cpu_samples();
+    let (temperatures, fans) = hwmon();
+
+    let mut by_package: BTreeMap<u32, Vec<&CpuSample>> = BTreeMap::new();
+    for sample in &after {
+        by_package.entry(sample.package).or_default().push(sample);
+    }
+    let packages = by_package
+        .into_iter()
+        .map(|(package, samples)| {
+            let cores: Vec<CoreFrequency> = samples.iter().filter_map(|s| s.frequency.clone()).collect();
+            let frequency_ratio = (!cores.is_empty()).then(|| {
+                cores.iter().map(|c| c.current_mhz as f32 / c.max_mhz as f32).sum::<f32>() / cores.len() as f32
+            });
+            let core_throttle_events = samples.iter().filter_map(|s| s.core_throttles).reduce(|a, b| a + b);
+            // The package counter is per package but repeated on every core
+            let package_throttle_events = samples.iter().filter_map(|s| s.package_throttles).max();
+            let earlier = |cpu: u32| before.iter().find(|b| b.cpu == cpu);
+            let new_core_events: u64 = samples
+                .iter()
+                .filter_map(|s| s.core_throttles.zip(earlier(s.cpu).and_then(|b| b.core_throttles)))
+                .map(|(now, then)| now.saturating_sub(then))
+                .sum();
+            let package_before = samples
+                .iter()
+                .filter_map(|s| earlier(s.cpu).and_then(|b| b.package_throttles))
+                .max();
+            let new_package_events = package_throttle_events
+                .zip(package_before)
+                .map_or(0, |(now, then)| now.saturating_sub(then));
+
+            let reading = package_temperature(&temperatures, package);
+            let temperature_c = reading.map(|r| r.celsius);
+            // Without a critical point, the high one is the nearest limit
+            let critical_c = reading.and_then(|r| r.critical_c.or(r.high_c));
+            let reason = judge(temperature_c, critical_c, frequency_ratio, new_core_events + new_package_events);
+            PackageThermal {
+                package,
+                temperature_c,
+                critical_c,
+                cores,
+                frequency_ratio,
+                core_throttle_events,
+                package_throttle_events,
+                throttling: reason.is_some(),
+                reason,
+            }
+        })
+        .collect();
+
+    ThermalStatus {
+        packages,
+        temperatures: temperatures.into_iter().map(|(_, reading)| reading).collect(),
+        fans,
+    }
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_thermal_status() -> CommandResult<ThermalStatus> {
+    Ok(thermal_status())
+}
@@ -0,0 +1,269 @@
+// Timestamps for display, in the timezone and clock format picked in
+// settings.
+//
+// Timestamps are stored and sent as RFC 3339 UTC. Responses that carry them
+// also get a `*_display` sibling with the same instant rendered for the
+// user and a relative form ("3m ago", "in 2h"), worked out when the
+// response is built so it is never stale by a cache. Like sizes (see
+// units), the frontend shows these as they are. `timezone` is "local" (the
+// system zone, the default), "utc" or a fixed offset such as "+05:30";
+// zone names would need a tz database the app doesn't ship.
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CommandError, CommandResult};
+use crate::settings::Settings;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeFormat {
+    #[default]
+    #[serde(rename = "24h")]
+    H24,
+    #[serde(rename = "12h")]
+    H12,
+}
+
+impl TimeFormat {
+    fn pattern(self) -> &'static str {
+        match self {
+            TimeFormat::H24 => "%Y-%m-%d %H:%M:%S",
+            TimeFormat::H12 => "%Y-%m-%d %-I:%M:%S %p",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Zone {
+    Local,
+    Utc,
+    Fixed(FixedOffset),
+}
+
+impl Zone {
+    // Wall-clock time in the zone
+    pub fn naive(self, at: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Zone::Local => at.with_timezone(&Local).naive_local(),
+            Zone::Utc => at.naive_utc(),
+            Zone::Fixed(offset) => at.with_timezone(&offset).naive_local(),
+        }
+    }
+
+    // The instant a wall-clock time in the zone names; the earlier one when
+    // a DST change repeats it, whole hours later when a change skips it
+    pub fn instant(self, naive: NaiveDateTime) -> DateTime<Utc> {
+        match self {
+            Zone::Local => (0..=4)
+                .find_map(|hours| {
+                    Local
+                        .from_local_datetime(&(naive + chrono::Duration::hours(hours)))
+                        .earliest()
+                })
+                .map(|at| at.with_timezone(&Utc))
+                .unwrap_or_else(|| naive.and_utc()),
+            Zone::Utc => naive.and_utc(),
+            Zone::Fixed(offset) => (naive - offset).and_utc(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TimeDisplay {
+    // The instant, RFC 3339 UTC
+    pub utc: String,
+    // e.g. "2026-10-14 15:04:05" in the configured zone
+    pub local: String,
+    pub relative: String,
+}
+
+// "+05:30", "-08:00", "+0930" or "+02" into seconds east of UTC
+fn parse_offset(text: &str) -> Option<i32> {
+    let sign = match text.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let digits: String = text[1..].chars().filter(|c| *c != ':').collect();
+    if !digits.chars().all(|c| c.is_ascii_digit()) || !matches!(digits.len(), 2 | 4) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = if digits.len() == 4 { digits[2..].parse().ok()? } else { 0 };
+    (hours <= 14 && minutes < 60).then_some(sign * (hours * 3600 + minutes * 60))
+}
+
+pub fn parse_zone(text: &str) -> CommandResult<Zone> {
+    let text = text.trim();
+    match text.to_ascii_lowercase().as_str() {
+        "" | "local" | "auto" => return Ok(Zone::Local),
+        "utc" | "z" => return Ok(Zone::Utc),
+        _ => {}
+    }
+    parse_offset(text)
+        .and_then(FixedOffset::east_opt)
+        .map(Zone::Fixed)
+        .ok_or_else(|| {
+            CommandError::InvalidInput(format!(
+                "unknown timezone '{}', expected local, utc or an offset like +05:30",
+                text
+            ))
+        })
+}
+
+// `seconds` from now to the instant: negative is the past. Under five
+// seconds either way is "just now"; then seconds, minutes, hours, days,
+// months of 30 days and years of 365, always whole and rounded down.
+pub fn relative(seconds: i64) -> String {
+    let magnitude = seconds.unsigned_abs();
+    if magnitude < 5 {
+        return "just now".to_string();
+    }
+    let (value, unit) = match magnitude {
+        0..=59 => (magnitude, "s"),
+        60..=3_599 => (magnitude / 60, "m"),
+        3_600..=86_399 => (magnitude / 3_600, "h"),
+        86_400..=2_591_999 => (magnitude / 86_400, "d"),
+        2_592_000..=31_535_999 => (magnitude / 2_592_000, "mo"),
+        _ => (magnitude / 31_536_000, "y"),
+    };
+    if seconds < 0 {
+        format!("{}{} ago", value, unit)
+    } else {
+        format!("in {}{}", value, unit)
+    }
+}
+
+// Settings and the moment a response is built
+pub struct Clock {
+    zone: Zone,
+    format: TimeFormat,
+    now: DateTime<Utc>,
+}
+
+impl Clock {
+    // An invalid stored zone falls back to local; update_settings refuses
+    // to store one
+    pub fn new(settings: &Settings) -> Self {
+        Clock::at(
+            parse_zone(&settings.timezone).unwrap_or(Zone::Local),
+            settings.time_format,
+            Utc::now(),
+        )
+    }
+
+    pub fn at(zone: Zone, format: TimeFormat, now: DateTime<Utc>) -> Self {
+        Clock { zone, format, now }
+    }
+
+    // UTC, 24h, stopped at `now` (RFC 3339), so tests render the same
+    // everywhere and whenever they run
+    #[cfg(test)]
+    pub fn fixed(now: &str) -> Self {
+        let now = DateTime::parse_from_rfc3339(now).expect("RFC 3339 time").with_timezone(&Utc);
+        Clock::at(Zone::Utc, TimeFormat::H24, now)
+    }
+
+    pub fn render(&self, at: DateTime<Utc>) -> TimeDisplay {
+        let pattern = self.format.pattern();
+        let local = match self.zone {
+            Zone::Local => at.with_timezone(&Local).format(pattern).to_string(),
+            Zone::Utc => format!("{} UTC", at.format(pattern)),
+            Zone::Fixed(offset) => format!("{} {}", at.with_timezone(&offset).format(pattern), offset),
+        };
+        TimeDisplay {
+            utc: at.to_rfc3339(),
+            local,
+            relative: relative((at - self.now).num_seconds()),
+        }
+    }
+
+    // None for a value that isn't RFC 3339
+    pub fn display(&self, timestamp: &str) -> Option<TimeDisplay> {
+        DateTime::parse_from_rfc3339(timestamp)
+            .ok()
+            .map(|at| self.render(at.with_timezone(&Utc)))
+    }
+
+    pub fn display_opt(&self, timestamp: Option<&str>) -> Option<TimeDisplay> {
+        timestamp.and_then(|t| self.display(t))
+    }
+}
+
+// Fills in the `*_display` fields of a response
+pub trait Localize {
+    fn localize(&mut self, clock: &Clock);
+}
+
+impl<T: Localize> Localize for Vec<T> {
+    fn localize(&mut self, clock: &Clock) {
+        for item in self.iter_mut() {
+            item.localize(clock);
+        }
+    }
+}
+
+// For command results: `localized(&settings, value)`
+pub fn localized<T: Localize>(settings: &Settings, mut value: T) -> T {
+    value.localize(&Clock::new(settings));
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_rounds_down_per_unit() {
+        assert_eq!(relative(0), "just now");
+        assert_eq!(relative(-4), "just now");
+        assert_eq!(relative(-5), "5s ago");
+        assert_eq!(relative(59), "in 59s");
+        assert_eq!(relative(-60), "1m ago");
+        assert_eq!(relative(-3_599), "59m ago");
+        assert_eq!(relative(3_600), "in 1h");
+        assert_eq!(relative(-86_400), "1d ago");
+        assert_eq!(relative(-2_592_000), "1mo ago");
+        assert_eq!(relative(-31_536_000 * 3), "3y ago");
+    }
+
+    #[test]
+    fn parse_zone_accepts_names_and_offsets() {
+        assert_eq!(parse_zone("").unwrap(), Zone::Local);
+        assert_eq!(parse_zone(" UTC ").unwrap(), Zone::Utc);
+        let plus = parse_zone("+05:30").unwrap();
+        assert_eq!(plus, Zone::Fixed(FixedOffset::east_opt(5 * 3600 + 30 * 60).unwrap()));
+        assert_eq!(parse_zone("-0800").unwrap(), Zone::Fixed(FixedOffset::west_opt(8 * 3600).unwrap()));
+        assert_eq!(parse_zone("+02").unwrap(), Zone::Fixed(FixedOffset::east_opt(2 * 3600).unwrap()));
+        for bad in ["Europe/Paris", "+15:00", "+05:60", "05:30", "+5"] {
+            assert!(parse_zone(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn a_fixed_clock_renders_relative_to_its_now() {
+        let clock = Clock::fixed("2026-10-14T12:00:00Z");
+        let shown = clock.display("2026-10-14T11:57:00+00:00").unwrap();
+        assert_eq!(shown.local, "2026-10-14 11:57:00 UTC");
+        assert_eq!(shown.relative, "3m ago");
+        assert!(clock.display("yesterday").is_none());
+        assert!(clock.display_opt(None).is_none());
+    }
+
+    #[test]
+    fn fixed_offsets_and_12h_format() {
+        let now = DateTime::parse_from_rfc3339("2026-10-14T12:00:00Z").unwrap().with_timezone(&Utc);
+        let zone = parse_zone("-08:00").unwrap();
+        let clock = Clock::at(zone, TimeFormat::H12, now);
+        let shown = clock.display("2026-10-14T14:30:00Z").unwrap();
+        assert_eq!(shown.local, "2026-10-14 6:30:00 AM -08:00");
+        assert_eq!(shown.relative, "in 2h");
+    }
+
+    #[test]
+    fn fixed_zones_round_trip_wall_clock_time() {
+        let at = DateTime::parse_from_rfc3339("2026-03-29T01:30:00Z").unwrap().with_timezone(&Utc);
+        for zone in [Zone::Utc, parse_zone("+05:30").unwrap(), parse_zone("-03:00").unwrap()] {
+            assert_eq!(zone.instant(zone.naive(at)), at);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+// Clock synchronization status (timedatectl, chrony, systemd-timesyncd)
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::app::State;
+use crate::exec;
+use crate::settings::SettingsStore;
+
+#[derive(Serialize, Default, Debug, Clone)]
+pub struct TimeSyncStatus {
+    pub system_time: String,
+    pub timezone: Option<String>,
+    pub ntp_enabled: Option<bool>,
+    pub ntp_synchronized: Option<bool>,
+    // Local clock minus reference clock; positive means we're ahead
+    pub offset_ms: Option<f64>,
+    pub sync_source: Option<String>,
+    // "chrony" or "systemd-timesyncd", whichever answered
+    pub sync_service: Option<String>,
+    pub drift_threshold_ms: f64,
+    pub drift_warning: bool,
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_time_sync_status(settings: State<'_, SettingsStore>) -> TimeSyncStatus {
+    probe_time_sync(settings.get().time_drift_threshold_ms)
+}
+
+pub fn probe_time_sync(drift_threshold_ms: f64) -> TimeSyncStatus {
+    let mut status = TimeSyncStatus {
+        system_time: chrono::Local::now().to_rfc3339(),
+        drift_threshold_ms,
+        ..Default::default()
+    };
+
+    if let Some(out) = exec::stdout("timedatectl", &["show"]) {
+        let values = parse_key_values(&out);
+        status.timezone = values.get("Timezone").filter(|v| !v.is_empty()).map(|v| v.to_string());
+        status.ntp_enabled = values.get("NTP").and_then(|v| parse_yes_no(v));
+        status.ntp_synchronized = values.get("NTPSynchronized").and_then(|v| parse_yes_no(v));
+    }
+
+    // Prefer chrony when it's running; timesyncd is the systemd fallback
+    if let Some(out) = exec::stdout("chronyc", &["tracking"]) {
+        let (source, offset) = parse_chronyc_tracking(&out);
+        status.sync_service = Some("chrony".to_string());
+        status.sync_source = source;
+        status.offset_ms = offset;
+    } else if let Some(out) = exec::stdout("timedatectl", &["timesync-status"]) {
+        let (source, offset) = parse_timesync_status(&out);
+        status.sync_service = Some("systemd-timesyncd".to_string());
+        status.sync_source = source;
+        status.offset_ms = offset;
+    }
+
+    if status.timezone.is_none() {
+        status.timezone = std::fs::read_to_string("/etc/timezone")
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+    }
+
+    status.drift_warning = status
+        .offset_ms
+        .map(|offset| offset.abs() > drift_threshold_ms)
+        .unwrap_or(false);
+    status
+}
+
+// `timedatectl show` prints one KEY=value pair per line
+fn parse_key_values(output: &str) -> HashMap<&str, &str> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(k, v)| (k.trim(), v.trim()))
+        .collect()
+}
+
+fn parse_yes_no(value: &str) -> Option<bool> {
+    match value {
+        "yes" | "true" | "1" => Some(true),
+        "no" | "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+// Human-readable `KEY : value` / `KEY: value` reports used by both
+// `chronyc tracking` and `timedatectl timesync-status`
+fn parse_colon_fields(output: &str) -> HashMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect()
+}
+
+// chronyc tracking:
+//   Reference ID    : C0A80001 (gateway.lan)
+//   System time     : 0.000123456 seconds slow of NTP time
+fn parse_chronyc_tracking(output: &str) -> (Option<String>, Option<f64>) {
+    let fields = parse_colon_fields(output);
+
+    let source = fields.get("Reference ID").map(|v| {
+        // Prefer the name in parentheses over the hex reference id
+        match (v.find('('), v.rfind(')')) {
+            (Some(start), Some(end)) if end > start + 1 => v[start + 1..end].to_string(),
+            _ => v.clone(),
+        }
+    });
+
+    let offset = fields.get("System time").and_then(|v| {
+        let mut parts = v.split_whitespace();
+        let seconds: f64 = parts.next()?.parse().ok()?;
+        let _unit = parts.next()?;
+        let sign = match parts.next()? {
+            "fast" => 1.0,
+            "slow" => -1.0,
+            _ => return None,
+        };
+        Some(sign * seconds * 1000.0)
+    });
+
+    (source, offset)
+}
+
+// timedatectl timesync-status:
+//          Server: 185.125.190.57 (ntp.ubuntu.com)
+//          Offset: -1.208ms
+fn parse_timesync_status(output: &str) -> (Option<String>, Option<f64>) {
+    let fields = parse_colon_fields(output);
+    let source = fields.get("Server").filter(|v| !v.is_empty()).cloned();
+    // timesyncd reports the NTP offset (server minus local), so flip the
+    // sign to match chrony's "local minus reference" convention
+    let offset = fields
+        .get("Offset")
+        .and_then(|v| parse_duration_ms(v))
+        .map(|ms| -ms);
+    (source, offset)
+}
+
+// Parse systemd-style durations such as "+1.208ms", "-215us" or "1.5s"
+fn parse_duration_ms(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let factor = match unit.trim() {
+        "ns" => 0.000001,
+        "us" | "µs" => 0.001,
+        "ms" => 1.0,
+        "s" | "" => 1000.0,
+        "min" => 60_000.0,
+        _ => return None,
+    };
+    Some(number * factor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: Option<f64>, expected: f64) -> bool {
+        actual.is_some_and(|v| (v - expected).abs() < 1e-9)
+    }
+
+    const CHRONY: &str = "Reference ID    : C0A80001 (gateway.lan)
+Stratum         : 3
+Ref time (UTC)  : Wed Oct 14 12:00:00 2026
+System time     : 0.000123456 seconds slow of NTP time
+Last offset     : -0.000010000 seconds
+";
+
+    #[test]
+    fn chrony_prefers_the_name_and_signs_the_offset() {
+        let (source, offset) = parse_chronyc_tracking(CHRONY);
+        assert_eq!(source.as_deref(), Some("gateway.lan"));
+        assert!(close(offset, -0.123456), "{:?}", offset);
+
+        let fast = CHRONY.replace("seconds slow of", "seconds fast of");
+        assert!(close(parse_chronyc_tracking(&fast).1, 0.123456));
+    }
+
+    #[test]
+    fn chrony_without_a_name_or_sign() {
+        let output = "Reference ID    : 7F7F0101 ()\nSystem time     : 0.5 seconds sideways of NTP time\n";
+        let (source, offset) = parse_chronyc_tracking(output);
+        assert_eq!(source.as_deref(), Some("7F7F0101 ()"));
+        assert_eq!(offset, None);
+        assert_eq!(parse_chronyc_tracking(""), (None, None));
+    }
+
+    #[test]
+    fn timesyncd_flips_the_offset_sign() {
+        let output = "       Server: 185.125.190.57 (ntp.ubuntu.com)
+Poll interval: 34min 8s (min: 32s; max 34min 8s)
+       Offset: -1.208ms
+";
+        let (source, offset) = parse_timesync_status(output);
+        assert_eq!(source.as_deref(), Some("185.125.190.57 (ntp.ubuntu.com)"));
+        assert!(close(offset, 1.208), "{:?}", offset);
+
+        let (source, offset) = parse_timesync_status("Server: \nOffset: unknown\n");
+        assert_eq!((source, offset), (None, None));
+    }
+
+    #[test]
+    fn durations_in_every_unit() {
+        assert!(close(parse_duration_ms("+1.208ms"), 1.208));
+        assert!(close(parse_duration_ms("-215us"), -0.215));
+        assert!(close(parse_duration_ms("215µs"), 0.215));
+        assert!(close(parse_duration_ms("1.5s"), 1500.0));
+        assert!(close(parse_duration_ms("2"), 2000.0));
+        assert!(close(parse_duration_ms("500ns"), 0.0005));
+        assert!(close(parse_duration_ms(" 1min "), 60_000.0));
+        assert_eq!(parse_duration_ms("1h"), None);
+        assert_eq!(parse_duration_ms("ms"), None);
+        assert_eq!(parse_duration_ms(""), None);
+    }
+}
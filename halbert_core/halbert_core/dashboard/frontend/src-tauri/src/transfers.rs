@@ -0,0 +1,251 @@
+// Large command responses, handed over in chunks.
+//
+// A command whose JSON would pass THRESHOLD_BYTES returns a handle
+// `{transfer_id, total_size, chunk_count, sha256}` instead of the value;
+// smaller ones return the value as they always did (see `respond`). The
+// frontend pulls each chunk with get_transfer_chunk, as raw bytes rather
+// than another JSON string, gzip-compressed on request, joins them and
+// checks the SHA-256 of the whole before parsing. A transfer is kept until
+// TTL or cancel_transfer, so a chunk can be fetched again after a failed
+// read or checksum; the store also keeps under MAX_HELD_BYTES by dropping
+// the oldest.
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use std::ops::Range;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+#[cfg(feature = "gui")]
+use tauri::ipc::Response;
+
+use crate::app::State;
+use crate::error::{CommandError, CommandResult};
+
+pub const THRESHOLD_BYTES: usize = 1024 * 1024;
+pub const CHUNK_BYTES: usize = 256 * 1024;
+const TTL: Duration = Duration::from_secs(5 * 60);
+const MAX_HELD_BYTES: usize = 256 * 1024 * 1024;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct TransferHandle {
+    pub transfer_id: String,
+    pub total_size: usize,
+    pub chunk_count: usize,
+    // Of all the bytes, hex
+    pub sha256: String,
+}
+
+// What a command that may go large returns: the value itself, or a handle
+// to fetch it by. A handle is told apart by its transfer_id.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum MaybeTransfer<T> {
+    Inline(T),
+    Transfer(TransferHandle),
+}
+
+struct Transfer {
+    data: Vec<u8>,
+    created: Instant,
+}
+
+#[derive(Default)]
+pub struct TransferStore {
+    transfers: Mutex<HashMap<String, Transfer>>,
+    next_id: Mutex<u64>,
+}
+
+pub fn checksum(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn chunk_count(total: usize) -> usize {
+    total.div_ceil(CHUNK_BYTES)
+}
+
+// Byte range of chunk `index`, None past the end
+pub fn chunk_range(total: usize, index: usize) -> Option<Range<usize>> {
+    let start = index.checked_mul(CHUNK_BYTES)?;
+    (start < total).then(|| start..(start + CHUNK_BYTES).min(total))
+}
+
+pub fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::with_capacity(data.len() / 2), Compression::fast());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+impl TransferStore {
+    fn sweep(transfers: &mut HashMap<String, Transfer>, incoming: usize) {
+        transfers.retain(|_, t| t.created.elapsed() < TTL);
+        let mut held: usize = transfers.values().map(|t| t.data.len()).sum();
+        while held + incoming > MAX_HELD_BYTES {
+            let Some(oldest) = transfers.iter().min_by_key(|(_, t)| t.created).map(|(id, _)| id.clone()) else {
+                break;
+            };
+            if let Some(dropped) = transfers.remove(&oldest) {
+                println!("[Halbert] Dropped transfer {} to make room", oldest);
+                held -= dropped.data.len();
+            }
+        }
+    }
+
+    pub fn start(&self, data: Vec<u8>) -> TransferHandle {
+        let transfer_id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            format!("xfer_{:03}", *next_id)
+        };
+        let handle = TransferHandle {
+            transfer_id: transfer_id.clone(),
+            total_size: data.len(),
+            chunk_count: chunk_count(data.len()),
+            sha256: checksum(&data),
+        };
+        let mut transfers = self.transfers.lock().unwrap();
+        Self::sweep(&mut transfers, data.len());
+        transfers.insert(
+            transfer_id,
+            Transfer {
+                data,
+                created: Instant::now(),
+            },
+        );
+        handle
+    }
+
+    pub fn chunk(&self, transfer_id: &str, index: usize) -> CommandResult<Vec<u8>> {
+        let mut transfers = self.transfers.lock().unwrap();
+        Self::sweep(&mut transfers, 0);
+        let transfer = transfers
+            .get(transfer_id)
+            .ok_or_else(|| CommandError::NotFound(format!("transfer {} (it may have expired)", transfer_id)))?;
+        let total = transfer.data.len();
+        let range = chunk_range(total, index).ok_or_else(|| {
+            CommandError::InvalidInput(format!("{} has chunks 0 to {}", transfer_id, chunk_count(total).saturating_sub(1)))
+        })?;
+        Ok(transfer.data[range].to_vec())
+    }
+
+    pub fn cancel(&self, transfer_id: &str) -> bool {
+        self.transfers.lock().unwrap().remove(transfer_id).is_some()
+    }
+
+    // The whole of a transfer, which is then done with
+    pub fn take(&self, transfer_id: &str) -> Option<Vec<u8>> {
+        self.transfers.lock().unwrap().remove(transfer_id).map(|t| t.data)
+    }
+}
+
+// The value as it is when its JSON is small, otherwise a transfer of it
+pub fn respond<T: Serialize>(store: &TransferStore, value: T) -> CommandResult<MaybeTransfer<T>> {
+    let data = serde_json::to_vec(&value).map_err(|e| CommandError::Internal(format!("failed to encode response: {}", e)))?;
+    if data.len() <= THRESHOLD_BYTES {
+        return Ok(MaybeTransfer::Inline(value));
+    }
+    let handle = store.start(data);
+    println!(
+        "[Halbert] Sending {} bytes as transfer {} in {} chunk(s)",
+        handle.total_size, handle.transfer_id, handle.chunk_count
+    );
+    Ok(MaybeTransfer::Transfer(handle))
+}
+
+// Raw bytes of one chunk; with `compressed` they are gzipped on their own,
+// so each chunk decompresses without the others
+// A raw IPC response, which only the window's IPC has
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn get_transfer_chunk(
+    store: State<'_, TransferStore>,
+    transfer_id: String,
+    index: usize,
+    compressed: Option<bool>,
+) -> CommandResult<Response> {
+    let chunk = store.chunk(&transfer_id, index)?;
+    let body = if compressed.unwrap_or(false) { gzip(&chunk)? } else { chunk };
+    Ok(Response::new(body))
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn cancel_transfer(store: State<'_, TransferStore>, transfer_id: String) -> bool {
+    store.cancel(&transfer_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    // Several MB of rows that don't repeat chunk to chunk
+    fn large_value() -> Vec<serde_json::Value> {
+        (0..120_000)
+            .map(|i| serde_json::json!({"id": i, "path": format!("/srv/data/{:08x}/file-{}.log", i * 2654435761u64, i)}))
+            .collect()
+    }
+
+    fn fetch_all(store: &TransferStore, handle: &TransferHandle, compressed: bool) -> Vec<u8> {
+        let mut data = Vec::with_capacity(handle.total_size);
+        for index in 0..handle.chunk_count {
+            let chunk = store.chunk(&handle.transfer_id, index).unwrap();
+            if compressed {
+                let mut inflated = Vec::new();
+                flate2::read::GzDecoder::new(&gzip(&chunk).unwrap()[..]).read_to_end(&mut inflated).unwrap();
+                data.extend(inflated);
+            } else {
+                data.extend(chunk);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn a_multi_megabyte_response_reassembles_to_its_checksum() {
+        let store = TransferStore::default();
+        let value = large_value();
+        let MaybeTransfer::Transfer(handle) = respond(&store, &value).unwrap() else {
+            panic!("a response this large should be a transfer");
+        };
+        assert!(handle.total_size > 4 * THRESHOLD_BYTES);
+        assert_eq!(handle.chunk_count, chunk_count(handle.total_size));
+
+        for compressed in [false, true] {
+            let data = fetch_all(&store, &handle, compressed);
+            assert_eq!(data.len(), handle.total_size);
+            assert_eq!(checksum(&data), handle.sha256);
+            let parsed: Vec<serde_json::Value> = serde_json::from_slice(&data).unwrap();
+            assert_eq!(parsed, value);
+        }
+    }
+
+    #[test]
+    fn a_fetched_transfer_stays_until_cancelled() {
+        let store = TransferStore::default();
+        let handle = store.start(vec![7; CHUNK_BYTES * 2 + 10]);
+        fetch_all(&store, &handle, false);
+        // A retry after a bad read of the last chunk
+        assert_eq!(store.chunk(&handle.transfer_id, 2).unwrap(), vec![7; 10]);
+        assert!(store.cancel(&handle.transfer_id));
+        assert!(matches!(store.chunk(&handle.transfer_id, 0), Err(CommandError::NotFound(_))));
+        assert!(!store.cancel(&handle.transfer_id));
+    }
+
+    #[test]
+    fn chunks_past_the_end_are_refused() {
+        let store = TransferStore::default();
+        let handle = store.start(vec![1; CHUNK_BYTES]);
+        assert_eq!(handle.chunk_count, 1);
+        assert!(matches!(store.chunk(&handle.transfer_id, 1), Err(CommandError::InvalidInput(_))));
+        assert!(matches!(store.chunk(&handle.transfer_id, usize::MAX), Err(CommandError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn small_responses_stay_inline() {
+        let store = TransferStore::default();
+        assert!(matches!(respond(&store, "small").unwrap(), MaybeTransfer::Inline("small")));
+        assert!(store.transfers.lock().unwrap().is_empty());
+    }
+}
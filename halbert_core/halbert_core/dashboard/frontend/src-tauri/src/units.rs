@@ -0,0 +1,109 @@
+// Byte sizes for display, in the unit system picked in settings.
+//
+// Every size the UI shows (metrics, documents, reports) goes through here
+// so the frontend never converts bytes itself and all views agree.
+// "binary" is powers of 1024 labelled KiB/MiB/GiB; "decimal" is powers of
+// 1000 labelled kB/MB/GB.
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Units {
+    #[default]
+    Binary,
+    Decimal,
+}
+
+const BINARY_LABELS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+const DECIMAL_LABELS: &[&str] = &["B", "kB", "MB", "GB", "TB", "PB", "EB"];
+
+impl Units {
+    fn base(self) -> f64 {
+        match self {
+            Units::Binary => 1024.0,
+            Units::Decimal => 1000.0,
+        }
+    }
+
+    fn labels(self) -> &'static [&'static str] {
+        match self {
+            Units::Binary => BINARY_LABELS,
+            Units::Decimal => DECIMAL_LABELS,
+        }
+    }
+
+    // For the legacy float fields (size_kb, *_gb)
+    pub fn kb(self, bytes: u64) -> f32 {
+        (bytes as f64 / self.base()) as f32
+    }
+
+    pub fn gb(self, bytes: u64) -> f32 {
+        (bytes as f64 / self.base().powi(3)) as f32
+    }
+
+    // "0 B", "812 B", "1.5 MiB", "931.3 GiB". One decimal above bytes; a
+    // value that would round up to the next unit is shown in that unit
+    // ("1.0 GiB", not "1024.0 MiB").
+    pub fn format_bytes(self, bytes: u64) -> String {
+        let base = self.base();
+        let labels = self.labels();
+        if (bytes as f64) < base {
+            return format!("{} B", bytes);
+        }
+        let mut value = bytes as f64;
+        let mut power = 0;
+        while power + 1 < labels.len() && value >= base {
+            value /= base;
+            power += 1;
+        }
+        if (value * 10.0).round() / 10.0 >= base && power + 1 < labels.len() {
+            value /= base;
+            power += 1;
+        }
+        format!("{:.1} {}", value, labels[power])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: u64 = 1 << 30;
+
+    #[test]
+    fn zero_and_small_sizes_stay_in_bytes() {
+        assert_eq!(Units::Binary.format_bytes(0), "0 B");
+        assert_eq!(Units::Decimal.format_bytes(0), "0 B");
+        assert_eq!(Units::Binary.format_bytes(1023), "1023 B");
+        assert_eq!(Units::Binary.format_bytes(1024), "1.0 KiB");
+        assert_eq!(Units::Decimal.format_bytes(999), "999 B");
+        assert_eq!(Units::Decimal.format_bytes(1000), "1.0 kB");
+    }
+
+    #[test]
+    fn just_under_a_unit_rounds_up_into_it() {
+        assert_eq!(Units::Binary.format_bytes(GIB - 1), "1.0 GiB");
+        assert_eq!(Units::Binary.format_bytes(GIB), "1.0 GiB");
+        assert_eq!(Units::Decimal.format_bytes(999_999_999), "1.0 GB");
+        // Far enough below not to round up
+        assert_eq!(Units::Binary.format_bytes(GIB - 100 * (1 << 20)), "924.0 MiB");
+        assert_eq!(Units::Decimal.format_bytes(999_940_000), "999.9 MB");
+    }
+
+    #[test]
+    fn huge_disks_stop_at_the_largest_unit() {
+        assert_eq!(Units::Binary.format_bytes(4 * (1 << 40)), "4.0 TiB");
+        assert_eq!(Units::Decimal.format_bytes(18_000_000_000_000), "18.0 TB");
+        assert_eq!(Units::Binary.format_bytes(u64::MAX), "16.0 EiB");
+        assert_eq!(Units::Decimal.format_bytes(u64::MAX), "18.4 EB");
+    }
+
+    #[test]
+    fn legacy_fields_divide_by_the_unit_base() {
+        assert_eq!(Units::Binary.kb(2048), 2.0);
+        assert_eq!(Units::Decimal.kb(2000), 2.0);
+        assert_eq!(Units::Binary.gb(3 * GIB), 3.0);
+        assert_eq!(Units::Decimal.gb(0), 0.0);
+        assert!(Units::Binary.gb(u64::MAX).is_finite());
+    }
+}
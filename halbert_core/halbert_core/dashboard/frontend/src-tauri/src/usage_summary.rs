@@ -0,0 +1,659 @@
+// Usage summaries per calendar period, for capacity planning.
+//
+// get_usage_summary("day" | "week" | "month") compares the current period
+// in the configured timezone (weeks start on Monday) with the one before.
+// Local metrics samples are stored at most once a minute, with the network
+// bytes moved since the previous one (/proc/net/dev, loopback left out).
+// Storage maintenance rolls complete hours into metrics_hourly and drops
+// samples a day after they're rolled up, so a month is a few hundred rows;
+// hours not rolled up yet are read from the samples. CPU p95 comes from
+// merged histograms of whole percents. Disk growth is the change between
+// the first and last disk usage sample of each mount in the period, and
+// jobs count in the period they finished. The current period is flagged
+// incomplete: its totals are compared with the previous period's scaled
+// to the share that has elapsed, averages and peaks as they are. Rollup
+// hours are UTC hours, so under a half-hour offset period edges are off
+// by up to half an hour.
+use chrono::{DateTime, Datelike, Months, NaiveDate, NaiveTime, Utc};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::app::{AppHandle, Manager, State};
+use crate::db::Database;
+use crate::error::{CommandError, CommandResult};
+use crate::jobs::Job;
+use crate::settings::SettingsStore;
+use crate::timestamps::{self, Clock, Localize, TimeDisplay, Zone};
+
+const PERSIST_INTERVAL_SECS: i64 = 60;
+const RAW_KEEP_SECS: i64 = 86_400;
+const ROLLUP_RETENTION_DAYS: i64 = 400;
+const HOUR: i64 = 3600;
+// Whole CPU percents, 0 to 100
+const BUCKETS: usize = 101;
+
+// The last stored sample's time and the interface byte counters then
+#[derive(Default)]
+pub struct UsageRecorder {
+    last: Mutex<Option<(i64, u64, u64)>>,
+}
+
+// Received and sent bytes summed over every interface but loopback
+pub fn parse_net_dev(text: &str) -> (u64, u64) {
+    text.lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (name, rest) = line.split_once(':')?;
+            if name.trim() == "lo" {
+                return None;
+            }
+            let fields: Vec<u64> = rest.split_whitespace().filter_map(|f| f.parse().ok()).collect();
+            Some((*fields.first()?, *fields.get(8)?))
+        })
+        .fold((0, 0), |(rx, tx), (r, t)| (rx + r, tx + t))
+}
+
+// Called by the sampler for every local sample
+pub fn record_sample(app: &AppHandle, at: i64, cpu_percent: f32, memory_percent: f32) {
+    let recorder = app.state::<UsageRecorder>();
+    let mut last = recorder.last.lock().unwrap();
+    if last.is_some_and(|(previous, _, _)| at - previous < PERSIST_INTERVAL_SECS) {
+        return;
+    }
+    let (rx, tx) = std::fs::read_to_string("/proc/net/dev")
+        .map(|text| parse_net_dev(&text))
+        .unwrap_or_default();
+    // Nothing is known from before the first sample; a counter that went
+    // back belongs to an interface that went away
+    let (rx_delta, tx_delta) = match *last {
+        Some((_, previous_rx, previous_tx)) => (rx.saturating_sub(previous_rx), tx.saturating_sub(previous_tx)),
+        None => (0, 0),
+    };
+    let stored = app.state::<Database>().with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO metrics_samples (at, cpu_percent, memory_percent, rx_bytes, tx_bytes) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![at, cpu_percent as f64, memory_percent as f64, rx_delta as i64, tx_delta as i64],
+        )
+    });
+    match stored {
+        Ok(_) => *last = Some((at, rx, tx)),
+        Err(e) => println!("[Halbert] Failed to store metrics sample: {}", e),
+    }
+}
+
+// Wired to job updates; a job is stored once it has finished
+pub fn record_job(db: &Database, job: &Job) {
+    let finished = job.finished_at.as_deref().and_then(|t| DateTime::parse_from_rfc3339(t).ok());
+    let (Some(finished), Ok(started)) = (finished, DateTime::parse_from_rfc3339(&job.started_at)) else {
+        return;
+    };
+    let duration_secs = (finished - started).num_milliseconds().max(0) as f64 / 1000.0;
+    let stored = db.with_conn(|conn| {
+        conn.execute(
+            "INSERT OR REPLACE INTO job_runs (job_id, started_at, task_type, status, finished_at, duration_secs)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![job.id, job.started_at, job.task_type, job.status, finished.timestamp(), duration_secs],
+        )
+    });
+    if let Err(e) = stored {
+        println!("[Halbert] Failed to record job run {}: {}", job.id, e);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rollup {
+    pub samples: u64,
+    pub cpu_sum: f64,
+    pub cpu_histogram: Vec<u64>,
+    pub memory_sum: f64,
+    pub memory_peak: f64,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+impl Default for Rollup {
+    fn default() -> Self {
+        Rollup {
+            samples: 0,
+            cpu_sum: 0.0,
+            cpu_histogram: vec![0; BUCKETS],
+            memory_sum: 0.0,
+            memory_peak: 0.0,
+            rx_bytes: 0,
+            tx_bytes: 0,
+        }
+    }
+}
+
+impl Rollup {
+    pub fn add(&mut self, cpu_percent: f64, memory_percent: f64, rx_bytes: u64, tx_bytes: u64) {
+        self.samples += 1;
+        self.cpu_sum += cpu_percent;
+        self.cpu_histogram[cpu_percent.round().clamp(0.0, 100.0) as usize] += 1;
+        self.memory_sum += memory_percent;
+        self.memory_peak = self.memory_peak.max(memory_percent);
+        self.rx_bytes += rx_bytes;
+        self.tx_bytes += tx_bytes;
+    }
+
+    pub fn merge(&mut self, other: &Rollup) {
+        self.samples += other.samples;
+        self.cpu_sum += other.cpu_sum;
+        for (bucket, count) in self.cpu_histogram.iter_mut().zip(&other.cpu_histogram) {
+            *bucket += count;
+        }
+        self.memory_sum += other.memory_sum;
+        self.memory_peak = self.memory_peak.max(other.memory_peak);
+        self.rx_bytes += other.rx_bytes;
+        self.tx_bytes += other.tx_bytes;
+    }
+}
+
+// The smallest bucket with at least `quantile` of the counts at or below it
+pub fn percentile(histogram: &[u64], quantile: f64) -> Option<f64> {
+    let total: u64 = histogram.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    let rank = ((quantile * total as f64).ceil() as u64).max(1);
+    let mut seen = 0;
+    histogram
+        .iter()
+        .position(|count| {
+            seen += count;
+            seen >= rank
+        })
+        .map(|bucket| bucket as f64)
+}
+
+fn rolled_until(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row("SELECT COALESCE(MAX(hour) + ?1, 0) FROM metrics_hourly", params![HOUR], |r| r.get(0))
+}
+
+// Samples in [start, end), bucketed by the UTC hour they fall in
+fn sample_hours(conn: &Connection, start: i64, end: i64) -> rusqlite::Result<BTreeMap<i64, Rollup>> {
+    let mut stmt = conn.prepare(
+        "SELECT at, cpu_percent, memory_percent, rx_bytes, tx_bytes FROM metrics_samples WHERE at >= ?1 AND at < ?2",
+    )?;
+    let mut hours: BTreeMap<i64, Rollup> = BTreeMap::new();
+    let rows = stmt.query_map(params![start, end], |r| {
+        Ok((r.get::<_, i64>(0)?, r.get(1)?, r.get(2)?, r.get::<_, i64>(3)?, r.get::<_, i64>(4)?))
+    })?;
+    for row in rows {
+        let (at, cpu, memory, rx, tx) = row?;
+        hours
+            .entry(at - at.rem_euclid(HOUR))
+            .or_default()
+            .add(cpu, memory, rx.max(0) as u64, tx.max(0) as u64);
+    }
+    Ok(hours)
+}
+
+// Called by storage maintenance: rolls the complete hours not rolled up yet
+// into metrics_hourly and prunes. Returns rows deleted by table.
+pub fn rollup(db: &Database) -> CommandResult<BTreeMap<String, usize>> {
+    let now = Utc::now().timestamp();
+    let current_hour = now - now.rem_euclid(HOUR);
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let start = rolled_until(&tx)?;
+        for (hour, rollup) in sample_hours(&tx, start, current_hour)? {
+            let histogram = serde_json::to_string(&rollup.cpu_histogram).unwrap_or_default();
+            tx.execute(
+                "INSERT OR REPLACE INTO metrics_hourly
+                 (hour, samples, cpu_sum, cpu_histogram, memory_sum, memory_peak, rx_bytes, tx_bytes)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    hour,
+                    rollup.samples as i64,
+                    rollup.cpu_sum,
+                    histogram,
+                    rollup.memory_sum,
+                    rollup.memory_peak,
+                    rollup.rx_bytes as i64,
+                    rollup.tx_bytes as i64
+                ],
+            )?;
+        }
+        let mut deleted = BTreeMap::new();
+        // Everything before the current hour has just been rolled up
+        let samples = tx.execute("DELETE FROM metrics_samples WHERE at < ?1", params![now - RAW_KEEP_SECS])?;
+        let hourly = tx.execute(
+            "DELETE FROM metrics_hourly WHERE hour < ?1",
+            params![now - ROLLUP_RETENTION_DAYS * 86_400],
+        )?;
+        tx.commit()?;
+        deleted.insert("metrics_samples".to_string(), samples);
+        deleted.insert("metrics_hourly".to_string(), hourly);
+        Ok(deleted)
+    })
+}
+
+fn load_usage(conn: &Connection, start: i64, end: i64) -> rusqlite::Result<Rollup> {
+    let mut usage = Rollup::default();
+    let mut stmt = conn.prepare(
+        "SELECT samples, cpu_sum, cpu_histogram, memory_sum, memory_peak, rx_bytes, tx_bytes FROM metrics_hourly
+         WHERE hour >= ?1 AND hour < ?2",
+    )?;
+    let rows = stmt.query_map(params![start, end], |r| {
+        let histogram: String = r.get(2)?;
+        Ok(Rollup {
+            samples: r.get::<_, i64>(0)? as u64,
+            cpu_sum: r.get(1)?,
+            cpu_histogram: serde_json::from_str(&histogram).unwrap_or_else(|_| vec![0; BUCKETS]),
+            memory_sum: r.get(3)?,
+            memory_peak: r.get(4)?,
+            rx_bytes: r.get::<_, i64>(5)? as u64,
+            tx_bytes: r.get::<_, i64>(6)? as u64,
+        })
+    })?;
+    for row in rows {
+        usage.merge(&row?);
+    }
+    for hour in sample_hours(conn, start.max(rolled_until(conn)?), end)?.values() {
+        usage.merge(hour);
+    }
+    Ok(usage)
+}
+
+// Used space at the first and last sample of each mount in [start, end);
+// SQLite takes the bare column from the row MIN or MAX picked
+fn disk_growth(conn: &Connection, start: i64, end: i64) -> rusqlite::Result<BTreeMap<String, f64>> {
+    let edge = |aggregate: &str| -> rusqlite::Result<BTreeMap<String, (i64, f64)>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT mount_point, {}(at), used_gb FROM disk_usage_samples WHERE at >= ?1 AND at < ?2 GROUP BY mount_point",
+            aggregate
+        ))?;
+        let rows = stmt.query_map(params![start, end], |r| Ok((r.get(0)?, (r.get(1)?, r.get(2)?))))?;
+        rows.collect()
+    };
+    let first = edge("MIN")?;
+    let last = edge("MAX")?;
+    Ok(first
+        .into_iter()
+        .filter_map(|(mount, (first_at, first_used))| {
+            let (last_at, last_used) = last.get(&mount)?;
+            (*last_at > first_at).then(|| (mount, last_used - first_used))
+        })
+        .collect())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+impl Period {
+    pub fn parse(text: &str) -> CommandResult<Self> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "day" => Ok(Period::Day),
+            "week" => Ok(Period::Week),
+            "month" => Ok(Period::Month),
+            other => Err(CommandError::InvalidInput(format!(
+                "unknown period '{}', expected day, week or month",
+                other
+            ))),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Period::Day => "day",
+            Period::Week => "week",
+            Period::Month => "month",
+        }
+    }
+}
+
+// The first day of the previous period, of the current one (holding
+// `today`) and of the next
+pub fn period_dates(period: Period, today: NaiveDate) -> (NaiveDate, NaiveDate, NaiveDate) {
+    match period {
+        Period::Day => (today.pred_opt().unwrap_or(today), today, today.succ_opt().unwrap_or(today)),
+        Period::Week => {
+            let monday = today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64);
+            (monday - chrono::Duration::days(7), monday, monday + chrono::Duration::days(7))
+        }
+        Period::Month => {
+            let first = today.with_day(1).unwrap_or(today);
+            (
+                first.checked_sub_months(Months::new(1)).unwrap_or(first),
+                first,
+                first.checked_add_months(Months::new(1)).unwrap_or(first),
+            )
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct PeriodUsage {
+    pub start: String,
+    // Exclusive
+    pub end: String,
+    pub complete: bool,
+    // Share of the period that has passed; 1 once complete
+    pub elapsed_fraction: f64,
+    pub samples: u64,
+    pub cpu_avg_percent: Option<f64>,
+    pub cpu_p95_percent: Option<f64>,
+    pub memory_avg_percent: Option<f64>,
+    pub memory_peak_percent: Option<f64>,
+    // Per mount point, for mounts with two or more samples in the period
+    pub disk_growth_gb: BTreeMap<String, f64>,
+    pub network_rx_bytes: u64,
+    pub network_tx_bytes: u64,
+    pub jobs: u64,
+    pub jobs_failed: u64,
+    pub job_seconds_total: f64,
+    pub job_seconds_avg: Option<f64>,
+    // Filled in per response; see timestamps
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_display: Option<TimeDisplay>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_display: Option<TimeDisplay>,
+}
+
+impl Localize for PeriodUsage {
+    fn localize(&mut self, clock: &Clock) {
+        self.start_display = clock.display(&self.start);
+        self.end_display = clock.display(&self.end);
+    }
+}
+
+// Percent changes of the current period against the previous one; None
+// where either side has no data or the baseline is zero
+#[derive(Serialize, Clone)]
+pub struct UsageDeltas {
+    pub cpu_avg_percent: Option<f64>,
+    pub cpu_p95_percent: Option<f64>,
+    pub memory_avg_percent: Option<f64>,
+    pub memory_peak_percent: Option<f64>,
+    pub disk_growth_gb: BTreeMap<String, Option<f64>>,
+    pub network_rx_bytes: Option<f64>,
+    pub network_tx_bytes: Option<f64>,
+    pub jobs: Option<f64>,
+    pub job_seconds_avg: Option<f64>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct UsageSummary {
+    pub period: String,
+    pub timezone: String,
+    pub current: PeriodUsage,
+    pub previous: PeriodUsage,
+    // Totals (transfer, jobs, disk growth) were compared with the previous
+    // period scaled by current.elapsed_fraction
+    pub pro_rata: bool,
+    pub deltas: UsageDeltas,
+}
+
+pub fn percent_change(current: Option<f64>, baseline: Option<f64>) -> Option<f64> {
+    let (current, baseline) = (current?, baseline?);
+    (baseline.abs() > f64::EPSILON).then(|| (current - baseline) / baseline * 100.0)
+}
+
+fn period_usage(
+    conn: &Connection,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> rusqlite::Result<PeriodUsage> {
+    let (from, to) = (start.timestamp(), end.timestamp());
+    let usage = load_usage(conn, from, to)?;
+    let (jobs, jobs_failed, job_seconds_total): (i64, i64, f64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(status = 'failed'), 0), COALESCE(SUM(duration_secs), 0) FROM job_runs
+         WHERE finished_at >= ?1 AND finished_at < ?2",
+        params![from, to],
+        |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+    )?;
+    let average = |sum: f64| (usage.samples > 0).then(|| sum / usage.samples as f64);
+    let length = (end - start).num_seconds().max(1) as f64;
+    let elapsed_fraction = ((now - start).num_seconds() as f64 / length).clamp(0.0, 1.0);
+    Ok(PeriodUsage {
+        start: start.to_rfc3339(),
+        end: end.to_rfc3339(),
+        complete: now >= end,
+        elapsed_fraction,
+        samples: usage.samples,
+        cpu_avg_percent: average(usage.cpu_sum),
+        cpu_p95_percent: percentile(&usage.cpu_histogram, 0.95),
+        memory_avg_percent: average(usage.memory_sum),
+        memory_peak_percent: (usage.samples > 0).then_some(usage.memory_peak),
+        disk_growth_gb: disk_growth(conn, from, to)?,
+        network_rx_bytes: usage.rx_bytes,
+        network_tx_bytes: usage.tx_bytes,
+        jobs: jobs as u64,
+        jobs_failed: jobs_failed as u64,
+        job_seconds_total,
+        job_seconds_avg: (jobs > 0).then(|| job_seconds_total / jobs as f64),
+        start_display: None,
+        end_display: None,
+    })
+}
+
+pub fn deltas(current: &PeriodUsage, previous: &PeriodUsage) -> UsageDeltas {
+    // A total for part of a period is held against the same share of the last
+    let scale = if current.complete { 1.0 } else { current.elapsed_fraction };
+    let total = |current: f64, previous: f64| percent_change(Some(current), Some(previous * scale));
+    let has_samples = |usage: &PeriodUsage| usage.samples > 0;
+    UsageDeltas {
+        cpu_avg_percent: percent_change(current.cpu_avg_percent, previous.cpu_avg_percent),
+        cpu_p95_percent: percent_change(current.cpu_p95_percent, previous.cpu_p95_percent),
+        memory_avg_percent: percent_change(current.memory_avg_percent, previous.memory_avg_percent),
+        memory_peak_percent: percent_change(current.memory_peak_percent, previous.memory_peak_percent),
+        disk_growth_gb: current
+            .disk_growth_gb
+            .iter()
+            .map(|(mount, growth)| {
+                let before = previous.disk_growth_gb.get(mount).map(|g| g * scale);
+                (mount.clone(), percent_change(Some(*growth), before))
+            })
+            .collect(),
+        network_rx_bytes: (has_samples(current) && has_samples(previous))
+            .then(|| total(current.network_rx_bytes as f64, previous.network_rx_bytes as f64))
+            .flatten(),
+        network_tx_bytes: (has_samples(current) && has_samples(previous))
+            .then(|| total(current.network_tx_bytes as f64, previous.network_tx_bytes as f64))
+            .flatten(),
+        jobs: total(current.jobs as f64, previous.jobs as f64),
+        job_seconds_avg: percent_change(current.job_seconds_avg, previous.job_seconds_avg),
+    }
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn get_usage_summary(
+    db: State<'_, Database>,
+    settings: State<'_, SettingsStore>,
+    period: String,
+) -> CommandResult<UsageSummary> {
+    let period = Period::parse(&period)?;
+    let settings = settings.get();
+    let zone = timestamps::parse_zone(&settings.timezone).unwrap_or(Zone::Local);
+    let now = Utc::now();
+    let (previous_day, current_day, next_day) = period_dates(period, zone.naive(now).date());
+    let instant = |day: NaiveDate| zone.instant(day.and_time(NaiveTime::default()));
+    let (previous_start, current_start, next_start) = (instant(previous_day), instant(current_day), instant(next_day));
+    let (current, previous) = db.with_conn(|conn| {
+        Ok((
+            period_usage(conn, current_start, next_start, now)?,
+            period_usage(conn, previous_start, current_start, now)?,
+        ))
+    })?;
+    let deltas = deltas(&current, &previous);
+    Ok(UsageSummary {
+        period: period.name().to_string(),
+        timezone: settings.timezone.clone(),
+        pro_rata: !current.complete,
+        current: timestamps::localized(&settings, current),
+        previous: timestamps::localized(&settings, previous),
+        deltas,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NET_DEV: &str = "Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo: 9000000   90000    0    0    0     0          0         0  9000000   90000    0    0    0     0       0          0
+  eth0: 1000       10    0    0    0     0          0         0     200       2    0    0    0     0       0          0
+wlan0:   30        1    0    0    0     0          0         0       4       1    0    0    0     0       0          0
+";
+
+    fn at(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text).unwrap().with_timezone(&Utc)
+    }
+
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn net_dev_sums_every_interface_but_loopback() {
+        assert_eq!(parse_net_dev(NET_DEV), (1030, 204));
+        assert_eq!(parse_net_dev(""), (0, 0));
+    }
+
+    #[test]
+    fn rollups_merge_into_the_same_totals() {
+        let mut one = Rollup::default();
+        one.add(10.0, 40.0, 100, 10);
+        one.add(20.4, 60.0, 100, 10);
+        let mut two = Rollup::default();
+        two.add(99.6, 50.0, 100, 10);
+        two.add(150.0, 20.0, 0, 0);
+        let mut all = Rollup::default();
+        all.merge(&one);
+        all.merge(&two);
+        assert_eq!((all.samples, all.memory_peak, all.rx_bytes, all.tx_bytes), (4, 60.0, 300, 30));
+        assert_eq!(all.cpu_sum, 10.0 + 20.4 + 99.6 + 150.0);
+        // Whole percents, anything past 100 in the last bucket
+        assert_eq!((all.cpu_histogram[10], all.cpu_histogram[20], all.cpu_histogram[100]), (1, 1, 2));
+        assert_eq!(percentile(&all.cpu_histogram, 0.5), Some(20.0));
+        assert_eq!(percentile(&all.cpu_histogram, 0.95), Some(100.0));
+        assert_eq!(percentile(&Rollup::default().cpu_histogram, 0.95), None);
+    }
+
+    #[test]
+    fn periods_start_on_mondays_and_firsts() {
+        // Sunday 2026-10-18 is in the week of Monday 2026-10-12
+        assert_eq!(
+            period_dates(Period::Week, date("2026-10-18")),
+            (date("2026-10-05"), date("2026-10-12"), date("2026-10-19"))
+        );
+        assert_eq!(
+            period_dates(Period::Week, date("2026-10-12")),
+            (date("2026-10-05"), date("2026-10-12"), date("2026-10-19"))
+        );
+        assert_eq!(
+            period_dates(Period::Month, date("2027-01-31")),
+            (date("2026-12-01"), date("2027-01-01"), date("2027-02-01"))
+        );
+        assert_eq!(
+            period_dates(Period::Day, date("2028-03-01")),
+            (date("2028-02-29"), date("2028-03-01"), date("2028-03-02"))
+        );
+        assert_eq!(Period::parse(" Week ").unwrap(), Period::Week);
+        assert!(matches!(Period::parse("year"), Err(CommandError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn a_period_aggregates_samples_jobs_and_disk_growth() {
+        let db = Database::in_memory();
+        db.with_conn(|conn| {
+            for (time, cpu, memory) in [("10:05", 10.0, 40.0), ("10:45", 20.0, 60.0), ("11:10", 90.0, 50.0)] {
+                conn.execute(
+                    "INSERT INTO metrics_samples (at, cpu_percent, memory_percent, rx_bytes, tx_bytes)
+                     VALUES (?1, ?2, ?3, 100, 10)",
+                    params![at(&format!("2026-10-01T{}:00Z", time)).timestamp(), cpu, memory],
+                )?;
+            }
+            // The day after: not in the period
+            conn.execute(
+                "INSERT INTO metrics_samples (at, cpu_percent, memory_percent, rx_bytes, tx_bytes) VALUES (?1, 5, 5, 7, 7)",
+                params![at("2026-10-02T00:00:00Z").timestamp()],
+            )?;
+            for (id, status, finished, seconds) in [("a", "completed", "10:30", 30.0), ("b", "failed", "23:59", 90.0)] {
+                conn.execute(
+                    "INSERT INTO job_runs (job_id, started_at, task_type, status, finished_at, duration_secs)
+                     VALUES (?1, '2026-10-01T00:00:00Z', 'backup', ?2, ?3, ?4)",
+                    params![id, status, at(&format!("2026-10-01T{}:00Z", finished)).timestamp(), seconds],
+                )?;
+            }
+            for (mount, time, used) in [("/", "08:00", 100.0), ("/", "20:00", 103.5), ("/home", "08:00", 50.0)] {
+                conn.execute(
+                    "INSERT INTO disk_usage_samples (mount_point, at, used_gb, total_gb) VALUES (?1, ?2, ?3, 500)",
+                    params![mount, at(&format!("2026-10-01T{}:00Z", time)).timestamp(), used],
+                )?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+        let (start, end, now) = (at("2026-10-01T00:00:00Z"), at("2026-10-02T00:00:00Z"), at("2026-10-05T00:00:00Z"));
+        let check = |usage: &PeriodUsage| {
+            assert!(usage.complete);
+            assert_eq!(usage.elapsed_fraction, 1.0);
+            assert_eq!(usage.samples, 3);
+            assert_eq!(usage.cpu_avg_percent, Some(40.0));
+            assert_eq!(usage.cpu_p95_percent, Some(90.0));
+            assert_eq!(usage.memory_avg_percent, Some(50.0));
+            assert_eq!(usage.memory_peak_percent, Some(60.0));
+            assert_eq!((usage.network_rx_bytes, usage.network_tx_bytes), (300, 30));
+            assert_eq!((usage.jobs, usage.jobs_failed, usage.job_seconds_total), (2, 1, 120.0));
+            assert_eq!(usage.job_seconds_avg, Some(60.0));
+            // A mount needs two samples to have grown
+            assert_eq!(usage.disk_growth_gb, [("/".to_string(), 3.5)].into_iter().collect());
+        };
+        check(&db.with_conn(|conn| period_usage(conn, start, end, now)).unwrap());
+
+        // Rolled into hours, it reads the same
+        rollup(&db).unwrap();
+        let hours: i64 = db
+            .with_conn(|conn| conn.query_row("SELECT COUNT(*) FROM metrics_hourly", [], |r| r.get(0)))
+            .unwrap();
+        assert_eq!(hours, 3);
+        check(&db.with_conn(|conn| period_usage(conn, start, end, now)).unwrap());
+
+        let empty = db
+            .with_conn(|conn| period_usage(conn, at("2026-09-01T00:00:00Z"), at("2026-09-02T00:00:00Z"), now))
+            .unwrap();
+        assert_eq!((empty.samples, empty.cpu_avg_percent, empty.cpu_p95_percent), (0, None, None));
+        assert_eq!(empty.memory_peak_percent, None);
+    }
+
+    #[test]
+    fn totals_of_a_partial_period_are_compared_pro_rata() {
+        let db = Database::in_memory();
+        let (start, end) = (at("2026-10-12T00:00:00Z"), at("2026-10-19T00:00:00Z"));
+        let usage = |now: &str| db.with_conn(|conn| period_usage(conn, start, end, at(now))).unwrap();
+        let mut current = usage("2026-10-15T12:00:00Z");
+        assert!(!current.complete);
+        assert_eq!(current.elapsed_fraction, 0.5);
+        let mut previous = usage("2026-10-20T00:00:00Z");
+        assert!(previous.complete);
+
+        // Half the week, ten jobs against twenty last week: on pace
+        (current.jobs, previous.jobs) = (10, 20);
+        (current.cpu_avg_percent, previous.cpu_avg_percent) = (Some(30.0), Some(20.0));
+        current.disk_growth_gb = [("/".to_string(), 2.0), ("/new".to_string(), 1.0)].into_iter().collect();
+        previous.disk_growth_gb = [("/".to_string(), 2.0)].into_iter().collect();
+        let deltas = deltas(&current, &previous);
+        assert_eq!(deltas.jobs, Some(0.0));
+        // Averages as they are
+        assert_eq!(deltas.cpu_avg_percent, Some(50.0));
+        assert_eq!(deltas.disk_growth_gb["/"], Some(100.0));
+        assert_eq!(deltas.disk_growth_gb["/new"], None);
+        // No samples on either side: no transfer comparison
+        assert_eq!(deltas.network_rx_bytes, None);
+
+        assert_eq!(percent_change(Some(5.0), Some(0.0)), None);
+        assert_eq!(percent_change(None, Some(1.0)), None);
+        assert_eq!(percent_change(Some(1.5), Some(1.0)), Some(50.0));
+    }
+}
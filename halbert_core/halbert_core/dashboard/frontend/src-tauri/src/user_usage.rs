@@ -0,0 +1,137 @@
+// Resource usage grouped by effective user.
+//
+// Aggregated from the same single process snapshot the process tree uses,
+// so per-user totals always add up to what the process views show. Uids
+// without a passwd entry are listed by number.
+use serde::Serialize;
+use std::collections::HashMap;
+use sysinfo::{Uid, Users};
+
+use crate::app::State;
+use crate::error::CommandResult;
+use crate::process_tree;
+use crate::settings::SettingsStore;
+
+const TOP_PROCESSES: usize = 3;
+const SYSTEM_BUCKET: &str = "system";
+const UNKNOWN_BUCKET: &str = "unknown";
+
+#[derive(Serialize, Clone)]
+pub struct UserProcess {
+    pub pid: u32,
+    pub name: String,
+    pub cpu_percent: f32,
+    pub memory_mb: f64,
+}
+
+#[derive(Serialize)]
+pub struct UserUsage {
+    // User name, the numeric uid, or "system" / "unknown"
+    pub user: String,
+    // None for the merged buckets
+    pub uid: Option<u32>,
+    pub process_count: usize,
+    // Per-core percent summed over the user's processes
+    pub cpu_percent: f32,
+    pub memory_mb: f64,
+    // Largest by memory
+    pub top_processes: Vec<UserProcess>,
+}
+
+#[derive(Serialize)]
+pub struct UsageByUser {
+    pub users: Vec<UserUsage>,
+    pub process_count: usize,
+    pub system_uid_threshold: u32,
+    pub collapsed_system: bool,
+}
+
+#[cfg(unix)]
+fn numeric(uid: &Uid) -> Option<u32> {
+    Some(**uid)
+}
+
+#[cfg(not(unix))]
+fn numeric(_uid: &Uid) -> Option<u32> {
+    None
+}
+
+#[derive(Default)]
+struct Bucket {
+    uid: Option<u32>,
+    process_count: usize,
+    cpu_percent: f32,
+    memory_bytes: u64,
+    processes: Vec<UserProcess>,
+}
+
+fn mb(bytes: u64) -> f64 {
+    bytes as f64 / 1024.0 / 1024.0
+}
+
+// Sorted by memory, heaviest user first
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_usage_by_user(
+    settings: State<'_, SettingsStore>,
+    collapse_system: Option<bool>,
+) -> CommandResult<UsageByUser> {
+    let threshold = settings.get().system_uid_threshold;
+    let collapse = collapse_system.unwrap_or(false);
+    let snap = process_tree::snapshot();
+    let users = Users::new_with_refreshed_list();
+
+    let mut buckets: HashMap<String, Bucket> = HashMap::new();
+    for (&pid, entry) in &snap.entries {
+        let uid = entry.uid.as_ref();
+        let number = uid.and_then(numeric);
+        let (key, bucket_uid) = match (uid, number) {
+            (None, _) => (UNKNOWN_BUCKET.to_string(), None),
+            (Some(_), Some(n)) if collapse && n < threshold => (SYSTEM_BUCKET.to_string(), None),
+            (Some(uid), _) => {
+                let name = users
+                    .get_user_by_id(uid)
+                    .map(|user| user.name().to_string())
+                    .or_else(|| number.map(|n| n.to_string()))
+                    .unwrap_or_else(|| UNKNOWN_BUCKET.to_string());
+                (name, number)
+            }
+        };
+        let bucket = buckets.entry(key).or_insert_with(|| Bucket {
+            uid: bucket_uid,
+            ..Default::default()
+        });
+        bucket.process_count += 1;
+        bucket.cpu_percent += entry.cpu_percent;
+        bucket.memory_bytes += entry.memory_bytes;
+        bucket.processes.push(UserProcess {
+            pid,
+            name: entry.name.clone(),
+            cpu_percent: entry.cpu_percent,
+            memory_mb: mb(entry.memory_bytes),
+        });
+    }
+
+    let mut result: Vec<UserUsage> = buckets
+        .into_iter()
+        .map(|(user, mut bucket)| {
+            bucket.processes.sort_by(|a, b| b.memory_mb.total_cmp(&a.memory_mb));
+            bucket.processes.truncate(TOP_PROCESSES);
+            UserUsage {
+                user,
+                uid: bucket.uid,
+                process_count: bucket.process_count,
+                cpu_percent: bucket.cpu_percent,
+                memory_mb: mb(bucket.memory_bytes),
+                top_processes: bucket.processes,
+            }
+        })
+        .collect();
+    result.sort_by(|a, b| b.memory_mb.total_cmp(&a.memory_mb));
+
+    Ok(UsageByUser {
+        users: result,
+        process_count: snap.entries.len(),
+        system_uid_threshold: threshold,
+        collapsed_system: collapse,
+    })
+}
@@ -0,0 +1,219 @@
+// The quick-glance widget: a small frameless, always-on-top window with
+// CPU, memory and the pending approval count.
+//
+// It is a second window ("widget", route /widget) opened on demand, fed by
+// the same app-wide `metrics://update` and `approvals://*` events as the
+// dashboard, so nothing here pushes data to it. Closing it leaves the app
+// running. Its position is kept in settings (`widget.position`), apart
+// from the main window, and saved when it closes. With `click_through` it
+// ignores the mouse entirely; `draggable` tells the frontend whether to
+// mark the window as a drag region. The tray's "Show widget" item follows
+// whether it is open, and the tray shows when automation is paused.
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::menu::{CheckMenuItem, Menu, MenuItem};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Emitter, LogicalPosition, Manager, WebviewUrl, WebviewWindowBuilder, Window, WindowEvent, Wry};
+
+use crate::automation::Gate;
+use crate::error::{CommandError, CommandResult};
+use crate::settings::{SettingsStore, WidgetPosition, WidgetSettings};
+
+pub const LABEL: &str = "widget";
+const WIDTH: f64 = 240.0;
+const HEIGHT: f64 = 132.0;
+
+#[derive(Serialize, Clone)]
+pub struct WidgetState {
+    pub open: bool,
+    pub settings: WidgetSettings,
+}
+
+#[derive(Default)]
+pub struct Widget {
+    // The tray's "Show widget" item, once the tray exists
+    tray_item: Mutex<Option<CheckMenuItem<Wry>>>,
+    // Where the widget last moved to, saved when it closes
+    moved_to: Mutex<Option<WidgetPosition>>,
+}
+
+impl Widget {
+    fn sync_tray(&self, open: bool) {
+        if let Some(item) = self.tray_item.lock().unwrap().as_ref() {
+            let _ = item.set_checked(open);
+        }
+    }
+}
+
+fn tauri_error(e: tauri::Error) -> CommandError {
+    CommandError::Internal(format!("widget window: {}", e))
+}
+
+fn state(app: &AppHandle, open: bool) -> WidgetState {
+    WidgetState {
+        open,
+        settings: app.state::<SettingsStore>().get().widget,
+    }
+}
+
+// Opens the widget, or brings it forward if it is already open
+pub fn show(app: &AppHandle) -> CommandResult<()> {
+    if let Some(window) = app.get_webview_window(LABEL) {
+        window.show().map_err(tauri_error)?;
+        return window.set_focus().map_err(tauri_error);
+    }
+    let settings = app.state::<SettingsStore>().get().widget;
+    let mut builder = WebviewWindowBuilder::new(app, LABEL, WebviewUrl::App("widget".into()))
+        .title("Halbert")
+        .inner_size(WIDTH, HEIGHT)
+        .decorations(false)
+        .always_on_top(true)
+        .resizable(false)
+        .skip_taskbar(true);
+    if let Some(position) = settings.position {
+        builder = builder.position(position.x, position.y);
+    }
+    let window = match builder.build() {
+        Ok(window) => window,
+        // Opened by a concurrent call in the meantime
+        Err(_) if app.get_webview_window(LABEL).is_some() => {
+            return app.get_webview_window(LABEL).map_or(Ok(()), |w| w.set_focus().map_err(tauri_error));
+        }
+        Err(e) => return Err(tauri_error(e)),
+    };
+    if settings.click_through {
+        window.set_ignore_cursor_events(true).map_err(tauri_error)?;
+    }
+    app.state::<Widget>().sync_tray(true);
+    Ok(())
+}
+
+pub fn toggle(app: &AppHandle) -> CommandResult<bool> {
+    match app.get_webview_window(LABEL) {
+        Some(window) => {
+            window.close().map_err(tauri_error)?;
+            Ok(false)
+        }
+        None => show(app).map(|()| true),
+    }
+}
+
+// From the app-wide window event handler
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    if window.label() != LABEL {
+        return;
+    }
+    let app = window.app_handle();
+    let widget = app.state::<Widget>();
+    match event {
+        WindowEvent::Moved(position) => {
+            let scale = window.scale_factor().unwrap_or(1.0);
+            let logical: LogicalPosition<f64> = position.to_logical(scale);
+            *widget.moved_to.lock().unwrap() = Some(WidgetPosition {
+                x: logical.x,
+                y: logical.y,
+            });
+        }
+        WindowEvent::Destroyed => {
+            widget.sync_tray(false);
+            let Some(position) = widget.moved_to.lock().unwrap().take() else {
+                return;
+            };
+            let saved = app.state::<SettingsStore>().update(|current| {
+                let mut next = current.clone();
+                next.widget.position = Some(position);
+                Ok(next)
+            });
+            if let Err(e) = saved {
+                println!("[Halbert] Failed to save widget position: {}", e);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Applies a changed click_through to an open widget
+pub fn apply_settings(app: &AppHandle, settings: &WidgetSettings) {
+    if let Some(window) = app.get_webview_window(LABEL) {
+        if let Err(e) = window.set_ignore_cursor_events(settings.click_through) {
+            println!("[Halbert] Can't change widget click-through: {}", e);
+        }
+    }
+}
+
+// The main window may have been closed while the widget kept the app
+// running; it is then built again from the app config
+fn show_dashboard(app: &AppHandle) -> tauri::Result<()> {
+    let window = match app.get_webview_window("main") {
+        Some(window) => window,
+        None => match app.config().app.windows.first() {
+            Some(config) => WebviewWindowBuilder::from_config(app, config)?.build()?,
+            None => return Ok(()),
+        },
+    };
+    window.show()?;
+    window.set_focus()
+}
+
+pub fn build_tray(app: &AppHandle) -> tauri::Result<()> {
+    let widget_item = CheckMenuItem::with_id(app, "widget", "Show widget", true, false, None::<&str>)?;
+    let dashboard = MenuItem::with_id(app, "dashboard", "Open dashboard", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app, "quit", "Quit Halbert", true, None::<&str>)?;
+    let menu = Menu::with_items(app, &[&widget_item, &dashboard, &quit])?;
+    let mut tray = TrayIconBuilder::with_id("halbert").tooltip("Halbert").menu(&menu).on_menu_event(
+        |app, event| match event.id().as_ref() {
+            "widget" => {
+                // The item flips itself when clicked; put it back to the truth
+                let open = toggle(app).unwrap_or_else(|e| {
+                    println!("[Halbert] Widget toggle failed: {}", e);
+                    app.get_webview_window(LABEL).is_some()
+                });
+                app.state::<Widget>().sync_tray(open);
+                let _ = app.emit("widget://changed", state(app, open));
+            }
+            "dashboard" => {
+                if let Err(e) = show_dashboard(app) {
+                    println!("[Halbert] Can't open the dashboard: {}", e);
+                }
+            }
+            "quit" => app.exit(0),
+            _ => {}
+        },
+    );
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    tray.build(app)?;
+    *app.state::<Widget>().tray_item.lock().unwrap() = Some(widget_item);
+    show_paused(app, app.state::<Gate>().is_paused());
+    Ok(())
+}
+
+// The tray's badge for paused automation: a title beside the icon where
+// the platform shows one, and the tooltip everywhere
+pub fn show_paused(app: &AppHandle, paused: bool) {
+    let Some(tray) = app.tray_by_id("halbert") else {
+        return;
+    };
+    let (tooltip, title) = if paused {
+        ("Halbert - automation paused", Some("Paused"))
+    } else {
+        ("Halbert", None)
+    };
+    if let Err(e) = tray.set_tooltip(Some(tooltip)).and_then(|()| tray.set_title(title)) {
+        println!("[Halbert] Can't update the tray icon: {}", e);
+    }
+}
+
+#[tauri::command]
+pub fn toggle_widget_window(app: AppHandle) -> CommandResult<WidgetState> {
+    let open = toggle(&app)?;
+    let state = state(&app, open);
+    let _ = app.emit("widget://changed", &state);
+    Ok(state)
+}
+
+#[tauri::command]
+pub fn get_widget_state(app: AppHandle) -> WidgetState {
+    state(&app, app.get_webview_window(LABEL).is_some())
+}
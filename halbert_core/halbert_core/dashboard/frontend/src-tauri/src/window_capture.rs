@@ -0,0 +1,186 @@
+// Screenshots of Halbert's own windows, for bug reports.
+//
+// Only ever taken by capture_window_screenshot, which
+// generate_diagnostic_bundle calls when asked to; nothing captures on its
+// own. The webview has no capture API of its own, so the window is shown,
+// raised, and the screen region under its content is grabbed with the
+// platform's tool: ImageMagick's `import` on X11, `screencapture` on macOS
+// and PowerShell's CopyFromScreen on Windows. Wayland doesn't tell a
+// window where it is, so there it's unsupported. The region comes from the
+// window's inner position and size in physical pixels, which is what X11
+// and a DPI-aware Windows process work in, so a window on a scaled monitor
+// is captured at full resolution and not cropped; macOS takes points and
+// scales them back up itself. The PNG goes to `captures/` in the app data
+// dir and its size is read back from the file.
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+use crate::error::{CommandError, CommandResult};
+use crate::exec;
+
+const MAIN_WINDOW: &str = "main";
+// For a raised window to be drawn on top before the grab
+const SETTLE: Duration = Duration::from_millis(300);
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// A physical-pixel region in logical units (points), rounded outwards so
+// no edge is lost
+pub fn to_logical(region: Region, scale_factor: f64) -> Region {
+    let scale = if scale_factor > 0.0 { scale_factor } else { 1.0 };
+    let left = (region.x as f64 / scale).floor();
+    let top = (region.y as f64 / scale).floor();
+    let right = ((region.x as f64 + region.width as f64) / scale).ceil();
+    let bottom = ((region.y as f64 + region.height as f64) / scale).ceil();
+    Region {
+        x: left as i32,
+        y: top as i32,
+        width: (right - left) as u32,
+        height: (bottom - top) as u32,
+    }
+}
+
+#[derive(Serialize)]
+pub struct WindowCapture {
+    pub window_label: String,
+    pub path: String,
+    // Of the PNG, in pixels
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f64,
+    pub captured_at: String,
+}
+
+#[cfg(target_os = "linux")]
+fn capture_command(region: Region, _points: Region, path: &Path) -> CommandResult<(String, Vec<String>)> {
+    let x11_backend = std::env::var("GDK_BACKEND").is_ok_and(|b| b == "x11");
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() && !x11_backend {
+        return Err(CommandError::NotSupported(
+            "window capture isn't available under Wayland".to_string(),
+        ));
+    }
+    if exec::find_in_path("import").is_none() {
+        return Err(CommandError::ToolMissing("import (ImageMagick) is needed to capture a window".to_string()));
+    }
+    let crop = format!("{}x{}+{}+{}", region.width, region.height, region.x, region.y);
+    let args = ["-silent", "-window", "root", "-crop", &crop, "+repage"];
+    let mut args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+    args.push(format!("png:{}", path.display()));
+    Ok(("import".to_string(), args))
+}
+
+#[cfg(target_os = "macos")]
+fn capture_command(_region: Region, points: Region, path: &Path) -> CommandResult<(String, Vec<String>)> {
+    if exec::find_in_path("screencapture").is_none() {
+        return Err(CommandError::ToolMissing("screencapture is needed to capture a window".to_string()));
+    }
+    let rect = format!("-R{},{},{},{}", points.x, points.y, points.width, points.height);
+    let args = vec!["-x".to_string(), "-t".to_string(), "png".to_string(), rect, path.display().to_string()];
+    Ok(("screencapture".to_string(), args))
+}
+
+#[cfg(target_os = "windows")]
+fn capture_command(region: Region, _points: Region, path: &Path) -> CommandResult<(String, Vec<String>)> {
+    // Per-monitor DPI awareness (-4) first, or the coordinates would be
+    // scaled for a DPI-unaware process
+    let script = format!(
+        "Add-Type -TypeDefinition 'using System; using System.Runtime.InteropServices; \
+         public class Dpi {{ [DllImport(\"user32.dll\")] public static extern bool \
+         SetProcessDpiAwarenessContext(IntPtr value); }}'; \
+         [Dpi]::SetProcessDpiAwarenessContext([IntPtr]::new(-4)) | Out-Null; \
+         Add-Type -AssemblyName System.Drawing; \
+         $bitmap = New-Object System.Drawing.Bitmap {w}, {h}; \
+         $graphics = [System.Drawing.Graphics]::FromImage($bitmap); \
+         $graphics.CopyFromScreen({x}, {y}, 0, 0, $bitmap.Size); \
+         $bitmap.Save('{path}', [System.Drawing.Imaging.ImageFormat]::Png)",
+        w = region.width,
+        h = region.height,
+        x = region.x,
+        y = region.y,
+        path = path.display().to_string().replace('\'', "''"),
+    );
+    let args = ["-NoProfile", "-NonInteractive", "-Command", &script];
+    Ok(("powershell".to_string(), args.iter().map(|a| a.to_string()).collect()))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn capture_command(_region: Region, _points: Region, _path: &Path) -> CommandResult<(String, Vec<String>)> {
+    Err(CommandError::NotSupported("window capture isn't available on this platform".to_string()))
+}
+
+fn tauri_error(e: tauri::Error) -> CommandError {
+    CommandError::Internal(format!("window: {}", e))
+}
+
+fn run(program: String, args: Vec<String>) -> CommandResult<()> {
+    let name = program.clone();
+    let output = exec::bounded(Instant::now() + CAPTURE_TIMEOUT, move || {
+        Command::new(&program).args(&args).output()
+    })
+    .ok_or_else(|| CommandError::Internal(format!("{} took longer than {:?}", name, CAPTURE_TIMEOUT)))??;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(CommandError::Internal(format!("{} failed: {}", name, stderr.trim())));
+    }
+    Ok(())
+}
+
+// async so the settle wait and the capture don't hold up the main thread
+#[tauri::command]
+pub async fn capture_window_screenshot(app: AppHandle, window_label: Option<String>) -> CommandResult<WindowCapture> {
+    let label = window_label.unwrap_or_else(|| MAIN_WINDOW.to_string());
+    let window = app
+        .get_webview_window(&label)
+        .ok_or_else(|| CommandError::NotFound(format!("window '{}'", label)))?;
+    window.unminimize().map_err(tauri_error)?;
+    window.show().map_err(tauri_error)?;
+    window.set_focus().map_err(tauri_error)?;
+    std::thread::sleep(SETTLE);
+
+    let position = window.inner_position().map_err(tauri_error)?;
+    let size = window.inner_size().map_err(tauri_error)?;
+    let scale_factor = window.scale_factor().map_err(tauri_error)?;
+    let region = Region {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    };
+    if region.width == 0 || region.height == 0 {
+        return Err(CommandError::Conflict(format!("window '{}' has no visible content", label)));
+    }
+
+    let dir: PathBuf = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| CommandError::Internal(e.to_string()))?
+        .join("captures");
+    std::fs::create_dir_all(&dir)?;
+    let now = chrono::Utc::now();
+    let file_label: String = label.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    let path = dir.join(format!("window-{}-{}.png", file_label, now.format("%Y%m%d-%H%M%S")));
+
+    let (program, args) = capture_command(region, to_logical(region, scale_factor), &path)?;
+    run(program, args)?;
+    let (width, height) = image::image_dimensions(&path)
+        .map_err(|e| CommandError::Internal(format!("{} isn't a readable PNG: {}", path.display(), e)))?;
+    println!("[Halbert] Captured window {} to {} ({}x{})", label, path.display(), width, height);
+    Ok(WindowCapture {
+        window_label: label,
+        path: path.display().to_string(),
+        width,
+        height,
+        scale_factor,
+        captured_at: now.to_rfc3339(),
+    })
+}
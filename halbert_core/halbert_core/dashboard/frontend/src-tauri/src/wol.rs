@@ -0,0 +1,145 @@
+// Wake-on-LAN.
+//
+// The magic packet is 6 bytes of 0xFF followed by the target MAC 16 times,
+// sent as a UDP broadcast. Delivery can't be confirmed at this level, so
+// when the MAC belongs to a registered host a background probe polls that
+// host's API and announces the result as `hosts://status`.
+use serde::Serialize;
+use std::net::{Ipv4Addr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::app::{AppHandle, Emitter, State};
+use crate::error::{CommandError, CommandResult};
+use crate::hosts::{self, HostEntry};
+use crate::settings::SettingsStore;
+
+const PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Serialize)]
+pub struct WakeResult {
+    pub sent: bool,
+    pub mac: String,
+    // "255.255.255.255:9"
+    pub target: String,
+    pub error: Option<String>,
+    // Set when a registered host has this MAC and is now being watched
+    pub watching_host: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct HostStatusEvent {
+    pub host_id: String,
+    // "online" once the API answers, "offline" if it never did
+    pub status: String,
+    pub elapsed_secs: u64,
+}
+
+// "aa:bb:cc:dd:ee:ff" or "AA-BB-CC-DD-EE-FF"; one separator throughout
+pub fn parse_mac(mac: &str) -> CommandResult<[u8; 6]> {
+    let mac = mac.trim();
+    let invalid = || CommandError::InvalidInput(format!("'{}' is not a MAC address (expected aa:bb:cc:dd:ee:ff)", mac));
+    let separator = if mac.contains(':') { ':' } else { '-' };
+    let parts: Vec<&str> = mac.split(separator).collect();
+    if parts.len() != 6 {
+        return Err(invalid());
+    }
+    let mut bytes = [0u8; 6];
+    for (byte, part) in bytes.iter_mut().zip(&parts) {
+        if part.len() != 2 || !part.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        *byte = u8::from_str_radix(part, 16).map_err(|_| invalid())?;
+    }
+    Ok(bytes)
+}
+
+// Canonical lowercase colon form, for storing and comparing
+pub fn format_mac(bytes: &[u8; 6]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":")
+}
+
+pub fn magic_packet(mac: &[u8; 6]) -> Vec<u8> {
+    let mut packet = vec![0xFF; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(mac);
+    }
+    packet
+}
+
+pub fn parse_broadcast(broadcast: Option<&str>) -> CommandResult<Ipv4Addr> {
+    match broadcast.map(str::trim).filter(|b| !b.is_empty()) {
+        None => Ok(Ipv4Addr::BROADCAST),
+        Some(addr) => addr
+            .parse()
+            .map_err(|_| CommandError::InvalidInput(format!("'{}' is not an IPv4 broadcast address", addr))),
+    }
+}
+
+fn send(packet: &[u8], target: (Ipv4Addr, u16)) -> std::io::Result<usize> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_broadcast(true)?;
+    socket.send_to(packet, target)
+}
+
+// Poll the host's API until it answers or `wait` runs out
+fn watch(app: AppHandle, host: HostEntry, wait: Duration) {
+    std::thread::spawn(move || {
+        let started = Instant::now();
+        let mut status = "offline";
+        while started.elapsed() < wait {
+            std::thread::sleep(PROBE_INTERVAL);
+            if hosts::probe(&host) {
+                status = "online";
+                break;
+            }
+        }
+        println!("[Halbert] Host {} is {} after wake-on-LAN", host.id, status);
+        let _ = app.emit(
+            "hosts://status",
+            HostStatusEvent {
+                host_id: host.id,
+                status: status.to_string(),
+                elapsed_secs: started.elapsed().as_secs(),
+            },
+        );
+    });
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn send_wake_on_lan(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    mac: String,
+    broadcast: Option<String>,
+) -> CommandResult<WakeResult> {
+    let settings = settings.get();
+    let bytes = parse_mac(&mac)?;
+    let mac = format_mac(&bytes);
+    let host = settings.hosts.iter().find(|h| h.mac.as_deref() == Some(mac.as_str())).cloned();
+    // A registered host's own broadcast address applies unless one is given
+    let broadcast = broadcast.or_else(|| host.as_ref().and_then(|h| h.wol_broadcast.clone()));
+    let target = (parse_broadcast(broadcast.as_deref())?, settings.wol_port);
+
+    let packet = magic_packet(&bytes);
+    let (sent, error) = match send(&packet, target) {
+        Ok(n) if n == packet.len() => (true, None),
+        Ok(n) => (false, Some(format!("only {} of {} bytes were sent", n, packet.len()))),
+        Err(e) => (false, Some(e.to_string())),
+    };
+
+    let watching_host = match host {
+        Some(host) if sent => {
+            let id = host.id.clone();
+            watch(app, host, Duration::from_secs(settings.wol_wait_secs));
+            Some(id)
+        }
+        _ => None,
+    };
+    Ok(WakeResult {
+        sent,
+        mac,
+        target: format!("{}:{}", target.0, target.1),
+        error,
+        watching_host,
+    })
+}
@@ -21,6 +21,7 @@ import { Button } from '@/components/ui/button'
 import { SidePanel } from './SidePanel'
 import { ConfigEditor } from './ConfigEditor'
 import { useDebug } from '@/contexts/DebugContext'
+import { useCore } from '@/hooks/useCore'
 
 const navigation = [
   // Overview
@@ -49,6 +50,7 @@ const navigation = [
 export function Layout({ children }: { children: React.ReactNode }) {
   const location = useLocation()
   const { isDebugMode, setDebugMode, chatMetrics } = useDebug()
+  const { status: core } = useCore()
   
   // Global config editor state (triggered from chat "Edit Config" button)
   const [editingConfigPath, setEditingConfigPath] = useState<string | null>(null)
@@ -156,6 +158,21 @@ export function Layout({ children }: { children: React.ReactNode }) {
               </Button>
             </div>
             <div className="text-xs text-muted-foreground">
+              {/* Which machine the pages show; another's once core_host is set */}
+              {core && (
+                <p
+                  className="flex items-center gap-1.5 truncate"
+                  title={core.url ? `Commands go to ${core.url}` : 'The core runs in this window'}
+                >
+                  <span
+                    className={cn(
+                      'h-2 w-2 shrink-0 rounded-full',
+                      core.reachable === false ? 'bg-red-500' : core.reachable ? 'bg-emerald-500' : 'bg-muted-foreground'
+                    )}
+                  />
+                  {core.host_id ? `Core on ${core.name}` : `Core on this machine (${core.name})`}
+                </p>
+              )}
               <p>v0.1.0-alpha.1</p>
             </div>
           </div>
@@ -0,0 +1,62 @@
+import { useCallback, useEffect, useState } from 'react'
+import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
+
+// Which Halbert core the window's commands go to (see remote_core.rs)
+export type CoreStatus = {
+  host_id: string | null
+  name: string
+  url: string | null
+  reachable: boolean | null
+}
+
+export type CoreHost = {
+  id: string
+  name: string
+  transport: 'http' | 'ssh'
+  is_local: boolean
+}
+
+const POLL_MS = 30000
+
+// Refreshed when core_host changes (core://changed) and every POLL_MS, so
+// an agent that stops answering shows as unreachable
+export function useCore() {
+  const [status, setStatus] = useState<CoreStatus | null>(null)
+
+  const refresh = useCallback(async () => {
+    try {
+      setStatus(await invoke<CoreStatus>('get_core_status'))
+    } catch (error) {
+      console.error('Failed to get the core status:', error)
+    }
+  }, [])
+
+  useEffect(() => {
+    if (!('__TAURI_INTERNALS__' in window)) return
+    refresh()
+    const timer = setInterval(refresh, POLL_MS)
+    const unlisten = listen<CoreStatus>('core://changed', (event) => {
+      setStatus(event.payload)
+      refresh()
+    })
+    return () => {
+      clearInterval(timer)
+      unlisten.then((stop) => stop())
+    }
+  }, [refresh])
+
+  // null runs the core in this window again
+  const setCoreHost = useCallback(async (hostId: string | null) => {
+    await invoke('update_settings', { patch: { core_host: hostId } })
+    await refresh()
+  }, [refresh])
+
+  // Hosts an agent can run the core on; SSH hosts run none
+  const listCoreHosts = useCallback(async () => {
+    const hosts = await invoke<CoreHost[]>('list_hosts')
+    return hosts.filter((host) => !host.is_local && host.transport === 'http')
+  }, [])
+
+  return { status, refresh, setCoreHost, listCoreHosts }
+}
@@ -1,4 +1,5 @@
 import { useEffect, useRef, useState } from 'react'
+import { listen } from '@tauri-apps/api/event'
 
 type WebSocketMessage = {
   type: 'system_status' | 'approval_request' | 'job_update' | 'decision'
@@ -7,21 +8,39 @@ type WebSocketMessage = {
 
 type WebSocketStatus = 'connecting' | 'connected' | 'disconnected' | 'error'
 
+// The backend's /ws next to its HTTP API; the page's own host outside the app
+function socketUrl(backendUrl?: string) {
+  if (!backendUrl) return `ws://${window.location.host}/ws`
+  return `${backendUrl.replace(/\/+$/, '').replace(/^http/, 'ws')}/ws`
+}
+
+// Reconnects when backend_url changes in settings (settings://changed)
 export function useWebSocket(onMessage?: (message: WebSocketMessage) => void) {
   const [status, setStatus] = useState<WebSocketStatus>('disconnected')
+  const [url, setUrl] = useState(() => socketUrl())
   const ws = useRef<WebSocket | null>(null)
 
   useEffect(() => {
-    const wsUrl = `ws://${window.location.host}/ws`
-    
-    ws.current = new WebSocket(wsUrl)
+    if (!('__TAURI_INTERNALS__' in window)) return
+    const unlisten = listen<{ backend_url: string }>('settings://changed', (event) => {
+      setUrl(socketUrl(event.payload.backend_url))
+    })
+    return () => {
+      unlisten.then((stop) => stop())
+    }
+  }, [])
+
+  useEffect(() => {
+    setStatus('connecting')
+    const socket = new WebSocket(url)
+    ws.current = socket
 
-    ws.current.onopen = () => {
+    socket.onopen = () => {
       setStatus('connected')
-      console.log('WebSocket connected')
+      console.log('WebSocket connected to', url)
     }
 
-    ws.current.onmessage = (event) => {
+    socket.onmessage = (event) => {
       try {
         const message: WebSocketMessage = JSON.parse(event.data)
         onMessage?.(message)
@@ -30,19 +49,20 @@ export function useWebSocket(onMessage?: (message: WebSocketMessage) => void) {
       }
     }
 
-    ws.current.onerror = () => {
+    socket.onerror = () => {
       setStatus('error')
     }
 
-    ws.current.onclose = () => {
-      setStatus('disconnected')
+    socket.onclose = () => {
+      // A socket replaced by a newer one doesn't speak for the connection
+      if (ws.current === socket) setStatus('disconnected')
       console.log('WebSocket disconnected')
     }
 
     return () => {
-      ws.current?.close()
+      socket.close()
     }
-  }, [onMessage])
+  }, [onMessage, url])
 
   return { status, ws: ws.current }
 }
@@ -10,6 +10,7 @@ import { ConfirmDialog, Toast } from '@/components/ui/confirm-dialog'
 import { api } from '@/lib/api'
 import type { SystemInfo } from '@/lib/tauri'
 import { getSystemInfo } from '@/lib/tauri'
+import { useCore, type CoreHost } from '@/hooks/useCore'
 import { 
   Settings as SettingsIcon, 
   Bell, 
@@ -103,6 +104,12 @@ export function Settings() {
   const [alertRules, setAlertRules] = useState<AlertRule[]>([])
   const [discoveryStats, setDiscoveryStats] = useState<DiscoveryStats | null>(null)
   const [clearing, setClearing] = useState(false)
+
+  // Which core the window shows (see useCore)
+  const { status: core, setCoreHost, listCoreHosts } = useCore()
+  const [coreHosts, setCoreHosts] = useState<CoreHost[]>([])
+  const [switchingCore, setSwitchingCore] = useState(false)
+  const [coreError, setCoreError] = useState<string | null>(null)
   
   // Model config state
   const [modelConfig, setModelConfig] = useState<ModelConfig | null>(null)
@@ -207,6 +214,9 @@ export function Settings() {
     loadSettings()
     loadSystemProfile()
     loadAiRules()
+    if ('__TAURI_INTERNALS__' in window) {
+      listCoreHosts().then(setCoreHosts).catch((err) => console.error('list_hosts failed', err))
+    }
   }, [])
 
   const loadSettings = async () => {
@@ -797,6 +807,56 @@ export function Settings() {
             </CardContent>
           </Card>
 
+          {/* Core Card */}
+          <Card>
+            <CardHeader>
+              <CardTitle className="flex items-center gap-2">
+                <Server className="h-5 w-5" />
+                Core
+              </CardTitle>
+              <CardDescription>
+                The machine whose Halbert runs what this window shows. Another machine's needs halbert-agent
+                and its changes token on the host entry.
+              </CardDescription>
+            </CardHeader>
+            <CardContent>
+              <div className="space-y-3">
+                <select
+                  className="h-8 w-full text-xs rounded-md border border-input bg-background px-2"
+                  value={core?.host_id ?? ''}
+                  disabled={switchingCore || !core}
+                  onChange={async (e) => {
+                    setSwitchingCore(true)
+                    setCoreError(null)
+                    try {
+                      await setCoreHost(e.target.value || null)
+                    } catch (error: any) {
+                      setCoreError(error?.message ?? String(error))
+                    } finally {
+                      setSwitchingCore(false)
+                    }
+                  }}
+                >
+                  <option value="">This machine</option>
+                  {core?.host_id && !coreHosts.some(host => host.id === core.host_id) && (
+                    <option value={core.host_id}>{core.name}</option>
+                  )}
+                  {coreHosts.map(host => (
+                    <option key={host.id} value={host.id}>{host.name}</option>
+                  ))}
+                </select>
+                {core?.url && (
+                  <p className="text-xs text-muted-foreground">
+                    Commands go to <code>{core.url}</code>
+                    {core.reachable === false && <span className="text-red-500"> (not answering)</span>}.
+                    Settings, hosts and this window's own tools stay on this machine.
+                  </p>
+                )}
+                {coreError && <p className="text-xs text-red-500">{coreError}</p>}
+              </div>
+            </CardContent>
+          </Card>
+
           {/* Computer Identity Card */}
           <Card>
             <CardHeader>